@@ -0,0 +1,249 @@
+//! Detection of unknown keys, against a list of the paths a document may have.
+
+use saphyr_parser::Marker;
+
+use crate::{yaml::key_path, MarkedYaml, YamlData};
+
+/// A segment of a pattern of a [`KeyAllowlist`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Segment {
+    /// A key written as in the pattern.
+    Key(String),
+    /// Any key (`*`).
+    AnyKey,
+    /// Any key which is not a scalar (`[?]`).
+    NonScalarKey,
+    /// An index of a sequence (e.g.: `[0]`).
+    Index(usize),
+    /// Any index of a sequence (`[*]`).
+    AnyIndex,
+    /// Any number of segments, including none (`**`).
+    AnyDepth,
+}
+
+impl Segment {
+    /// Return whether the segment matches `step`, which is not matched against
+    /// [`Segment::AnyDepth`].
+    fn matches(&self, step: &Step) -> bool {
+        match (self, step) {
+            (Segment::Key(key), Step::Key(Some(text))) => key == text,
+            (Segment::Index(index), Step::Index(step)) => index == step,
+            (Segment::AnyKey, Step::Key(_))
+            | (Segment::NonScalarKey, Step::Key(None))
+            | (Segment::AnyIndex, Step::Index(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// A step of the path from the root of a document to a node.
+enum Step {
+    /// The value of a key, written `Some(text)` if it is a scalar.
+    Key(Option<String>),
+    /// An item of a sequence.
+    Index(usize),
+}
+
+/// A key of a document which no pattern of a [`KeyAllowlist`] allows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownKey {
+    /// The path of the value of the key, as [`Yaml::paths`] normalizes it (e.g.: `a.b[0].c`).
+    ///
+    /// [`Yaml::paths`]: crate::Yaml::paths
+    pub path: String,
+    /// The position of the key.
+    pub marker: Marker,
+}
+
+impl std::fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unknown key `{}` at line {} column {}",
+            self.path,
+            self.marker.line(),
+            self.marker.col().saturating_add(1)
+        )
+    }
+}
+
+/// A list of the paths the keys of a document may have, to detect misspelled keys without
+/// validating documents against a full schema.
+///
+/// Patterns are written as the paths [`Yaml::paths`] returns (e.g.: `spec.ports[0].name`), and
+/// may contain wildcards: `*` matches any key, `[*]` any index of a sequence, and `**` any number
+/// of keys and indices, including none. A pattern also allows the keys leading to it: `a.b`
+/// allows `a`, but no other key under it. Keys under a sequence are allowed only if a pattern
+/// goes through the sequence with an index or `[*]`.
+///
+/// ```
+/// use saphyr::{KeyAllowlist, MarkedYaml};
+///
+/// let docs = MarkedYaml::load_from_str("\
+/// name: web
+/// ports:
+/// - port: 80
+///   protocl: TCP
+/// labels: { app: web, tier: front }
+/// ").unwrap();
+/// let mut allowlist = KeyAllowlist::new();
+/// allowlist
+///     .allow("name")
+///     .allow("ports[*].port")
+///     .allow("ports[*].protocol")
+///     .allow("labels.**");
+/// let unknown = allowlist.unknown_keys(&docs[0]);
+/// assert_eq!(unknown.len(), 1);
+/// assert_eq!(unknown[0].to_string(), "unknown key `ports[0].protocl` at line 4 column 3");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct KeyAllowlist {
+    /// The segments of each pattern.
+    patterns: Vec<Vec<Segment>>,
+}
+
+impl KeyAllowlist {
+    /// Create an allowlist allowing no key.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow the keys matching `pattern`, and the keys leading to them.
+    ///
+    /// Text in brackets which is neither an index, `*`, `?` nor a quoted key is taken as a key.
+    pub fn allow(&mut self, pattern: &str) -> &mut Self {
+        self.patterns.push(parse_pattern(pattern));
+        self
+    }
+
+    /// Return the keys of `doc` no pattern allows, in the order they appear.
+    ///
+    /// The keys under an unknown key are not returned.
+    #[must_use]
+    pub fn unknown_keys(&self, doc: &MarkedYaml) -> Vec<UnknownKey> {
+        let mut unknown = Vec::new();
+        self.check(doc, &mut Vec::new(), "", &mut unknown);
+        unknown
+    }
+
+    /// Add the unknown keys within `node`, whose path is `path`, to `unknown`. `steps` are the
+    /// steps of `path`.
+    fn check(
+        &self,
+        node: &MarkedYaml,
+        steps: &mut Vec<Step>,
+        path: &str,
+        unknown: &mut Vec<UnknownKey>,
+    ) {
+        match &node.data {
+            YamlData::Tagged(_, node) => self.check(node, steps, path, unknown),
+            YamlData::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    steps.push(Step::Index(index));
+                    self.check(item, steps, &format!("{path}[{index}]"), unknown);
+                    steps.pop();
+                }
+            }
+            YamlData::Hash(hash) => {
+                for (key, value) in hash {
+                    let text = key_text(key);
+                    let path = key_path(path, text.as_deref());
+                    steps.push(Step::Key(text));
+                    if self.patterns.iter().any(|pattern| allows(pattern, steps)) {
+                        self.check(value, steps, &path, unknown);
+                    } else {
+                        unknown.push(UnknownKey {
+                            path,
+                            marker: key.marker,
+                        });
+                    }
+                    steps.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Return whether `pattern` matches `steps`, or the start of a path `steps` lead to.
+fn allows(pattern: &[Segment], steps: &[Step]) -> bool {
+    let Some((step, rest)) = steps.split_first() else {
+        return true;
+    };
+    match pattern.split_first() {
+        None => false,
+        Some((Segment::AnyDepth, after)) => allows(after, steps) || allows(pattern, rest),
+        Some((segment, after)) => segment.matches(step) && allows(after, rest),
+    }
+}
+
+/// Return the text of `key` in paths, if it is a scalar. See [`crate::Yaml::paths`].
+fn key_text(key: &MarkedYaml) -> Option<String> {
+    match &key.data {
+        YamlData::String(text) | YamlData::Real(text) => Some(text.clone()),
+        YamlData::Integer(i) => Some(i.to_string()),
+        YamlData::Boolean(b) => Some(b.to_string()),
+        YamlData::Null => Some("~".to_owned()),
+        YamlData::Tagged(_, key) => key_text(key),
+        _ => None,
+    }
+}
+
+/// Split `pattern` into its segments.
+pub(crate) fn parse_pattern(pattern: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut rest = pattern;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            let (segment, after) = parse_bracket(after);
+            segments.push(segment);
+            rest = after;
+            continue;
+        }
+        let after = rest.strip_prefix('.').unwrap_or(rest);
+        let end = after.find(['.', '[']).unwrap_or(after.len());
+        segments.push(match &after[..end] {
+            "*" => Segment::AnyKey,
+            "**" => Segment::AnyDepth,
+            key => Segment::Key(key.to_owned()),
+        });
+        rest = &after[end..];
+    }
+    segments
+}
+
+/// Parse the segment in brackets starting `text`, after its `[`. Returns the segment and the
+/// text following its `]`.
+fn parse_bracket(text: &str) -> (Segment, &str) {
+    if let Some(quoted) = text.strip_prefix('"') {
+        let mut key = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((offset, c)) = chars.next() {
+            match c {
+                '"' => {
+                    let after = &quoted[offset + 1..];
+                    return (Segment::Key(key), after.strip_prefix(']').unwrap_or(after));
+                }
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => key.push('\n'),
+                    Some('t') => key.push('\t'),
+                    Some('r') => key.push('\r'),
+                    Some(c) => key.push(c),
+                    None => {}
+                },
+                c => key.push(c),
+            }
+        }
+        return (Segment::Key(key), "");
+    }
+    let (contents, after) = text.split_once(']').unwrap_or((text, ""));
+    let segment = match contents {
+        "*" => Segment::AnyIndex,
+        "?" => Segment::NonScalarKey,
+        _ => contents
+            .parse()
+            .map_or_else(|_| Segment::Key(contents.to_owned()), Segment::Index),
+    };
+    (segment, after)
+}
@@ -0,0 +1,309 @@
+//! Utilities to inspect the anchors of YAML documents.
+//!
+//! The parser only gives numeric IDs to anchors in the events it emits. Names are recovered by
+//! running the scanner over the input: anchors are numbered in the order in which their tokens
+//! appear in the stream, starting from 1.
+
+use std::collections::HashMap;
+
+use hashlink::LinkedHashMap;
+use saphyr_parser::{
+    scanner::{Scanner, Token, TokenType},
+    Event, Marker, Parser, ScanError,
+};
+
+use crate::{event_path::EventPaths, HashBuilder, Schema, Yaml};
+
+/// An anchor as it was found in the input stream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct AnchorDefinition {
+    /// The name of the anchor, without the leading `&`.
+    pub(crate) name: String,
+    /// The position of the `&` in the input stream.
+    pub(crate) marker: Marker,
+}
+
+/// Scan `source` and return the anchors it defines, in the order the parser numbers them.
+///
+/// The anchor with ID `n` is at index `n - 1` in the returned `Vec`. Scanning stops at the first
+/// error. The parser will run into the same error and report it.
+pub(crate) fn scan_anchors<I: Iterator<Item = char>>(source: I) -> Vec<AnchorDefinition> {
+    Scanner::new(source)
+        .filter_map(|token| anchor_definition(&token))
+        .collect()
+}
+
+/// Return the anchor `token` defines, if it is an anchor.
+pub(crate) fn anchor_definition(token: &Token) -> Option<AnchorDefinition> {
+    match &token.1 {
+        TokenType::Anchor(name) => Some(AnchorDefinition {
+            name: name.clone(),
+            marker: token.0,
+        }),
+        _ => None,
+    }
+}
+
+/// The anchors defined in a YAML document, along with the node they are attached to.
+///
+/// Anchors are scoped to the document they are defined in. If the same anchor name is defined
+/// more than once in a document, the last definition wins, as it would for aliases.
+///
+/// ```
+/// use saphyr::Yaml;
+///
+/// let docs = Yaml::load_from_str_with_anchors("
+/// defaults: &defaults
+///   retries: 3
+/// job: *defaults
+/// ").unwrap();
+/// let (_, anchors) = &docs[0];
+/// assert_eq!(anchors.resolve("defaults").unwrap()["retries"].as_i64(), Some(3));
+/// assert!(anchors.resolve("unknown").is_none());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnchorTable<Node> {
+    /// Anchored nodes, indexed by anchor name, in order of first definition.
+    anchors: LinkedHashMap<String, Node, HashBuilder>,
+}
+
+impl<Node> AnchorTable<Node> {
+    /// Create an empty table.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            anchors: LinkedHashMap::default(),
+        }
+    }
+
+    /// Return the node the anchor `name` refers to, if any.
+    #[must_use]
+    pub fn resolve(&self, name: &str) -> Option<&Node> {
+        self.anchors.get(name)
+    }
+
+    /// Return the number of distinct anchor names in the table.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.anchors.len()
+    }
+
+    /// Return whether the table contains no anchor.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.anchors.is_empty()
+    }
+
+    /// Iterate over the anchor names and the nodes they refer to.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Node)> {
+        self.anchors
+            .iter()
+            .map(|(name, node)| (name.as_str(), node))
+    }
+
+    /// Add an anchor to the table, replacing the previous node of the same name, if any.
+    pub(crate) fn insert(&mut self, name: String, node: Node) {
+        self.anchors.insert(name, node);
+    }
+}
+
+impl<Node> Default for AnchorTable<Node> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The anchors of a YAML stream and the aliases referring to them, to inspect how nodes are
+/// shared.
+///
+/// Each alias is an edge from the innermost anchored node containing it, if any, to the anchor it
+/// refers to. This helps debugging documents built from templates, where an alias may share more
+/// than intended (e.g.: a mapping meant to be copied then modified).
+///
+/// ```
+/// use saphyr::AnchorGraph;
+///
+/// let graph = AnchorGraph::scan_str("\
+/// base: &base {retries: 3}
+/// defaults: &defaults {policy: *base}
+/// jobs: [*defaults, *base]
+/// ").unwrap();
+/// let names: Vec<_> = graph.anchors.iter().map(|anchor| anchor.name.as_str()).collect();
+/// assert_eq!(names, ["base", "defaults"]);
+/// assert_eq!(graph.aliases_of(0).count(), 2);
+///
+/// let alias = &graph.aliases[0];
+/// assert_eq!((alias.path.as_deref(), alias.anchor, alias.within), (Some("defaults.policy"), 0, Some(1)));
+/// assert!(graph.to_dot().contains("anchor1 -> anchor0 [label=\"defaults.policy\"];"));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AnchorGraph {
+    /// The anchors, in the order they are defined.
+    pub anchors: Vec<AnchorNode>,
+    /// The aliases, in the order they appear.
+    pub aliases: Vec<AliasEdge>,
+}
+
+/// An anchor of an [`AnchorGraph`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnchorNode {
+    /// The name of the anchor, without the leading `&`.
+    pub name: String,
+    /// The index of the document the anchor is defined in.
+    pub document: usize,
+    /// The path of the anchored node, as [`Yaml::paths`] writes it, or `None` if it is within a
+    /// key which is not a scalar.
+    ///
+    /// [`Yaml::paths`]: crate::Yaml::paths
+    pub path: Option<String>,
+    /// The position of the `&` of the anchor.
+    pub marker: Marker,
+    /// The index of the innermost other anchor whose node contains this one, if any.
+    pub within: Option<usize>,
+}
+
+/// An alias of an [`AnchorGraph`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AliasEdge {
+    /// The index of the anchor the alias refers to, in [`AnchorGraph::anchors`].
+    pub anchor: usize,
+    /// The index of the document the alias is in.
+    pub document: usize,
+    /// The path of the alias, as [`Yaml::paths`] writes it, or `None` if it is within a key
+    /// which is not a scalar.
+    ///
+    /// [`Yaml::paths`]: crate::Yaml::paths
+    pub path: Option<String>,
+    /// The position of the `*` of the alias.
+    pub marker: Marker,
+    /// The index of the innermost anchor whose node contains the alias, if any.
+    pub within: Option<usize>,
+}
+
+impl AnchorGraph {
+    /// Scan the anchors and aliases of `source`.
+    ///
+    /// # Errors
+    /// Returns `ScanError` when parsing fails.
+    pub fn scan_str(source: &str) -> Result<Self, ScanError> {
+        let definitions = scan_anchors(source.chars());
+        let mut graph = Self::default();
+        let mut paths = EventPaths::default();
+        let mut document = 0;
+        // The index in `graph.anchors` of each anchor ID.
+        let mut ids: HashMap<usize, usize> = HashMap::new();
+        // The anchor of each collection containing the next node, if any.
+        let mut collections: Vec<Option<usize>> = vec![];
+        // The value of anchored scalars, which aliases used as keys resolve to.
+        let mut scalars: HashMap<usize, Yaml> = HashMap::new();
+        for event in Parser::new_from_str(source) {
+            let (event, marker) = event?;
+            let within = collections.iter().rev().find_map(|anchor| *anchor);
+            let (anchor, path) = match event {
+                Event::DocumentStart => {
+                    paths.clear();
+                    continue;
+                }
+                Event::DocumentEnd => {
+                    document += 1;
+                    continue;
+                }
+                Event::Scalar(value, style, anchor, tag) => {
+                    let resolved = Schema::Legacy.resolve(value, style, tag.as_ref());
+                    let path = paths.on_scalar(&resolved);
+                    if anchor > 0 {
+                        scalars.insert(anchor, resolved);
+                    }
+                    (anchor, path)
+                }
+                Event::Alias(id) => {
+                    let path = paths.on_alias(scalars.get(&id));
+                    if let Some(&anchor) = ids.get(&id) {
+                        graph.aliases.push(AliasEdge {
+                            anchor,
+                            document,
+                            path,
+                            marker,
+                            within,
+                        });
+                    }
+                    continue;
+                }
+                Event::SequenceStart(anchor, _) | Event::MappingStart(anchor, _) => {
+                    let mapping = matches!(event, Event::MappingStart(..));
+                    let path = paths.on_collection_start(mapping);
+                    collections.push((anchor > 0).then_some(graph.anchors.len()));
+                    (anchor, path)
+                }
+                Event::SequenceEnd | Event::MappingEnd => {
+                    paths.on_collection_end();
+                    collections.pop();
+                    continue;
+                }
+                _ => continue,
+            };
+            if let Some(definition) = anchor.checked_sub(1).and_then(|i| definitions.get(i)) {
+                ids.insert(anchor, graph.anchors.len());
+                graph.anchors.push(AnchorNode {
+                    name: definition.name.clone(),
+                    document,
+                    path,
+                    marker: definition.marker,
+                    within,
+                });
+            }
+        }
+        Ok(graph)
+    }
+
+    /// Iterate over the aliases referring to the anchor at `index` in [`Self::anchors`].
+    pub fn aliases_of(&self, index: usize) -> impl Iterator<Item = &AliasEdge> {
+        self.aliases
+            .iter()
+            .filter(move |alias| alias.anchor == index)
+    }
+
+    /// Write the graph in the DOT language of Graphviz.
+    ///
+    /// Anchors are boxes named `anchor<index>`, labelled with their name and path. Aliases are
+    /// edges labelled with their path, from the innermost anchor containing them or from the
+    /// document they are in (named `document<index>`).
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        fn quote(text: &str) -> String {
+            format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+        }
+
+        let mut lines = vec!["digraph anchors {".to_owned()];
+        for (i, anchor) in self.anchors.iter().enumerate() {
+            let path = anchor.path.as_deref().unwrap_or("?");
+            let label = quote(&format!("&{}\n{path}", anchor.name)).replace('\n', "\\n");
+            lines.push(format!("  anchor{i} [shape=box, label={label}];"));
+        }
+        let mut documents: Vec<usize> = self
+            .aliases
+            .iter()
+            .filter(|alias| alias.within.is_none())
+            .map(|alias| alias.document)
+            .collect();
+        documents.dedup();
+        for document in documents {
+            lines.push(format!(
+                "  document{document} [shape=plaintext, label=\"document {document}\"];"
+            ));
+        }
+        for alias in &self.aliases {
+            let from = match alias.within {
+                Some(within) => format!("anchor{within}"),
+                None => format!("document{}", alias.document),
+            };
+            let label = quote(alias.path.as_deref().unwrap_or("?"));
+            lines.push(format!(
+                "  {from} -> anchor{} [label={label}];",
+                alias.anchor
+            ));
+        }
+        lines.push("}\n".to_owned());
+        lines.join("\n")
+    }
+}
@@ -6,7 +6,7 @@ use std::ops::{Index, IndexMut};
 
 use hashlink::LinkedHashMap;
 
-use crate::loader::parse_f64;
+use crate::{loader::parse_real, HashBuilder, Tag};
 
 /// YAML data for nodes that will contain annotations.
 ///
@@ -58,6 +58,10 @@ where
     ///
     /// Insertion order will match the order of insertion into the map.
     Hash(AnnotatedHash<Node>),
+    /// A YAML node with an explicit tag the loader recognizes. See [`Yaml::Tagged`].
+    ///
+    /// [`Yaml::Tagged`]: crate::Yaml::Tagged
+    Tagged(Tag, Box<Node>),
     /// Alias, not fully supported yet.
     Alias(usize),
     /// YAML null, e.g. `null` or `~`.
@@ -73,7 +77,7 @@ where
 pub type AnnotatedArray<Node> = Vec<Node>;
 /// The type contained in the [`YamlData::Hash`] variant. This corresponds to YAML mappings.
 #[allow(clippy::module_name_repetitions)]
-pub type AnnotatedHash<Node> = LinkedHashMap<Node, Node>;
+pub type AnnotatedHash<Node> = LinkedHashMap<Node, Node, HashBuilder>;
 
 impl<Node> YamlData<Node>
 where
@@ -104,6 +108,7 @@ where
     define_is!(is_null, Self::Null);
     define_is!(is_real, Self::Real(_));
     define_is!(is_string, Self::String(_));
+    define_is!(is_tagged, Self::Tagged(..));
 
     /// Return the `f64` value contained in this YAML node.
     ///
@@ -112,7 +117,7 @@ where
     #[must_use]
     pub fn as_f64(&self) -> Option<f64> {
         if let Self::Real(ref v) = self {
-            parse_f64(v)
+            parse_real(v)
         } else {
             None
         }
@@ -215,8 +220,11 @@ where
         if let Some(v) = self.as_vec() {
             v.get(idx).unwrap()
         } else if let Some(v) = self.as_hash() {
-            let key = Self::Integer(i64::try_from(idx).unwrap());
-            v.get(&key.into()).unwrap()
+            // Indices past `i64::MAX` cannot be keys.
+            i64::try_from(idx)
+                .ok()
+                .and_then(|key| v.get(&Self::Integer(key).into()))
+                .unwrap_or_else(|| panic!("{idx}: key not found in the mapping"))
         } else {
             panic!("{idx}: Index out of bounds");
         }
@@ -239,10 +247,11 @@ where
     fn index_mut(&mut self, idx: usize) -> &mut Node {
         match self {
             Self::Array(sequence) => sequence.index_mut(idx),
-            Self::Hash(mapping) => {
-                let key = Self::Integer(i64::try_from(idx).unwrap());
-                mapping.get_mut(&key.into()).unwrap()
-            }
+            // Indices past `i64::MAX` cannot be keys.
+            Self::Hash(mapping) => i64::try_from(idx)
+                .ok()
+                .and_then(|key| mapping.get_mut(&Self::Integer(key).into()))
+                .unwrap_or_else(|| panic!("{idx}: key not found in the mapping")),
             _ => panic!("Attempting to index but `self` is not a sequence nor a mapping"),
         }
     }
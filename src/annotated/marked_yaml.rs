@@ -2,10 +2,11 @@
 //!
 //! This is set aside so as to not clutter `annotated.rs`.
 
-use hashlink::LinkedHashMap;
 use saphyr_parser::{Marker, Parser, ScanError};
 
-use crate::{LoadableYamlNode, Yaml, YamlData, YamlLoader};
+use crate::{
+    AnchorTable, AnnotatedHash, LoadError, LoadableYamlNode, Tag, Yaml, YamlData, YamlLoader,
+};
 
 /// A YAML node with [`Marker`]s pointing to the start of the node.
 ///
@@ -63,6 +64,20 @@ impl MarkedYaml {
         parser.load(&mut loader, true)?;
         Ok(loader.into_documents())
     }
+
+    /// Load the given string as an array of YAML documents, along with the anchors they define.
+    ///
+    /// See the function [`load_from_str_with_anchors`] for more details.
+    ///
+    /// # Errors
+    /// Returns `LoadError` when loading fails.
+    ///
+    /// [`load_from_str_with_anchors`]: `Yaml::load_from_str_with_anchors`
+    pub fn load_from_str_with_anchors(
+        source: &str,
+    ) -> Result<Vec<(Self, AnchorTable<Self>)>, LoadError> {
+        YamlLoader::<Self>::default().load_from_str_with_anchors(source)
+    }
 }
 
 impl PartialEq for MarkedYaml {
@@ -100,7 +115,10 @@ impl LoadableYamlNode for MarkedYaml {
                 Yaml::Boolean(x) => YamlData::Boolean(x),
                 // Array and Hash will always have their container empty.
                 Yaml::Array(_) => YamlData::Array(vec![]),
-                Yaml::Hash(_) => YamlData::Hash(LinkedHashMap::new()),
+                Yaml::Hash(_) => YamlData::Hash(AnnotatedHash::default()),
+                Yaml::Tagged(tag, node) => {
+                    YamlData::Tagged(tag, Box::new(MarkedYaml::from_bare_yaml(*node)))
+                }
                 Yaml::Alias(x) => YamlData::Alias(x),
                 Yaml::Null => YamlData::Null,
                 Yaml::BadValue => YamlData::BadValue,
@@ -128,7 +146,7 @@ impl LoadableYamlNode for MarkedYaml {
         }
     }
 
-    fn hash_mut(&mut self) -> &mut LinkedHashMap<Self, Self> {
+    fn hash_mut(&mut self) -> &mut AnnotatedHash<Self> {
         if let YamlData::Hash(x) = &mut self.data {
             x
         } else {
@@ -149,4 +167,11 @@ impl LoadableYamlNode for MarkedYaml {
         self.marker = marker;
         self
     }
+
+    fn with_tag(self, tag: Tag) -> Self {
+        Self {
+            marker: self.marker,
+            data: YamlData::Tagged(tag, Box::new(self)),
+        }
+    }
 }
@@ -0,0 +1,254 @@
+//! Loading many files at once.
+
+use std::{
+    borrow::Cow,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+};
+
+use crate::{LoadError, Yaml, YamlLoader};
+#[cfg(feature = "rc")]
+use crate::{SharedYaml, StringPool};
+
+/// A function setting the options of the loader of each file. See [`LoadOptions::configure`].
+type Configure = Arc<dyn Fn(&mut YamlLoader<Yaml>) + Send + Sync>;
+
+/// Options for [`load_files`].
+#[derive(Clone, Default)]
+pub struct LoadOptions {
+    /// The number of threads loading files. `0` and `1` both load files on the calling thread.
+    threads: usize,
+    /// The maximum number of bytes read from all files, if limited.
+    max_total_bytes: Option<u64>,
+    /// The function setting the options of the loader of each file, if any.
+    configure: Option<Configure>,
+}
+
+impl LoadOptions {
+    /// Create options loading files one after the other, without limit.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load files on `threads` threads at once (defaults to loading them on the calling thread).
+    pub fn threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Limit the number of bytes read from all files to `max_total_bytes`.
+    ///
+    /// Loaded documents take memory in proportion to the size of their files, so this bounds
+    /// the memory the whole batch may take. Files are read no further than the bytes left in the
+    /// budget, and those which would exceed it fail with [`LoadError::BudgetExceeded`]. When
+    /// loading files on several threads, which files those are depends on the order in which
+    /// threads get to them.
+    pub fn max_total_bytes(&mut self, max_total_bytes: u64) -> &mut Self {
+        self.max_total_bytes = Some(max_total_bytes);
+        self
+    }
+
+    /// Call `configure` on the loader of each file before loading it, to set its options.
+    ///
+    /// ```
+    /// use saphyr::{load_files, LoadOptions, Schema};
+    ///
+    /// let mut options = LoadOptions::new();
+    /// options.configure(|loader| {
+    ///     loader.schema(Schema::Core);
+    /// });
+    /// # let _ = load_files::<&str>(&[], &options);
+    /// ```
+    pub fn configure<F>(&mut self, configure: F) -> &mut Self
+    where
+        F: Fn(&mut YamlLoader<Yaml>) + Send + Sync + 'static,
+    {
+        self.configure = Some(Arc::new(configure));
+        self
+    }
+}
+
+impl std::fmt::Debug for LoadOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoadOptions")
+            .field("threads", &self.threads)
+            .field("max_total_bytes", &self.max_total_bytes)
+            .field("configure", &self.configure.is_some())
+            .finish()
+    }
+}
+
+/// Load each file of `paths` as an array of YAML documents.
+///
+/// This is meant for tools scanning whole repositories of YAML files (e.g.: linters). Files are
+/// loaded with a [`YamlLoader`] set up by [`LoadOptions::configure`], optionally on several
+/// threads. Results are returned in the order of `paths`, each with the path of its file.
+///
+/// ```
+/// use saphyr::{load_files, LoadError, LoadOptions};
+///
+/// let dir = std::env::temp_dir().join(format!("saphyr-load-files-{}", std::process::id()));
+/// std::fs::create_dir_all(&dir).unwrap();
+/// std::fs::write(dir.join("a.yaml"), "a: 1").unwrap();
+/// std::fs::write(dir.join("b.yaml"), "b: [").unwrap();
+///
+/// let mut options = LoadOptions::new();
+/// options.threads(2);
+/// let results = load_files(&[dir.join("a.yaml"), dir.join("b.yaml"), dir.join("c.yaml")], &options);
+/// assert_eq!(results[0].1.as_ref().unwrap()[0]["a"].as_i64(), Some(1));
+/// assert!(matches!(results[1].1, Err(LoadError::Scan(_))));
+/// assert!(matches!(results[2].1, Err(LoadError::IO(_))));
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+///
+/// Files must be valid UTF-8. Strings of [`Yaml`] trees own their text, so the keys repeated
+/// across files are stored once per occurrence. With the `rc` feature, `load_shared_files` stores
+/// them once.
+///
+/// # Panics
+/// Panics if the function given to [`LoadOptions::configure`] panics.
+pub fn load_files<P: AsRef<Path> + Sync>(
+    paths: &[P],
+    options: &LoadOptions,
+) -> Vec<(PathBuf, Result<Vec<Yaml>, LoadError>)> {
+    load_each(paths, options, |docs| docs)
+}
+
+/// Load each file of `paths` as an array of [`SharedYaml`] documents, whose strings are shared
+/// through `pool`.
+///
+/// This is [`load_files`], except that the documents of each file are converted to
+/// [`SharedYaml`] and passed through `pool` once loaded, so that keys and other strings repeated
+/// across files are stored once for the whole batch. The [`Yaml`] trees of a file are dropped as
+/// soon as they are converted. The pool is shared by all threads: passing trees through it is not
+/// done in parallel.
+///
+/// ```
+/// use saphyr::{load_shared_files, LoadOptions, StringPool};
+///
+/// let dir = std::env::temp_dir().join(format!("saphyr-load-shared-{}", std::process::id()));
+/// std::fs::create_dir_all(&dir).unwrap();
+/// std::fs::write(dir.join("a.yaml"), "name: a").unwrap();
+/// std::fs::write(dir.join("b.yaml"), "name: b").unwrap();
+///
+/// let mut pool = StringPool::new();
+/// let results = load_shared_files(&[dir.join("a.yaml"), dir.join("b.yaml")], &LoadOptions::new(), &mut pool);
+/// assert_eq!(results[1].1.as_ref().unwrap()[0]["name"].as_str(), Some("b"));
+/// // `name`, `a` and `b`.
+/// assert_eq!(pool.len(), 3);
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+///
+/// This function is only available with the `rc` feature.
+///
+/// # Panics
+/// Panics if the function given to [`LoadOptions::configure`] panics.
+#[cfg(feature = "rc")]
+pub fn load_shared_files<P: AsRef<Path> + Sync>(
+    paths: &[P],
+    options: &LoadOptions,
+    pool: &mut StringPool,
+) -> Vec<(PathBuf, Result<Vec<SharedYaml>, LoadError>)> {
+    let pool = Mutex::new(pool);
+    load_each(paths, options, |docs| {
+        let mut docs: Vec<_> = docs.into_iter().map(SharedYaml::from).collect();
+        let mut pool = pool.lock().unwrap_or_else(PoisonError::into_inner);
+        for doc in &mut docs {
+            pool.deduplicate(doc);
+        }
+        docs
+    })
+}
+
+/// Load each file of `paths` as [`load_files`] does, passing the documents of each file through
+/// `convert` once loaded.
+fn load_each<P, T, F>(
+    paths: &[P],
+    options: &LoadOptions,
+    convert: F,
+) -> Vec<(PathBuf, Result<T, LoadError>)>
+where
+    P: AsRef<Path> + Sync,
+    T: Send,
+    F: Fn(Vec<Yaml>) -> T + Sync,
+{
+    let budget = options.max_total_bytes.map(|max| (max, AtomicU64::new(0)));
+    let load = |path: &Path| {
+        let mut loader = YamlLoader::default();
+        if let Some(configure) = &options.configure {
+            configure(&mut loader);
+        }
+        load_file(&mut loader, path, budget.as_ref()).map(&convert)
+    };
+
+    let threads = options.threads.min(paths.len());
+    if threads <= 1 {
+        return paths
+            .iter()
+            .map(|path| (path.as_ref().to_owned(), load(path.as_ref())))
+            .collect();
+    }
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..paths.len()).map(|_| None).collect::<Vec<_>>());
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(index) else {
+                    break;
+                };
+                let result = load(path.as_ref());
+                results.lock().unwrap_or_else(PoisonError::into_inner)[index] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner)
+        .into_iter()
+        .zip(paths)
+        .map(|(result, path)| {
+            (
+                path.as_ref().to_owned(),
+                result.expect("all files are loaded"),
+            )
+        })
+        .collect()
+}
+
+/// Load the file at `path` with `loader`, counting its size against `budget` if any.
+///
+/// The budget is made of the maximum number of bytes to read and the number of bytes read so far.
+fn load_file(
+    loader: &mut YamlLoader<Yaml>,
+    path: &Path,
+    budget: Option<&(u64, AtomicU64)>,
+) -> Result<Vec<Yaml>, LoadError> {
+    let mut bytes = vec![];
+    let file = std::fs::File::open(path)?;
+    if let Some((max, used)) = budget {
+        // Read one byte past the budget left to tell whether the file fits. The size of the file
+        // is not trusted, since it may change while it is read.
+        let left = max.saturating_sub(used.load(Ordering::Relaxed));
+        file.take(left.saturating_add(1)).read_to_end(&mut bytes)?;
+        let size = bytes.len() as u64;
+        let counted = used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                used.checked_add(size).filter(|total| total <= max)
+            })
+            .is_ok();
+        if !counted {
+            return Err(LoadError::BudgetExceeded(*max));
+        }
+    } else {
+        (&file).read_to_end(&mut bytes)?;
+    }
+    let source = String::from_utf8(bytes)
+        .map_err(|error| LoadError::Decode(Cow::Owned(error.to_string())))?;
+    loader.load_from_str(&source)
+}
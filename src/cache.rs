@@ -0,0 +1,467 @@
+//! A cache of loaded documents, stored on disk in a compact binary format.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use saphyr_parser::Marker;
+
+use crate::{
+    annotated::marked_yaml::MarkedYaml, ErrorCode, LoadError, LoadableYamlNode, Tag, Yaml,
+    YamlData, YamlLoader,
+};
+
+/// The bytes starting encoded documents, followed by the version of the format.
+const MAGIC: &[u8] = b"SAPHYR\0";
+
+/// The version of the format, to be bumped on each change.
+const VERSION: u8 = 1;
+
+/// The maximum depth of the nodes of decoded documents, so that corrupted data does not overflow
+/// the stack.
+const MAX_DEPTH: usize = 1024;
+
+/// Tags of the encoded nodes.
+const REAL: u8 = 0;
+const INTEGER: u8 = 1;
+const STRING: u8 = 2;
+const FALSE: u8 = 3;
+const TRUE: u8 = 4;
+const ARRAY: u8 = 5;
+const HASH: u8 = 6;
+const TAGGED: u8 = 7;
+const ALIAS: u8 = 8;
+const NULL: u8 = 9;
+const BAD_VALUE: u8 = 10;
+
+/// A node type which can be encoded into the cache: [`Yaml`] or [`MarkedYaml`].
+///
+/// This trait is sealed and cannot be implemented outside of this crate.
+pub trait CacheableNode: LoadableYamlNode + private::Sealed {
+    /// The byte telling which node type the data holds, so that both are cached separately.
+    #[doc(hidden)]
+    const KIND: u8;
+
+    /// Append the encoding of `self` to `out`.
+    #[doc(hidden)]
+    fn encode_node(&self, out: &mut Vec<u8>);
+}
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for crate::Yaml {}
+    impl Sealed for crate::MarkedYaml {}
+}
+
+impl CacheableNode for Yaml {
+    const KIND: u8 = 0;
+
+    fn encode_node(&self, out: &mut Vec<u8>) {
+        match self {
+            Yaml::Real(v) => encode_str(out, REAL, v),
+            Yaml::Integer(v) => {
+                out.push(INTEGER);
+                encode_int(out, *v);
+            }
+            Yaml::String(v) => encode_str(out, STRING, v),
+            Yaml::Boolean(v) => out.push(if *v { TRUE } else { FALSE }),
+            Yaml::Array(items) => {
+                encode_len(out, ARRAY, items.len());
+                for item in items {
+                    item.encode_node(out);
+                }
+            }
+            Yaml::Hash(entries) => {
+                encode_len(out, HASH, entries.len());
+                for (key, value) in entries {
+                    key.encode_node(out);
+                    value.encode_node(out);
+                }
+            }
+            Yaml::Tagged(tag, node) => {
+                encode_tag(out, tag);
+                node.encode_node(out);
+            }
+            Yaml::Alias(id) => encode_len(out, ALIAS, *id),
+            Yaml::Null => out.push(NULL),
+            Yaml::BadValue => out.push(BAD_VALUE),
+        }
+    }
+}
+
+impl CacheableNode for MarkedYaml {
+    const KIND: u8 = 1;
+
+    fn encode_node(&self, out: &mut Vec<u8>) {
+        encode_uint(out, self.marker.index() as u64);
+        encode_uint(out, self.marker.line() as u64);
+        encode_uint(out, self.marker.col() as u64);
+        match &self.data {
+            YamlData::Real(v) => encode_str(out, REAL, v),
+            YamlData::Integer(v) => {
+                out.push(INTEGER);
+                encode_int(out, *v);
+            }
+            YamlData::String(v) => encode_str(out, STRING, v),
+            YamlData::Boolean(v) => out.push(if *v { TRUE } else { FALSE }),
+            YamlData::Array(items) => {
+                encode_len(out, ARRAY, items.len());
+                for item in items {
+                    item.encode_node(out);
+                }
+            }
+            YamlData::Hash(entries) => {
+                encode_len(out, HASH, entries.len());
+                for (key, value) in entries {
+                    key.encode_node(out);
+                    value.encode_node(out);
+                }
+            }
+            YamlData::Tagged(tag, node) => {
+                encode_tag(out, tag);
+                node.encode_node(out);
+            }
+            YamlData::Alias(id) => encode_len(out, ALIAS, *id),
+            YamlData::Null => out.push(NULL),
+            YamlData::BadValue => out.push(BAD_VALUE),
+        }
+    }
+}
+
+/// Encode `docs` into a compact binary format, which [`decode_documents`] reads back.
+///
+/// The format is specific to this crate and to the node type. It is versioned, so that data
+/// encoded by other versions of this crate fails to decode rather than decoding incorrectly.
+///
+/// ```
+/// use saphyr::{decode_documents, encode_documents, MarkedYaml, Yaml};
+///
+/// let docs = Yaml::load_from_str("a: [1, 2.5, !!set {x}]").unwrap();
+/// assert_eq!(decode_documents::<Yaml>(&encode_documents(&docs)).unwrap(), docs);
+///
+/// // Markers of `MarkedYaml`s are kept.
+/// let docs = MarkedYaml::load_from_str("a:\n  b: c").unwrap();
+/// let decoded = decode_documents::<MarkedYaml>(&encode_documents(&docs)).unwrap();
+/// assert_eq!(decoded[0].data["a"].data["b"].marker.line(), 2);
+/// ```
+#[must_use]
+pub fn encode_documents<N: CacheableNode>(docs: &[N]) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    out.push(VERSION);
+    out.push(N::KIND);
+    encode_uint(&mut out, docs.len() as u64);
+    for doc in docs {
+        doc.encode_node(&mut out);
+    }
+    out
+}
+
+/// Decode documents encoded by [`encode_documents`].
+///
+/// # Errors
+/// Returns a [`CacheError`] if `data` was not encoded by this version of the crate for `N`, or
+/// is corrupted.
+pub fn decode_documents<N: CacheableNode>(data: &[u8]) -> Result<Vec<N>, CacheError> {
+    let mut decoder = Decoder { data, offset: 0 };
+    if !data.starts_with(MAGIC) {
+        return Err(decoder.error());
+    }
+    decoder.offset = MAGIC.len();
+    if decoder.byte()? != VERSION || decoder.byte()? != N::KIND {
+        return Err(CacheError {
+            offset: decoder.offset - 1,
+        });
+    }
+    let count = decoder.len()?;
+    let mut docs = Vec::with_capacity(count.min(decoder.remaining()));
+    for _ in 0..count {
+        docs.push(decoder.node(N::KIND == MarkedYaml::KIND, 0)?);
+    }
+    if decoder.remaining() > 0 {
+        return Err(decoder.error());
+    }
+    Ok(docs)
+}
+
+/// Cached documents could not be decoded. See [`decode_documents`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CacheError {
+    /// The offset, in bytes, of the invalid data.
+    pub offset: usize,
+}
+
+impl CacheError {
+    /// Return the stable code of the error, which [`Display`] prefixes the message with.
+    ///
+    /// [`Display`]: std::fmt::Display
+    #[must_use]
+    #[allow(clippy::unused_self)]
+    pub fn code(&self) -> ErrorCode {
+        ErrorCode::new("CACHE001")
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+impl std::fmt::Display for CacheError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: invalid cached documents at byte {}",
+            self.code(),
+            self.offset
+        )
+    }
+}
+
+/// A directory caching the documents loaded from YAML sources, keyed by a hash of the sources.
+///
+/// Command-line tools loading the same large files over and over spend most of their time
+/// parsing. The cache keeps the documents of each source in a file named after a hash of the
+/// source, in a compact binary format (see [`encode_documents`]). Loading an unchanged source
+/// again decodes that file instead of parsing the source.
+///
+/// ```
+/// use saphyr::{DocumentCache, Yaml};
+///
+/// let dir = std::env::temp_dir().join(format!("saphyr-cache-doc-{}", std::process::id()));
+/// let cache = DocumentCache::new(&dir);
+/// let docs = cache.load_str::<Yaml>("a: 1").unwrap();
+/// assert!(cache.entry_path::<Yaml>("a: 1").exists());
+/// // This time, the documents come from the cache.
+/// assert_eq!(cache.load_str::<Yaml>("a: 1").unwrap(), docs);
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+///
+/// Sources are loaded with the default options of the [`YamlLoader`]. The cache is best-effort:
+/// entries which cannot be read or decoded are ignored, and failing to write an entry does not
+/// fail the load. Entries are never removed; clearing the directory is left to the user.
+///
+/// This type is only available with the `cache` feature.
+#[derive(Clone, Debug)]
+pub struct DocumentCache {
+    /// The directory in which entries are stored.
+    dir: PathBuf,
+}
+
+impl DocumentCache {
+    /// Create a cache storing its entries in `dir`, which is created when needed.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Load `source` as an array of YAML documents, from the cache if it has them.
+    ///
+    /// # Errors
+    /// Returns `LoadError` when the source is not in the cache and loading it fails. Failures
+    /// are not cached.
+    pub fn load_str<N: CacheableNode>(&self, source: &str) -> Result<Vec<N>, LoadError> {
+        let path = self.entry_path::<N>(source);
+        if let Some(docs) = fs::read(&path)
+            .ok()
+            .and_then(|data| decode_documents(&data).ok())
+        {
+            return Ok(docs);
+        }
+        let docs = YamlLoader::<N>::default().load_from_str(source)?;
+        // Write to a temporary file first, so that other processes never read partial entries.
+        let temporary = path.with_extension(format!("tmp{}", std::process::id()));
+        let written = fs::create_dir_all(&self.dir)
+            .and_then(|()| fs::write(&temporary, encode_documents(&docs)))
+            .and_then(|()| fs::rename(&temporary, &path));
+        if written.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+        Ok(docs)
+    }
+
+    /// Load the file at `path` as an array of YAML documents, from the cache if it has them.
+    ///
+    /// The file is read in full to find its entry in the cache.
+    ///
+    /// # Errors
+    /// Returns `LoadError` when the file cannot be read, is not valid UTF-8, or is not in the
+    /// cache and loading it fails.
+    pub fn load_file<N: CacheableNode, P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<Vec<N>, LoadError> {
+        let source = String::from_utf8(fs::read(path)?)
+            .map_err(|error| LoadError::Decode(error.to_string().into()))?;
+        self.load_str(&source)
+    }
+
+    /// Return the path of the entry of `source` for nodes of type `N`, whether it exists or not.
+    #[must_use]
+    pub fn entry_path<N: CacheableNode>(&self, source: &str) -> PathBuf {
+        // 64-bit FNV-1a. Unlike the hashers of the standard library, it is stable across
+        // versions of Rust. The length of the source makes collisions even less likely.
+        let hash = source
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+            });
+        self.dir
+            .join(format!("{hash:016x}-{}.{}", source.len(), N::KIND))
+    }
+}
+
+/// Append the tag of a scalar followed by its text.
+fn encode_str(out: &mut Vec<u8>, tag: u8, v: &str) {
+    encode_len(out, tag, v.len());
+    out.extend_from_slice(v.as_bytes());
+}
+
+/// Append the tag of a node followed by a length or ID.
+fn encode_len(out: &mut Vec<u8>, tag: u8, len: usize) {
+    out.push(tag);
+    encode_uint(out, len as u64);
+}
+
+/// Append the tag of a tagged node followed by its handle and suffix.
+fn encode_tag(out: &mut Vec<u8>, tag: &Tag) {
+    encode_str(out, TAGGED, &tag.handle);
+    encode_uint(out, tag.suffix.len() as u64);
+    out.extend_from_slice(tag.suffix.as_bytes());
+}
+
+/// Append `v`, as a LEB128 variable-length integer.
+fn encode_uint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        // Truncation keeps the 7 bits we write.
+        #[allow(clippy::cast_possible_truncation)]
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    out.push(v as u8);
+}
+
+/// Append `v`, zigzag-encoded so that small negative integers are short.
+fn encode_int(out: &mut Vec<u8>, v: i64) {
+    #[allow(clippy::cast_sign_loss)]
+    encode_uint(out, ((v << 1) ^ (v >> 63)) as u64);
+}
+
+/// A reader of encoded documents.
+struct Decoder<'a> {
+    /// The encoded documents.
+    data: &'a [u8],
+    /// The offset of the next byte to read.
+    offset: usize,
+}
+
+impl Decoder<'_> {
+    /// Decode a node, and its marker if `with_markers`, at the given depth.
+    fn node<N: LoadableYamlNode>(
+        &mut self,
+        with_markers: bool,
+        depth: usize,
+    ) -> Result<N, CacheError> {
+        if depth > MAX_DEPTH {
+            return Err(self.error());
+        }
+        let marker = if with_markers {
+            Marker::new(self.len()?, self.len()?, self.len()?)
+        } else {
+            Marker::default()
+        };
+        let tag_offset = self.offset;
+        let node = match self.byte()? {
+            REAL => N::from_bare_yaml(Yaml::Real(self.string()?)),
+            INTEGER => {
+                let v = self.uint()?;
+                #[allow(clippy::cast_possible_wrap)]
+                N::from_bare_yaml(Yaml::Integer((v >> 1) as i64 ^ -((v & 1) as i64)))
+            }
+            STRING => N::from_bare_yaml(Yaml::String(self.string()?)),
+            FALSE => N::from_bare_yaml(Yaml::Boolean(false)),
+            TRUE => N::from_bare_yaml(Yaml::Boolean(true)),
+            ARRAY => {
+                let len = self.len()?;
+                let mut node = N::from_bare_yaml(Yaml::Array(vec![]));
+                let items = node.array_mut();
+                items.reserve(len.min(self.remaining()));
+                for _ in 0..len {
+                    items.push(self.node(with_markers, depth + 1)?);
+                }
+                node
+            }
+            HASH => {
+                let len = self.len()?;
+                let mut node = N::from_bare_yaml(Yaml::Hash(crate::Hash::default()));
+                let entries = node.hash_mut();
+                for _ in 0..len {
+                    let key = self.node(with_markers, depth + 1)?;
+                    let value = self.node(with_markers, depth + 1)?;
+                    entries.insert(key, value);
+                }
+                node
+            }
+            TAGGED => {
+                let tag = Tag::new(self.string()?, self.string()?);
+                self.node::<N>(with_markers, depth + 1)?.with_tag(tag)
+            }
+            ALIAS => N::from_bare_yaml(Yaml::Alias(self.len()?)),
+            NULL => N::from_bare_yaml(Yaml::Null),
+            BAD_VALUE => N::from_bare_yaml(Yaml::BadValue),
+            _ => return Err(CacheError { offset: tag_offset }),
+        };
+        Ok(node.with_marker(marker))
+    }
+
+    /// Read a byte.
+    fn byte(&mut self) -> Result<u8, CacheError> {
+        let byte = *self.data.get(self.offset).ok_or_else(|| self.error())?;
+        self.offset += 1;
+        Ok(byte)
+    }
+
+    /// Read a LEB128 variable-length integer.
+    fn uint(&mut self) -> Result<u64, CacheError> {
+        let start = self.offset;
+        let mut v = 0_u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            v |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(CacheError { offset: start })
+    }
+
+    /// Read a variable-length integer which fits in a `usize`.
+    fn len(&mut self) -> Result<usize, CacheError> {
+        let start = self.offset;
+        usize::try_from(self.uint()?).map_err(|_| CacheError { offset: start })
+    }
+
+    /// Read a length followed by as many bytes of UTF-8.
+    fn string(&mut self) -> Result<String, CacheError> {
+        let start = self.offset;
+        let len = self.len()?;
+        let bytes = self
+            .data
+            .get(self.offset..)
+            .and_then(|rest| rest.get(..len))
+            .ok_or(CacheError { offset: start })?;
+        let string = std::str::from_utf8(bytes).map_err(|_| CacheError { offset: start })?;
+        self.offset += len;
+        Ok(string.to_owned())
+    }
+
+    /// Return the number of bytes left to read.
+    fn remaining(&self) -> usize {
+        self.data.len() - self.offset
+    }
+
+    /// Return an error at the current offset.
+    fn error(&self) -> CacheError {
+        CacheError {
+            offset: self.offset,
+        }
+    }
+}
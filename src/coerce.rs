@@ -0,0 +1,345 @@
+//! Coercions of scalars into the types configuration files commonly use.
+
+use std::time::Duration;
+
+use crate::{ErrorCode, Yaml};
+
+/// The multipliers of the units accepted by [`Yaml::as_duration`], in nanoseconds.
+const DURATION_UNITS: &[(&str, u128)] = &[
+    ("ns", 1),
+    ("us", 1_000),
+    ("ms", 1_000_000),
+    ("s", 1_000_000_000),
+    ("m", 60 * 1_000_000_000),
+    ("h", 60 * 60 * 1_000_000_000),
+    ("d", 24 * 60 * 60 * 1_000_000_000),
+];
+
+/// The multipliers of the units accepted by [`Yaml::as_bytesize`], in bytes.
+const BYTESIZE_UNITS: &[(&str, u64)] = &[
+    ("B", 1),
+    ("kB", 1_000),
+    ("KB", 1_000),
+    ("MB", 1_000_000),
+    ("GB", 1_000_000_000),
+    ("TB", 1_000_000_000_000),
+    ("KiB", 1 << 10),
+    ("MiB", 1 << 20),
+    ("GiB", 1 << 30),
+    ("TiB", 1 << 40),
+];
+
+impl Yaml {
+    /// Return the value of the node if it is an integer fitting in a `u8`.
+    ///
+    /// # Errors
+    /// Returns a [`CoerceError`] if the node is not an integer or does not fit in a `u8`.
+    pub fn as_u8_checked(&self) -> Result<u8, CoerceError> {
+        self.as_int_checked("a u8")
+    }
+
+    /// Return the value of the node if it is an integer fitting in a `u16`.
+    ///
+    /// ```
+    /// use saphyr::{CoerceErrorKind, Yaml};
+    ///
+    /// assert_eq!(Yaml::Integer(8080).as_u16_checked(), Ok(8080));
+    /// let error = Yaml::Integer(70000).as_u16_checked().unwrap_err();
+    /// assert_eq!(error.kind, CoerceErrorKind::OutOfRange);
+    /// assert_eq!(error.text.as_deref(), Some("70000"));
+    /// ```
+    ///
+    /// # Errors
+    /// Returns a [`CoerceError`] if the node is not an integer or does not fit in a `u16`.
+    pub fn as_u16_checked(&self) -> Result<u16, CoerceError> {
+        self.as_int_checked("a u16")
+    }
+
+    /// Return the value of the node if it is an integer fitting in a `u32`.
+    ///
+    /// # Errors
+    /// Returns a [`CoerceError`] if the node is not an integer or does not fit in a `u32`.
+    pub fn as_u32_checked(&self) -> Result<u32, CoerceError> {
+        self.as_int_checked("a u32")
+    }
+
+    /// Return the value of the node if it is a non-negative integer.
+    ///
+    /// # Errors
+    /// Returns a [`CoerceError`] if the node is not an integer or is negative.
+    pub fn as_u64_checked(&self) -> Result<u64, CoerceError> {
+        self.as_int_checked("a u64")
+    }
+
+    /// Return the value of the node if it is an integer fitting in an `i32`.
+    ///
+    /// # Errors
+    /// Returns a [`CoerceError`] if the node is not an integer or does not fit in an `i32`.
+    pub fn as_i32_checked(&self) -> Result<i32, CoerceError> {
+        self.as_int_checked("an i32")
+    }
+
+    /// Return the duration the node is written as (e.g.: `30s`, `1h30m`, `250ms`).
+    ///
+    /// Durations are made of one or more integers, each followed by a unit: `ns`, `us`, `ms`,
+    /// `s`, `m`, `h` or `d` (24 hours). A bare integer is a number of seconds, whether it is
+    /// written as an integer or as a string. Units may be separated from their integer by a
+    /// space, but quantities may not be separated. Negative durations are out of range.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use saphyr::Yaml;
+    ///
+    /// let doc = &Yaml::load_from_str("{ timeout: 1m30s, retry: 250ms, ttl: 60 }").unwrap()[0];
+    /// assert_eq!(doc["timeout"].as_duration(), Ok(Duration::from_secs(90)));
+    /// assert_eq!(doc["retry"].as_duration(), Ok(Duration::from_millis(250)));
+    /// assert_eq!(doc["ttl"].as_duration(), Ok(Duration::from_secs(60)));
+    /// ```
+    ///
+    /// # Errors
+    /// Returns a [`CoerceError`] if the node is not a duration, or if it is too long for a
+    /// [`Duration`].
+    pub fn as_duration(&self) -> Result<Duration, CoerceError> {
+        const EXPECTED: &str = "a duration";
+        if let Yaml::Integer(secs) = self.untagged() {
+            return u64::try_from(*secs)
+                .map(Duration::from_secs)
+                .map_err(|_| self.coerce_error(EXPECTED, CoerceErrorKind::OutOfRange));
+        }
+        let text = self.coerce_str(EXPECTED)?;
+        if text.is_empty() {
+            return Err(self.coerce_error(EXPECTED, CoerceErrorKind::Invalid));
+        }
+        if is_negative(text) {
+            return Err(self.coerce_error(EXPECTED, CoerceErrorKind::OutOfRange));
+        }
+        let mut nanos: u128 = 0;
+        let mut rest = text;
+        while !rest.is_empty() {
+            let (value, unit, tail) = split_quantity(rest)
+                .ok_or_else(|| self.coerce_error(EXPECTED, CoerceErrorKind::Invalid))?;
+            let unit = unit.strip_prefix(' ').unwrap_or(unit);
+            // Only a bare integer may go without a unit.
+            let unit = if unit.is_empty() && rest == text && tail.is_empty() {
+                "s"
+            } else {
+                unit
+            };
+            let multiplier = DURATION_UNITS
+                .iter()
+                .find(|(name, _)| *name == unit)
+                .map(|(_, multiplier)| *multiplier)
+                .ok_or_else(|| self.coerce_error(EXPECTED, CoerceErrorKind::Invalid))?;
+            nanos = value
+                .map(u128::from)
+                .and_then(|value| value.checked_mul(multiplier))
+                .and_then(|value| nanos.checked_add(value))
+                .ok_or_else(|| self.coerce_error(EXPECTED, CoerceErrorKind::OutOfRange))?;
+            rest = tail;
+        }
+        let secs = u64::try_from(nanos / 1_000_000_000)
+            .map_err(|_| self.coerce_error(EXPECTED, CoerceErrorKind::OutOfRange))?;
+        // The remainder is lower than 10^9, which fits in a `u32`.
+        #[allow(clippy::cast_possible_truncation)]
+        Ok(Duration::new(secs, (nanos % 1_000_000_000) as u32))
+    }
+
+    /// Return the number of bytes the node is written as (e.g.: `10MiB`, `512 kB`, `4096`).
+    ///
+    /// The size is an integer, optionally followed by a space and a unit: `B`, decimal units
+    /// (`kB` or `KB`, `MB`, `GB`, `TB`) or binary units (`KiB`, `MiB`, `GiB`, `TiB`). A bare
+    /// integer is a number of bytes, whether it is written as an integer or as a string. Negative
+    /// sizes are out of range.
+    ///
+    /// ```
+    /// use saphyr::Yaml;
+    ///
+    /// let doc = &Yaml::load_from_str("{ cache: 10MiB, upload: 2 GB, page: 4096 }").unwrap()[0];
+    /// assert_eq!(doc["cache"].as_bytesize(), Ok(10 * 1024 * 1024));
+    /// assert_eq!(doc["upload"].as_bytesize(), Ok(2_000_000_000));
+    /// assert_eq!(doc["page"].as_bytesize(), Ok(4096));
+    /// ```
+    ///
+    /// # Errors
+    /// Returns a [`CoerceError`] if the node is not a size, or if it does not fit in a `u64`.
+    pub fn as_bytesize(&self) -> Result<u64, CoerceError> {
+        const EXPECTED: &str = "a size in bytes";
+        if let Yaml::Integer(bytes) = self.untagged() {
+            return u64::try_from(*bytes)
+                .map_err(|_| self.coerce_error(EXPECTED, CoerceErrorKind::OutOfRange));
+        }
+        let text = self.coerce_str(EXPECTED)?;
+        if is_negative(text) {
+            return Err(self.coerce_error(EXPECTED, CoerceErrorKind::OutOfRange));
+        }
+        let (value, unit, "") = split_quantity(text)
+            .ok_or_else(|| self.coerce_error(EXPECTED, CoerceErrorKind::Invalid))?
+        else {
+            return Err(self.coerce_error(EXPECTED, CoerceErrorKind::Invalid));
+        };
+        let unit = match unit.strip_prefix(' ').unwrap_or(unit) {
+            "" => "B",
+            unit => unit,
+        };
+        let multiplier = BYTESIZE_UNITS
+            .iter()
+            .find(|(name, _)| *name == unit)
+            .map(|(_, multiplier)| *multiplier)
+            .ok_or_else(|| self.coerce_error(EXPECTED, CoerceErrorKind::Invalid))?;
+        value
+            .and_then(|value| value.checked_mul(multiplier))
+            .ok_or_else(|| self.coerce_error(EXPECTED, CoerceErrorKind::OutOfRange))
+    }
+
+    /// Return the boolean the node is written as, accepting the spellings configuration files
+    /// commonly use.
+    ///
+    /// Besides booleans, this accepts the strings `true`, `yes`, `on`, `y` and `1` and their
+    /// negations `false`, `no`, `off`, `n` and `0`, regardless of case, and the integers `1` and
+    /// `0`.
+    ///
+    /// ```
+    /// use saphyr::Yaml;
+    ///
+    /// let doc = &Yaml::load_from_str("[yes, Off, 1, true]").unwrap()[0];
+    /// let values: Result<Vec<_>, _> = doc.as_vec().unwrap().iter().map(Yaml::as_bool_relaxed).collect();
+    /// assert_eq!(values, Ok(vec![true, false, true, true]));
+    /// ```
+    ///
+    /// # Errors
+    /// Returns a [`CoerceError`] if the node is written as none of them.
+    pub fn as_bool_relaxed(&self) -> Result<bool, CoerceError> {
+        const EXPECTED: &str = "a boolean";
+        match self.untagged() {
+            Yaml::Boolean(b) => return Ok(*b),
+            Yaml::Integer(1) => return Ok(true),
+            Yaml::Integer(0) => return Ok(false),
+            _ => {}
+        }
+        let text = self.coerce_str(EXPECTED)?.to_ascii_lowercase();
+        match text.as_str() {
+            "true" | "yes" | "on" | "y" | "1" => Ok(true),
+            "false" | "no" | "off" | "n" | "0" => Ok(false),
+            _ => Err(self.coerce_error(EXPECTED, CoerceErrorKind::Invalid)),
+        }
+    }
+
+    /// Return the value of the node if it is an integer fitting in a `T`, named `expected`.
+    fn as_int_checked<T: TryFrom<i64>>(&self, expected: &'static str) -> Result<T, CoerceError> {
+        match self.untagged() {
+            Yaml::Integer(v) => T::try_from(*v)
+                .map_err(|_| self.coerce_error(expected, CoerceErrorKind::OutOfRange)),
+            _ => Err(self.coerce_error(expected, CoerceErrorKind::Invalid)),
+        }
+    }
+
+    /// Return the text of the node if it is a string, or an error saying `expected` otherwise.
+    fn coerce_str(&self, expected: &'static str) -> Result<&str, CoerceError> {
+        match self.untagged() {
+            Yaml::String(v) => Ok(v),
+            _ => Err(self.coerce_error(expected, CoerceErrorKind::Invalid)),
+        }
+    }
+
+    /// Return an error of the given kind for the node, which was expected to be `expected`.
+    ///
+    /// Errors about nodes which are not scalars are always of kind
+    /// [`CoerceErrorKind::NotAScalar`].
+    fn coerce_error(&self, expected: &'static str, kind: CoerceErrorKind) -> CoerceError {
+        let text = match self.untagged() {
+            Yaml::Real(v) | Yaml::String(v) => Some(v.clone()),
+            Yaml::Integer(v) => Some(v.to_string()),
+            Yaml::Boolean(v) => Some(v.to_string()),
+            Yaml::Null => Some("null".to_string()),
+            _ => None,
+        };
+        let kind = if text.is_some() {
+            kind
+        } else {
+            CoerceErrorKind::NotAScalar(self.type_name())
+        };
+        CoerceError {
+            text,
+            expected,
+            kind,
+        }
+    }
+}
+
+/// Return whether `text` is a quantity written with a minus sign (e.g.: `-5s`).
+fn is_negative(text: &str) -> bool {
+    text.strip_prefix('-')
+        .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+/// Split the integer at the start of `text` from the unit after it, and the rest of `text`.
+///
+/// The unit is made of letters, optionally preceded by a space. The integer is `None` if it is
+/// too large for a `u64`.
+fn split_quantity(text: &str) -> Option<(Option<u64>, &str, &str)> {
+    let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 {
+        return None;
+    }
+    let (value, rest) = text.split_at(digits);
+    let unit_start = usize::from(rest.starts_with(' '));
+    let unit_len = rest[unit_start..]
+        .trim_start_matches(|c: char| c.is_ascii_alphabetic())
+        .len();
+    let unit_end = rest.len() - unit_len;
+    Some((value.parse().ok(), &rest[..unit_end], &rest[unit_end..]))
+}
+
+/// A node could not be coerced into the type a helper such as [`Yaml::as_duration`] returns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoerceError {
+    /// The text of the scalar, or `None` if the node is not a scalar.
+    pub text: Option<String>,
+    /// What the node was expected to be (e.g.: `a duration` or `a u16`).
+    pub expected: &'static str,
+    /// Why the node could not be coerced.
+    pub kind: CoerceErrorKind,
+}
+
+/// The kinds of [`CoerceError`]s.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CoerceErrorKind {
+    /// The node is not a scalar. This holds its type. See [`Yaml::type_name`].
+    NotAScalar(&'static str),
+    /// The scalar is not written as the expected type.
+    Invalid,
+    /// The scalar is written as the expected type, but its value does not fit in it.
+    OutOfRange,
+}
+
+impl CoerceError {
+    /// Return the stable code of the error, which [`Display`] prefixes the message with.
+    ///
+    /// [`Display`]: std::fmt::Display
+    #[must_use]
+    pub fn code(&self) -> ErrorCode {
+        match self.kind {
+            CoerceErrorKind::NotAScalar(_) => ErrorCode::new("COERCE001"),
+            CoerceErrorKind::Invalid => ErrorCode::new("COERCE002"),
+            CoerceErrorKind::OutOfRange => ErrorCode::new("COERCE003"),
+        }
+    }
+}
+
+impl std::error::Error for CoerceError {}
+
+impl std::fmt::Display for CoerceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.code())?;
+        let text = self.text.as_deref().unwrap_or_default();
+        match self.kind {
+            CoerceErrorKind::NotAScalar(found) => {
+                write!(f, "expected {}, found {found}", self.expected)
+            }
+            CoerceErrorKind::Invalid => write!(f, "`{text}` is not {}", self.expected),
+            CoerceErrorKind::OutOfRange => {
+                write!(f, "`{text}` is out of range for {}", self.expected)
+            }
+        }
+    }
+}
@@ -0,0 +1,356 @@
+//! The comments of documents, attached to the nodes they are about, to write them back.
+
+use std::collections::{HashMap, HashSet};
+
+use saphyr_parser::{
+    scanner::{Scanner, Token, TokenType},
+    Event, Marker, Parser, ScanError, TScalarStyle,
+};
+
+use crate::{event_path::EventPaths, Schema, Yaml};
+
+/// Where a [`Comment`] is, relative to the node it is attached to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CommentPlacement {
+    /// On its own line, right before the line of the node (e.g.: a description of a key).
+    Leading,
+    /// At the end of the line of the node, after its contents (e.g.: `port: 80 # HTTP`).
+    Trailing,
+    /// On its own line, separated from the next node by a blank line, if any (e.g.: a section
+    /// header, or commented out entries).
+    Standalone,
+}
+
+/// A comment of a document. See [`Comments`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Comment {
+    /// The text of the comment, after its `#` and without trailing whitespace.
+    pub text: String,
+    /// Where the comment is, relative to the node it is attached to.
+    pub placement: CommentPlacement,
+    /// The path of the node the comment is attached to, as [`Yaml::paths`] writes it (the path
+    /// of the document itself is empty). Comments are attached to keys of mappings and items of
+    /// sequences: a leading or standalone comment to the next one, a trailing comment to the
+    /// innermost one starting on its line.
+    ///
+    /// This is `None` for standalone comments after the last node of their document, and for
+    /// trailing comments on lines where no node starts (e.g.: after the end of a flow sequence
+    /// spanning several lines).
+    ///
+    /// [`Yaml::paths`]: crate::Yaml::paths
+    pub path: Option<String>,
+    /// The position of the `#` of the comment.
+    pub marker: Marker,
+}
+
+/// The comments of a document, attached to the nodes they are about.
+///
+/// Loading a document drops its comments, so that emitting it writes none. Scan the comments of
+/// a document along with loading it, and give them to [`YamlEmitter::comments`] to write them
+/// back where they were, relative to the nodes they are attached to:
+///
+/// ```
+/// use saphyr::{CommentPlacement, Comments, Yaml, YamlEmitter};
+///
+/// let source = "\
+/// ## The server.
+/// server:
+///   port: 8080 # Not 80.
+///
+/// ## Limits.
+///
+/// limits: {cpu: 2}
+/// ";
+/// let comments = Comments::scan_str(source).unwrap().remove(0);
+/// let placements: Vec<_> = comments
+///     .iter()
+///     .map(|comment| (comment.path.as_deref(), comment.placement))
+///     .collect();
+/// assert_eq!(placements, [
+///     (Some("server"), CommentPlacement::Leading),
+///     (Some("server.port"), CommentPlacement::Trailing),
+///     (Some("limits"), CommentPlacement::Standalone),
+/// ]);
+///
+/// let doc = &Yaml::load_from_str(source).unwrap()[0];
+/// let mut output = String::new();
+/// let mut emitter = YamlEmitter::new(&mut output);
+/// emitter.comments(&comments);
+/// emitter.dump(doc).unwrap();
+/// assert_eq!(output, "\
+/// ---
+/// ## The server.
+/// server:
+///   port: 8080 # Not 80.
+/// ## Limits.
+///
+/// limits:
+///   cpu: 2");
+/// ```
+///
+/// Comments are written with the text they were scanned with. Those attached to nodes which are
+/// not emitted (e.g.: removed since) are not written.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Comments {
+    /// The comments, in the order they appear.
+    comments: Vec<Comment>,
+}
+
+/// A key of a mapping or an item of a sequence, which comments are attached to.
+struct Entry {
+    /// The index of the document of the entry.
+    document: usize,
+    /// The line the entry starts on.
+    line: usize,
+    /// The number of collections containing the entry.
+    depth: usize,
+    /// The path of the entry, i.e. of the value of the key for keys.
+    path: String,
+}
+
+impl Comments {
+    /// Create an empty set of comments.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scan the comments of each document of `source`.
+    ///
+    /// # Errors
+    /// Returns `ScanError` when parsing fails.
+    pub fn scan_str(source: &str) -> Result<Vec<Self>, ScanError> {
+        let (entries, document_starts) = scan_entries(source)?;
+        let lines: Vec<&str> = source.split('\n').collect();
+        let found = scan_comments(source, &lines)?;
+        let own_line: HashSet<usize> = found
+            .iter()
+            .filter(|(_, own_line)| *own_line)
+            .map(|(marker, _)| marker.line())
+            .collect();
+
+        let mut documents: Vec<Self> = vec![Self::new(); document_starts.len().max(1)];
+        for (marker, own_line_comment) in found {
+            let line = marker.line();
+            let document_of_line = document_starts
+                .iter()
+                .rposition(|&start| start <= line)
+                .unwrap_or_default();
+            let text: String = lines[line - 1].chars().skip(marker.col() + 1).collect();
+            let text = text.trim_end().to_owned();
+
+            let (placement, entry) = if own_line_comment {
+                // The next line which is neither blank nor a comment, and whether a blank line
+                // precedes it.
+                let mut blank = false;
+                let mut next = None;
+                for (i, text) in lines.iter().enumerate().skip(line) {
+                    if text.trim().is_empty() {
+                        blank = true;
+                    } else if !own_line.contains(&(i + 1)) {
+                        next = Some(i + 1);
+                        break;
+                    }
+                }
+                let entry = next.and_then(|next| {
+                    entries
+                        .iter()
+                        .filter(|entry| entry.line == next && entry.document == document_of_line)
+                        .min_by_key(|entry| entry.depth)
+                });
+                let placement = if blank || entry.is_none() {
+                    CommentPlacement::Standalone
+                } else {
+                    CommentPlacement::Leading
+                };
+                (placement, entry)
+            } else {
+                let entry = entries
+                    .iter()
+                    .filter(|entry| entry.line == line)
+                    .max_by_key(|entry| entry.depth);
+                (CommentPlacement::Trailing, entry)
+            };
+            let document = entry.map_or(document_of_line, |entry| entry.document);
+            documents[document].comments.push(Comment {
+                text,
+                placement,
+                path: entry.map(|entry| entry.path.clone()),
+                marker,
+            });
+        }
+        Ok(documents)
+    }
+
+    /// Add a comment, after the others.
+    pub fn push(&mut self, comment: Comment) {
+        self.comments.push(comment);
+    }
+
+    /// Return the number of comments.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.comments.len()
+    }
+
+    /// Return whether there is no comment.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.comments.is_empty()
+    }
+
+    /// Iterate over the comments, in the order they appear.
+    pub fn iter(&self) -> impl Iterator<Item = &Comment> {
+        self.comments.iter()
+    }
+
+    /// Iterate over the comments attached to the node at `path` (or after the last node, for
+    /// `None`), in the order they appear.
+    pub fn of<'a>(&'a self, path: Option<&'a str>) -> impl Iterator<Item = &'a Comment> {
+        self.comments
+            .iter()
+            .filter(move |comment| comment.path.as_deref() == path)
+    }
+
+    /// Return whether a comment is attached to a node within the node at `path`.
+    pub(crate) fn any_within(&self, path: &str) -> bool {
+        self.comments.iter().any(|comment| {
+            comment.path.as_deref().is_some_and(|within| {
+                within.len() > path.len()
+                    && within.starts_with(path)
+                    && (path.is_empty() || matches!(within.as_bytes()[path.len()], b'.' | b'['))
+            })
+        })
+    }
+}
+
+/// Return the keys and items of `source`, along with the line each document starts on.
+fn scan_entries(source: &str) -> Result<(Vec<Entry>, Vec<usize>), ScanError> {
+    let mut entries = vec![];
+    let mut document_starts = vec![];
+    let mut paths = EventPaths::default();
+    // The value of anchored scalars, which aliases used as keys resolve to.
+    let mut scalars: HashMap<usize, Yaml> = HashMap::new();
+    for event in Parser::new_from_str(source) {
+        let (event, marker) = event?;
+        let depth = paths.depth();
+        let item = depth == 0 || paths.in_sequence();
+        let path = match event {
+            Event::DocumentStart => {
+                document_starts.push(marker.line());
+                paths.clear();
+                continue;
+            }
+            Event::Scalar(value, style, anchor, tag) => {
+                let resolved = Schema::Legacy.resolve(value, style, tag.as_ref());
+                let path = paths.on_scalar(&resolved);
+                if anchor > 0 {
+                    scalars.insert(anchor, resolved);
+                }
+                path
+            }
+            Event::Alias(anchor) => paths.on_alias(scalars.get(&anchor)),
+            Event::SequenceStart(..) | Event::MappingStart(..) => {
+                let path = paths.on_collection_start(matches!(event, Event::MappingStart(..)));
+                // Only items of sequences are entries, and not the collections of the document
+                // nor values of keys.
+                path.filter(|_| depth > 0 && item)
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                paths.on_collection_end();
+                continue;
+            }
+            _ => continue,
+        };
+        let path = match path {
+            Some(path) if item => Some(path),
+            Some(_) => None,
+            // A key, whose entry is at the path of its value.
+            None => paths.value_path().map(ToOwned::to_owned),
+        };
+        if let Some(path) = path {
+            entries.push(Entry {
+                document: document_starts.len().saturating_sub(1),
+                line: marker.line(),
+                depth,
+                path,
+            });
+        }
+    }
+    Ok((entries, document_starts))
+}
+
+/// Return the position of the `#` of each comment of `source`, split into `lines`, and whether
+/// the comment is on its own line.
+fn scan_comments(source: &str, lines: &[&str]) -> Result<Vec<(Marker, bool)>, ScanError> {
+    // The indices of the opening quotes of quoted scalars, and the first line, last line and
+    // indentation of the contents of block scalars.
+    let mut quotes = HashSet::new();
+    let mut block_scalars = vec![];
+    let mut block_scalar: Option<Marker> = None;
+    let mut scanner = Scanner::new(source.chars());
+    for Token(marker, token) in scanner.by_ref() {
+        if let Some(start) = block_scalar.take() {
+            block_scalars.push((start.line(), marker.line() - 1, start.col()));
+        }
+        match token {
+            TokenType::Scalar(TScalarStyle::SingleQuoted | TScalarStyle::DoubleQuoted, _) => {
+                quotes.insert(marker.index());
+            }
+            TokenType::Scalar(TScalarStyle::Literal | TScalarStyle::Folded, _) => {
+                block_scalar = Some(marker);
+            }
+            _ => {}
+        }
+    }
+    if let Some(error) = scanner.get_error() {
+        return Err(error);
+    }
+    // Lines of block scalars less indented than their contents are comments or blank.
+    let verbatim = |line: usize, text: &str| {
+        block_scalars.iter().any(|&(first, last, indent)| {
+            (first..=last).contains(&line)
+                && !(text.trim_start().starts_with('#')
+                    && text.len() - text.trim_start().len() < indent)
+        })
+    };
+
+    let mut comments = vec![];
+    let mut index = 0;
+    let mut quote: Option<char> = None;
+    for (i, text) in lines.iter().enumerate() {
+        let line = i + 1;
+        let start = index;
+        index += text.chars().count() + 1;
+        if quote.is_none() && verbatim(line, text) {
+            continue;
+        }
+        let mut after_space = true;
+        let mut chars = text.chars().enumerate();
+        while let Some((col, c)) = chars.next() {
+            match quote {
+                Some('\'') if c == '\'' => {
+                    if text.chars().nth(col + 1) == Some('\'') {
+                        chars.next();
+                    } else {
+                        quote = None;
+                    }
+                }
+                Some('"') if c == '\\' => {
+                    chars.next();
+                }
+                Some('"') if c == '"' => quote = None,
+                None if quotes.contains(&(start + col)) => quote = Some(c),
+                None if c == '#' && after_space => {
+                    let own_line = text.chars().take(col).all(char::is_whitespace);
+                    comments.push((Marker::new(start + col, line, col), own_line));
+                    break;
+                }
+                // Within a quoted scalar, or a token.
+                _ => {}
+            }
+            after_space = c == ' ' || c == '\t';
+        }
+    }
+    Ok(comments)
+}
@@ -0,0 +1,326 @@
+//! Partial parsing of documents being edited, for completion in editors.
+
+use saphyr_parser::{
+    scanner::{Scanner, TokenType},
+    Marker, TScalarStyle,
+};
+
+use crate::MarkerExt;
+
+/// What is expected at the cursor of a document being edited. See [`parse_for_completion`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CompletionKind {
+    /// A key of the mapping at [`Completion::path`].
+    Key,
+    /// The node at [`Completion::path`], i.e. the value of a mapping entry or an item of a
+    /// sequence.
+    ///
+    /// A value on a line of its own may also be the first key of a mapping being written.
+    Value,
+}
+
+/// The context of the cursor of a document being edited. See [`parse_for_completion`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Completion {
+    /// What is expected at the cursor.
+    pub kind: CompletionKind,
+    /// The path from the root of the document to the mapping receiving the key, or to the value.
+    ///
+    /// Each segment is the key of a mapping or the index of an item of a sequence, as
+    /// [`Yaml::lookup`] takes them. Keys which are not scalars are written `?`.
+    ///
+    /// [`Yaml::lookup`]: crate::Yaml::lookup
+    pub path: Vec<String>,
+    /// The text of the node being written, from its start to the cursor. This is the text a
+    /// completion replaces, and is empty if the node is not started yet.
+    pub prefix: String,
+    /// The start of the node being written.
+    pub start: Marker,
+}
+
+/// A collection containing the cursor.
+struct Frame {
+    /// Whether the collection is a mapping or a sequence.
+    mapping: bool,
+    /// Whether the collection is in flow style.
+    flow: bool,
+    /// Whether the collection is a sequence whose entries are not indented from the mapping it is
+    /// a value of.
+    indentless: bool,
+    /// The column of the keys or entries of a block collection, once known.
+    indent: Option<usize>,
+    /// The key of the current entry of a mapping, as a path segment.
+    key: Option<String>,
+    /// The number of entries of a sequence so far.
+    entries: usize,
+    /// What the collection expects next.
+    state: State,
+}
+
+/// What a [`Frame`] expects next.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// The key of an entry.
+    Key,
+    /// The value of an entry.
+    Value,
+    /// Another entry, the current one being complete.
+    Done,
+}
+
+impl Frame {
+    /// Return the path segment of the current entry of the collection.
+    fn segment(&self) -> String {
+        if self.mapping {
+            self.key.clone().unwrap_or_else(|| "?".into())
+        } else {
+            self.entries.saturating_sub(1).to_string()
+        }
+    }
+}
+
+/// Parse the text of `source` before `cursor` (a byte offset) to tell what the cursor is writing.
+///
+/// This is meant for language servers, which offer keys and values the schema of a document
+/// allows at the cursor. The node being written at the cursor (e.g.: a key without its `:` yet,
+/// or a quoted scalar without its closing quote) is tolerated. The structure around it is taken
+/// from the tokens of the text and the indentation of the cursor, without parsing the document.
+///
+/// ```
+/// use saphyr::{parse_for_completion, CompletionKind};
+///
+/// let source = "\
+/// services:
+///   web:
+///     image: nginx
+///     po
+/// ";
+/// let cursor = source.find("po").unwrap() + 2;
+/// let completion = parse_for_completion(source, cursor).unwrap();
+/// assert_eq!(completion.kind, CompletionKind::Key);
+/// assert_eq!(completion.path, ["services", "web"]);
+/// assert_eq!(completion.prefix, "po");
+///
+/// let completion = parse_for_completion("ports: [80, ", 12).unwrap();
+/// assert_eq!(completion.kind, CompletionKind::Value);
+/// assert_eq!(completion.path, ["ports", "1"]);
+/// ```
+///
+/// Returns `None` if nothing is expected at the cursor, e.g. in a comment or right after a
+/// complete value. Paths are relative to the document containing the cursor. If the text has an
+/// error before the cursor, the node at the error is taken to extend to the cursor. `cursor` is
+/// moved back to the start of the `char` it is in, if any, and to the end of `source` at most.
+#[must_use]
+pub fn parse_for_completion(source: &str, cursor: usize) -> Option<Completion> {
+    let mut cursor = cursor.min(source.len());
+    while !source.is_char_boundary(cursor) {
+        cursor -= 1;
+    }
+    let text = &source[..cursor];
+    let len = text.chars().count();
+
+    let mut scanner = Scanner::new(text.chars());
+    let mut tokens: Vec<_> = scanner
+        .by_ref()
+        // The end of the text unrolls indentation, which the cursor may not do.
+        .take_while(|token| {
+            !(token.0.index() == len
+                && matches!(token.1, TokenType::BlockEnd | TokenType::StreamEnd))
+        })
+        .collect();
+    let line = text.matches('\n').count() + 1;
+    let cursor_line = text.rsplit('\n').next().unwrap_or_default();
+    let end = Marker::new(len, line, cursor_line.chars().count());
+    // The node being written is the plain scalar ending the text, or the node at the error.
+    let start = match (scanner.get_error(), tokens.last()) {
+        // A key is required where the last line starts, but its `:` is not written yet.
+        (Some(error), last) if error.marker().index() == len => {
+            let indent = cursor_line
+                .chars()
+                .take_while(|c| matches!(c, ' ' | '\t'))
+                .count();
+            let start = Marker::new(end.index() - end.col() + indent, line, indent);
+            if last.map_or(true, |token| token.0.index() <= start.index()) && indent < end.col() {
+                start
+            } else {
+                end
+            }
+        }
+        (Some(error), _) => *error.marker(),
+        // Plain scalars may continue on the line of the cursor, once something is written on it.
+        (None, Some(token))
+            if matches!(token.1, TokenType::Scalar(TScalarStyle::Plain, _))
+                && (token.0.line() == line || !cursor_line.trim().is_empty()) =>
+        {
+            tokens.pop().map_or(end, |token| token.0)
+        }
+        _ => end,
+    };
+    let prefix = &text[start.to_byte_offset(text)..];
+
+    // Plain scalars end at comments: the cursor is in a comment if one starts after the last
+    // token on its line.
+    let last_token = if start == end {
+        tokens.last().map_or(Marker::default(), |token| token.0)
+    } else {
+        start
+    };
+    let line_start = text.rfind('\n').map_or(0, |line_break| line_break + 1);
+    let tail = &text[line_start.max(last_token.to_byte_offset(text))..];
+    let quoted = prefix.starts_with(['\'', '"']);
+    if !quoted && (tail.starts_with('#') || tail.contains(" #") || tail.contains("\t#")) {
+        return None;
+    }
+
+    let mut stack = Vec::new();
+    let mut last_line = 1;
+    for token in &tokens {
+        on_token(&mut stack, token.0, &token.1, source);
+        // Ends of block collections are at the token following them.
+        if token.1 != TokenType::BlockEnd {
+            last_line = token.0.line();
+        }
+    }
+    let (kind, path) = resolve(&mut stack, start, start.line() > last_line)?;
+    Some(Completion {
+        kind,
+        path,
+        prefix: prefix.to_owned(),
+        start,
+    })
+}
+
+/// Update the collections containing the cursor with the token of type `token` at `marker`.
+fn on_token(stack: &mut Vec<Frame>, marker: Marker, token: &TokenType, source: &str) {
+    let collection = |mapping, flow, indent, state| Frame {
+        mapping,
+        flow,
+        indentless: false,
+        indent,
+        key: None,
+        entries: usize::from(flow && !mapping),
+        state,
+    };
+    match token {
+        TokenType::DocumentStart | TokenType::DocumentEnd => stack.clear(),
+        TokenType::BlockMappingStart => stack.push(collection(true, false, None, State::Key)),
+        TokenType::FlowMappingStart => stack.push(collection(true, true, None, State::Key)),
+        TokenType::BlockSequenceStart => {
+            stack.push(collection(false, false, Some(marker.col()), State::Done));
+        }
+        TokenType::FlowSequenceStart => stack.push(collection(false, true, None, State::Value)),
+        TokenType::BlockEntry => match stack.last_mut() {
+            Some(top) if !top.flow && !top.mapping => {
+                top.entries += 1;
+                top.state = State::Value;
+            }
+            Some(top) if !top.flow => stack.push(Frame {
+                indentless: true,
+                entries: 1,
+                ..collection(false, false, Some(dash_col(source, marker)), State::Value)
+            }),
+            _ => {}
+        },
+        TokenType::FlowEntry => match stack.last_mut() {
+            Some(top) if top.flow && top.mapping => {
+                top.key = None;
+                top.state = State::Key;
+            }
+            Some(top) if top.flow => {
+                top.entries += 1;
+                top.state = State::Value;
+            }
+            _ => {}
+        },
+        TokenType::Key => {
+            if stack.last().is_some_and(|top| top.indentless) {
+                stack.pop();
+                end_node(stack, None);
+            }
+            if let Some(top) = stack.last_mut().filter(|top| top.mapping) {
+                top.key = None;
+                top.state = State::Key;
+                if !top.flow {
+                    top.indent.get_or_insert(marker.col());
+                }
+            }
+        }
+        TokenType::Value => {
+            if let Some(top) = stack.last_mut().filter(|top| top.mapping) {
+                top.key.get_or_insert_with(|| "~".into());
+                top.state = State::Value;
+            }
+        }
+        TokenType::Scalar(_, value) => end_node(stack, Some(value.as_str())),
+        TokenType::Alias(_) => end_node(stack, None),
+        TokenType::BlockEnd | TokenType::FlowMappingEnd | TokenType::FlowSequenceEnd => {
+            stack.pop();
+            end_node(stack, None);
+        }
+        _ => {}
+    }
+}
+
+/// Record that a node of the innermost collection ended, which is a key written `key` if the
+/// collection expects one.
+fn end_node(stack: &mut [Frame], key: Option<&str>) {
+    if let Some(top) = stack.last_mut() {
+        if top.mapping && top.state == State::Key {
+            top.key = Some(key.unwrap_or("?").to_owned());
+        } else {
+            top.state = State::Done;
+        }
+    }
+}
+
+/// Return the column of the `-` of the block entry token at `marker`, which is after the `-`.
+fn dash_col(source: &str, marker: Marker) -> usize {
+    let line = source
+        .split('\n')
+        .nth(marker.line() - 1)
+        .unwrap_or_default();
+    let before: Vec<char> = line.chars().take(marker.col()).collect();
+    before
+        .iter()
+        .rposition(|&c| !matches!(c, ' ' | '\t'))
+        .unwrap_or_default()
+}
+
+/// Return what is expected at `start`, and where, given the collections containing it.
+///
+/// `new_line` tells whether `start` is on a line after the last token, in which case its
+/// indentation tells which collection it belongs to.
+fn resolve(
+    stack: &mut Vec<Frame>,
+    start: Marker,
+    new_line: bool,
+) -> Option<(CompletionKind, Vec<String>)> {
+    let path = |stack: &[Frame], with_top: bool| {
+        let parents = stack.len() - usize::from(!with_top);
+        stack[..parents].iter().map(Frame::segment).collect()
+    };
+    while let Some(top) = stack.last() {
+        let indent = top.indent.unwrap_or_default();
+        let state = if top.flow || !new_line {
+            top.state
+        } else if top.state == State::Value && start.col() > indent {
+            State::Value
+        } else if start.col() < indent || (!top.mapping && start.col() == indent) {
+            // The cursor is dedented out of the collection.
+            stack.pop();
+            end_node(stack, None);
+            continue;
+        } else if top.mapping {
+            State::Key
+        } else {
+            // The cursor continues the last item.
+            State::Value
+        };
+        return match state {
+            State::Key => Some((CompletionKind::Key, path(stack, false))),
+            State::Value => Some((CompletionKind::Value, path(stack, true))),
+            State::Done => None,
+        };
+    }
+    Some((CompletionKind::Value, Vec::new()))
+}
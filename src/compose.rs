@@ -0,0 +1,782 @@
+//! The composition stage of loading, exposed on its own.
+//!
+//! Loading a YAML stream happens in three stages:
+//!   1. Parsing turns the input into a sequence of events (see [`saphyr_parser::Parser`]).
+//!   2. Composition assembles events into a graph of nodes, resolving aliases to the node they
+//!      refer to. Nodes keep their tag, anchor and style; scalars are not interpreted.
+//!   3. Construction turns the graph into native values (e.g.: [`Yaml`]), resolving scalars as
+//!      per their tag.
+//!
+//! [`YamlLoader`] performs the last two stages at once. This module allows stopping after
+//! composition, inspecting or altering the graph, and constructing values from it afterwards.
+
+use std::collections::HashMap;
+
+use saphyr_parser::{Event, MarkedEventReceiver, Marker, Parser, ScanError, TScalarStyle};
+
+use crate::{
+    anchor::{scan_anchors, AnchorDefinition},
+    loader::resolve_scalar,
+    ErrorCode, LoadError, LoadableYamlNode, Scalar, Tag, Yaml, YamlLoader,
+};
+
+/// The identifier of a node within a [`ComposedDocument`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    /// Return the index of the node in [`ComposedDocument::nodes`].
+    #[must_use]
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// The contents of a [`ComposedNode`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ComposedData {
+    /// A scalar, as written in the document.
+    Scalar {
+        /// The value of the scalar, with escapes and folding already processed.
+        value: String,
+        /// The style in which the scalar was written.
+        style: TScalarStyle,
+    },
+    /// A sequence of nodes.
+    Sequence(Vec<NodeId>),
+    /// A mapping, as a list of key-value pairs in the order they appear in the document.
+    ///
+    /// Duplicate keys are kept: deciding what to do with them is up to construction.
+    Mapping(Vec<(NodeId, NodeId)>),
+}
+
+/// A node of a [`ComposedDocument`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ComposedNode {
+    /// The contents of the node.
+    pub data: ComposedData,
+    /// The tag of the node, if any.
+    pub tag: Option<Tag>,
+    /// The name of the anchor of the node, if any and if it is known.
+    ///
+    /// Names are only known when composing from a string (see [`compose_str`]).
+    pub anchor: Option<String>,
+    /// The position of the start of the node.
+    pub marker: Marker,
+}
+
+impl ComposedNode {
+    /// Return the node as a [`Scalar`], if it is one.
+    #[must_use]
+    pub fn scalar(&self) -> Option<Scalar> {
+        match &self.data {
+            ComposedData::Scalar { value, style } => Some(Scalar {
+                value: value.clone(),
+                tag: self.tag.clone(),
+                style: *style,
+                marker: self.marker,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// A YAML document, composed into a graph of nodes.
+///
+/// Nodes are stored in an arena and refer to each other by [`NodeId`]. An alias is replaced by
+/// the [`NodeId`] of the node it refers to, which is thus shared by several parents.
+///
+/// ```
+/// use saphyr::{compose_str, ComposedData, Yaml};
+///
+/// let docs = compose_str("base: &base { a: 1 }\nderived: *base").unwrap();
+/// let doc = &docs[0];
+/// let ComposedData::Mapping(entries) = &doc.node(doc.root()).data else {
+///     panic!("not a mapping")
+/// };
+/// // Both values are the same node.
+/// assert_eq!(entries[0].1, entries[1].1);
+/// assert_eq!(doc.node(entries[0].1).anchor.as_deref(), Some("base"));
+///
+/// assert_eq!(doc.construct()["derived"]["a"], Yaml::Integer(1));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ComposedDocument {
+    /// The nodes of the document.
+    nodes: Vec<ComposedNode>,
+    /// The root node of the document.
+    root: NodeId,
+    /// The position of the start of the document.
+    start: Marker,
+    /// The position of the end of the document.
+    end: Marker,
+}
+
+impl ComposedDocument {
+    /// Return the root node of the document.
+    #[must_use]
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+
+    /// Return the node with the given identifier.
+    ///
+    /// # Panics
+    /// This function panics if `id` does not belong to this document.
+    #[must_use]
+    pub fn node(&self, id: NodeId) -> &ComposedNode {
+        &self.nodes[id.0]
+    }
+
+    /// Return the node with the given identifier, mutably.
+    ///
+    /// # Panics
+    /// This function panics if `id` does not belong to this document.
+    pub fn node_mut(&mut self, id: NodeId) -> &mut ComposedNode {
+        &mut self.nodes[id.0]
+    }
+
+    /// Return all the nodes of the document, indexed by [`NodeId::index`].
+    #[must_use]
+    pub fn nodes(&self) -> &[ComposedNode] {
+        &self.nodes
+    }
+
+    /// Iterate over the identifiers of all the nodes of the document, in the order of
+    /// [`Self::nodes`].
+    pub fn ids(&self) -> impl Iterator<Item = NodeId> {
+        (0..self.nodes.len()).map(NodeId)
+    }
+
+    /// Return all the nodes of the document mutably, indexed by [`NodeId::index`].
+    pub fn nodes_mut(&mut self) -> &mut [ComposedNode] {
+        &mut self.nodes
+    }
+
+    /// Send the events describing the document to `receiver`.
+    ///
+    /// Nodes referred to more than once are anchored where they first appear and aliased
+    /// afterwards. Events start with [`Event::DocumentStart`] and end with [`Event::DocumentEnd`].
+    pub fn replay<R: MarkedEventReceiver>(&self, receiver: &mut R) {
+        self.replay_from(self.root, receiver);
+    }
+
+    /// Send the events describing the subtree starting at `root` to `receiver`, as a document.
+    fn replay_from<R: MarkedEventReceiver>(&self, root: NodeId, receiver: &mut R) {
+        let mut references = vec![0_usize; self.nodes.len()];
+        references[root.0] += 1;
+        for node in &self.nodes {
+            match &node.data {
+                ComposedData::Scalar { .. } => {}
+                ComposedData::Sequence(items) => {
+                    for item in items {
+                        references[item.0] += 1;
+                    }
+                }
+                ComposedData::Mapping(entries) => {
+                    for (key, value) in entries {
+                        references[key.0] += 1;
+                        references[value.0] += 1;
+                    }
+                }
+            }
+        }
+
+        let mut replay = Replay {
+            document: self,
+            references,
+            anchor_ids: HashMap::new(),
+            next_anchor_id: 1,
+        };
+        receiver.on_event(Event::DocumentStart, self.start);
+        replay.node(root, receiver);
+        receiver.on_event(Event::DocumentEnd, self.end);
+    }
+
+    /// Return a view of the root node of the document.
+    #[must_use]
+    pub fn view(&self) -> NodeView<'_> {
+        self.view_of(self.root)
+    }
+
+    /// Return a view of the node with the given identifier.
+    ///
+    /// # Panics
+    /// This function panics if `id` does not belong to this document.
+    #[must_use]
+    pub fn view_of(&self, id: NodeId) -> NodeView<'_> {
+        assert!(
+            id.0 < self.nodes.len(),
+            "node does not belong to the document"
+        );
+        NodeView { document: self, id }
+    }
+
+    /// Construct the document as a [`Yaml`] node, as [`Yaml::load_from_str`] would.
+    #[must_use]
+    pub fn construct(&self) -> Yaml {
+        // The default loader cannot fail.
+        self.construct_with(&mut YamlLoader::default())
+            .unwrap_or(Yaml::BadValue)
+    }
+
+    /// Construct the document using `loader`, which allows using any node type and the options of
+    /// the loader.
+    ///
+    /// # Errors
+    /// Returns `LoadError` if the document goes against the options of `loader`.
+    pub fn construct_with<Node: LoadableYamlNode>(
+        &self,
+        loader: &mut YamlLoader<Node>,
+    ) -> Result<Node, LoadError> {
+        let mut docs = loader.load_from_events(|loader| self.replay(loader))?;
+        Ok(docs
+            .pop()
+            .unwrap_or_else(|| Node::from_bare_yaml(Yaml::BadValue)))
+    }
+}
+
+/// A read-only view of a node of a [`ComposedDocument`], navigating it as a tree.
+///
+/// Loading a document into [`Yaml`] copies the node an alias refers to for each alias. In
+/// documents reusing the same anchors many times, this may take a lot of memory (or even be used
+/// maliciously, as in the "billion laughs" attack). A [`ComposedDocument`] keeps a single copy of
+/// each node instead, and views allow reading it with the same semantics as [`Yaml`], without
+/// ever copying it:
+///
+/// ```
+/// use saphyr::compose_str;
+///
+/// let docs = compose_str("
+/// a: &a [lol, lol, lol, lol, lol, lol, lol, lol, lol]
+/// b: &b [*a, *a, *a, *a, *a, *a, *a, *a, *a]
+/// c: &c [*b, *b, *b, *b, *b, *b, *b, *b, *b]
+/// d: [*c, *c, *c, *c, *c, *c, *c, *c, *c]
+/// ").unwrap();
+/// let doc = &docs[0];
+/// let d = doc.view().get("d").unwrap();
+/// assert_eq!(d.at(8).unwrap().at(8).unwrap().at(8).unwrap().at(8).unwrap().as_str(), Some("lol"));
+/// // 6561 strings are reachable, but only a handful of nodes exist.
+/// assert_eq!(doc.nodes().len(), 18);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct NodeView<'doc> {
+    /// The document the node belongs to.
+    document: &'doc ComposedDocument,
+    /// The node we are viewing.
+    id: NodeId,
+}
+
+impl<'doc> NodeView<'doc> {
+    /// Return the identifier of the node.
+    #[must_use]
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    /// Return the node.
+    #[must_use]
+    pub fn node(&self) -> &'doc ComposedNode {
+        self.document.node(self.id)
+    }
+
+    /// Return the value of the node if it is a scalar, resolved as per its tag.
+    ///
+    /// Return `None` if the node is a collection.
+    #[must_use]
+    pub fn scalar(&self) -> Option<Yaml> {
+        let node = self.node();
+        match &node.data {
+            ComposedData::Scalar { value, style } => {
+                let tag = node.tag.clone().map(|tag| saphyr_parser::Tag {
+                    handle: tag.handle,
+                    suffix: tag.suffix,
+                });
+                Some(resolve_scalar(value.clone(), *style, tag.as_ref()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the contents of the node if it is a scalar resolving to a string.
+    #[must_use]
+    pub fn as_str(&self) -> Option<&'doc str> {
+        match (&self.node().data, self.scalar()?) {
+            (ComposedData::Scalar { value, .. }, Yaml::String(_)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Return the value of the node if it is a scalar resolving to an integer.
+    #[must_use]
+    pub fn as_i64(&self) -> Option<i64> {
+        self.scalar()?.as_i64()
+    }
+
+    /// Return the value of the node if it is a scalar resolving to a real.
+    #[must_use]
+    pub fn as_f64(&self) -> Option<f64> {
+        self.scalar()?.as_f64()
+    }
+
+    /// Return the value of the node if it is a scalar resolving to a boolean.
+    #[must_use]
+    pub fn as_bool(&self) -> Option<bool> {
+        self.scalar()?.as_bool()
+    }
+
+    /// Return whether the node is a scalar resolving to null.
+    #[must_use]
+    pub fn is_null(&self) -> bool {
+        self.scalar().is_some_and(|scalar| scalar.is_null())
+    }
+
+    /// Return the number of items of a sequence or entries of a mapping, or `None` for scalars.
+    #[must_use]
+    pub fn len(&self) -> Option<usize> {
+        match &self.node().data {
+            ComposedData::Scalar { .. } => None,
+            ComposedData::Sequence(items) => Some(items.len()),
+            ComposedData::Mapping(entries) => Some(entries.len()),
+        }
+    }
+
+    /// Return whether the node is an empty collection, or `None` for scalars.
+    #[must_use]
+    pub fn is_empty(&self) -> Option<bool> {
+        self.len().map(|len| len == 0)
+    }
+
+    /// Return the item at `idx` if the node is a sequence.
+    #[must_use]
+    pub fn at(&self, idx: usize) -> Option<NodeView<'doc>> {
+        match &self.node().data {
+            ComposedData::Sequence(items) => items.get(idx).map(|&id| self.document.view_of(id)),
+            _ => None,
+        }
+    }
+
+    /// Return the value for the string key `key` if the node is a mapping.
+    ///
+    /// If the key appears more than once, the last value is returned, as when loading into
+    /// [`Yaml`].
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<NodeView<'doc>> {
+        self.entries()?
+            .filter(|(k, _)| k.as_str() == Some(key))
+            .last()
+            .map(|(_, v)| v)
+    }
+
+    /// Iterate over the items of the node if it is a sequence.
+    #[must_use]
+    pub fn items(&self) -> Option<impl Iterator<Item = NodeView<'doc>>> {
+        let document = self.document;
+        match &self.node().data {
+            ComposedData::Sequence(items) => {
+                Some(items.iter().map(move |&id| document.view_of(id)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Iterate over the entries of the node if it is a mapping, duplicates included.
+    #[must_use]
+    pub fn entries(&self) -> Option<impl Iterator<Item = (NodeView<'doc>, NodeView<'doc>)>> {
+        let document = self.document;
+        match &self.node().data {
+            ComposedData::Mapping(entries) => Some(
+                entries
+                    .iter()
+                    .map(move |&(k, v)| (document.view_of(k), document.view_of(v))),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Construct the subtree starting at the node as a [`Yaml`] node.
+    ///
+    /// This copies nodes aliases refer to, as loading does.
+    #[must_use]
+    pub fn to_yaml(&self) -> Yaml {
+        let mut loader = YamlLoader::default();
+        loader
+            .load_from_events(|loader| self.document.replay_from(self.id, loader))
+            .ok()
+            .and_then(|mut docs| docs.pop())
+            .unwrap_or(Yaml::BadValue)
+    }
+}
+
+/// A side table associating data with the nodes of a [`ComposedDocument`].
+///
+/// This allows annotating a document (e.g.: with the results of an analysis) without altering or
+/// rebuilding it. Nodes aliases refer to have a single [`NodeId`], and thus a single entry.
+///
+/// ```
+/// use saphyr::{compose_str, NodeMap};
+///
+/// let docs = compose_str("a: &a [1, 2]\nb: *a\nc: 3").unwrap();
+/// let doc = &docs[0];
+///
+/// // Count how many times each node is referred to.
+/// let mut references = NodeMap::new(doc);
+/// for (_, value) in doc.view().entries().unwrap() {
+///     *references.get_or_insert_with(value.id(), || 0) += 1;
+/// }
+/// let a = doc.view().get("a").unwrap().id();
+/// assert_eq!(references.get(a), Some(&2));
+/// assert_eq!(references.len(), 2);
+/// ```
+///
+/// The map is sized for the document it is created for. Using it with the [`NodeId`]s of another
+/// document is a logic error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeMap<T> {
+    /// The data associated with each node, indexed by [`NodeId::index`].
+    values: Vec<Option<T>>,
+    /// The number of nodes with data.
+    len: usize,
+}
+
+impl<T> NodeMap<T> {
+    /// Create an empty map for the nodes of `document`.
+    #[must_use]
+    pub fn new(document: &ComposedDocument) -> Self {
+        Self {
+            values: std::iter::repeat_with(|| None)
+                .take(document.nodes.len())
+                .collect(),
+            len: 0,
+        }
+    }
+
+    /// Associate `value` with the node `id`, returning the value it was previously associated
+    /// with, if any.
+    ///
+    /// # Panics
+    /// This function panics if `id` does not belong to the document of the map.
+    pub fn insert(&mut self, id: NodeId, value: T) -> Option<T> {
+        let previous = self.values[id.0].replace(value);
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous
+    }
+
+    /// Return the value associated with the node `id`, associating it with `default()` first if
+    /// there is none.
+    ///
+    /// # Panics
+    /// This function panics if `id` does not belong to the document of the map.
+    pub fn get_or_insert_with<F: FnOnce() -> T>(&mut self, id: NodeId, default: F) -> &mut T {
+        let slot = &mut self.values[id.0];
+        if slot.is_none() {
+            self.len += 1;
+        }
+        slot.get_or_insert_with(default)
+    }
+
+    /// Return the value associated with the node `id`, if any.
+    #[must_use]
+    pub fn get(&self, id: NodeId) -> Option<&T> {
+        self.values.get(id.0)?.as_ref()
+    }
+
+    /// Return the value associated with the node `id` mutably, if any.
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut T> {
+        self.values.get_mut(id.0)?.as_mut()
+    }
+
+    /// Remove the value associated with the node `id`, returning it.
+    pub fn remove(&mut self, id: NodeId) -> Option<T> {
+        let value = self.values.get_mut(id.0)?.take();
+        if value.is_some() {
+            self.len -= 1;
+        }
+        value
+    }
+
+    /// Return whether a value is associated with the node `id`.
+    #[must_use]
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Return the number of nodes with a value.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return whether no node has a value.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over the nodes with a value and their value, in the order of their [`NodeId`].
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &T)> {
+        self.values
+            .iter()
+            .enumerate()
+            .filter_map(|(index, value)| Some((NodeId(index), value.as_ref()?)))
+    }
+}
+
+/// The state of [`ComposedDocument::replay`].
+struct Replay<'a> {
+    /// The document being replayed.
+    document: &'a ComposedDocument,
+    /// The number of times each node is referred to.
+    references: Vec<usize>,
+    /// The anchor IDs given to nodes referred to more than once.
+    anchor_ids: HashMap<NodeId, usize>,
+    /// The next anchor ID to give.
+    next_anchor_id: usize,
+}
+
+impl Replay<'_> {
+    fn node<R: MarkedEventReceiver>(&mut self, id: NodeId, receiver: &mut R) {
+        let node = self.document.node(id);
+        if let Some(&anchor_id) = self.anchor_ids.get(&id) {
+            receiver.on_event(Event::Alias(anchor_id), node.marker);
+            return;
+        }
+        let anchor_id = if self.references[id.0] > 1 {
+            let anchor_id = self.next_anchor_id;
+            self.next_anchor_id += 1;
+            self.anchor_ids.insert(id, anchor_id);
+            anchor_id
+        } else {
+            0
+        };
+        let tag = node.tag.clone().map(|tag| saphyr_parser::Tag {
+            handle: tag.handle,
+            suffix: tag.suffix,
+        });
+
+        match &node.data {
+            ComposedData::Scalar { value, style } => {
+                receiver.on_event(
+                    Event::Scalar(value.clone(), *style, anchor_id, tag),
+                    node.marker,
+                );
+            }
+            ComposedData::Sequence(items) => {
+                receiver.on_event(Event::SequenceStart(anchor_id, tag), node.marker);
+                for &item in items {
+                    self.node(item, receiver);
+                }
+                receiver.on_event(Event::SequenceEnd, node.marker);
+            }
+            ComposedData::Mapping(entries) => {
+                receiver.on_event(Event::MappingStart(anchor_id, tag), node.marker);
+                for &(key, value) in entries {
+                    self.node(key, receiver);
+                    self.node(value, receiver);
+                }
+                receiver.on_event(Event::MappingEnd, node.marker);
+            }
+        }
+    }
+}
+
+/// An error that happened when composing a YAML document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ComposeError {
+    /// An error within the scanner. This indicates a malformed YAML input.
+    Scan(ScanError),
+    /// An alias referred to an anchor which was not defined before it.
+    UnknownAnchor(Marker),
+}
+
+impl ComposeError {
+    /// Return the stable code of the error, which [`Display`] prefixes the message with.
+    ///
+    /// [`Display`]: std::fmt::Display
+    #[must_use]
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ComposeError::Scan(e) => ErrorCode::of_scan_error(e),
+            ComposeError::UnknownAnchor(_) => ErrorCode::new("COMPOSE001"),
+        }
+    }
+}
+
+impl std::error::Error for ComposeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ComposeError::Scan(e) => Some(e),
+            ComposeError::UnknownAnchor(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ComposeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.code())?;
+        match self {
+            ComposeError::Scan(e) => e.fmt(f),
+            ComposeError::UnknownAnchor(marker) => write!(
+                f,
+                "alias to an unknown anchor at line {} column {}",
+                marker.line(),
+                marker.col() + 1
+            ),
+        }
+    }
+}
+
+/// Compose the given string into an array of YAML documents.
+///
+/// # Errors
+/// Returns `ComposeError` when the input is not valid YAML.
+pub fn compose_str(source: &str) -> Result<Vec<ComposedDocument>, ComposeError> {
+    let composer = Composer {
+        anchor_names: scan_anchors(source.chars()),
+        ..Composer::default()
+    };
+    composer.compose(&mut Parser::new_from_str(source))
+}
+
+/// Compose the contents of the specified [`Parser`] into an array of YAML documents.
+///
+/// Anchor names are unknown when composing from a parser; [`ComposedNode::anchor`] is `None`.
+///
+/// # Errors
+/// Returns `ComposeError` when the input is not valid YAML.
+pub fn compose_from_parser<I: Iterator<Item = char>>(
+    parser: &mut Parser<I>,
+) -> Result<Vec<ComposedDocument>, ComposeError> {
+    Composer::default().compose(parser)
+}
+
+/// The receiver of events building [`ComposedDocument`]s.
+#[derive(Default)]
+struct Composer {
+    /// The anchors of the stream, indexed by their ID minus 1.
+    anchor_names: Vec<AnchorDefinition>,
+    /// The documents composed so far.
+    documents: Vec<ComposedDocument>,
+    /// The nodes of the current document.
+    nodes: Vec<ComposedNode>,
+    /// The collections being composed.
+    stack: Vec<NodeId>,
+    /// For each mapping in `stack`, the key waiting for its value, if any.
+    keys: Vec<Option<NodeId>>,
+    /// The nodes of the current document, indexed by anchor ID.
+    anchors: HashMap<usize, NodeId>,
+    /// The start of the current document.
+    start: Marker,
+    /// The first error that happened, if any.
+    error: Option<ComposeError>,
+}
+
+impl Composer {
+    fn compose<I: Iterator<Item = char>>(
+        mut self,
+        parser: &mut Parser<I>,
+    ) -> Result<Vec<ComposedDocument>, ComposeError> {
+        let result = parser.load(&mut self, true);
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        result.map_err(ComposeError::Scan)?;
+        Ok(self.documents)
+    }
+
+    /// Add a node to the current document and attach it to its parent, if any.
+    fn push_node(
+        &mut self,
+        data: ComposedData,
+        anchor_id: usize,
+        tag: Option<saphyr_parser::Tag>,
+        marker: Marker,
+    ) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(ComposedNode {
+            data,
+            tag: tag.map(Tag::from),
+            anchor: anchor_id
+                .checked_sub(1)
+                .and_then(|idx| self.anchor_names.get(idx))
+                .map(|definition| definition.name.clone()),
+            marker,
+        });
+        if anchor_id > 0 {
+            self.anchors.insert(anchor_id, id);
+        }
+        self.attach(id);
+        id
+    }
+
+    /// Attach the node `id` to the collection being composed, if any.
+    fn attach(&mut self, id: NodeId) {
+        let Some(&parent) = self.stack.last() else {
+            return;
+        };
+        // Only collections are pushed to `stack`, each along with the key of its entry.
+        match (&mut self.nodes[parent.0].data, self.keys.last_mut()) {
+            (ComposedData::Sequence(items), _) => items.push(id),
+            (ComposedData::Mapping(entries), Some(key)) => match key.take() {
+                Some(key) => entries.push((key, id)),
+                None => *key = Some(id),
+            },
+            (ComposedData::Mapping(_), None) | (ComposedData::Scalar { .. }, _) => {}
+        }
+    }
+}
+
+impl MarkedEventReceiver for Composer {
+    fn on_event(&mut self, ev: Event, marker: Marker) {
+        if self.error.is_some() {
+            return;
+        }
+        match ev {
+            Event::Nothing | Event::StreamStart | Event::StreamEnd => {}
+            Event::DocumentStart => {
+                self.nodes.clear();
+                self.anchors.clear();
+                self.start = marker;
+            }
+            Event::DocumentEnd => {
+                // The parser always emits a node within a document, even if it is empty.
+                if !self.nodes.is_empty() {
+                    self.documents.push(ComposedDocument {
+                        nodes: std::mem::take(&mut self.nodes),
+                        root: NodeId(0),
+                        start: self.start,
+                        end: marker,
+                    });
+                }
+            }
+            Event::Scalar(value, style, anchor_id, tag) => {
+                self.push_node(
+                    ComposedData::Scalar { value, style },
+                    anchor_id,
+                    tag,
+                    marker,
+                );
+            }
+            Event::SequenceStart(anchor_id, tag) => {
+                let id = self.push_node(ComposedData::Sequence(vec![]), anchor_id, tag, marker);
+                self.stack.push(id);
+                self.keys.push(None);
+            }
+            Event::MappingStart(anchor_id, tag) => {
+                let id = self.push_node(ComposedData::Mapping(vec![]), anchor_id, tag, marker);
+                self.stack.push(id);
+                self.keys.push(None);
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                self.stack.pop();
+                self.keys.pop();
+            }
+            Event::Alias(anchor_id) => match self.anchors.get(&anchor_id) {
+                Some(&id) => self.attach(id),
+                None => self.error = Some(ComposeError::UnknownAnchor(marker)),
+            },
+        }
+    }
+}
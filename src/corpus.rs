@@ -0,0 +1,231 @@
+//! Running corpora of YAML files through the loader and the emitter, to find regressions.
+
+use std::{
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+use saphyr_parser::ScanError;
+
+use crate::{EmitError, Yaml, YamlEmitter};
+
+/// What went wrong with a file of a corpus. See [`run_regression_corpus`].
+#[derive(Debug)]
+pub enum CorpusFailureKind {
+    /// Loading or emitting the file panicked. This holds the message of the panic.
+    Panic(String),
+    /// The documents of the file were emitted, but the emitted text fails to load.
+    Reload(ScanError),
+    /// The documents loaded back from the emitted text differ from those of the file. This holds
+    /// the emitted text.
+    Lossy(String),
+    /// The documents loaded back from the emitted text are not emitted as the same text.
+    Unstable {
+        /// The text the documents of the file were emitted as.
+        first: String,
+        /// The text the documents loaded back from `first` were emitted as.
+        second: String,
+    },
+}
+
+/// A file of a corpus which [`run_regression_corpus`] found a regression with.
+#[derive(Debug)]
+pub struct CorpusFailure {
+    /// The path of the file.
+    pub path: PathBuf,
+    /// What went wrong with it.
+    pub kind: CorpusFailureKind,
+}
+
+/// The outcome of [`run_regression_corpus`].
+#[derive(Debug, Default)]
+pub struct CorpusReport {
+    /// The number of files run through the loader.
+    pub files: usize,
+    /// The files which failed, in the order of their names.
+    pub failures: Vec<CorpusFailure>,
+}
+
+impl CorpusReport {
+    /// Return whether no file failed.
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for CorpusReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {} files failed", self.failures.len(), self.files)?;
+        for failure in &self.failures {
+            write!(f, "\n{}: ", failure.path.display())?;
+            match &failure.kind {
+                CorpusFailureKind::Panic(message) => write!(f, "panicked: {message}")?,
+                CorpusFailureKind::Reload(error) => {
+                    write!(f, "emitted text fails to load: {error}")?;
+                }
+                CorpusFailureKind::Lossy(first) => {
+                    write!(f, "emitted {first:?}, which loads back as other documents")?;
+                }
+                CorpusFailureKind::Unstable { first, second } => {
+                    write!(f, "emitted {first:?}, then {second:?}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run each file of the directory `dir` through the loader and the emitter, and report those
+/// which make them misbehave.
+///
+/// This is meant for projects keeping a corpus of YAML files found by fuzzing, or which once
+/// broke something, to check in their own CI that this crate still handles them. Each file is:
+///   * loaded, which must not panic, whether it succeeds or not;
+///   * if it loads, emitted, which must not panic either;
+///   * if it is emitted, loaded back from the emitted text, which must succeed and give the same
+///     documents, and emitted again, which must give the same text.
+///
+/// Files are not required to load: fuzzers find plenty of invalid documents, which must only be
+/// rejected without panicking. Likewise, documents which cannot be emitted (e.g.: with reals not
+/// representable in YAML) are only required not to panic. Invalid nodes (see [`Yaml::BadValue`])
+/// are written as nulls, so they may load back as such. Files which are not valid UTF-8 are
+/// loaded with their invalid sequences replaced. Subdirectories are not visited.
+///
+/// ```
+/// use saphyr::run_regression_corpus;
+///
+/// let dir = std::env::temp_dir().join(format!("saphyr-corpus-{}", std::process::id()));
+/// std::fs::create_dir_all(&dir).unwrap();
+/// std::fs::write(dir.join("crash-1"), "a: [b, {c: &x d}]\ne: *x").unwrap();
+/// std::fs::write(dir.join("crash-2"), "[[[{").unwrap();
+///
+/// let report = run_regression_corpus(&dir).unwrap();
+/// assert!(report.is_success(), "{report}");
+/// assert_eq!(report.files, 2);
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+///
+/// Panics are caught, so the panic hook still prints their message as they happen.
+///
+/// # Errors
+/// Returns an I/O error if `dir` or one of its files cannot be read.
+pub fn run_regression_corpus<P: AsRef<Path>>(dir: P) -> std::io::Result<CorpusReport> {
+    let mut paths = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+
+    let mut report = CorpusReport::default();
+    for path in paths {
+        let source = String::from_utf8_lossy(&std::fs::read(&path)?).into_owned();
+        report.files += 1;
+        let kind = match panic::catch_unwind(AssertUnwindSafe(|| check_round_trip(&source))) {
+            Ok(Ok(())) => continue,
+            Ok(Err(kind)) => kind,
+            Err(payload) => CorpusFailureKind::Panic(panic_message(payload.as_ref())),
+        };
+        report.failures.push(CorpusFailure { path, kind });
+    }
+    Ok(report)
+}
+
+/// Load `source`, emit it, and check the emitted text loads back and is emitted alike. See
+/// [`run_regression_corpus`].
+fn check_round_trip(source: &str) -> Result<(), CorpusFailureKind> {
+    let Ok(docs) = Yaml::load_from_str(source) else {
+        return Ok(());
+    };
+    let Ok(first) = emit_documents(&docs) else {
+        return Ok(());
+    };
+    let reloaded = Yaml::load_from_str(&first).map_err(CorpusFailureKind::Reload)?;
+    if docs.len() != reloaded.len()
+        || !docs
+            .iter()
+            .zip(&reloaded)
+            .all(|(doc, reloaded)| loads_back_as(doc, reloaded))
+    {
+        return Err(CorpusFailureKind::Lossy(first));
+    }
+    match emit_documents(&reloaded) {
+        Ok(second) if second == first => Ok(()),
+        Ok(second) => Err(CorpusFailureKind::Unstable { first, second }),
+        Err(error) => Err(CorpusFailureKind::Unstable {
+            first,
+            second: format!("<{error}>"),
+        }),
+    }
+}
+
+/// Return whether `node`, once emitted, loading back as `reloaded` is lossless. Invalid nodes are
+/// emitted as nulls.
+fn loads_back_as(node: &Yaml, reloaded: &Yaml) -> bool {
+    match (node, reloaded) {
+        (Yaml::BadValue, Yaml::Null) => true,
+        (Yaml::Array(items), Yaml::Array(reloaded)) => {
+            items.len() == reloaded.len()
+                && items
+                    .iter()
+                    .zip(reloaded)
+                    .all(|(item, reloaded)| loads_back_as(item, reloaded))
+        }
+        (Yaml::Hash(entries), Yaml::Hash(reloaded)) => {
+            entries.len() == reloaded.len()
+                && entries.iter().zip(reloaded).all(|(entry, reloaded)| {
+                    loads_back_as(entry.0, reloaded.0) && loads_back_as(entry.1, reloaded.1)
+                })
+        }
+        (Yaml::Tagged(tag, node), Yaml::Tagged(reloaded_tag, reloaded)) => {
+            tag == reloaded_tag && loads_back_as(node, reloaded)
+        }
+        _ => node == reloaded,
+    }
+}
+
+/// Emit `docs` one after the other.
+fn emit_documents(docs: &[Yaml]) -> Result<String, EmitError> {
+    let mut output = String::new();
+    for doc in docs {
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        YamlEmitter::new(&mut output).dump(doc)?;
+    }
+    Ok(output)
+}
+
+/// Return the message of a caught panic, if it has one.
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_owned()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "<no message>".to_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_round_trip, loads_back_as};
+    use crate::Yaml;
+
+    #[test]
+    fn test_lossy_round_trip() {
+        // Invalid nodes and empty documents load back as nulls.
+        assert!(check_round_trip("a: !!int foo\n---\n---\n[b]").is_ok());
+        assert!(loads_back_as(&Yaml::BadValue, &Yaml::Null));
+
+        // A string loading back as an integer is lossy, even though it is emitted alike.
+        assert!(!loads_back_as(
+            &Yaml::Array(vec![Yaml::String("1".into())]),
+            &Yaml::Array(vec![Yaml::Integer(1)]),
+        ));
+    }
+}
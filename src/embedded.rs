@@ -0,0 +1,247 @@
+//! Parsing of YAML documents embedded in strings of other documents.
+
+use saphyr_parser::Marker;
+
+use crate::{
+    ErrorCode, LoadError, LookupError, LookupErrorKind, MarkedYaml, SourceMap, TypeError, Yaml,
+    YamlData, YamlLoader,
+};
+
+impl Yaml {
+    /// Parse the string at `path` as a stream of YAML documents.
+    ///
+    /// Documents are often embedded in strings of other documents (e.g.: the files of a
+    /// Kubernetes `ConfigMap`). `path` is looked up as with [`Self::lookup`]. The tag of the
+    /// string, if any, is ignored.
+    ///
+    /// ```
+    /// use saphyr::Yaml;
+    ///
+    /// let docs = Yaml::load_from_str("\
+    /// kind: ConfigMap
+    /// data:
+    ///   config.yaml: |
+    ///     port: 8080
+    /// ").unwrap();
+    /// let embedded = docs[0].parse_embedded(&["data", "config.yaml"]).unwrap();
+    /// assert_eq!(embedded[0]["port"].as_i64(), Some(8080));
+    /// ```
+    ///
+    /// # Errors
+    /// Returns [`EmbeddedError`] if `path` cannot be looked up, is not a string, or if the string
+    /// is not valid YAML.
+    pub fn parse_embedded(&self, path: &[&str]) -> Result<Vec<Yaml>, EmbeddedError> {
+        let node = self.lookup(path).map_err(EmbeddedError::Lookup)?;
+        let text = node
+            .untagged()
+            .as_str()
+            .ok_or(EmbeddedError::NotAString(TypeError {
+                expected: "string",
+                found: node.type_name(),
+            }))?;
+        YamlLoader::default()
+            .load_from_str(text)
+            .map_err(EmbeddedError::Load)
+    }
+}
+
+impl MarkedYaml {
+    /// Parse the string at `path` as a stream of YAML documents, with markers pointing into
+    /// `source`, the text `self` was loaded from, where possible.
+    ///
+    /// See [`Yaml::parse_embedded`]. Markers of the embedded nodes and errors point into `source`
+    /// if the string is written exactly as its value in `source`: in a literal block scalar, or
+    /// on a single line, without escapes. Otherwise (e.g.: in a folded block scalar), they are
+    /// relative to the start of the string.
+    ///
+    /// ```
+    /// use saphyr::MarkedYaml;
+    ///
+    /// let source = "\
+    /// data:
+    ///   config.yaml: |
+    ///     server:
+    ///       port: 8080
+    /// ";
+    /// let docs = MarkedYaml::load_from_str(source).unwrap();
+    /// let embedded = docs[0].parse_embedded(&["data", "config.yaml"], source).unwrap();
+    /// let port = &embedded[0].data["server"].data["port"];
+    /// assert_eq!((port.marker.line(), port.marker.col()), (4, 12));
+    /// ```
+    ///
+    /// # Errors
+    /// Returns [`EmbeddedError`] if `path` cannot be looked up, is not a string, or if the string
+    /// is not valid YAML.
+    pub fn parse_embedded(
+        &self,
+        path: &[&str],
+        source: &str,
+    ) -> Result<Vec<MarkedYaml>, EmbeddedError> {
+        let node = lookup(self, path)?;
+        let mut untagged = node;
+        while let YamlData::Tagged(_, inner) = &untagged.data {
+            untagged = inner;
+        }
+        let YamlData::String(text) = &untagged.data else {
+            return Err(EmbeddedError::NotAString(TypeError {
+                expected: "string",
+                found: type_name(&node.data),
+            }));
+        };
+        let mut loader = YamlLoader::default();
+        if let Some(source_map) = source_map(text, untagged.marker, source) {
+            loader.source_map(source_map);
+        }
+        loader.load_from_str(text).map_err(EmbeddedError::Load)
+    }
+}
+
+/// The reason [`Yaml::parse_embedded`] or [`MarkedYaml::parse_embedded`] failed.
+#[derive(Debug)]
+pub enum EmbeddedError {
+    /// The path could not be looked up.
+    Lookup(LookupError),
+    /// The node at the path is not a string.
+    NotAString(TypeError),
+    /// The string is not valid YAML.
+    Load(LoadError),
+}
+
+impl EmbeddedError {
+    /// Return the stable code of the error, which [`Display`] prefixes the message with. This is
+    /// the code of the wrapped error.
+    ///
+    /// [`Display`]: std::fmt::Display
+    #[must_use]
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            EmbeddedError::Lookup(error) => error.code(),
+            EmbeddedError::NotAString(error) => error.code(),
+            EmbeddedError::Load(error) => error.code(),
+        }
+    }
+}
+
+impl std::error::Error for EmbeddedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(match self {
+            EmbeddedError::Lookup(error) => error,
+            EmbeddedError::NotAString(error) => error,
+            EmbeddedError::Load(error) => error,
+        })
+    }
+}
+
+impl std::fmt::Display for EmbeddedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EmbeddedError::Lookup(error) => error.fmt(f),
+            EmbeddedError::NotAString(error) => error.fmt(f),
+            EmbeddedError::Load(error) => error.fmt(f),
+        }
+    }
+}
+
+/// Return the node at `path` within `node`, as [`Yaml::lookup`] does.
+fn lookup<'a>(mut node: &'a MarkedYaml, path: &[&str]) -> Result<&'a MarkedYaml, EmbeddedError> {
+    for (depth, segment) in path.iter().enumerate() {
+        let error = |kind| {
+            EmbeddedError::Lookup(LookupError {
+                path: path[..depth].iter().map(ToString::to_string).collect(),
+                segment: (*segment).to_string(),
+                kind,
+            })
+        };
+        while let YamlData::Tagged(_, inner) = &node.data {
+            node = inner;
+        }
+        let index = segment.parse::<usize>().ok();
+        node = match (&node.data, index) {
+            (YamlData::Hash(hash), _) => hash
+                .iter()
+                .find(|(key, _)| matches!(&key.data, YamlData::String(key) if key == segment))
+                .or_else(|| {
+                    let segment = segment.parse().ok()?;
+                    hash.iter()
+                        .find(|(key, _)| key.data == YamlData::Integer(segment))
+                })
+                .map(|(_, value)| value)
+                .ok_or_else(|| error(LookupErrorKind::MissingKey))?,
+            (YamlData::Array(items), Some(index)) => items
+                .get(index)
+                .ok_or_else(|| error(LookupErrorKind::IndexOutOfRange(items.len())))?,
+            (found, index) => {
+                return Err(error(LookupErrorKind::TypeMismatch {
+                    expected: if index.is_some() {
+                        "sequence"
+                    } else {
+                        "mapping"
+                    },
+                    found: type_name(found),
+                }))
+            }
+        };
+    }
+    Ok(node)
+}
+
+/// Return the name of the type of `data`, as [`Yaml::type_name`] does.
+fn type_name(data: &YamlData<MarkedYaml>) -> &'static str {
+    match data {
+        YamlData::Real(_) => "real",
+        YamlData::Integer(_) => "integer",
+        YamlData::String(_) => "string",
+        YamlData::Boolean(_) => "boolean",
+        YamlData::Array(_) => "sequence",
+        YamlData::Hash(_) => "mapping",
+        YamlData::Tagged(_, node) => type_name(&node.data),
+        YamlData::Alias(_) => "alias",
+        YamlData::Null => "null",
+        YamlData::BadValue => "bad value",
+    }
+}
+
+/// Return the map from positions in `text`, the value of the string at `marker` in `source`, to
+/// positions in `source`, if `text` is written as it is there.
+///
+/// The markers of block scalars point to the start of their contents, and those of quoted
+/// scalars to their opening quote.
+fn source_map(text: &str, marker: Marker, source: &str) -> Option<SourceMap> {
+    let lines: Vec<&str> = source.split('\n').collect();
+    let start_line = marker.line().checked_sub(1)?;
+    let mut map = SourceMap::new();
+    if !text.contains('\n') {
+        let line = lines.get(start_line)?;
+        let rest: String = line.chars().skip(marker.col()).collect();
+        let quoted = rest.starts_with(['\'', '"']);
+        let rest = if quoted { &rest[1..] } else { &rest[..] };
+        if !rest.starts_with(text) {
+            return None;
+        }
+        let shift = usize::from(quoted);
+        map.add(
+            Marker::new(0, 1, 0),
+            Marker::new(marker.index() + shift, marker.line(), marker.col() + shift),
+        );
+        return Some(map);
+    }
+
+    // A literal block scalar, whose lines are all indented by the column of its contents.
+    let mut line_start = marker.index() - marker.col();
+    let mut index = 0;
+    let contents = text.strip_suffix('\n').unwrap_or(text);
+    for (i, line) in contents.split('\n').enumerate() {
+        let outer = lines.get(start_line + i)?;
+        let written: String = outer.chars().skip(marker.col()).collect();
+        if written != line && !(line.is_empty() && outer.trim().is_empty()) {
+            return None;
+        }
+        map.add(
+            Marker::new(index, i + 1, 0),
+            Marker::new(line_start + marker.col(), marker.line() + i, marker.col()),
+        );
+        index += line.chars().count() + 1;
+        line_start += outer.chars().count() + 1;
+    }
+    Some(map)
+}
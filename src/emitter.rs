@@ -1,16 +1,74 @@
 //! YAML serialization helpers.
 
-use crate::char_traits;
-use crate::yaml::{Hash, Yaml};
+use crate::loader::resolve_scalar;
+use crate::scalar::{select_scalar_style, Scalar};
+use crate::schema::{core_float, core_int, yaml11_bool, yaml11_float, yaml11_int};
+use crate::tag::CORE_SCHEMA_HANDLE;
+use crate::yaml::{child_mut, key_path, key_text, untagged_mut, Hash, Yaml};
+use crate::{
+    char_traits, BoolLexemes, CommentPlacement, Comments, ErrorCode, LookupError, LookupErrorKind,
+    Tag,
+};
+use saphyr_parser::TScalarStyle;
 use std::convert::From;
 use std::error::Error;
 use std::fmt::{self, Display};
 
 /// An error when emitting YAML.
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum EmitError {
     /// A formatting error.
     FmtError(fmt::Error),
+    /// A node of the document cannot be represented in YAML.
+    Unrepresentable {
+        /// The path from the root of the document to the node. Each segment is the key of a
+        /// mapping or the index of an item of a sequence.
+        path: Vec<String>,
+        /// Why the node cannot be represented.
+        reason: &'static str,
+    },
+    /// An emitted document is larger than the limit of its output. See [`SplitWriter`].
+    ///
+    /// [`SplitWriter`]: crate::SplitWriter
+    TooLarge {
+        /// The size of the document, in bytes.
+        size: usize,
+        /// The maximum size of the output, in bytes.
+        max: usize,
+    },
+    /// The version pinned with [`YamlEmitter::yaml_directive`] is neither 1.1 nor 1.2.
+    UnsupportedVersion {
+        /// The major version.
+        major: u32,
+        /// The minor version.
+        minor: u32,
+    },
+    /// The path of an override given to [`YamlEmitter::dump_with_overrides`] does not lead to a
+    /// node, nor to a missing key of a mapping.
+    InvalidOverride(LookupError),
+}
+
+impl EmitError {
+    /// Return the stable code of the error, which [`Display`] prefixes the message with.
+    #[must_use]
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            EmitError::FmtError(_) => ErrorCode::new("EMIT001"),
+            EmitError::Unrepresentable { .. } => ErrorCode::new("EMIT002"),
+            EmitError::TooLarge { .. } => ErrorCode::new("EMIT003"),
+            EmitError::UnsupportedVersion { .. } => ErrorCode::new("EMIT004"),
+            EmitError::InvalidOverride(_) => ErrorCode::new("EMIT005"),
+        }
+    }
+
+    /// Prepend `segment` to the path of the node the error is about, if any.
+    fn within(mut self, segment: impl FnOnce() -> String) -> Self {
+        if let EmitError::Unrepresentable { path, .. } = &mut self {
+            path.insert(0, segment());
+        }
+        self
+    }
 }
 
 impl Error for EmitError {
@@ -21,8 +79,30 @@ impl Error for EmitError {
 
 impl Display for EmitError {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}: ", self.code())?;
         match *self {
             EmitError::FmtError(ref err) => Display::fmt(err, formatter),
+            EmitError::Unrepresentable { ref path, reason } if path.is_empty() => {
+                write!(formatter, "cannot emit the root node: {reason}")
+            }
+            EmitError::Unrepresentable { ref path, reason } => {
+                write!(
+                    formatter,
+                    "cannot emit the node at `{}`: {reason}",
+                    path.join(".")
+                )
+            }
+            EmitError::TooLarge { size, max } => write!(
+                formatter,
+                "document of {size} bytes exceeds the limit of {max} bytes"
+            ),
+            EmitError::UnsupportedVersion { major, minor } => write!(
+                formatter,
+                "cannot emit YAML {major}.{minor}, only YAML 1.1 and 1.2 are supported"
+            ),
+            EmitError::InvalidOverride(ref error) => {
+                write!(formatter, "cannot apply an override: {error}")
+            }
         }
     }
 }
@@ -47,20 +127,147 @@ impl From<fmt::Error> for EmitError {
 /// a: b
 /// c: d"#);
 /// ```
-#[allow(clippy::module_name_repetitions)]
+#[allow(clippy::module_name_repetitions, clippy::struct_excessive_bools)]
 pub struct YamlEmitter<'a> {
     writer: &'a mut dyn fmt::Write,
     best_indent: usize,
     compact: bool,
     level: isize,
     multiline_strings: bool,
+    quote_style: QuoteStyle,
+    quote_all_strings: bool,
+    quote_non_string_keys: bool,
+    null_style: NullStyle,
+    indent_sequences: bool,
+    yaml_directive: Option<(u32, u32)>,
+    bool_lexemes: Option<&'a BoolLexemes>,
+    auto_style: Option<AutoStyle>,
+    tag_directives: bool,
+    /// The prefix of tags of the current document a `%TAG` directive shortens, if any, with the
+    /// name of its handle (without `!`).
+    tag_handle: Option<(String, String)>,
+    /// Whether a document was written, which must be ended before the next one starts.
+    wrote_document: bool,
+    comments: Option<&'a Comments>,
+    /// The trailing comment to write at the end of the current line.
+    pending_comment: Option<&'a str>,
+    /// The path of the node being emitted, tracked only with `bool_lexemes` or `comments`. `None`
+    /// within keys.
+    path: Option<String>,
+}
+
+/// The quotes the [`YamlEmitter`] writes strings in when they are quoted.
+///
+/// See [`YamlEmitter::quote_style`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum QuoteStyle {
+    /// Write strings in double quotes, e.g. `"it's"`.
+    #[default]
+    Double,
+    /// Write strings in single quotes when they can be, e.g. `'it''s'`.
+    ///
+    /// Strings which cannot (i.e. containing line breaks or control characters) are written in
+    /// double quotes, where they can be escaped.
+    Single,
+}
+
+/// How the [`YamlEmitter`] writes nulls.
+///
+/// See [`YamlEmitter::null_style`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum NullStyle {
+    /// Write nulls as `~`.
+    #[default]
+    Tilde,
+    /// Write nulls as `null`.
+    Null,
+}
+
+/// Sets of [`YamlEmitter`] options writing files as they are usually written for a tool.
+///
+/// See [`YamlEmitter::profile`]. All profiles write booleans as `true` or `false`, nulls as
+/// `null`, strings of multiple lines in literal style, no `%YAML` directive, and collections in
+/// block style, indented by two spaces. They differ as follows:
+///
+/// | Profile        | Quotes | Sequences within mappings | Keys which are not strings |
+/// |----------------|--------|---------------------------|----------------------------|
+/// | GitHub Actions | single | indented                  | as they are                |
+/// | Kubernetes     | double | not indented              | quoted                     |
+/// | Ansible        | double | indented                  | as they are                |
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EmitProfile {
+    /// Workflow files of GitHub Actions, whose examples quote strings in single quotes (e.g.:
+    /// `python-version: '3.10'`).
+    GitHubActions,
+    /// Manifests of Kubernetes, whose labels, annotations and data only accept string keys.
+    Kubernetes,
+    /// Playbooks and inventories of Ansible, whose linter only accepts `true` and `false` as
+    /// booleans.
+    Ansible,
+}
+
+/// The thresholds under which the [`YamlEmitter`] writes a collection in flow style, e.g.
+/// `[80, 443]`, rather than in block style.
+///
+/// See [`YamlEmitter::auto_style`]. A collection is written in flow style if it meets all of
+/// them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AutoStyle {
+    /// The maximal number of items (or entries, for mappings) of the collection. Defaults to 8.
+    pub max_items: usize,
+    /// The maximal depth of the collections within the collection, which are written in flow
+    /// style along with it. Defaults to 0: only collections of scalars are written in flow style.
+    pub max_depth: usize,
+    /// The maximal length, in `char`s, of the scalars within the collection. Defaults to 20.
+    pub max_scalar_len: usize,
+    /// The maximal length, in `char`s, of the collection written in flow style. Defaults to 60.
+    pub max_width: usize,
+}
+
+impl Default for AutoStyle {
+    fn default() -> Self {
+        Self {
+            max_items: 8,
+            max_depth: 0,
+            max_scalar_len: 20,
+            max_width: 60,
+        }
+    }
+}
+
+impl AutoStyle {
+    /// Return whether the shape of `node` allows writing it in flow style, `depth` levels within
+    /// the collection being considered.
+    fn fits(&self, node: &Yaml, depth: usize) -> bool {
+        match node {
+            Yaml::Array(v) => {
+                depth <= self.max_depth
+                    && v.len() <= self.max_items
+                    && v.iter().all(|x| self.fits(x, depth + 1))
+            }
+            Yaml::Hash(h) => {
+                depth <= self.max_depth
+                    && h.len() <= self.max_items
+                    && h.iter().all(|(k, v)| {
+                        !matches!(k, Yaml::Array(_) | Yaml::Hash(_))
+                            && self.fits(k, depth + 1)
+                            && self.fits(v, depth + 1)
+                    })
+            }
+            Yaml::String(v) => !v.contains('\n') && v.chars().count() <= self.max_scalar_len,
+            Yaml::Real(v) => v.chars().count() <= self.max_scalar_len,
+            // Tags and aliases are left to block style, which writes or rejects them.
+            Yaml::Tagged(..) | Yaml::Alias(_) => false,
+            Yaml::Integer(_) | Yaml::Boolean(_) | Yaml::Null | Yaml::BadValue => true,
+        }
+    }
 }
 
 /// A convenience alias for emitter functions that may fail without returning a value.
 pub type EmitResult = Result<(), EmitError>;
 
 // from serialize::json
-fn escape_str(wr: &mut dyn fmt::Write, v: &str) -> Result<(), fmt::Error> {
+pub(crate) fn escape_str(wr: &mut dyn fmt::Write, v: &str) -> Result<(), fmt::Error> {
     wr.write_str("\"")?;
 
     let mut start = 0;
@@ -124,13 +331,27 @@ fn escape_str(wr: &mut dyn fmt::Write, v: &str) -> Result<(), fmt::Error> {
 
 impl<'a> YamlEmitter<'a> {
     /// Create a new emitter serializing into `writer`.
-    pub fn new(writer: &'a mut dyn fmt::Write) -> YamlEmitter {
+    pub fn new(writer: &'a mut dyn fmt::Write) -> YamlEmitter<'a> {
         YamlEmitter {
             writer,
             best_indent: 2,
             compact: true,
             level: -1,
             multiline_strings: false,
+            quote_style: QuoteStyle::Double,
+            quote_all_strings: false,
+            quote_non_string_keys: false,
+            null_style: NullStyle::Tilde,
+            indent_sequences: true,
+            yaml_directive: None,
+            bool_lexemes: None,
+            auto_style: None,
+            tag_directives: false,
+            tag_handle: None,
+            wrote_document: false,
+            comments: None,
+            pending_comment: None,
+            path: None,
         }
     }
 
@@ -156,6 +377,10 @@ impl<'a> YamlEmitter<'a> {
 
     /// Render strings containing multiple lines in [literal style].
     ///
+    /// Keys, which must fit on a single line, and strings a literal block scalar cannot hold as
+    /// they are (e.g.: starting with a space or ending with several line breaks) are quoted
+    /// instead.
+    ///
     /// # Examples
     ///
     /// ```rust
@@ -171,7 +396,7 @@ impl<'a> YamlEmitter<'a> {
     /// emitter.dump(&parsed[0]).unwrap();
     /// assert_eq!(output.as_str(), "\
     /// ---
-    /// foo: |
+    /// foo: |-
     ///   bar!
     ///   bar!
     /// baz: 42");
@@ -188,14 +413,614 @@ impl<'a> YamlEmitter<'a> {
         self.multiline_strings
     }
 
+    /// Set the quotes strings which need quoting are written in (defaults to
+    /// [`QuoteStyle::Double`]).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use saphyr::{QuoteStyle, Yaml, YamlEmitter};
+    ///
+    /// let parsed = Yaml::load_from_str(r#"[plain, "it's: quoted", "line\nbreak"]"#).unwrap();
+    ///
+    /// let mut output = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.quote_style(QuoteStyle::Single);
+    /// emitter.dump(&parsed[0]).unwrap();
+    /// assert_eq!(output.as_str(), r#"---
+    /// - plain
+    /// - 'it''s: quoted'
+    /// - "line\nbreak""#);
+    /// ```
+    pub fn quote_style(&mut self, quote_style: QuoteStyle) {
+        self.quote_style = quote_style;
+    }
+
+    /// Determine the quotes this emitter writes strings in.
+    #[must_use]
+    pub fn get_quote_style(&self) -> QuoteStyle {
+        self.quote_style
+    }
+
+    /// Quote all strings, including those which could be written as plain scalars.
+    ///
+    /// This is meant for style guides requiring strings to be quoted, so that they are told apart
+    /// from other scalars at a glance. Strings written in literal style (see
+    /// [`Self::multiline_strings`]) are not quoted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use saphyr::{QuoteStyle, Yaml, YamlEmitter};
+    ///
+    /// let parsed = Yaml::load_from_str("{name: saphyr, stars: 42}").unwrap();
+    ///
+    /// let mut output = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.quote_all_strings(true);
+    /// emitter.quote_style(QuoteStyle::Single);
+    /// emitter.dump(&parsed[0]).unwrap();
+    /// assert_eq!(output.as_str(), "---\n'name': 'saphyr'\n'stars': 42");
+    /// ```
+    pub fn quote_all_strings(&mut self, quote_all_strings: bool) {
+        self.quote_all_strings = quote_all_strings;
+    }
+
+    /// Determine if this emitter quotes all strings.
+    #[must_use]
+    pub fn is_quote_all_strings(&self) -> bool {
+        self.quote_all_strings
+    }
+
+    /// Write keys of mappings which are integers, reals, booleans or nulls as quoted strings
+    /// (e.g.: `"8080":` rather than `8080:`).
+    ///
+    /// This is meant for documents which go through systems requiring string keys, such as JSON
+    /// ones: the keys then load back as strings, with the text they were written with. Null keys
+    /// are written `"null"`. Tagged keys are written as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use saphyr::{Yaml, YamlEmitter};
+    ///
+    /// let parsed = Yaml::load_from_str("{8080: http, true: tls, name: web}").unwrap();
+    ///
+    /// let mut output = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.quote_non_string_keys(true);
+    /// emitter.dump(&parsed[0]).unwrap();
+    /// assert_eq!(output.as_str(), "---\n\"8080\": http\n\"true\": tls\nname: web");
+    /// ```
+    pub fn quote_non_string_keys(&mut self, quote_non_string_keys: bool) {
+        self.quote_non_string_keys = quote_non_string_keys;
+    }
+
+    /// Determine if this emitter quotes keys which are not strings.
+    #[must_use]
+    pub fn is_quote_non_string_keys(&self) -> bool {
+        self.quote_non_string_keys
+    }
+
+    /// Set how nulls are written (defaults to [`NullStyle::Tilde`]).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use saphyr::{NullStyle, Yaml, YamlEmitter};
+    ///
+    /// let parsed = Yaml::load_from_str("{proxy: ~, ports: [null]}").unwrap();
+    ///
+    /// let mut output = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.null_style(NullStyle::Null);
+    /// emitter.dump(&parsed[0]).unwrap();
+    /// assert_eq!(output.as_str(), "---\nproxy: null\nports:\n  - null");
+    /// ```
+    pub fn null_style(&mut self, null_style: NullStyle) {
+        self.null_style = null_style;
+    }
+
+    /// Determine how this emitter writes nulls.
+    #[must_use]
+    pub fn get_null_style(&self) -> NullStyle {
+        self.null_style
+    }
+
+    /// Indent block sequences which are values of mappings one level deeper than their key
+    /// (defaults to `true`).
+    ///
+    /// When unset, their items start at the column of their key instead, as `kubectl` and other
+    /// tools written in Go write them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use saphyr::{Yaml, YamlEmitter};
+    ///
+    /// let parsed = Yaml::load_from_str("containers: [{name: web, ports: [80]}]").unwrap();
+    ///
+    /// let mut output = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.indent_sequences(false);
+    /// emitter.dump(&parsed[0]).unwrap();
+    /// assert_eq!(output.as_str(), "---\ncontainers:\n- name: web\n  ports:\n  - 80");
+    /// ```
+    pub fn indent_sequences(&mut self, indent_sequences: bool) {
+        self.indent_sequences = indent_sequences;
+    }
+
+    /// Determine if this emitter indents block sequences which are values of mappings.
+    #[must_use]
+    pub fn is_indent_sequences(&self) -> bool {
+        self.indent_sequences
+    }
+
+    /// Set the options of this emitter to write files as they are usually written for a tool.
+    /// See [`EmitProfile`] for the options each profile sets.
+    ///
+    /// This replaces the options the profile covers, dropping the [`Self::bool_lexemes`] and
+    /// [`Self::auto_style`] set before, if any. Set options after the profile to override it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use saphyr::{EmitProfile, Yaml, YamlEmitter};
+    ///
+    /// let parsed = Yaml::load_from_str("\
+    /// on: push
+    /// jobs:
+    ///   test:
+    ///     steps:
+    ///       - uses: actions/setup-python@v5
+    ///         with: {python-version: '3.10', cache: null}
+    ///       - run: \"pip install .\\npytest\\n\"
+    /// ").unwrap();
+    ///
+    /// let mut output = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.profile(EmitProfile::GitHubActions);
+    /// emitter.dump(&parsed[0]).unwrap();
+    /// assert_eq!(output.as_str(), "\
+    /// ---
+    /// 'on': push
+    /// jobs:
+    ///   test:
+    ///     steps:
+    ///       - uses: actions/setup-python@v5
+    ///         with:
+    ///           python-version: '3.10'
+    ///           cache: null
+    ///       - run: |
+    ///           pip install .
+    ///           pytest");
+    /// ```
+    pub fn profile(&mut self, profile: EmitProfile) {
+        self.compact = true;
+        self.multiline_strings = true;
+        self.quote_all_strings = false;
+        self.null_style = NullStyle::Null;
+        self.yaml_directive = None;
+        self.bool_lexemes = None;
+        self.auto_style = None;
+        (
+            self.quote_style,
+            self.indent_sequences,
+            self.quote_non_string_keys,
+        ) = match profile {
+            EmitProfile::GitHubActions => (QuoteStyle::Single, true, false),
+            EmitProfile::Kubernetes => (QuoteStyle::Double, false, true),
+            EmitProfile::Ansible => (QuoteStyle::Double, true, false),
+        };
+    }
+
+    /// Write booleans with the lexemes they were loaded from (e.g.: `yes` or `Off`), rather than
+    /// `true` or `false`. See [`BoolLexemes`].
+    ///
+    /// Booleans whose path has no lexeme, or whose lexeme does not resolve to their value as per
+    /// [`Schema::Yaml11Booleans`], are written `true` or `false`. Booleans within keys are
+    /// always so.
+    ///
+    /// [`Schema::Yaml11Booleans`]: crate::Schema::Yaml11Booleans
+    pub fn bool_lexemes(&mut self, bool_lexemes: &'a BoolLexemes) {
+        self.bool_lexemes = Some(bool_lexemes);
+    }
+
+    /// Write the comments of documents back, relative to the nodes they are attached to. See
+    /// [`Comments`].
+    ///
+    /// Leading and standalone comments are written on their own lines before the key or item
+    /// they are attached to, standalone ones followed by a blank line. Trailing comments are
+    /// written at the end of the first line of their key or item. Comments attached to no node
+    /// are written after the document.
+    pub fn comments(&mut self, comments: &'a Comments) {
+        self.comments = Some(comments);
+    }
+
+    /// Pin the version of YAML documents are written in, with a `%YAML` directive before each of
+    /// them (defaults to `None`, writing no directive).
+    ///
+    /// Only versions 1.1 and 1.2 are supported. Emitted documents are then checked to load back
+    /// alike as per that version:
+    ///   * With YAML 1.1, strings which it resolves to other types (e.g.: `y`, `0b101` or
+    ///     `1_000`) are quoted.
+    ///   * Reals (see [`Yaml::Real`]) whose text is not a float of that version (e.g.: `inf` or
+    ///     `1e3` in YAML 1.1, `1_000.5` in YAML 1.2) fail with [`EmitError::Unrepresentable`],
+    ///     since they would not load back as reals.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use saphyr::{Yaml, YamlEmitter};
+    ///
+    /// let parsed = Yaml::load_from_str("[y, 0b101, 1.5]").unwrap();
+    ///
+    /// let mut output = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.yaml_directive(Some((1, 1)));
+    /// emitter.dump(&parsed[0]).unwrap();
+    /// assert_eq!(output.as_str(), "%YAML 1.1\n---\n- \"y\"\n- \"0b101\"\n- 1.5");
+    ///
+    /// let mut output = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.yaml_directive(Some((1, 2)));
+    /// assert!(emitter.dump(&Yaml::Real("inf".into())).is_err());
+    /// ```
+    pub fn yaml_directive(&mut self, version: Option<(u32, u32)>) {
+        self.yaml_directive = version;
+    }
+
+    /// Determine the version of YAML this emitter pins documents to, if any.
+    #[must_use]
+    pub fn get_yaml_directive(&self) -> Option<(u32, u32)> {
+        self.yaml_directive
+    }
+
+    /// Pick the style of each collection below the top level from its shape: write it in flow
+    /// style if it is under the given thresholds, in block style otherwise (defaults to `None`,
+    /// writing all non-empty collections in block style).
+    ///
+    /// This writes short lists of scalars on one line, as one usually would by hand, and keeps
+    /// long or nested collections in block style. Collections containing tags or strings of
+    /// multiple lines are always written in block style.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use saphyr::{AutoStyle, Yaml, YamlEmitter};
+    ///
+    /// let parsed = Yaml::load_from_str("\
+    /// ports: [80, 443]
+    /// env: {LEVEL: debug}
+    /// args: [serve, /etc/app/config.yaml, verbose]
+    /// ").unwrap();
+    ///
+    /// let mut output = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.auto_style(Some(AutoStyle { max_items: 2, ..AutoStyle::default() }));
+    /// emitter.dump(&parsed[0]).unwrap();
+    /// assert_eq!(
+    ///     output,
+    ///     "---\nports: [80, 443]\nenv: {LEVEL: debug}\nargs:\n  - serve\n  - /etc/app/config.yaml\n  - verbose",
+    /// );
+    /// ```
+    pub fn auto_style(&mut self, auto_style: Option<AutoStyle>) {
+        self.auto_style = auto_style;
+    }
+
+    /// Determine the thresholds under which this emitter writes collections in flow style, if
+    /// any.
+    #[must_use]
+    pub fn get_auto_style(&self) -> Option<AutoStyle> {
+        self.auto_style
+    }
+
+    /// Shorten the prefix of tags repeated within documents with a `%TAG` directive (defaults to
+    /// `false`, writing tags other than `!!` and `!` ones in verbatim form).
+    ///
+    /// The prefix shared by the most tags of a document (see [`Tag::handle`]), if several share
+    /// one, is given a named handle after its domain name, with a `%TAG` directive before the
+    /// document. Tags with that prefix are then written with the handle rather than in full.
+    /// Other tags are kept in verbatim form: the parser only keeps the last `%TAG` directive of a
+    /// document, so writing more would not load back.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use saphyr::{Tag, Yaml, YamlEmitter};
+    ///
+    /// let tagged = |suffix: &str| {
+    ///     let tag = Tag::new("tag:example.com,2024:", suffix);
+    ///     Yaml::Tagged(tag, Box::new(Yaml::Array(vec![Yaml::Integer(1)])))
+    /// };
+    /// let doc = Yaml::Array(vec![tagged("point"), tagged("vector")]);
+    ///
+    /// let mut output = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.tag_directives(true);
+    /// emitter.dump(&doc).unwrap();
+    /// assert_eq!(output, "\
+    /// %TAG !example! tag:example.com,2024:
+    /// ---
+    /// - !example!point
+    ///   - 1
+    /// - !example!vector
+    ///   - 1");
+    /// ```
+    ///
+    /// [`Tag::handle`]: crate::Tag::handle
+    pub fn tag_directives(&mut self, tag_directives: bool) {
+        self.tag_directives = tag_directives;
+    }
+
+    /// Determine if this emitter shortens repeated tag prefixes with a `%TAG` directive.
+    #[must_use]
+    pub fn is_tag_directives(&self) -> bool {
+        self.tag_directives
+    }
+
+    /// Write the `%YAML` and `%TAG` directives if any, and the start of a document.
+    ///
+    /// A `%TAG` directive is written for the most repeated tag prefix of `doc`, if given and if
+    /// [`Self::tag_directives`] is set. If a document was written before, it is ended first: with
+    /// a line break, and with a `...` marker if directives follow, since they would otherwise be
+    /// read as part of its contents.
+    fn write_document_start(&mut self, doc: Option<&Yaml>) -> EmitResult {
+        if let Some((major, minor)) = self.yaml_directive {
+            if (major, minor) != (1, 1) && (major, minor) != (1, 2) {
+                return Err(EmitError::UnsupportedVersion { major, minor });
+            }
+        }
+        self.tag_handle = doc.filter(|_| self.tag_directives).and_then(tag_handle);
+        if self.wrote_document {
+            self.write_newline()?;
+            if self.yaml_directive.is_some() || self.tag_handle.is_some() {
+                writeln!(self.writer, "...")?;
+            }
+        }
+        self.wrote_document = true;
+        if let Some((major, minor)) = self.yaml_directive {
+            writeln!(self.writer, "%YAML {major}.{minor}")?;
+        }
+        if let Some((prefix, name)) = &self.tag_handle {
+            writeln!(self.writer, "%TAG !{name}! {prefix}")?;
+        }
+        write!(self.writer, "---")?;
+        Ok(())
+    }
+
+    /// Return whether the pinned version of YAML resolves the plain scalar `v` to something else
+    /// than a string, which [`need_quotes`] does not tell.
+    fn need_version_quotes(&self, v: &str) -> bool {
+        self.yaml_directive == Some((1, 1))
+            && (matches!(v, "y" | "Y" | "n" | "N") || yaml11_int(v) || yaml11_float(v))
+    }
+
+    /// Return whether `v`, the text of a real, is a float as per the pinned version of YAML.
+    fn is_version_real(&self, v: &str) -> bool {
+        match self.yaml_directive {
+            Some((1, 1)) => !yaml11_int(v) && yaml11_float(v),
+            Some(_) => core_int(v).is_none() && core_float(v).is_some(),
+            None => true,
+        }
+    }
+
     /// Dump Yaml to an output stream.
     /// # Errors
     /// Returns `EmitError` when an error occurs.
     pub fn dump(&mut self, doc: &Yaml) -> EmitResult {
-        // write DocumentStart
-        writeln!(self.writer, "---")?;
+        self.write_document_start(Some(doc))?;
+        self.write_newline()?;
         self.level = -1;
-        self.emit_node(doc)
+        let track_paths = self.bool_lexemes.is_some() || self.comments.is_some();
+        self.path = track_paths.then(String::new);
+        self.write_comments(Some(""))?;
+        self.emit_node(doc)?;
+        if let Some(text) = self.pending_comment.take() {
+            write!(self.writer, " #{text}")?;
+        }
+        for comment in self
+            .comments
+            .into_iter()
+            .flat_map(|comments| comments.of(None))
+        {
+            write!(self.writer, "\n#{}", comment.text)?;
+        }
+        Ok(())
+    }
+
+    /// Dump Yaml to an output stream as if the nodes at the paths of `overrides` were replaced by
+    /// their values, leaving `doc` untouched.
+    ///
+    /// This is meant for previewing changes to a document (e.g.: rendering it with the values of
+    /// command-line flags, or showing a diff before saving it). Paths are made of segments, as
+    /// for [`Yaml::lookup`]. Overrides are applied in order, each to the document as the previous
+    /// ones left it. An override whose last segment is missing from a mapping adds it as a key,
+    /// after the others. An empty path overrides the whole document.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use saphyr::{Yaml, YamlEmitter};
+    ///
+    /// let doc = Yaml::load_from_str("server: {host: localhost, port: 8080}").unwrap().remove(0);
+    /// let overrides: [(&[&str], Yaml); 2] = [
+    ///     (&["server", "port"], Yaml::Integer(9090)),
+    ///     (&["debug"], Yaml::Boolean(true)),
+    /// ];
+    ///
+    /// let mut output = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.dump_with_overrides(&doc, &overrides).unwrap();
+    /// assert_eq!(output, "---\nserver:\n  host: localhost\n  port: 9090\ndebug: true");
+    /// assert_eq!(doc["server"]["port"].as_i64(), Some(8080));
+    /// ```
+    ///
+    /// # Errors
+    /// Returns [`EmitError::InvalidOverride`] if a path does not lead to a node nor to a missing
+    /// key of a mapping, and `EmitError` when an error occurs while emitting.
+    pub fn dump_with_overrides(&mut self, doc: &Yaml, overrides: &[(&[&str], Yaml)]) -> EmitResult {
+        let mut doc = doc.clone();
+        for (path, value) in overrides {
+            apply_override(&mut doc, path, value.clone()).map_err(EmitError::InvalidOverride)?;
+        }
+        self.dump(&doc)
+    }
+
+    /// Dump a document without contents to an output stream.
+    ///
+    /// Unlike dumping [`Yaml::Null`], this only writes the start of the document. It loads back as
+    /// [`Yaml::Null`], but is reported as empty by [`YamlLoader::documents`].
+    ///
+    /// ```
+    /// # use saphyr::YamlEmitter;
+    /// let mut output = String::new();
+    /// YamlEmitter::new(&mut output).dump_empty().unwrap();
+    /// assert_eq!(output, "---");
+    /// ```
+    ///
+    /// # Errors
+    /// Returns `EmitError` when an error occurs.
+    ///
+    /// [`YamlLoader::documents`]: crate::YamlLoader::documents
+    pub fn dump_empty(&mut self) -> EmitResult {
+        self.write_document_start(None)
+    }
+
+    /// Dump a document made of a single scalar to an output stream, in the style of the scalar.
+    ///
+    /// The tag of the scalar is written before it. Scalars which cannot be written in their
+    /// style (e.g.: a plain scalar starting with a space, or a single-quoted one containing a
+    /// control character) are written in double quotes instead. Folded scalars are written as
+    /// literal block scalars, which hold the same value.
+    ///
+    /// ```
+    /// use saphyr::{Scalar, TScalarStyle, YamlEmitter};
+    ///
+    /// let mut scalar = Scalar::new("line 1\nline 2");
+    /// assert_eq!(scalar.style, TScalarStyle::Literal);
+    /// let mut output = String::new();
+    /// YamlEmitter::new(&mut output).dump_scalar(&scalar).unwrap();
+    /// assert_eq!(output, "---\n|-\n  line 1\n  line 2");
+    ///
+    /// scalar.style = TScalarStyle::Plain;
+    /// output.clear();
+    /// YamlEmitter::new(&mut output).dump_scalar(&scalar).unwrap();
+    /// assert_eq!(output, "---\n\"line 1\\nline 2\"");
+    /// ```
+    ///
+    /// # Errors
+    /// Returns `EmitError` when an error occurs.
+    pub fn dump_scalar(&mut self, scalar: &Scalar) -> EmitResult {
+        self.write_document_start(None)?;
+        self.write_newline()?;
+        if let Some(tag) = &scalar.tag {
+            write!(self.writer, "{tag} ")?;
+        }
+        self.emit_scalar(&scalar.value, scalar.style)
+    }
+
+    /// Emit the text of a scalar in the given style, falling back to double quotes if it cannot
+    /// be written in that style. See [`Self::dump_scalar`].
+    fn emit_scalar(&mut self, v: &str, style: TScalarStyle) -> EmitResult {
+        let plain_text = || {
+            // Plain text which resolves to something else than a string is written as it is,
+            // e.g. the plain scalar `true`.
+            !need_quotes(v) || !matches!(resolve_scalar(v.to_owned(), style, None), Yaml::String(_))
+        };
+        match style {
+            TScalarStyle::Plain if !v.is_empty() && !v.contains('\n') && plain_text() => {
+                self.writer.write_str(v)?;
+            }
+            TScalarStyle::SingleQuoted if can_single_quote(v) => {
+                write!(self.writer, "'{}'", v.replace('\'', "''"))?;
+            }
+            TScalarStyle::Literal | TScalarStyle::Folded if can_write_literal(v) => {
+                self.emit_literal(v)?;
+            }
+            _ => escape_str(self.writer, v)?,
+        }
+        Ok(())
+    }
+
+    /// Emit a string as a literal block scalar, which [`can_write_literal`] must allow.
+    fn emit_literal(&mut self, v: &str) -> EmitResult {
+        // Clipping keeps a single final line break, stripping keeps none.
+        let (v, chomping) = match v.strip_suffix('\n') {
+            Some(v) => (v, ""),
+            None => (v, "-"),
+        };
+        write!(self.writer, "|{chomping}")?;
+        // Contents are indented even at the top level, where they could be mistaken for markers.
+        let level = self.level;
+        self.level = level.max(0) + 1;
+        for line in v.split('\n') {
+            self.write_newline()?;
+            if !line.is_empty() {
+                self.write_indent()?;
+            }
+            self.writer.write_str(line)?;
+        }
+        self.level = level;
+        Ok(())
+    }
+
+    /// Emit a string in the quotes set by [`Self::quote_style`], if it can be written in them.
+    fn emit_quoted(&mut self, v: &str) -> EmitResult {
+        if self.quote_style == QuoteStyle::Single && can_single_quote(v) {
+            write!(self.writer, "'{}'", v.replace('\'', "''"))?;
+        } else {
+            escape_str(self.writer, v)?;
+        }
+        Ok(())
+    }
+
+    /// End the current line, after its trailing comment if any.
+    fn write_newline(&mut self) -> EmitResult {
+        if let Some(text) = self.pending_comment.take() {
+            write!(self.writer, " #{text}")?;
+        }
+        writeln!(self.writer)?;
+        Ok(())
+    }
+
+    /// Write the leading and standalone comments of the node at `path`, which starts at the
+    /// current position, and keep its trailing comment for the end of the line.
+    fn write_comments(&mut self, path: Option<&str>) -> EmitResult {
+        let Some((comments, path)) = self.comments.zip(path) else {
+            return Ok(());
+        };
+        // The first line is already indented, and blank lines are not.
+        let mut indented = true;
+        for placement in [CommentPlacement::Standalone, CommentPlacement::Leading] {
+            let mut any = false;
+            for comment in comments
+                .of(Some(path))
+                .filter(|comment| comment.placement == placement)
+            {
+                if !indented {
+                    self.write_indent()?;
+                }
+                write!(self.writer, "#{}", comment.text)?;
+                self.write_newline()?;
+                indented = false;
+                any = true;
+            }
+            if any && placement == CommentPlacement::Standalone {
+                self.write_newline()?;
+            }
+        }
+        if !indented {
+            self.write_indent()?;
+        }
+        self.pending_comment = comments
+            .iter()
+            .find(|comment| {
+                comment.placement == CommentPlacement::Trailing
+                    && comment.path.as_deref() == Some(path)
+            })
+            .map(|comment| comment.text.as_str());
+        Ok(())
     }
 
     fn write_indent(&mut self) -> EmitResult {
@@ -210,33 +1035,34 @@ impl<'a> YamlEmitter<'a> {
         Ok(())
     }
 
-    fn emit_node(&mut self, node: &Yaml) -> EmitResult {
+    pub(crate) fn emit_node(&mut self, node: &Yaml) -> EmitResult {
         match *node {
             Yaml::Array(ref v) => self.emit_array(v),
             Yaml::Hash(ref h) => self.emit_hash(h),
             Yaml::String(ref v) => {
-                if self.multiline_strings
-                    && v.contains('\n')
-                    && char_traits::is_valid_literal_block_scalar(v)
+                let style = select_scalar_style(v);
+                if self.multiline_strings && style == TScalarStyle::Literal && can_write_literal(v)
                 {
-                    write!(self.writer, "|")?;
-                    self.level += 1;
-                    for line in v.lines() {
-                        writeln!(self.writer)?;
-                        self.write_indent()?;
-                        // It's literal text, so don't escape special chars.
-                        write!(self.writer, "{line}")?;
-                    }
-                    self.level -= 1;
-                } else if need_quotes(v) {
-                    escape_str(self.writer, v)?;
+                    self.emit_literal(v)?;
+                } else if style != TScalarStyle::Plain
+                    || self.quote_all_strings
+                    || self.need_version_quotes(v)
+                {
+                    self.emit_quoted(v)?;
                 } else {
                     write!(self.writer, "{v}")?;
                 }
                 Ok(())
             }
             Yaml::Boolean(v) => {
-                if v {
+                let lexeme = self
+                    .bool_lexemes
+                    .zip(self.path.as_deref())
+                    .and_then(|(lexemes, path)| lexemes.get(path))
+                    .filter(|lexeme| yaml11_bool(lexeme) == Some(v));
+                if let Some(lexeme) = lexeme {
+                    self.writer.write_str(lexeme)?;
+                } else if v {
                     self.writer.write_str("true")?;
                 } else {
                     self.writer.write_str("false")?;
@@ -247,17 +1073,77 @@ impl<'a> YamlEmitter<'a> {
                 write!(self.writer, "{v}")?;
                 Ok(())
             }
+            Yaml::Real(ref v) if !self.is_version_real(v) => Err(EmitError::Unrepresentable {
+                path: vec![],
+                reason: "the text of the real is not a float in the pinned version of YAML",
+            }),
             Yaml::Real(ref v) => {
                 write!(self.writer, "{v}")?;
                 Ok(())
             }
             Yaml::Null | Yaml::BadValue => {
-                write!(self.writer, "~")?;
+                match self.null_style {
+                    NullStyle::Tilde => self.writer.write_str("~")?,
+                    NullStyle::Null => self.writer.write_str("null")?,
+                }
                 Ok(())
             }
-            // XXX(chenyh) Alias
-            Yaml::Alias(_) => Ok(()),
+            Yaml::Alias(_) => Err(EmitError::Unrepresentable {
+                path: vec![],
+                reason: "aliases cannot be emitted without the node they refer to",
+            }),
+            Yaml::Tagged(ref tag, ref node) => self.emit_tagged(tag, node),
+        }
+    }
+
+    /// Emit a tagged node: the tag, followed by the node itself.
+    ///
+    /// Non-empty collections start on the next line, indented one level deeper than the tag.
+    fn emit_tagged(&mut self, tag: &Tag, node: &Yaml) -> EmitResult {
+        let handle = self
+            .tag_handle
+            .as_ref()
+            .filter(|(prefix, _)| *prefix == tag.handle)
+            .filter(|_| is_shorthand_suffix(&tag.suffix));
+        if let Some((_, name)) = handle {
+            write!(self.writer, "!{name}!{}", tag.suffix)?;
+        } else {
+            write!(self.writer, "{tag}")?;
+        }
+        let collection = match node {
+            Yaml::Array(v) => !v.is_empty(),
+            Yaml::Hash(h) => !h.is_empty(),
+            _ => false,
+        };
+        if !collection {
+            write!(self.writer, " ")?;
+            return self.emit_node(node);
+        }
+
+        self.write_newline()?;
+        self.level += 1;
+        self.write_indent()?;
+        self.level -= 1;
+        match node {
+            Yaml::Hash(h) if tag.is_core("set") => self.emit_set(h),
+            _ => self.emit_node(node),
+        }
+    }
+
+    /// Emit the items of a `!!set` as explicit keys, omitting their null values.
+    fn emit_set(&mut self, h: &Hash) -> EmitResult {
+        self.level += 1;
+        for (cnt, k) in h.keys().enumerate() {
+            if cnt > 0 {
+                self.write_newline()?;
+                self.write_indent()?;
+            }
+            write!(self.writer, "?")?;
+            self.within_path(None, |emitter| emitter.emit_val(true, k))
+                .map_err(|e| e.within(|| path_segment(k)))?;
         }
+        self.level -= 1;
+        Ok(())
     }
 
     fn emit_array(&mut self, v: &[Yaml]) -> EmitResult {
@@ -267,11 +1153,14 @@ impl<'a> YamlEmitter<'a> {
             self.level += 1;
             for (cnt, x) in v.iter().enumerate() {
                 if cnt > 0 {
-                    writeln!(self.writer)?;
+                    self.write_newline()?;
                     self.write_indent()?;
                 }
+                let path = self.path.as_ref().map(|path| format!("{path}[{cnt}]"));
+                self.write_comments(path.as_deref())?;
                 write!(self.writer, "-")?;
-                self.emit_val(true, x)?;
+                self.within_path(path, |emitter| emitter.emit_val(true, x))
+                    .map_err(|e| e.within(|| cnt.to_string()))?;
             }
             self.level -= 1;
         }
@@ -284,40 +1173,180 @@ impl<'a> YamlEmitter<'a> {
         } else {
             self.level += 1;
             for (cnt, (k, v)) in h.iter().enumerate() {
-                let complex_key = matches!(*k, Yaml::Hash(_) | Yaml::Array(_));
+                let complex_key = matches!(*k.untagged(), Yaml::Hash(_) | Yaml::Array(_));
                 if cnt > 0 {
-                    writeln!(self.writer)?;
+                    self.write_newline()?;
                     self.write_indent()?;
                 }
-                if complex_key {
+                let path = self
+                    .path
+                    .as_ref()
+                    .map(|path| key_path(path, key_text(k).as_deref()));
+                self.write_comments(path.as_deref())?;
+                let entry = if complex_key {
                     write!(self.writer, "?")?;
-                    self.emit_val(true, k)?;
-                    writeln!(self.writer)?;
-                    self.write_indent()?;
-                    write!(self.writer, ":")?;
-                    self.emit_val(true, v)?;
+                    self.within_path(None, |emitter| emitter.emit_val(true, k))
+                        .and_then(|()| {
+                            self.write_newline()?;
+                            self.write_indent()?;
+                            write!(self.writer, ":")?;
+                            self.within_path(path, |emitter| emitter.emit_val(true, v))
+                        })
                 } else {
-                    self.emit_node(k)?;
-                    write!(self.writer, ":")?;
-                    self.emit_val(false, v)?;
-                }
+                    self.within_path(None, |emitter| emitter.emit_key(k))
+                        .and_then(|()| {
+                            write!(self.writer, ":")?;
+                            self.within_path(path, |emitter| emitter.emit_val(false, v))
+                        })
+                };
+                entry.map_err(|e| e.within(|| path_segment(k)))?;
             }
             self.level -= 1;
         }
         Ok(())
     }
 
+    /// Emit a key of a mapping which is not a collection, as a quoted string if it is not one and
+    /// [`Self::quote_non_string_keys`] is set.
+    ///
+    /// Keys must fit on a single line, so multi-line strings are quoted even if
+    /// [`Self::multiline_strings`] is set.
+    fn emit_key(&mut self, key: &Yaml) -> EmitResult {
+        if self.quote_non_string_keys {
+            let text = match key {
+                Yaml::Integer(v) => Some(v.to_string()),
+                Yaml::Real(v) => Some(v.clone()),
+                Yaml::Boolean(v) => Some(v.to_string()),
+                Yaml::Null => Some("null".to_owned()),
+                _ => None,
+            };
+            if let Some(text) = text {
+                return self.emit_quoted(&text);
+            }
+        }
+        let multiline_strings = std::mem::replace(&mut self.multiline_strings, false);
+        let result = self.emit_node(key);
+        self.multiline_strings = multiline_strings;
+        result
+    }
+
+    /// Run `emit` with `path` as the path of the node being emitted.
+    fn within_path(
+        &mut self,
+        path: Option<String>,
+        emit: impl FnOnce(&mut Self) -> EmitResult,
+    ) -> EmitResult {
+        let parent = std::mem::replace(&mut self.path, path);
+        let result = emit(self);
+        self.path = parent;
+        result
+    }
+
+    /// Return `node` written in flow style, if [`Self::auto_style`] picks that style for it.
+    fn auto_flow(&self, node: &Yaml) -> Result<Option<String>, EmitError> {
+        let Some(auto_style) = self.auto_style else {
+            return Ok(None);
+        };
+        let collection = match node {
+            Yaml::Array(v) => !v.is_empty(),
+            Yaml::Hash(h) => !h.is_empty(),
+            _ => false,
+        };
+        let commented = self
+            .comments
+            .zip(self.path.as_deref())
+            .is_some_and(|(comments, path)| comments.any_within(path));
+        if !collection || commented || !auto_style.fits(node, 0) {
+            return Ok(None);
+        }
+        let mut text = String::new();
+        YamlEmitter {
+            writer: &mut text,
+            best_indent: self.best_indent,
+            compact: self.compact,
+            level: self.level,
+            multiline_strings: self.multiline_strings,
+            quote_style: self.quote_style,
+            quote_all_strings: self.quote_all_strings,
+            quote_non_string_keys: self.quote_non_string_keys,
+            null_style: self.null_style,
+            indent_sequences: self.indent_sequences,
+            yaml_directive: self.yaml_directive,
+            bool_lexemes: self.bool_lexemes,
+            auto_style: self.auto_style,
+            tag_directives: self.tag_directives,
+            tag_handle: self.tag_handle.clone(),
+            wrote_document: false,
+            comments: None,
+            pending_comment: None,
+            path: self.path.clone(),
+        }
+        .emit_flow(node)?;
+        Ok((text.chars().count() <= auto_style.max_width).then_some(text))
+    }
+
+    /// Emit `node` in flow style. Its strings must fit on a single line.
+    fn emit_flow(&mut self, node: &Yaml) -> EmitResult {
+        match node {
+            Yaml::Array(v) => {
+                self.writer.write_str("[")?;
+                for (cnt, x) in v.iter().enumerate() {
+                    if cnt > 0 {
+                        self.writer.write_str(", ")?;
+                    }
+                    let path = self.path.as_ref().map(|path| format!("{path}[{cnt}]"));
+                    self.within_path(path, |emitter| emitter.emit_flow(x))
+                        .map_err(|e| e.within(|| cnt.to_string()))?;
+                }
+                self.writer.write_str("]")?;
+            }
+            Yaml::Hash(h) => {
+                self.writer.write_str("{")?;
+                for (cnt, (k, v)) in h.iter().enumerate() {
+                    if cnt > 0 {
+                        self.writer.write_str(", ")?;
+                    }
+                    let path = self
+                        .path
+                        .as_ref()
+                        .map(|path| key_path(path, key_text(k).as_deref()));
+                    self.within_path(None, |emitter| emitter.emit_key(k))
+                        .and_then(|()| {
+                            self.writer.write_str(": ")?;
+                            self.within_path(path, |emitter| emitter.emit_flow(v))
+                        })
+                        .map_err(|e| e.within(|| path_segment(k)))?;
+                }
+                self.writer.write_str("}")?;
+            }
+            _ => self.emit_node(node)?,
+        }
+        Ok(())
+    }
+
     /// Emit a yaml as a hash or array value: i.e., which should appear
     /// following a ":" or "-", either after a space, or on a new line.
     /// If `inline` is true, then the preceding characters are distinct
     /// and short enough to respect the compact flag.
     fn emit_val(&mut self, inline: bool, val: &Yaml) -> EmitResult {
+        if let Some(flow) = self.auto_flow(val)? {
+            write!(self.writer, " {flow}")?;
+            return Ok(());
+        }
         match *val {
             Yaml::Array(ref v) => {
                 if (inline && self.compact) || v.is_empty() {
                     write!(self.writer, " ")?;
+                } else if !inline && !self.indent_sequences {
+                    // The items start at the column of the key.
+                    self.write_newline()?;
+                    self.write_indent()?;
+                    self.level -= 1;
+                    let result = self.emit_array(v);
+                    self.level += 1;
+                    return result;
                 } else {
-                    writeln!(self.writer)?;
+                    self.write_newline()?;
                     self.level += 1;
                     self.write_indent()?;
                     self.level -= 1;
@@ -328,7 +1357,7 @@ impl<'a> YamlEmitter<'a> {
                 if (inline && self.compact) || h.is_empty() {
                     write!(self.writer, " ")?;
                 } else {
-                    writeln!(self.writer)?;
+                    self.write_newline()?;
                     self.level += 1;
                     self.write_indent()?;
                     self.level -= 1;
@@ -343,6 +1372,214 @@ impl<'a> YamlEmitter<'a> {
     }
 }
 
+/// Return the segment designating the entry with the key `key` in the path of an
+/// [`EmitError::Unrepresentable`] node.
+fn path_segment(key: &Yaml) -> String {
+    match key.untagged() {
+        Yaml::String(v) | Yaml::Real(v) => v.clone(),
+        Yaml::Integer(v) => v.to_string(),
+        Yaml::Boolean(v) => v.to_string(),
+        Yaml::Null => "~".into(),
+        _ => "?".into(),
+    }
+}
+
+/// Return whether `v` can be written in single quotes, where nothing but quotes can be escaped.
+fn can_single_quote(v: &str) -> bool {
+    !v.contains('\n') && char_traits::is_valid_literal_block_scalar(v)
+}
+
+/// Return the tag prefix of `doc` shared by the most tags, if several share one, with the name of
+/// the handle a `%TAG` directive gives it. See [`YamlEmitter::tag_directives`].
+fn tag_handle(doc: &Yaml) -> Option<(String, String)> {
+    /// Count the tags of `node` written in verbatim form by default which a handle could shorten,
+    /// by prefix.
+    fn count<'a>(node: &'a Yaml, counts: &mut Vec<(&'a str, usize)>) {
+        match node {
+            Yaml::Tagged(tag, node) => {
+                let shortenable = !matches!(tag.handle.as_str(), "" | "!" | CORE_SCHEMA_HANDLE)
+                    && is_shorthand_suffix(&tag.suffix);
+                if shortenable {
+                    match counts.iter_mut().find(|(prefix, _)| *prefix == tag.handle) {
+                        Some((_, n)) => *n += 1,
+                        None => counts.push((&tag.handle, 1)),
+                    }
+                }
+                count(node, counts);
+            }
+            Yaml::Array(items) => items.iter().for_each(|item| count(item, counts)),
+            Yaml::Hash(hash) => hash.iter().for_each(|(key, value)| {
+                count(key, counts);
+                count(value, counts);
+            }),
+            _ => {}
+        }
+    }
+
+    let mut counts = vec![];
+    count(doc, &mut counts);
+    // The first of the most repeated prefixes.
+    let (prefix, _) = counts
+        .into_iter()
+        .rev()
+        .max_by_key(|&(_, n)| n)
+        .filter(|&(_, n)| n > 1)?;
+    // The first label of the domain name of the prefix, if any.
+    let rest = prefix
+        .strip_prefix("tag:")
+        .or_else(|| prefix.split_once("://").map(|(_, rest)| rest))
+        .unwrap_or(prefix);
+    let name: String = rest
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect();
+    let name = if name.is_empty() {
+        "t".to_owned()
+    } else {
+        name
+    };
+    Some((prefix.to_owned(), name))
+}
+
+/// Return whether `v` can be written as a literal block scalar holding the same string.
+///
+/// The scalar must span several lines, start with neither a space, which would need an
+/// indentation indicator, nor a blank line, and end with at most one line break, which clipping
+/// keeps.
+fn can_write_literal(v: &str) -> bool {
+    v.contains('\n')
+        && !v.starts_with([' ', '\n'])
+        && !v.ends_with("\n\n")
+        && char_traits::is_valid_literal_block_scalar(v)
+}
+
+/// Return whether `suffix` can follow a named handle, rather than requiring the verbatim form.
+fn is_shorthand_suffix(suffix: &str) -> bool {
+    !suffix.is_empty()
+        && suffix
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-#;/?:@&=+$_.~*'()%".contains(c))
+}
+
+/// Replace the node of `doc` at `path` by `value`, or add its last segment as a key of the mapping
+/// it is missing from. See [`YamlEmitter::dump_with_overrides`].
+fn apply_override(doc: &mut Yaml, path: &[&str], value: Yaml) -> Result<(), LookupError> {
+    let Some((last, parents)) = path.split_last() else {
+        *doc = value;
+        return Ok(());
+    };
+    // Errors are reported as `Yaml::lookup` does.
+    let parent = doc.lookup_mut(parents)?;
+    match child_mut(parent, last) {
+        Ok(node) => *node = value,
+        Err(LookupErrorKind::MissingKey) => {
+            if let Yaml::Hash(hash) = untagged_mut(parent) {
+                hash.insert(Yaml::String((*last).to_owned()), value);
+            }
+        }
+        Err(kind) => {
+            return Err(LookupError {
+                path: parents.iter().map(ToString::to_string).collect(),
+                segment: (*last).to_string(),
+                kind,
+            })
+        }
+    }
+    Ok(())
+}
+
+/// Write `node` in flow style on a single line, without the start of a document nor allocating.
+///
+/// This is meant for hot paths writing many tiny documents, such as the labels of objects (e.g.:
+/// `{app: web, tier: "1"}`), where setting up a [`YamlEmitter`] for each of them costs more than
+/// writing them. Output is kept minimal rather than pretty: strings made of letters, digits and
+/// `_`, `.`, `/` or `-`, starting with a letter, are written plain; other strings are written in
+/// double quotes. The output loads back as `node`.
+///
+/// ```
+/// use saphyr::{write_compact, Yaml};
+///
+/// let doc = Yaml::load_from_str("{app: web, tier: '1', ports: [80, 443], note: 'a, b'}").unwrap();
+/// let mut output = String::new();
+/// write_compact(&doc[0], &mut output).unwrap();
+/// assert_eq!(output, r#"{app: web, tier: "1", ports: [80, 443], note: "a, b"}"#);
+/// ```
+///
+/// # Errors
+/// Returns [`EmitError::Unrepresentable`] for aliases, and `EmitError` when writing fails.
+pub fn write_compact(node: &Yaml, writer: &mut impl fmt::Write) -> EmitResult {
+    match node {
+        Yaml::String(v) if is_compact_plain(v) => writer.write_str(v)?,
+        Yaml::String(v) => escape_str(writer, v)?,
+        Yaml::Real(v) => writer.write_str(v)?,
+        Yaml::Integer(v) => write!(writer, "{v}")?,
+        Yaml::Boolean(true) => writer.write_str("true")?,
+        Yaml::Boolean(false) => writer.write_str("false")?,
+        Yaml::Null | Yaml::BadValue => writer.write_str("~")?,
+        Yaml::Array(items) => {
+            writer.write_str("[")?;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    writer.write_str(", ")?;
+                }
+                write_compact(item, writer).map_err(|e| e.within(|| i.to_string()))?;
+            }
+            writer.write_str("]")?;
+        }
+        Yaml::Hash(hash) => {
+            writer.write_str("{")?;
+            for (i, (key, value)) in hash.iter().enumerate() {
+                if i > 0 {
+                    writer.write_str(", ")?;
+                }
+                write_compact(key, writer)
+                    .and_then(|()| {
+                        writer.write_str(": ")?;
+                        write_compact(value, writer)
+                    })
+                    .map_err(|e| e.within(|| path_segment(key)))?;
+            }
+            writer.write_str("}")?;
+        }
+        Yaml::Tagged(tag, node) => {
+            write!(writer, "{tag} ")?;
+            write_compact(node, writer)?;
+        }
+        Yaml::Alias(_) => {
+            return Err(EmitError::Unrepresentable {
+                path: vec![],
+                reason: "aliases cannot be emitted without the node they refer to",
+            })
+        }
+    }
+    Ok(())
+}
+
+/// Return whether [`write_compact`] can write the string `v` plain: whether it is made of
+/// characters which are never special, and cannot be read as another type.
+fn is_compact_plain(v: &str) -> bool {
+    v.starts_with(|c: char| c.is_ascii_alphabetic())
+        && v.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '/' | '-'))
+        && !BOOL_WORDS.contains(&v)
+        && !NULL_WORDS.contains(&v)
+        // Such as `inf` or `NaN`.
+        && v.parse::<f64>().is_err()
+}
+
+/// Plain scalars which YAML 1.1 or YAML 1.2 resolve to a boolean.
+const BOOL_WORDS: &[&str] = &[
+    // http://yaml.org/type/bool.html
+    // Note: 'y', 'Y', 'n', 'N', is not quoted deliberately, as in libyaml. PyYAML also parse
+    // them as string, not booleans, although it is violating the YAML 1.1 specification.
+    // See https://github.com/dtolnay/serde-yaml/pull/83#discussion_r152628088.
+    "yes", "Yes", "YES", "no", "No", "NO", "True", "TRUE", "true", "False", "FALSE", "false", "on",
+    "On", "ON", "off", "Off", "OFF",
+];
+
+/// Plain scalars which resolve to null. See <http://yaml.org/type/null.html>.
+const NULL_WORDS: &[&str] = &["null", "Null", "NULL", "~"];
+
 /// Check if the string requires quoting.
 /// Strings starting with any of the following characters must be quoted.
 /// :, &, *, ?, |, -, <, >, =, !, %, @
@@ -356,9 +1593,10 @@ impl<'a> YamlEmitter<'a> {
 /// * When the string is true or false (otherwise, it would be treated as a boolean value);
 /// * When the string is null or ~ (otherwise, it would be considered as a null value);
 /// * When the string looks like a number, such as integers (e.g. 2, 14, etc.), floats (e.g. 2.6, 14.9) and exponential numbers (e.g. 12e7, etc.) (otherwise, it would be treated as a numeric value);
-/// * When the string looks like a date (e.g. 2014-12-31) (otherwise it would be automatically converted into a Unix timestamp).
+/// * When the string looks like a date (e.g. 2014-12-31) (otherwise it would be automatically converted into a Unix timestamp);
+/// * More generally, when the string would not load back as a string if written plain (e.g. 0o17 or +.inf).
 #[allow(clippy::doc_markdown)]
-fn need_quotes(string: &str) -> bool {
+pub(crate) fn need_quotes(string: &str) -> bool {
     fn need_quotes_spaces(string: &str) -> bool {
         string.starts_with(' ') || string.ends_with(' ')
     }
@@ -390,21 +1628,13 @@ fn need_quotes(string: &str) -> bool {
             | '\x0e'..='\x1a'
             | '\x1c'..='\x1f')
         })
-        || [
-            // http://yaml.org/type/bool.html
-            // Note: 'y', 'Y', 'n', 'N', is not quoted deliberately, as in libyaml. PyYAML also parse
-            // them as string, not booleans, although it is violating the YAML 1.1 specification.
-            // See https://github.com/dtolnay/serde-yaml/pull/83#discussion_r152628088.
-            "yes", "Yes", "YES", "no", "No", "NO", "True", "TRUE", "true", "False", "FALSE",
-            "false", "on", "On", "ON", "off", "Off", "OFF",
-            // http://yaml.org/type/null.html
-            "null", "Null", "NULL", "~",
-        ]
-        .contains(&string)
+        || BOOL_WORDS.contains(&string)
+        || NULL_WORDS.contains(&string)
         || string.starts_with('.')
         || string.starts_with("0x")
         || string.parse::<i64>().is_ok()
         || string.parse::<f64>().is_ok()
+        || !matches!(Yaml::value_from_str(string), Yaml::String(_))
 }
 
 #[cfg(test)]
@@ -0,0 +1,380 @@
+//! Stable, machine-readable codes identifying errors.
+//!
+//! Messages of errors may be reworded from one version to the next. Codes do not change: once a
+//! code is given to a kind of error, it keeps designating it, and new kinds of errors get new
+//! codes. This allows handling errors programmatically, or linking to their documentation.
+//!
+//! Codes are made of a prefix telling which stage the error happened at, followed by a number:
+//!   * `SCAN` codes designate errors of the scanner and parser (i.e.: malformed YAML). They are
+//!     returned by [`ErrorCode::of_scan_error`]. `SCAN000` designates messages unknown to this
+//!     version of the library.
+//!   * `LOAD` codes designate other errors of the [`YamlLoader`], and `COMPOSE` codes errors of
+//!     composition. See [`LoadError::code`] and [`ComposeError::code`].
+//!   * `EMIT` codes designate errors of the [`YamlEmitter`]. See [`EmitError::code`].
+//!   * `LOOKUP` codes designate errors of [`Yaml::lookup`] and of the getters of [`ConfigStack`].
+//!     See [`LookupError::code`].
+//!   * `TYPE` codes designate operations on nodes of the wrong type. See [`TypeError::code`].
+//!   * `EXTRACT` codes designate errors of the [`Extractor`]. See [`ExtractError::code`].
+//!   * `COERCE` codes designate scalars which coercions such as [`Yaml::as_duration`] reject.
+//!     See [`CoerceError::code`].
+//!   * `JSON` codes designate changes [`Yaml::to_json_compatible`] makes to nodes JSON cannot
+//!     represent. See [`JsonLoss::code`].
+//!   * `CACHE` codes designate cached documents which cannot be decoded. They only exist with the
+//!     `cache` feature.
+//!   * `TRACE` codes designate event traces which cannot be read. They only exist with the
+//!     `trace` feature.
+//!
+//! [`YamlLoader`]: crate::YamlLoader
+//! [`LoadError::code`]: crate::LoadError::code
+//! [`ComposeError::code`]: crate::ComposeError::code
+//! [`YamlEmitter`]: crate::YamlEmitter
+//! [`EmitError::code`]: crate::EmitError::code
+//! [`Yaml::lookup`]: crate::Yaml::lookup
+//! [`LookupError::code`]: crate::LookupError::code
+//! [`ConfigStack`]: crate::ConfigStack
+//! [`TypeError::code`]: crate::TypeError::code
+//! [`Extractor`]: crate::Extractor
+//! [`ExtractError::code`]: crate::ExtractError::code
+//! [`Yaml::as_duration`]: crate::Yaml::as_duration
+//! [`CoerceError::code`]: crate::CoerceError::code
+//! [`Yaml::to_json_compatible`]: crate::Yaml::to_json_compatible
+//! [`JsonLoss::code`]: crate::JsonLoss::code
+
+use std::fmt;
+
+use saphyr_parser::ScanError;
+
+/// A stable code identifying the kind of an error (e.g.: `SCAN023`).
+///
+/// See the [module-level documentation](self) for the guarantees codes offer.
+///
+/// ```
+/// use saphyr::{ErrorCode, Yaml};
+///
+/// let error = Yaml::load_from_str("a: b: c").unwrap_err();
+/// let code = ErrorCode::of_scan_error(&error);
+/// assert_eq!(code.as_str(), "SCAN023");
+/// assert_eq!(code.to_string(), "SCAN023");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ErrorCode(&'static str);
+
+/// The codes of [`ScanError`]s, along with the message they are given for.
+///
+/// Entries must never be removed nor renumbered. The message of `unexpected character` errors
+/// carries the character, and is matched by its prefix.
+const SCAN_ERRORS: &[(&str, &str)] = &[
+    ("SCAN001", "\"-\" is only valid inside a block"),
+    ("SCAN002", "'-' must be followed by a valid YAML whitespace"),
+    ("SCAN003", "':' must be followed by a valid YAML whitespace"),
+    ("SCAN004", "a block scalar content cannot start with a tab"),
+    (
+        "SCAN005",
+        "block sequence entries are not allowed in this context",
+    ),
+    (
+        "SCAN006",
+        "comments must be separated from other tokens by whitespace",
+    ),
+    ("SCAN007", "did not find expected <document start>"),
+    ("SCAN008", "did not find expected <document-start>"),
+    ("SCAN009", "did not find expected <stream-start>"),
+    ("SCAN010", "did not find expected next token"),
+    ("SCAN011", "duplicate version directive"),
+    ("SCAN012", "expected whitespace"),
+    ("SCAN013", "found incompatible YAML document"),
+    ("SCAN014", "illegal placement of ':' indicator"),
+    ("SCAN015", "invalid content after document end marker"),
+    ("SCAN016", "invalid global tag character"),
+    ("SCAN017", "invalid indentation for anchor"),
+    ("SCAN018", "invalid indentation in flow construct"),
+    ("SCAN019", "invalid indentation in quoted scalar"),
+    ("SCAN020", "invalid indentation"),
+    (
+        "SCAN021",
+        "invalid trailing content after double-quoted scalar",
+    ),
+    ("SCAN022", "mapping keys are not allowed in this context"),
+    ("SCAN023", "mapping values are not allowed in this context"),
+    (
+        "SCAN024",
+        "missing explicit document end marker before directive",
+    ),
+    (
+        "SCAN025",
+        "plain scalar cannot start with '-' followed by ,[]{}",
+    ),
+    ("SCAN026", "recursion limit exceeded"),
+    ("SCAN027", "simple key expect ':'"),
+    ("SCAN028", "simple key expected"),
+    ("SCAN029", "tab cannot be used as indentation"),
+    ("SCAN030", "tabs disallowed in this context"),
+    (
+        "SCAN031",
+        "tabs disallowed within this context (block indentation)",
+    ),
+    (
+        "SCAN032",
+        "the TAG directive must only be given at most once per handle in the same document",
+    ),
+    ("SCAN033", "the handle wasn't declared"),
+    ("SCAN034", "unexpected character"),
+    ("SCAN035", "unexpected eof"),
+    (
+        "SCAN036",
+        "while parsing a block collection, did not find expected '-' indicator",
+    ),
+    (
+        "SCAN037",
+        "while parsing a block mapping, did not find expected key",
+    ),
+    (
+        "SCAN038",
+        "while parsing a flow mapping, did not find expected ',' or '}'",
+    ),
+    (
+        "SCAN039",
+        "while parsing a flow sequence, expected ',' or ']'",
+    ),
+    (
+        "SCAN040",
+        "while parsing a node, did not find expected node content",
+    ),
+    (
+        "SCAN041",
+        "while parsing a quoted scalar, did not find expected hexadecimal number",
+    ),
+    (
+        "SCAN042",
+        "while parsing a quoted scalar, found invalid Unicode character escape code",
+    ),
+    (
+        "SCAN043",
+        "while parsing a quoted scalar, found unknown escape character",
+    ),
+    (
+        "SCAN044",
+        "while parsing a tag directive, did not find expected '!'",
+    ),
+    (
+        "SCAN045",
+        "while parsing a tag, did not find URI escaped octet",
+    ),
+    (
+        "SCAN046",
+        "while parsing a tag, did not find expected tag URI",
+    ),
+    (
+        "SCAN047",
+        "while parsing a tag, found an incorrect leading UTF-8 octet",
+    ),
+    (
+        "SCAN048",
+        "while parsing a tag, found an incorrect trailing UTF-8 octet",
+    ),
+    (
+        "SCAN049",
+        "while parsing a tag, found an invalid UTF-8 codepoint",
+    ),
+    ("SCAN050", "while parsing anchor, found duplicated anchor"),
+    ("SCAN051", "while parsing node, found unknown anchor"),
+    (
+        "SCAN052",
+        "while scanning TAG, did not find expected whitespace or line break",
+    ),
+    (
+        "SCAN053",
+        "while scanning a YAML directive, did not find expected digit or '.' character",
+    ),
+    (
+        "SCAN054",
+        "while scanning a YAML directive, did not find expected version number",
+    ),
+    (
+        "SCAN055",
+        "while scanning a YAML directive, found extremely long version number",
+    ),
+    (
+        "SCAN056",
+        "while scanning a block scalar, did not find expected comment or line break",
+    ),
+    (
+        "SCAN057",
+        "while scanning a block scalar, found an indentation indicator equal to 0",
+    ),
+    (
+        "SCAN058",
+        "while scanning a directive, could not find expected directive name",
+    ),
+    (
+        "SCAN059",
+        "while scanning a directive, did not find expected comment or line break",
+    ),
+    (
+        "SCAN060",
+        "while scanning a directive, found unexpected non-alphabetical character",
+    ),
+    (
+        "SCAN061",
+        "while scanning a directive, found unknown directive name",
+    ),
+    ("SCAN062", "while scanning a plain scalar, found a tab"),
+    (
+        "SCAN063",
+        "while scanning a quoted scalar, found unexpected document indicator",
+    ),
+    (
+        "SCAN064",
+        "while scanning a quoted scalar, found unexpected end of stream",
+    ),
+    ("SCAN065", "while scanning a tag, did not find expected '!'"),
+    (
+        "SCAN066",
+        "while scanning a tag, did not find expected whitespace or line break",
+    ),
+    (
+        "SCAN067",
+        "while scanning a verbatim tag, did not find the expected '>'",
+    ),
+    (
+        "SCAN068",
+        "while scanning an anchor or alias, did not find expected alphabetic or numeric character",
+    ),
+    ("SCAN069", "wrongly indented line in block scalar"),
+];
+
+impl ErrorCode {
+    /// The code of scan errors whose message is unknown to this version of the library.
+    pub const UNKNOWN_SCAN_ERROR: ErrorCode = ErrorCode("SCAN000");
+
+    /// Create a code for an error of this crate.
+    pub(crate) const fn new(code: &'static str) -> Self {
+        Self(code)
+    }
+
+    /// Return the code of `error`, from its message.
+    #[must_use]
+    pub fn of_scan_error(error: &ScanError) -> Self {
+        let info = error.info();
+        SCAN_ERRORS
+            .iter()
+            .find(|(_, message)| {
+                info == *message
+                    || (*message == "unexpected character" && info.starts_with(message))
+            })
+            .map_or(Self::UNKNOWN_SCAN_ERROR, |(code, _)| Self(code))
+    }
+
+    /// Return the code as a string (e.g.: `SCAN023`).
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        self.0
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ErrorCode, SCAN_ERRORS};
+    use crate::Yaml;
+
+    /// An input raising each scan error, along with its code.
+    const INPUTS: &[(&str, &str)] = &[
+        ("SCAN001", "[- a]"),
+        ("SCAN002", "-\t- a"),
+        ("SCAN003", "a:\t- b"),
+        ("SCAN004", "a: |\n\tb"),
+        ("SCAN005", "&x - a"),
+        ("SCAN006", "[a]#c"),
+        ("SCAN007", "%YAML 1.2\na"),
+        ("SCAN011", "%YAML 1.2\n%YAML 1.2\n---"),
+        ("SCAN012", "?\t-a"),
+        ("SCAN014", "[a\nb: c]"),
+        ("SCAN015", "... a"),
+        ("SCAN016", "%TAG !a! [x\n---"),
+        ("SCAN018", "- [a,\nb]"),
+        ("SCAN019", "a: \"b\nc\""),
+        ("SCAN020", "a: [\nb]"),
+        ("SCAN021", "\"a\" b"),
+        ("SCAN022", "a: ? b"),
+        ("SCAN023", "a: b: c"),
+        ("SCAN024", "a: b\n---\n%YAML 1.1\n---"),
+        ("SCAN025", "[-,]"),
+        ("SCAN027", "key:\n&a\n- b"),
+        ("SCAN028", "? a\n:b"),
+        ("SCAN029", "a:\n  \"b\n\tc\""),
+        ("SCAN030", "? \t- a"),
+        ("SCAN031", "a:\n\t b"),
+        ("SCAN033", "a: !x!y b"),
+        ("SCAN034", "@a"),
+        ("SCAN036", "- a\nb: - c"),
+        ("SCAN037", "a:\n  - b\n c"),
+        ("SCAN038", "{a: b c: d}"),
+        ("SCAN039", "[a, b\n"),
+        ("SCAN040", "]"),
+        ("SCAN041", "\"\\x1\""),
+        ("SCAN042", "\"\\uD800\""),
+        ("SCAN043", "\"\\q\""),
+        ("SCAN044", "%TAG !a b\n---"),
+        ("SCAN045", "!%zz a"),
+        ("SCAN046", "!a!"),
+        ("SCAN047", "!%F8%80 a"),
+        ("SCAN048", "!%C3%41 a"),
+        ("SCAN049", "!%ED%A0%80 a"),
+        ("SCAN051", "*a"),
+        ("SCAN052", "%TAG !a! b\"c\n---"),
+        ("SCAN053", "%YAML 1a\n---"),
+        ("SCAN054", "%YAML x"),
+        ("SCAN055", "%YAML 1111111111.1"),
+        ("SCAN056", "a: |x"),
+        ("SCAN057", "a: |0"),
+        ("SCAN058", "% a"),
+        ("SCAN059", "%YAML 1.1 a"),
+        ("SCAN060", "%YAML1.1\n---"),
+        ("SCAN062", "a: b\n\tc: d"),
+        ("SCAN063", "a: \"x\n---\n"),
+        ("SCAN064", "\"a"),
+        ("SCAN065", "%TAG x y\n---"),
+        ("SCAN066", "!a\u{1}"),
+        ("SCAN067", "!<a"),
+        ("SCAN068", "&"),
+        ("SCAN069", "a: |2\n b"),
+    ];
+
+    /// The codes of messages no input is known to raise. saphyr-parser 0.0.2 raises them in
+    /// states its scanner does not reach, or not at all (their checks are commented out).
+    const UNREACHED: &[&str] = &[
+        "SCAN008", "SCAN009", "SCAN010", "SCAN013", "SCAN017", "SCAN032", "SCAN035", "SCAN050",
+        "SCAN061",
+    ];
+
+    /// Codes are found from the messages of the parser, so a message it rewords silently gets
+    /// `SCAN000`. Check each message is still raised as written.
+    #[test]
+    fn test_scan_error_messages() {
+        for (code, input) in INPUTS {
+            let error = Yaml::load_from_str(input).unwrap_err();
+            assert_eq!(
+                ErrorCode::of_scan_error(&error).as_str(),
+                *code,
+                "{input:?} raised {:?}",
+                error.info()
+            );
+        }
+        let error = Yaml::load_from_str(&"[".repeat(3000)).unwrap_err();
+        assert_eq!(ErrorCode::of_scan_error(&error).as_str(), "SCAN026");
+        for (code, _) in SCAN_ERRORS {
+            assert!(
+                INPUTS.iter().any(|(tested, _)| tested == code)
+                    || UNREACHED.contains(code)
+                    || *code == "SCAN026",
+                "no input raises {code}"
+            );
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Tracking of the paths of nodes while walking the events of a document.
+
+use crate::{
+    yaml::{key_path, key_text},
+    Yaml,
+};
+
+/// The paths of the nodes of a document, followed event by event.
+///
+/// Paths are written as [`Yaml::paths`] returns them. Nodes within keys which are not scalars
+/// have no path.
+#[derive(Default)]
+pub(crate) struct EventPaths {
+    /// The collections containing the next node.
+    stack: Vec<Frame>,
+}
+
+/// A collection containing the next node.
+enum Frame {
+    /// A sequence at the given path, and the index of its next item.
+    Sequence(String, usize),
+    /// A mapping at the given path, and the path of the value of its current key once the key
+    /// is known.
+    Mapping(String, Option<String>),
+    /// A collection within a key which is not a scalar, whose nodes have no path.
+    Key,
+}
+
+/// What the next node of a document is.
+enum Role {
+    /// A node at the given path.
+    Value(String),
+    /// A key of the mapping at the given path.
+    Key(String),
+    /// A node within a key which is not a scalar.
+    WithinKey,
+}
+
+impl EventPaths {
+    /// Forget the nodes so far, at the start of a document.
+    pub(crate) fn clear(&mut self) {
+        self.stack.clear();
+    }
+
+    /// Return the number of collections containing the next node.
+    pub(crate) fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Return whether the next node is an item of a sequence.
+    pub(crate) fn in_sequence(&self) -> bool {
+        matches!(self.stack.last(), Some(Frame::Sequence(..)))
+    }
+
+    /// Return the path of the value of the key the innermost mapping is at, once the key is
+    /// known.
+    pub(crate) fn value_path(&self) -> Option<&str> {
+        match self.stack.last() {
+            Some(Frame::Mapping(_, value)) => value.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Move past a scalar resolved to `resolved`, returning its path if it is not a key.
+    pub(crate) fn on_scalar(&mut self, resolved: &Yaml) -> Option<String> {
+        match self.next_role() {
+            Role::Value(path) => Some(path),
+            Role::Key(path) => {
+                if let Some(Frame::Mapping(_, value)) = self.stack.last_mut() {
+                    *value = Some(key_path(&path, key_text(resolved).as_deref()));
+                }
+                None
+            }
+            Role::WithinKey => None,
+        }
+    }
+
+    /// Move past an alias, returning its path if it is not a key. `resolved` is the scalar the
+    /// alias refers to, if it is known to refer to one.
+    pub(crate) fn on_alias(&mut self, resolved: Option<&Yaml>) -> Option<String> {
+        if let Some(resolved) = resolved {
+            return self.on_scalar(resolved);
+        }
+        match self.next_role() {
+            Role::Value(path) => Some(path),
+            role => {
+                self.on_key_end(role);
+                None
+            }
+        }
+    }
+
+    /// Enter a collection, returning its path if it is not a key.
+    pub(crate) fn on_collection_start(&mut self, mapping: bool) -> Option<String> {
+        let (frame, path) = match self.next_role() {
+            Role::Value(path) if mapping => (Frame::Mapping(path.clone(), None), Some(path)),
+            Role::Value(path) => (Frame::Sequence(path.clone(), 0), Some(path)),
+            Role::Key(_) | Role::WithinKey => (Frame::Key, None),
+        };
+        self.stack.push(frame);
+        path
+    }
+
+    /// Leave the current collection.
+    pub(crate) fn on_collection_end(&mut self) {
+        if let Some(Frame::Key) = self.stack.pop() {
+            self.on_key_end(Role::WithinKey);
+        }
+    }
+
+    /// Return what the next node is, and move past it.
+    fn next_role(&mut self) -> Role {
+        match self.stack.last_mut() {
+            None => Role::Value(String::new()),
+            Some(Frame::Sequence(path, index)) => {
+                *index += 1;
+                Role::Value(format!("{path}[{}]", *index - 1))
+            }
+            Some(Frame::Mapping(path, value)) => match value.take() {
+                Some(value) => Role::Value(value),
+                None => Role::Key(path.clone()),
+            },
+            Some(Frame::Key) => Role::WithinKey,
+        }
+    }
+
+    /// Update the collections containing a node which is not a scalar once it ended, given what
+    /// it was.
+    fn on_key_end(&mut self, role: Role) {
+        if let (Role::Key(_) | Role::WithinKey, Some(Frame::Mapping(path, value))) =
+            (role, self.stack.last_mut())
+        {
+            *value = Some(key_path(path, None));
+        }
+    }
+}
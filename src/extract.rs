@@ -0,0 +1,487 @@
+//! Extraction of typed fields straight from parser events.
+//!
+//! Loading builds a whole [`Yaml`] tree, only for most of it to be dropped when a program needs a
+//! handful of values. An [`Extractor`] declares the fields it needs, and picks them from the
+//! events of the parser without building any node.
+
+use std::collections::HashMap;
+
+use saphyr_parser::{Event, MarkedEventReceiver, Marker, Parser, ScanError};
+
+use crate::{loader::parse_real, ErrorCode, Schema, Yaml};
+
+/// The type of a field of an [`Extractor`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    /// A boolean, extracted as [`FieldValue::Bool`].
+    Bool,
+    /// An integer, extracted as [`FieldValue::I64`].
+    I64,
+    /// A non-negative integer, extracted as [`FieldValue::U64`].
+    U64,
+    /// An integer fitting in a `u32`, extracted as [`FieldValue::U64`].
+    U32,
+    /// An integer fitting in a `u16`, extracted as [`FieldValue::U64`].
+    U16,
+    /// A real or an integer, extracted as [`FieldValue::F64`].
+    F64,
+    /// A string, extracted as [`FieldValue::String`]. Scalars resolving to another type (e.g.:
+    /// `1.0`) must be quoted.
+    String,
+}
+
+impl FieldType {
+    /// Convert the resolved scalar `value` into a value of this type, if it is one.
+    #[allow(clippy::cast_precision_loss)]
+    fn convert(self, value: Yaml) -> Option<FieldValue> {
+        Some(match (self, value) {
+            (FieldType::Bool, Yaml::Boolean(v)) => FieldValue::Bool(v),
+            (FieldType::I64, Yaml::Integer(v)) => FieldValue::I64(v),
+            (FieldType::U64, Yaml::Integer(v)) => FieldValue::U64(v.try_into().ok()?),
+            (FieldType::U32, Yaml::Integer(v)) => FieldValue::U64(u32::try_from(v).ok()?.into()),
+            (FieldType::U16, Yaml::Integer(v)) => FieldValue::U64(u16::try_from(v).ok()?.into()),
+            (FieldType::F64, Yaml::Integer(v)) => FieldValue::F64(v as f64),
+            (FieldType::F64, Yaml::Real(v)) => FieldValue::F64(parse_real(&v)?),
+            (FieldType::String, Yaml::String(v)) => FieldValue::String(v),
+            _ => return None,
+        })
+    }
+}
+
+/// The value of a field extracted by an [`Extractor`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldValue {
+    /// The value of a [`FieldType::Bool`] field.
+    Bool(bool),
+    /// The value of a [`FieldType::I64`] field.
+    I64(i64),
+    /// The value of a [`FieldType::U64`], [`FieldType::U32`] or [`FieldType::U16`] field.
+    U64(u64),
+    /// The value of a [`FieldType::F64`] field.
+    F64(f64),
+    /// The value of a [`FieldType::String`] field.
+    String(String),
+}
+
+impl FieldValue {
+    define_as!(as_bool, bool, Bool);
+    define_as!(as_i64, i64, I64);
+    define_as!(as_u64, u64, U64);
+    define_as!(as_f64, f64, F64);
+
+    /// Return the value if it is an unsigned integer fitting in a `u32`.
+    #[must_use]
+    pub fn as_u32(&self) -> Option<u32> {
+        self.as_u64()?.try_into().ok()
+    }
+
+    /// Return the value if it is an unsigned integer fitting in a `u16`.
+    #[must_use]
+    pub fn as_u16(&self) -> Option<u16> {
+        self.as_u64()?.try_into().ok()
+    }
+
+    /// Return the value if it is a string.
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            FieldValue::String(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// The fields an [`Extractor`] found in a document.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Record {
+    /// The value of each field, by name. Fields absent from the document are missing.
+    values: HashMap<String, FieldValue>,
+}
+
+impl Record {
+    /// Return the value of the field `name`, if it was found in the document.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&FieldValue> {
+        self.values.get(name)
+    }
+
+    /// Return the number of fields found in the document.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Return whether no field was found in the document.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// A field declared on an [`Extractor`].
+#[derive(Clone, Debug)]
+struct Field {
+    /// The name under which the value is stored in the [`Record`].
+    name: String,
+    /// The path of keys (or indices, for sequences) to the value.
+    path: Vec<String>,
+    /// The expected type of the value.
+    ty: FieldType,
+}
+
+/// Extract typed fields from YAML documents, without loading them.
+///
+/// Fields are declared with a name, the path to their value and their type. Paths are made of
+/// mapping keys, and of indices in decimal for sequences, as in [`Yaml::lookup`]. Scalars are
+/// resolved as per the [`Schema`] (defaults to [`Schema::Legacy`]), then checked against the type
+/// of the field. The rest of the document is parsed, but no node is built for it.
+///
+/// ```
+/// use saphyr::{Extractor, FieldType};
+///
+/// let mut extractor = Extractor::new();
+/// extractor
+///     .field("port", &["server", "port"], FieldType::U16)
+///     .field("first_user", &["users", "0", "name"], FieldType::String)
+///     .field("debug", &["debug"], FieldType::Bool);
+///
+/// let records = extractor.extract_str("
+/// server: {host: example.com, port: 8080}
+/// users:
+///   - name: alice
+///   - name: bob
+/// ").unwrap();
+/// assert_eq!(records[0].get("port").and_then(|v| v.as_u16()), Some(8080));
+/// assert_eq!(records[0].get("first_user").and_then(|v| v.as_str()), Some("alice"));
+/// assert!(records[0].get("debug").is_none());
+///
+/// assert!(extractor.extract_str("server: {port: 70000}").is_err());
+/// ```
+///
+/// Fields are only found through aliases if the alias refers to a scalar.
+#[derive(Clone, Debug, Default)]
+pub struct Extractor {
+    /// The declared fields.
+    fields: Vec<Field>,
+    /// How scalars are resolved.
+    schema: Schema,
+}
+
+impl Extractor {
+    /// Create an extractor without any field.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare a field named `name`, whose value of type `ty` is at `path`.
+    ///
+    /// If several fields are at the same path, all of them are filled.
+    pub fn field(&mut self, name: &str, path: &[&str], ty: FieldType) -> &mut Self {
+        self.fields.push(Field {
+            name: name.to_owned(),
+            path: path.iter().map(ToString::to_string).collect(),
+            ty,
+        });
+        self
+    }
+
+    /// Set the schema by which scalars are resolved (defaults to [`Schema::Legacy`]).
+    pub fn schema(&mut self, schema: Schema) -> &mut Self {
+        self.schema = schema;
+        self
+    }
+
+    /// Extract the fields from each document of `source`.
+    ///
+    /// # Errors
+    /// Returns an [`ExtractError`] if `source` is not valid YAML, or if the value of a field is
+    /// not of its type.
+    pub fn extract_str(&self, source: &str) -> Result<Vec<Record>, ExtractError> {
+        self.extract_from_parser(&mut Parser::new_from_str(source))
+    }
+
+    /// Extract the fields from each document `parser` reads.
+    ///
+    /// # Errors
+    /// Returns an [`ExtractError`] if the input is not valid YAML, or if the value of a field is
+    /// not of its type.
+    pub fn extract_from_parser<I: Iterator<Item = char>>(
+        &self,
+        parser: &mut Parser<I>,
+    ) -> Result<Vec<Record>, ExtractError> {
+        let mut state = ExtractState {
+            extractor: self,
+            records: vec![],
+            record: Record::default(),
+            path: vec![],
+            frames: vec![],
+            scalar_anchors: HashMap::new(),
+            error: None,
+        };
+        let result = parser.load(&mut state, true);
+        if let Some(error) = state.error {
+            return Err(error);
+        }
+        result.map_err(ExtractError::Scan)?;
+        Ok(state.records)
+    }
+}
+
+/// An error that happened while extracting fields.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExtractError {
+    /// An error within the scanner. This indicates a malformed YAML input.
+    Scan(ScanError),
+    /// The value of a field is not of the type of the field.
+    InvalidValue {
+        /// The name of the field.
+        field: String,
+        /// The type of the field.
+        expected: FieldType,
+        /// The position of the value.
+        marker: Marker,
+    },
+}
+
+impl ExtractError {
+    /// Return the stable code of the error, which [`Display`] prefixes the message with.
+    ///
+    /// [`Display`]: std::fmt::Display
+    #[must_use]
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ExtractError::Scan(e) => ErrorCode::of_scan_error(e),
+            ExtractError::InvalidValue { .. } => ErrorCode::new("EXTRACT001"),
+        }
+    }
+
+    /// Return the position in the input at which the error happened.
+    #[must_use]
+    pub fn marker(&self) -> &Marker {
+        match self {
+            ExtractError::Scan(e) => e.marker(),
+            ExtractError::InvalidValue { marker, .. } => marker,
+        }
+    }
+}
+
+impl std::error::Error for ExtractError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExtractError::Scan(e) => Some(e),
+            ExtractError::InvalidValue { .. } => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.code())?;
+        match self {
+            ExtractError::Scan(e) => e.fmt(f),
+            ExtractError::InvalidValue {
+                field,
+                expected,
+                marker,
+            } => write!(
+                f,
+                "field `{field}` at line {} column {} is not of type {expected:?}",
+                marker.line(),
+                marker.col().saturating_add(1)
+            ),
+        }
+    }
+}
+
+/// A collection being parsed by an [`ExtractState`].
+enum Frame {
+    /// A sequence, with the index of its next item.
+    Sequence(usize),
+    /// A mapping.
+    Mapping {
+        /// Whether the next node is a key.
+        next_is_key: bool,
+        /// The key of the current entry, if it is a scalar.
+        key: Option<String>,
+    },
+}
+
+/// The position of a node within its parent.
+enum Position {
+    /// The node is the root of the document.
+    Root,
+    /// The node is a key of a mapping.
+    Key,
+    /// The node is an item of a sequence or a value of a mapping, at the given segment. The
+    /// segment is `None` for values whose key is not a scalar.
+    Value(Option<String>),
+}
+
+/// The receiver of events of [`Extractor::extract_from_parser`].
+struct ExtractState<'a> {
+    /// The extractor whose fields are extracted.
+    extractor: &'a Extractor,
+    /// The records of the documents parsed so far.
+    records: Vec<Record>,
+    /// The record of the current document.
+    record: Record,
+    /// The segment of each collection in `frames` but the root. Segments are `None` for keys
+    /// and values of keys which are not scalars, below which no field can be.
+    path: Vec<Option<String>>,
+    /// The collections being parsed.
+    frames: Vec<Frame>,
+    /// The values of the anchored scalars of the current document, by anchor ID.
+    scalar_anchors: HashMap<usize, Yaml>,
+    /// The first error that happened, if any. Events are ignored once it is set.
+    error: Option<ExtractError>,
+}
+
+impl<'a> ExtractState<'a> {
+    /// Return the position of the next node, and move on to the node after it.
+    fn advance(&mut self) -> Position {
+        match self.frames.last_mut() {
+            None => Position::Root,
+            Some(Frame::Sequence(index)) => {
+                let segment = index.to_string();
+                *index += 1;
+                Position::Value(Some(segment))
+            }
+            Some(Frame::Mapping { next_is_key, key }) => {
+                *next_is_key = !*next_is_key;
+                if *next_is_key {
+                    Position::Value(key.take())
+                } else {
+                    Position::Key
+                }
+            }
+        }
+    }
+
+    /// Return the first field whose value is at `position`, if any.
+    fn field_at(&self, position: &Position) -> Option<&'a Field> {
+        self.fields_at(position).next()
+    }
+
+    /// Return the fields whose value is at `position`.
+    fn fields_at<'s>(&'s self, position: &'s Position) -> impl Iterator<Item = &'a Field> + 's {
+        self.extractor
+            .fields
+            .iter()
+            .filter(move |field| match position {
+                Position::Root => field.path.is_empty(),
+                Position::Key | Position::Value(None) => false,
+                Position::Value(Some(segment)) => {
+                    field.path.len() == self.path.len() + 1
+                        && field.path.last() == Some(segment)
+                        && field
+                            .path
+                            .iter()
+                            .zip(&self.path)
+                            .all(|(a, b)| b.as_ref() == Some(a))
+                }
+            })
+    }
+
+    /// Handle a scalar whose text is `text` and which resolves to `value`, at `marker`.
+    fn scalar(&mut self, text: Option<String>, value: &Yaml, marker: Marker) {
+        let position = self.advance();
+        if let Position::Key = position {
+            if let Some(Frame::Mapping { key, .. }) = self.frames.last_mut() {
+                *key = text;
+            }
+            return;
+        }
+        let found = self
+            .fields_at(&position)
+            .map(|field| match field.ty.convert(value.clone()) {
+                Some(converted) => Ok((field.name.clone(), converted)),
+                None => Err(invalid_value(field, marker)),
+            })
+            .collect::<Result<Vec<_>, _>>();
+        match found {
+            Ok(found) => self.record.values.extend(found),
+            Err(error) => self.error = Some(error),
+        }
+    }
+
+    /// Handle the start of a collection at `marker`. Fields cannot be collections.
+    fn start_collection(&mut self, frame: Frame, marker: Marker) {
+        let position = self.advance();
+        if let Some(field) = self.field_at(&position) {
+            self.error = Some(invalid_value(field, marker));
+        }
+        match position {
+            Position::Root => {}
+            Position::Key => self.path.push(None),
+            Position::Value(segment) => self.path.push(segment),
+        }
+        self.frames.push(frame);
+    }
+
+    /// Handle the end of a collection.
+    fn end_collection(&mut self) {
+        self.frames.pop();
+        if !self.frames.is_empty() {
+            self.path.pop();
+        }
+    }
+
+    /// Handle an alias to a collection at `marker`, below which no field is looked for.
+    fn collection_alias(&mut self, marker: Marker) {
+        let position = self.advance();
+        if let Some(field) = self.field_at(&position) {
+            self.error = Some(invalid_value(field, marker));
+        }
+    }
+}
+
+/// Return the error for a value of the wrong type for `field`, at `marker`.
+fn invalid_value(field: &Field, marker: Marker) -> ExtractError {
+    ExtractError::InvalidValue {
+        field: field.name.clone(),
+        expected: field.ty,
+        marker,
+    }
+}
+
+impl MarkedEventReceiver for ExtractState<'_> {
+    fn on_event(&mut self, ev: Event, marker: Marker) {
+        if self.error.is_some() {
+            return;
+        }
+        match ev {
+            Event::Nothing | Event::StreamStart | Event::StreamEnd | Event::DocumentStart => {}
+            Event::DocumentEnd => {
+                self.records.push(std::mem::take(&mut self.record));
+                self.scalar_anchors.clear();
+            }
+            Event::SequenceStart(..) => self.start_collection(Frame::Sequence(0), marker),
+            Event::MappingStart(..) => self.start_collection(
+                Frame::Mapping {
+                    next_is_key: true,
+                    key: None,
+                },
+                marker,
+            ),
+            Event::SequenceEnd | Event::MappingEnd => self.end_collection(),
+            Event::Scalar(v, style, aid, tag) => {
+                let value = self
+                    .extractor
+                    .schema
+                    .resolve(v.clone(), style, tag.as_ref());
+                if aid > 0 {
+                    self.scalar_anchors.insert(aid, value.clone());
+                }
+                self.scalar(Some(v), &value, marker);
+            }
+            Event::Alias(id) => match self.scalar_anchors.get(&id).cloned() {
+                Some(value) => {
+                    let text = value.as_str().map(ToOwned::to_owned);
+                    self.scalar(text, &value, marker);
+                }
+                None => self.collection_alias(marker),
+            },
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! YAML trees caching a fingerprint of their structure, to compare them quickly.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use crate::{LookupError, Yaml};
+
+/// A [`Yaml`] tree caching a fingerprint of its structure, so that comparing it again is quick.
+///
+/// This is meant for reconciliation loops comparing the same trees over and over (e.g.: the
+/// desired configuration of a service against the actual one). The fingerprint is a hash of the
+/// whole tree, computed the first time it is needed. Comparing two trees then takes constant time
+/// if:
+///  - their fingerprints differ, in which case the trees differ; or
+///  - one is a clone of the other which neither mutated since, as clones share their tree.
+///
+/// Otherwise, trees with equal fingerprints are still compared in full, so that a collision of
+/// fingerprints never makes different trees equal.
+///
+/// The fingerprint of each collection of the tree is cached as well, and [`Self::fingerprint_at`]
+/// returns that of a subtree, e.g. to tell which services of a document changed. Mutating a node
+/// through [`Self::get_mut_at`] only drops the fingerprints of that node and of the collections
+/// containing it: computing the fingerprint of the tree again does not hash the subtrees which
+/// were left unchanged. [`Self::get_mut`] drops them all.
+///
+/// ```
+/// use saphyr::{Fingerprinted, Yaml};
+///
+/// let doc = Yaml::load_from_str("web: {replicas: 3}\ndb: {replicas: 1}").unwrap().remove(0);
+/// let desired = Fingerprinted::new(doc);
+/// let mut actual = desired.clone();
+/// assert_eq!(actual, desired);
+///
+/// *actual.get_mut_at(&["web", "replicas"]).unwrap() = Yaml::Integer(2);
+/// assert_ne!(actual.fingerprint(), desired.fingerprint());
+/// assert_ne!(actual, desired);
+/// assert_ne!(actual.fingerprint_at(&["web"]), desired.fingerprint_at(&["web"]));
+/// assert_eq!(actual.fingerprint_at(&["db"]), desired.fingerprint_at(&["db"]));
+/// ```
+///
+/// Fingerprints are only meaningful within a process: they may change between versions of this
+/// crate or of Rust, and must not be stored.
+#[derive(Debug)]
+pub struct Fingerprinted {
+    /// The tree, shared between clones until one of them is mutated.
+    node: Arc<Yaml>,
+    /// The fingerprints of the tree and its subtrees computed so far, shared between clones until
+    /// one of them is mutated.
+    fingerprints: Mutex<Arc<Fingerprints>>,
+}
+
+/// The fingerprint of a node, if computed, and those of its children.
+#[derive(Clone, Debug, Default)]
+struct Fingerprints {
+    /// The fingerprint of the node, once computed.
+    fingerprint: Option<u64>,
+    /// The fingerprints of the items of the node if it is a sequence, or of its values if it is a
+    /// mapping, in order. Tags are looked through. Empty until computed.
+    children: Vec<Fingerprints>,
+}
+
+impl Fingerprinted {
+    /// Wrap `node`, whose fingerprint is computed when first needed.
+    #[must_use]
+    pub fn new(node: Yaml) -> Self {
+        Self {
+            node: Arc::new(node),
+            fingerprints: Mutex::default(),
+        }
+    }
+
+    /// Return the tree.
+    #[must_use]
+    pub fn get(&self) -> &Yaml {
+        &self.node
+    }
+
+    /// Return the tree, to mutate it.
+    ///
+    /// This drops all cached fingerprints, and copies the tree first if it is shared with clones
+    /// of `self`, so that they are not altered.
+    pub fn get_mut(&mut self) -> &mut Yaml {
+        *self
+            .fingerprints
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner) = Arc::default();
+        Arc::make_mut(&mut self.node)
+    }
+
+    /// Return the node at `path`, to mutate it.
+    ///
+    /// Paths are made of segments, as for [`Yaml::lookup`]. This only drops the fingerprints of the
+    /// node and of the collections containing it, and copies the tree first if it is shared with
+    /// clones of `self`, so that they are not altered.
+    ///
+    /// # Errors
+    /// Returns a [`LookupError`] if `path` cannot be looked up.
+    pub fn get_mut_at(&mut self, path: &[&str]) -> Result<&mut Yaml, LookupError> {
+        let indices = child_indices(&self.node, path)?;
+        let cache = self
+            .fingerprints
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut fingerprints = Arc::make_mut(cache);
+        for index in indices {
+            fingerprints.fingerprint = None;
+            if index >= fingerprints.children.len() {
+                // Not computed yet.
+                break;
+            }
+            fingerprints = &mut fingerprints.children[index];
+        }
+        *fingerprints = Fingerprints::default();
+        Arc::make_mut(&mut self.node).lookup_mut(path)
+    }
+
+    /// Return the tree, copying it if it is shared with clones of `self`.
+    #[must_use]
+    pub fn into_inner(self) -> Yaml {
+        Arc::try_unwrap(self.node).unwrap_or_else(|node| (*node).clone())
+    }
+
+    /// Return the fingerprint of the tree, computing it if it is not cached yet.
+    ///
+    /// Equal trees have equal fingerprints. Different trees have different fingerprints, unless
+    /// they collide, which is very unlikely.
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint_of(&[])
+    }
+
+    /// Return the fingerprint of the subtree at `path`, computing the fingerprints of the tree if
+    /// they are not cached yet.
+    ///
+    /// Paths are made of segments, as for [`Yaml::lookup`]. Subtrees of different trees have equal
+    /// fingerprints if they are equal, and different ones if they differ, unless they collide.
+    ///
+    /// # Errors
+    /// Returns a [`LookupError`] if `path` cannot be looked up.
+    pub fn fingerprint_at(&self, path: &[&str]) -> Result<u64, LookupError> {
+        let indices = child_indices(&self.node, path)?;
+        Ok(self.fingerprint_of(&indices))
+    }
+
+    /// Return the fingerprint of the subtree reached through the children at `indices`, computing
+    /// the fingerprints of the tree if needed.
+    fn fingerprint_of(&self, indices: &[usize]) -> u64 {
+        let mut cache = self.cache();
+        if cache.fingerprint.is_none() {
+            compute(&self.node, Arc::make_mut(&mut cache));
+        }
+        let mut fingerprints: &Fingerprints = &cache;
+        for &index in indices {
+            fingerprints = &fingerprints.children[index];
+        }
+        fingerprints.fingerprint.unwrap_or_default()
+    }
+
+    /// Return the cached fingerprints.
+    fn cache(&self) -> std::sync::MutexGuard<'_, Arc<Fingerprints>> {
+        // The fingerprints are consistent even if computing them panicked.
+        self.fingerprints
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Clone for Fingerprinted {
+    fn clone(&self) -> Self {
+        Self {
+            node: Arc::clone(&self.node),
+            fingerprints: Mutex::new(Arc::clone(&self.cache())),
+        }
+    }
+}
+
+/// Compute the fingerprints of `node` and of its subtrees which `fingerprints` does not hold yet.
+fn compute(node: &Yaml, fingerprints: &mut Fingerprints) -> u64 {
+    if let Some(fingerprint) = fingerprints.fingerprint {
+        return fingerprint;
+    }
+    let mut hasher = DefaultHasher::new();
+    let mut node = node;
+    while let Yaml::Tagged(tag, tagged) = node {
+        tag.hash(&mut hasher);
+        node = tagged;
+    }
+    std::mem::discriminant(node).hash(&mut hasher);
+    match node {
+        Yaml::Array(items) => {
+            fingerprints
+                .children
+                .resize_with(items.len(), Fingerprints::default);
+            for (item, child) in items.iter().zip(&mut fingerprints.children) {
+                compute(item, child).hash(&mut hasher);
+            }
+        }
+        Yaml::Hash(hash) => {
+            fingerprints
+                .children
+                .resize_with(hash.len(), Fingerprints::default);
+            for ((key, value), child) in hash.iter().zip(&mut fingerprints.children) {
+                key.hash(&mut hasher);
+                compute(value, child).hash(&mut hasher);
+            }
+        }
+        node => node.hash(&mut hasher),
+    }
+    let fingerprint = hasher.finish();
+    fingerprints.fingerprint = Some(fingerprint);
+    fingerprint
+}
+
+/// Return the indices of the children leading from `root` to the node at `path`.
+///
+/// # Errors
+/// Returns a [`LookupError`] if `path` cannot be looked up.
+fn child_indices(root: &Yaml, path: &[&str]) -> Result<Vec<usize>, LookupError> {
+    let mut indices = Vec::with_capacity(path.len());
+    let mut node = root;
+    for (depth, segment) in path.iter().enumerate() {
+        let child = node.lookup(&[segment]).map_err(|error| LookupError {
+            path: path[..depth].iter().map(ToString::to_string).collect(),
+            ..error
+        })?;
+        let index = match node.untagged() {
+            Yaml::Hash(hash) => hash.values().position(|value| std::ptr::eq(value, child)),
+            Yaml::Array(items) => items.iter().position(|item| std::ptr::eq(item, child)),
+            _ => None,
+        };
+        indices.push(index.unwrap_or_default());
+        node = child;
+    }
+    Ok(indices)
+}
+
+impl From<Yaml> for Fingerprinted {
+    fn from(node: Yaml) -> Self {
+        Self::new(node)
+    }
+}
+
+impl PartialEq for Fingerprinted {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.node, &other.node)
+            || (self.fingerprint() == other.fingerprint() && self.node == other.node)
+    }
+}
+
+impl Eq for Fingerprinted {}
@@ -0,0 +1,283 @@
+//! Foldable regions of a document, for editors.
+
+use saphyr_parser::{
+    scanner::{Scanner, TokenType},
+    Marker, ScanError, TScalarStyle,
+};
+
+/// The kind of node a [`FoldingRegion`] spans.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FoldingKind {
+    /// A block or flow mapping.
+    Mapping,
+    /// A block or flow sequence.
+    Sequence,
+    /// A literal or folded block scalar.
+    BlockScalar,
+}
+
+/// A node spanning several lines, which an editor may fold. See [`folding_regions`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FoldingRegion {
+    /// The kind of node the region spans.
+    pub kind: FoldingKind,
+    /// The start of the region.
+    ///
+    /// For the value of an entry of a mapping or sequence, this is the start of the entry, so that
+    /// folding the region hides the node under its key.
+    pub start: Marker,
+    /// The position right after the last `char` of the node.
+    ///
+    /// Blank lines and comments following the node are not part of it.
+    pub end: Marker,
+}
+
+/// A collection whose region is not complete yet.
+struct OpenCollection {
+    /// Whether the collection is a mapping or a sequence.
+    kind: FoldingKind,
+    /// Whether the collection is in flow style.
+    flow: bool,
+    /// Whether the collection is a sequence whose entries are not indented from the mapping it is
+    /// a value of.
+    indentless: bool,
+    /// The start of the region, unless it is given by the first key of the mapping.
+    start: Option<Marker>,
+}
+
+/// An iterator over the foldable regions of a document. See [`folding_regions`].
+pub struct FoldingRegions<'input> {
+    /// The scanner producing tokens from the source.
+    scanner: Scanner<std::str::Chars<'input>>,
+    /// The lines of the source, along with the index of the `char` each starts at.
+    lines: Vec<(usize, &'input str)>,
+    /// The collections containing the current token, innermost last.
+    open: Vec<OpenCollection>,
+    /// The start of the block scalar whose end is given by the next token, if any, and the
+    /// indentation of its contents.
+    block_scalar: Option<(Marker, usize)>,
+    /// The start of the entry the next node is the value of, if any.
+    entry_start: Option<Marker>,
+    /// The start of the last key.
+    last_key: Option<Marker>,
+    /// The line of the last token, which nodes end on or after.
+    last_line: usize,
+    /// The regions completed by the last token, which are yet to be returned.
+    completed: Vec<FoldingRegion>,
+}
+
+/// Return the regions of `source` spanning several lines, for editor folding providers.
+///
+/// Regions are found from the tokens of `source`, without parsing it nor building its nodes. They
+/// are returned as soon as the token ending them is scanned, i.e. nested regions come before the
+/// ones containing them.
+///
+/// ```
+/// use saphyr::{folding_regions, FoldingKind};
+///
+/// let source = "\
+/// server:
+///   hosts:
+///   - a
+///   - b
+///   motd: |
+///     Hello
+///     World
+/// port: 80
+/// ";
+/// let lines: Vec<_> = folding_regions(source)
+///     .map(|region| (region.kind, region.start.line(), region.end.line()))
+///     .collect();
+/// assert_eq!(lines, [
+///     (FoldingKind::Sequence, 2, 4),
+///     (FoldingKind::BlockScalar, 5, 7),
+///     (FoldingKind::Mapping, 1, 7),
+///     (FoldingKind::Mapping, 1, 8),
+/// ]);
+/// ```
+///
+/// Documents being edited are often invalid. Iteration stops at the first error of the scanner,
+/// which [`FoldingRegions::error`] returns, after the regions completed before it. Errors which
+/// only parsing detects (e.g.: a mapping key without value in a sequence) do not stop it.
+#[must_use]
+pub fn folding_regions(source: &str) -> FoldingRegions<'_> {
+    let mut lines = Vec::new();
+    let mut index = 0;
+    for line in source.split('\n') {
+        lines.push((index, line));
+        index += line.chars().count() + 1;
+    }
+    FoldingRegions {
+        scanner: Scanner::new(source.chars()),
+        lines,
+        open: Vec::new(),
+        block_scalar: None,
+        entry_start: None,
+        last_key: None,
+        last_line: 1,
+        completed: Vec::new(),
+    }
+}
+
+impl FoldingRegions<'_> {
+    /// Return the error which stopped the iteration, if any.
+    #[must_use]
+    pub fn error(&self) -> Option<ScanError> {
+        self.scanner.get_error()
+    }
+
+    /// Update the open regions with the token of type `token` at `marker`.
+    fn on_token(&mut self, marker: Marker, token: &TokenType) {
+        if let Some((start, indent)) = self.block_scalar.take() {
+            // Less indented comments follow the scalar.
+            let end = self.content_end(marker, indent);
+            self.complete(FoldingKind::BlockScalar, start, end);
+        }
+        // Indentless sequences end at the next key of the mapping, or at its end.
+        if matches!(token, TokenType::Key | TokenType::BlockEnd)
+            && self.open.last().is_some_and(|open| open.indentless)
+        {
+            self.close(marker);
+        }
+
+        match token {
+            TokenType::BlockMappingStart => self.open(FoldingKind::Mapping, false, None),
+            TokenType::BlockSequenceStart => {
+                self.open(FoldingKind::Sequence, false, Some(marker));
+            }
+            TokenType::FlowMappingStart => self.open(FoldingKind::Mapping, true, Some(marker)),
+            TokenType::FlowSequenceStart => self.open(FoldingKind::Sequence, true, Some(marker)),
+            TokenType::BlockEnd => self.close(marker),
+            TokenType::FlowMappingEnd | TokenType::FlowSequenceEnd => {
+                // The end is right after the closing bracket.
+                self.last_line = marker.line();
+                self.close(Marker::new(
+                    marker.index() + 1,
+                    marker.line(),
+                    marker.col() + 1,
+                ));
+            }
+            TokenType::BlockEntry => {
+                let in_mapping = self
+                    .open
+                    .last()
+                    .is_some_and(|open| !open.flow && open.kind == FoldingKind::Mapping);
+                if in_mapping {
+                    let start = self.entry_start.take();
+                    self.open.push(OpenCollection {
+                        kind: FoldingKind::Sequence,
+                        flow: false,
+                        indentless: true,
+                        start: start.or(Some(marker)),
+                    });
+                }
+                self.entry_start = Some(marker);
+            }
+            TokenType::Key => {
+                if let Some(open) = self.open.last_mut() {
+                    open.start.get_or_insert(marker);
+                }
+                self.last_key = Some(marker);
+                self.entry_start = None;
+            }
+            TokenType::Value => self.entry_start = self.last_key,
+            TokenType::Scalar(TScalarStyle::Literal | TScalarStyle::Folded, _) => {
+                // Block scalar tokens start at their contents.
+                let start = self.entry_start.take().unwrap_or(marker);
+                self.block_scalar = Some((start, marker.col()));
+            }
+            // Properties come before the node they are the properties of.
+            TokenType::Anchor(_) | TokenType::Tag(..) => {}
+            TokenType::StreamEnd | TokenType::DocumentStart | TokenType::DocumentEnd => {
+                while !self.open.is_empty() {
+                    self.close(marker);
+                }
+                self.entry_start = None;
+            }
+            _ => self.entry_start = None,
+        }
+        if !matches!(
+            token,
+            TokenType::BlockEnd | TokenType::StreamEnd | TokenType::DocumentEnd
+        ) {
+            self.last_line = self.last_line.max(marker.line());
+        }
+    }
+
+    /// Open a collection whose region starts at `start`, or at the entry it is the value of.
+    fn open(&mut self, kind: FoldingKind, flow: bool, start: Option<Marker>) {
+        let start = self.entry_start.take().or(start);
+        self.open.push(OpenCollection {
+            kind,
+            flow,
+            indentless: false,
+            start,
+        });
+    }
+
+    /// Close the innermost collection, whose contents end before `limit`.
+    ///
+    /// Flow collections are closed right after their closing bracket, unless they are left
+    /// unclosed at the end of a document.
+    fn close(&mut self, limit: Marker) {
+        let Some(open) = self.open.pop() else {
+            return;
+        };
+        let end = self.content_end(limit, usize::MAX);
+        if let Some(start) = open.start {
+            self.complete(open.kind, start, end);
+        }
+    }
+
+    /// Record the region from `start` to `end`, if it spans several lines.
+    fn complete(&mut self, kind: FoldingKind, start: Marker, end: Marker) {
+        if end.line() > start.line() {
+            self.completed.push(FoldingRegion { kind, start, end });
+        }
+    }
+
+    /// Return the end of the contents preceding `limit`, skipping blank lines and comments
+    /// indented less than `comment_indent`. Contents do not end before the line of the last token.
+    fn content_end(&self, limit: Marker, comment_indent: usize) -> Marker {
+        let mut line = limit.line();
+        let mut col = Some(limit.col());
+        // The end of the stream may be past the last line.
+        if line > self.lines.len() {
+            line = self.lines.len();
+            col = None;
+        }
+        while let Some(&(index, text)) = self.lines.get(line.wrapping_sub(1)) {
+            let text = match col.take() {
+                Some(col) => text
+                    .char_indices()
+                    .nth(col)
+                    .map_or(text, |(offset, _)| &text[..offset]),
+                None => text,
+            };
+            let contents = text.trim_end();
+            let trimmed = contents.trim_start();
+            let indent = contents.len() - trimmed.len();
+            let blank = trimmed.is_empty() || (trimmed.starts_with('#') && indent < comment_indent);
+            if !blank || line <= self.last_line {
+                let col = contents.chars().count();
+                return Marker::new(index + col, line, col);
+            }
+            line -= 1;
+        }
+        limit
+    }
+}
+
+impl Iterator for FoldingRegions<'_> {
+    type Item = FoldingRegion;
+
+    fn next(&mut self) -> Option<FoldingRegion> {
+        while self.completed.is_empty() {
+            let token = self.scanner.next()?;
+            self.on_token(token.0, &token.1);
+            // Regions completed by the same token are returned innermost first.
+            self.completed.reverse();
+        }
+        self.completed.pop()
+    }
+}
@@ -0,0 +1,366 @@
+//! Partial formatting of YAML documents, normalizing chosen aspects of their layout.
+
+use saphyr_parser::{
+    scanner::{Scanner, Token, TokenType},
+    ScanError, TScalarStyle,
+};
+
+use crate::{apply_patch, TextEdit};
+
+/// A formatter normalizing only the aspects of a document it is asked to.
+///
+/// Re-emitting a document normalizes all of its layout, and loses its comments and the style of
+/// its scalars. This rather edits the whitespace of the text: comments, scalars, quoting and the
+/// spacing within lines (e.g.: values aligned after their keys) are left untouched. Nothing is
+/// normalized by default.
+///
+/// ```
+/// use saphyr::Formatter;
+///
+/// let source = "\
+/// server:
+///     host:  'localhost'   # aligned
+///     ports:
+///     - 80
+///     - 443
+///     motd: |
+///         Hello
+/// ";
+/// let mut formatter = Formatter::new();
+/// formatter.indent(2);
+/// assert_eq!(formatter.format(source).unwrap(), "\
+/// server:
+///   host:  'localhost'   # aligned
+///   ports:
+///   - 80
+///   - 443
+///   motd: |
+///       Hello
+/// ");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Formatter {
+    /// The number of spaces block collections are indented by from their parent, if normalized.
+    indent: Option<usize>,
+    /// Whether to remove whitespace at the end of lines.
+    strip_trailing_whitespace: bool,
+}
+
+/// A line of the text being formatted.
+struct Line<'input> {
+    /// The offset in bytes of the start of the line.
+    offset: usize,
+    /// The text of the line, without its line break.
+    text: &'input str,
+    /// The number of spaces the line starts with.
+    indent: usize,
+    /// How many columns to move the line by, once known.
+    shift: Option<isize>,
+    /// Whether the line is in the contents of a block scalar, where whitespace is significant.
+    verbatim: bool,
+}
+
+/// A block collection containing the current token.
+struct Block {
+    /// Whether the collection is a mapping or a sequence.
+    mapping: bool,
+    /// The column of the keys or entries of the collection, once known.
+    old_col: Option<usize>,
+    /// The column at which the keys or entries of the collection are moved.
+    new_col: usize,
+    /// Whether the collection is a sequence whose entries are not indented from the mapping it is
+    /// a value of.
+    indentless: bool,
+}
+
+impl Block {
+    /// Return how many columns the keys or entries of the collection move by.
+    fn shift(&self) -> isize {
+        to_isize(self.new_col) - to_isize(self.old_col.unwrap_or(self.new_col))
+    }
+}
+
+impl Formatter {
+    /// Create a formatter normalizing nothing.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Indent block collections by `width` spaces from their parent.
+    ///
+    /// Sequences which are not indented from the mapping they are a value of (e.g.: `key:\n- a`)
+    /// stay so. Lines that are not keys or entries of block collections (e.g.: the contents of a
+    /// block scalar, or of a flow collection spanning several lines) move along with the line
+    /// they belong to, so that their own indentation is kept.
+    pub fn indent(&mut self, width: usize) -> &mut Self {
+        self.indent = Some(width);
+        self
+    }
+
+    /// Set whether to remove whitespace at the end of lines (defaults to `false`).
+    ///
+    /// Whitespace is kept in the contents of block scalars, where it is significant.
+    pub fn strip_trailing_whitespace(&mut self, strip: bool) -> &mut Self {
+        self.strip_trailing_whitespace = strip;
+        self
+    }
+
+    /// Format `source` as per the options set on `self`.
+    ///
+    /// # Errors
+    /// Returns `ScanError` if the tokens of `source` cannot be scanned (e.g.: a quoted scalar is
+    /// not closed). Errors which only parsing detects are not reported.
+    pub fn format(&self, source: &str) -> Result<String, ScanError> {
+        Ok(apply_patch(source, &self.edits(source)?))
+    }
+
+    /// Return the edits formatting `source` as per the options set on `self`, e.g. for editors
+    /// formatting documents through a language server.
+    ///
+    /// # Errors
+    /// Returns `ScanError` if the tokens of `source` cannot be scanned. See [`Self::format`].
+    pub fn edits(&self, source: &str) -> Result<Vec<TextEdit>, ScanError> {
+        let mut lines: Vec<Line> = Vec::new();
+        let mut offset = 0;
+        for text in source.split('\n') {
+            lines.push(Line {
+                offset,
+                text,
+                indent: text.len() - text.trim_start_matches(' ').len(),
+                shift: None,
+                verbatim: false,
+            });
+            offset += text.len() + 1;
+        }
+        if self.indent.is_some() {
+            self.shift_lines(source, &mut lines)?;
+        } else {
+            let mut scanner = Scanner::new(source.chars());
+            mark_block_scalars(&mut lines, scanner.by_ref());
+            if let Some(error) = scanner.get_error() {
+                return Err(error);
+            }
+        }
+
+        let mut edits = vec![];
+        for line in &lines {
+            let shift = line.shift.unwrap_or_default();
+            let indent = usize::try_from(to_isize(line.indent) + shift).unwrap_or_default();
+            if indent != line.indent {
+                edits.push(TextEdit {
+                    range: line.offset..line.offset + line.indent,
+                    replacement: " ".repeat(indent),
+                });
+            }
+            if self.strip_trailing_whitespace && !line.verbatim {
+                let text = line.text.strip_suffix('\r').unwrap_or(line.text);
+                let trimmed = text.trim_end_matches([' ', '\t']);
+                // Whitespace-only lines are part of their indentation.
+                let start = if indent == line.indent {
+                    trimmed.len()
+                } else {
+                    trimmed.len().max(line.indent)
+                };
+                if start < text.len() {
+                    edits.push(TextEdit {
+                        range: line.offset + start..line.offset + text.len(),
+                        replacement: String::new(),
+                    });
+                }
+            }
+        }
+        Ok(edits)
+    }
+
+    /// Compute how many columns each of `lines` moves by to normalize indentation.
+    fn shift_lines(&self, source: &str, lines: &mut [Line]) -> Result<(), ScanError> {
+        let width = self.indent.unwrap_or_default();
+        let mut blocks: Vec<Block> = vec![];
+        let mut flow_depth = 0_usize;
+        // The shift of the lines of the outermost flow collection, once it started.
+        let mut flow_shift = 0;
+        // The line of the header of the block scalar whose contents end at the next token.
+        let mut block_scalar = None;
+        let mut last_line = 0;
+
+        let mut scanner = Scanner::new(source.chars());
+        for Token(marker, token) in scanner.by_ref() {
+            let line = marker.line() - 1;
+            let header_line = std::mem::replace(&mut last_line, line);
+            if let Some(header) = block_scalar.take() {
+                let shift = lines.get(header).and_then(|line: &Line| line.shift);
+                for line in lines.iter_mut().take(line).skip(header + 1) {
+                    line.shift = shift;
+                    line.verbatim = true;
+                }
+            }
+            if line >= lines.len() {
+                continue;
+            }
+
+            if flow_depth == 0 {
+                match token {
+                    TokenType::BlockMappingStart | TokenType::BlockSequenceStart => {
+                        blocks.push(Block {
+                            mapping: token == TokenType::BlockMappingStart,
+                            old_col: None,
+                            new_col: 0,
+                            indentless: false,
+                        });
+                        continue;
+                    }
+                    TokenType::BlockEnd => {
+                        if blocks.last().is_some_and(|block| block.indentless) {
+                            blocks.pop();
+                        }
+                        blocks.pop();
+                        continue;
+                    }
+                    TokenType::Key => {
+                        if blocks.last().is_some_and(|block| block.indentless) {
+                            blocks.pop();
+                        }
+                        resolve(&mut blocks, lines, marker.col(), line, width);
+                    }
+                    TokenType::BlockEntry => {
+                        // Block entry tokens are after their `-`.
+                        let col = dash_col(lines[line].text, marker.col());
+                        let indentless = match blocks.last() {
+                            Some(top) if top.mapping => top.old_col == Some(col),
+                            _ => false,
+                        };
+                        if indentless {
+                            let new_col = blocks.last().map_or(col, |top| top.new_col);
+                            blocks.push(Block {
+                                mapping: false,
+                                old_col: Some(col),
+                                new_col,
+                                indentless: true,
+                            });
+                        } else {
+                            resolve(&mut blocks, lines, col, line, width);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            if lines[line].shift.is_none() {
+                lines[line].shift = Some(if flow_depth > 0 {
+                    flow_shift
+                } else {
+                    blocks.last().map_or(0, Block::shift)
+                });
+            }
+            match token {
+                TokenType::FlowSequenceStart | TokenType::FlowMappingStart => {
+                    if flow_depth == 0 {
+                        flow_shift = lines[line].shift.unwrap_or_default();
+                    }
+                    flow_depth += 1;
+                }
+                TokenType::FlowSequenceEnd | TokenType::FlowMappingEnd => {
+                    flow_depth = flow_depth.saturating_sub(1);
+                }
+                TokenType::Scalar(TScalarStyle::Literal | TScalarStyle::Folded, _) => {
+                    // Block scalar tokens start at their contents, after their header.
+                    block_scalar = Some(header_line);
+                }
+                _ => {}
+            }
+        }
+        if let Some(error) = scanner.get_error() {
+            return Err(error);
+        }
+
+        shift_lines_without_tokens(lines);
+        Ok(())
+    }
+}
+
+/// Compute the shift of the `lines` without tokens, once the others are known.
+///
+/// Those lines continue the node of the last line with tokens, unless they are comments aligned
+/// with a line of their own.
+fn shift_lines_without_tokens(lines: &mut [Line]) {
+    let mut previous: Option<(usize, isize)> = None;
+    for i in 0..lines.len() {
+        if let Some(shift) = lines[i].shift {
+            if !lines[i].text.trim().is_empty() {
+                previous = Some((lines[i].indent, shift));
+            }
+            continue;
+        }
+        let trimmed = lines[i].text.trim();
+        let shift = if trimmed.is_empty() {
+            0
+        } else if trimmed.starts_with('#') {
+            let next = lines[i + 1..]
+                .iter()
+                .find(|line| {
+                    let trimmed = line.text.trim();
+                    !trimmed.is_empty() && !trimmed.starts_with('#')
+                })
+                .and_then(|line| Some((line.indent, line.shift?)));
+            match (next, previous) {
+                (Some((indent, shift)), _) | (_, Some((indent, shift)))
+                    if indent == lines[i].indent =>
+                {
+                    shift
+                }
+                _ => 0,
+            }
+        } else {
+            previous.map_or(0, |(_, shift)| shift)
+        };
+        lines[i].shift = Some(shift);
+    }
+}
+
+/// Set the columns of the innermost block collection, whose key or entry is at `col` on `line`,
+/// if they are not known yet.
+fn resolve(blocks: &mut [Block], lines: &[Line], col: usize, line: usize, width: usize) {
+    let Some((top, parents)) = blocks.split_last_mut() else {
+        return;
+    };
+    if top.old_col.is_some() {
+        return;
+    }
+    top.old_col = Some(col);
+    top.new_col = match (lines[line].shift, parents.last()) {
+        // The collection starts after another node on its line (e.g.: `- a: 1`).
+        (Some(shift), _) => usize::try_from(to_isize(col) + shift).unwrap_or_default(),
+        (None, Some(parent)) => parent.new_col + width,
+        (None, None) => col,
+    };
+}
+
+/// Mark the lines of the contents of block scalars as verbatim.
+fn mark_block_scalars<I: Iterator<Item = Token>>(lines: &mut [Line], tokens: I) {
+    let mut block_scalar = None;
+    for Token(marker, token) in tokens {
+        if let Some(start) = block_scalar.take() {
+            for line in lines.iter_mut().take(marker.line() - 1).skip(start) {
+                line.verbatim = true;
+            }
+        }
+        if let TokenType::Scalar(TScalarStyle::Literal | TScalarStyle::Folded, _) = token {
+            block_scalar = Some(marker.line() - 1);
+        }
+    }
+}
+
+/// Return the column of the `-` of the block entry token at `col` on the line `text`.
+fn dash_col(text: &str, col: usize) -> usize {
+    let before: Vec<char> = text.chars().take(col).collect();
+    before
+        .iter()
+        .rposition(|&c| !matches!(c, ' ' | '\t'))
+        .unwrap_or_default()
+}
+
+/// Convert a column to a signed number of columns.
+fn to_isize(col: usize) -> isize {
+    isize::try_from(col).unwrap_or(isize::MAX)
+}
@@ -0,0 +1,662 @@
+//! Adapters for the input of the parser.
+//!
+//! The parser reads its input from any `Iterator<Item = char>`. The adapters in this module wrap
+//! such an iterator to observe or alter the input before the parser sees it.
+
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use saphyr_parser::Marker;
+
+use crate::{lex_line, LineState, LoadError};
+
+/// How far into its input the parser is. See [`ProgressInput`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+    /// The number of bytes (in UTF-8) read from the input so far.
+    pub bytes_read: usize,
+    /// The total size of the input in bytes (in UTF-8), if it is known.
+    pub total_bytes: Option<usize>,
+    /// The position in the input right after the last `char` read.
+    pub marker: Marker,
+}
+
+impl Progress {
+    /// Return the fraction of the input read so far, between `0.0` and `1.0`.
+    ///
+    /// Return `None` if the total size of the input is unknown.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn fraction(&self) -> Option<f64> {
+        match self.total_bytes? {
+            0 => Some(1.0),
+            total => Some((self.bytes_read as f64 / total as f64).min(1.0)),
+        }
+    }
+}
+
+/// An input adapter reporting progress as the parser reads its input.
+///
+/// The callback is invoked each time at least `every` bytes were read since it was last invoked,
+/// and once more when the end of the input is reached. This allows showing progress when loading
+/// very large documents.
+///
+/// Note that the parser reads a few `char`s ahead of the events it emits. Progress thus reflects
+/// how much input was read, rather than how much was parsed.
+///
+/// ```
+/// use saphyr::{ProgressInput, Yaml};
+///
+/// let source = "- item\n".repeat(1000);
+/// let mut reports = vec![];
+/// let input = ProgressInput::new_from_str(&source, 1024, |progress| {
+///     reports.push(progress.fraction().unwrap());
+/// });
+/// let docs = Yaml::load_from_iter(input).unwrap();
+/// assert_eq!(docs[0].as_vec().unwrap().len(), 1000);
+/// assert_eq!(reports.len(), 7);
+/// assert_eq!(reports.last(), Some(&1.0));
+/// ```
+pub struct ProgressInput<I, F> {
+    /// The input we read from.
+    inner: I,
+    /// The function to call when reporting progress.
+    callback: F,
+    /// The number of bytes between two reports.
+    every: usize,
+    /// The number of bytes after which to report progress next.
+    next_report: usize,
+    /// Whether we reached the end of `inner`.
+    finished: bool,
+    /// The progress so far.
+    progress: Progress,
+}
+
+impl<I, F> ProgressInput<I, F>
+where
+    I: Iterator<Item = char>,
+    F: FnMut(&Progress),
+{
+    /// Wrap `inner`, calling `callback` every `every` bytes.
+    ///
+    /// The total size of the input is unknown. Use [`Self::total_bytes`] to provide it.
+    pub fn new(inner: I, every: usize, callback: F) -> Self {
+        let every = every.max(1);
+        Self {
+            inner,
+            callback,
+            every,
+            next_report: every,
+            finished: false,
+            progress: Progress {
+                bytes_read: 0,
+                total_bytes: None,
+                marker: Marker::new(0, 1, 0),
+            },
+        }
+    }
+
+    /// Set the total size of the input in bytes, so that [`Progress::fraction`] is available.
+    #[must_use]
+    pub fn total_bytes(mut self, total_bytes: usize) -> Self {
+        self.progress.total_bytes = Some(total_bytes);
+        self
+    }
+
+    /// Return the progress so far.
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+}
+
+impl<'input, F> ProgressInput<std::str::Chars<'input>, F>
+where
+    F: FnMut(&Progress),
+{
+    /// Read from `source`, calling `callback` every `every` bytes.
+    ///
+    /// The total size of the input is known.
+    pub fn new_from_str(source: &'input str, every: usize, callback: F) -> Self {
+        Self::new(source.chars(), every, callback).total_bytes(source.len())
+    }
+}
+
+impl<I, F> Iterator for ProgressInput<I, F>
+where
+    I: Iterator<Item = char>,
+    F: FnMut(&Progress),
+{
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let Some(c) = self.inner.next() else {
+            if !self.finished {
+                self.finished = true;
+                (self.callback)(&self.progress);
+            }
+            return None;
+        };
+
+        let marker = &self.progress.marker;
+        self.progress.marker = if c == '\n' {
+            Marker::new(marker.index() + 1, marker.line() + 1, 0)
+        } else {
+            Marker::new(marker.index() + 1, marker.line(), marker.col() + 1)
+        };
+        self.progress.bytes_read += c.len_utf8();
+        if self.progress.bytes_read >= self.next_report {
+            self.next_report = self.progress.bytes_read + self.every;
+            (self.callback)(&self.progress);
+        }
+        Some(c)
+    }
+}
+
+/// A token allowing to cancel a parse from another thread.
+///
+/// Clones of a token share the same state: cancelling one cancels all of them.
+///
+/// ```
+/// use saphyr::{CancellationToken, LoadError, Yaml, YamlLoader};
+///
+/// let token = CancellationToken::new();
+/// let mut loader = YamlLoader::<Yaml>::default();
+/// loader.cancellation(token.clone());
+/// // This would usually be done from another thread, e.g.: when the user clicks a button.
+/// token.cancel();
+/// assert!(matches!(loader.load_from_str("a: b"), Err(LoadError::Cancelled)));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    /// Whether the token was cancelled.
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Create a new token which is not cancelled.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the token and all its clones.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Return whether the token was cancelled.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// An input adapter which stops yielding `char`s once its [`CancellationToken`] is cancelled.
+///
+/// The parser then stops promptly, usually with an error about the unexpected end of the input.
+/// Since it may also stop successfully (e.g.: if cancelled between two documents), the token should
+/// be checked after the parser returns. [`YamlLoader::cancellation`] does so and reports
+/// [`LoadError::Cancelled`].
+///
+/// [`YamlLoader::cancellation`]: crate::YamlLoader::cancellation
+/// [`LoadError::Cancelled`]: crate::LoadError::Cancelled
+pub struct CancellableInput<I> {
+    /// The input we read from.
+    inner: I,
+    /// The token to check before yielding each `char`.
+    token: CancellationToken,
+}
+
+impl<I: Iterator<Item = char>> CancellableInput<I> {
+    /// Wrap `inner`, stopping once `token` is cancelled.
+    pub fn new(inner: I, token: CancellationToken) -> Self {
+        Self { inner, token }
+    }
+}
+
+impl<I: Iterator<Item = char>> Iterator for CancellableInput<I> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if self.token.is_cancelled() {
+            None
+        } else {
+            self.inner.next()
+        }
+    }
+}
+
+/// An input adapter which stops yielding `char`s once a line exceeds a maximum length.
+///
+/// This is meant for protocol-style usage, where inputs are expected to be made of short lines
+/// and overly long ones indicate abuse. The length of a line counts its `char`s, line break
+/// excluded. Once a line goes over the limit, the adapter behaves as if the input ended, and
+/// [`Self::exceeded`] returns the position of the first `char` past the limit.
+/// [`YamlLoader::max_line_length`] uses this adapter and reports [`LoadError::LineTooLong`].
+///
+/// Positions are tracked with saturating arithmetic, so that inputs with absurdly long lines do
+/// not cause overflows, with or without a limit.
+///
+/// ```
+/// use saphyr::{LineLimitedInput, Yaml};
+///
+/// let mut input = LineLimitedInput::new("key: a\nlonger: line".chars(), 10);
+/// // The input is cut short, which the parser may not notice: always check the adapter.
+/// let docs = Yaml::load_from_iter(&mut input).unwrap();
+/// assert_eq!(docs[0]["longer"].as_str(), Some("li"));
+/// let marker = input.exceeded().unwrap();
+/// assert_eq!((marker.line(), marker.col()), (2, 10));
+/// ```
+///
+/// [`YamlLoader::max_line_length`]: crate::YamlLoader::max_line_length
+/// [`LoadError::LineTooLong`]: crate::LoadError::LineTooLong
+pub struct LineLimitedInput<I> {
+    /// The input we read from.
+    inner: I,
+    /// The maximum number of `char`s on a line.
+    max_line_length: usize,
+    /// The position of the next `char` of the input.
+    index: usize,
+    /// The line of the next `char` of the input.
+    line: usize,
+    /// The column of the next `char` of the input.
+    col: usize,
+    /// The position of the first `char` past the limit, once the limit is exceeded.
+    exceeded: Option<Marker>,
+}
+
+impl<I: Iterator<Item = char>> LineLimitedInput<I> {
+    /// Wrap `inner`, stopping once a line is longer than `max_line_length` `char`s.
+    pub fn new(inner: I, max_line_length: usize) -> Self {
+        Self {
+            inner,
+            max_line_length,
+            index: 0,
+            line: 1,
+            col: 0,
+            exceeded: None,
+        }
+    }
+
+    /// Return the position of the first `char` past the limit, if it was exceeded.
+    #[must_use]
+    pub fn exceeded(&self) -> Option<Marker> {
+        self.exceeded
+    }
+}
+
+impl<I: Iterator<Item = char>> Iterator for LineLimitedInput<I> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if self.exceeded.is_some() {
+            return None;
+        }
+        let c = self.inner.next()?;
+        if c == '\n' || c == '\r' {
+            if c == '\n' {
+                self.line = self.line.saturating_add(1);
+            }
+            self.col = 0;
+        } else if self.col >= self.max_line_length {
+            self.exceeded = Some(Marker::new(self.index, self.line, self.col));
+            return None;
+        } else {
+            self.col = self.col.saturating_add(1);
+        }
+        self.index = self.index.saturating_add(1);
+        Some(c)
+    }
+}
+
+/// An input adapter fixing common issues of hand-written inputs before the parser sees them.
+///
+/// None of the transformations is enabled by default. They apply to the whole input, including
+/// the contents of quoted scalars:
+///   * [`Self::expand_tabs`] replaces tabs with spaces, up to the next tab stop. This allows
+///     loading documents indented with tabs, which YAML forbids.
+///   * [`Self::expand_indentation_tabs`] does the same, but only for tabs within the
+///     indentation of lines, and records where it did so.
+///   * [`Self::strip_trailing_whitespace`] removes spaces and tabs at the end of lines.
+///
+/// Markers reported by the parser point into the transformed input. Use [`Self::original_marker`]
+/// to find the corresponding position in the original input. To do so, the parser must be given
+/// a mutable reference to the adapter, rather than the adapter itself:
+///
+/// ```
+/// use saphyr::{MarkedYaml, PreprocessedInput};
+///
+/// let source = "a:\n\tb:\tc  \n";
+/// let mut input = PreprocessedInput::new(source.chars())
+///     .expand_tabs(4)
+///     .strip_trailing_whitespace(true);
+/// let docs = MarkedYaml::load_from_iter(&mut input).unwrap();
+/// let c = &docs[0].data["a"].data["b"];
+/// assert_eq!(c.data.as_str(), Some("c"));
+/// // Once tabs are expanded, `c` is at column 8. It is at column 4 in `source`.
+/// assert_eq!(c.marker.col(), 8);
+/// assert_eq!(input.original_marker(&c.marker).col(), 4);
+/// ```
+pub struct PreprocessedInput<I> {
+    /// The input we read from.
+    inner: I,
+    /// The distance between two tab stops, if tabs must be expanded.
+    tab_width: Option<usize>,
+    /// The distance between two tab stops, if tabs in indentation must be expanded.
+    indentation_tab_width: Option<usize>,
+    /// Whether to remove blanks at the end of lines.
+    strip_trailing_whitespace: bool,
+    /// Transformed `char`s that are yet to be yielded.
+    pending: VecDeque<char>,
+    /// Blanks read from `inner` which may be trailing whitespace.
+    blanks: Vec<char>,
+    /// The index of the next `char` to be transformed, in the output.
+    output_index: usize,
+    /// The column of the next `char` to be transformed, in the output.
+    output_col: usize,
+    /// The position of the next `char` to be transformed, in `inner`.
+    original_index: usize,
+    /// The line of the next `char` to be transformed, in `inner`.
+    original_line: usize,
+    /// The column of the next `char` to be transformed, in `inner`.
+    original_col: usize,
+    /// Whether only blanks were transformed since the start of the line.
+    in_indentation: bool,
+    /// The output of the current line so far. Only kept if tabs in indentation are expanded.
+    line: String,
+    /// The state of [`lex_line`] at the start of the current line, telling whether it is within
+    /// a block scalar. Only tracked if tabs in indentation are expanded.
+    line_state: LineState,
+    /// The position of the first tab expanded in the indentation of each line.
+    expanded_indentation_tabs: Vec<Marker>,
+    /// The points at which the correspondence between output and original indices changes.
+    checkpoints: Vec<Checkpoint>,
+}
+
+/// A point from which output indices map to original indices in a new way.
+#[derive(Clone, Copy, Debug)]
+struct Checkpoint {
+    /// The index in the output from which this checkpoint applies.
+    output_index: usize,
+    /// The index in the original input `output_index` corresponds to.
+    original_index: usize,
+    /// Whether the following output indices all correspond to `original_index` (e.g.: a tab
+    /// expanded into several spaces) rather than to subsequent original indices.
+    fixed: bool,
+}
+
+impl<I: Iterator<Item = char>> PreprocessedInput<I> {
+    /// Wrap `inner`, without any transformation enabled.
+    pub fn new(inner: I) -> Self {
+        Self {
+            inner,
+            tab_width: None,
+            indentation_tab_width: None,
+            strip_trailing_whitespace: false,
+            pending: VecDeque::new(),
+            blanks: vec![],
+            output_index: 0,
+            output_col: 0,
+            original_index: 0,
+            original_line: 1,
+            original_col: 0,
+            in_indentation: true,
+            line: String::new(),
+            line_state: LineState::new(),
+            expanded_indentation_tabs: vec![],
+            checkpoints: vec![Checkpoint {
+                output_index: 0,
+                original_index: 0,
+                fixed: false,
+            }],
+        }
+    }
+
+    /// Replace tabs with spaces, with a tab stop every `width` columns.
+    #[must_use]
+    pub fn expand_tabs(mut self, width: usize) -> Self {
+        self.tab_width = Some(width.max(1));
+        self
+    }
+
+    /// Replace tabs within the indentation of lines with spaces, with a tab stop every `width`
+    /// columns.
+    ///
+    /// The indentation of a line is made of the spaces and tabs at its start. Within the contents
+    /// of a block scalar, it ends at the indentation of the contents (or at their minimal
+    /// indentation, on their first line): tabs past it are part of the contents, and are kept.
+    #[must_use]
+    pub fn expand_indentation_tabs(mut self, width: usize) -> Self {
+        self.indentation_tab_width = Some(width.max(1));
+        self
+    }
+
+    /// Return the position in the original input of the first tab expanded in the indentation of
+    /// each line so far, if [`Self::expand_indentation_tabs`] is set.
+    #[must_use]
+    pub fn expanded_indentation_tabs(&self) -> &[Marker] {
+        &self.expanded_indentation_tabs
+    }
+
+    /// Set whether to remove spaces and tabs at the end of lines (defaults to `false`).
+    #[must_use]
+    pub fn strip_trailing_whitespace(mut self, strip: bool) -> Self {
+        self.strip_trailing_whitespace = strip;
+        self
+    }
+
+    /// Return the position in the original input corresponding to `marker`.
+    ///
+    /// `marker` must point into the input yielded so far. Positions within an expanded tab all
+    /// map to the tab itself.
+    #[must_use]
+    pub fn original_marker(&self, marker: &Marker) -> Marker {
+        let index = self.original_index_of(marker.index());
+        let line_start = self.original_index_of(marker.index().saturating_sub(marker.col()));
+        Marker::new(index, marker.line(), index - line_start)
+    }
+
+    /// Return the index in the original input corresponding to the output index `index`.
+    fn original_index_of(&self, index: usize) -> usize {
+        let position = self
+            .checkpoints
+            .partition_point(|checkpoint| checkpoint.output_index <= index);
+        let checkpoint = self.checkpoints[position - 1];
+        if checkpoint.fixed {
+            checkpoint.original_index
+        } else {
+            checkpoint.original_index + (index - checkpoint.output_index)
+        }
+    }
+
+    /// Record that the correspondence between output and original indices changes here.
+    fn checkpoint(&mut self, fixed: bool) {
+        self.checkpoints.push(Checkpoint {
+            output_index: self.output_index,
+            original_index: self.original_index,
+            fixed,
+        });
+    }
+
+    /// Transform the original `char` `c` and queue the result.
+    fn transform(&mut self, c: char) {
+        let indentation = self.in_indentation
+            && self.indentation_tab_width.is_some()
+            && self
+                .line_state
+                .block_scalar_indent()
+                .map_or(true, |indent| self.output_col < indent);
+        let tab_width = if indentation {
+            self.indentation_tab_width
+        } else {
+            self.tab_width
+        };
+        match tab_width {
+            Some(width) if c == '\t' => {
+                if indentation
+                    && self
+                        .expanded_indentation_tabs
+                        .last()
+                        .map_or(true, |marker| marker.line() != self.original_line)
+                {
+                    self.expanded_indentation_tabs.push(Marker::new(
+                        self.original_index,
+                        self.original_line,
+                        self.original_col,
+                    ));
+                }
+                let spaces = width - self.output_col % width;
+                self.checkpoint(true);
+                self.pending.extend(std::iter::repeat(' ').take(spaces));
+                self.output_index += spaces;
+                self.output_col += spaces;
+                if self.indentation_tab_width.is_some() {
+                    self.line.extend(std::iter::repeat(' ').take(spaces));
+                }
+            }
+            _ => {
+                self.pending.push_back(c);
+                self.output_index += 1;
+                self.output_col = if c == '\n' { 0 } else { self.output_col + 1 };
+                if self.indentation_tab_width.is_some() {
+                    self.line.push(c);
+                }
+            }
+        }
+
+        self.original_index += 1;
+        if c == '\n' {
+            self.original_line += 1;
+            self.original_col = 0;
+            self.in_indentation = true;
+            if self.indentation_tab_width.is_some() {
+                self.line_state = lex_line(&self.line, self.line_state).1;
+                self.line.clear();
+            }
+        } else {
+            self.original_col += 1;
+            self.in_indentation &= c == ' ' || c == '\t';
+        }
+        if c == '\t' && tab_width.is_some() {
+            self.checkpoint(false);
+        }
+    }
+
+    /// Drop the blanks buffered so far.
+    fn drop_blanks(&mut self) {
+        if !self.blanks.is_empty() {
+            self.original_index += self.blanks.len();
+            self.blanks.clear();
+            self.checkpoint(false);
+        }
+    }
+}
+
+impl<I: Iterator<Item = char>> Iterator for PreprocessedInput<I> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        while self.pending.is_empty() {
+            let Some(c) = self.inner.next() else {
+                self.drop_blanks();
+                return None;
+            };
+            if !self.strip_trailing_whitespace {
+                self.transform(c);
+            } else if c == ' ' || c == '\t' {
+                self.blanks.push(c);
+            } else {
+                if c == '\n' || c == '\r' {
+                    self.drop_blanks();
+                } else {
+                    for blank in std::mem::take(&mut self.blanks) {
+                        self.transform(blank);
+                    }
+                }
+                self.transform(c);
+            }
+        }
+        self.pending.pop_front()
+    }
+}
+
+/// How bytes which are not valid UTF-8 are decoded. See [`Yaml::load_from_bytes`].
+///
+/// [`Yaml::load_from_bytes`]: crate::Yaml::load_from_bytes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Utf8Policy {
+    /// Fail with [`LoadError::InvalidUtf8`] at the first invalid sequence (default).
+    #[default]
+    Strict,
+    /// Replace each invalid sequence with U+FFFD REPLACEMENT CHARACTER, as
+    /// [`String::from_utf8_lossy`] does.
+    Lossy,
+    /// Decode each byte of an invalid sequence as the Latin-1 (ISO 8859-1) character of the same
+    /// value.
+    ///
+    /// This recovers the text of files saved in Latin-1 by older tools, in which only non-ASCII
+    /// characters are invalid UTF-8. Valid UTF-8 sequences are decoded as such.
+    Latin1Fallback,
+}
+
+impl Utf8Policy {
+    /// Decode `bytes` as UTF-8, handling invalid sequences as per the policy.
+    ///
+    /// ```
+    /// use saphyr::Utf8Policy;
+    ///
+    /// let bytes = b"caf\xe9: \xe2\x82\xac";
+    /// assert_eq!(Utf8Policy::Lossy.decode(bytes).unwrap(), "caf\u{FFFD}: \u{20AC}");
+    /// assert_eq!(Utf8Policy::Latin1Fallback.decode(bytes).unwrap(), "caf\u{E9}: \u{20AC}");
+    /// assert!(Utf8Policy::Strict.decode(bytes).is_err());
+    /// ```
+    ///
+    /// # Errors
+    /// Returns [`LoadError::InvalidUtf8`] if `bytes` is not valid UTF-8 and the policy is
+    /// [`Utf8Policy::Strict`].
+    pub fn decode(self, bytes: &[u8]) -> Result<Cow<'_, str>, LoadError> {
+        let mut decoded = String::new();
+        let mut rest = bytes;
+        loop {
+            let error = match std::str::from_utf8(rest) {
+                Ok(valid) if decoded.is_empty() => return Ok(Cow::Borrowed(valid)),
+                Ok(valid) => {
+                    decoded.push_str(valid);
+                    return Ok(Cow::Owned(decoded));
+                }
+                Err(error) => error,
+            };
+            let (valid, invalid) = rest.split_at(error.valid_up_to());
+            // `valid` was just validated.
+            decoded.push_str(std::str::from_utf8(valid).unwrap_or_default());
+            // An incomplete sequence ends the input.
+            let invalid_len = error.error_len().unwrap_or(invalid.len());
+            match self {
+                Utf8Policy::Strict => {
+                    let line = decoded.matches('\n').count() + 1;
+                    let col = decoded
+                        .rsplit('\n')
+                        .next()
+                        .unwrap_or_default()
+                        .chars()
+                        .count();
+                    return Err(LoadError::InvalidUtf8 {
+                        offset: bytes.len() - invalid.len(),
+                        marker: Marker::new(decoded.chars().count(), line, col),
+                    });
+                }
+                Utf8Policy::Lossy => decoded.push(char::REPLACEMENT_CHARACTER),
+                Utf8Policy::Latin1Fallback => {
+                    decoded.extend(invalid[..invalid_len].iter().map(|&byte| char::from(byte)));
+                }
+            }
+            rest = &invalid[invalid_len..];
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! Projection of YAML trees onto the values JSON can represent.
+
+use crate::{loader::parse_real, yaml::key_path, ErrorCode, Hash, Tag, Yaml};
+
+/// What [`Yaml::to_json_compatible`] does with nodes it cannot represent in JSON as they are.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum JsonLossPolicy {
+    /// Represent them as closely as possible, and report what was lost (default).
+    #[default]
+    Report,
+    /// Fail with the first loss.
+    Reject,
+}
+
+/// A [`Yaml`] tree projected onto the values JSON can represent, along with what was lost in the
+/// process. See [`Yaml::to_json_compatible`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JsonProjection {
+    /// The projected tree.
+    pub value: Yaml,
+    /// The changes made to nodes which JSON cannot represent, in the order of the nodes.
+    pub losses: Vec<JsonLoss>,
+}
+
+/// A change [`Yaml::to_json_compatible`] made to a node to represent it in JSON.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JsonLoss {
+    /// The path of the node, as [`Yaml::paths`] writes it, once its keys are projected.
+    pub path: String,
+    /// What was changed.
+    pub kind: JsonLossKind,
+}
+
+/// What [`Yaml::to_json_compatible`] changed in a node to represent it in JSON.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JsonLossKind {
+    /// The key of the entry was a scalar but not a string (e.g.: `1` or `true`), and was turned
+    /// into a string.
+    StringifiedKey,
+    /// The key of the entry was a collection, and the entry was dropped.
+    DroppedEntry,
+    /// The key of the entry was turned into the same string as a key before it (e.g.: `1` and
+    /// `"1"`). The value of the last one was kept.
+    DuplicateKey,
+    /// The real was an infinity, NaN, or not a float, none of which are JSON numbers, and was
+    /// turned into a string of its text.
+    StringifiedReal,
+    /// The node was tagged with the given tag, which was dropped.
+    DroppedTag(Tag),
+    /// The node was an alias or a bad value, and was turned into a null.
+    Nullified,
+}
+
+impl JsonLoss {
+    /// Return the stable code of the loss, which [`Display`] prefixes the message with.
+    ///
+    /// [`Display`]: std::fmt::Display
+    #[must_use]
+    pub fn code(&self) -> ErrorCode {
+        match self.kind {
+            JsonLossKind::StringifiedKey => ErrorCode::new("JSON001"),
+            JsonLossKind::DroppedEntry => ErrorCode::new("JSON002"),
+            JsonLossKind::DuplicateKey => ErrorCode::new("JSON003"),
+            JsonLossKind::StringifiedReal => ErrorCode::new("JSON004"),
+            JsonLossKind::DroppedTag(_) => ErrorCode::new("JSON005"),
+            JsonLossKind::Nullified => ErrorCode::new("JSON006"),
+        }
+    }
+}
+
+impl std::error::Error for JsonLoss {}
+
+impl std::fmt::Display for JsonLoss {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.code())?;
+        match &self.kind {
+            JsonLossKind::StringifiedKey => write!(f, "key turned into a string")?,
+            JsonLossKind::DroppedEntry => write!(f, "entry with a collection as key dropped")?,
+            JsonLossKind::DuplicateKey => write!(f, "key duplicated once turned into a string")?,
+            JsonLossKind::StringifiedReal => {
+                write!(f, "real which is not a JSON number turned into a string")?;
+            }
+            JsonLossKind::DroppedTag(tag) => write!(f, "tag `{tag}` dropped")?,
+            JsonLossKind::Nullified => write!(f, "alias or bad value turned into null")?,
+        }
+        if self.path.is_empty() {
+            write!(f, " at the root")
+        } else {
+            write!(f, " at `{}`", self.path)
+        }
+    }
+}
+
+impl Yaml {
+    /// Project `self` onto the values JSON can represent, reporting what could not be kept.
+    ///
+    /// This is meant for storing YAML documents in places which only accept JSON (e.g.: indexing
+    /// their metadata in a document database). The projected tree only contains nulls, booleans,
+    /// integers, finite reals, strings, sequences and mappings with string keys, which map one
+    /// to one to JSON values. Reals are rewritten as Rust prints their value (e.g.: `1e3` as
+    /// `1000.0`), which JSON accepts.
+    ///
+    /// Other nodes are changed as told by [`JsonLossKind`]: tags are dropped, keys which are
+    /// scalars are turned into strings, and so on. With [`JsonLossPolicy::Report`], each change
+    /// is reported in [`JsonProjection::losses`].
+    ///
+    /// ```
+    /// use saphyr::{JsonLossKind, JsonLossPolicy, Yaml};
+    ///
+    /// let doc = Yaml::load_from_str("\
+    /// name: web
+    /// ratio: 1e3
+    /// limit: .inf
+    /// 8080: http
+    /// ").unwrap().remove(0);
+    /// let projection = doc.to_json_compatible(JsonLossPolicy::Report).unwrap();
+    /// assert_eq!(projection.value["ratio"], Yaml::Real("1000.0".into()));
+    /// assert_eq!(projection.value["limit"], Yaml::String(".inf".into()));
+    /// assert_eq!(projection.value["8080"], Yaml::String("http".into()));
+    ///
+    /// let kinds: Vec<_> = projection.losses.iter().map(|loss| &loss.kind).collect();
+    /// assert_eq!(kinds, [&JsonLossKind::StringifiedReal, &JsonLossKind::StringifiedKey]);
+    /// assert_eq!(projection.losses[1].to_string(), "JSON001: key turned into a string at `8080`");
+    /// ```
+    ///
+    /// # Errors
+    /// With [`JsonLossPolicy::Reject`], returns the first [`JsonLoss`] the projection causes.
+    pub fn to_json_compatible(&self, policy: JsonLossPolicy) -> Result<JsonProjection, JsonLoss> {
+        let mut projector = Projector {
+            policy,
+            losses: vec![],
+        };
+        let value = projector.project(self, String::new())?;
+        Ok(JsonProjection {
+            value,
+            losses: projector.losses,
+        })
+    }
+}
+
+/// The state of [`Yaml::to_json_compatible`].
+struct Projector {
+    /// What to do with losses.
+    policy: JsonLossPolicy,
+    /// The losses so far.
+    losses: Vec<JsonLoss>,
+}
+
+impl Projector {
+    /// Record that the node at `path` lost what `kind` tells, or fail if losses are rejected.
+    fn lose(&mut self, path: &str, kind: JsonLossKind) -> Result<(), JsonLoss> {
+        let loss = JsonLoss {
+            path: path.to_owned(),
+            kind,
+        };
+        match self.policy {
+            JsonLossPolicy::Report => {
+                self.losses.push(loss);
+                Ok(())
+            }
+            JsonLossPolicy::Reject => Err(loss),
+        }
+    }
+
+    /// Return the projection of `node`, at `path` in the projected tree.
+    fn project(&mut self, node: &Yaml, path: String) -> Result<Yaml, JsonLoss> {
+        Ok(match node {
+            Yaml::Null | Yaml::Boolean(_) | Yaml::Integer(_) | Yaml::String(_) => node.clone(),
+            Yaml::Real(v) => match parse_real(v) {
+                Some(value) if value.is_finite() => Yaml::Real(format!("{value:?}")),
+                _ => {
+                    self.lose(&path, JsonLossKind::StringifiedReal)?;
+                    Yaml::String(v.clone())
+                }
+            },
+            Yaml::Tagged(tag, node) => {
+                self.lose(&path, JsonLossKind::DroppedTag(tag.clone()))?;
+                self.project(node, path)?
+            }
+            Yaml::Alias(_) | Yaml::BadValue => {
+                self.lose(&path, JsonLossKind::Nullified)?;
+                Yaml::Null
+            }
+            Yaml::Array(items) => Yaml::Array(
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| self.project(item, format!("{path}[{i}]")))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Yaml::Hash(hash) => {
+                let mut projected = Hash::default();
+                for (key, value) in hash {
+                    let Some(text) = self.project_key(key, &path)? else {
+                        continue;
+                    };
+                    let entry_path = key_path(&path, Some(&text));
+                    let value = self.project(value, entry_path.clone())?;
+                    if projected.insert(Yaml::String(text), value).is_some() {
+                        self.lose(&entry_path, JsonLossKind::DuplicateKey)?;
+                    }
+                }
+                Yaml::Hash(projected)
+            }
+        })
+    }
+
+    /// Return the string `key`, a key of the mapping at `path`, is projected onto, or `None` if
+    /// its entry is dropped.
+    fn project_key(&mut self, key: &Yaml, path: &str) -> Result<Option<String>, JsonLoss> {
+        let text = match key.untagged() {
+            Yaml::String(text) | Yaml::Real(text) => text.clone(),
+            Yaml::Integer(i) => i.to_string(),
+            Yaml::Boolean(b) => b.to_string(),
+            Yaml::Null | Yaml::Alias(_) | Yaml::BadValue => "null".to_owned(),
+            Yaml::Array(_) | Yaml::Hash(_) | Yaml::Tagged(..) => {
+                self.lose(&key_path(path, None), JsonLossKind::DroppedEntry)?;
+                return Ok(None);
+            }
+        };
+        let entry_path = key_path(path, Some(&text));
+        if let Yaml::Tagged(tag, _) = key {
+            self.lose(&entry_path, JsonLossKind::DroppedTag(tag.clone()))?;
+        }
+        if !matches!(key.untagged(), Yaml::String(_)) {
+            self.lose(&entry_path, JsonLossKind::StringifiedKey)?;
+        }
+        Ok(Some(text))
+    }
+}
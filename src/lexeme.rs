@@ -0,0 +1,135 @@
+//! The lexemes the booleans of a document are written with, to write them back alike.
+
+use std::collections::HashMap;
+
+use saphyr_parser::{Event, Parser, ScanError};
+
+use crate::{event_path::EventPaths, Schema, Yaml};
+
+/// The lexemes the booleans of a document are written with (e.g.: `True`, `yes` or `off`), by
+/// path.
+///
+/// Loading a document turns its booleans into [`Yaml::Boolean`], which the [`YamlEmitter`]
+/// writes `true` or `false`. Some tools following YAML 1.1 are sensitive to the literal, and
+/// expect booleans to be written back as they were. Scan the lexemes of a document along with
+/// loading it, and give them to [`YamlEmitter::bool_lexemes`]:
+///
+/// ```
+/// use saphyr::{BoolLexemes, Schema, Yaml, YamlEmitter, YamlLoader};
+///
+/// let source = "debug: No\nfeatures: {cache: on, tracing: Off}";
+/// let mut loader = YamlLoader::<Yaml>::default();
+/// loader.schema(Schema::Yaml11Booleans);
+/// let mut doc = loader.load_from_str(source).unwrap().remove(0);
+/// let lexemes = BoolLexemes::scan_str(source, Schema::Yaml11Booleans).unwrap();
+/// assert_eq!(lexemes[0].get("features.cache"), Some("on"));
+///
+/// doc["features"]["tracing"] = Yaml::Boolean(true);
+/// let mut output = String::new();
+/// let mut emitter = YamlEmitter::new(&mut output);
+/// emitter.bool_lexemes(&lexemes[0]);
+/// emitter.dump(&doc).unwrap();
+/// assert_eq!(output, "---\ndebug: No\nfeatures:\n  cache: on\n  tracing: true");
+/// ```
+///
+/// Paths are written as [`Yaml::paths`] returns them. A lexeme is only written back if it still
+/// resolves to the value of the boolean at its path: booleans which were changed since are
+/// written `true` or `false`.
+///
+/// [`YamlEmitter`]: crate::YamlEmitter
+/// [`YamlEmitter::bool_lexemes`]: crate::YamlEmitter::bool_lexemes
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BoolLexemes {
+    /// The lexeme of each boolean, by path.
+    lexemes: HashMap<String, String>,
+}
+
+impl BoolLexemes {
+    /// Create an empty set of lexemes.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scan the lexemes of the booleans of each document of `source`, as per `schema`.
+    ///
+    /// Booleans reached through aliases get the lexeme of the node they refer to.
+    ///
+    /// # Errors
+    /// Returns `ScanError` when parsing fails.
+    pub fn scan_str(source: &str, schema: Schema) -> Result<Vec<Self>, ScanError> {
+        let mut documents = vec![];
+        let mut lexemes = Self::new();
+        let mut paths = EventPaths::default();
+        // The value and lexeme of each anchored scalar.
+        let mut anchors: HashMap<usize, (Yaml, String)> = HashMap::new();
+        for event in Parser::new_from_str(source) {
+            let (event, _) = event?;
+            match event {
+                Event::DocumentStart => paths.clear(),
+                Event::DocumentEnd => documents.push(std::mem::take(&mut lexemes)),
+                Event::Scalar(value, style, anchor, tag) => {
+                    let resolved = schema.resolve(value.clone(), style, tag.as_ref());
+                    lexemes.on_scalar(paths.on_scalar(&resolved), &resolved, &value);
+                    if anchor > 0 {
+                        anchors.insert(anchor, (resolved, value));
+                    }
+                }
+                Event::Alias(anchor) => {
+                    let scalar = anchors.get(&anchor);
+                    let path = paths.on_alias(scalar.map(|(resolved, _)| resolved));
+                    if let Some((resolved, value)) = scalar {
+                        lexemes.on_scalar(path, resolved, value);
+                    }
+                }
+                Event::SequenceStart(..) => {
+                    paths.on_collection_start(false);
+                }
+                Event::MappingStart(..) => {
+                    paths.on_collection_start(true);
+                }
+                Event::SequenceEnd | Event::MappingEnd => paths.on_collection_end(),
+                _ => {}
+            }
+        }
+        Ok(documents)
+    }
+
+    /// Record the scalar resolved to `resolved` and written `lexeme`, at `path` if it is not a
+    /// key.
+    fn on_scalar(&mut self, path: Option<String>, resolved: &Yaml, lexeme: &str) {
+        if let (Some(path), Yaml::Boolean(_)) = (path, resolved) {
+            self.lexemes.insert(path, lexeme.to_owned());
+        }
+    }
+
+    /// Set the lexeme of the boolean at `path`, returning the previous one if any.
+    pub fn insert(&mut self, path: impl Into<String>, lexeme: impl Into<String>) -> Option<String> {
+        self.lexemes.insert(path.into(), lexeme.into())
+    }
+
+    /// Return the lexeme of the boolean at `path`, if known.
+    #[must_use]
+    pub fn get(&self, path: &str) -> Option<&str> {
+        self.lexemes.get(path).map(String::as_str)
+    }
+
+    /// Return the number of lexemes.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.lexemes.len()
+    }
+
+    /// Return whether no lexeme is known.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lexemes.is_empty()
+    }
+
+    /// Iterate over the paths of the booleans and their lexemes, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.lexemes
+            .iter()
+            .map(|(path, lexeme)| (path.as_str(), lexeme.as_str()))
+    }
+}
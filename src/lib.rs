@@ -32,40 +32,214 @@
 //! emitter.dump(doc).unwrap(); // dump the YAML object to a String
 //! ```
 //!
+//! # Panics
+//! Loading does not panic, whatever the input: malformed documents are reported as errors, and so
+//! are events which do not form a valid stream when they are sent to a [`YamlLoader`] by hand.
+//! This is checked by fuzz tests feeding random inputs to the loaders.
+//!
+//! Functions which panic on misuse say so in a `Panics` section (e.g.: mutably indexing a
+//! [`Yaml`] node with a key it does not have).
+//!
+//! # Strings only
+//! The default schema resolves plain scalars which look like booleans, integers or reals to those
+//! types (e.g.: `1.10` is the real `1.1`). Set [`Schema::Strings`] on the [`YamlLoader`] to load
+//! them all as strings instead, or use [`YamlLoader::coerce_path`] to do so for some settings
+//! only.
+//!
+//! # Scanning only
+//! The scanner, the tokens it produces and the parser live in the
+//! [`saphyr-parser`](https://docs.rs/saphyr-parser) crate, which this crate builds its loaders,
+//! emitter and [`Yaml`] type on. Tools only needing tokens (e.g.: syntax highlighters) should
+//! depend on `saphyr-parser` alone rather than on this crate with its features disabled: it has
+//! none of the loading and emitting code, and fewer dependencies. Its scanner reads whole
+//! documents, though: highlighters lexing one line at a time can use [`lex_line`] instead.
+//!
 //! # Features
-//! **Note:** With all features disabled, this crate's MSRV is `1.65.0`.
+//! **Note:** This crate's MSRV is `1.70.0`, whether features are enabled or not.
 //!
 //! #### `encoding` (_enabled by default_)
 //! Enables encoding-aware decoding of Yaml documents.
 //!
-//! The MSRV for this feature is `1.70.0`.
+//! #### `fast-hash`
+//! Uses a faster hasher for mappings, which is not resistant to hash flooding. See
+//! [`HashBuilder`].
+//!
+//! #### `rc`
+//! Provides [`SharedYaml`], a variant of [`Yaml`] whose strings and collections are
+//! reference-counted, making clones cheap, and [`StringPool`], sharing the storage of their
+//! identical strings.
+//!
+//! #### `nfc`
+//! Allows normalizing mapping keys to Unicode Normalization Form C when loading. See
+//! [`YamlLoader::normalize_keys`].
+//!
+//! #### `cache`
+//! Provides [`DocumentCache`], caching loaded documents on disk so that unchanged sources are not
+//! parsed again, and the binary format it uses (see [`encode_documents`]).
+//!
+//! #### `trace`
+//! Provides [`EventTrace`], recording the events of YAML streams as text to replay them, e.g.
+//! for bug reports which cannot share the documents, and [`TokenTrace`], recording their tokens.
+//!
+//! #### `serde`
+//! Implements `Serialize` and `Deserialize` for the traces of the `trace` feature, which it
+//! enables, as their text.
+//!
+//! #### `forbid-unsafe`
+//! Builds this crate with `#![forbid(unsafe_code)]`, for consumers certifying their dependencies
+//! are free of `unsafe` code. This crate has none, and the feature guarantees it stays so: such a
+//! build fails rather than compiling `unsafe` code in. [`FORBID_UNSAFE`] tells whether the
+//! feature is enabled. It does not apply to the dependencies of this crate, which must be audited
+//! on their own.
 
 #![warn(missing_docs, clippy::pedantic)]
+#![cfg_attr(feature = "forbid-unsafe", forbid(unsafe_code))]
 
 #[macro_use]
 mod macros;
 
+mod allowlist;
+mod anchor;
 mod annotated;
+mod batch;
 mod char_traits;
+mod coerce;
+mod comment;
+mod completion;
+mod compose;
+mod corpus;
+mod embedded;
 mod emitter;
+mod error_code;
+mod event_path;
+mod extract;
+mod fingerprint;
+mod folding;
+mod format;
+mod input;
+mod json;
+mod lexeme;
+mod line_lexer;
 mod loader;
+mod manifest;
+mod marker;
+mod merge;
+mod patch;
+mod scalar;
+mod schema;
+mod snippet;
+mod source_map;
+mod tag;
+mod validate;
+mod writer;
 mod yaml;
 
+/// Whether this crate was built with the `forbid-unsafe` feature, i.e. with
+/// `#![forbid(unsafe_code)]`.
+///
+/// This allows audit tooling to check the build it links against, e.g. in a test:
+///
+/// ```
+/// assert_eq!(saphyr::FORBID_UNSAFE, cfg!(feature = "forbid-unsafe"));
+/// ```
+pub const FORBID_UNSAFE: bool = cfg!(feature = "forbid-unsafe");
+
 // Re-export main components.
+pub use crate::allowlist::{KeyAllowlist, UnknownKey};
+pub use crate::anchor::{AliasEdge, AnchorGraph, AnchorNode, AnchorTable};
 pub use crate::annotated::{
     marked_yaml::MarkedYaml, AnnotatedArray, AnnotatedHash, AnnotatedYamlIter, YamlData,
 };
-pub use crate::emitter::YamlEmitter;
-pub use crate::loader::{LoadableYamlNode, YamlLoader};
-pub use crate::yaml::{Array, Hash, Yaml, YamlIter};
+pub use crate::batch::{load_files, LoadOptions};
+pub use crate::coerce::{CoerceError, CoerceErrorKind};
+pub use crate::comment::{Comment, CommentPlacement, Comments};
+pub use crate::completion::{parse_for_completion, Completion, CompletionKind};
+pub use crate::compose::{
+    compose_from_parser, compose_str, ComposeError, ComposedData, ComposedDocument, ComposedNode,
+    NodeId, NodeMap, NodeView,
+};
+pub use crate::corpus::{run_regression_corpus, CorpusFailure, CorpusFailureKind, CorpusReport};
+pub use crate::embedded::EmbeddedError;
+pub use crate::emitter::{
+    write_compact, AutoStyle, EmitError, EmitProfile, NullStyle, QuoteStyle, YamlEmitter,
+};
+pub use crate::error_code::ErrorCode;
+pub use crate::extract::{ExtractError, Extractor, FieldType, FieldValue, Record};
+pub use crate::fingerprint::Fingerprinted;
+pub use crate::folding::{folding_regions, FoldingKind, FoldingRegion, FoldingRegions};
+pub use crate::format::Formatter;
+pub use crate::input::{
+    CancellableInput, CancellationToken, LineLimitedInput, PreprocessedInput, Progress,
+    ProgressInput, Utf8Policy,
+};
+pub use crate::json::{JsonLoss, JsonLossKind, JsonLossPolicy, JsonProjection};
+pub use crate::lexeme::BoolLexemes;
+pub use crate::line_lexer::{lex_line, LineState, LineToken, LineTokenKind};
+pub use crate::loader::{
+    parse_real, DocumentInfo, DuplicateAnchorPolicy, DuplicateKeyPolicy, KeyCharset, LoadError,
+    LoadWarning, LoadableYamlNode, MarkerInScalarPolicy, PathCoercion, RealKeyPolicy,
+    ValueKeyPolicy, YamlLoader,
+};
+pub use crate::manifest::{
+    emit_manifests, group_manifests, join_manifests, split_manifests, Manifest, ManifestGroup,
+};
+pub use crate::marker::MarkerExt;
+pub use crate::merge::{ConfigStack, MergeStrategy, Provenance, ValueOrigin};
+pub use crate::patch::{apply_patch, compute_patch, TextEdit};
+pub use crate::scalar::{
+    resolve_block_scalar, select_scalar_style, BlockScalarHeader, Chomping, DetailedEvents, Scalar,
+    ScalarDetails, ScalarStyleExt,
+};
+pub use crate::schema::{Resolution, ResolutionRule, Schema};
+pub use crate::snippet::{ErrorSnippet, IndentationHelp};
+pub use crate::source_map::SourceMap;
+pub use crate::tag::Tag;
+pub use crate::validate::validate_syntax;
+pub use crate::writer::{
+    EmitterPool, MappingSink, PooledBuffer, SplitWriter, StreamWriter, TeeWriter,
+};
+pub use crate::yaml::{
+    Array, Hash, HashBuilder, KeyHasher, LookupError, LookupErrorKind, TypeError, Yaml, YamlIter,
+    YamlPath, YamlPaths,
+};
 
 #[cfg(feature = "encoding")]
 mod encoding;
 #[cfg(feature = "encoding")]
 pub use crate::encoding::{YAMLDecodingTrap, YAMLDecodingTrapFn, YamlDecoder};
 
+#[cfg(feature = "cache")]
+mod cache;
+#[cfg(feature = "cache")]
+pub use crate::cache::{
+    decode_documents, encode_documents, CacheError, CacheableNode, DocumentCache,
+};
+
+#[cfg(feature = "rc")]
+mod shared;
+#[cfg(feature = "rc")]
+pub use crate::batch::load_shared_files;
+#[cfg(feature = "rc")]
+pub use crate::shared::{SharedYaml, StringPool};
+
+#[cfg(feature = "trace")]
+mod trace;
+#[cfg(feature = "trace")]
+pub use crate::trace::{EventTrace, TokenTrace, TraceError};
+
 // Re-export `ScanError` as it is used as part of our public API and we want consumers to be able
 // to inspect it (e.g. perform a `match`). They wouldn't be able without it.
 pub use saphyr_parser::ScanError;
 // Re-export [`Marker`] which is used for annotated YAMLs.
 pub use saphyr_parser::Marker;
+// Re-export [`TScalarStyle`] which is used to describe how scalars are written.
+pub use saphyr_parser::TScalarStyle;
+// Re-export [`Event`] which is yielded by [`DetailedEvents`].
+pub use saphyr_parser::Event;
+// Re-export `Parser`, whose events convert to and from [`Scalar`]s.
+pub use saphyr_parser::Parser;
+// Re-export `MarkedEventReceiver`, through which events are sent to a [`YamlLoader`].
+pub use saphyr_parser::MarkedEventReceiver;
+// Re-export `TokenType`, of which [`TokenTrace`]s are made.
+#[cfg(feature = "trace")]
+pub use saphyr_parser::scanner::TokenType;
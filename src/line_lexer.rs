@@ -0,0 +1,448 @@
+//! Lexing documents one line at a time, for syntax highlighting.
+
+use crate::{manifest::is_marker_line, Schema, Yaml};
+
+/// The kind of a [`LineToken`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LineTokenKind {
+    /// A comment, from its `#` to the end of the line.
+    Comment,
+    /// A directive, e.g. `%YAML 1.2`.
+    Directive,
+    /// A document marker, `---` or `...`.
+    DocumentMarker,
+    /// An indicator: `-`, `?` or `:` of block collections, brackets, braces and commas of flow
+    /// collections, and the header of block scalars (e.g.: `|-`).
+    Indicator,
+    /// An anchor, e.g. `&base`.
+    Anchor,
+    /// An alias, e.g. `*base`.
+    Alias,
+    /// A tag, e.g. `!!str`.
+    Tag,
+    /// A scalar which is a key of a mapping.
+    Key,
+    /// A plain scalar which is a null, e.g. `~`.
+    Null,
+    /// A plain scalar which is a boolean.
+    Boolean,
+    /// A plain scalar which is an integer or a real.
+    Number,
+    /// A plain scalar which is a string.
+    String,
+    /// A quoted scalar, or the part of it on the line.
+    QuotedString,
+    /// A line of the contents of a block scalar.
+    BlockScalar,
+}
+
+/// A token of a line. See [`lex_line`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LineToken {
+    /// The kind of the token.
+    pub kind: LineTokenKind,
+    /// The column (0-based, in `char`s) of the first `char` of the token.
+    pub start: usize,
+    /// The column right after the last `char` of the token.
+    pub end: usize,
+}
+
+/// The state of the lexer at the start of a line, which [`lex_line`] takes and returns.
+///
+/// States are small and `Copy`, so that editors can keep the state at the start of each line and
+/// compare them: once re-lexing a modified line gives the state the next line started with
+/// before, the following lines need not be lexed again. The default state is that of the start of
+/// a stream.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct LineState {
+    /// The construct spanning several lines the line starts within.
+    context: LineContext,
+    /// The number of flow collections the line starts within.
+    flow_depth: usize,
+}
+
+/// A construct spanning several lines. See [`LineState`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+enum LineContext {
+    /// Nothing special: the line starts with nodes.
+    #[default]
+    Nodes,
+    /// A quoted scalar, in single or double quotes.
+    Quoted(char),
+    /// The contents of a block scalar. The indentation of its contents is `None` until its first
+    /// line which is not blank, which must be indented by at least `min_indent`.
+    BlockScalar {
+        /// The indentation of the contents, if known.
+        indent: Option<usize>,
+        /// The minimal indentation of the contents.
+        min_indent: usize,
+    },
+}
+
+impl LineState {
+    /// Create the state of the start of a stream.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return whether the line starts within a quoted scalar or a block scalar.
+    #[must_use]
+    pub fn in_scalar(&self) -> bool {
+        self.context != LineContext::Nodes
+    }
+
+    /// Return the number of flow collections the line starts within.
+    #[must_use]
+    pub fn flow_depth(&self) -> usize {
+        self.flow_depth
+    }
+
+    /// Return the indentation of the contents of the block scalar the line starts within, if it
+    /// does, or their minimal indentation until it is known.
+    pub(crate) fn block_scalar_indent(&self) -> Option<usize> {
+        match self.context {
+            LineContext::BlockScalar { indent, min_indent } => Some(indent.unwrap_or(min_indent)),
+            _ => None,
+        }
+    }
+}
+
+/// Split `line` into tokens, starting from `state`, and return them with the state of the next
+/// line.
+///
+/// This is meant for the syntax highlighters of editors, which lex documents one line at a time
+/// and re-lex only the lines around edits. Unlike the scanner, which reads whole documents, this
+/// needs no more than the state the line starts with (see [`LineState`]). Tokens are meant to be
+/// colored, not to be parsed: they are found without checking the structure of the document,
+/// so that documents being edited, which are often invalid, still get highlighted.
+///
+/// ```
+/// use saphyr::{lex_line, LineState, LineTokenKind};
+///
+/// let mut state = LineState::new();
+/// let mut kinds = vec![];
+/// for line in "port: 8080 # HTTP\nmotd: |\n  Hello\nhosts: [a, 'b']".lines() {
+///     let (tokens, next) = lex_line(line, state);
+///     kinds.push(tokens.iter().map(|token| token.kind).collect::<Vec<_>>());
+///     state = next;
+/// }
+/// use LineTokenKind::*;
+/// assert_eq!(kinds, [
+///     vec![Key, Indicator, Number, Comment],
+///     vec![Key, Indicator, Indicator],
+///     vec![BlockScalar],
+///     vec![Key, Indicator, Indicator, String, Indicator, QuotedString, Indicator],
+/// ]);
+/// ```
+///
+/// A trailing line break is ignored. Whitespace is not a token. Plain scalars which are not keys
+/// are told apart as [`Schema::Legacy`] resolves them. Plain scalars spanning several lines are
+/// lexed as one scalar per line.
+#[must_use]
+pub fn lex_line(line: &str, state: LineState) -> (Vec<LineToken>, LineState) {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    let chars: Vec<char> = line.chars().collect();
+    let mut lexer = Lexer {
+        chars: &chars,
+        pos: 0,
+        tokens: vec![],
+        state,
+    };
+    lexer.lex();
+    (lexer.tokens, lexer.state)
+}
+
+/// The lexer of a line. See [`lex_line`].
+struct Lexer<'a> {
+    /// The `char`s of the line.
+    chars: &'a [char],
+    /// The column of the next `char` to lex.
+    pos: usize,
+    /// The tokens lexed so far.
+    tokens: Vec<LineToken>,
+    /// The state of the lexer, which becomes that of the next line.
+    state: LineState,
+}
+
+impl Lexer<'_> {
+    /// Lex the whole line.
+    fn lex(&mut self) {
+        let indent = self.chars.iter().take_while(|&&c| c == ' ').count();
+        let blank = self.chars.iter().all(|c| c.is_whitespace());
+        let text: String = self.chars.iter().collect();
+        let marker = is_marker_line(&text, "---") || is_marker_line(&text, "...");
+
+        match self.state.context {
+            // Document markers end scalars, even though they are invalid within them.
+            _ if marker => self.state = LineState::default(),
+            LineContext::Nodes => {}
+            LineContext::Quoted(quote) => {
+                self.skip_whitespace();
+                let start = self.pos;
+                self.quoted(start, quote);
+            }
+            LineContext::BlockScalar { indent: None, .. } if blank => return,
+            LineContext::BlockScalar {
+                indent: None,
+                min_indent,
+            } if indent >= min_indent => {
+                self.state.context = LineContext::BlockScalar {
+                    indent: Some(indent),
+                    min_indent,
+                };
+                self.push(LineTokenKind::BlockScalar, indent, self.chars.len());
+                return;
+            }
+            LineContext::BlockScalar {
+                indent: Some(contents),
+                ..
+            } if blank || indent >= contents => {
+                if !blank {
+                    self.push(LineTokenKind::BlockScalar, contents, self.chars.len());
+                }
+                return;
+            }
+            LineContext::BlockScalar { .. } => self.state.context = LineContext::Nodes,
+        }
+
+        if self.state.context == LineContext::Nodes && self.state.flow_depth == 0 {
+            if marker {
+                self.push(LineTokenKind::DocumentMarker, 0, 3);
+                self.pos = 3;
+            } else if self.chars.first() == Some(&'%') {
+                let end = self.comment_start(0).unwrap_or(self.chars.len());
+                self.push(LineTokenKind::Directive, 0, trim_end(self.chars, 0, end));
+                self.pos = end;
+            }
+        }
+        while self.state.context == LineContext::Nodes {
+            self.skip_whitespace();
+            if self.pos >= self.chars.len() {
+                break;
+            }
+            self.node(indent, marker);
+        }
+    }
+
+    /// Lex the token starting at the current position, within nodes. `indent` is the
+    /// indentation of the line, and `marker` whether it starts with a document marker.
+    fn node(&mut self, indent: usize, marker: bool) {
+        let start = self.pos;
+        let c = self.chars[start];
+        let flow = self.state.flow_depth > 0;
+        match c {
+            '#' if start == 0 || self.chars[start - 1].is_whitespace() => {
+                self.push(LineTokenKind::Comment, start, self.chars.len());
+                self.pos = self.chars.len();
+            }
+            '-' | '?' | ':' if self.is_separated(start + 1) => {
+                self.push(LineTokenKind::Indicator, start, start + 1);
+                self.pos += 1;
+            }
+            '[' | '{' => {
+                self.state.flow_depth += 1;
+                self.push(LineTokenKind::Indicator, start, start + 1);
+                self.pos += 1;
+            }
+            ']' | '}' | ',' if flow => {
+                if c != ',' {
+                    self.state.flow_depth -= 1;
+                }
+                self.push(LineTokenKind::Indicator, start, start + 1);
+                self.pos += 1;
+            }
+            '&' | '*' | '!' => {
+                let end = self.word_end(start + 1);
+                let kind = match c {
+                    '&' => LineTokenKind::Anchor,
+                    '*' => LineTokenKind::Alias,
+                    _ => LineTokenKind::Tag,
+                };
+                self.push(kind, start, end);
+                self.pos = end;
+            }
+            '|' | '>' if !flow => {
+                // The contents are indented relative to the collection owning the scalar: that of
+                // the key or entry it is the value of, which may not start the line (e.g.:
+                // `- key: |`).
+                let owner = self.owner_column().unwrap_or(indent);
+                let mut end = start + 1;
+                let mut explicit = None;
+                while let Some(&c) = self.chars.get(end) {
+                    match c {
+                        '+' | '-' => {}
+                        '1'..='9' => explicit = c.to_digit(10),
+                        _ => break,
+                    }
+                    end += 1;
+                }
+                self.push(LineTokenKind::Indicator, start, end);
+                self.pos = end;
+                // The rest of the line may only be a comment.
+                self.skip_whitespace();
+                if self.pos < self.chars.len() {
+                    let start = self.pos;
+                    self.push(LineTokenKind::Comment, start, self.chars.len());
+                    self.pos = self.chars.len();
+                }
+                let min_indent = if marker { 0 } else { owner + 1 };
+                self.state.context = LineContext::BlockScalar {
+                    indent: explicit.map(|explicit| owner + explicit as usize),
+                    min_indent,
+                };
+            }
+            '\'' | '"' => {
+                self.pos += 1;
+                self.quoted(start, c);
+            }
+            _ => self.plain(start),
+        }
+    }
+
+    /// Return the indentation of the block collection owning the node starting at the current
+    /// position, if it is the value of a key or the entry of a sequence on the line. This is the
+    /// column of the key (or of its properties), or that of its `-`, `?` or `:` indicator.
+    fn owner_column(&self) -> Option<usize> {
+        let is_property =
+            |token: &&LineToken| matches!(token.kind, LineTokenKind::Anchor | LineTokenKind::Tag);
+        let mut before = self.tokens.iter().rev().skip_while(is_property);
+        let indicator = before.next()?;
+        if indicator.kind != LineTokenKind::Indicator {
+            return None;
+        }
+        match self.chars[indicator.start] {
+            '-' | '?' => Some(indicator.start),
+            ':' => match before.next() {
+                Some(key) if key.kind == LineTokenKind::Key => Some(
+                    before
+                        .take_while(is_property)
+                        .last()
+                        .map_or(key.start, |property| property.start),
+                ),
+                _ => Some(indicator.start),
+            },
+            _ => None,
+        }
+    }
+
+    /// Lex the quoted scalar (or the part of it on the line) starting at `start`, with the
+    /// current position right after its opening quote, if any.
+    fn quoted(&mut self, start: usize, quote: char) {
+        self.state.context = LineContext::Quoted(quote);
+        while let Some(&c) = self.chars.get(self.pos) {
+            self.pos += 1;
+            if quote == '"' && c == '\\' {
+                self.pos += 1;
+            } else if c == quote {
+                if quote == '\'' && self.chars.get(self.pos) == Some(&'\'') {
+                    self.pos += 1;
+                } else {
+                    self.state.context = LineContext::Nodes;
+                    break;
+                }
+            }
+        }
+        self.pos = self.pos.min(self.chars.len());
+        let end = self.pos;
+        let kind = if self.state.context == LineContext::Nodes && self.next_is_value_indicator() {
+            LineTokenKind::Key
+        } else {
+            LineTokenKind::QuotedString
+        };
+        if end > start {
+            self.push(kind, start, end);
+        }
+    }
+
+    /// Lex the plain scalar starting at `start`.
+    fn plain(&mut self, start: usize) {
+        let flow = self.state.flow_depth > 0;
+        let mut end = start;
+        while let Some(&c) = self.chars.get(end) {
+            let ends = match c {
+                ':' => self.is_separated(end + 1),
+                '#' => end > start && self.chars[end - 1].is_whitespace(),
+                ',' | '[' | ']' | '{' | '}' => flow,
+                _ => false,
+            };
+            if ends {
+                break;
+            }
+            end += 1;
+        }
+        let end = trim_end(self.chars, start, end);
+        self.pos = end;
+        let kind = if self.next_is_value_indicator() {
+            LineTokenKind::Key
+        } else {
+            let text: String = self.chars[start..end].iter().collect();
+            match Schema::Legacy.resolve_plain(&text) {
+                Yaml::Null => LineTokenKind::Null,
+                Yaml::Boolean(_) => LineTokenKind::Boolean,
+                Yaml::Integer(_) | Yaml::Real(_) => LineTokenKind::Number,
+                _ => LineTokenKind::String,
+            }
+        };
+        self.push(kind, start, end);
+    }
+
+    /// Return whether the next `char` but whitespace is a `:` indicator.
+    fn next_is_value_indicator(&self) -> bool {
+        let next = self.pos
+            + self.chars[self.pos..]
+                .iter()
+                .take_while(|c| c.is_whitespace())
+                .count();
+        self.chars.get(next) == Some(&':') && self.is_separated(next + 1)
+    }
+
+    /// Return whether an indicator right before `pos` is separated from what follows it.
+    fn is_separated(&self, pos: usize) -> bool {
+        match self.chars.get(pos) {
+            None => true,
+            Some(c) if c.is_whitespace() => true,
+            Some(',' | '[' | ']' | '{' | '}') => self.state.flow_depth > 0,
+            Some(_) => false,
+        }
+    }
+
+    /// Return the end of the anchor, alias or tag whose name starts at `pos`.
+    fn word_end(&self, mut pos: usize) -> usize {
+        let flow = self.state.flow_depth > 0;
+        while let Some(&c) = self.chars.get(pos) {
+            if c.is_whitespace() || (flow && matches!(c, ',' | '[' | ']' | '{' | '}')) {
+                break;
+            }
+            pos += 1;
+        }
+        pos
+    }
+
+    /// Return the column of the `#` of the comment starting at or after `pos`, if any.
+    fn comment_start(&self, pos: usize) -> Option<usize> {
+        (pos..self.chars.len())
+            .find(|&i| self.chars[i] == '#' && (i == 0 || self.chars[i - 1].is_whitespace()))
+    }
+
+    /// Move the current position past whitespace.
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    /// Add a token.
+    fn push(&mut self, kind: LineTokenKind, start: usize, end: usize) {
+        self.tokens.push(LineToken { kind, start, end });
+    }
+}
+
+/// Return the end of `chars[start..end]` without trailing whitespace.
+fn trim_end(chars: &[char], start: usize, mut end: usize) -> usize {
+    while end > start && chars[end - 1].is_whitespace() {
+        end -= 1;
+    }
+    end
+}
@@ -1,11 +1,22 @@
 //! The default loader.
 
-use std::collections::BTreeMap;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+};
 
-use hashlink::LinkedHashMap;
-use saphyr_parser::{Event, MarkedEventReceiver, Marker, ScanError, TScalarStyle, Tag};
+use saphyr_parser::{
+    scanner::{Scanner, Token, TokenType},
+    Event, MarkedEventReceiver, Marker, Parser, ScanError, TScalarStyle, Tag,
+};
 
-use crate::{Hash, Yaml};
+use crate::{
+    anchor::{anchor_definition, scan_anchors, AnchorDefinition, AnchorTable},
+    input::{CancellableInput, CancellationToken, LineLimitedInput, PreprocessedInput},
+    line_lexer::{lex_line, LineState, LineTokenKind},
+    manifest::is_marker_line,
+    AnnotatedHash, ErrorCode, Hash, Schema, SourceMap, Yaml,
+};
 
 /// Main structure for parsing YAML.
 ///
@@ -14,7 +25,15 @@ use crate::{Hash, Yaml};
 ///
 /// Each node must implement [`LoadableYamlNode`]. The methods are required for the loader to
 /// manipulate and populate the `Node`.
-#[allow(clippy::module_name_repetitions)]
+///
+/// Some options inspect the input itself rather than the events of the parser:
+/// [`Self::duplicate_anchor_policy`], [`Self::warn_unused_anchors`],
+/// [`Self::marker_in_scalar_policy`], [`Self::warn_trailing_commas`],
+/// [`Self::warn_mixed_indentation`], [`Self::reject_trailing_content`] and
+/// [`Self::reject_adjacent_values`]. When any of them is set, [`Self::load_from_str`] scans its
+/// input once before parsing it. They are only taken into account when loading through that
+/// method.
+#[allow(clippy::module_name_repetitions, clippy::struct_excessive_bools)]
 pub struct YamlLoader<Node>
 where
     Node: LoadableYamlNode,
@@ -25,7 +44,112 @@ where
     // (current node, anchor_id) tuple
     doc_stack: Vec<(Node, usize)>,
     key_stack: Vec<Node>,
+    /// The recognized tag of each collection in `doc_stack`, along with its start marker.
+    collection_tags: Vec<Option<(crate::Tag, Marker)>>,
     anchor_map: BTreeMap<usize, Node>,
+    /// The anchors of the stream, indexed by their ID minus 1, if they were scanned beforehand.
+    anchor_names: Option<Vec<AnchorDefinition>>,
+    /// The anchors of each loaded document. Only filled if `anchor_names` is set.
+    anchor_tables: Vec<AnchorTable<Node>>,
+    /// How the types of scalars are resolved.
+    schema: Schema,
+    /// How reals used as mapping keys are handled.
+    real_key_policy: RealKeyPolicy,
+    /// How plain `=` mapping keys are handled.
+    value_key_policy: ValueKeyPolicy,
+    /// Whether each mapping in `doc_stack` has a plain `=` key. Only tracked if
+    /// `value_key_policy` is [`ValueKeyPolicy::Collapse`].
+    value_keys: Vec<bool>,
+    /// The characters scalar keys are restricted to, if they are.
+    key_charset: Option<KeyCharset>,
+    /// Whether to normalize string keys to Unicode Normalization Form C.
+    #[cfg(feature = "nfc")]
+    normalize_keys: bool,
+    /// How anchors defined more than once in a document are handled.
+    duplicate_anchor_policy: DuplicateAnchorPolicy,
+    /// How keys appearing more than once in a mapping are handled.
+    duplicate_key_policy: DuplicateKeyPolicy,
+    /// The keys of each mapping in `doc_stack`. Only tracked if `duplicate_key_policy` is not
+    /// [`DuplicateKeyPolicy::Allow`].
+    mapping_keys: Vec<MappingKeys<Node>>,
+    /// The position of the last definition of each anchor of the current document. Only tracked
+    /// if `anchor_names` is set.
+    anchor_definitions: HashMap<String, Marker>,
+    /// Whether to warn about anchors no alias refers to.
+    warn_unused_anchors: bool,
+    /// How `---` and `...` markers within scalars are handled.
+    marker_in_scalar_policy: MarkerInScalarPolicy,
+    /// Whether to warn about flow collections ending with a comma.
+    warn_trailing_commas: bool,
+    /// Whether to warn about lines indented by another width than the rest of their document.
+    warn_mixed_indentation: bool,
+    /// Whether to reject documents following a `...` end marker without a `---` separator.
+    reject_trailing_content: bool,
+    /// Whether to reject values written right after the `:` of their key, without a space.
+    reject_adjacent_values: bool,
+    /// Whether to wrap errors in a [`LoadError::InDocument`] telling the document they are in.
+    report_error_documents: bool,
+    /// Whether to wrap scan errors in a [`LoadError::InCollection`] telling the collection they
+    /// are in.
+    report_error_collections: bool,
+    /// Whether each collection in `doc_stack` is a mapping, and its start. Only tracked if
+    /// `report_error_collections` is set.
+    collection_starts: Vec<(bool, Marker)>,
+    /// The IDs of the anchors of the current document no alias referred to so far. Only tracked
+    /// if `warn_unused_anchors` and `anchor_names` are set.
+    unused_anchors: BTreeSet<usize>,
+    /// The token to check to know whether loading was cancelled.
+    cancellation: Option<CancellationToken>,
+    /// The tab width with which to expand tabs in indentation, if they are accepted.
+    tab_indentation: Option<usize>,
+    /// The maximum number of `char`s on a line of the input, if limited.
+    max_line_length: Option<usize>,
+    /// The maximum number of aliases nested within one another, if limited.
+    max_alias_depth: Option<usize>,
+    /// The maximum number of documents in a stream, if limited.
+    max_documents: Option<usize>,
+    /// The depth of the aliases within each collection in `doc_stack`. Only tracked if
+    /// `max_alias_depth` is set.
+    alias_depths: Vec<usize>,
+    /// The depth of the aliases within each anchored node of the current document. Only tracked
+    /// if `max_alias_depth` is set.
+    anchor_alias_depths: BTreeMap<usize, usize>,
+    /// The position in an enclosing file at which the input starts, if it is embedded.
+    marker_offset: Option<Marker>,
+    /// The map from positions in the input to positions in the template it was generated from,
+    /// if it was.
+    source_map: Option<SourceMap>,
+    /// The position of each run of quotes added to the input to close quoted scalars, with the
+    /// number of characters added up to the end of that run. Only filled while loading through
+    /// `load_from_str` with [`MarkerInScalarPolicy::Terminate`].
+    closed_quotes: Vec<(Marker, usize)>,
+    /// The positions of the scalars a `---` or `...` marker is found within, and of the markers,
+    /// by position of the scalars. Only filled while loading through `load_from_str` with
+    /// [`MarkerInScalarPolicy::Reject`].
+    rejected_scalars: Vec<(Marker, Marker)>,
+    /// The key paths under which block scalars are kept as written.
+    opaque_paths: Vec<Vec<String>>,
+    /// The key paths under which plain scalars are resolved differently, with how they are.
+    path_coercions: Vec<(Vec<String>, PathCoercion)>,
+    /// A copy of the input, kept while loading if `opaque_paths` is not empty.
+    source: Option<String>,
+    /// The delimiters of the placeholders kept as opaque text, if any.
+    placeholder_delimiters: Option<(String, String)>,
+    /// The text of each placeholder of the input, while loading. See [`mask_placeholders`].
+    placeholders: Vec<String>,
+    /// The key of the current entry of each collection in `doc_stack`, if it is a mapping and the
+    /// key is a scalar. Only tracked if `opaque_paths` or `path_coercions` is not empty.
+    key_names: Vec<Option<String>>,
+    /// The first error that happened while loading, if any. Events are ignored once it is set.
+    error: Option<LoadError>,
+    /// The warnings raised while loading.
+    warnings: Vec<LoadWarning>,
+    /// Information about each loaded document.
+    documents: Vec<DocumentInfo>,
+    /// The start of the current document.
+    document_start: Marker,
+    /// The position of the current document, if it is a plain `~` scalar.
+    empty_document_scalar: Option<Marker>,
 }
 
 // For some reason, rustc wants `Node: Default` if I `#[derive(Default)]`.
@@ -38,7 +162,53 @@ where
             docs: vec![],
             doc_stack: vec![],
             key_stack: vec![],
+            collection_tags: vec![],
             anchor_map: BTreeMap::new(),
+            anchor_names: None,
+            anchor_tables: vec![],
+            schema: Schema::default(),
+            real_key_policy: RealKeyPolicy::default(),
+            value_key_policy: ValueKeyPolicy::default(),
+            value_keys: vec![],
+            key_charset: None,
+            #[cfg(feature = "nfc")]
+            normalize_keys: false,
+            duplicate_anchor_policy: DuplicateAnchorPolicy::default(),
+            duplicate_key_policy: DuplicateKeyPolicy::default(),
+            mapping_keys: vec![],
+            anchor_definitions: HashMap::new(),
+            warn_unused_anchors: false,
+            marker_in_scalar_policy: MarkerInScalarPolicy::default(),
+            warn_trailing_commas: false,
+            warn_mixed_indentation: false,
+            reject_trailing_content: false,
+            reject_adjacent_values: false,
+            report_error_documents: false,
+            report_error_collections: false,
+            collection_starts: vec![],
+            unused_anchors: BTreeSet::new(),
+            cancellation: None,
+            tab_indentation: None,
+            max_line_length: None,
+            max_alias_depth: None,
+            max_documents: None,
+            alias_depths: vec![],
+            anchor_alias_depths: BTreeMap::new(),
+            marker_offset: None,
+            source_map: None,
+            closed_quotes: vec![],
+            rejected_scalars: vec![],
+            opaque_paths: vec![],
+            path_coercions: vec![],
+            source: None,
+            placeholder_delimiters: None,
+            placeholders: vec![],
+            key_names: vec![],
+            error: None,
+            warnings: vec![],
+            documents: vec![],
+            document_start: Marker::default(),
+            empty_document_scalar: None,
         }
     }
 }
@@ -48,131 +218,1769 @@ where
     Node: LoadableYamlNode,
 {
     fn on_event(&mut self, ev: Event, marker: Marker) {
+        if self.error.is_some() {
+            return;
+        }
+        if self
+            .cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            self.error = Some(LoadError::Cancelled);
+            return;
+        }
+        let input_marker = marker;
+        let marker = self.offset_marker(marker);
         match ev {
-            Event::DocumentStart | Event::Nothing | Event::StreamStart | Event::StreamEnd => {
+            Event::Nothing | Event::StreamStart | Event::StreamEnd => {
                 // do nothing
             }
+            Event::DocumentStart => {
+                if self.max_documents.is_some_and(|max| self.docs.len() >= max) {
+                    self.error = Some(LoadError::TooManyDocuments(marker));
+                    return;
+                }
+                self.document_start = marker;
+                self.empty_document_scalar = None;
+            }
             Event::DocumentEnd => {
-                match self.doc_stack.len() {
-                    // empty document
-                    0 => self
-                        .docs
-                        .push(Node::from_bare_yaml(Yaml::BadValue).with_marker(marker)),
-                    1 => self.docs.push(self.doc_stack.pop().unwrap().0),
-                    _ => unreachable!(),
+                if let Err(error) = self.end_document(marker) {
+                    self.error = Some(error);
                 }
             }
-            Event::SequenceStart(aid, _) => {
+            Event::SequenceStart(aid, tag) => {
+                self.start_collection(aid, false, marker);
                 self.doc_stack.push((
                     Node::from_bare_yaml(Yaml::Array(Vec::new())).with_marker(marker),
                     aid,
                 ));
+                self.collection_tags
+                    .push(collection_tag(tag).map(|tag| (tag, marker)));
             }
             Event::SequenceEnd => {
-                let node = self.doc_stack.pop().unwrap();
+                let Some(node) = self.pop_collection(LoadableYamlNode::is_array) else {
+                    self.error = Some(LoadError::UnexpectedEvent(marker));
+                    return;
+                };
+                self.key_names.pop();
+                self.end_alias_depth(node.1);
                 self.insert_new_node(node);
             }
-            Event::MappingStart(aid, _) => {
+            Event::MappingStart(aid, tag) => {
+                self.start_collection(aid, true, marker);
                 self.doc_stack.push((
-                    Node::from_bare_yaml(Yaml::Hash(Hash::new())).with_marker(marker),
+                    Node::from_bare_yaml(Yaml::Hash(Hash::default())).with_marker(marker),
                     aid,
                 ));
                 self.key_stack.push(Node::from_bare_yaml(Yaml::BadValue));
+                self.start_mapping_keys();
+                self.collection_tags
+                    .push(collection_tag(tag).map(|tag| (tag, marker)));
             }
             Event::MappingEnd => {
-                self.key_stack.pop().unwrap();
-                let node = self.doc_stack.pop().unwrap();
+                let Some(node) = self.pop_collection(LoadableYamlNode::is_hash) else {
+                    self.error = Some(LoadError::UnexpectedEvent(marker));
+                    return;
+                };
+                self.key_names.pop();
+                self.key_stack.pop();
+                self.mapping_keys.pop();
+                self.end_alias_depth(node.1);
+                let node = self.collapse_value_key(node);
                 self.insert_new_node(node);
             }
             Event::Scalar(v, style, aid, tag) => {
-                let node = if style != TScalarStyle::Plain {
-                    Yaml::String(v)
-                } else if let Some(Tag {
-                    ref handle,
-                    ref suffix,
-                }) = tag
+                if let Some(error) = self.rejected_scalar(input_marker) {
+                    self.error = Some(error);
+                    return;
+                }
+                self.define_anchor(aid);
+                if self.doc_stack.is_empty()
+                    && aid == 0
+                    && tag.is_none()
+                    && style == TScalarStyle::Plain
+                    && v == "~"
                 {
-                    if handle == "tag:yaml.org,2002:" {
-                        match suffix.as_ref() {
-                            "bool" => {
-                                // "true" or "false"
-                                match v.parse::<bool>() {
-                                    Err(_) => Yaml::BadValue,
-                                    Ok(v) => Yaml::Boolean(v),
-                                }
-                            }
-                            "int" => match v.parse::<i64>() {
-                                Err(_) => Yaml::BadValue,
-                                Ok(v) => Yaml::Integer(v),
-                            },
-                            "float" => match parse_f64(&v) {
-                                Some(_) => Yaml::Real(v),
-                                None => Yaml::BadValue,
-                            },
-                            "null" => match v.as_ref() {
-                                "~" | "null" => Yaml::Null,
-                                _ => Yaml::BadValue,
-                            },
-                            _ => Yaml::String(v),
-                        }
-                    } else {
-                        Yaml::String(v)
+                    self.empty_document_scalar = Some(marker);
+                }
+                match self.scalar_value(v, style, tag.as_ref(), marker, input_marker) {
+                    Ok(node) => {
+                        self.insert_new_node((Node::from_bare_yaml(node).with_marker(marker), aid));
                     }
+                    Err(error) => self.error = Some(error),
+                }
+            }
+            Event::Alias(id) => {
+                if let Err(error) = self.insert_alias(id, marker) {
+                    self.error = Some(error);
+                }
+            }
+        }
+    }
+}
+
+impl<Node> YamlLoader<Node>
+where
+    Node: LoadableYamlNode,
+{
+    /// Add the document ending at `marker` to `docs`.
+    fn end_document(&mut self, marker: Marker) -> Result<(), LoadError> {
+        // Events fed by hand may end a document within a collection, or after several nodes.
+        if !self.collection_tags.is_empty() || self.doc_stack.len() > 1 {
+            return Err(LoadError::UnexpectedEvent(marker));
+        }
+        // The parser gives documents without contents a `~` scalar located at their end.
+        self.documents.push(DocumentInfo {
+            start: self.document_start,
+            end: marker,
+            empty: self.empty_document_scalar == Some(marker),
+        });
+        match self.doc_stack.pop() {
+            Some((node, _)) => self.docs.push(node),
+            // empty document
+            None => self
+                .docs
+                .push(Node::from_bare_yaml(Yaml::BadValue).with_marker(marker)),
+        }
+        self.end_document_anchors();
+        Ok(())
+    }
+
+    /// Insert a copy of the node with the anchor `id`, referred to by an alias at `marker`.
+    fn insert_alias(&mut self, id: usize, marker: Marker) -> Result<(), LoadError> {
+        self.unused_anchors.remove(&id);
+        self.alias_depth(id, marker)?;
+        self.track_key(None, marker);
+        let n = match self.anchor_map.get(&id) {
+            Some(v) => v.clone(),
+            None => Node::from_bare_yaml(Yaml::BadValue),
+        };
+        self.insert_new_node((n.with_marker(marker), 0));
+        Ok(())
+    }
+
+    fn insert_new_node(&mut self, node: (Node, usize)) {
+        // valid anchor id starts from 1
+        if node.1 > 0 {
+            self.anchor_map.insert(node.1, node.0.clone());
+        }
+        // Without open collections, the node is the root of the document.
+        let parent = if self.collection_tags.is_empty() {
+            None
+        } else {
+            self.doc_stack.last_mut()
+        };
+        if let Some(parent) = parent {
+            let parent_node = &mut parent.0;
+            if parent_node.is_array() {
+                parent_node.array_mut().push(node.0);
+            } else if let (true, Some(cur_key)) = (parent_node.is_hash(), self.key_stack.last_mut())
+            {
+                // current node is a key
+                if cur_key.is_badvalue() {
+                    *cur_key = node.0;
+                // current node is a value
                 } else {
-                    // Datatype is not specified, or unrecognized
-                    Yaml::from_str(&v)
-                };
-                self.insert_new_node((Node::from_bare_yaml(node).with_marker(marker), aid));
+                    let key = cur_key.take();
+                    let duplicate = self
+                        .mapping_keys
+                        .last_mut()
+                        .and_then(|keys| keys.insert(&key));
+                    match (duplicate, self.duplicate_key_policy) {
+                        (None, _) | (_, DuplicateKeyPolicy::Allow) => {}
+                        (Some((key, first, second)), DuplicateKeyPolicy::Warn) => {
+                            self.warnings
+                                .push(LoadWarning::DuplicateKey { key, first, second });
+                        }
+                        (Some((key, first, second)), DuplicateKeyPolicy::Reject) => {
+                            self.error = Some(LoadError::DuplicateKey { key, first, second });
+                        }
+                    }
+                    parent_node.hash_mut().insert(key, node.0);
+                }
+            }
+        } else {
+            self.doc_stack.push(node);
+        }
+    }
+
+    /// Start tracking a collection with the anchor `aid` starting at `marker`, before pushing it
+    /// to `doc_stack`.
+    fn start_collection(&mut self, aid: usize, mapping: bool, marker: Marker) {
+        self.define_anchor(aid);
+        self.start_alias_depth();
+        self.track_key(None, marker);
+        self.key_names.push(None);
+        if self.report_error_collections {
+            self.collection_starts.push((mapping, marker));
+        }
+    }
+
+    /// Start tracking the keys of a mapping, as far as the options require it.
+    fn start_mapping_keys(&mut self) {
+        if self.value_key_policy == ValueKeyPolicy::Collapse {
+            self.value_keys.push(false);
+        }
+        if self.duplicate_key_policy != DuplicateKeyPolicy::Allow {
+            self.mapping_keys.push(MappingKeys::default());
+        }
+    }
+
+    /// Replace the mapping that just ended with the value of its `=` key, if it has one and
+    /// [`ValueKeyPolicy::Collapse`] is set.
+    fn collapse_value_key(&mut self, (mut node, aid): (Node, usize)) -> (Node, usize) {
+        if self.value_keys.pop() != Some(true) || !node.is_hash() {
+            return (node, aid);
+        }
+        let key = Node::from_bare_yaml(Yaml::String("=".to_owned()));
+        match node.hash_mut().remove(&key) {
+            Some(value) => (value, aid),
+            None => (node, aid),
+        }
+    }
+
+    /// Start tracking the depth of the aliases within a collection, if it is limited.
+    fn start_alias_depth(&mut self) {
+        if self.max_alias_depth.is_some() {
+            self.alias_depths.push(0);
+        }
+    }
+
+    /// Stop tracking the depth of the aliases within the collection that just ended, with the
+    /// anchor `aid`, and account for them in its parent.
+    fn end_alias_depth(&mut self, aid: usize) {
+        if let Some(depth) = self.alias_depths.pop() {
+            self.add_alias_depth(aid, depth);
+        }
+    }
+
+    /// Account for a node with the anchor `aid` (if any), within which aliases are nested `depth`
+    /// deep, in the current collection.
+    fn add_alias_depth(&mut self, aid: usize, depth: usize) {
+        if aid > 0 {
+            self.anchor_alias_depths.insert(aid, depth);
+        }
+        if let Some(parent) = self.alias_depths.last_mut() {
+            *parent = (*parent).max(depth);
+        }
+    }
+
+    /// Check that the alias to `id` at `marker` does not nest aliases deeper than allowed.
+    fn alias_depth(&mut self, id: usize, marker: Marker) -> Result<(), LoadError> {
+        let Some(max_depth) = self.max_alias_depth else {
+            return Ok(());
+        };
+        let depth = self
+            .anchor_alias_depths
+            .get(&id)
+            .map_or(1, |depth| depth.saturating_add(1));
+        if depth > max_depth {
+            return Err(LoadError::AliasTooDeep(marker));
+        }
+        self.add_alias_depth(0, depth);
+        Ok(())
+    }
+
+    /// Pop the collection that just ended from `doc_stack`, applying its tag if it has one.
+    ///
+    /// Returns `None` if no collection is open, or if the innermost one is not of the kind
+    /// `is_kind` checks. Once all collections are closed, the root node of the document may
+    /// remain in `doc_stack`.
+    ///
+    /// A collection which does not match the structure its tag requires is replaced with a
+    /// `BadValue`, as scalars are (e.g.: `!!int foo`).
+    fn pop_collection(&mut self, is_kind: fn(&Node) -> bool) -> Option<(Node, usize)> {
+        if self.collection_tags.is_empty()
+            || !self.doc_stack.last().is_some_and(|(node, _)| is_kind(node))
+        {
+            return None;
+        }
+        let (mut node, aid) = self.doc_stack.pop()?;
+        self.collection_starts.pop();
+        let Some((tag, marker)) = self.collection_tags.pop().flatten() else {
+            return Some((node, aid));
+        };
+
+        let valid = if tag.is_core("set") {
+            // `!!set` is a mapping whose values are all null.
+            let null = Node::from_bare_yaml(Yaml::Null);
+            node.is_hash() && node.hash_mut().values().all(|value| *value == null)
+        } else if node.is_array() {
+            // `!!omap` and `!!pairs` are sequences of single-entry mappings. Keys of an `!!omap`
+            // must be unique.
+            let unique_keys = tag.is_core("omap");
+            let mut keys = HashSet::new();
+            node.array_mut().iter_mut().all(|item| {
+                item.is_hash()
+                    && item.hash_mut().len() == 1
+                    && (!unique_keys
+                        || item
+                            .hash_mut()
+                            .keys()
+                            .next()
+                            .is_some_and(|key| keys.insert(key.clone())))
+            })
+        } else {
+            false
+        };
+
+        if valid {
+            Some((node.with_tag(tag), aid))
+        } else {
+            Some((
+                Node::from_bare_yaml(Yaml::BadValue).with_marker(marker),
+                aid,
+            ))
+        }
+    }
+
+    /// Return whether the next node inserted will be a mapping key.
+    fn next_is_key(&self) -> bool {
+        matches!(self.doc_stack.last(), Some((parent, _)) if parent.is_hash())
+            && self
+                .key_stack
+                .last()
+                .is_some_and(LoadableYamlNode::is_badvalue)
+    }
+
+    /// Resolve the value of the scalar `v`, as per its style and tag and the options set on `self`.
+    ///
+    /// `marker` is the position of the scalar, and `input_marker` the same position before
+    /// [`Self::marker_offset`] is applied.
+    fn scalar_value(
+        &mut self,
+        v: String,
+        style: TScalarStyle,
+        tag: Option<&Tag>,
+        marker: Marker,
+        input_marker: Marker,
+    ) -> Result<Yaml, LoadError> {
+        let v = if style == TScalarStyle::Plain {
+            self.unmask_placeholders(v)
+        } else {
+            v
+        };
+        self.track_key(Some(&v), marker);
+        if let Some(charset) = &self.key_charset {
+            if self.next_is_key() && !charset.accepts(&v) {
+                return Err(LoadError::InvalidKey(v, marker));
+            }
+        }
+        if style == TScalarStyle::Plain && tag.is_none() && v == "=" && self.next_is_key() {
+            match self.value_key_policy {
+                ValueKeyPolicy::Verbatim => {}
+                ValueKeyPolicy::Collapse => {
+                    if let Some(has_value_key) = self.value_keys.last_mut() {
+                        *has_value_key = true;
+                    }
+                }
+                ValueKeyPolicy::Reject => return Err(LoadError::ValueKey(marker)),
+            }
+        }
+        let node = if self.is_opaque_block_scalar(style) {
+            // Without contents, the parser places the scalar at the line following it.
+            if v.chars().all(|c| c == '\n') {
+                Yaml::String(String::new())
+            } else {
+                Yaml::String(self.raw_block_scalar(input_marker))
+            }
+        } else {
+            match self.path_coercion(style, tag) {
+                Some(PathCoercion::String) => Yaml::String(v),
+                Some(PathCoercion::Schema(schema)) => schema.resolve(v, style, tag),
+                None => self.schema.resolve(v, style, tag),
+            }
+        };
+        match node {
+            Yaml::Real(v) if self.next_is_key() => self.real_key_policy.apply(v, marker),
+            #[cfg(feature = "nfc")]
+            Yaml::String(v)
+                if self.normalize_keys
+                    && self.next_is_key()
+                    && !unicode_normalization::is_nfc(&v) =>
+            {
+                use unicode_normalization::UnicodeNormalization;
+                Ok(Yaml::String(v.nfc().collect()))
+            }
+            node => Ok(node),
+        }
+    }
+
+    /// Replace the placeholders masked in the plain scalar `v` with their text. See
+    /// [`mask_placeholders`].
+    fn unmask_placeholders(&self, v: String) -> String {
+        if self.placeholders.is_empty() || !v.chars().any(|c| placeholder_index(c).is_some()) {
+            return v;
+        }
+        let mut unmasked = String::with_capacity(v.len());
+        for c in v.chars() {
+            match placeholder_index(c) {
+                Some(index) => match self.placeholders.get(index) {
+                    Some(placeholder) => unmasked.push_str(placeholder),
+                    None => unmasked.push(c),
+                },
+                None if c == PLACEHOLDER_FILLER => {}
+                None => unmasked.push(c),
+            }
+        }
+        unmasked
+    }
+
+    /// Record `key`, starting at `marker`, as the key of the current entry if the next node is a
+    /// key, and keys are tracked. `key` is `None` for keys which are not scalars.
+    fn track_key(&mut self, key: Option<&str>, marker: Marker) {
+        if !self.next_is_key() {
+            return;
+        }
+        if let Some(keys) = self.mapping_keys.last_mut() {
+            keys.current = Some((key.unwrap_or("?").to_owned(), marker));
+        }
+        if self.opaque_paths.is_empty() && self.path_coercions.is_empty() {
+            return;
+        }
+        if let Some(name) = self.key_names.last_mut() {
+            *name = key.map(ToOwned::to_owned);
+        }
+    }
+
+    /// Return whether a scalar of the given style, coming next, is to be kept as written. See
+    /// [`Self::opaque_block_scalar`].
+    fn is_opaque_block_scalar(&mut self, style: TScalarStyle) -> bool {
+        if !matches!(style, TScalarStyle::Literal | TScalarStyle::Folded)
+            || self.source.is_none()
+            || self.next_is_key()
+        {
+            return false;
+        }
+        self.next_path()
+            .is_some_and(|path| self.opaque_paths.contains(&path))
+    }
+
+    /// Return how the plain scalar coming next is to be resolved, if its path matches one given
+    /// to [`Self::coerce_path`].
+    fn path_coercion(&mut self, style: TScalarStyle, tag: Option<&Tag>) -> Option<PathCoercion> {
+        if self.path_coercions.is_empty()
+            || style != TScalarStyle::Plain
+            || tag.is_some()
+            || self.next_is_key()
+        {
+            return None;
+        }
+        let path = self.next_path()?;
+        self.path_coercions
+            .iter()
+            .find(|(pattern, _)| {
+                pattern.len() == path.len()
+                    && pattern
+                        .iter()
+                        .zip(&path)
+                        .all(|(pattern, segment)| pattern == "*" || pattern == segment)
+            })
+            .map(|(_, coercion)| *coercion)
+    }
+
+    /// Return the key path of the value coming next, or `None` if a key leading to it is not a
+    /// scalar. Keys are only tracked if [`Self::opaque_block_scalar`] or [`Self::coerce_path`]
+    /// was called.
+    fn next_path(&mut self) -> Option<Vec<String>> {
+        let mut path = Vec::with_capacity(self.doc_stack.len());
+        for ((node, _), key) in self.doc_stack.iter_mut().zip(&self.key_names) {
+            if node.is_array() {
+                path.push(node.array_mut().len().to_string());
+            } else {
+                path.push(key.clone()?);
+            }
+        }
+        Some(path)
+    }
+
+    /// Return the lines of the input making up the block scalar whose contents start at `marker`.
+    ///
+    /// The parser places `marker` at the indentation of the contents. These end before the first
+    /// line which is indented less and not blank. Trailing blank lines are left out.
+    fn raw_block_scalar(&self, marker: Marker) -> String {
+        let source = self.source.as_deref().unwrap_or_default();
+        let offset = source
+            .char_indices()
+            .nth(marker.index())
+            .map_or(source.len(), |(offset, _)| offset);
+        let start = source[..offset].rfind('\n').map_or(0, |idx| idx + 1);
+        let mut end = start;
+        let mut contents_end = start;
+        for line in source[start..].split_inclusive('\n') {
+            let text = line.trim_end_matches(['\n', '\r']);
+            let unindented = text.trim_start_matches(' ');
+            if !unindented.is_empty() && text.len() - unindented.len() < marker.col() {
+                break;
+            }
+            end += line.len();
+            if !unindented.is_empty() {
+                contents_end = end;
+            }
+        }
+        source[start..contents_end].to_owned()
+    }
+
+    /// Return the document nodes from `self`, consuming it in the process.
+    #[must_use]
+    pub fn into_documents(self) -> Vec<Node> {
+        self.docs
+    }
+
+    /// Set the schema by which the types of scalars are resolved (defaults to
+    /// [`Schema::Legacy`]).
+    ///
+    /// ```
+    /// use saphyr::{Schema, Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.schema(Schema::Core);
+    /// let docs = loader.load_from_str("[True, NULL, +.inf, 0o14]").unwrap();
+    /// assert_eq!(docs[0][0].as_bool(), Some(true));
+    /// assert!(docs[0][1].is_null());
+    /// assert_eq!(docs[0][2].as_f64(), Some(f64::INFINITY));
+    /// assert_eq!(docs[0][3].as_i64(), Some(12));
+    /// ```
+    pub fn schema(&mut self, schema: Schema) -> &mut Self {
+        self.schema = schema;
+        self
+    }
+
+    /// Set how reals used as mapping keys are handled (defaults to [`RealKeyPolicy::Verbatim`]).
+    ///
+    /// Options are only taken into account when loading through [`Self::load_from_str`] or
+    /// [`Self::load_from_parser`], which report the errors they may raise.
+    pub fn real_key_policy(&mut self, policy: RealKeyPolicy) -> &mut Self {
+        self.real_key_policy = policy;
+        self
+    }
+
+    /// Set how plain `=` mapping keys are handled (defaults to [`ValueKeyPolicy::Verbatim`]).
+    ///
+    /// ```
+    /// use saphyr::{ValueKeyPolicy, Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.value_key_policy(ValueKeyPolicy::Collapse);
+    /// let docs = loader.load_from_str("port: { =: 80, comment: default }").unwrap();
+    /// assert_eq!(docs[0]["port"].as_i64(), Some(80));
+    /// ```
+    pub fn value_key_policy(&mut self, policy: ValueKeyPolicy) -> &mut Self {
+        self.value_key_policy = policy;
+        self
+    }
+
+    /// Set how anchors defined more than once in a document are handled (defaults to
+    /// [`DuplicateAnchorPolicy::Allow`]).
+    ///
+    /// ```
+    /// use saphyr::{DuplicateAnchorPolicy, LoadWarning, Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.duplicate_anchor_policy(DuplicateAnchorPolicy::Warn);
+    /// let docs = loader.load_from_str("a: &x 1\nb: &x 2\nc: *x").unwrap();
+    /// assert_eq!(docs[0]["c"].as_i64(), Some(2));
+    /// assert!(matches!(
+    ///     loader.warnings(),
+    ///     [LoadWarning::DuplicateAnchor { name, first, second }]
+    ///         if name == "x" && first.line() == 1 && second.line() == 2
+    /// ));
+    /// ```
+    pub fn duplicate_anchor_policy(&mut self, policy: DuplicateAnchorPolicy) -> &mut Self {
+        self.duplicate_anchor_policy = policy;
+        self
+    }
+
+    /// Set how keys appearing more than once in a mapping are handled (defaults to
+    /// [`DuplicateKeyPolicy::Allow`]).
+    ///
+    /// The error and warning hold the positions of both occurrences of the key, e.g. for editors
+    /// to point at each of them.
+    ///
+    /// ```
+    /// use saphyr::{DuplicateKeyPolicy, LoadError, Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.duplicate_key_policy(DuplicateKeyPolicy::Reject);
+    /// let error = loader.load_from_str("port: 80\nhost: a\nport: 81").unwrap_err();
+    /// assert!(matches!(
+    ///     error,
+    ///     LoadError::DuplicateKey { key, first, second }
+    ///         if key == "port" && first.line() == 1 && second.line() == 3
+    /// ));
+    /// ```
+    pub fn duplicate_key_policy(&mut self, policy: DuplicateKeyPolicy) -> &mut Self {
+        self.duplicate_key_policy = policy;
+        self
+    }
+
+    /// Set how `---` and `...` markers at the start of a line within a scalar are handled
+    /// (defaults to [`MarkerInScalarPolicy::Standard`]).
+    ///
+    /// ```
+    /// use saphyr::{LoadWarning, MarkerInScalarPolicy, Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.marker_in_scalar_policy(MarkerInScalarPolicy::Terminate);
+    /// let docs = loader.load_from_str("a: \"first\n---\nb: second").unwrap();
+    /// assert_eq!(docs[0]["a"].as_str(), Some("first"));
+    /// assert_eq!(docs[1]["b"].as_str(), Some("second"));
+    /// assert!(matches!(
+    ///     loader.warnings(),
+    ///     [LoadWarning::ScalarEndedByMarker { scalar, marker }]
+    ///         if scalar.line() == 1 && marker.line() == 2
+    /// ));
+    /// ```
+    pub fn marker_in_scalar_policy(&mut self, policy: MarkerInScalarPolicy) -> &mut Self {
+        self.marker_in_scalar_policy = policy;
+        self
+    }
+
+    /// Set whether to raise a [`LoadWarning::UnusedAnchor`] for each anchor no alias refers to
+    /// (defaults to `false`).
+    ///
+    /// Such anchors are valid YAML, but often hint at a misspelled alias.
+    ///
+    /// ```
+    /// use saphyr::{LoadWarning, Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.warn_unused_anchors(true);
+    /// loader.load_from_str("a: &x 1\nb: &y 2\nc: *y").unwrap();
+    /// assert!(matches!(
+    ///     loader.warnings(),
+    ///     [LoadWarning::UnusedAnchor { name, marker }] if name == "x" && marker.line() == 1
+    /// ));
+    /// ```
+    pub fn warn_unused_anchors(&mut self, warn: bool) -> &mut Self {
+        self.warn_unused_anchors = warn;
+        self
+    }
+
+    /// Set whether to raise a [`LoadWarning::TrailingComma`] for each flow collection ending with a
+    /// comma (defaults to `false`).
+    ///
+    /// Trailing commas (e.g.: `[a, b,]`) are valid YAML and are always accepted. They are common
+    /// in hand-edited, JSON-like documents, where other tools may reject them.
+    ///
+    /// ```
+    /// use saphyr::{LoadWarning, Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.warn_trailing_commas(true);
+    /// let docs = loader.load_from_str("{a: [1, 2,], b: 3}").unwrap();
+    /// assert_eq!(docs[0]["a"].as_vec().unwrap().len(), 2);
+    /// assert!(matches!(loader.warnings(), [LoadWarning::TrailingComma(marker)] if marker.col() == 9));
+    /// ```
+    pub fn warn_trailing_commas(&mut self, warn: bool) -> &mut Self {
+        self.warn_trailing_commas = warn;
+        self
+    }
+
+    /// Set whether to raise a [`LoadWarning::MixedIndentation`] for each line of a block
+    /// collection indented by another width than the first indented line of its document
+    /// (defaults to `false`).
+    ///
+    /// Mixing widths (e.g.: 2 spaces here, 4 spaces there) is valid YAML as long as siblings are
+    /// aligned, but often comes from pasting a snippet from another document, and can hide a
+    /// value nested at the wrong level. Sequences written at the indentation of their key
+    /// (`key:` followed by `- item`) and the contents of sequence items following their `-` are
+    /// not widths of their own.
+    ///
+    /// ```
+    /// use saphyr::{LoadWarning, Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.warn_mixed_indentation(true);
+    /// loader.load_from_str("\
+    /// server:
+    ///   host: localhost
+    ///   tls:
+    ///       cert: server.pem
+    /// ").unwrap();
+    /// assert!(matches!(
+    ///     loader.warnings(),
+    ///     [LoadWarning::MixedIndentation { width: 2, found: 4, marker }] if marker.line() == 4
+    /// ));
+    /// ```
+    pub fn warn_mixed_indentation(&mut self, warn: bool) -> &mut Self {
+        self.warn_mixed_indentation = warn;
+        self
+    }
+
+    /// Set whether to normalize string keys to Unicode Normalization Form C (defaults to
+    /// `false`).
+    ///
+    /// The same text may be encoded with different sequences of code points (e.g.: `é` as a
+    /// single code point, or as `e` followed by a combining accent). Such keys look identical, but
+    /// are distinct keys unless normalized. Once normalized, they collide as duplicate keys do:
+    /// the last value wins.
+    ///
+    /// Only scalar keys are normalized. Values, and keys nested within collections used as keys,
+    /// are kept as they are.
+    ///
+    /// ```
+    /// use saphyr::{Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.normalize_keys(true);
+    /// let docs = loader.load_from_str("caf\u{e9}: 1\ncafe\u{301}: 2").unwrap();
+    /// assert_eq!(docs[0].as_hash().unwrap().len(), 1);
+    /// assert_eq!(docs[0]["caf\u{e9}"].as_i64(), Some(2));
+    /// ```
+    ///
+    /// This option is only available with the `nfc` feature.
+    #[cfg(feature = "nfc")]
+    pub fn normalize_keys(&mut self, normalize: bool) -> &mut Self {
+        self.normalize_keys = normalize;
+        self
+    }
+
+    /// Set whether to reject contents following a `...` document end marker without a `---`
+    /// separator (defaults to `false`).
+    ///
+    /// YAML allows a bare document after `...`, which is seldom intended. It rather comes from
+    /// concatenating files, one of which ends with `...`. Loading then fails with
+    /// [`LoadError::TrailingContent`], pointing to the start of those contents. Comments and
+    /// directives may still follow `...`.
+    ///
+    /// ```
+    /// use saphyr::{LoadError, Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.reject_trailing_content(true);
+    /// assert!(loader.load_from_str("a: 1\n...\n# end\n---\nb: 2\n").is_ok());
+    /// assert!(matches!(
+    ///     loader.load_from_str("a: 1\n...\nb: 2\n"),
+    ///     Err(LoadError::TrailingContent(marker)) if marker.line() == 3
+    /// ));
+    /// ```
+    pub fn reject_trailing_content(&mut self, reject: bool) -> &mut Self {
+        self.reject_trailing_content = reject;
+        self
+    }
+
+    /// Set whether to reject values of flow mappings written right after the `:` of their key.
+    ///
+    /// For JSON compatibility, YAML 1.2 allows omitting the space after the `:` of a key written
+    /// as a quoted scalar or a flow collection, within flow collections (e.g.: `{"a":1}`). YAML 1.1
+    /// and the tools built on it require the space. With this option, loading fails with
+    /// [`LoadError::AdjacentValue`] at the first such value, so that documents are portable.
+    /// Values following a space are accepted as usual.
+    ///
+    /// ```
+    /// use saphyr::{LoadError, Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// assert!(loader.load_from_str(r#"{"a":1}"#).is_ok());
+    /// loader.reject_adjacent_values(true);
+    /// assert!(loader.load_from_str(r#"{"a": 1}"#).is_ok());
+    /// assert!(matches!(
+    ///     loader.load_from_str(r#"{"a":1}"#),
+    ///     Err(LoadError::AdjacentValue(marker)) if marker.col() == 5
+    /// ));
+    /// ```
+    pub fn reject_adjacent_values(&mut self, reject: bool) -> &mut Self {
+        self.reject_adjacent_values = reject;
+        self
+    }
+
+    /// Set whether to tell which document of the stream errors are in.
+    ///
+    /// When loading a stream of many documents (e.g.: concatenated manifests), the position of an
+    /// error is not enough to tell which document is broken. With this option, each error is
+    /// wrapped in a [`LoadError::InDocument`] holding the index of its document and the
+    /// position at which that document starts. Its code and marker are those of the wrapped
+    /// error.
+    ///
+    /// ```
+    /// use saphyr::{LoadError, Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.report_error_documents(true);
+    /// let error = loader.load_from_str("a: 1\n---\nb: 2\n---\nc: [3\n").unwrap_err();
+    /// assert!(matches!(
+    ///     &error,
+    ///     LoadError::InDocument { index: 2, start, .. } if start.line() == 4
+    /// ));
+    /// assert_eq!(error.code().as_str(), "SCAN039");
+    /// assert!(error
+    ///     .to_string()
+    ///     .starts_with("SCAN039: in document 3 starting at line 4: "));
+    /// ```
+    pub fn report_error_documents(&mut self, report: bool) -> &mut Self {
+        self.report_error_documents = report;
+        self
+    }
+
+    /// Set whether to tell which collection scan errors are in.
+    ///
+    /// The scanner reports where the input stops being valid, which may be far from the
+    /// construct that is broken (e.g.: a flow sequence missing its `]` is reported where the
+    /// input ends). With this option, scan errors raised within a collection are wrapped in a
+    /// [`LoadError::InCollection`] holding the innermost collection the parser started, and its
+    /// start, and whose [`source`] is the scan error. Its code and marker are those of the scan
+    /// error.
+    ///
+    /// The scanner reads ahead of the parser, e.g. to tell whether a flow collection is a key. A
+    /// collection which starts shortly before the error, such as a flow sequence on the same line,
+    /// may not be started yet: the collection containing it is then reported instead.
+    ///
+    /// ```
+    /// use std::error::Error;
+    /// use saphyr::{LoadError, Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.report_error_collections(true);
+    /// let error = loader.load_from_str("a:\n  b: [1, 2\n  c: 3\n").unwrap_err();
+    /// assert!(matches!(
+    ///     &error,
+    ///     LoadError::InCollection { kind: "sequence", start, .. } if start.line() == 2
+    /// ));
+    /// assert_eq!(error.marker().unwrap().line(), 3);
+    /// assert!(matches!(error.source().unwrap().downcast_ref(), Some(LoadError::Scan(_))));
+    /// ```
+    ///
+    /// [`source`]: std::error::Error::source
+    pub fn report_error_collections(&mut self, report: bool) -> &mut Self {
+        self.report_error_collections = report;
+        self
+    }
+
+    /// Restrict scalar mapping keys to the characters of `charset`.
+    ///
+    /// Loading fails with [`LoadError::InvalidKey`] at the first key which is empty or contains
+    /// another character. This is meant for documents whose keys become identifiers (e.g.:
+    /// environment variables or metric labels). Keys are checked as written, before their type is
+    /// resolved. Keys which are collections are not checked.
+    ///
+    /// ```
+    /// use saphyr::{KeyCharset, LoadError, Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.key_charset(KeyCharset::identifier());
+    /// assert!(loader.load_from_str("http_port: 80").is_ok());
+    /// assert!(matches!(
+    ///     loader.load_from_str("http port: 80"),
+    ///     Err(LoadError::InvalidKey(key, _)) if key == "http port"
+    /// ));
+    /// ```
+    pub fn key_charset(&mut self, charset: KeyCharset) -> &mut Self {
+        self.key_charset = Some(charset);
+        self
+    }
+
+    /// Allow cancelling the load from another thread with `token`.
+    ///
+    /// If the token is cancelled while loading, [`LoadError::Cancelled`] is returned. When loading
+    /// through [`Self::load_from_str`], the parser stops reading its input promptly. When loading
+    /// through [`Self::load_from_parser`], the input of the parser should be wrapped in a
+    /// [`CancellableInput`] for the same effect. Otherwise, the input is parsed to its end first.
+    pub fn cancellation(&mut self, token: CancellationToken) -> &mut Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Accept tabs in indentation, treating them as spaces up to the next tab stop.
+    ///
+    /// YAML forbids indenting with tabs. With this option, tabs at the start of lines are expanded
+    /// into spaces, with a tab stop every `width` columns, before the input is parsed. Each line on
+    /// which this happens yields a [`LoadWarning::TabIndentation`]. Positions reported in nodes
+    /// and errors refer to the input with tabs expanded; lines are unchanged.
+    ///
+    /// Within block scalars, tabs are only expanded up to the indentation of the contents: those
+    /// past it are part of the contents, and are kept. This only applies when loading through
+    /// [`Self::load_from_str`].
+    ///
+    /// ```
+    /// use saphyr::{LoadWarning, Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.tab_indentation(4);
+    /// let docs = loader.load_from_str("a:\n\tb: c").unwrap();
+    /// assert_eq!(docs[0]["a"]["b"].as_str(), Some("c"));
+    /// assert!(matches!(loader.warnings(), [LoadWarning::TabIndentation(marker)] if marker.line() == 2));
+    /// ```
+    pub fn tab_indentation(&mut self, width: usize) -> &mut Self {
+        self.tab_indentation = Some(width);
+        self
+    }
+
+    /// Limit the number of aliases nested within one another, through the nodes they refer to.
+    ///
+    /// An alias to a node without aliases has a depth of 1. An alias to a node containing an
+    /// alias of depth `n` has a depth of `n + 1`. Loading fails with [`LoadError::AliasTooDeep`]
+    /// at the first alias deeper than `max_depth`.
+    ///
+    /// Since the loader copies the node an alias refers to, each level may multiply the size of
+    /// the document (e.g.: "billion laughs"). This limit is independent of how deeply collections
+    /// are nested.
+    ///
+    /// ```
+    /// use saphyr::{LoadError, Yaml, YamlLoader};
+    ///
+    /// let source = "a: &a [x]\nb: &b [*a, *a]\nc: [*b, *b]";
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.max_alias_depth(2);
+    /// assert!(loader.load_from_str(source).is_ok());
+    /// loader.max_alias_depth(1);
+    /// assert!(matches!(loader.load_from_str(source), Err(LoadError::AliasTooDeep(_))));
+    /// ```
+    pub fn max_alias_depth(&mut self, max_depth: usize) -> &mut Self {
+        self.max_alias_depth = Some(max_depth);
+        self
+    }
+
+    /// Limit the number of documents in a stream to `max_documents`.
+    ///
+    /// This is meant for services expecting a single document (or a few), so that a stream made
+    /// of many `---` separators does not make them build as many documents. Loading fails with
+    /// [`LoadError::TooManyDocuments`] at the start of the first document past the limit. The
+    /// rest of the input is still parsed, but no more nodes are built from it.
+    ///
+    /// ```
+    /// use saphyr::{LoadError, Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.max_documents(1);
+    /// assert!(loader.load_from_str("a: 1").is_ok());
+    /// let flood = "---\n".repeat(1000);
+    /// assert!(matches!(loader.load_from_str(&flood), Err(LoadError::TooManyDocuments(marker)) if marker.line() == 2));
+    /// ```
+    pub fn max_documents(&mut self, max_documents: usize) -> &mut Self {
+        self.max_documents = Some(max_documents);
+        self
+    }
+
+    /// Reject inputs with lines longer than `max_line_length` `char`s, line break excluded.
+    ///
+    /// This is meant for protocol-style usage, where long lines indicate abuse. Parsing stops as
+    /// soon as the limit is exceeded, and [`LoadError::LineTooLong`] is returned. The limit applies
+    /// to the input before any other option alters it. This only applies when loading through
+    /// [`Self::load_from_str`]; wrap the input of the parser in a [`LineLimitedInput`] otherwise.
+    ///
+    /// ```
+    /// use saphyr::{LoadError, Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.max_line_length(16);
+    /// assert!(loader.load_from_str("key: value").is_ok());
+    /// let long = format!("key: {}", "a".repeat(100));
+    /// assert!(matches!(loader.load_from_str(&long), Err(LoadError::LineTooLong(marker)) if marker.col() == 16));
+    /// ```
+    pub fn max_line_length(&mut self, max_line_length: usize) -> &mut Self {
+        self.max_line_length = Some(max_line_length);
+        self
+    }
+
+    /// Report positions as if the input started at `offset` in an enclosing file.
+    ///
+    /// This is meant for YAML embedded inside another file (e.g.: front matter or heredocs), so
+    /// that markers of nodes, errors and warnings match the coordinates of the enclosing file.
+    /// Like all markers, `offset` counts its index in `char`s. Its column only shifts positions on
+    /// the first line of the input.
+    ///
+    /// ```
+    /// use saphyr::{MarkedYaml, Marker, YamlLoader};
+    ///
+    /// let file = "---\ntitle: Hello\n---\n# Hello\n";
+    /// let mut loader = YamlLoader::<MarkedYaml>::default();
+    /// // The front matter starts after the first line of `file`.
+    /// loader.marker_offset(Marker::new(4, 2, 0));
+    /// let docs = loader.load_from_str("title: Hello\n").unwrap();
+    /// let title = &docs[0].data["title"];
+    /// assert_eq!((title.marker.index(), title.marker.line(), title.marker.col()), (11, 2, 7));
+    /// ```
+    pub fn marker_offset(&mut self, offset: Marker) -> &mut Self {
+        self.marker_offset = Some(offset);
+        self
+    }
+
+    /// Report positions in the template the input was generated from, as per `source_map`.
+    ///
+    /// Markers of nodes, errors and warnings are translated, so that they point into the template
+    /// rather than into the generated text. This takes precedence over [`Self::marker_offset`].
+    ///
+    /// ```
+    /// use saphyr::{LoadError, Marker, SourceMap, Yaml, YamlLoader};
+    ///
+    /// // The template `a: 1\n{{ include "b" }}\n` renders as `a: 1\nb: [\n`.
+    /// let mut map = SourceMap::new();
+    /// map.add(Marker::new(0, 1, 0), Marker::new(0, 1, 0))
+    ///     .add(Marker::new(5, 2, 0), Marker::new(40, 7, 2));
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.source_map(map);
+    /// let error = loader.load_from_str("a: 1\nb: [\n").unwrap_err();
+    /// // The error is reported at the end of the input, after the included text.
+    /// assert_eq!(error.marker().map(|m| (m.line(), m.col())), Some((8, 0)));
+    /// ```
+    pub fn source_map(&mut self, source_map: SourceMap) -> &mut Self {
+        self.source_map = Some(source_map);
+        self
+    }
+
+    /// Translate `marker`, pointing into the input, by [`Self::source_map`] or shift it by
+    /// [`Self::marker_offset`].
+    fn offset_marker(&self, marker: Marker) -> Marker {
+        let marker = self.unclosed_marker(marker);
+        if let Some(source_map) = &self.source_map {
+            return source_map.translate(marker);
+        }
+        let Some(offset) = self.marker_offset else {
+            return marker;
+        };
+        let col = if marker.line() <= 1 {
+            offset.col().saturating_add(marker.col())
+        } else {
+            marker.col()
+        };
+        Marker::new(
+            offset.index().saturating_add(marker.index()),
+            offset
+                .line()
+                .saturating_add(marker.line().saturating_sub(1)),
+            col,
+        )
+    }
+
+    /// Keep block scalars found at the key path `path` as they are written in the input.
+    ///
+    /// This is meant for files embedding scripts or templates, where whitespace matters more than
+    /// YAML semantics. Such scalars are not folded nor chomped. Instead, they are loaded as a
+    /// string of the lines of their contents, including their indentation and line breaks; the
+    /// indentation of the contents is that of their first line. Other scalars at `path` are
+    /// loaded as usual.
+    ///
+    /// `path` lists the keys of the mappings leading to the scalar, from the root of the
+    /// document. Items of sequences are designated by their index. This may be called multiple
+    /// times to add more paths. This only applies when loading through [`Self::load_from_str`],
+    /// which then keeps a copy of the input while loading.
+    ///
+    /// ```
+    /// use saphyr::{Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.opaque_block_scalar(&["steps", "0", "run"]);
+    /// let docs = loader
+    ///     .load_from_str("steps:\n  - run: >\n      if true; then\n        echo ok\n      fi\n")
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     docs[0]["steps"][0]["run"].as_str(),
+    ///     Some("      if true; then\n        echo ok\n      fi\n")
+    /// );
+    /// ```
+    pub fn opaque_block_scalar(&mut self, path: &[&str]) -> &mut Self {
+        self.opaque_paths
+            .push(path.iter().map(ToString::to_string).collect());
+        self
+    }
+
+    /// Resolve the plain scalars found at the key path `path` as `coercion` says, rather than
+    /// with the schema of the loader.
+    ///
+    /// This is meant for consumers who know the types of some settings better than the schema
+    /// does, such as those whose values are always strings (e.g.: the annotations of Kubernetes
+    /// objects, where `on` or `1.10` would otherwise become a boolean or a real).
+    ///
+    /// `path` lists the keys of the mappings leading to the scalar, from the root of the
+    /// document. Items of sequences are designated by their index, and `*` matches any key or
+    /// index. This may be called multiple times to add more paths; the first one matching a
+    /// scalar applies. Quoted, block and tagged scalars, as well as keys, are loaded as usual.
+    ///
+    /// ```
+    /// use saphyr::{PathCoercion, Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.coerce_path(&["metadata", "annotations", "*"], PathCoercion::String);
+    /// let docs = loader
+    ///     .load_from_str("metadata:\n  annotations: {enabled: true, version: 1.10}\nreplicas: 3")
+    ///     .unwrap();
+    /// assert_eq!(docs[0]["metadata"]["annotations"]["enabled"].as_str(), Some("true"));
+    /// assert_eq!(docs[0]["metadata"]["annotations"]["version"].as_str(), Some("1.10"));
+    /// assert_eq!(docs[0]["replicas"].as_i64(), Some(3));
+    /// ```
+    pub fn coerce_path(&mut self, path: &[&str], coercion: PathCoercion) -> &mut Self {
+        self.path_coercions
+            .push((path.iter().map(ToString::to_string).collect(), coercion));
+        self
+    }
+
+    /// Keep placeholders delimited by `open` and `close` (e.g.: `{{` and `}}`) as opaque text.
+    ///
+    /// This allows loading the structure of templates (e.g.: Helm charts or Jinja templates)
+    /// without rendering them first. Placeholders would otherwise be read as YAML syntax (e.g.:
+    /// `{{` starts nested flow mappings). Instead, they are read as if they were plain text, and
+    /// are kept verbatim in the plain scalars and keys they are part of. Quoted and block scalars
+    /// can already hold them and are read as usual. Placeholders must start and end on the same
+    /// line. Lines made only of a placeholder (e.g.: `{{- if .Values.enabled }}`) are read as
+    /// plain scalars and are likely to break the structure of the document.
+    ///
+    /// This only applies when loading through [`Self::load_from_str`].
+    ///
+    /// ```
+    /// use saphyr::{Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.opaque_placeholders("{{", "}}");
+    /// let docs = loader
+    ///     .load_from_str("image: {{ .Values.image }}:{{ .Values.tag }}\nports: [{{ .Values.port }}]")
+    ///     .unwrap();
+    /// assert_eq!(docs[0]["image"].as_str(), Some("{{ .Values.image }}:{{ .Values.tag }}"));
+    /// assert_eq!(docs[0]["ports"][0].as_str(), Some("{{ .Values.port }}"));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if `open` or `close` is empty.
+    pub fn opaque_placeholders(&mut self, open: &str, close: &str) -> &mut Self {
+        assert!(
+            !open.is_empty() && !close.is_empty(),
+            "placeholder delimiters may not be empty"
+        );
+        self.placeholder_delimiters = Some((open.to_owned(), close.to_owned()));
+        self
+    }
+
+    /// Load the given string as an array of YAML documents, using the options set on `self`.
+    ///
+    /// ```
+    /// use saphyr::{LoadError, RealKeyPolicy, Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.real_key_policy(RealKeyPolicy::Reject);
+    /// assert!(matches!(loader.load_from_str("1.5: a"), Err(LoadError::RealKey(_))));
+    /// ```
+    ///
+    /// # Errors
+    /// Returns `LoadError` when the input is not valid YAML or goes against the options.
+    pub fn load_from_str(&mut self, source: &str) -> Result<Vec<Node>, LoadError> {
+        let masked;
+        let source = match &self.placeholder_delimiters {
+            Some((open, close)) => {
+                (masked, self.placeholders) = mask_placeholders(source, open, close);
+                &masked
+            }
+            None => source,
+        };
+        let scan_anchor_names = (self.duplicate_anchor_policy != DuplicateAnchorPolicy::Allow
+            || self.warn_unused_anchors)
+            && self.anchor_names.is_none();
+        let scans_input = scan_anchor_names
+            || self.warn_trailing_commas
+            || self.warn_mixed_indentation
+            || self.reject_trailing_content
+            || self.reject_adjacent_values;
+        let closed;
+        let mut scan =
+            if scans_input || self.marker_in_scalar_policy != MarkerInScalarPolicy::Standard {
+                PreScan::new(self.expanded_source(source))
+            } else {
+                PreScan::default()
+            };
+        let mut ended_scalars = vec![];
+        let source = match self.marker_in_scalar_policy {
+            MarkerInScalarPolicy::Standard => source,
+            MarkerInScalarPolicy::Terminate => {
+                (closed, ended_scalars) = self.close_quoted_scalars(source, &scan);
+                match &closed {
+                    Some(closed) => {
+                        if scans_input {
+                            scan = PreScan::new(self.expanded_source(closed));
+                        }
+                        closed
+                    }
+                    None => source,
+                }
+            }
+            MarkerInScalarPolicy::Reject => {
+                self.rejected_scalars =
+                    scan_plain_scalars_ended_by_markers(&scan.text, &scan.tokens);
+                // Quoted scalars make the scanner fail, with a less helpful error.
+                self.rejected_scalars
+                    .extend(quoted_scalar_ended_by_marker(&scan));
+                source
+            }
+        };
+        if !self.opaque_paths.is_empty() {
+            self.source = Some(self.expanded_source(source).into_owned());
+        }
+        if scan_anchor_names {
+            self.anchor_names = Some(scan.tokens.iter().filter_map(anchor_definition).collect());
+        }
+        let mut result = self.load_input(source);
+        self.source = None;
+        self.placeholders.clear();
+        self.rejected_scalars.clear();
+        if scan_anchor_names {
+            self.anchor_tables.clear();
+        }
+        if self.warn_trailing_commas {
+            for marker in scan_trailing_commas(&scan.tokens) {
+                let marker = self.offset_marker(marker);
+                self.warnings.push(LoadWarning::TrailingComma(marker));
+            }
+        }
+        for (scalar, marker) in ended_scalars {
+            self.warnings.push(LoadWarning::ScalarEndedByMarker {
+                scalar: self.offset_marker(scalar),
+                marker: self.offset_marker(marker),
+            });
+        }
+        if self.warn_mixed_indentation {
+            for (marker, width, found) in scan_mixed_indentation(&scan.text, &scan.tokens) {
+                let marker = self.offset_marker(marker);
+                self.warnings.push(LoadWarning::MixedIndentation {
+                    width,
+                    found,
+                    marker,
+                });
+            }
+        }
+        if self.reject_trailing_content && result.is_ok() {
+            if let Some(marker) = scan_trailing_content(&scan.tokens) {
+                let error = LoadError::TrailingContent(self.offset_marker(marker));
+                result = Err(self.locate_error(error, true));
+            }
+        }
+        if self.reject_adjacent_values && result.is_ok() {
+            if let Some(marker) = scan_adjacent_value(&scan.tokens) {
+                let error = LoadError::AdjacentValue(self.offset_marker(marker));
+                result = Err(self.locate_error(error, true));
+            }
+        }
+        self.closed_quotes.clear();
+        result
+    }
+
+    /// Close the quoted scalars of `source` a `---` or `...` marker is found within, on the last
+    /// line before the marker which is not blank, given the pre-scan of `source`.
+    ///
+    /// Return `source` with the quotes added if any was, and the positions of the scalars ended by
+    /// a marker and of the markers, in order, as they are in that input. The quotes added are
+    /// kept in `closed_quotes`, for [`Self::offset_marker`] to point into `source` again.
+    fn close_quoted_scalars(
+        &mut self,
+        source: &str,
+        scan: &PreScan,
+    ) -> (Option<String>, Vec<(Marker, Marker)>) {
+        let shift = |marker: Marker, index: usize, line: usize| {
+            Marker::new(marker.index() + index, marker.line() + line, marker.col())
+        };
+        let lines: Vec<&str> = source.split_inclusive('\n').collect();
+        let expanded: Vec<&str> = scan.text.split_inclusive('\n').collect();
+        // The index and offset at which each line of the expanded input starts.
+        let mut starts = Vec::with_capacity(expanded.len());
+        let (mut index, mut offset) = (0, 0);
+        for line in &expanded {
+            starts.push((index, offset));
+            index += line.chars().count();
+            offset += line.len();
+        }
+
+        let mut ended = vec![];
+        // The line of each quote to add, the offset in that line to add it at, and the quote.
+        let mut closings = vec![];
+        let mut added = 0;
+        // The input is scanned anew from each marker a quoted scalar was closed before, since
+        // the scanner stops at such scalars. Markers start documents afresh.
+        let mut segment;
+        let mut current = scan;
+        let mut first_line = 0;
+        loop {
+            let (first_index, _) = starts.get(first_line).copied().unwrap_or_default();
+            ended.extend(
+                scan_plain_scalars_ended_by_markers(&current.text, &current.tokens)
+                    .into_iter()
+                    .map(|(scalar, marker)| {
+                        (
+                            shift(scalar, first_index + added, first_line),
+                            shift(marker, first_index + added, first_line),
+                        )
+                    }),
+            );
+            let Some((start, marker)) = quoted_scalar_ended_by_marker(current) else {
+                break;
+            };
+            let start_line = first_line + start.line() - 1;
+            let marker_line = first_line + marker.line() - 1;
+            // Close the scalar on the last line which is not blank, which at worst is the one it
+            // starts on.
+            let last = (start_line..marker_line)
+                .rev()
+                .find(|&i| !lines[i].trim().is_empty())
+                .unwrap_or(start_line);
+            let contents = lines[last].trim_end();
+            let backslashes = contents.len() - contents.trim_end_matches('\\').len();
+            let quote = expanded[start_line].chars().nth(start.col()).unwrap_or('"');
+            // A trailing `\` would escape the quote, so escape a space with it instead.
+            let closing = if quote == '"' && backslashes % 2 == 1 {
+                " \""
+            } else if quote == '"' {
+                "\""
+            } else {
+                "'"
+            };
+            let col = expanded[last].trim_end().chars().count();
+            let quotes = Marker::new(starts[last].0 + col + added, last + 1, col);
+            ended.push((
+                shift(start, first_index + added, first_line),
+                shift(marker, first_index + added + closing.len(), first_line),
+            ));
+            added += closing.len();
+            self.closed_quotes.push((quotes, added));
+            closings.push((last, contents.len(), closing));
+
+            first_line = marker_line;
+            segment = PreScan::new(Cow::Borrowed(&scan.text[starts[first_line].1..]));
+            current = &segment;
+        }
+        if closings.is_empty() {
+            return (None, ended);
+        }
+
+        let mut closed = String::with_capacity(source.len() + added);
+        let mut closings = closings.into_iter().peekable();
+        for (i, line) in lines.iter().enumerate() {
+            match closings.next_if(|&(last, ..)| last == i) {
+                Some((_, offset, closing)) => {
+                    closed.push_str(&line[..offset]);
+                    closed.push_str(closing);
+                    closed.push_str(&line[offset..]);
+                }
+                None => closed.push_str(line),
+            }
+        }
+        (Some(closed), ended)
+    }
+
+    /// Return `marker`, pointing into the input with the quotes of `closed_quotes` added, as it
+    /// points into the input without them.
+    fn unclosed_marker(&self, marker: Marker) -> Marker {
+        let count = self
+            .closed_quotes
+            .partition_point(|(quotes, _)| quotes.index() < marker.index());
+        let Some(&(quotes, added)) = count
+            .checked_sub(1)
+            .and_then(|last| self.closed_quotes.get(last))
+        else {
+            return marker;
+        };
+        // Only the last quotes before the marker may be on its line.
+        let col = if quotes.line() == marker.line() {
+            let before = count
+                .checked_sub(2)
+                .map_or(0, |previous| self.closed_quotes[previous].1);
+            marker.col().saturating_sub(added - before)
+        } else {
+            marker.col()
+        };
+        Marker::new(marker.index().saturating_sub(added), marker.line(), col)
+    }
+
+    /// Return the error to raise if the scalar at `marker`, as the parser sees it, is one a `---`
+    /// or `...` marker is found within, which [`MarkerInScalarPolicy::Reject`] forbids.
+    fn rejected_scalar(&self, marker: Marker) -> Option<LoadError> {
+        let index = self
+            .rejected_scalars
+            .binary_search_by_key(&marker.index(), |(scalar, _)| scalar.index())
+            .ok()?;
+        let (scalar, marker) = self.rejected_scalars[index];
+        Some(LoadError::MarkerInScalar {
+            scalar: self.offset_marker(scalar),
+            marker: self.offset_marker(marker),
+        })
+    }
+
+    /// Return `source` as the parser sees it, once tabs are expanded, so that markers point into
+    /// it.
+    fn expanded_source<'a>(&self, source: &'a str) -> Cow<'a, str> {
+        match self.tab_indentation {
+            Some(width) => Cow::Owned(
+                PreprocessedInput::new(source.chars())
+                    .expand_indentation_tabs(width)
+                    .collect(),
+            ),
+            None => Cow::Borrowed(source),
+        }
+    }
+
+    /// Load `source`, through the input adapters the options require, if any.
+    fn load_input(&mut self, source: &str) -> Result<Vec<Node>, LoadError> {
+        let Some(max_line_length) = self.max_line_length else {
+            return self.load_expanded(source.chars());
+        };
+        let mut limited = LineLimitedInput::new(source.chars(), max_line_length);
+        let result = self.load_expanded(&mut limited);
+        // The parser most likely failed because the input was cut short.
+        match limited.exceeded() {
+            Some(marker) => {
+                let error = LoadError::LineTooLong(self.offset_marker(marker));
+                Err(self.locate_error(error, false))
+            }
+            None => result,
+        }
+    }
+
+    /// Load `input`, expanding the tabs of its indentation if [`Self::tab_indentation`] is set.
+    fn load_expanded<I: Iterator<Item = char>>(
+        &mut self,
+        input: I,
+    ) -> Result<Vec<Node>, LoadError> {
+        let Some(width) = self.tab_indentation else {
+            return self.load_cancellable(input);
+        };
+        let mut input = PreprocessedInput::new(input).expand_indentation_tabs(width);
+        let result = self.load_cancellable(&mut input);
+        for marker in input.expanded_indentation_tabs() {
+            let marker = self.offset_marker(*marker);
+            self.warnings.push(LoadWarning::TabIndentation(marker));
+        }
+        result
+    }
+
+    /// Load `input`, stopping early if the token set with [`Self::cancellation`] is cancelled.
+    fn load_cancellable<I: Iterator<Item = char>>(
+        &mut self,
+        input: I,
+    ) -> Result<Vec<Node>, LoadError> {
+        match self.cancellation.clone() {
+            Some(token) => {
+                self.load_from_parser(&mut Parser::new(CancellableInput::new(input, token)))
+            }
+            None => self.load_from_parser(&mut Parser::new(input)),
+        }
+    }
+
+    /// Load the contents from the specified [`Parser`], using the options set on `self`.
+    ///
+    /// See [`Self::load_from_str`] for details. Options altering the input (e.g.:
+    /// [`Self::tab_indentation`]) are ignored, since the parser already owns its input.
+    ///
+    /// # Errors
+    /// Returns `LoadError` when the input is not valid YAML or goes against the options.
+    pub fn load_from_parser<I: Iterator<Item = char>>(
+        &mut self,
+        parser: &mut Parser<I>,
+    ) -> Result<Vec<Node>, LoadError> {
+        let mut result = Ok(());
+        let docs = self.load_from_events(|loader| result = parser.load(loader, true))?;
+        result.map_err(|error| self.scan_error(&error))?;
+        Ok(docs)
+    }
+
+    /// Load the first document of `source`, and return it along with the rest of the input.
+    ///
+    /// This is meant for YAML embedded in larger protocols, where a document is followed by other
+    /// data. The document must end with a `...` marker for the rest of the input to be left
+    /// unparsed; the rest then starts on the line after the marker. Otherwise, it starts at the
+    /// `---` of the next document, or is empty. Returns `None` if `source` has no document.
+    ///
+    /// ```
+    /// use saphyr::{Yaml, YamlLoader};
+    ///
+    /// let buffer = "command: put\nkey: a\n...\n\x00\x01binary payload";
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// let (doc, rest) = loader.load_first_document(buffer).unwrap().unwrap();
+    /// assert_eq!(doc["command"].as_str(), Some("put"));
+    /// assert_eq!(rest, "\x00\x01binary payload");
+    ///
+    /// let (doc, rest) = loader.load_first_document("a\n---\nb").unwrap().unwrap();
+    /// assert_eq!((doc.as_str(), rest), (Some("a"), "---\nb"));
+    /// assert!(loader.load_first_document("# nothing").unwrap().is_none());
+    /// ```
+    ///
+    /// Options altering the input (e.g.: [`Self::tab_indentation`]) are ignored, as with
+    /// [`Self::load_from_parser`].
+    ///
+    /// # Errors
+    /// Returns `LoadError` when the document is not valid YAML or goes against the options. The
+    /// position at which parsing stopped is then given by [`LoadError::marker`].
+    pub fn load_first_document<'input>(
+        &mut self,
+        source: &'input str,
+    ) -> Result<Option<(Node, &'input str)>, LoadError> {
+        let mut parser = Parser::new_from_str(source);
+        let mut end = None;
+        let mut result = Ok(());
+        let mut docs = self.load_from_events(|loader| {
+            let mut receiver = FirstDocumentReceiver {
+                loader,
+                end: &mut end,
+            };
+            result = parser.load(&mut receiver, false);
+        })?;
+        result.map_err(|error| self.scan_error(&error))?;
+        let (Some(doc), Some(end)) = (docs.pop(), end) else {
+            return Ok(None);
+        };
+        let offset = source
+            .char_indices()
+            .nth(end.index())
+            .map_or(source.len(), |(offset, _)| offset);
+        let rest = &source[offset..];
+        // The parser ends documents at their `...` marker, which is followed by a comment at most.
+        let rest = match rest.strip_prefix("...") {
+            Some(marker_line) => marker_line
+                .find('\n')
+                .map_or("", |line_break| &marker_line[line_break + 1..]),
+            None => rest,
+        };
+        Ok(Some((doc, rest)))
+    }
+
+    /// Load the documents from the events `feed` sends to the loader, using the options set on
+    /// `self`.
+    ///
+    /// This allows loading events which do not come from a [`Parser`], e.g. events rewritten
+    /// by a filter. `feed` sends them through [`MarkedEventReceiver::on_event`].
+    ///
+    /// ```
+    /// use saphyr::{Event, LoadError, Marker, MarkedEventReceiver, TScalarStyle, Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// let docs = loader
+    ///     .load_from_events(|loader| {
+    ///         let marker = Marker::default();
+    ///         loader.on_event(Event::DocumentStart, marker);
+    ///         loader.on_event(Event::Scalar("1".into(), TScalarStyle::Plain, 0, None), marker);
+    ///         loader.on_event(Event::DocumentEnd, marker);
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(docs, [Yaml::Integer(1)]);
+    ///
+    /// let result = loader.load_from_events(|loader| {
+    ///     loader.on_event(Event::DocumentStart, Marker::default());
+    ///     loader.on_event(Event::SequenceEnd, Marker::default());
+    /// });
+    /// assert!(matches!(result, Err(LoadError::UnexpectedEvent(_))));
+    /// ```
+    ///
+    /// Errors raised by the loader take precedence over those `feed` may have run into, since
+    /// they happened first.
+    ///
+    /// # Errors
+    /// Returns `LoadError` when the events go against the options, or do not form a valid YAML
+    /// stream.
+    pub fn load_from_events<F: FnOnce(&mut Self)>(
+        &mut self,
+        feed: F,
+    ) -> Result<Vec<Node>, LoadError> {
+        self.reset();
+        feed(self);
+        // The names of anchors only hold for the stream they were scanned from.
+        self.anchor_names = None;
+        if self
+            .cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            return Err(self.locate_error(LoadError::Cancelled, false));
+        }
+        if let Some(error) = self.error.take() {
+            return Err(self.locate_error(error, false));
+        }
+        Ok(std::mem::take(&mut self.docs))
+    }
+
+    /// Convert the scan error the parser failed with, wrapping it in a [`LoadError::InCollection`]
+    /// and a [`LoadError::InDocument`] as the options require it.
+    fn scan_error(&self, error: &ScanError) -> LoadError {
+        if error.info() == QUOTED_MARKER_ERROR {
+            if let Some(error) = self.rejected_scalar(*error.marker()) {
+                return self.locate_error(error, false);
+            }
+        }
+        let mut error = LoadError::Scan(ScanError::new_str(
+            self.offset_marker(*error.marker()),
+            error.info(),
+        ));
+        if let Some(&(mapping, start)) = self.collection_starts.last() {
+            error = LoadError::InCollection {
+                kind: if mapping { "mapping" } else { "sequence" },
+                start,
+                error: Box::new(error),
+            };
+        }
+        self.locate_error(error, false)
+    }
+
+    /// Wrap `error` in a [`LoadError::InDocument`], if [`Self::report_error_documents`] is set.
+    ///
+    /// The error is in the document being loaded when it was raised, unless it was raised
+    /// by checking the input once `loaded`, in which case its marker tells which document it is
+    /// in.
+    fn locate_error(&self, error: LoadError, loaded: bool) -> LoadError {
+        if !self.report_error_documents || matches!(error, LoadError::InDocument { .. }) {
+            return error;
+        }
+        let index = match error.marker() {
+            Some(marker) if loaded => self
+                .documents
+                .iter()
+                .take_while(|document| document.end.index() <= marker.index())
+                .count(),
+            _ => self.documents.len(),
+        };
+        let start = match (self.documents.get(index), self.documents.last()) {
+            (Some(document), _) => document.start,
+            // The document had not started yet, which it does where the previous one ends.
+            (None, Some(previous)) if previous.start == self.document_start => previous.end,
+            (None, _) => self.document_start,
+        };
+        LoadError::InDocument {
+            index,
+            start,
+            error: Box::new(error),
+        }
+    }
+
+    /// Return the warnings raised during the last load.
+    ///
+    /// Warnings report suspicious inputs that loaded successfully thanks to the options set on
+    /// `self`. They are available even if loading ultimately failed.
+    #[must_use]
+    pub fn warnings(&self) -> &[LoadWarning] {
+        &self.warnings
+    }
+
+    /// Return information about each document of the last load, in order.
+    ///
+    /// This is available even if the documents were not loaded through [`Self::load_from_str`]
+    /// or [`Self::load_from_parser`].
+    ///
+    /// ```
+    /// use saphyr::{Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// let docs = loader.load_from_str("---\n--- null\n").unwrap();
+    /// assert_eq!(docs, [Yaml::Null, Yaml::Null]);
+    /// assert!(loader.documents()[0].empty);
+    /// assert!(!loader.documents()[1].empty);
+    /// ```
+    #[must_use]
+    pub fn documents(&self) -> &[DocumentInfo] {
+        &self.documents
+    }
+
+    /// Clear the state left by a previous load, keeping options.
+    fn reset(&mut self) {
+        self.docs.clear();
+        self.doc_stack.clear();
+        self.key_stack.clear();
+        self.collection_tags.clear();
+        self.anchor_map.clear();
+        self.anchor_tables.clear();
+        self.anchor_definitions.clear();
+        self.unused_anchors.clear();
+        self.alias_depths.clear();
+        self.anchor_alias_depths.clear();
+        self.error = None;
+        self.warnings.clear();
+        self.documents.clear();
+        self.key_names.clear();
+        self.mapping_keys.clear();
+        self.collection_starts.clear();
+    }
+
+    /// Load `source` as [`Self::load_from_str`] does, along with an [`AnchorTable`] for each
+    /// document.
+    pub(crate) fn load_from_str_with_anchors(
+        &mut self,
+        source: &str,
+    ) -> Result<Vec<(Node, AnchorTable<Node>)>, LoadError> {
+        self.anchor_names = Some(scan_anchors(source.chars()));
+        let docs = self.load_from_str(source)?;
+        let mut tables = std::mem::take(&mut self.anchor_tables).into_iter();
+        Ok(docs
+            .into_iter()
+            .map(|doc| (doc, tables.next().unwrap_or_default()))
+            .collect())
+    }
+
+    /// Record the definition of the anchor `aid` (if any), applying the duplicate anchor policy.
+    fn define_anchor(&mut self, aid: usize) {
+        let Some(definition) = aid
+            .checked_sub(1)
+            .and_then(|index| self.anchor_names.as_ref()?.get(index))
+        else {
+            return;
+        };
+        let name = definition.name.clone();
+        let second = self.offset_marker(definition.marker);
+        if self.warn_unused_anchors {
+            self.unused_anchors.insert(aid);
+        }
+        let Some(first) = self.anchor_definitions.insert(name.clone(), second) else {
+            return;
+        };
+        match self.duplicate_anchor_policy {
+            DuplicateAnchorPolicy::Allow => {}
+            DuplicateAnchorPolicy::Warn => {
+                self.warnings.push(LoadWarning::DuplicateAnchor {
+                    name,
+                    first,
+                    second,
+                });
             }
-            Event::Alias(id) => {
-                let n = match self.anchor_map.get(&id) {
-                    Some(v) => v.clone(),
-                    None => Node::from_bare_yaml(Yaml::BadValue),
-                };
-                self.insert_new_node((n.with_marker(marker), 0));
+            DuplicateAnchorPolicy::Reject => {
+                self.error = Some(LoadError::DuplicateAnchor {
+                    name,
+                    first,
+                    second,
+                });
             }
         }
     }
-}
 
-impl<Node> YamlLoader<Node>
-where
-    Node: LoadableYamlNode,
-{
-    fn insert_new_node(&mut self, node: (Node, usize)) {
-        // valid anchor id starts from 1
-        if node.1 > 0 {
-            self.anchor_map.insert(node.1, node.0.clone());
+    /// Move the anchors of the document that just ended into a new [`AnchorTable`].
+    ///
+    /// Anchors cannot be referred to from another document, so we can drop them afterwards.
+    fn end_document_anchors(&mut self) {
+        let anchor_map = std::mem::take(&mut self.anchor_map);
+        self.anchor_definitions.clear();
+        self.anchor_alias_depths.clear();
+        let unused_anchors = std::mem::take(&mut self.unused_anchors);
+        if let Some(names) = &self.anchor_names {
+            for id in unused_anchors {
+                let definition = &names[id - 1];
+                self.warnings.push(LoadWarning::UnusedAnchor {
+                    name: definition.name.clone(),
+                    marker: self.offset_marker(definition.marker),
+                });
+            }
         }
-        if let Some(parent) = self.doc_stack.last_mut() {
-            let parent_node = &mut parent.0;
-            if parent_node.is_array() {
-                parent_node.array_mut().push(node.0);
-            } else if parent_node.is_hash() {
-                let cur_key = self.key_stack.last_mut().unwrap();
-                // current node is a key
-                if cur_key.is_badvalue() {
-                    *cur_key = node.0;
-                // current node is a value
-                } else {
-                    let hash = parent_node.hash_mut();
-                    hash.insert(cur_key.take(), node.0);
+        if let Some(names) = &self.anchor_names {
+            let mut table = AnchorTable::new();
+            for (id, node) in anchor_map {
+                if let Some(definition) = names.get(id - 1) {
+                    table.insert(definition.name.clone(), node);
                 }
             }
-        } else {
-            self.doc_stack.push(node);
+            self.anchor_tables.push(table);
         }
     }
+}
 
-    /// Return the document nodes from `self`, consuming it in the process.
-    #[must_use]
-    pub fn into_documents(self) -> Vec<Node> {
-        self.docs
+/// A receiver forwarding events to a loader, recording the raw position at which the document
+/// ends. See [`YamlLoader::load_first_document`].
+struct FirstDocumentReceiver<'a, Node: LoadableYamlNode> {
+    /// The loader receiving the events.
+    loader: &'a mut YamlLoader<Node>,
+    /// The position of the end of the document, once it ended.
+    end: &'a mut Option<Marker>,
+}
+
+impl<Node: LoadableYamlNode> MarkedEventReceiver for FirstDocumentReceiver<'_, Node> {
+    fn on_event(&mut self, ev: Event, marker: Marker) {
+        if ev == Event::DocumentEnd {
+            *self.end = Some(marker);
+        }
+        self.loader.on_event(ev, marker);
     }
 }
 
 /// An error that happened when loading a YAML document.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum LoadError {
     /// An I/O error.
     IO(std::io::Error),
@@ -180,6 +1988,166 @@ pub enum LoadError {
     Scan(ScanError),
     /// A decoding error (e.g.: Invalid UTF-8).
     Decode(std::borrow::Cow<'static, str>),
+    /// A real was used as a mapping key, which the [`RealKeyPolicy`] forbids.
+    RealKey(Marker),
+    /// Loading was cancelled through a [`CancellationToken`].
+    Cancelled,
+    /// A line of the input was longer than [`YamlLoader::max_line_length`] allows. The marker
+    /// points to the first `char` past the limit.
+    LineTooLong(Marker),
+    /// An anchor was defined more than once in a document, which the [`DuplicateAnchorPolicy`]
+    /// forbids.
+    DuplicateAnchor {
+        /// The name of the anchor, without the leading `&`.
+        name: String,
+        /// The position of the previous definition of the anchor.
+        first: Marker,
+        /// The position of the definition that redefined it.
+        second: Marker,
+    },
+    /// A key appeared more than once in a mapping, which the [`DuplicateKeyPolicy`] forbids.
+    DuplicateKey {
+        /// The repeated key, if it is a scalar (e.g.: `port`, without quotes), or `?`.
+        key: String,
+        /// The position of the first occurrence of the key.
+        first: Marker,
+        /// The position of the occurrence that repeated it.
+        second: Marker,
+    },
+    /// A scalar key contained characters outside of the [`YamlLoader::key_charset`], or was
+    /// empty. Holds the key as written and its position.
+    InvalidKey(String, Marker),
+    /// An alias nested aliases deeper than [`YamlLoader::max_alias_depth`] allows. The marker
+    /// points to the alias.
+    AliasTooDeep(Marker),
+    /// Contents followed a `...` document end marker without a `---` separator, which
+    /// [`YamlLoader::reject_trailing_content`] forbids. The marker points to the contents.
+    TrailingContent(Marker),
+    /// A file was not read because it would have exceeded [`LoadOptions::max_total_bytes`],
+    /// which this holds.
+    ///
+    /// [`LoadOptions::max_total_bytes`]: crate::LoadOptions::max_total_bytes
+    BudgetExceeded(u64),
+    /// The stream had more documents than [`YamlLoader::max_documents`] allows. The marker points
+    /// to the start of the first document past the limit.
+    TooManyDocuments(Marker),
+    /// The loader received events which do not form a valid YAML stream (e.g.: a sequence ending
+    /// where none started). The parser never sends such events; they may only be sent by hand
+    /// (see [`YamlLoader::load_from_events`]). The marker is that of the unexpected event.
+    UnexpectedEvent(Marker),
+    /// A plain `=` was used as a mapping key, which the [`ValueKeyPolicy`] forbids.
+    ValueKey(Marker),
+    /// A value was written right after the `:` of its key, which
+    /// [`YamlLoader::reject_adjacent_values`] forbids. The marker points to the value.
+    AdjacentValue(Marker),
+    /// The input was not valid UTF-8, which [`Utf8Policy::Strict`] forbids.
+    ///
+    /// [`Utf8Policy::Strict`]: crate::Utf8Policy::Strict
+    InvalidUtf8 {
+        /// The offset in bytes of the first invalid sequence.
+        offset: usize,
+        /// The position of the first invalid sequence in the text decoded before it.
+        marker: Marker,
+    },
+    /// An error in a document of a stream, which [`YamlLoader::report_error_documents`] tells.
+    InDocument {
+        /// The index of the document in the stream, starting from 0.
+        index: usize,
+        /// The position at which the document starts.
+        start: Marker,
+        /// The error.
+        error: Box<LoadError>,
+    },
+    /// A `---` or `...` marker started a line within a scalar, which [`MarkerInScalarPolicy::Reject`]
+    /// forbids.
+    MarkerInScalar {
+        /// The position at which the scalar starts.
+        scalar: Marker,
+        /// The position of the marker.
+        marker: Marker,
+    },
+    /// A scan error within a collection, which [`YamlLoader::report_error_collections`] tells.
+    InCollection {
+        /// The kind of the innermost collection containing the error which the parser started:
+        /// `sequence` or `mapping`.
+        kind: &'static str,
+        /// The position at which the collection starts.
+        start: Marker,
+        /// The scan error.
+        error: Box<LoadError>,
+    },
+}
+
+impl LoadError {
+    /// Return the stable code of the error, which [`Display`] prefixes the message with.
+    ///
+    /// ```
+    /// use saphyr::{LoadError, Yaml, YamlLoader};
+    ///
+    /// let error = YamlLoader::<Yaml>::default().load_from_str("[a").unwrap_err();
+    /// assert_eq!(error.code().as_str(), "SCAN039");
+    /// assert!(error.to_string().starts_with("SCAN039: "));
+    /// ```
+    ///
+    /// [`Display`]: std::fmt::Display
+    #[must_use]
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            LoadError::IO(_) => ErrorCode::new("LOAD001"),
+            LoadError::Scan(e) => ErrorCode::of_scan_error(e),
+            LoadError::Decode(_) => ErrorCode::new("LOAD002"),
+            LoadError::RealKey(_) => ErrorCode::new("LOAD003"),
+            LoadError::Cancelled => ErrorCode::new("LOAD004"),
+            LoadError::LineTooLong(_) => ErrorCode::new("LOAD005"),
+            LoadError::DuplicateAnchor { .. } => ErrorCode::new("LOAD006"),
+            LoadError::AliasTooDeep(_) => ErrorCode::new("LOAD007"),
+            LoadError::InvalidKey(..) => ErrorCode::new("LOAD008"),
+            LoadError::TrailingContent(_) => ErrorCode::new("LOAD009"),
+            LoadError::BudgetExceeded(_) => ErrorCode::new("LOAD010"),
+            LoadError::TooManyDocuments(_) => ErrorCode::new("LOAD011"),
+            LoadError::UnexpectedEvent(_) => ErrorCode::new("LOAD012"),
+            LoadError::ValueKey(_) => ErrorCode::new("LOAD013"),
+            LoadError::AdjacentValue(_) => ErrorCode::new("LOAD014"),
+            LoadError::InvalidUtf8 { .. } => ErrorCode::new("LOAD015"),
+            LoadError::DuplicateKey { .. } => ErrorCode::new("LOAD016"),
+            LoadError::MarkerInScalar { .. } => ErrorCode::new("LOAD017"),
+            LoadError::InDocument { error, .. } | LoadError::InCollection { error, .. } => {
+                error.code()
+            }
+        }
+    }
+
+    /// Return the position in the input at which the error happened, if it is known.
+    ///
+    /// This can be used to build an [`ErrorSnippet`] to display along with the error.
+    ///
+    /// [`ErrorSnippet`]: crate::ErrorSnippet
+    #[must_use]
+    pub fn marker(&self) -> Option<&Marker> {
+        match self {
+            LoadError::Scan(e) => Some(e.marker()),
+            LoadError::RealKey(marker)
+            | LoadError::LineTooLong(marker)
+            | LoadError::DuplicateAnchor { second: marker, .. }
+            | LoadError::DuplicateKey { second: marker, .. }
+            | LoadError::AliasTooDeep(marker)
+            | LoadError::InvalidKey(_, marker)
+            | LoadError::TrailingContent(marker)
+            | LoadError::TooManyDocuments(marker)
+            | LoadError::UnexpectedEvent(marker)
+            | LoadError::ValueKey(marker)
+            | LoadError::AdjacentValue(marker)
+            | LoadError::InvalidUtf8 { marker, .. }
+            | LoadError::MarkerInScalar { marker, .. } => Some(marker),
+            LoadError::InDocument { error, .. } | LoadError::InCollection { error, .. } => {
+                error.marker()
+            }
+            LoadError::IO(_)
+            | LoadError::Decode(_)
+            | LoadError::Cancelled
+            | LoadError::BudgetExceeded(_) => None,
+        }
+    }
 }
 
 impl From<std::io::Error> for LoadError {
@@ -193,21 +2161,557 @@ impl std::error::Error for LoadError {
         Some(match &self {
             LoadError::IO(e) => e,
             LoadError::Scan(e) => e,
-            LoadError::Decode(_) => return None,
+            LoadError::InDocument { error, .. } | LoadError::InCollection { error, .. } => {
+                error.as_ref()
+            }
+            LoadError::Decode(_)
+            | LoadError::RealKey(_)
+            | LoadError::Cancelled
+            | LoadError::LineTooLong(_)
+            | LoadError::DuplicateAnchor { .. }
+            | LoadError::DuplicateKey { .. }
+            | LoadError::AliasTooDeep(_)
+            | LoadError::InvalidKey(..)
+            | LoadError::TrailingContent(_)
+            | LoadError::BudgetExceeded(_)
+            | LoadError::TooManyDocuments(_)
+            | LoadError::UnexpectedEvent(_)
+            | LoadError::ValueKey(_)
+            | LoadError::AdjacentValue(_)
+            | LoadError::InvalidUtf8 { .. }
+            | LoadError::MarkerInScalar { .. } => return None,
         })
     }
 }
 
 impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.code())?;
+        self.fmt_message(f)
+    }
+}
+
+impl LoadError {
+    /// Write the message of the error, without its code.
+    #[allow(clippy::too_many_lines)]
+    fn fmt_message(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::IO(e) => std::fmt::Display::fmt(e, f),
+            LoadError::Scan(e) => std::fmt::Display::fmt(e, f),
+            LoadError::Decode(e) => f.write_str(e),
+            LoadError::RealKey(marker) => write!(
+                f,
+                "real number used as a mapping key at line {} column {}",
+                marker.line(),
+                marker.col().saturating_add(1)
+            ),
+            LoadError::Cancelled => f.write_str("loading was cancelled"),
+            LoadError::LineTooLong(marker) => write!(
+                f,
+                "line {} is longer than {} characters",
+                marker.line(),
+                marker.col()
+            ),
+            LoadError::DuplicateAnchor {
+                name,
+                first,
+                second,
+            } => write_duplicate_anchor(f, name, first, second),
+            LoadError::DuplicateKey { key, first, second } => {
+                write_duplicate_key(f, key, first, second)
+            }
+            LoadError::InvalidKey(key, marker) => write!(
+                f,
+                "key {key:?} at line {} column {} contains characters that are not allowed",
+                marker.line(),
+                marker.col().saturating_add(1)
+            ),
+            LoadError::AliasTooDeep(marker) => write!(
+                f,
+                "alias at line {} column {} nests aliases too deeply",
+                marker.line(),
+                marker.col().saturating_add(1)
+            ),
+            LoadError::TooManyDocuments(marker) => write!(
+                f,
+                "document at line {} column {} exceeds the maximum number of documents",
+                marker.line(),
+                marker.col().saturating_add(1)
+            ),
+            LoadError::UnexpectedEvent(marker) => write!(
+                f,
+                "unexpected event at line {} column {}",
+                marker.line(),
+                marker.col().saturating_add(1)
+            ),
+            LoadError::ValueKey(marker) => write!(
+                f,
+                "`=` value key at line {} column {}",
+                marker.line(),
+                marker.col().saturating_add(1)
+            ),
+            LoadError::AdjacentValue(marker) => write!(
+                f,
+                "value at line {} column {} is not separated from its key by a space",
+                marker.line(),
+                marker.col().saturating_add(1)
+            ),
+            LoadError::MarkerInScalar { scalar, marker } => {
+                write_marker_in_scalar(f, scalar, marker)
+            }
+            LoadError::InvalidUtf8 { offset, marker } => write!(
+                f,
+                "invalid UTF-8 at byte {offset} (line {} column {})",
+                marker.line(),
+                marker.col().saturating_add(1)
+            ),
+            LoadError::InDocument {
+                index,
+                start,
+                error,
+            } => {
+                write!(
+                    f,
+                    "in document {} starting at line {}: ",
+                    index.saturating_add(1),
+                    start.line()
+                )?;
+                error.fmt_message(f)
+            }
+            LoadError::InCollection { kind, start, error } => {
+                write!(
+                    f,
+                    "in the {kind} starting at line {} column {}: ",
+                    start.line(),
+                    start.col().saturating_add(1)
+                )?;
+                error.fmt_message(f)
+            }
+            LoadError::BudgetExceeded(max) => {
+                write!(f, "reading the file would exceed the budget of {max} bytes")
+            }
+            LoadError::TrailingContent(marker) => write!(
+                f,
+                "contents at line {} column {} follow the end of a document without a `---` separator",
+                marker.line(),
+                marker.col().saturating_add(1)
+            ),
+        }
+    }
+}
+
+/// Information about a document of a YAML stream, beyond its contents.
+///
+/// See [`YamlLoader::documents`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DocumentInfo {
+    /// The position at which the document starts.
+    pub start: Marker,
+    /// The position at which the document ends.
+    pub end: Marker,
+    /// Whether the document has no contents (e.g.: `---` followed by another `---`).
+    ///
+    /// Empty documents load as [`Yaml::Null`], as do documents containing only `null` or `~`.
+    /// This allows telling them apart, for instance to write them back with
+    /// [`YamlEmitter::dump_empty`].
+    ///
+    /// [`YamlEmitter::dump_empty`]: crate::YamlEmitter::dump_empty
+    pub empty: bool,
+}
+
+/// A suspicious construct the [`YamlLoader`] accepted because of its options.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LoadWarning {
+    /// Tabs were used to indent the line at the given position, which points to the first of them
+    /// in the original input. See [`YamlLoader::tab_indentation`].
+    TabIndentation(Marker),
+    /// An anchor was defined more than once in a document. Aliases refer to the last definition
+    /// preceding them. See [`YamlLoader::duplicate_anchor_policy`].
+    DuplicateAnchor {
+        /// The name of the anchor, without the leading `&`.
+        name: String,
+        /// The position of the previous definition of the anchor.
+        first: Marker,
+        /// The position of the definition that redefined it.
+        second: Marker,
+    },
+    /// A key appeared more than once in a mapping. The last value of the key replaces the
+    /// previous ones. See [`YamlLoader::duplicate_key_policy`].
+    DuplicateKey {
+        /// The repeated key, if it is a scalar (e.g.: `port`, without quotes), or `?`.
+        key: String,
+        /// The position of the first occurrence of the key.
+        first: Marker,
+        /// The position of the occurrence that repeated it.
+        second: Marker,
+    },
+    /// A flow collection ended with a comma, at the given position. See
+    /// [`YamlLoader::warn_trailing_commas`].
+    TrailingComma(Marker),
+    /// A line was indented by another width than the first indented line of its document. See
+    /// [`YamlLoader::warn_mixed_indentation`].
+    MixedIndentation {
+        /// The width, in spaces, the document is first indented by.
+        width: usize,
+        /// The width, in spaces, the line is indented by relative to its parent.
+        found: usize,
+        /// The position of the start of the contents of the line.
+        marker: Marker,
+    },
+    /// No alias referred to the anchor defined at the given position. See
+    /// [`YamlLoader::warn_unused_anchors`].
+    UnusedAnchor {
+        /// The name of the anchor, without the leading `&`.
+        name: String,
+        /// The position of the definition of the anchor.
+        marker: Marker,
+    },
+    /// A `---` or `...` marker started a line within a scalar, which was ended before it. See
+    /// [`MarkerInScalarPolicy::Terminate`].
+    ScalarEndedByMarker {
+        /// The position at which the scalar starts.
+        scalar: Marker,
+        /// The position of the marker.
+        marker: Marker,
+    },
+}
+
+impl std::fmt::Display for LoadWarning {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            LoadError::IO(e) => e.fmt(f),
-            LoadError::Scan(e) => e.fmt(f),
-            LoadError::Decode(e) => e.fmt(f),
+            LoadWarning::TabIndentation(marker) => write!(
+                f,
+                "tabs used for indentation at line {} column {}",
+                marker.line(),
+                marker.col().saturating_add(1)
+            ),
+            LoadWarning::DuplicateAnchor {
+                name,
+                first,
+                second,
+            } => write_duplicate_anchor(f, name, first, second),
+            LoadWarning::DuplicateKey { key, first, second } => {
+                write_duplicate_key(f, key, first, second)
+            }
+            LoadWarning::TrailingComma(marker) => write!(
+                f,
+                "trailing comma in flow collection at line {} column {}",
+                marker.line(),
+                marker.col().saturating_add(1)
+            ),
+            LoadWarning::MixedIndentation {
+                width,
+                found,
+                marker,
+            } => write!(
+                f,
+                "line {} indented by {found} spaces, while its document is indented by {width}",
+                marker.line()
+            ),
+            LoadWarning::UnusedAnchor { name, marker } => write!(
+                f,
+                "anchor `{name}` defined at line {} column {} is never used",
+                marker.line(),
+                marker.col().saturating_add(1)
+            ),
+            LoadWarning::ScalarEndedByMarker { scalar, marker } => {
+                write_marker_in_scalar(f, scalar, marker)?;
+                f.write_str(", which ends the scalar")
+            }
+        }
+    }
+}
+
+/// Describe the redefinition of the anchor `name` at `second`, first defined at `first`.
+fn write_duplicate_anchor(
+    f: &mut std::fmt::Formatter<'_>,
+    name: &str,
+    first: &Marker,
+    second: &Marker,
+) -> std::fmt::Result {
+    write!(
+        f,
+        "anchor `{name}` defined at line {} column {} is redefined at line {} column {}",
+        first.line(),
+        first.col().saturating_add(1),
+        second.line(),
+        second.col().saturating_add(1)
+    )
+}
+
+/// Describe the repetition of `key` at `second`, first appearing at `first`.
+fn write_duplicate_key(
+    f: &mut std::fmt::Formatter<'_>,
+    key: &str,
+    first: &Marker,
+    second: &Marker,
+) -> std::fmt::Result {
+    write!(
+        f,
+        "key {key:?} at line {} column {} is repeated at line {} column {}",
+        first.line(),
+        first.col().saturating_add(1),
+        second.line(),
+        second.col().saturating_add(1)
+    )
+}
+
+/// Describe the `---` or `...` marker at `marker` within the scalar starting at `scalar`.
+fn write_marker_in_scalar(
+    f: &mut std::fmt::Formatter<'_>,
+    scalar: &Marker,
+    marker: &Marker,
+) -> std::fmt::Result {
+    write!(
+        f,
+        "document marker at line {} within the scalar starting at line {} column {}",
+        marker.line(),
+        scalar.line(),
+        scalar.col().saturating_add(1)
+    )
+}
+
+/// How the [`YamlLoader`] handles anchors defined more than once in a document.
+///
+/// Redefining an anchor is valid YAML: aliases refer to the closest definition preceding them.
+/// Some tools disagree on this, and a redefinition is often a mistake in templated documents.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicateAnchorPolicy {
+    /// Accept redefined anchors (default).
+    #[default]
+    Allow,
+    /// Accept redefined anchors, raising a [`LoadWarning::DuplicateAnchor`] for each of them.
+    Warn,
+    /// Fail loading with [`LoadError::DuplicateAnchor`] if an anchor is redefined.
+    Reject,
+}
+
+/// How the [`YamlLoader`] handles keys appearing more than once in a mapping.
+///
+/// The YAML specification requires keys to be unique, but most loaders accept repeated keys and
+/// keep the last value, which hides mistakes such as a key pasted twice.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicateKeyPolicy {
+    /// Accept repeated keys, keeping the last value (default).
+    #[default]
+    Allow,
+    /// Accept repeated keys, raising a [`LoadWarning::DuplicateKey`] for each of them.
+    Warn,
+    /// Fail loading with [`LoadError::DuplicateKey`] if a key is repeated.
+    Reject,
+}
+
+/// How the [`YamlLoader`] handles `---` and `...` markers at the start of a line within a
+/// scalar.
+///
+/// Such markers end the document, and with it the scalar. Plain scalars spanning several lines
+/// end there, which is easy to miss when the marker was meant as part of their text. Quoted
+/// scalars must be closed first, so the parser fails on them, while some tools end them at the
+/// marker instead.
+///
+/// | Policy                         | Plain scalar                  | Quoted scalar                 |
+/// |--------------------------------|-------------------------------|-------------------------------|
+/// | [`Standard`](Self::Standard)   | ended                         | [`LoadError::Scan`]           |
+/// | [`Terminate`](Self::Terminate) | ended, with a warning         | ended, with a warning         |
+/// | [`Reject`](Self::Reject)       | [`LoadError::MarkerInScalar`] | [`LoadError::MarkerInScalar`] |
+///
+/// Plain scalars on a single line are not concerned: a marker on the next line is the usual way
+/// to end their document.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MarkerInScalarPolicy {
+    /// Follow the YAML specification (default): plain scalars end at the marker, and quoted
+    /// scalars fail loading with a scan error.
+    #[default]
+    Standard,
+    /// End scalars at the marker, raising a [`LoadWarning::ScalarEndedByMarker`] for each of
+    /// them.
+    ///
+    /// Quoted scalars are closed at the end of the last line before the marker which is not
+    /// blank. Positions still point into the input as it is written, without those quotes.
+    Terminate,
+    /// Fail loading with [`LoadError::MarkerInScalar`] if a marker is found within a plain
+    /// scalar spanning several lines, or within a quoted scalar.
+    Reject,
+}
+
+/// The keys of a mapping being loaded, to detect repeated ones.
+struct MappingKeys<Node> {
+    /// The position of the first occurrence of each key so far.
+    seen: HashMap<Node, Marker>,
+    /// The text and position of the key of the current entry, once it started.
+    current: Option<(String, Marker)>,
+}
+
+impl<Node> Default for MappingKeys<Node> {
+    fn default() -> Self {
+        Self {
+            seen: HashMap::new(),
+            current: None,
+        }
+    }
+}
+
+impl<Node: LoadableYamlNode> MappingKeys<Node> {
+    /// Record `key` as the key of the current entry.
+    ///
+    /// Returns the text of the key and the positions of its first and current occurrences if it
+    /// was already recorded.
+    fn insert(&mut self, key: &Node) -> Option<(String, Marker, Marker)> {
+        let (text, marker) = self.current.take()?;
+        if let Some(first) = self.seen.get(key) {
+            return Some((text, *first, marker));
+        }
+        self.seen.insert(key.clone(), marker);
+        None
+    }
+}
+
+/// A set of ASCII characters scalar keys are restricted to. See [`YamlLoader::key_charset`].
+///
+/// Whitespace and non-ASCII characters are never part of the set.
+///
+/// ```
+/// use saphyr::KeyCharset;
+///
+/// let charset = KeyCharset::ascii_alphanumeric().with("_");
+/// assert!(charset.accepts("max_connections"));
+/// assert!(!charset.accepts("max-connections"));
+/// assert!(!charset.accepts("caf\u{e9}"));
+/// assert!(!charset.accepts(""));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeyCharset {
+    /// The accepted characters, as a bit set indexed by their code.
+    chars: u128,
+}
+
+impl KeyCharset {
+    /// Create a set of the characters of `chars`. Whitespace and non-ASCII characters are
+    /// ignored.
+    #[must_use]
+    pub fn new(chars: &str) -> Self {
+        Self { chars: 0 }.with(chars)
+    }
+
+    /// Create the set of ASCII letters and digits.
+    #[must_use]
+    pub fn ascii_alphanumeric() -> Self {
+        Self::new("ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789")
+    }
+
+    /// Create the set of ASCII letters, digits, `_`, `-` and `.`, which most systems accept in
+    /// identifiers.
+    #[must_use]
+    pub fn identifier() -> Self {
+        Self::ascii_alphanumeric().with("_-.")
+    }
+
+    /// Return the set with the characters of `chars` added. Whitespace and non-ASCII characters
+    /// are ignored.
+    #[must_use]
+    pub fn with(mut self, chars: &str) -> Self {
+        for c in chars.chars() {
+            if c.is_ascii() && !c.is_ascii_whitespace() {
+                self.chars |= 1 << u32::from(c);
+            }
         }
+        self
+    }
+
+    /// Return whether `key` is made only of characters of the set, and is not empty.
+    #[must_use]
+    pub fn accepts(&self, key: &str) -> bool {
+        !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_ascii() && self.chars & (1 << u32::from(c)) != 0)
+    }
+}
+
+/// How the [`YamlLoader`] handles reals used as mapping keys.
+///
+/// [`Yaml::Real`] stores the text of the real, as written in the document. Two keys that hold the
+/// same number but are written differently (e.g.: `1.0` and `1.00`) are thus distinct keys, while
+/// all `.nan` keys collide even though NaN is not equal to itself. Since either may surprise, this
+/// policy allows choosing another behavior.
+///
+/// The policy only applies to scalar keys. Reals nested within a collection used as a key are kept
+/// as they are.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RealKeyPolicy {
+    /// Keep real keys as they are written (default).
+    #[default]
+    Verbatim,
+    /// Rewrite real keys in a canonical form, so that keys holding the same number collide.
+    ///
+    /// Numbers are compared as per [`f64::total_cmp`]: all NaNs are the same key and `-0.0` is
+    /// distinct from `0.0`.
+    Normalize,
+    /// Load real keys as [`Yaml::String`]s.
+    Stringify,
+    /// Fail loading if a NaN is used as a mapping key.
+    RejectNan,
+    /// Fail loading if any real is used as a mapping key.
+    Reject,
+}
+
+impl RealKeyPolicy {
+    /// Apply the policy to the real `v`, used as a mapping key at `marker`.
+    fn apply(self, v: String, marker: Marker) -> Result<Yaml, LoadError> {
+        // Reals only reach the loader if `parse_real` accepted them.
+        let value = parse_real(&v).unwrap_or(f64::NAN);
+        let rejected = match self {
+            RealKeyPolicy::Reject => true,
+            RealKeyPolicy::RejectNan => value.is_nan(),
+            _ => false,
+        };
+        if rejected {
+            return Err(LoadError::RealKey(marker));
+        }
+
+        Ok(match self {
+            RealKeyPolicy::Normalize if value.is_nan() => Yaml::Real(".nan".to_owned()),
+            RealKeyPolicy::Normalize if value.is_infinite() => {
+                Yaml::Real(if value > 0.0 { ".inf" } else { "-.inf" }.to_owned())
+            }
+            RealKeyPolicy::Normalize => Yaml::Real(format!("{value:?}")),
+            RealKeyPolicy::Stringify => Yaml::String(v),
+            _ => Yaml::Real(v),
+        })
     }
 }
 
+/// How the [`YamlLoader`] handles plain `=` mapping keys.
+///
+/// YAML 1.1 defines `=` as the "value" key: the value of a mapping with such a key is its
+/// default value, the other entries annotating it. Old tools relied on this to extend a scalar
+/// setting into a mapping while keeping its readers working. YAML 1.2 removed it, and `=` is a
+/// string like any other.
+///
+/// Only plain `=` scalars are value keys. Quoted ones (e.g.: `"=": a`) are always kept as strings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ValueKeyPolicy {
+    /// Keep `=` keys as strings, as YAML 1.2 does (default).
+    #[default]
+    Verbatim,
+    /// Load a mapping with a `=` key as the value of that key, dropping its other entries.
+    ///
+    /// An anchor on the mapping refers to the value. The value keeps its own position.
+    Collapse,
+    /// Fail loading if `=` is used as a mapping key.
+    Reject,
+}
+
+/// How [`YamlLoader::coerce_path`] resolves the plain scalars at a path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathCoercion {
+    /// Load them as strings, whatever they look like.
+    String,
+    /// Resolve them with the given schema, rather than that of the loader.
+    Schema(Schema),
+}
+
 /// A trait providing methods used by the [`YamlLoader`].
 ///
 /// This trait must be implemented on YAML node types (i.e.: [`Yaml`] and annotated YAML nodes). It
@@ -244,7 +2748,7 @@ pub trait LoadableYamlNode: Clone + std::hash::Hash + Eq {
     ///
     /// # Panics
     /// This function panics if `self` is not a hash.
-    fn hash_mut(&mut self) -> &mut LinkedHashMap<Self, Self>;
+    fn hash_mut(&mut self) -> &mut AnnotatedHash<Self>;
 
     /// Take the contained node out of `Self`, leaving a `BadValue` in its place.
     #[must_use]
@@ -256,6 +2760,16 @@ pub trait LoadableYamlNode: Clone + std::hash::Hash + Eq {
     fn with_marker(self, _: Marker) -> Self {
         self
     }
+
+    /// Wrap the node in the given tag (builder-style).
+    ///
+    /// The loader only calls this for tags it recognizes (e.g.: `!!set`), once the node is
+    /// complete. Nodes which cannot hold tags may ignore it.
+    #[inline]
+    #[must_use]
+    fn with_tag(self, _: crate::Tag) -> Self {
+        self
+    }
 }
 
 impl LoadableYamlNode for Yaml {
@@ -283,7 +2797,7 @@ impl LoadableYamlNode for Yaml {
         }
     }
 
-    fn hash_mut(&mut self) -> &mut LinkedHashMap<Self, Self> {
+    fn hash_mut(&mut self) -> &mut AnnotatedHash<Self> {
         if let Yaml::Hash(x) = self {
             x
         } else {
@@ -296,11 +2810,413 @@ impl LoadableYamlNode for Yaml {
         std::mem::swap(&mut taken_out, self);
         taken_out
     }
+
+    fn with_tag(self, tag: crate::Tag) -> Self {
+        Yaml::Tagged(tag, Box::new(self))
+    }
+}
+
+/// Build the [`Yaml`] node for a scalar, as per its style and tag.
+pub(crate) fn resolve_scalar(v: String, style: TScalarStyle, tag: Option<&Tag>) -> Yaml {
+    if style != TScalarStyle::Plain {
+        Yaml::String(v)
+    } else if let Some(Tag {
+        ref handle,
+        ref suffix,
+    }) = tag
+    {
+        if handle == "tag:yaml.org,2002:" {
+            match suffix.as_ref() {
+                "bool" => {
+                    // "true" or "false"
+                    match v.parse::<bool>() {
+                        Err(_) => Yaml::BadValue,
+                        Ok(v) => Yaml::Boolean(v),
+                    }
+                }
+                "int" => match v.parse::<i64>() {
+                    Err(_) => Yaml::BadValue,
+                    Ok(v) => Yaml::Integer(v),
+                },
+                "float" => match parse_real(&v) {
+                    Some(_) => Yaml::Real(v),
+                    None => Yaml::BadValue,
+                },
+                "null" => match v.as_ref() {
+                    "~" | "null" => Yaml::Null,
+                    _ => Yaml::BadValue,
+                },
+                _ => Yaml::String(v),
+            }
+        } else {
+            Yaml::String(v)
+        }
+    } else {
+        // Datatype is not specified, or unrecognized
+        Yaml::value_from_str(&v)
+    }
+}
+
+/// The first `char` standing for the placeholder of index 0 in masked inputs. Subsequent
+/// placeholders are numbered from there, in the Supplementary Private Use Area-A.
+const PLACEHOLDER_START: u32 = 0xF_0000;
+
+/// The number of placeholders which can be masked.
+const MAX_PLACEHOLDERS: usize = 0xF_FFFE - 0xF_0000;
+
+/// The `char` the rest of the text of a masked placeholder is replaced with.
+const PLACEHOLDER_FILLER: char = '\u{E000}';
+
+/// Replace placeholders delimited by `open` and `close` in `source` with text the parser reads
+/// as plain text, and return their original text.
+///
+/// Each placeholder is replaced with as many `char`s as it has, so that markers are unchanged.
+/// The first stands for the index of the placeholder, and the others are [`PLACEHOLDER_FILLER`].
+/// They are all in private use areas, which YAML allows in plain scalars. The `char`s of
+/// `source` which are already among them are masked as placeholders of their own, so that they
+/// are restored as they were.
+///
+/// Only plain scalars are masked: the text of quoted and block scalars is read as it is, and is
+/// not unmasked, so that escape sequences standing for the `char`s above are left alone.
+fn mask_placeholders(source: &str, open: &str, close: &str) -> (String, Vec<String>) {
+    let mut masked = String::with_capacity(source.len());
+    let mut placeholders = vec![];
+    let mut state = LineState::new();
+    for source_line in source.split_inclusive('\n') {
+        let mut line = source_line;
+        let mut masked_line = String::with_capacity(line.len());
+        loop {
+            let reserved = line
+                .char_indices()
+                .find(|(_, c)| *c == PLACEHOLDER_FILLER || placeholder_index(*c).is_some())
+                .map(|(start, c)| (start, start + c.len_utf8()));
+            let placeholder = line.find(open).and_then(|start| {
+                let len = line[start + open.len()..].find(close)?;
+                Some((start, start + open.len() + len + close.len()))
+            });
+            // Reserved `char`s within a placeholder are kept in its text.
+            let (start, end) = match (reserved, placeholder) {
+                (Some(reserved), Some(placeholder)) if reserved.0 < placeholder.0 => reserved,
+                (_, Some(placeholder)) => placeholder,
+                (Some(reserved), None) => reserved,
+                (None, None) => break,
+            };
+            // `MAX_PLACEHOLDERS` keeps the index within the private use area.
+            let index = match u32::try_from(placeholders.len()) {
+                Ok(index) if placeholders.len() < MAX_PLACEHOLDERS => {
+                    char::from_u32(PLACEHOLDER_START + index)
+                }
+                _ => None,
+            };
+            let Some(index) = index else {
+                break;
+            };
+            let placeholder = &line[start..end];
+            masked_line.push_str(&line[..start]);
+            masked_line.push(index);
+            masked_line.extend(
+                std::iter::repeat(PLACEHOLDER_FILLER).take(placeholder.chars().count() - 1),
+            );
+            placeholders.push(placeholder.to_owned());
+            line = &line[end..];
+        }
+        masked_line.push_str(line);
+        // Delimiters would throw the lexer off, so it lexes the masked line. Markers being
+        // unchanged, its columns are those of the source line too.
+        let tokens;
+        (tokens, state) = lex_line(&masked_line, state);
+        if masked_line == source_line {
+            masked.push_str(source_line);
+            continue;
+        }
+        let masked_chars: Vec<char> = masked_line.chars().collect();
+        let source_chars: Vec<char> = source_line.chars().collect();
+        let mut end = 0;
+        for token in tokens.iter().filter(|token| {
+            matches!(
+                token.kind,
+                LineTokenKind::QuotedString | LineTokenKind::BlockScalar
+            )
+        }) {
+            masked.extend(&masked_chars[end..token.start]);
+            masked.extend(&source_chars[token.start..token.end]);
+            end = token.end;
+        }
+        masked.extend(&masked_chars[end..]);
+    }
+    (masked, placeholders)
+}
+
+/// Return the index of the placeholder `c` stands for in masked inputs, if it stands for one.
+fn placeholder_index(c: char) -> Option<usize> {
+    let index = (c as u32).checked_sub(PLACEHOLDER_START)? as usize;
+    (index < MAX_PLACEHOLDERS).then_some(index)
+}
+
+/// The tokens of the input of [`YamlLoader::load_from_str`], scanned once before parsing it for
+/// the options which inspect the input.
+#[derive(Default)]
+struct PreScan<'a> {
+    /// The input, as the parser sees it.
+    text: Cow<'a, str>,
+    /// The tokens of the input, up to the first error.
+    tokens: Vec<Token>,
+    /// The error the scanner stopped at, if any. The parser will run into the same error.
+    error: Option<ScanError>,
+}
+
+impl<'a> PreScan<'a> {
+    /// Scan `text`.
+    fn new(text: Cow<'a, str>) -> Self {
+        let mut scanner = Scanner::new(text.chars());
+        let tokens = scanner.by_ref().collect();
+        let error = scanner.get_error();
+        Self {
+            text,
+            tokens,
+            error,
+        }
+    }
+}
+
+/// Return the position of the commas ending flow collections among `tokens`.
+fn scan_trailing_commas(tokens: &[Token]) -> Vec<Marker> {
+    let mut commas = vec![];
+    let mut last_comma = None;
+    for Token(marker, token) in tokens {
+        let marker = *marker;
+        match token {
+            TokenType::FlowEntry => last_comma = Some(marker),
+            TokenType::FlowSequenceEnd | TokenType::FlowMappingEnd => {
+                commas.extend(last_comma.take());
+            }
+            _ => last_comma = None,
+        }
+    }
+    commas
+}
+
+/// Return the position of the lines of block collections of `source` indented by another width
+/// than the first indented line of their document, with that width and theirs, given the
+/// `tokens` of `source`.
+fn scan_mixed_indentation(source: &str, tokens: &[Token]) -> Vec<(Marker, usize, usize)> {
+    // The lines on which a node or key of a block collection starts, and those on which
+    // documents start.
+    let mut node_lines = BTreeSet::new();
+    let mut document_lines = BTreeSet::new();
+    let mut flow_depth = 0usize;
+    for Token(marker, token) in tokens {
+        match token {
+            TokenType::DocumentStart => {
+                document_lines.insert(marker.line());
+            }
+            TokenType::FlowSequenceEnd | TokenType::FlowMappingEnd => {
+                flow_depth = flow_depth.saturating_sub(1);
+            }
+            TokenType::FlowSequenceStart | TokenType::FlowMappingStart => {
+                if flow_depth == 0 {
+                    node_lines.insert(marker.line());
+                }
+                flow_depth += 1;
+            }
+            TokenType::Key
+            | TokenType::BlockEntry
+            | TokenType::Scalar(..)
+            | TokenType::Alias(_)
+            | TokenType::Anchor(_)
+            | TokenType::Tag(..)
+                if flow_depth == 0 =>
+            {
+                node_lines.insert(marker.line());
+            }
+            _ => {}
+        }
+    }
+
+    let mut mixed = vec![];
+    // The width of the document, and the columns the nodes of the lines so far start at.
+    let mut width = None;
+    let mut columns: Vec<usize> = vec![];
+    let mut index = 0;
+    for (i, text) in source.split('\n').enumerate() {
+        let line = i + 1;
+        let start = index;
+        index += text.chars().count() + 1;
+        if document_lines.contains(&line) {
+            width = None;
+            columns.clear();
+            continue;
+        }
+        let indent = text.len() - text.trim_start_matches(' ').len();
+        let rest = &text[indent..];
+        if !node_lines.contains(&line) || rest.is_empty() || rest.starts_with('#') {
+            continue;
+        }
+        while columns.last().is_some_and(|&column| column > indent) {
+            columns.pop();
+        }
+        if let Some(&parent) = columns.last().filter(|&&parent| parent < indent) {
+            let found = indent - parent;
+            match width {
+                None => width = Some(found),
+                Some(width) if width != found => {
+                    mixed.push((Marker::new(start + indent, line, indent), width, found));
+                }
+                Some(_) => {}
+            }
+        }
+        // Nodes following the `-` of sequence items start columns of their own.
+        let mut column = indent;
+        let mut rest = rest;
+        loop {
+            if columns.last() != Some(&column) {
+                columns.push(column);
+            }
+            let Some(after) = rest
+                .strip_prefix('-')
+                .filter(|after| after.starts_with(' '))
+            else {
+                break;
+            };
+            let contents = after.trim_start_matches(' ');
+            if contents.is_empty() || contents.starts_with('#') {
+                break;
+            }
+            column += 1 + after.len() - contents.len();
+            rest = contents;
+        }
+    }
+    mixed
+}
+
+/// Return the position of the first of `tokens` following a `...` document end marker other than
+/// a `---` separator or a directive, if any.
+fn scan_trailing_content(tokens: &[Token]) -> Option<Marker> {
+    let mut after_end = false;
+    for Token(marker, token) in tokens {
+        match token {
+            TokenType::DocumentEnd => after_end = true,
+            TokenType::DocumentStart
+            | TokenType::StreamEnd
+            | TokenType::VersionDirective(..)
+            | TokenType::TagDirective(..) => after_end = false,
+            _ if after_end => return Some(*marker),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Return the position of the first value among `tokens` written right after the `:` of its key,
+/// if any.
+fn scan_adjacent_value(tokens: &[Token]) -> Option<Marker> {
+    let mut value_end = None;
+    for Token(marker, token) in tokens {
+        let starts_node = matches!(
+            token,
+            TokenType::Scalar(..)
+                | TokenType::Alias(_)
+                | TokenType::Anchor(_)
+                | TokenType::Tag(..)
+                | TokenType::FlowSequenceStart
+                | TokenType::FlowMappingStart
+        );
+        if starts_node && value_end == Some(marker.index()) {
+            return Some(*marker);
+        }
+        value_end = (*token == TokenType::Value).then_some(marker.index() + 1);
+    }
+    None
+}
+
+/// The information of the scan error raised when a quoted scalar reaches a `---` or `...` marker.
+const QUOTED_MARKER_ERROR: &str =
+    "while scanning a quoted scalar, found unexpected document indicator";
+
+/// Return the positions of the quoted scalar the scanner of `scan` failed on because a `---` or
+/// `...` marker is found within it, and of the marker, if it did.
+fn quoted_scalar_ended_by_marker(scan: &PreScan) -> Option<(Marker, Marker)> {
+    let start = *scan
+        .error
+        .as_ref()
+        .filter(|error| error.info() == QUOTED_MARKER_ERROR)?
+        .marker();
+    // The marker is on the first line after the start of the scalar starting with one.
+    let mut index = 0;
+    for (i, line) in scan.text.split_inclusive('\n').enumerate() {
+        if i >= start.line() && (is_marker_line(line, "---") || is_marker_line(line, "...")) {
+            return Some((start, Marker::new(index, i + 1, 0)));
+        }
+        index += line.chars().count();
+    }
+    None
+}
+
+/// Return the positions of the plain scalars of `source` spanning several lines which are ended
+/// by a `---` or `...` marker, along with those of the markers, given the `tokens` of `source`.
+fn scan_plain_scalars_ended_by_markers(source: &str, tokens: &[Token]) -> Vec<(Marker, Marker)> {
+    let lines: Vec<&str> = source.split('\n').collect();
+    let mut found = vec![];
+    let mut plain = None;
+    for Token(marker, token) in tokens {
+        let marker = *marker;
+        match token {
+            TokenType::Scalar(TScalarStyle::Plain, value) => plain = Some((marker, value)),
+            // Blocks are closed between the scalar and the marker.
+            TokenType::BlockEnd => {}
+            TokenType::DocumentStart | TokenType::DocumentEnd => {
+                if let Some((start, value)) = plain.take() {
+                    // The text of the scalar is that of its first line, unless it spans several.
+                    let first: String = lines[start.line() - 1].chars().skip(start.col()).collect();
+                    let first = first
+                        .find(" #")
+                        .or_else(|| first.find("\t#"))
+                        .map_or(first.as_str(), |end| &first[..end]);
+                    if first.trim() != value {
+                        found.push((start, marker));
+                    }
+                }
+            }
+            _ => plain = None,
+        }
+    }
+    found
+}
+
+/// Return the tag of a collection if it is one of the YAML 1.1 collection tags we recognize.
+fn collection_tag(tag: Option<Tag>) -> Option<crate::Tag> {
+    let tag = crate::Tag::from(tag?);
+    (tag.is_core("set") || tag.is_core("omap") || tag.is_core("pairs")).then_some(tag)
 }
 
-// parse f64 as Core schema
+/// Parse `v`, the text of a [`Yaml::Real`], as an `f64`.
+///
+/// This is the definition of a float [`Schema::Legacy`] resolves plain scalars with, and which
+/// `as_f64` (e.g.: [`Yaml::as_f64`]) parses reals with, so that applications can share it:
+///  - `.inf`, `.Inf` and `.INF`, optionally signed, are infinities, and `.nan`, `.NAN` and `NaN`
+///    are NaN (`.NaN` is not, but the other schemas load it as `.nan`);
+///  - otherwise, `v` is parsed as by [`f64::from_str`]: decimal numbers with an optional sign,
+///    fraction and exponent (e.g.: `-1.5e3`, `.5` or `7.`), as well as `inf`, `infinity` and
+///    `nan` in any case.
+///
+/// Underscores are not allowed, as in YAML 1.2 (e.g.: `1_000.5` is not a float), nor are the
+/// base 60 floats of YAML 1.1 (e.g.: `190:20:30.15`).
+///
+/// ```
+/// use saphyr::{parse_real, Yaml};
+///
+/// assert_eq!(parse_real("-1.5e3"), Some(-1500.0));
+/// assert_eq!(parse_real("+.INF"), Some(f64::INFINITY));
+/// assert!(parse_real(".nan").unwrap().is_nan());
+/// assert_eq!(parse_real("1_000.5"), None);
+/// assert_eq!(Yaml::Real("0.1".into()).as_f64(), parse_real("0.1"));
+/// ```
+///
+/// [`Schema::Legacy`]: crate::Schema::Legacy
+/// [`f64::from_str`]: std::str::FromStr::from_str
 // See: https://github.com/chyh1990/yaml-rust/issues/51
-pub(crate) fn parse_f64(v: &str) -> Option<f64> {
+#[must_use]
+pub fn parse_real(v: &str) -> Option<f64> {
     match v {
         ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => Some(f64::INFINITY),
         "-.inf" | "-.Inf" | "-.INF" => Some(f64::NEG_INFINITY),
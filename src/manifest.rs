@@ -0,0 +1,325 @@
+//! Utilities for multi-document streams of manifests, such as Kubernetes ones.
+
+use saphyr_parser::{Marker, ScanError};
+
+use crate::{EmitError, Yaml, YamlEmitter};
+
+/// A document of a multi-document stream, with the text it was written as.
+///
+/// This is designed for streams of Kubernetes manifests (e.g.: the output of `helm template`),
+/// which are commonly split, filtered, reordered and joined back. See [`split_manifests`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Manifest {
+    /// The text of the document, with its separator, its comments and its end marker, if any.
+    text: String,
+    /// Whether the text contains a `---` separator.
+    explicit_start: bool,
+    /// The contents of the document.
+    doc: Yaml,
+    /// Whether the contents may have been modified since the document was split.
+    modified: bool,
+}
+
+impl Manifest {
+    /// Return the text of the document, as it is written in the stream.
+    ///
+    /// This includes the `---` separator starting the document, if any, and the comments,
+    /// directives and blank lines around it. Comments at the end of the stream are part of its
+    /// last document.
+    #[must_use]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Return the contents of the document.
+    #[must_use]
+    pub fn doc(&self) -> &Yaml {
+        &self.doc
+    }
+
+    /// Return the contents of the document, for modification.
+    ///
+    /// This marks the manifest as modified, so that [`emit_manifests`] emits its contents rather
+    /// than its text.
+    pub fn doc_mut(&mut self) -> &mut Yaml {
+        self.modified = true;
+        &mut self.doc
+    }
+
+    /// Replace the contents of the document, marking the manifest as modified.
+    pub fn set_doc(&mut self, doc: Yaml) {
+        *self.doc_mut() = doc;
+    }
+
+    /// Return whether the manifest was marked as modified by [`Manifest::doc_mut`].
+    ///
+    /// Since the contents are not compared, this is also the case if they were borrowed mutably
+    /// without being changed.
+    #[must_use]
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// Return the contents of the document, dropping its text.
+    #[must_use]
+    pub fn into_doc(self) -> Yaml {
+        self.doc
+    }
+
+    /// Return the `kind` of the manifest (e.g.: `Deployment`), if it has one.
+    #[must_use]
+    pub fn kind(&self) -> Option<&str> {
+        self.doc["kind"].as_str()
+    }
+
+    /// Return the `apiVersion` of the manifest (e.g.: `apps/v1`), if it has one.
+    #[must_use]
+    pub fn api_version(&self) -> Option<&str> {
+        self.doc["apiVersion"].as_str()
+    }
+
+    /// Return the `metadata.name` of the manifest, if it has one.
+    #[must_use]
+    pub fn name(&self) -> Option<&str> {
+        self.doc["metadata"]["name"].as_str()
+    }
+
+    /// Return the `metadata.namespace` of the manifest, if it has one.
+    #[must_use]
+    pub fn namespace(&self) -> Option<&str> {
+        self.doc["metadata"]["namespace"].as_str()
+    }
+}
+
+/// Manifests of a stream sharing the same `apiVersion` and `kind`. See [`group_manifests`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ManifestGroup<'a> {
+    /// The `apiVersion` of the manifests, if they have one.
+    pub api_version: Option<&'a str>,
+    /// The `kind` of the manifests, if they have one.
+    pub kind: Option<&'a str>,
+    /// The manifests, in the order they appear in the stream.
+    pub manifests: Vec<&'a Manifest>,
+}
+
+/// Split a multi-document stream into its documents, keeping the text of each.
+///
+/// Joining the manifests back with [`join_manifests`] yields `source`, unless it has no
+/// document (e.g.: it only has comments), in which case there is no manifest to keep its text.
+///
+/// ```
+/// use saphyr::{group_manifests, join_manifests, split_manifests};
+///
+/// let source = "\
+/// # Source: app/service.yaml
+/// apiVersion: v1
+/// kind: Service
+/// metadata: { name: web }
+/// ---
+/// apiVersion: apps/v1
+/// kind: Deployment
+/// metadata: { name: web }
+/// ---
+/// apiVersion: v1
+/// kind: Service
+/// metadata: { name: db }
+/// ";
+/// let manifests = split_manifests(source).unwrap();
+/// assert_eq!(manifests[2].name(), Some("db"));
+/// assert_eq!(join_manifests(&manifests), source);
+///
+/// let groups = group_manifests(&manifests);
+/// assert_eq!(groups[0].kind, Some("Service"));
+/// assert_eq!(groups[0].manifests.len(), 2);
+///
+/// // Put deployments first.
+/// let reordered = join_manifests(groups.iter().rev().flat_map(|group| &group.manifests).copied());
+/// assert!(reordered.starts_with("---\napiVersion: apps/v1\n"));
+/// ```
+///
+/// Documents start at `---` separators, and after `...` end markers. Comments, directives and
+/// blank lines which precede a document are part of it.
+///
+/// # Errors
+/// Returns `ScanError` when loading fails, or if the text of the documents cannot be told apart.
+pub fn split_manifests(source: &str) -> Result<Vec<Manifest>, ScanError> {
+    let docs = Yaml::load_from_str(source)?;
+
+    // Split the text into chunks, each of which starts with a separator or has contents.
+    let mut chunks: Vec<(String, bool)> = Vec::new();
+    let mut current = String::new();
+    let mut explicit_start = false;
+    let mut has_contents = false;
+    let mut after_end = false;
+    for line in source.split_inclusive('\n') {
+        let starts_document = is_marker_line(line, "---");
+        if (starts_document || after_end) && (explicit_start || has_contents) {
+            chunks.push((std::mem::take(&mut current), explicit_start));
+            explicit_start = false;
+            has_contents = false;
+        }
+        after_end = is_marker_line(line, "...");
+        explicit_start |= starts_document;
+        has_contents |= !starts_document && !after_end && !is_blank_line(line);
+        current.push_str(line);
+    }
+    match chunks.last_mut() {
+        // Comments after the last document belong to it.
+        Some((text, _)) if !explicit_start && !has_contents => text.push_str(&current),
+        _ => chunks.push((current, explicit_start)),
+    }
+
+    // Document markers may not appear in the contents of a document, so each chunk should be a
+    // document. Refuse to pair chunks with the wrong documents if this does not hold.
+    if !docs.is_empty() && chunks.len() != docs.len() {
+        return Err(ScanError::new(
+            Marker::new(0, 1, 0),
+            format!(
+                "the stream has {} documents, but its text splits into {}",
+                docs.len(),
+                chunks.len()
+            ),
+        ));
+    }
+    Ok(chunks
+        .into_iter()
+        .zip(docs)
+        .map(|((text, explicit_start), doc)| Manifest {
+            text,
+            explicit_start,
+            doc,
+            modified: false,
+        })
+        .collect())
+}
+
+/// Group manifests by `apiVersion` and `kind`.
+///
+/// Groups are in the order of the first manifest of each in `manifests`.
+#[must_use]
+pub fn group_manifests(manifests: &[Manifest]) -> Vec<ManifestGroup<'_>> {
+    let mut groups: Vec<ManifestGroup<'_>> = Vec::new();
+    for manifest in manifests {
+        let (api_version, kind) = (manifest.api_version(), manifest.kind());
+        match groups
+            .iter_mut()
+            .find(|group| group.api_version == api_version && group.kind == kind)
+        {
+            Some(group) => group.manifests.push(manifest),
+            None => groups.push(ManifestGroup {
+                api_version,
+                kind,
+                manifests: vec![manifest],
+            }),
+        }
+    }
+    groups
+}
+
+/// Join manifests into a multi-document stream, in the given order.
+///
+/// The text of each manifest is kept as it is, even if its contents were modified (see
+/// [`emit_manifests`]). A `---` separator is added before manifests
+/// which did not have one (e.g.: the first document of a stream) when they are not the first of
+/// the joined stream, and a newline after manifests not ending with one.
+pub fn join_manifests<'a, I: IntoIterator<Item = &'a Manifest>>(manifests: I) -> String {
+    let mut out = String::new();
+    for manifest in manifests {
+        if !out.is_empty() && !out.ends_with('\n') {
+            out.push('\n');
+        }
+        if !out.is_empty() && !manifest.explicit_start {
+            out.push_str("---\n");
+        }
+        out.push_str(&manifest.text);
+    }
+    out
+}
+
+/// Join manifests into a multi-document stream, emitting only the ones which were modified.
+///
+/// Manifests which were not modified (see [`Manifest::is_modified`]) are written from their text,
+/// as [`join_manifests`] does, so that their formatting and comments do not change. This keeps the
+/// diff with the original stream down to the documents which did change. The contents of the
+/// others are emitted with a [`YamlEmitter`], after the comments and directives preceding them
+/// and followed by a `...` end marker if they had one.
+///
+/// ```
+/// use saphyr::{emit_manifests, split_manifests, Yaml};
+///
+/// let source = "\
+/// kind:   Service   # This comment and formatting stay.
+/// ---
+/// kind: Deployment
+/// spec: { replicas: 1 }
+/// ";
+/// let mut manifests = split_manifests(source).unwrap();
+/// manifests[1].doc_mut()["spec"]["replicas"] = Yaml::Integer(3);
+/// assert_eq!(emit_manifests(&manifests).unwrap(), "\
+/// kind:   Service   # This comment and formatting stay.
+/// ---
+/// kind: Deployment
+/// spec:
+///   replicas: 3
+/// ");
+/// ```
+///
+/// # Errors
+/// Returns `EmitError` when emitting a modified manifest fails.
+pub fn emit_manifests<'a, I: IntoIterator<Item = &'a Manifest>>(
+    manifests: I,
+) -> Result<String, EmitError> {
+    let mut out = String::new();
+    for manifest in manifests {
+        if !out.is_empty() && !out.ends_with('\n') {
+            out.push('\n');
+        }
+        if !manifest.modified {
+            if !out.is_empty() && !manifest.explicit_start {
+                out.push_str("---\n");
+            }
+            out.push_str(&manifest.text);
+            continue;
+        }
+        let first = out.is_empty();
+        // Keep the comments and directives preceding the document.
+        let preamble = manifest
+            .text
+            .split_inclusive('\n')
+            .take_while(|line| !is_marker_line(line, "---") && is_blank_line(line))
+            .map(str::len)
+            .sum();
+        out.push_str(&manifest.text[..preamble]);
+        let mut emitted = String::new();
+        YamlEmitter::new(&mut emitted).dump(&manifest.doc)?;
+        match emitted.strip_prefix("---\n") {
+            // Like `join_manifests`, only add a separator where the stream needs one.
+            Some(contents) if first && !manifest.explicit_start => {
+                out.push_str(contents);
+            }
+            _ => out.push_str(&emitted),
+        }
+        out.push('\n');
+        // The end marker may be needed by directives of the next document.
+        if manifest
+            .text
+            .split_inclusive('\n')
+            .any(|line| is_marker_line(line, "..."))
+        {
+            out.push_str("...\n");
+        }
+    }
+    Ok(out)
+}
+
+/// Return whether `line` is a document marker (`---` or `...`), optionally followed by contents.
+pub(crate) fn is_marker_line(line: &str, marker: &str) -> bool {
+    line.strip_prefix(marker)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\t', '\r', '\n']))
+}
+
+/// Return whether `line` has no contents, i.e. is empty, a comment or a directive.
+fn is_blank_line(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.is_empty() || trimmed.starts_with('#') || line.starts_with('%')
+}
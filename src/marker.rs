@@ -0,0 +1,67 @@
+//! Utilities to compare markers and convert them from and to byte offsets.
+
+use std::cmp::Ordering;
+
+use saphyr_parser::Marker;
+
+/// Additional methods on [`Marker`].
+///
+/// [`Marker`] is defined in `saphyr-parser`, and we can neither add inherent methods to it nor
+/// implement [`Ord`] for it. Bring this trait into scope to use them:
+///
+/// ```
+/// use saphyr::{Marker, MarkerExt};
+///
+/// let text = "name: café\nport: 80\n";
+/// let port = Marker::from_byte_offset(text, text.find("80").unwrap());
+/// assert_eq!((port.index(), port.line(), port.col()), (17, 2, 6));
+/// assert_eq!(port.to_byte_offset(text), 18);
+///
+/// let mut markers = vec![port, Marker::from_byte_offset(text, 0)];
+/// markers.sort_by(MarkerExt::cmp_position);
+/// assert_eq!(markers[1], port);
+/// ```
+///
+/// Markers count `char`s, as the scanner does, whereas editors and `str` slicing count bytes.
+pub trait MarkerExt {
+    /// Return the marker of the `char` at the byte `offset` of `text`.
+    ///
+    /// `offset` is moved back to the start of the `char` it is in, if any, and to the end of
+    /// `text` at most. Lines are separated by `\n`, as the scanner counts them.
+    fn from_byte_offset(text: &str, offset: usize) -> Self;
+
+    /// Return the byte offset in `text` of the `char` the marker points to, or the length of
+    /// `text` if the marker is past its end.
+    ///
+    /// `text` must be the text the marker was created from.
+    fn to_byte_offset(&self, text: &str) -> usize;
+
+    /// Compare the positions of markers from the same text, the way [`Ord::cmp`] would.
+    fn cmp_position(&self, other: &Self) -> Ordering;
+}
+
+impl MarkerExt for Marker {
+    fn from_byte_offset(text: &str, offset: usize) -> Self {
+        let mut offset = offset.min(text.len());
+        while !text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let before = &text[..offset];
+        let line_start = before.rfind('\n').map_or(0, |line_break| line_break + 1);
+        Marker::new(
+            before.chars().count(),
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count(),
+        )
+    }
+
+    fn to_byte_offset(&self, text: &str) -> usize {
+        text.char_indices()
+            .nth(self.index())
+            .map_or(text.len(), |(offset, _)| offset)
+    }
+
+    fn cmp_position(&self, other: &Self) -> Ordering {
+        self.index().cmp(&other.index())
+    }
+}
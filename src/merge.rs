@@ -0,0 +1,470 @@
+//! Deep merging of documents, e.g. layers of configuration files.
+
+use std::{collections::BTreeMap, path::Path};
+
+use saphyr_parser::{Marker, ScanError};
+
+use crate::{
+    allowlist::{parse_pattern, Segment},
+    yaml::{key_path, key_text},
+    LoadError, LookupError, LookupErrorKind, MarkedYaml, Yaml, YamlData,
+};
+
+impl Yaml {
+    /// Merge `other` into `self`, as a layer overriding it.
+    ///
+    /// Mappings are merged key by key, recursively: keys of `other` which `self` does not have are
+    /// appended to it. Any other node of `other` (including sequences) replaces the node of `self`
+    /// it merges into.
+    ///
+    /// ```
+    /// use saphyr::Yaml;
+    ///
+    /// let mut config = Yaml::load_from_str("server: {host: a, port: 80}\nhosts: [a]").unwrap().remove(0);
+    /// let local = Yaml::load_from_str("server: {port: 8080}\nhosts: [b, c]").unwrap().remove(0);
+    /// config.merge_from(local);
+    /// assert_eq!(
+    ///     config,
+    ///     Yaml::load_from_str("server: {host: a, port: 8080}\nhosts: [b, c]").unwrap()[0]
+    /// );
+    /// ```
+    pub fn merge_from(&mut self, other: Yaml) {
+        match (self, other) {
+            (Yaml::Hash(hash), Yaml::Hash(other)) => {
+                for (key, value) in other {
+                    match hash.get_mut(&key) {
+                        Some(node) => node.merge_from(value),
+                        None => {
+                            hash.insert(key, value);
+                        }
+                    }
+                }
+            }
+            (this, other) => *this = other,
+        }
+    }
+}
+
+/// How the layers of a [`ConfigStack`] are merged.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Merge mappings key by key, recursively. Other nodes, including sequences, replace the ones
+    /// they merge into (default). This is how [`Yaml::merge_from`] merges.
+    #[default]
+    Deep,
+    /// Merge as [`MergeStrategy::Deep`] does, but append the items of sequences to the sequences
+    /// they merge into.
+    AppendSequences,
+    /// Merge the top-level mappings key by key, the values of the layer replacing the previous
+    /// ones.
+    Shallow,
+}
+
+/// The source of the value of a leaf of a merged document. See [`Provenance`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValueOrigin {
+    /// The name of the source the value comes from (e.g.: the name of its file).
+    pub source: String,
+    /// The position of the value in the source.
+    pub marker: Marker,
+}
+
+/// The source of each leaf of a document merged from several ones.
+///
+/// Merging through [`Provenance::merge`] rather than [`Yaml::merge_from`] records which source
+/// provided the final value of each leaf, i.e. of each scalar, and of each empty collection. This
+/// allows telling users where a setting came from. Leaves are identified by their path, as
+/// [`Yaml::paths`] normalizes it.
+///
+/// ```
+/// use saphyr::{MarkedYaml, Provenance, Yaml};
+///
+/// let defaults = MarkedYaml::load_from_str("server:\n  host: a\n  port: 80").unwrap();
+/// let local = MarkedYaml::load_from_str("server: {port: 8080}").unwrap();
+/// let mut config = Yaml::Null;
+/// let mut provenance = Provenance::new();
+/// provenance.merge(&mut config, &defaults[0], "defaults.yaml");
+/// provenance.merge(&mut config, &local[0], "local.yaml");
+///
+/// assert_eq!(config["server"]["port"].as_i64(), Some(8080));
+/// let origin = provenance.get("server.port").unwrap();
+/// assert_eq!((origin.source.as_str(), origin.marker.col()), ("local.yaml", 15));
+/// assert_eq!(provenance.get("server.host").unwrap().source, "defaults.yaml");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Provenance {
+    /// The origin of each leaf, by path.
+    origins: BTreeMap<String, ValueOrigin>,
+}
+
+impl Provenance {
+    /// Create a record of no leaf.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Merge `other`, read from `source`, into `target` as [`Yaml::merge_from`] does, recording
+    /// the origin of each leaf `other` provides.
+    ///
+    /// The origins of the nodes `other` replaces are forgotten.
+    pub fn merge(&mut self, target: &mut Yaml, other: &MarkedYaml, source: &str) {
+        self.merge_at(target, other, "", source, MergeStrategy::Deep);
+    }
+
+    /// Return the origin of the leaf at `path`, if it was merged by `self`.
+    #[must_use]
+    pub fn get(&self, path: &str) -> Option<&ValueOrigin> {
+        self.origins.get(path)
+    }
+
+    /// Return the paths of the leaves and their origins, sorted by path.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ValueOrigin)> {
+        self.origins
+            .iter()
+            .map(|(path, origin)| (path.as_str(), origin))
+    }
+
+    /// Return the number of leaves whose origin is recorded.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.origins.len()
+    }
+
+    /// Return whether no origin is recorded.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.origins.is_empty()
+    }
+
+    /// Merge `other` into `target`, which is at `path`, as per `strategy`.
+    fn merge_at(
+        &mut self,
+        target: &mut Yaml,
+        other: &MarkedYaml,
+        path: &str,
+        source: &str,
+        strategy: MergeStrategy,
+    ) {
+        match (&mut *target, &other.data) {
+            (Yaml::Array(items), YamlData::Array(others))
+                if strategy == MergeStrategy::AppendSequences =>
+            {
+                // Empty collections are leaves, until nodes are merged into them.
+                if !others.is_empty() {
+                    self.origins.remove(path);
+                }
+                for item in others {
+                    self.record(item, &format!("{path}[{}]", items.len()), source);
+                    items.push(to_yaml(item));
+                }
+                return;
+            }
+            (Yaml::Hash(hash), YamlData::Hash(entries))
+                if strategy != MergeStrategy::Shallow || path.is_empty() =>
+            {
+                self.merge_entries(hash, entries, path, source, strategy);
+                return;
+            }
+            _ => {}
+        }
+        // The node is replaced, along with the leaves it contained.
+        self.origins.retain(|leaf, _| {
+            !leaf.strip_prefix(path).is_some_and(|rest| {
+                path.is_empty() || rest.is_empty() || rest.starts_with(['.', '['])
+            })
+        });
+        *target = to_yaml(other);
+        self.record(other, path, source);
+    }
+
+    /// Merge the `entries` of a mapping into the mapping `hash`, which is at `path`.
+    fn merge_entries(
+        &mut self,
+        hash: &mut crate::Hash,
+        entries: &crate::AnnotatedHash<MarkedYaml>,
+        path: &str,
+        source: &str,
+        strategy: MergeStrategy,
+    ) {
+        if !entries.is_empty() {
+            self.origins.remove(path);
+        }
+        for (key, value) in entries {
+            let key = to_yaml(key);
+            let path = key_path(path, key_text(&key).as_deref());
+            // `LinkedHashMap::entry` would move existing keys to the end.
+            if !hash.contains_key(&key) {
+                hash.insert(key.clone(), Yaml::BadValue);
+            }
+            if let Some(node) = hash.get_mut(&key) {
+                self.merge_at(node, value, &path, source, strategy);
+            }
+        }
+    }
+
+    /// Record `source` as the origin of the leaves of `node`, which is at `path`.
+    fn record(&mut self, node: &MarkedYaml, path: &str, source: &str) {
+        match &node.data {
+            YamlData::Array(items) if !items.is_empty() => {
+                for (index, item) in items.iter().enumerate() {
+                    self.record(item, &format!("{path}[{index}]"), source);
+                }
+            }
+            YamlData::Hash(entries) if !entries.is_empty() => {
+                for (key, value) in entries {
+                    let path = key_path(path, key_text(&to_yaml(key)).as_deref());
+                    self.record(value, &path, source);
+                }
+            }
+            _ => {
+                self.origins.insert(
+                    path.to_owned(),
+                    ValueOrigin {
+                        source: source.to_owned(),
+                        marker: node.marker,
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// A configuration merged from layers of YAML inputs, which remembers where each value comes
+/// from.
+///
+/// Layers are added in priority order: each overrides the ones added before it (e.g.: defaults,
+/// then a system file, then a user file), as per the [`MergeStrategy`] set when it is added. The
+/// getters take paths written as [`Yaml::paths`] returns them (e.g.: `server.ports[0]`), and
+/// [`Self::origin`] tells which layer the value at a path comes from.
+///
+/// ```
+/// use saphyr::ConfigStack;
+///
+/// let mut config = ConfigStack::new();
+/// config.add_str("defaults", "server: {host: localhost, port: 80}").unwrap();
+/// config.add_str("user.yaml", "server:\n  port: \"8080\"").unwrap();
+///
+/// assert_eq!(config.get_str("server.host"), Ok("localhost"));
+/// let error = config.get_i64("server.port").unwrap_err();
+/// assert_eq!(
+///     error.to_string(),
+///     "LOOKUP003: expected integer at `server.port`, found string"
+/// );
+/// let origin = config.origin("server.port").unwrap();
+/// assert_eq!((origin.source.as_str(), origin.marker.line()), ("user.yaml", 2));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ConfigStack {
+    /// How the next layers are merged.
+    strategy: MergeStrategy,
+    /// The merged configuration.
+    doc: Yaml,
+    /// The origin of each leaf of `doc`.
+    provenance: Provenance,
+}
+
+impl Default for ConfigStack {
+    fn default() -> Self {
+        Self {
+            strategy: MergeStrategy::default(),
+            doc: Yaml::Null,
+            provenance: Provenance::default(),
+        }
+    }
+}
+
+impl ConfigStack {
+    /// Create an empty configuration, merging layers as per [`MergeStrategy::Deep`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how the layers added afterwards are merged (defaults to [`MergeStrategy::Deep`]).
+    pub fn strategy(&mut self, strategy: MergeStrategy) -> &mut Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Add the documents of `text` as layers, named `source` in origins.
+    ///
+    /// # Errors
+    /// Returns `ScanError` if `text` is not valid YAML, in which case no layer is added.
+    pub fn add_str(&mut self, source: &str, text: &str) -> Result<&mut Self, ScanError> {
+        for doc in MarkedYaml::load_from_str(text)? {
+            self.add_doc(source, &doc);
+        }
+        Ok(self)
+    }
+
+    /// Add the documents of the file at `path` as layers, named after the path in origins.
+    ///
+    /// # Errors
+    /// Returns `LoadError` if the file cannot be read, or is not valid YAML.
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> Result<&mut Self, LoadError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        self.add_str(&path.display().to_string(), &text)
+            .map_err(LoadError::Scan)
+    }
+
+    /// Add `doc` as a layer, named `source` in origins.
+    pub fn add_doc(&mut self, source: &str, doc: &MarkedYaml) -> &mut Self {
+        self.provenance
+            .merge_at(&mut self.doc, doc, "", source, self.strategy);
+        self
+    }
+
+    /// Return the merged configuration.
+    #[must_use]
+    pub fn doc(&self) -> &Yaml {
+        &self.doc
+    }
+
+    /// Return the origins of the leaves of the merged configuration.
+    #[must_use]
+    pub fn provenance(&self) -> &Provenance {
+        &self.provenance
+    }
+
+    /// Return the origin of the leaf at `path`, if it has one.
+    #[must_use]
+    pub fn origin(&self, path: &str) -> Option<&ValueOrigin> {
+        self.provenance.get(path)
+    }
+
+    /// Return the node at `path`, if there is one.
+    #[must_use]
+    pub fn get(&self, path: &str) -> Option<&Yaml> {
+        self.lookup(path).ok()
+    }
+
+    /// Return the string at `path`.
+    ///
+    /// # Errors
+    /// Returns a [`LookupError`] if there is no node at `path`, or if it is not a string.
+    pub fn get_str(&self, path: &str) -> Result<&str, LookupError> {
+        self.get_as(path, "string", Yaml::as_str)
+    }
+
+    /// Return the integer at `path`.
+    ///
+    /// # Errors
+    /// Returns a [`LookupError`] if there is no node at `path`, or if it is not an integer.
+    pub fn get_i64(&self, path: &str) -> Result<i64, LookupError> {
+        self.get_as(path, "integer", Yaml::as_i64)
+    }
+
+    /// Return the real at `path`, which may be written as an integer.
+    ///
+    /// # Errors
+    /// Returns a [`LookupError`] if there is no node at `path`, or if it is not a number.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn get_f64(&self, path: &str) -> Result<f64, LookupError> {
+        self.get_as(path, "real", |node| {
+            node.as_f64().or_else(|| node.as_i64().map(|i| i as f64))
+        })
+    }
+
+    /// Return the boolean at `path`.
+    ///
+    /// # Errors
+    /// Returns a [`LookupError`] if there is no node at `path`, or if it is not a boolean.
+    pub fn get_bool(&self, path: &str) -> Result<bool, LookupError> {
+        self.get_as(path, "boolean", Yaml::as_bool)
+    }
+
+    /// Return the node at `path`, or why there is none.
+    fn lookup(&self, path: &str) -> Result<&Yaml, LookupError> {
+        let mut node = &self.doc;
+        let mut parents = vec![];
+        for segment in parse_pattern(path) {
+            let (text, expected) = segment_text(&segment);
+            let error = |kind| LookupError {
+                path: parents.clone(),
+                segment: text.clone(),
+                kind,
+            };
+            node = match (node.untagged(), segment) {
+                (Yaml::Hash(hash), Segment::Key(segment)) => {
+                    hash.iter()
+                        .find(|(key, _)| key_text(key).as_deref() == Some(segment.as_str()))
+                        .ok_or_else(|| error(LookupErrorKind::MissingKey))?
+                        .1
+                }
+                (Yaml::Array(items), Segment::Index(index)) => items
+                    .get(index)
+                    .ok_or_else(|| error(LookupErrorKind::IndexOutOfRange(items.len())))?,
+                // Wildcards designate no single node.
+                (
+                    Yaml::Hash(_) | Yaml::Array(_),
+                    Segment::AnyKey | Segment::NonScalarKey | Segment::AnyIndex | Segment::AnyDepth,
+                ) => return Err(error(LookupErrorKind::MissingKey)),
+                (node, _) => {
+                    return Err(error(LookupErrorKind::TypeMismatch {
+                        expected,
+                        found: node.type_name(),
+                    }))
+                }
+            };
+            parents.push(text);
+        }
+        Ok(node)
+    }
+
+    /// Return the node at `path` converted by `convert`, which returns `None` if the node is not
+    /// of the `expected` type.
+    fn get_as<'a, T>(
+        &'a self,
+        path: &str,
+        expected: &'static str,
+        convert: fn(&'a Yaml) -> Option<T>,
+    ) -> Result<T, LookupError> {
+        let node = self.lookup(path)?;
+        convert(node.untagged()).ok_or_else(|| LookupError {
+            path: parse_pattern(path)
+                .iter()
+                .map(|segment| segment_text(segment).0)
+                .collect(),
+            segment: String::new(),
+            kind: LookupErrorKind::TypeMismatch {
+                expected,
+                found: node.type_name(),
+            },
+        })
+    }
+}
+
+/// Return the text of `segment` in a [`LookupError`], and the type of the nodes it looks into.
+fn segment_text(segment: &Segment) -> (String, &'static str) {
+    match segment {
+        Segment::Key(key) => (key.clone(), "mapping"),
+        Segment::AnyKey => ("*".to_owned(), "mapping"),
+        Segment::NonScalarKey => ("[?]".to_owned(), "mapping"),
+        Segment::Index(index) => (index.to_string(), "sequence"),
+        Segment::AnyIndex => ("[*]".to_owned(), "sequence"),
+        Segment::AnyDepth => ("**".to_owned(), "mapping"),
+    }
+}
+
+/// Copy `node` into a [`Yaml`] node, without its markers.
+fn to_yaml(node: &MarkedYaml) -> Yaml {
+    match &node.data {
+        YamlData::Real(v) => Yaml::Real(v.clone()),
+        YamlData::Integer(v) => Yaml::Integer(*v),
+        YamlData::String(v) => Yaml::String(v.clone()),
+        YamlData::Boolean(v) => Yaml::Boolean(*v),
+        YamlData::Array(items) => Yaml::Array(items.iter().map(to_yaml).collect()),
+        YamlData::Hash(entries) => Yaml::Hash(
+            entries
+                .iter()
+                .map(|(key, value)| (to_yaml(key), to_yaml(value)))
+                .collect(),
+        ),
+        YamlData::Tagged(tag, node) => Yaml::Tagged(tag.clone(), Box::new(to_yaml(node))),
+        YamlData::Alias(id) => Yaml::Alias(*id),
+        YamlData::Null => Yaml::Null,
+        YamlData::BadValue => Yaml::BadValue,
+    }
+}
@@ -0,0 +1,346 @@
+//! Surgical write-back of edited YAML documents.
+//!
+//! Re-emitting a whole document loses comments, formatting and quoting choices of the original
+//! text. When only some scalars have been edited, it is preferable to only replace those in the
+//! original text. This module computes such minimal patches.
+//!
+//! This is not a full concrete syntax tree implementation. Only edits to scalar values can be
+//! applied surgically. If the structure of a document changed (e.g.: keys were added or removed,
+//! a scalar was replaced by a collection), the patch falls back to re-emitting the whole stream.
+
+use std::{collections::HashSet, ops::Range};
+
+use saphyr_parser::{
+    scanner::{Scanner, Token, TokenType},
+    ScanError,
+};
+
+use crate::{emitter::escape_str, MarkedYaml, Yaml, YamlData, YamlEmitter};
+
+/// A replacement of a range of bytes in a text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextEdit {
+    /// The range of bytes in the original text to replace.
+    pub range: Range<usize>,
+    /// The text to insert in place of the range.
+    pub replacement: String,
+}
+
+/// Compute the edits to apply to `original` so that it loads as `edited`.
+///
+/// `original` is parsed again to find the position of each node. Then, each document of `edited`
+/// is compared against the corresponding document of `original`. Scalars that differ are replaced
+/// in place, leaving the rest of the text (comments included) untouched.
+///
+/// If the differences cannot be expressed as scalar replacements, a single edit replacing the
+/// whole text with the emitted `edited` documents is returned. This is also the case when an
+/// edited scalar has an anchor an alias refers to, since the alias would change along with it. If
+/// there is no difference, no edit is returned.
+///
+/// ```
+/// use saphyr::{apply_patch, compute_patch, Yaml};
+///
+/// let original = "# Server settings
+/// host: localhost # change me
+/// port: 8080
+/// ";
+/// let mut docs = Yaml::load_from_str(original).unwrap();
+/// docs[0]["port"] = Yaml::Integer(9090);
+///
+/// let edits = compute_patch(original, &docs).unwrap();
+/// assert_eq!(edits.len(), 1);
+/// assert_eq!(apply_patch(original, &edits), "# Server settings
+/// host: localhost # change me
+/// port: 9090
+/// ");
+/// ```
+///
+/// # Errors
+/// Returns `ScanError` if `original` is not valid YAML.
+pub fn compute_patch(original: &str, edited: &[Yaml]) -> Result<Vec<TextEdit>, ScanError> {
+    let original_docs = MarkedYaml::load_from_str(original)?;
+    let offsets = CharOffsets::new(original);
+    let aliased = aliased_scalars(original);
+
+    let mut edits = vec![];
+    let surgical = original_docs.len() == edited.len()
+        && original_docs
+            .iter()
+            .zip(edited)
+            .all(|(old, new)| diff_node(original, &offsets, &aliased, old, new, &mut edits));
+
+    if surgical {
+        Ok(edits)
+    } else {
+        Ok(vec![TextEdit {
+            range: 0..original.len(),
+            replacement: emit_stream(edited),
+        }])
+    }
+}
+
+/// Apply the given edits to `original` and return the resulting text.
+///
+/// Edits must not overlap. They may be given in any order.
+///
+/// # Panics
+/// This function panics if the range of an edit is out of bounds or not on a `char` boundary.
+#[must_use]
+pub fn apply_patch(original: &str, edits: &[TextEdit]) -> String {
+    let mut edits = edits.iter().collect::<Vec<_>>();
+    edits.sort_by_key(|edit| edit.range.start);
+
+    let mut output = String::with_capacity(original.len());
+    let mut position = 0;
+    for edit in edits {
+        output.push_str(&original[position..edit.range.start]);
+        output.push_str(&edit.replacement);
+        position = edit.range.end;
+    }
+    output.push_str(&original[position..]);
+    output
+}
+
+/// Emit all documents of a stream, separated by newlines.
+fn emit_stream(docs: &[Yaml]) -> String {
+    let mut output = String::new();
+    for doc in docs {
+        // Emitting into a `String` cannot fail.
+        let _ = YamlEmitter::new(&mut output).dump(doc);
+        output.push('\n');
+    }
+    output
+}
+
+/// Compare `old` and `new`, pushing edits for each differing scalar into `edits`.
+///
+/// Return `false` if the differences cannot be expressed as scalar replacements. Scalars whose
+/// anchor is referred to by an alias (see [`aliased_scalars`]) cannot be replaced.
+fn diff_node(
+    source: &str,
+    offsets: &CharOffsets,
+    aliased: &HashSet<usize>,
+    old: &MarkedYaml,
+    new: &Yaml,
+    edits: &mut Vec<TextEdit>,
+) -> bool {
+    match (&old.data, new) {
+        (YamlData::Array(old_items), Yaml::Array(new_items)) => {
+            old_items.len() == new_items.len()
+                && old_items
+                    .iter()
+                    .zip(new_items)
+                    .all(|(old, new)| diff_node(source, offsets, aliased, old, new, edits))
+        }
+        (YamlData::Hash(old_entries), Yaml::Hash(new_entries)) => {
+            old_entries.len() == new_entries.len()
+                && old_entries.iter().zip(new_entries).all(
+                    |((old_key, old_value), (new_key, new_value))| {
+                        same_node(old_key, new_key)
+                            && diff_node(source, offsets, aliased, old_value, new_value, edits)
+                    },
+                )
+        }
+        (YamlData::Tagged(old_tag, old_node), Yaml::Tagged(new_tag, new_node))
+            if old_tag == new_tag =>
+        {
+            diff_node(source, offsets, aliased, old_node, new_node, edits)
+        }
+        (YamlData::Array(_) | YamlData::Hash(_) | YamlData::Tagged(..), _)
+        | (_, Yaml::Array(_) | Yaml::Hash(_) | Yaml::Tagged(..)) => same_node(old, new),
+        _ if same_node(old, new) => true,
+        _ if aliased.contains(&old.marker.index()) => false,
+        _ => match scalar_range(source, offsets, old) {
+            Some(range) => {
+                let quote = source[range.clone()].chars().next();
+                edits.push(TextEdit {
+                    range,
+                    replacement: render_scalar(new, quote),
+                });
+                true
+            }
+            None => false,
+        },
+    }
+}
+
+/// Return the positions, in `char`s, of the scalars of `source` whose anchor an alias refers to.
+///
+/// Aliases load as copies of the node their anchor is attached to. Replacing the text of such a
+/// scalar would change its aliases along with it.
+fn aliased_scalars(source: &str) -> HashSet<usize> {
+    let mut aliases = HashSet::new();
+    let mut anchored = vec![];
+    let mut anchor = None;
+    for Token(marker, token) in Scanner::new(source.chars()) {
+        match token {
+            TokenType::Anchor(name) => anchor = Some(name),
+            TokenType::Alias(name) => {
+                aliases.insert(name);
+            }
+            TokenType::Scalar(..) => {
+                if let Some(name) = anchor.take() {
+                    anchored.push((name, marker.index()));
+                }
+            }
+            // Properties may be given in any order.
+            TokenType::Tag(..) => {}
+            _ => anchor = None,
+        }
+    }
+    anchored
+        .into_iter()
+        .filter(|(name, _)| aliases.contains(name))
+        .map(|(_, index)| index)
+        .collect()
+}
+
+/// Return whether the marked node holds the same data as the YAML node.
+fn same_node(marked: &MarkedYaml, yaml: &Yaml) -> bool {
+    match (&marked.data, yaml) {
+        (YamlData::Real(a), Yaml::Real(b)) | (YamlData::String(a), Yaml::String(b)) => a == b,
+        (YamlData::Integer(a), Yaml::Integer(b)) => a == b,
+        (YamlData::Boolean(a), Yaml::Boolean(b)) => a == b,
+        (YamlData::Alias(a), Yaml::Alias(b)) => a == b,
+        (YamlData::Tagged(tag_a, a), Yaml::Tagged(tag_b, b)) => tag_a == tag_b && same_node(a, b),
+        (YamlData::Null, Yaml::Null) | (YamlData::BadValue, Yaml::BadValue) => true,
+        (YamlData::Array(a), Yaml::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_node(a, b))
+        }
+        (YamlData::Hash(a), Yaml::Hash(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b)
+                    .all(|((ka, va), (kb, vb))| same_node(ka, kb) && same_node(va, vb))
+        }
+        _ => false,
+    }
+}
+
+/// Find the range of bytes in `source` spanned by the scalar `node`.
+///
+/// Return `None` if it cannot be found reliably (e.g.: block scalars, multi-line plain scalars,
+/// aliases, empty nodes). To be sure, the text we find is loaded again and must yield the same
+/// value as `node`.
+fn scalar_range(source: &str, offsets: &CharOffsets, node: &MarkedYaml) -> Option<Range<usize>> {
+    let start = offsets.byte(node.marker.index())?;
+    let text = &source[start..];
+    let length = match text.chars().next()? {
+        '"' => double_quoted_length(text)?,
+        '\'' => single_quoted_length(text)?,
+        '|' | '>' | '*' => return None,
+        _ => plain_length(text),
+    };
+    let range = start..start + length;
+
+    let reloaded = Yaml::load_from_str(&source[range.clone()]).ok()?;
+    match reloaded.as_slice() {
+        [doc] if same_node(node, doc) => Some(range),
+        _ => None,
+    }
+}
+
+/// Return the length in bytes of the double-quoted scalar at the start of `text`.
+fn double_quoted_length(text: &str) -> Option<usize> {
+    let mut chars = text.char_indices().skip(1);
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '"' => return Some(idx + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Return the length in bytes of the single-quoted scalar at the start of `text`.
+fn single_quoted_length(text: &str) -> Option<usize> {
+    let mut chars = text.char_indices().skip(1).peekable();
+    while let Some((idx, c)) = chars.next() {
+        if c == '\'' {
+            if chars.peek().map(|&(_, c)| c) == Some('\'') {
+                chars.next();
+            } else {
+                return Some(idx + 1);
+            }
+        }
+    }
+    None
+}
+
+/// Return the length in bytes of the single-line plain scalar at the start of `text`.
+///
+/// The scalar ends at the end of the line, before a comment, a flow indicator or a `: `.
+fn plain_length(text: &str) -> usize {
+    let mut end = 0;
+    let mut previous_blank = false;
+    let mut chars = text.char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, c)| c);
+        let ends_scalar = match c {
+            '\n' | '\r' | ',' | '[' | ']' | '{' | '}' => true,
+            '#' => previous_blank,
+            ':' => matches!(
+                next,
+                None | Some(' ' | '\t' | '\n' | '\r' | ',' | ']' | '}')
+            ),
+            _ => false,
+        };
+        if ends_scalar {
+            break;
+        }
+        previous_blank = c == ' ' || c == '\t';
+        if !previous_blank {
+            end = idx + c.len_utf8();
+        }
+    }
+    end
+}
+
+/// Render a scalar as it would appear in a document.
+///
+/// If the original scalar was quoted with `quote`, the same quoting is kept if possible.
+fn render_scalar(node: &Yaml, quote: Option<char>) -> String {
+    let mut output = String::new();
+    match node {
+        Yaml::String(v) if quote == Some('"') => {
+            // Writing into a `String` cannot fail.
+            let _ = escape_str(&mut output, v);
+        }
+        Yaml::String(v)
+            if quote == Some('\'') && !v.contains(|c: char| c.is_control() || c == '\n') =>
+        {
+            output.push('\'');
+            output.push_str(&v.replace('\'', "''"));
+            output.push('\'');
+        }
+        _ => {
+            let _ = YamlEmitter::new(&mut output).emit_node(node);
+        }
+    }
+    output
+}
+
+/// A table converting indices in `char`s (as in [`saphyr_parser::Marker`]) into byte offsets.
+struct CharOffsets {
+    /// The byte offset of each `char`, followed by the length of the text.
+    offsets: Vec<usize>,
+}
+
+impl CharOffsets {
+    fn new(source: &str) -> Self {
+        let mut offsets = source
+            .char_indices()
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>();
+        offsets.push(source.len());
+        Self { offsets }
+    }
+
+    /// Return the byte offset of the `char` at the given index.
+    fn byte(&self, char_index: usize) -> Option<usize> {
+        self.offsets.get(char_index).copied()
+    }
+}
@@ -0,0 +1,453 @@
+//! Utilities to manipulate YAML scalars and their styles.
+
+use std::str::Chars;
+
+use saphyr_parser::{
+    scanner::{Scanner, Token, TokenType},
+    Event, Marker, Parser, ScanError, TScalarStyle,
+};
+
+use crate::{char_traits, emitter::need_quotes, loader::resolve_scalar, Tag, Yaml};
+
+/// Additional methods on [`TScalarStyle`].
+///
+/// [`TScalarStyle`] is defined in `saphyr-parser` and we cannot add inherent methods to it. Bring
+/// this trait into scope to use them:
+///
+/// ```
+/// use saphyr::{ScalarStyleExt, TScalarStyle};
+///
+/// assert!(TScalarStyle::Folded.is_block());
+/// assert!(TScalarStyle::SingleQuoted.is_flow());
+/// ```
+///
+/// Note that the folded style is spelled [`TScalarStyle::Folded`] by the version of
+/// `saphyr-parser` we depend on. Older versions of `yaml-rust` spelled it `Foled`; there is no
+/// need for a compatibility alias here.
+pub trait ScalarStyleExt {
+    /// Return whether the style is a block scalar style (literal `|` or folded `>`).
+    fn is_block(&self) -> bool;
+
+    /// Return whether the style is a flow scalar style (plain, single or double quoted).
+    fn is_flow(&self) -> bool;
+
+    /// Return whether the style is a quoted scalar style (single or double quoted).
+    fn is_quoted(&self) -> bool;
+}
+
+impl ScalarStyleExt for TScalarStyle {
+    fn is_block(&self) -> bool {
+        matches!(self, TScalarStyle::Literal | TScalarStyle::Folded)
+    }
+
+    fn is_flow(&self) -> bool {
+        !self.is_block()
+    }
+
+    fn is_quoted(&self) -> bool {
+        matches!(
+            self,
+            TScalarStyle::SingleQuoted | TScalarStyle::DoubleQuoted
+        )
+    }
+}
+
+/// Select the style in which the [`YamlEmitter`] would write the given string.
+///
+/// The rules are as follows:
+///   * Strings spanning multiple lines which can be written as a literal block scalar use
+///     [`TScalarStyle::Literal`]. The emitter only uses that style if
+///     [`YamlEmitter::multiline_strings`] is set and falls back to double quotes otherwise.
+///   * Strings that would be misinterpreted as plain scalars (e.g.: `true`, `"42"`, `"- a"`) use
+///     [`TScalarStyle::DoubleQuoted`].
+///   * All other strings use [`TScalarStyle::Plain`].
+///
+/// ```
+/// use saphyr::{select_scalar_style, TScalarStyle};
+///
+/// assert_eq!(select_scalar_style("foo"), TScalarStyle::Plain);
+/// assert_eq!(select_scalar_style("true"), TScalarStyle::DoubleQuoted);
+/// assert_eq!(select_scalar_style("foo\nbar"), TScalarStyle::Literal);
+/// ```
+///
+/// [`YamlEmitter`]: crate::YamlEmitter
+/// [`YamlEmitter::multiline_strings`]: crate::YamlEmitter::multiline_strings
+#[must_use]
+pub fn select_scalar_style(string: &str) -> TScalarStyle {
+    if string.contains('\n') && char_traits::is_valid_literal_block_scalar(string) {
+        TScalarStyle::Literal
+    } else if need_quotes(string) {
+        TScalarStyle::DoubleQuoted
+    } else {
+        TScalarStyle::Plain
+    }
+}
+
+/// A scalar, as written in a document: its text, tag and style.
+///
+/// This is the model of scalars shared by both directions: parser events convert to and from it,
+/// and the [`YamlEmitter`] writes it in its style. Scalars are not resolved: the scalar `true`
+/// is text until [`Scalar::into_yaml`] resolves it to a boolean.
+///
+/// ```
+/// use saphyr::{Event, Marker, Parser, Scalar, TScalarStyle, Yaml, YamlEmitter};
+///
+/// let parser = Parser::new_from_str("'it''s'");
+/// let (event, marker) = parser.map(Result::unwrap)
+///     .find(|(event, _)| matches!(event, Event::Scalar(..)))
+///     .unwrap();
+/// let (scalar, _anchor_id) = Scalar::from_event(event, marker).unwrap();
+/// assert_eq!(scalar.value, "it's");
+/// assert_eq!(scalar.style, TScalarStyle::SingleQuoted);
+///
+/// let mut output = String::new();
+/// YamlEmitter::new(&mut output).dump_scalar(&scalar).unwrap();
+/// assert_eq!(output, "---\n'it''s'");
+/// assert_eq!(scalar.into_yaml(), Yaml::String("it's".into()));
+/// ```
+///
+/// [`YamlEmitter`]: crate::YamlEmitter
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scalar {
+    /// The value of the scalar, with escapes and folding already processed.
+    pub value: String,
+    /// The tag of the scalar, if any.
+    pub tag: Option<Tag>,
+    /// The style in which the scalar is written.
+    pub style: TScalarStyle,
+    /// The position of the start of the scalar. Scalars which were not parsed have a default
+    /// marker.
+    pub marker: Marker,
+}
+
+impl Scalar {
+    /// Create an untagged scalar for the string `value`, in the style selected by
+    /// [`select_scalar_style`].
+    ///
+    /// The style is chosen so that the scalar loads back as a string.
+    pub fn new(value: impl Into<String>) -> Self {
+        let value = value.into();
+        Self {
+            style: select_scalar_style(&value),
+            value,
+            tag: None,
+            marker: Marker::default(),
+        }
+    }
+
+    /// Convert a parser event at `marker` into a scalar and the anchor ID of the event.
+    ///
+    /// # Errors
+    /// Returns the event back if it is not an [`Event::Scalar`].
+    pub fn from_event(event: Event, marker: Marker) -> Result<(Self, usize), Event> {
+        match event {
+            Event::Scalar(value, style, anchor_id, tag) => Ok((
+                Self {
+                    value,
+                    tag: tag.map(Tag::from),
+                    style,
+                    marker,
+                },
+                anchor_id,
+            )),
+            event => Err(event),
+        }
+    }
+
+    /// Convert the scalar into a parser event, with the given anchor ID (`0` for none).
+    #[must_use]
+    pub fn into_event(self, anchor_id: usize) -> Event {
+        let tag = self.tag.map(|tag| saphyr_parser::Tag {
+            handle: tag.handle,
+            suffix: tag.suffix,
+        });
+        Event::Scalar(self.value, self.style, anchor_id, tag)
+    }
+
+    /// Resolve the scalar into a [`Yaml`] node as per its style and tag, as loading it would.
+    #[must_use]
+    pub fn into_yaml(self) -> Yaml {
+        let tag = self.tag.map(|tag| saphyr_parser::Tag {
+            handle: tag.handle,
+            suffix: tag.suffix,
+        });
+        resolve_scalar(self.value, self.style, tag.as_ref())
+    }
+}
+
+/// How the line breaks at the end of a block scalar are kept.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chomping {
+    /// The final line break is kept, trailing empty lines are not (the default, no indicator).
+    Clip,
+    /// Neither the final line break nor trailing empty lines are kept (`-`).
+    Strip,
+    /// Both the final line break and trailing empty lines are kept (`+`).
+    Keep,
+}
+
+/// The header of a block scalar (e.g.: `|2-`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockScalarHeader {
+    /// The chomping indicator.
+    pub chomping: Chomping,
+    /// The indentation indicator, if the header has one. The contents are then indented by this
+    /// many spaces more than their parent node.
+    pub indent: Option<usize>,
+}
+
+/// Return the contents of the block scalar with the header `header` (e.g.: `|2-`) and the lines
+/// `body`, as the scanner resolves them.
+///
+/// This is meant for tools explaining how block scalars are folded and chomped, and for comparing
+/// the behavior of YAML libraries. `body` is written as it would be under a mapping key at column
+/// 0, e.g. indented by the indentation indicator of `header` if it has one.
+///
+/// ```
+/// use saphyr::resolve_block_scalar;
+///
+/// let body = "  first\n  line\n\n    indented\n\n\n";
+/// assert_eq!(resolve_block_scalar("|", body).unwrap(), "first\nline\n\n  indented\n");
+/// assert_eq!(resolve_block_scalar(">-", body).unwrap(), "first line\n\n  indented");
+/// assert_eq!(resolve_block_scalar("|+", body).unwrap(), "first\nline\n\n  indented\n\n\n");
+/// assert_eq!(resolve_block_scalar("|1", body).unwrap(), " first\n line\n\n   indented\n");
+/// ```
+///
+/// # Errors
+/// Returns `ScanError` if `header` is not a block scalar header, or if the scalar ends before
+/// `body` does (e.g.: a line less indented than the contents). Positions are in `header`, on line
+/// 1, followed by `body`.
+pub fn resolve_block_scalar(header: &str, body: &str) -> Result<String, ScanError> {
+    // Put the scalar under a key, so that its indentation is relative to column 0.
+    const PREFIX: &str = "_: ";
+
+    if header.contains('\n') || block_scalar_header(header).is_none() {
+        return Err(ScanError::new_str(
+            Marker::new(0, 1, 0),
+            "expected a block scalar header",
+        ));
+    }
+    let source = format!("{PREFIX}{header}\n{body}");
+    let unprefixed = |marker: &Marker| {
+        let prefix = PREFIX.len();
+        let col = if marker.line() == 1 {
+            marker.col().saturating_sub(prefix)
+        } else {
+            marker.col()
+        };
+        Marker::new(marker.index().saturating_sub(prefix), marker.line(), col)
+    };
+
+    let mut scanner = Scanner::new(source.chars());
+    let mut contents = None;
+    for Token(marker, token) in scanner.by_ref() {
+        match token {
+            TokenType::Scalar(TScalarStyle::Literal | TScalarStyle::Folded, value)
+                if contents.is_none() =>
+            {
+                contents = Some(value);
+            }
+            TokenType::BlockEnd | TokenType::StreamEnd => {}
+            _ if contents.is_some() => {
+                return Err(ScanError::new_str(
+                    unprefixed(&marker),
+                    "the block scalar ends before its body",
+                ));
+            }
+            _ => {}
+        }
+    }
+    if let Some(error) = scanner.get_error() {
+        return Err(ScanError::new_str(unprefixed(error.marker()), error.info()));
+    }
+    contents
+        .ok_or_else(|| ScanError::new_str(Marker::new(0, 1, 0), "expected a block scalar header"))
+}
+
+/// How a scalar is written, beyond its style. See [`DetailedEvents`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScalarDetails {
+    /// A plain scalar.
+    Plain,
+    /// A single or double quoted scalar.
+    Quoted {
+        /// The style of the scalar.
+        style: TScalarStyle,
+        /// The text between the quotes, as it is written. Escapes (e.g.: `\n` or `''`) and line
+        /// breaks are not processed.
+        raw: String,
+    },
+    /// A literal or folded block scalar.
+    Block {
+        /// The style of the scalar.
+        style: TScalarStyle,
+        /// The header of the scalar.
+        header: BlockScalarHeader,
+    },
+}
+
+/// The events of a YAML stream, along with details about how their scalars are written.
+///
+/// Parser events only give the style of scalars. Re-emitters and linters may need to know more
+/// about them, e.g. to keep the chomping indicator of a block scalar. This reads them from the
+/// source of the events.
+///
+/// ```
+/// use saphyr::{BlockScalarHeader, Chomping, DetailedEvents, ScalarDetails, TScalarStyle};
+///
+/// let source = "script: |2-\n    echo ok\nname: 'it''s'\n";
+/// let details: Vec<_> = DetailedEvents::new(source)
+///     .filter_map(|event| event.unwrap().2)
+///     .collect();
+/// assert_eq!(
+///     details[1],
+///     ScalarDetails::Block {
+///         style: TScalarStyle::Literal,
+///         header: BlockScalarHeader { chomping: Chomping::Strip, indent: Some(2) },
+///     }
+/// );
+/// assert_eq!(
+///     details[3],
+///     ScalarDetails::Quoted { style: TScalarStyle::SingleQuoted, raw: "it''s".into() }
+/// );
+/// ```
+pub struct DetailedEvents<'a> {
+    /// The parser generating events from `source`.
+    parser: Parser<Chars<'a>>,
+    /// The source of the events.
+    source: &'a str,
+    /// The index, in `char`s, of a position in `source`.
+    cursor_index: usize,
+    /// The offset, in bytes, of the position at `cursor_index`.
+    cursor_offset: usize,
+    /// Whether parsing failed. The parser keeps returning its error afterwards, while we stop.
+    failed: bool,
+}
+
+impl<'a> DetailedEvents<'a> {
+    /// Parse `source`.
+    #[must_use]
+    pub fn new(source: &'a str) -> Self {
+        Self {
+            parser: Parser::new_from_str(source),
+            source,
+            cursor_index: 0,
+            cursor_offset: 0,
+            failed: false,
+        }
+    }
+
+    /// Return the details of a scalar of the given style, at `marker`.
+    ///
+    /// Those of block scalars are `None` if their header cannot be found. This should not happen
+    /// since the parser accepted them.
+    fn details(&mut self, style: TScalarStyle, marker: &Marker) -> Option<ScalarDetails> {
+        let offset = self.offset_of(marker.index());
+        match style {
+            TScalarStyle::Plain => Some(ScalarDetails::Plain),
+            TScalarStyle::SingleQuoted | TScalarStyle::DoubleQuoted => {
+                Some(ScalarDetails::Quoted {
+                    style,
+                    raw: raw_quoted(&self.source[offset..], style).to_owned(),
+                })
+            }
+            TScalarStyle::Literal | TScalarStyle::Folded => {
+                // The marker points to the contents, on a line after the header.
+                let line_start = self.source[..offset].rfind('\n').map_or(0, |idx| idx + 1);
+                let header = self.source[..line_start]
+                    .lines()
+                    .rev()
+                    .find(|line| !line.trim().is_empty())
+                    .and_then(block_scalar_header)?;
+                Some(ScalarDetails::Block { style, header })
+            }
+        }
+    }
+
+    /// Return the offset in bytes of the `char` at `index` in the source.
+    fn offset_of(&mut self, index: usize) -> usize {
+        // Scalars come in order, so the cursor seldom moves back.
+        if index < self.cursor_index {
+            self.cursor_index = 0;
+            self.cursor_offset = 0;
+        }
+        let rest = &self.source[self.cursor_offset..];
+        self.cursor_offset += rest
+            .char_indices()
+            .nth(index - self.cursor_index)
+            .map_or(rest.len(), |(offset, _)| offset);
+        self.cursor_index = index;
+        self.cursor_offset
+    }
+}
+
+impl Iterator for DetailedEvents<'_> {
+    type Item = Result<(Event, Marker, Option<ScalarDetails>), ScanError>;
+
+    /// Return the next event, its position and, if it is a scalar, how it is written.
+    ///
+    /// Iteration ends after the first error.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let (event, marker) = match self.parser.next()? {
+            Ok(next) => next,
+            Err(error) => {
+                self.failed = true;
+                return Some(Err(error));
+            }
+        };
+        let details = match &event {
+            Event::Scalar(_, style, ..) => self.details(*style, &marker),
+            _ => None,
+        };
+        Some(Ok((event, marker, details)))
+    }
+}
+
+/// Return the text between the quotes of the quoted scalar at the start of `text`.
+fn raw_quoted(text: &str, style: TScalarStyle) -> &str {
+    let mut chars = text.char_indices().skip(1);
+    let end = loop {
+        match (chars.next(), style) {
+            (None, _) => break text.len(),
+            (Some((_, '\\')), TScalarStyle::DoubleQuoted) => {
+                chars.next();
+            }
+            (Some((idx, '"')), TScalarStyle::DoubleQuoted) => break idx,
+            (Some((idx, '\'')), TScalarStyle::SingleQuoted) => {
+                if text[idx + 1..].starts_with('\'') {
+                    chars.next();
+                } else {
+                    break idx;
+                }
+            }
+            _ => {}
+        }
+    };
+    text.get(1..end).unwrap_or_default()
+}
+
+/// Parse the header of the block scalar ending `line` (e.g.: `key: |2- # comment`).
+pub(crate) fn block_scalar_header(line: &str) -> Option<BlockScalarHeader> {
+    let header = line
+        .split_whitespace()
+        .take_while(|word| !word.starts_with('#'))
+        .last()?;
+    let indicators = header.strip_prefix(['|', '>'])?;
+    if indicators.len() > 2 {
+        return None;
+    }
+    let mut chomping = Chomping::Clip;
+    let mut indent = None;
+    for c in indicators.chars() {
+        match c {
+            '-' => chomping = Chomping::Strip,
+            '+' => chomping = Chomping::Keep,
+            '1'..='9' => indent = c.to_digit(10).map(|digit| digit as usize),
+            _ => return None,
+        }
+    }
+    Some(BlockScalarHeader { chomping, indent })
+}
@@ -0,0 +1,429 @@
+//! Schemas, resolving the type of plain scalars.
+
+use std::fmt;
+
+use saphyr_parser::{Parser, ScanError, TScalarStyle, Tag};
+
+use crate::{loader::resolve_scalar, tag::CORE_SCHEMA_HANDLE, Scalar, Yaml};
+
+/// The rules by which the [`YamlLoader`] resolves the type of scalars.
+///
+/// Set with [`YamlLoader::schema`].
+///
+/// ```
+/// use saphyr::{Schema, Yaml};
+///
+/// assert_eq!(Schema::Legacy.resolve_plain("NULL"), Yaml::String("NULL".into()));
+/// assert_eq!(Schema::Core.resolve_plain("NULL"), Yaml::Null);
+/// assert_eq!(Schema::Legacy.resolve_plain("inf"), Yaml::Real("inf".into()));
+/// assert_eq!(Schema::Core.resolve_plain("inf"), Yaml::String("inf".into()));
+/// ```
+///
+/// [`YamlLoader`]: crate::YamlLoader
+/// [`YamlLoader::schema`]: crate::YamlLoader::schema
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Schema {
+    /// The rules this crate has always followed (default). See [`Yaml::value_from_str`].
+    ///
+    /// They are close to the core schema, but only accept lowercase `null`, `true` and `false`,
+    /// and accept reals Rust can parse even if the schema does not (e.g.: `inf` or `NaN`).
+    #[default]
+    Legacy,
+    /// The YAML 1.2.2 core schema, exactly as specified (`null`, `Null`, `NULL`, `~`, `True`,
+    /// `+12`, `0o14`, `+.inf`, `.NaN`, ...).
+    ///
+    /// `.nan`, `.NaN` and `.NAN` are all loaded as `Yaml::Real(".nan")`. Other reals keep the
+    /// text they are written with. Decimal integers too large for an `i64` are loaded as reals.
+    ///
+    /// Explicit core tags (e.g.: `!!bool`) are checked against the same rules: scalars they do
+    /// not match are loaded as [`Yaml::BadValue`].
+    ///
+    /// See <https://yaml.org/spec/1.2.2/#1032-tag-resolution>.
+    Core,
+    /// The core schema, with the booleans of YAML 1.1 as libyaml and `PyYAML` accept them: `yes`,
+    /// `no`, `on` and `off` in lowercase, capitalized or uppercase, as well as those of the core
+    /// schema. `y` and `n` are strings, as with those libraries.
+    ///
+    /// This is meant for documents shared with tools following YAML 1.1. See [`BoolLexemes`] to
+    /// write booleans back as they were written.
+    ///
+    /// [`BoolLexemes`]: crate::BoolLexemes
+    Yaml11Booleans,
+    /// Plain scalars are strings, whatever they look like, except for the nulls of the core
+    /// schema (`null`, `Null`, `NULL`, `~` and empty values). Only quoted or tagged scalars
+    /// (e.g.: `!!bool true` or `!!int 3`) are of other types.
+    ///
+    /// This is the failsafe schema, with nulls so that empty values stay empty. It is meant for
+    /// users tired of the "Norway problem", where the country code `NO` becomes `false` and a
+    /// version `1.10` becomes `1.1`: values are read as they are written, and their consumers
+    /// decide what they mean (e.g.: with [`Yaml::as_bool_relaxed`]).
+    ///
+    /// ```
+    /// use saphyr::{Schema, Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::<Yaml>::default();
+    /// loader.schema(Schema::Strings);
+    /// let docs = loader
+    ///     .load_from_str("countries: [NO, SE]\nversion: 1.10\nport: !!int 80\nproxy:\n")
+    ///     .unwrap();
+    /// assert_eq!(docs[0]["countries"][0].as_str(), Some("NO"));
+    /// assert_eq!(docs[0]["version"].as_str(), Some("1.10"));
+    /// assert_eq!(docs[0]["port"].as_i64(), Some(80));
+    /// assert!(docs[0]["proxy"].is_null());
+    /// ```
+    Strings,
+}
+
+impl Schema {
+    /// Resolve the untagged plain scalar `v`.
+    #[must_use]
+    pub fn resolve_plain(self, v: &str) -> Yaml {
+        match self {
+            Schema::Legacy => Yaml::value_from_str(v),
+            Schema::Core => core_null(v)
+                .or_else(|| core_bool(v))
+                .or_else(|| core_int(v))
+                .or_else(|| core_float(v))
+                .unwrap_or_else(|| Yaml::String(v.to_owned())),
+            Schema::Yaml11Booleans => core_null(v)
+                .or_else(|| yaml11_bool(v).map(Yaml::Boolean))
+                .or_else(|| core_int(v))
+                .or_else(|| core_float(v))
+                .unwrap_or_else(|| Yaml::String(v.to_owned())),
+            Schema::Strings => core_null(v).unwrap_or_else(|| Yaml::String(v.to_owned())),
+        }
+    }
+
+    /// Explain how `scalar` is resolved: to which value, and by which rule.
+    ///
+    /// ```
+    /// use saphyr::{ResolutionRule, Scalar, Schema, TScalarStyle, Yaml};
+    ///
+    /// let mut scalar = Scalar::new("0x2A");
+    /// scalar.style = TScalarStyle::Plain;
+    /// let resolution = Schema::Core.explain(&scalar);
+    /// assert_eq!(resolution.value, Yaml::Integer(42));
+    /// assert_eq!(resolution.rule, ResolutionRule::Int);
+    /// ```
+    #[must_use]
+    pub fn explain(self, scalar: &Scalar) -> Resolution {
+        let tag = scalar.tag.clone().map(|tag| Tag {
+            handle: tag.handle,
+            suffix: tag.suffix,
+        });
+        let value = self.resolve(scalar.value.clone(), scalar.style, tag.as_ref());
+        let rule = match (&value, &tag) {
+            _ if scalar.style != TScalarStyle::Plain => ResolutionRule::Style,
+            (_, Some(_)) => ResolutionRule::Tag,
+            (Yaml::Null, None) => ResolutionRule::Null,
+            (Yaml::Boolean(_), None) => ResolutionRule::Bool,
+            (Yaml::Integer(_), None) => ResolutionRule::Int,
+            (Yaml::Real(_), None) => ResolutionRule::Float,
+            (_, None) => ResolutionRule::Fallback,
+        };
+        Resolution {
+            scalar: scalar.clone(),
+            schema: self,
+            value,
+            rule,
+        }
+    }
+
+    /// Explain how each scalar of `source` is resolved, in the order they appear.
+    ///
+    /// This helps finding out why a scalar is not loaded with the type one expects:
+    ///
+    /// ```
+    /// use saphyr::Schema;
+    ///
+    /// let resolutions = Schema::Legacy.explain_str("enabled: no\nport: 0x1F90").unwrap();
+    /// assert_eq!(
+    ///     resolutions[1].to_string(),
+    ///     "`no` at line 1 column 10 is a string via the fallback of the legacy schema \
+    ///      (no other rule matches)"
+    /// );
+    /// assert_eq!(
+    ///     resolutions[3].to_string(),
+    ///     "`0x1F90` at line 2 column 7 is an integer via the integer rule of the legacy schema"
+    /// );
+    /// ```
+    ///
+    /// Keys are explained too. Aliases are not: they are resolved like the node they refer to.
+    ///
+    /// # Errors
+    /// Returns `ScanError` when parsing fails.
+    pub fn explain_str(self, source: &str) -> Result<Vec<Resolution>, ScanError> {
+        let mut resolutions = vec![];
+        for event in Parser::new_from_str(source) {
+            let (event, marker) = event?;
+            if let Ok((scalar, _)) = Scalar::from_event(event, marker) {
+                resolutions.push(self.explain(&scalar));
+            }
+        }
+        Ok(resolutions)
+    }
+
+    /// Resolve the scalar `v`, of the given style and tag.
+    pub(crate) fn resolve(self, v: String, style: TScalarStyle, tag: Option<&Tag>) -> Yaml {
+        match (self, tag) {
+            (Schema::Legacy, _) => resolve_scalar(v, style, tag),
+            (_, _) if style != TScalarStyle::Plain => Yaml::String(v),
+            (_, None) => self.resolve_plain(&v),
+            (_, Some(tag)) if tag.handle == CORE_SCHEMA_HANDLE => {
+                let resolved = match tag.suffix.as_ref() {
+                    "null" => core_null(&v),
+                    "bool" if self == Schema::Yaml11Booleans => yaml11_bool(&v).map(Yaml::Boolean),
+                    "bool" => core_bool(&v),
+                    "int" => core_int(&v),
+                    // Integers are valid reals.
+                    "float" => core_float(&v),
+                    _ => Some(Yaml::String(v)),
+                };
+                resolved.unwrap_or(Yaml::BadValue)
+            }
+            (_, Some(_)) => Yaml::String(v),
+        }
+    }
+}
+
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Schema::Legacy => "legacy",
+            Schema::Core => "core",
+            Schema::Yaml11Booleans => "YAML 1.1 booleans",
+            Schema::Strings => "strings",
+        })
+    }
+}
+
+/// The rule by which a [`Schema`] resolved a scalar. See [`Schema::explain`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResolutionRule {
+    /// The scalar is quoted or a block scalar, and thus a string. Only plain scalars are
+    /// resolved.
+    Style,
+    /// The scalar has an explicit tag, which decides its type. Scalars with tags outside of the
+    /// core schema are strings, and scalars not matching their core tag are bad values.
+    Tag,
+    /// The scalar matches the null rule of the schema.
+    Null,
+    /// The scalar matches the boolean rule of the schema.
+    Bool,
+    /// The scalar matches the integer rule of the schema.
+    Int,
+    /// The scalar matches the float rule of the schema.
+    Float,
+    /// The scalar matches no rule of the schema, and is a string.
+    Fallback,
+}
+
+/// How a scalar is resolved. See [`Schema::explain`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Resolution {
+    /// The scalar being resolved.
+    pub scalar: Scalar,
+    /// The schema resolving the scalar.
+    pub schema: Schema,
+    /// The value the scalar is resolved to, without its tag.
+    pub value: Yaml,
+    /// The rule by which the scalar is resolved to `value`.
+    pub rule: ResolutionRule,
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let marker = &self.scalar.marker;
+        write!(
+            f,
+            "`{}` at line {} column {} is ",
+            self.scalar.value,
+            marker.line(),
+            marker.col() + 1
+        )?;
+        match self.value.type_name() {
+            name @ ("integer" | "alias") => write!(f, "an {name}")?,
+            name => write!(f, "a {name}")?,
+        }
+        let rule = match self.rule {
+            ResolutionRule::Style => {
+                return write!(
+                    f,
+                    " via its {} style (only plain scalars are resolved)",
+                    style_name(self.scalar.style)
+                );
+            }
+            ResolutionRule::Tag => match &self.scalar.tag {
+                Some(tag) => return write!(f, " via its explicit `{tag}` tag"),
+                None => return write!(f, " via its explicit tag"),
+            },
+            ResolutionRule::Fallback => {
+                return write!(
+                    f,
+                    " via the fallback of the {} schema (no other rule matches)",
+                    self.schema
+                );
+            }
+            ResolutionRule::Null => "null",
+            ResolutionRule::Bool => "boolean",
+            ResolutionRule::Int => "integer",
+            ResolutionRule::Float => "float",
+        };
+        write!(f, " via the {rule} rule of the {} schema", self.schema)
+    }
+}
+
+/// Return the name of `style` in messages.
+fn style_name(style: TScalarStyle) -> &'static str {
+    match style {
+        TScalarStyle::Plain => "plain",
+        TScalarStyle::SingleQuoted => "single-quoted",
+        TScalarStyle::DoubleQuoted => "double-quoted",
+        TScalarStyle::Literal => "literal",
+        TScalarStyle::Folded => "folded",
+    }
+}
+
+/// Resolve `v` if it is a null as per the core schema.
+fn core_null(v: &str) -> Option<Yaml> {
+    matches!(v, "" | "~" | "null" | "Null" | "NULL").then_some(Yaml::Null)
+}
+
+/// Resolve `v` if it is a boolean as per the core schema.
+fn core_bool(v: &str) -> Option<Yaml> {
+    match v {
+        "true" | "True" | "TRUE" => Some(Yaml::Boolean(true)),
+        "false" | "False" | "FALSE" => Some(Yaml::Boolean(false)),
+        _ => None,
+    }
+}
+
+/// Return the value of `v` if it is a boolean as per [`Schema::Yaml11Booleans`].
+pub(crate) fn yaml11_bool(v: &str) -> Option<bool> {
+    match v {
+        "true" | "True" | "TRUE" | "yes" | "Yes" | "YES" | "on" | "On" | "ON" => Some(true),
+        "false" | "False" | "FALSE" | "no" | "No" | "NO" | "off" | "Off" | "OFF" => Some(false),
+        _ => None,
+    }
+}
+
+/// Resolve `v` if it is an integer as per the core schema that fits in an `i64`.
+pub(crate) fn core_int(v: &str) -> Option<Yaml> {
+    let (digits, radix) = if let Some(digits) = v.strip_prefix("0o") {
+        (digits, 8)
+    } else if let Some(digits) = v.strip_prefix("0x") {
+        (digits, 16)
+    } else {
+        (v.strip_prefix(['-', '+']).unwrap_or(v), 10)
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    // The sign is only allowed in decimal integers, which `parse` accepts it for.
+    let value = if radix == 10 {
+        v.parse()
+    } else {
+        i64::from_str_radix(digits, radix)
+    };
+    value.ok().map(Yaml::Integer)
+}
+
+/// Return whether `v` is an integer as per YAML 1.1. See <https://yaml.org/type/int.html>.
+pub(crate) fn yaml11_int(v: &str) -> bool {
+    fn digits(v: &str, radix: u32) -> bool {
+        !v.is_empty() && v.chars().all(|c| c == '_' || c.is_digit(radix))
+    }
+
+    let v = v.strip_prefix(['-', '+']).unwrap_or(v);
+    if let Some(binary) = v.strip_prefix("0b") {
+        digits(binary, 2)
+    } else if let Some(hexadecimal) = v.strip_prefix("0x") {
+        digits(hexadecimal, 16)
+    } else if let Some(octal) = v.strip_prefix('0') {
+        octal.is_empty() || digits(octal, 8)
+    } else if let Some((head, sexagesimal)) = v.split_once(':') {
+        // Base 60 (e.g.: `190:20:30`).
+        digits(head, 10)
+            && sexagesimal.split(':').all(|part| {
+                part.len() <= 2
+                    && digits(part, 10)
+                    && !part.contains('_')
+                    && (part.len() == 1 || part.starts_with(['0', '1', '2', '3', '4', '5']))
+            })
+    } else {
+        digits(v, 10) && !v.starts_with('_')
+    }
+}
+
+/// Return whether `v` is a float as per YAML 1.1. See <https://yaml.org/type/float.html>.
+pub(crate) fn yaml11_float(v: &str) -> bool {
+    if matches!(v, ".nan" | ".NaN" | ".NAN") {
+        return true;
+    }
+    let v = v.strip_prefix(['-', '+']).unwrap_or(v);
+    if matches!(v, ".inf" | ".Inf" | ".INF") {
+        return true;
+    }
+    let (mantissa, exponent) = match v.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (v, None),
+    };
+    if let Some(exponent) = exponent {
+        match exponent.strip_prefix(['-', '+']) {
+            Some(digits) if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) => {}
+            _ => return false,
+        }
+    }
+    let Some((integer, fraction)) = mantissa.split_once('.') else {
+        return false;
+    };
+    if integer.contains(':') {
+        // Base 60 (e.g.: `190:20:30.15`).
+        exponent.is_none()
+            && yaml11_int(integer)
+            && fraction.chars().all(|c| c == '_' || c.is_ascii_digit())
+    } else {
+        (integer.is_empty()
+            || (integer.starts_with(|c: char| c.is_ascii_digit())
+                && integer.chars().all(|c| c == '_' || c.is_ascii_digit())))
+            && fraction.chars().all(|c| c == '.' || c.is_ascii_digit())
+    }
+}
+
+/// Resolve `v` if it is a float as per the core schema.
+pub(crate) fn core_float(v: &str) -> Option<Yaml> {
+    if matches!(v, ".nan" | ".NaN" | ".NAN") {
+        return Some(Yaml::Real(".nan".to_owned()));
+    }
+    let unsigned = v.strip_prefix(['-', '+']).unwrap_or(v);
+    if matches!(unsigned, ".inf" | ".Inf" | ".INF") || is_core_number(unsigned) {
+        Some(Yaml::Real(v.to_owned()))
+    } else {
+        None
+    }
+}
+
+/// Return whether `v` matches `( \. [0-9]+ | [0-9]+ ( \. [0-9]* )? ) ( [eE] [-+]? [0-9]+ )?`.
+fn is_core_number(v: &str) -> bool {
+    fn skip_digits(v: &str) -> (&str, usize) {
+        let rest = v.trim_start_matches(|c: char| c.is_ascii_digit());
+        (rest, v.len() - rest.len())
+    }
+
+    let (rest, integer_digits) = skip_digits(v);
+    let (rest, fraction_digits) = match rest.strip_prefix('.') {
+        Some(fraction) => skip_digits(fraction),
+        None => (rest, 0),
+    };
+    if integer_digits == 0 && fraction_digits == 0 {
+        return false;
+    }
+    match rest.strip_prefix(['e', 'E']) {
+        Some(exponent) => {
+            let (rest, exponent_digits) =
+                skip_digits(exponent.strip_prefix(['-', '+']).unwrap_or(exponent));
+            rest.is_empty() && exponent_digits > 0
+        }
+        None => rest.is_empty(),
+    }
+}
@@ -0,0 +1,389 @@
+//! A YAML node whose contents are shared between its clones.
+
+use std::{collections::HashSet, ops::Index, sync::Arc};
+
+use saphyr_parser::{Parser, ScanError};
+
+use crate::{loader::parse_real, AnnotatedHash, LoadableYamlNode, Tag, Yaml, YamlLoader};
+
+/// A YAML node whose strings and collections are reference-counted.
+///
+/// This mirrors [`Yaml`], but cloning a node only increments reference counts instead of copying
+/// its whole subtree. This is meant for pipelines passing large documents around. Mutating a node
+/// through [`Self::as_mut_vec`] or [`Self::as_mut_hash`] copies its contents first if they are
+/// shared with other clones (copy-on-write), so that clones are never altered.
+///
+/// Documents can be loaded directly as `SharedYaml`, or converted from and to [`Yaml`].
+///
+/// ```
+/// use saphyr::SharedYaml;
+///
+/// let docs = SharedYaml::load_from_str("items: [1, 2, 3]").unwrap();
+/// let mut copy = docs[0].clone();
+/// assert!(copy.shares_contents_with(&docs[0]));
+///
+/// copy.as_mut_hash().unwrap().clear();
+/// assert!(!copy.shares_contents_with(&docs[0]));
+/// assert_eq!(docs[0]["items"][2].as_i64(), Some(3));
+/// ```
+///
+/// This type is only available with the `rc` feature.
+#[derive(Clone, PartialEq, PartialOrd, Debug, Eq, Ord, Hash)]
+pub enum SharedYaml {
+    /// Float types are stored as the text of the real. See [`Yaml::Real`].
+    Real(Arc<str>),
+    /// YAML int is stored as i64.
+    Integer(i64),
+    /// YAML scalar.
+    String(Arc<str>),
+    /// YAML bool, e.g. `true` or `false`.
+    Boolean(bool),
+    /// YAML array.
+    Array(Arc<Vec<SharedYaml>>),
+    /// YAML hash. Insertion order will match the order of insertion into the map.
+    Hash(Arc<AnnotatedHash<SharedYaml>>),
+    /// A YAML node with an explicit tag the loader recognizes. See [`Yaml::Tagged`].
+    Tagged(Arc<Tag>, Arc<SharedYaml>),
+    /// Alias, not fully supported yet.
+    Alias(usize),
+    /// YAML null, e.g. `null` or `~`.
+    Null,
+    /// Accessing a nonexistent node via the Index trait returns `BadValue`.
+    BadValue,
+}
+
+impl SharedYaml {
+    /// Load the given string as an array of YAML documents.
+    ///
+    /// See the function [`load_from_str`] for more details.
+    ///
+    /// # Errors
+    /// Returns `ScanError` when loading fails.
+    ///
+    /// [`load_from_str`]: `Yaml::load_from_str`
+    pub fn load_from_str(source: &str) -> Result<Vec<Self>, ScanError> {
+        Self::load_from_iter(source.chars())
+    }
+
+    /// Load the contents of the given iterator as an array of YAML documents.
+    ///
+    /// See the function [`load_from_str`] for more details.
+    ///
+    /// # Errors
+    /// Returns `ScanError` when loading fails.
+    ///
+    /// [`load_from_str`]: `Yaml::load_from_str`
+    pub fn load_from_iter<I: Iterator<Item = char>>(source: I) -> Result<Vec<Self>, ScanError> {
+        let mut parser = Parser::new(source);
+        Self::load_from_parser(&mut parser)
+    }
+
+    /// Load the contents from the specified [`Parser`] as an array of YAML documents.
+    ///
+    /// See the function [`load_from_str`] for more details.
+    ///
+    /// # Errors
+    /// Returns `ScanError` when loading fails.
+    ///
+    /// [`load_from_str`]: `Yaml::load_from_str`
+    pub fn load_from_parser<I: Iterator<Item = char>>(
+        parser: &mut Parser<I>,
+    ) -> Result<Vec<Self>, ScanError> {
+        let mut loader = YamlLoader::<Self>::default();
+        parser.load(&mut loader, true)?;
+        Ok(loader.into_documents())
+    }
+
+    define_as!(as_bool, bool, Boolean);
+    define_as!(as_i64, i64, Integer);
+
+    define_is!(is_alias, Self::Alias(_));
+    define_is!(is_array, Self::Array(_));
+    define_is!(is_badvalue, Self::BadValue);
+    define_is!(is_boolean, Self::Boolean(_));
+    define_is!(is_hash, Self::Hash(_));
+    define_is!(is_integer, Self::Integer(_));
+    define_is!(is_null, Self::Null);
+    define_is!(is_real, Self::Real(_));
+    define_is!(is_string, Self::String(_));
+    define_is!(is_tagged, Self::Tagged(..));
+
+    /// Return the contents of the node if it is a string.
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            SharedYaml::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return the value of the node if it is a real that parses as an `f64`.
+    #[must_use]
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            SharedYaml::Real(v) => parse_real(v),
+            _ => None,
+        }
+    }
+
+    /// Return the items of the node if it is a sequence.
+    #[must_use]
+    pub fn as_vec(&self) -> Option<&Vec<SharedYaml>> {
+        match self {
+            SharedYaml::Array(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return the entries of the node if it is a mapping.
+    #[must_use]
+    pub fn as_hash(&self) -> Option<&AnnotatedHash<SharedYaml>> {
+        match self {
+            SharedYaml::Hash(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Return the items of the node if it is a sequence, for mutation.
+    ///
+    /// If the items are shared with other clones of the node, they are copied first.
+    #[must_use]
+    pub fn as_mut_vec(&mut self) -> Option<&mut Vec<SharedYaml>> {
+        match self {
+            SharedYaml::Array(v) => Some(Arc::make_mut(v)),
+            _ => None,
+        }
+    }
+
+    /// Return the entries of the node if it is a mapping, for mutation.
+    ///
+    /// If the entries are shared with other clones of the node, they are copied first.
+    #[must_use]
+    pub fn as_mut_hash(&mut self) -> Option<&mut AnnotatedHash<SharedYaml>> {
+        match self {
+            SharedYaml::Hash(v) => Some(Arc::make_mut(v)),
+            _ => None,
+        }
+    }
+
+    /// Return the node without its tags, if any.
+    #[must_use]
+    pub fn untagged(&self) -> &SharedYaml {
+        match self {
+            SharedYaml::Tagged(_, node) => node.untagged(),
+            node => node,
+        }
+    }
+
+    /// Return whether `self` and `other` are both strings or collections pointing to the same
+    /// contents in memory, rather than to equal copies.
+    #[must_use]
+    pub fn shares_contents_with(&self, other: &SharedYaml) -> bool {
+        match (self, other) {
+            (SharedYaml::Real(a), SharedYaml::Real(b))
+            | (SharedYaml::String(a), SharedYaml::String(b)) => Arc::ptr_eq(a, b),
+            (SharedYaml::Array(a), SharedYaml::Array(b)) => Arc::ptr_eq(a, b),
+            (SharedYaml::Hash(a), SharedYaml::Hash(b)) => Arc::ptr_eq(a, b),
+            (SharedYaml::Tagged(_, a), SharedYaml::Tagged(_, b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl From<Yaml> for SharedYaml {
+    fn from(yaml: Yaml) -> Self {
+        match yaml {
+            Yaml::Real(v) => SharedYaml::Real(v.into()),
+            Yaml::Integer(v) => SharedYaml::Integer(v),
+            Yaml::String(v) => SharedYaml::String(v.into()),
+            Yaml::Boolean(v) => SharedYaml::Boolean(v),
+            Yaml::Array(v) => SharedYaml::Array(Arc::new(v.into_iter().map(Into::into).collect())),
+            Yaml::Hash(v) => SharedYaml::Hash(Arc::new(
+                v.into_iter().map(|(k, v)| (k.into(), v.into())).collect(),
+            )),
+            Yaml::Tagged(tag, node) => SharedYaml::Tagged(Arc::new(tag), Arc::new((*node).into())),
+            Yaml::Alias(v) => SharedYaml::Alias(v),
+            Yaml::Null => SharedYaml::Null,
+            Yaml::BadValue => SharedYaml::BadValue,
+        }
+    }
+}
+
+impl From<&SharedYaml> for Yaml {
+    /// Copy the node and its whole subtree into a [`Yaml`].
+    fn from(node: &SharedYaml) -> Self {
+        match node {
+            SharedYaml::Real(v) => Yaml::Real(v.to_string()),
+            SharedYaml::Integer(v) => Yaml::Integer(*v),
+            SharedYaml::String(v) => Yaml::String(v.to_string()),
+            SharedYaml::Boolean(v) => Yaml::Boolean(*v),
+            SharedYaml::Array(v) => Yaml::Array(v.iter().map(Into::into).collect()),
+            SharedYaml::Hash(v) => {
+                Yaml::Hash(v.iter().map(|(k, v)| (k.into(), v.into())).collect())
+            }
+            SharedYaml::Tagged(tag, node) => {
+                Yaml::Tagged(Tag::clone(tag), Box::new(Yaml::from(&**node)))
+            }
+            SharedYaml::Alias(v) => Yaml::Alias(*v),
+            SharedYaml::Null => Yaml::Null,
+            SharedYaml::BadValue => Yaml::BadValue,
+        }
+    }
+}
+
+impl LoadableYamlNode for SharedYaml {
+    fn from_bare_yaml(yaml: Yaml) -> Self {
+        yaml.into()
+    }
+
+    fn is_array(&self) -> bool {
+        self.is_array()
+    }
+
+    fn is_hash(&self) -> bool {
+        self.is_hash()
+    }
+
+    fn is_badvalue(&self) -> bool {
+        self.is_badvalue()
+    }
+
+    fn array_mut(&mut self) -> &mut Vec<Self> {
+        self.as_mut_vec().expect("Called array_mut on a non-array")
+    }
+
+    fn hash_mut(&mut self) -> &mut AnnotatedHash<Self> {
+        self.as_mut_hash().expect("Called hash_mut on a non-hash")
+    }
+
+    fn take(&mut self) -> Self {
+        std::mem::replace(self, SharedYaml::BadValue)
+    }
+
+    fn with_tag(self, tag: Tag) -> Self {
+        SharedYaml::Tagged(Arc::new(tag), Arc::new(self))
+    }
+}
+
+static BAD_VALUE: SharedYaml = SharedYaml::BadValue;
+
+impl<'a> Index<&'a str> for SharedYaml {
+    type Output = SharedYaml;
+
+    fn index(&self, idx: &'a str) -> &SharedYaml {
+        let key = SharedYaml::String(idx.into());
+        match self.untagged().as_hash() {
+            Some(h) => h.get(&key).unwrap_or(&BAD_VALUE),
+            None => &BAD_VALUE,
+        }
+    }
+}
+
+impl Index<usize> for SharedYaml {
+    type Output = SharedYaml;
+
+    fn index(&self, idx: usize) -> &SharedYaml {
+        match self.untagged() {
+            SharedYaml::Array(v) => v.get(idx).unwrap_or(&BAD_VALUE),
+            SharedYaml::Hash(v) => i64::try_from(idx)
+                .ok()
+                .and_then(|key| v.get(&SharedYaml::Integer(key)))
+                .unwrap_or(&BAD_VALUE),
+            _ => &BAD_VALUE,
+        }
+    }
+}
+
+/// A pool of the strings of [`SharedYaml`] trees, sharing the storage of identical ones.
+///
+/// Loading a document allocates each of its strings separately, even when the same string is
+/// repeated across nodes or documents (e.g.: the keys and labels of a set of manifests). Passing
+/// trees through a pool makes all of their identical strings and reals point to the same
+/// contents, so that holding many documents at once stores each distinct string only once.
+///
+/// ```
+/// use saphyr::{SharedYaml, StringPool};
+///
+/// let mut docs = SharedYaml::load_from_str("\
+/// app: web
+/// ---
+/// app: web
+/// ").unwrap();
+/// assert!(!docs[0]["app"].shares_contents_with(&docs[1]["app"]));
+///
+/// let mut pool = StringPool::new();
+/// for doc in &mut docs {
+///     pool.deduplicate(doc);
+/// }
+/// assert!(docs[0]["app"].shares_contents_with(&docs[1]["app"]));
+/// assert_eq!(pool.len(), 2);
+/// ```
+///
+/// Collections shared with other clones of the trees are copied first, as with
+/// [`SharedYaml::as_mut_hash`]. The pool holds a reference to each of its strings until dropped.
+///
+/// This type is only available with the `rc` feature.
+#[derive(Clone, Debug, Default)]
+pub struct StringPool {
+    /// The distinct strings seen so far.
+    strings: HashSet<Arc<str>>,
+}
+
+impl StringPool {
+    /// Create an empty pool.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make the strings and reals of `node` and its whole subtree, keys included, point to the
+    /// strings of the pool, adding those it does not have yet.
+    pub fn deduplicate(&mut self, node: &mut SharedYaml) {
+        match node {
+            SharedYaml::Real(v) | SharedYaml::String(v) => *v = self.intern(v),
+            SharedYaml::Array(v) => {
+                for item in Arc::make_mut(v) {
+                    self.deduplicate(item);
+                }
+            }
+            SharedYaml::Hash(v) => {
+                let hash = Arc::make_mut(v);
+                *hash = std::mem::take(hash)
+                    .into_iter()
+                    .map(|(mut key, mut value)| {
+                        self.deduplicate(&mut key);
+                        self.deduplicate(&mut value);
+                        (key, value)
+                    })
+                    .collect();
+            }
+            SharedYaml::Tagged(_, node) => self.deduplicate(Arc::make_mut(node)),
+            SharedYaml::Integer(_)
+            | SharedYaml::Boolean(_)
+            | SharedYaml::Alias(_)
+            | SharedYaml::Null
+            | SharedYaml::BadValue => {}
+        }
+    }
+
+    /// Return the number of distinct strings in the pool.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Return whether the pool has no string.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Return the string of the pool equal to `text`, adding it if it has none.
+    fn intern(&mut self, text: &Arc<str>) -> Arc<str> {
+        if let Some(pooled) = self.strings.get(&**text) {
+            return Arc::clone(pooled);
+        }
+        self.strings.insert(Arc::clone(text));
+        Arc::clone(text)
+    }
+}
@@ -0,0 +1,249 @@
+//! Excerpts of the input to display alongside errors.
+
+use std::{cmp::Reverse, fmt};
+
+use saphyr_parser::Marker;
+
+use crate::scalar::block_scalar_header;
+
+/// The part of the input an error points to, for inclusion in error messages.
+///
+/// Displaying the snippet prints the line the error happened on, followed by a caret under the
+/// column of the error. Lines longer than [`Self::max_width`] `char`s are truncated around the
+/// error, so that huge scalars or minified documents do not flood logs. The whole line remains
+/// available through [`Self::line`].
+///
+/// ```
+/// use saphyr::{ErrorSnippet, Yaml};
+///
+/// let source = "key: [1, 2\nother: 3";
+/// let error = Yaml::load_from_str(source).unwrap_err();
+/// let snippet = ErrorSnippet::new(source, error.marker());
+/// assert_eq!(snippet.line(), "other: 3");
+/// assert_eq!(snippet.to_string(), "other: 3\n     ^");
+/// ```
+///
+/// When the line is indented inconsistently with the lines before it, which YAML errors often
+/// stem from, the snippet also suggests an indentation. See [`Self::help`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErrorSnippet<'input> {
+    /// The whole line the error happened on, without its line break.
+    line: &'input str,
+    /// The column of the error within `line`, in `char`s.
+    col: usize,
+    /// The maximum number of `char`s of `line` to display.
+    max_width: usize,
+    /// A suggestion on how to fix the indentation of `line`, if it looks wrong.
+    help: Option<IndentationHelp>,
+}
+
+/// A suggestion to indent the line of an error differently. See [`ErrorSnippet::help`].
+///
+/// ```
+/// use saphyr::{ErrorSnippet, IndentationHelp, Yaml};
+///
+/// let source = "server:\n  host: a\n   port: 80\n";
+/// let error = Yaml::load_from_str(source).unwrap_err();
+/// let snippet = ErrorSnippet::new(source, error.marker());
+/// assert_eq!(snippet.help(), Some(&IndentationHelp { line: 3, found: 3, suggested: 2 }));
+/// assert_eq!(
+///     snippet.help().unwrap().to_string(),
+///     "did you mean to indent line 3 by 2 spaces instead of 3?"
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndentationHelp {
+    /// The line of the error, starting at 1 like in [`Marker`]s.
+    pub line: usize,
+    /// The number of spaces the line is indented by.
+    pub found: usize,
+    /// The number of spaces the line should likely be indented by: that of the closest block
+    /// the lines before it open.
+    pub suggested: usize,
+}
+
+impl fmt::Display for IndentationHelp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "did you mean to indent line {} by {} spaces instead of {}?",
+            self.line, self.suggested, self.found
+        )
+    }
+}
+
+impl<'input> ErrorSnippet<'input> {
+    /// Create a snippet of `source` for an error at `marker`.
+    ///
+    /// `marker` must come from parsing `source`. If it points past the end of `source`, the snippet
+    /// points to the end of the last line.
+    #[must_use]
+    pub fn new(source: &'input str, marker: &Marker) -> Self {
+        let offset = source
+            .char_indices()
+            .nth(marker.index())
+            .map_or(source.len(), |(offset, _)| offset);
+        let start = source[..offset].rfind('\n').map_or(0, |idx| idx + 1);
+        let end = source[start..]
+            .find(['\n', '\r'])
+            .map_or(source.len(), |idx| start + idx);
+        Self {
+            line: &source[start..end],
+            col: source[start..offset].chars().count(),
+            max_width: 80,
+            help: indentation_help(source, marker.line()),
+        }
+    }
+
+    /// Set the maximum number of `char`s of the line to display (defaults to 80).
+    #[must_use]
+    pub fn max_width(mut self, max_width: usize) -> Self {
+        self.max_width = max_width;
+        self
+    }
+
+    /// Return the whole line the error happened on, regardless of its length.
+    #[must_use]
+    pub fn line(&self) -> &'input str {
+        self.line
+    }
+
+    /// Return the column of the error within [`Self::line`], in `char`s.
+    #[must_use]
+    pub fn col(&self) -> usize {
+        self.col
+    }
+
+    /// Return a suggestion on how to indent the line, if its indentation is inconsistent with the
+    /// lines before it.
+    ///
+    /// This is a heuristic: it compares the indentation of the line with those of the blocks the
+    /// lines before it open, and may miss mistakes or suggest the wrong level. Displaying the
+    /// snippet includes the suggestion on a last line, starting with `help: `.
+    #[must_use]
+    pub fn help(&self) -> Option<&IndentationHelp> {
+        self.help.as_ref()
+    }
+
+    /// Return whether displaying the snippet truncates the line.
+    #[must_use]
+    pub fn is_truncated(&self) -> bool {
+        self.line.chars().count() > self.max_width
+    }
+}
+
+impl fmt::Display for ErrorSnippet<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_excerpt(f)?;
+        match &self.help {
+            Some(help) => write!(f, "\nhelp: {help}"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl ErrorSnippet<'_> {
+    /// Write the line, truncated if needed, and the caret under the error.
+    fn fmt_excerpt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let length = self.line.chars().count();
+        if length <= self.max_width {
+            return write!(f, "{}\n{:>col$}^", self.line, "", col = self.col);
+        }
+
+        // Center the window on the error, without going past either end of the line.
+        let start = self
+            .col
+            .saturating_sub(self.max_width / 2)
+            .min(length - self.max_width);
+        let end = start + self.max_width;
+        let prefix = if start > 0 { "..." } else { "" };
+        let suffix = if end < length { "..." } else { "" };
+        let excerpt = self
+            .line
+            .chars()
+            .skip(start)
+            .take(self.max_width)
+            .collect::<String>();
+        write!(
+            f,
+            "{prefix}{excerpt}{suffix}\n{:>col$}^",
+            "",
+            col = prefix.len() + self.col - start
+        )
+    }
+}
+
+/// Suggest how to indent the line `line` of `source`, if it is indented inconsistently with the
+/// lines before it.
+fn indentation_help(source: &str, line: usize) -> Option<IndentationHelp> {
+    // The indentations of the blocks open at the end of the previous line.
+    let mut levels: Vec<usize> = vec![];
+    // Whether the previous line opens a block, which the next line may indent further.
+    let mut opens_block = true;
+    // The indentation of the line starting the block scalar we are in, if any.
+    let mut block_scalar = None;
+    for text in source.lines().take(line.saturating_sub(1)) {
+        let (indent, content) = split_indentation(text);
+        if content.is_empty() || content.starts_with('#') {
+            continue;
+        }
+        match block_scalar {
+            Some(parent) if indent > parent => continue,
+            _ => block_scalar = None,
+        }
+        if content.starts_with("---") || content.starts_with("...") {
+            levels.clear();
+            opens_block = true;
+            continue;
+        }
+        while levels.last().is_some_and(|&level| level > indent) {
+            levels.pop();
+        }
+        if levels.last() != Some(&indent) {
+            levels.push(indent);
+        }
+        // The contents of items of sequences are a block of their own.
+        let mut rest = content;
+        let mut col = indent;
+        while let Some(item) = rest.strip_prefix('-').filter(|item| item.starts_with(' ')) {
+            let contents = item.trim_start_matches(' ');
+            col += 1 + item.len() - contents.len();
+            levels.push(col);
+            rest = contents;
+        }
+        let code = rest.split(" #").next().unwrap_or_default().trim_end();
+        if code
+            .split_whitespace()
+            .last()
+            .and_then(block_scalar_header)
+            .is_some()
+        {
+            block_scalar = Some(indent);
+        }
+        opens_block = code.is_empty() || code == "-" || code.ends_with(':');
+    }
+
+    let (found, content) = split_indentation(source.lines().nth(line.checked_sub(1)?)?);
+    if content.is_empty()
+        || levels.contains(&found)
+        || (opens_block && levels.last().map_or(true, |&level| found > level))
+    {
+        return None;
+    }
+    // On ties, the line more likely continues the deeper block.
+    let suggested = levels
+        .iter()
+        .copied()
+        .min_by_key(|&level| (level.abs_diff(found), Reverse(level)))?;
+    Some(IndentationHelp {
+        line,
+        found,
+        suggested,
+    })
+}
+
+/// Split `line` into the number of spaces it is indented by and the rest of it.
+fn split_indentation(line: &str) -> (usize, &str) {
+    let content = line.trim_start_matches(' ');
+    (line.len() - content.len(), content)
+}
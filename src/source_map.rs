@@ -0,0 +1,102 @@
+//! Translation of positions in generated YAML back to the source it was generated from.
+
+use saphyr_parser::Marker;
+
+/// A map from positions in generated YAML to positions in the template it was rendered from.
+///
+/// Template engines (e.g.: Helm charts) render a template into YAML, which is then loaded. Errors
+/// and nodes report positions in the rendered text, which users never see. A source map lists,
+/// for each span of the rendered text copied from the template, where the span starts in both.
+/// Positions within a span are translated relative to its start: `char`s on the first line of
+/// the span are shifted by the column of the span, and lines after it keep their column.
+///
+/// ```
+/// use saphyr::{Marker, SourceMap};
+///
+/// // The template `name: {{ name }}\nport: 80\n` renders as `name: web\nport: 80\n`.
+/// let mut map = SourceMap::new();
+/// map.add(Marker::new(0, 1, 0), Marker::new(0, 1, 0))
+///     // `web`, rendered at column 6, comes from the expression at column 6 of the template.
+///     .add(Marker::new(6, 1, 6), Marker::new(6, 1, 6))
+///     // The text after `web` comes from after `}}` in the template.
+///     .add(Marker::new(9, 1, 9), Marker::new(16, 1, 16));
+///
+/// // `port` on the second line of the rendered text.
+/// let port = map.translate(Marker::new(10, 2, 0));
+/// assert_eq!((port.index(), port.line(), port.col()), (17, 2, 0));
+/// ```
+///
+/// Set it on a loader with [`YamlLoader::source_map`].
+///
+/// [`YamlLoader::source_map`]: crate::YamlLoader::source_map
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    /// The start of each span in the generated text and in the template, sorted by their index
+    /// in the generated text.
+    spans: Vec<(Marker, Marker)>,
+}
+
+impl SourceMap {
+    /// Create an empty source map, which translates no position.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that the span of the generated text starting at `generated` was copied from the
+    /// template at `original`. The span extends to the start of the next span.
+    ///
+    /// Spans may be added in any order. Adding a span starting at the same index as another
+    /// replaces it.
+    pub fn add(&mut self, generated: Marker, original: Marker) -> &mut Self {
+        match self
+            .spans
+            .binary_search_by_key(&generated.index(), |(start, _)| start.index())
+        {
+            Ok(i) => self.spans[i] = (generated, original),
+            Err(i) => self.spans.insert(i, (generated, original)),
+        }
+        self
+    }
+
+    /// Return the number of spans in the map.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// Return whether the map has no span.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// Translate `marker`, pointing into the generated text, into a position in the template.
+    ///
+    /// Positions before the first span are returned as they are.
+    #[must_use]
+    pub fn translate(&self, marker: Marker) -> Marker {
+        let i = self
+            .spans
+            .partition_point(|(start, _)| start.index() <= marker.index());
+        let Some((generated, original)) = i.checked_sub(1).map(|i| self.spans[i]) else {
+            return marker;
+        };
+        let col = if marker.line() == generated.line() {
+            original
+                .col()
+                .saturating_add(marker.col().saturating_sub(generated.col()))
+        } else {
+            marker.col()
+        };
+        Marker::new(
+            original
+                .index()
+                .saturating_add(marker.index() - generated.index()),
+            original
+                .line()
+                .saturating_add(marker.line().saturating_sub(generated.line())),
+            col,
+        )
+    }
+}
@@ -0,0 +1,81 @@
+//! YAML tags attached to nodes.
+
+use std::fmt;
+
+/// The handle of the tags of the YAML core schema (`!!`), once resolved.
+pub(crate) const CORE_SCHEMA_HANDLE: &str = "tag:yaml.org,2002:";
+
+/// A YAML tag attached to a [`Yaml::Tagged`] node.
+///
+/// This mirrors [`saphyr_parser::Tag`], with the additional traits required to be stored inside a
+/// [`Yaml`] node. The handle is stored resolved, as given by the parser. For instance, `!!set`
+/// has a handle of `tag:yaml.org,2002:` and a suffix of `set`.
+///
+/// [`Yaml`]: crate::Yaml
+/// [`Yaml::Tagged`]: crate::Yaml::Tagged
+#[derive(Clone, PartialEq, PartialOrd, Debug, Eq, Ord, Hash)]
+pub struct Tag {
+    /// Handle of the tag (`!` included).
+    pub handle: String,
+    /// The suffix of the tag.
+    pub suffix: String,
+}
+
+impl Tag {
+    /// Create a new tag from its handle and suffix.
+    #[must_use]
+    pub fn new(handle: impl Into<String>, suffix: impl Into<String>) -> Self {
+        Self {
+            handle: handle.into(),
+            suffix: suffix.into(),
+        }
+    }
+
+    /// Create a tag from the YAML core schema (e.g.: `!!set`).
+    #[must_use]
+    pub fn core(suffix: impl Into<String>) -> Self {
+        Self::new(CORE_SCHEMA_HANDLE, suffix)
+    }
+
+    /// Return whether the tag is the core schema tag with the given suffix.
+    ///
+    /// ```
+    /// # use saphyr::Tag;
+    /// assert!(Tag::core("set").is_core("set"));
+    /// assert!(!Tag::new("!", "set").is_core("set"));
+    /// ```
+    #[must_use]
+    pub fn is_core(&self, suffix: &str) -> bool {
+        self.handle == CORE_SCHEMA_HANDLE && self.suffix == suffix
+    }
+}
+
+impl From<saphyr_parser::Tag> for Tag {
+    fn from(tag: saphyr_parser::Tag) -> Self {
+        Self {
+            handle: tag.handle,
+            suffix: tag.suffix,
+        }
+    }
+}
+
+impl fmt::Display for Tag {
+    /// Write the tag as it would appear in a YAML document.
+    ///
+    /// Core schema tags use the `!!` shorthand, local tags the `!` shorthand. Other tags are
+    /// written in verbatim form (`!<...>`).
+    ///
+    /// ```
+    /// # use saphyr::Tag;
+    /// assert_eq!(Tag::core("omap").to_string(), "!!omap");
+    /// assert_eq!(Tag::new("!", "local").to_string(), "!local");
+    /// assert_eq!(Tag::new("tag:example.com,2024:", "x").to_string(), "!<tag:example.com,2024:x>");
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.handle.as_str() {
+            CORE_SCHEMA_HANDLE => write!(f, "!!{}", self.suffix),
+            "!" => write!(f, "!{}", self.suffix),
+            handle => write!(f, "!<{handle}{}>", self.suffix),
+        }
+    }
+}
@@ -0,0 +1,567 @@
+//! Traces of the events of YAML streams, to record and replay them.
+
+use std::collections::HashMap;
+
+use saphyr_parser::{
+    scanner::{Scanner, TEncoding, Token, TokenType},
+    Event, MarkedEventReceiver, Marker, Parser, ScanError, TScalarStyle, Tag,
+};
+
+use crate::{ErrorCode, Schema, Yaml};
+
+/// The events the parser produces for a YAML stream, recorded to be written down and replayed.
+///
+/// Traces are meant for bug reports: rather than sharing a document which makes loading fail or
+/// misbehave, record its events, [redact](Self::redacted) them if the document is sensitive, and
+/// share the text of the trace. Replaying the trace into a [`YamlLoader`] reproduces the load.
+///
+/// ```
+/// use saphyr::{EventTrace, Yaml, YamlLoader};
+///
+/// let trace = EventTrace::record_str("password: &p hunter2\nretries: 3\n").unwrap();
+/// let text = trace.redacted().to_string();
+/// assert_eq!(text, "\
+/// +STR
+/// +DOC
+/// +MAP
+/// =VAL :s1
+/// =VAL &1 :s2
+/// =VAL :s3
+/// =VAL :3
+/// -MAP
+/// -DOC
+/// -STR
+/// ");
+///
+/// let replayed: EventTrace = text.parse().unwrap();
+/// let mut loader = YamlLoader::<Yaml>::default();
+/// replayed.replay(&mut loader);
+/// let docs = loader.into_documents();
+/// assert_eq!(docs[0]["s3"].as_i64(), Some(3));
+/// ```
+///
+/// The text of a trace follows the event format of the YAML test suite: one event per line,
+/// `+` starting and `-` ending streams, documents, mappings and sequences, `=VAL` for scalars
+/// (with `:`, `'`, `"`, `|` or `>` telling their style) and `=ALI` for aliases. It differs in
+/// two ways: anchors are the numbers the parser gives them rather than their names, and tags are
+/// written as their handle and suffix separated by a space (e.g.: `<tag:yaml.org,2002: str>`).
+/// The positions of events are not recorded.
+///
+/// With the `serde` feature, traces implement `Serialize` and `Deserialize` as their text.
+///
+/// This type is only available with the `trace` feature.
+///
+/// [`YamlLoader`]: crate::YamlLoader
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "String", try_from = "String")
+)]
+pub struct EventTrace {
+    /// The events, in the order the parser produced them.
+    events: Vec<Event>,
+}
+
+impl EventTrace {
+    /// Record the events of `source`.
+    ///
+    /// # Errors
+    /// Returns `ScanError` when parsing fails.
+    pub fn record_str(source: &str) -> Result<Self, ScanError> {
+        let mut events = vec![];
+        for event in Parser::new_from_str(source) {
+            let (event, _) = event?;
+            if event != Event::Nothing {
+                events.push(event);
+            }
+        }
+        Ok(Self { events })
+    }
+
+    /// Return the events, in the order the parser produced them.
+    #[must_use]
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Return a copy of `self` whose scalars are replaced by placeholders (`s1`, `s2`, ...) if they
+    /// are strings.
+    ///
+    /// Scalars which are nulls, booleans, integers or reals are kept, as are tags: the redacted
+    /// trace loads into the same tree as `self`, with the same types, but with meaningless strings.
+    /// Equal strings get equal placeholders, so that duplicate keys stay duplicates.
+    #[must_use]
+    pub fn redacted(&self) -> Self {
+        let mut placeholders: HashMap<&str, String> = HashMap::new();
+        let events = self
+            .events
+            .iter()
+            .map(|event| match event {
+                Event::Scalar(value, style, anchor, tag) => Event::Scalar(
+                    redact(&mut placeholders, value, *style, tag.as_ref()),
+                    *style,
+                    *anchor,
+                    tag.clone(),
+                ),
+                event => event.clone(),
+            })
+            .collect();
+        Self { events }
+    }
+
+    /// Send the events to `receiver`, in order.
+    ///
+    /// Events are sent with the default position, since it is not recorded.
+    pub fn replay<R: MarkedEventReceiver>(&self, receiver: &mut R) {
+        for event in &self.events {
+            receiver.on_event(event.clone(), Marker::default());
+        }
+    }
+}
+
+impl std::fmt::Display for EventTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for event in &self.events {
+            match event {
+                Event::Nothing => continue,
+                Event::StreamStart => write!(f, "+STR")?,
+                Event::StreamEnd => write!(f, "-STR")?,
+                Event::DocumentStart => write!(f, "+DOC")?,
+                Event::DocumentEnd => write!(f, "-DOC")?,
+                Event::Alias(anchor) => write!(f, "=ALI *{anchor}")?,
+                Event::Scalar(value, style, anchor, tag) => {
+                    write!(f, "=VAL")?;
+                    write_properties(f, *anchor, tag.as_ref())?;
+                    write!(f, " ")?;
+                    write_scalar(f, value, *style)?;
+                }
+                Event::SequenceStart(anchor, tag) | Event::MappingStart(anchor, tag) => {
+                    let kind = if matches!(event, Event::MappingStart(..)) {
+                        "MAP"
+                    } else {
+                        "SEQ"
+                    };
+                    write!(f, "+{kind}")?;
+                    write_properties(f, *anchor, tag.as_ref())?;
+                }
+                Event::SequenceEnd => write!(f, "-SEQ")?,
+                Event::MappingEnd => write!(f, "-MAP")?,
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write the anchor and tag of a node, each preceded by a space, if it has them.
+fn write_properties(
+    f: &mut std::fmt::Formatter<'_>,
+    anchor: usize,
+    tag: Option<&Tag>,
+) -> std::fmt::Result {
+    if anchor > 0 {
+        write!(f, " &{anchor}")?;
+    }
+    if let Some(tag) = tag {
+        write!(f, " <{} {}>", tag.handle, tag.suffix)?;
+    }
+    Ok(())
+}
+
+impl std::str::FromStr for EventTrace {
+    type Err = TraceError;
+
+    /// Read a trace from the text [`Display`] writes.
+    ///
+    /// [`Display`]: std::fmt::Display
+    fn from_str(text: &str) -> Result<Self, TraceError> {
+        let events = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| parse_event(line).ok_or(TraceError { line: i + 1 }))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { events })
+    }
+}
+
+/// Read an event written as [`EventTrace`] writes it.
+fn parse_event(line: &str) -> Option<Event> {
+    let (kind, mut rest) = line.split_once(' ').unwrap_or((line, ""));
+    let mut anchor = 0;
+    let mut tag = None;
+    let mut value = None;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('&') {
+            let (number, after) = after.split_once(' ').unwrap_or((after, ""));
+            anchor = number.parse().ok().filter(|&anchor| anchor > 0)?;
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('<') {
+            let (text, after) = after.split_once('>')?;
+            let (handle, suffix) = text.split_once(' ')?;
+            tag = Some(Tag {
+                handle: handle.to_owned(),
+                suffix: suffix.to_owned(),
+            });
+            rest = after.strip_prefix(' ').unwrap_or(after);
+        } else {
+            value = Some(rest);
+            break;
+        }
+    }
+    let bare = anchor == 0 && tag.is_none() && value.is_none();
+    Some(match kind {
+        "+STR" if bare => Event::StreamStart,
+        "-STR" if bare => Event::StreamEnd,
+        "+DOC" if bare => Event::DocumentStart,
+        "-DOC" if bare => Event::DocumentEnd,
+        "-SEQ" if bare => Event::SequenceEnd,
+        "-MAP" if bare => Event::MappingEnd,
+        "+SEQ" if value.is_none() => Event::SequenceStart(anchor, tag),
+        "+MAP" if value.is_none() => Event::MappingStart(anchor, tag),
+        "=ALI" if anchor == 0 && tag.is_none() => {
+            Event::Alias(value?.strip_prefix('*')?.parse().ok()?)
+        }
+        "=VAL" => {
+            let (value, style) = parse_scalar(value?)?;
+            Event::Scalar(value, style, anchor, tag)
+        }
+        _ => return None,
+    })
+}
+
+/// Return `value`, or a placeholder (`s1`, `s2`, ...) for it if it is a string, as
+/// [`EventTrace::redacted`] does. `placeholders` holds the placeholders given so far.
+fn redact<'a>(
+    placeholders: &mut HashMap<&'a str, String>,
+    value: &'a str,
+    style: TScalarStyle,
+    tag: Option<&Tag>,
+) -> String {
+    match Schema::Legacy.resolve(value.to_owned(), style, tag) {
+        Yaml::Null | Yaml::Boolean(_) | Yaml::Integer(_) | Yaml::Real(_) => value.to_owned(),
+        _ => {
+            let next = format!("s{}", placeholders.len() + 1);
+            placeholders.entry(value).or_insert(next).clone()
+        }
+    }
+}
+
+/// Write a scalar as its style indicator followed by its escaped text.
+fn write_scalar(
+    f: &mut std::fmt::Formatter<'_>,
+    value: &str,
+    style: TScalarStyle,
+) -> std::fmt::Result {
+    let indicator = match style {
+        TScalarStyle::Plain => ':',
+        TScalarStyle::SingleQuoted => '\'',
+        TScalarStyle::DoubleQuoted => '"',
+        TScalarStyle::Literal => '|',
+        TScalarStyle::Folded => '>',
+    };
+    write!(f, "{indicator}")?;
+    for c in value.chars() {
+        match c {
+            '\\' => write!(f, "\\\\")?,
+            '\0' => write!(f, "\\0")?,
+            '\x08' => write!(f, "\\b")?,
+            '\t' => write!(f, "\\t")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    Ok(())
+}
+
+/// Read a scalar written as [`write_scalar`] writes it.
+fn parse_scalar(text: &str) -> Option<(String, TScalarStyle)> {
+    let mut chars = text.chars();
+    let style = match chars.next()? {
+        ':' => TScalarStyle::Plain,
+        '\'' => TScalarStyle::SingleQuoted,
+        '"' => TScalarStyle::DoubleQuoted,
+        '|' => TScalarStyle::Literal,
+        '>' => TScalarStyle::Folded,
+        _ => return None,
+    };
+    let mut value = String::new();
+    while let Some(c) = chars.next() {
+        value.push(if c == '\\' {
+            match chars.next()? {
+                '\\' => '\\',
+                '0' => '\0',
+                'b' => '\x08',
+                't' => '\t',
+                'n' => '\n',
+                'r' => '\r',
+                _ => return None,
+            }
+        } else {
+            c
+        });
+    }
+    Some((value, style))
+}
+
+/// The tokens the scanner produces for a YAML stream, recorded to be written down.
+///
+/// Where an [`EventTrace`] shows how the parser understood a document, a token trace shows how
+/// the scanner split it, e.g. for bug reports about where a scalar ends or which indicator
+/// started a collection. Like event traces, token traces can be [redacted](Self::redacted)
+/// before they are shared, and read back from their text. They cannot be replayed, though: the
+/// parser reads characters, not tokens.
+///
+/// ```
+/// use saphyr::TokenTrace;
+///
+/// let trace = TokenTrace::record_str("token: &t [abc, 3]").unwrap();
+/// let text = trace.redacted().to_string();
+/// assert_eq!(text, "\
+/// STREAM-START
+/// BLOCK-MAPPING-START
+/// KEY
+/// SCALAR :s1
+/// VALUE
+/// ANCHOR a1
+/// FLOW-SEQUENCE-START
+/// SCALAR :s2
+/// FLOW-ENTRY
+/// SCALAR :3
+/// FLOW-SEQUENCE-END
+/// BLOCK-END
+/// STREAM-END
+/// ");
+/// assert_eq!(text.parse::<TokenTrace>().unwrap(), trace.redacted());
+/// ```
+///
+/// Each token is written on its own line, as its kind followed by its contents, if any. Scalars
+/// are written as in event traces. Tags and tag directives are written as their handle and
+/// suffix (or prefix) separated by a space. The positions of tokens are not recorded.
+///
+/// With the `serde` feature, traces implement `Serialize` and `Deserialize` as their text.
+///
+/// This type is only available with the `trace` feature.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "String", try_from = "String")
+)]
+pub struct TokenTrace {
+    /// The tokens, in the order the scanner produced them.
+    tokens: Vec<TokenType>,
+}
+
+impl TokenTrace {
+    /// Record the tokens of `source`.
+    ///
+    /// # Errors
+    /// Returns `ScanError` when scanning fails.
+    pub fn record_str(source: &str) -> Result<Self, ScanError> {
+        let mut scanner = Scanner::new(source.chars());
+        let tokens = scanner.by_ref().map(|Token(_, token)| token).collect();
+        match scanner.get_error() {
+            Some(error) => Err(error),
+            None => Ok(Self { tokens }),
+        }
+    }
+
+    /// Return the tokens, in the order the scanner produced them.
+    #[must_use]
+    pub fn tokens(&self) -> &[TokenType] {
+        &self.tokens
+    }
+
+    /// Return a copy of `self` whose scalars are replaced by placeholders as for
+    /// [`EventTrace::redacted`], and whose anchors and aliases are replaced by placeholders
+    /// (`a1`, `a2`, ...).
+    ///
+    /// Tags are kept. Since tokens are not resolved against tags, scalars are kept if they would
+    /// be nulls, booleans, integers or reals without tags.
+    #[must_use]
+    pub fn redacted(&self) -> Self {
+        let mut placeholders: HashMap<&str, String> = HashMap::new();
+        let mut anchors: HashMap<&str, String> = HashMap::new();
+        let mut anchor = |name| {
+            let next = format!("a{}", anchors.len() + 1);
+            anchors.entry(name).or_insert(next).clone()
+        };
+        let tokens = self
+            .tokens
+            .iter()
+            .map(|token| match token {
+                TokenType::Scalar(style, value) => {
+                    TokenType::Scalar(*style, redact(&mut placeholders, value, *style, None))
+                }
+                TokenType::Anchor(name) => TokenType::Anchor(anchor(name)),
+                TokenType::Alias(name) => TokenType::Alias(anchor(name)),
+                token => token.clone(),
+            })
+            .collect();
+        Self { tokens }
+    }
+}
+
+impl std::fmt::Display for TokenTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for token in &self.tokens {
+            match token {
+                TokenType::StreamStart(_) => write!(f, "STREAM-START")?,
+                TokenType::StreamEnd => write!(f, "STREAM-END")?,
+                TokenType::VersionDirective(major, minor) => {
+                    write!(f, "VERSION-DIRECTIVE {major}.{minor}")?;
+                }
+                TokenType::TagDirective(handle, prefix) => {
+                    write!(f, "TAG-DIRECTIVE {handle} {prefix}")?;
+                }
+                TokenType::DocumentStart => write!(f, "DOCUMENT-START")?,
+                TokenType::DocumentEnd => write!(f, "DOCUMENT-END")?,
+                TokenType::BlockSequenceStart => write!(f, "BLOCK-SEQUENCE-START")?,
+                TokenType::BlockMappingStart => write!(f, "BLOCK-MAPPING-START")?,
+                TokenType::BlockEnd => write!(f, "BLOCK-END")?,
+                TokenType::FlowSequenceStart => write!(f, "FLOW-SEQUENCE-START")?,
+                TokenType::FlowSequenceEnd => write!(f, "FLOW-SEQUENCE-END")?,
+                TokenType::FlowMappingStart => write!(f, "FLOW-MAPPING-START")?,
+                TokenType::FlowMappingEnd => write!(f, "FLOW-MAPPING-END")?,
+                TokenType::BlockEntry => write!(f, "BLOCK-ENTRY")?,
+                TokenType::FlowEntry => write!(f, "FLOW-ENTRY")?,
+                TokenType::Key => write!(f, "KEY")?,
+                TokenType::Value => write!(f, "VALUE")?,
+                TokenType::Alias(name) => write!(f, "ALIAS {name}")?,
+                TokenType::Anchor(name) => write!(f, "ANCHOR {name}")?,
+                TokenType::Tag(handle, suffix) => write!(f, "TAG {handle} {suffix}")?,
+                TokenType::Scalar(style, value) => {
+                    write!(f, "SCALAR ")?;
+                    write_scalar(f, value, *style)?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for TokenTrace {
+    type Err = TraceError;
+
+    /// Read a trace from the text [`Display`] writes.
+    ///
+    /// [`Display`]: std::fmt::Display
+    fn from_str(text: &str) -> Result<Self, TraceError> {
+        let tokens = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| parse_token(line).ok_or(TraceError { line: i + 1 }))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { tokens })
+    }
+}
+
+/// Read a token written as [`TokenTrace`] writes it.
+fn parse_token(line: &str) -> Option<TokenType> {
+    let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let pair = || {
+        let (first, second) = rest.split_once(' ')?;
+        Some((first.to_owned(), second.to_owned()))
+    };
+    let bare = rest.is_empty();
+    Some(match kind {
+        "STREAM-START" if bare => TokenType::StreamStart(TEncoding::Utf8),
+        "STREAM-END" if bare => TokenType::StreamEnd,
+        "VERSION-DIRECTIVE" => {
+            let (major, minor) = rest.split_once('.')?;
+            TokenType::VersionDirective(major.parse().ok()?, minor.parse().ok()?)
+        }
+        "TAG-DIRECTIVE" => {
+            let (handle, prefix) = pair()?;
+            TokenType::TagDirective(handle, prefix)
+        }
+        "DOCUMENT-START" if bare => TokenType::DocumentStart,
+        "DOCUMENT-END" if bare => TokenType::DocumentEnd,
+        "BLOCK-SEQUENCE-START" if bare => TokenType::BlockSequenceStart,
+        "BLOCK-MAPPING-START" if bare => TokenType::BlockMappingStart,
+        "BLOCK-END" if bare => TokenType::BlockEnd,
+        "FLOW-SEQUENCE-START" if bare => TokenType::FlowSequenceStart,
+        "FLOW-SEQUENCE-END" if bare => TokenType::FlowSequenceEnd,
+        "FLOW-MAPPING-START" if bare => TokenType::FlowMappingStart,
+        "FLOW-MAPPING-END" if bare => TokenType::FlowMappingEnd,
+        "BLOCK-ENTRY" if bare => TokenType::BlockEntry,
+        "FLOW-ENTRY" if bare => TokenType::FlowEntry,
+        "KEY" if bare => TokenType::Key,
+        "VALUE" if bare => TokenType::Value,
+        "ALIAS" if !bare => TokenType::Alias(rest.to_owned()),
+        "ANCHOR" if !bare => TokenType::Anchor(rest.to_owned()),
+        "TAG" => {
+            let (handle, suffix) = pair()?;
+            TokenType::Tag(handle, suffix)
+        }
+        "SCALAR" => {
+            let (value, style) = parse_scalar(rest)?;
+            TokenType::Scalar(style, value)
+        }
+        _ => return None,
+    })
+}
+
+#[cfg(feature = "serde")]
+impl From<EventTrace> for String {
+    fn from(trace: EventTrace) -> Self {
+        trace.to_string()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<String> for EventTrace {
+    type Error = TraceError;
+
+    fn try_from(text: String) -> Result<Self, TraceError> {
+        text.parse()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<TokenTrace> for String {
+    fn from(trace: TokenTrace) -> Self {
+        trace.to_string()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<String> for TokenTrace {
+    type Error = TraceError;
+
+    fn try_from(text: String) -> Result<Self, TraceError> {
+        text.parse()
+    }
+}
+
+/// A line of a trace is not an event or a token written as [`EventTrace`] or [`TokenTrace`]
+/// write them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceError {
+    /// The line (1-based) of the invalid event or token.
+    pub line: usize,
+}
+
+impl TraceError {
+    /// Return the stable code of the error, which [`Display`] prefixes the message with.
+    ///
+    /// [`Display`]: std::fmt::Display
+    #[must_use]
+    #[allow(clippy::unused_self)]
+    pub fn code(&self) -> ErrorCode {
+        ErrorCode::new("TRACE001")
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+impl std::fmt::Display for TraceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: invalid event at line {}", self.code(), self.line)
+    }
+}
@@ -0,0 +1,83 @@
+//! Checking the syntax of YAML streams without loading them.
+
+use saphyr_parser::{Marker, Parser, ScanError};
+
+use crate::manifest::is_marker_line;
+
+/// Check that `source` is syntactically valid YAML, without loading it.
+///
+/// This is meant for pipelines gating on whether files are valid YAML, where loading them would
+/// build trees only to drop them. The input is run through the scanner and the parser of
+/// `saphyr-parser`: no node is built, no anchor is recorded and no alias is resolved. This is not
+/// a scanner-level fast path, though: the scanner still copies the text of each scalar into a
+/// string, as when loading. Those strings are dropped as soon as the parser emits them, so this
+/// takes no memory in proportion to the size of the documents, but scanning takes as long as it
+/// does when loading.
+///
+/// The parser stops at the first error of a document. Checking then resumes at the next `---`
+/// line after the error, so that the errors of several broken documents of a stream are all
+/// reported, in order. Errors after the first one are only as accurate as the documents can be
+/// told apart: a `%TAG` directive before a document which follows a broken one is not taken
+/// into account.
+///
+/// ```
+/// use saphyr::validate_syntax;
+///
+/// assert!(validate_syntax("a: [1, 2]\n---\nb: {c: d}").is_ok());
+///
+/// let errors = validate_syntax("a: [1, 2\n---\nb: ok\n---\nc: 'broken\n").unwrap_err();
+/// let lines: Vec<_> = errors.iter().map(|error| error.marker().line()).collect();
+/// assert_eq!(lines, [2, 5]);
+/// ```
+///
+/// This checks syntax only: documents which are valid YAML may still fail to load with the
+/// options of a [`YamlLoader`] (e.g.: duplicate keys it rejects).
+///
+/// # Errors
+/// Returns the errors of the scanner and of the parser, in the order they appear.
+///
+/// [`YamlLoader`]: crate::YamlLoader
+pub fn validate_syntax(source: &str) -> Result<(), Vec<ScanError>> {
+    let mut errors = vec![];
+    // The rest of the input to check, with the position at which it starts.
+    let mut rest = source;
+    let mut start = Marker::new(0, 1, 0);
+    while let Some(error) = first_error(rest) {
+        let marker = error.marker();
+        errors.push(ScanError::new(
+            Marker::new(
+                start.index() + marker.index(),
+                start.line() + marker.line() - 1,
+                marker.col(),
+            ),
+            error.info().to_owned(),
+        ));
+        // Resume at the next line starting a document, after the line of the error.
+        let mut offset = 0;
+        let mut chars = 0;
+        let mut resumed = false;
+        for (i, line) in rest.split_inclusive('\n').enumerate() {
+            if i + 1 > marker.line() && is_marker_line(line, "---") {
+                start = Marker::new(start.index() + chars, start.line() + i, 0);
+                rest = &rest[offset..];
+                resumed = true;
+                break;
+            }
+            offset += line.len();
+            chars += line.chars().count();
+        }
+        if !resumed {
+            break;
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Return the first error the parser finds in `source`, if any.
+fn first_error(source: &str) -> Option<ScanError> {
+    Parser::new_from_str(source).find_map(Result::err)
+}
@@ -0,0 +1,659 @@
+//! Higher-level utilities to write YAML into sinks.
+
+use std::{
+    borrow::Cow,
+    fmt, io,
+    ops::Deref,
+    sync::{Mutex, PoisonError},
+};
+
+use crate::{EmitError, Hash, Yaml, YamlEmitter};
+
+/// A writer appending YAML documents to an [`io::Write`] sink over time.
+///
+/// Each document is started with a `---` marker and terminated with a newline, so that documents
+/// written at different times (or by different runs of a program, as long as the sink is opened
+/// in append mode) form a valid YAML stream. This is designed for long-running processes logging
+/// events as YAML documents.
+///
+/// ```
+/// use saphyr::{StreamWriter, Yaml};
+///
+/// let mut writer = StreamWriter::new(Vec::new());
+/// writer.explicit_end(true);
+/// writer.write_document(&Yaml::Integer(1)).unwrap();
+/// writer.write_document(&Yaml::String("foo".into())).unwrap();
+///
+/// let output = String::from_utf8(writer.into_inner()).unwrap();
+/// assert_eq!(output, "---\n1\n...\n---\nfoo\n...\n");
+/// assert_eq!(Yaml::load_from_str(&output).unwrap().len(), 2);
+/// ```
+///
+/// The sink must be positioned at the start of a line when the writer is created.
+#[allow(clippy::struct_excessive_bools)]
+pub struct StreamWriter<W: io::Write> {
+    /// The sink in which we write documents.
+    sink: W,
+    /// A buffer into which documents are emitted before being written to the sink.
+    ///
+    /// It is kept across documents to avoid reallocating for each of them.
+    buffer: String,
+    /// Whether to flush the sink after each document.
+    flush: bool,
+    /// Whether to terminate each document with a `...` marker.
+    explicit_end: bool,
+    /// Whether to use 'compact inline notation' when emitting. See [`YamlEmitter::compact`].
+    compact: bool,
+    /// Whether to emit multiline strings in literal style. See
+    /// [`YamlEmitter::multiline_strings`].
+    multiline_strings: bool,
+    /// The number of documents written so far.
+    documents_written: usize,
+}
+
+impl<W: io::Write> StreamWriter<W> {
+    /// Create a new writer appending documents to `sink`.
+    pub fn new(sink: W) -> Self {
+        Self {
+            sink,
+            buffer: String::new(),
+            flush: false,
+            explicit_end: false,
+            compact: true,
+            multiline_strings: false,
+            documents_written: 0,
+        }
+    }
+
+    /// Set whether to flush the sink after each document (defaults to `false`).
+    ///
+    /// This ensures that readers of the sink (e.g.: someone tailing a log file) see each document
+    /// as soon as it is written.
+    pub fn flush_each_document(&mut self, flush: bool) -> &mut Self {
+        self.flush = flush;
+        self
+    }
+
+    /// Set whether to terminate each document with a `...` marker (defaults to `false`).
+    ///
+    /// With the end marker, readers of the stream know a document is complete without waiting for
+    /// the next one to start.
+    pub fn explicit_end(&mut self, explicit_end: bool) -> &mut Self {
+        self.explicit_end = explicit_end;
+        self
+    }
+
+    /// Set 'compact inline notation' on or off. See [`YamlEmitter::compact`].
+    pub fn compact(&mut self, compact: bool) -> &mut Self {
+        self.compact = compact;
+        self
+    }
+
+    /// Render strings containing multiple lines in literal style. See
+    /// [`YamlEmitter::multiline_strings`].
+    pub fn multiline_strings(&mut self, multiline_strings: bool) -> &mut Self {
+        self.multiline_strings = multiline_strings;
+        self
+    }
+
+    /// Append a document to the sink.
+    ///
+    /// The document is emitted entirely before being written to the sink. If emitting fails,
+    /// nothing is written.
+    ///
+    /// # Errors
+    /// Returns an [`io::Error`] if emitting the document or writing to the sink fails.
+    pub fn write_document(&mut self, doc: &Yaml) -> io::Result<()> {
+        self.emit(doc)?;
+        self.buffer.push('\n');
+        self.end_document()
+    }
+
+    /// Append a document made of a mapping whose entries `entries` provides, one at a time.
+    ///
+    /// Each entry is written to the sink as soon as it is given to the [`MappingSink`], without
+    /// building the whole mapping first. This allows interleaving computation with output (e.g.:
+    /// streaming the rows of a database query).
+    ///
+    /// ```
+    /// use saphyr::{StreamWriter, Yaml};
+    ///
+    /// let mut writer = StreamWriter::new(Vec::new());
+    /// writer
+    ///     .write_mapping(|sink| {
+    ///         for id in 1..=2 {
+    ///             let row = Yaml::load_from_str(&format!("[row {id}]")).unwrap().remove(0);
+    ///             sink.entry(Yaml::Integer(id), row)?;
+    ///         }
+    ///         Ok::<_, std::io::Error>(())
+    ///     })
+    ///     .unwrap();
+    ///
+    /// let output = String::from_utf8(writer.into_inner()).unwrap();
+    /// assert_eq!(output, "---\n1:\n  - row 1\n2:\n  - row 2\n");
+    /// ```
+    ///
+    /// If `entries` gives no entry, an empty mapping is written.
+    ///
+    /// # Errors
+    /// Returns the error of `entries`, or an [`io::Error`] if emitting an entry or writing to the
+    /// sink fails. The document is then left incomplete in the sink.
+    pub fn write_mapping<F, E>(&mut self, entries: F) -> Result<(), E>
+    where
+        F: FnOnce(&mut MappingSink<'_, W>) -> Result<(), E>,
+        E: From<io::Error>,
+    {
+        self.sink.write_all(b"---\n")?;
+        let mut sink = MappingSink {
+            writer: self,
+            entries: 0,
+        };
+        entries(&mut sink)?;
+        let empty = sink.entries == 0;
+        self.buffer.clear();
+        if empty {
+            self.buffer.push_str("{}\n");
+        }
+        Ok(self.end_document()?)
+    }
+
+    /// Emit `doc` into `buffer`, starting with a `---` marker.
+    fn emit(&mut self, doc: &Yaml) -> io::Result<()> {
+        self.buffer.clear();
+        let mut emitter = YamlEmitter::new(&mut self.buffer);
+        emitter.compact(self.compact);
+        emitter.multiline_strings(self.multiline_strings);
+        emitter
+            .dump(doc)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+
+    /// Append a document without contents to the sink. See [`YamlEmitter::dump_empty`].
+    ///
+    /// # Errors
+    /// Returns an [`io::Error`] if writing to the sink fails.
+    pub fn write_empty_document(&mut self) -> io::Result<()> {
+        self.buffer.clear();
+        self.buffer.push_str("---\n");
+        self.end_document()
+    }
+
+    /// Write the document in `buffer`, followed by the end marker if needed, to the sink.
+    fn end_document(&mut self) -> io::Result<()> {
+        if self.explicit_end {
+            self.buffer.push_str("...\n");
+        }
+
+        self.sink.write_all(self.buffer.as_bytes())?;
+        if self.flush {
+            self.sink.flush()?;
+        }
+        self.documents_written += 1;
+        Ok(())
+    }
+
+    /// Return the number of documents written so far.
+    #[must_use]
+    pub fn documents_written(&self) -> usize {
+        self.documents_written
+    }
+
+    /// Flush the underlying sink.
+    ///
+    /// # Errors
+    /// Returns an [`io::Error`] if flushing the sink fails.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.sink.flush()
+    }
+
+    /// Return a reference to the underlying sink.
+    pub fn get_ref(&self) -> &W {
+        &self.sink
+    }
+
+    /// Return the underlying sink, consuming `self`.
+    pub fn into_inner(self) -> W {
+        self.sink
+    }
+}
+
+/// A writer splitting a stream of YAML documents into chunks of at most a given size.
+///
+/// Systems capping the size of their messages (e.g.: Kubernetes `ConfigMap`s, message queues)
+/// cannot take streams of any length. This writer emits documents into a chunk until the next
+/// one would not fit, in which case it returns the full chunk and starts a new one with that
+/// document. Each chunk is a YAML stream on its own.
+///
+/// ```
+/// use saphyr::{SplitWriter, Yaml};
+///
+/// let mut writer = SplitWriter::new(24);
+/// let mut chunks = Vec::new();
+/// for i in 0..4 {
+///     let doc = Yaml::String(format!("event {i}"));
+///     chunks.extend(writer.write_document(&doc).unwrap());
+/// }
+/// chunks.extend(writer.finish());
+/// assert_eq!(chunks, ["---\nevent 0\n---\nevent 1\n", "---\nevent 2\n---\nevent 3\n"]);
+/// ```
+///
+/// Documents are emitted as [`StreamWriter::write_document`] writes them.
+pub struct SplitWriter {
+    /// The maximum size of a chunk, in bytes.
+    max_bytes: usize,
+    /// The chunk being filled.
+    chunk: String,
+    /// A buffer into which documents are emitted before being added to the chunk.
+    buffer: String,
+    /// Whether to use 'compact inline notation' when emitting. See [`YamlEmitter::compact`].
+    compact: bool,
+    /// Whether to emit multiline strings in literal style. See
+    /// [`YamlEmitter::multiline_strings`].
+    multiline_strings: bool,
+}
+
+impl SplitWriter {
+    /// Create a new writer splitting documents into chunks of at most `max_bytes` bytes.
+    #[must_use]
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            chunk: String::new(),
+            buffer: String::new(),
+            compact: true,
+            multiline_strings: false,
+        }
+    }
+
+    /// Set 'compact inline notation' on or off. See [`YamlEmitter::compact`].
+    pub fn compact(&mut self, compact: bool) -> &mut Self {
+        self.compact = compact;
+        self
+    }
+
+    /// Render strings containing multiple lines in literal style. See
+    /// [`YamlEmitter::multiline_strings`].
+    pub fn multiline_strings(&mut self, multiline_strings: bool) -> &mut Self {
+        self.multiline_strings = multiline_strings;
+        self
+    }
+
+    /// Add a document to the chunk being filled.
+    ///
+    /// If the document does not fit in the chunk, the chunk is returned and the document starts a
+    /// new one. Otherwise, `None` is returned.
+    ///
+    /// # Errors
+    /// Returns `EmitError` if emitting the document fails, or [`EmitError::TooLarge`] if the
+    /// document alone is larger than the limit. Nothing is added to the chunk then.
+    pub fn write_document(&mut self, doc: &Yaml) -> Result<Option<String>, EmitError> {
+        self.buffer.clear();
+        let mut emitter = YamlEmitter::new(&mut self.buffer);
+        emitter.compact(self.compact);
+        emitter.multiline_strings(self.multiline_strings);
+        emitter.dump(doc)?;
+        self.buffer.push('\n');
+
+        if self.buffer.len() > self.max_bytes {
+            return Err(EmitError::TooLarge {
+                size: self.buffer.len(),
+                max: self.max_bytes,
+            });
+        }
+        let full = if self.chunk.len() + self.buffer.len() > self.max_bytes {
+            Some(std::mem::take(&mut self.chunk))
+        } else {
+            None
+        };
+        self.chunk.push_str(&self.buffer);
+        Ok(full)
+    }
+
+    /// Return the size of the chunk being filled, in bytes.
+    #[must_use]
+    pub fn chunk_len(&self) -> usize {
+        self.chunk.len()
+    }
+
+    /// Return the chunk being filled, unless no document was added to it.
+    #[must_use]
+    pub fn finish(self) -> Option<String> {
+        Some(self.chunk).filter(|chunk| !chunk.is_empty())
+    }
+}
+
+/// The entries of a mapping being written by [`StreamWriter::write_mapping`].
+pub struct MappingSink<'a, W: io::Write> {
+    /// The writer the mapping is written with.
+    writer: &'a mut StreamWriter<W>,
+    /// The number of entries written so far.
+    entries: usize,
+}
+
+impl<W: io::Write> MappingSink<'_, W> {
+    /// Write an entry of the mapping to the sink.
+    ///
+    /// Keys are not checked for uniqueness, since previous entries are not kept.
+    ///
+    /// # Errors
+    /// Returns an [`io::Error`] if emitting the entry or writing to the sink fails.
+    pub fn entry(&mut self, key: Yaml, value: Yaml) -> io::Result<()> {
+        let mut entry = Hash::default();
+        entry.insert(key, value);
+        // A mapping with a single entry at the root is emitted as that entry would be in the
+        // whole mapping.
+        self.writer.emit(&Yaml::Hash(entry))?;
+        self.writer.buffer.push('\n');
+        let contents = self.writer.buffer.strip_prefix("---\n").unwrap_or_default();
+        self.writer.sink.write_all(contents.as_bytes())?;
+        self.entries += 1;
+        Ok(())
+    }
+
+    /// Return the number of entries written so far.
+    #[must_use]
+    pub fn entries_written(&self) -> usize {
+        self.entries
+    }
+}
+
+/// A pool of buffers to emit documents into, shared between threads.
+///
+/// Services emitting many small documents (e.g.: responses to requests) spend time growing a new
+/// `String` for each of them. The pool keeps the buffers of previously emitted documents, and
+/// reuses them without reallocating.
+///
+/// ```
+/// use std::sync::Arc;
+/// use saphyr::{EmitterPool, Yaml};
+///
+/// let pool = Arc::new(EmitterPool::new());
+/// let threads: Vec<_> = (0..4)
+///     .map(|i| {
+///         let pool = Arc::clone(&pool);
+///         std::thread::spawn(move || pool.dump(&Yaml::Integer(i)).unwrap().to_string())
+///     })
+///     .collect();
+/// for (i, thread) in threads.into_iter().enumerate() {
+///     assert_eq!(thread.join().unwrap(), format!("---\n{i}"));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct EmitterPool {
+    /// The buffers not in use.
+    buffers: Mutex<Vec<String>>,
+    /// The maximum number of buffers kept when not in use.
+    max_pooled: usize,
+    /// Whether to use 'compact inline notation' when emitting. See [`YamlEmitter::compact`].
+    compact: bool,
+    /// Whether to emit multiline strings in literal style. See
+    /// [`YamlEmitter::multiline_strings`].
+    multiline_strings: bool,
+}
+
+impl EmitterPool {
+    /// Create an empty pool.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            buffers: Mutex::new(Vec::new()),
+            max_pooled: 16,
+            compact: true,
+            multiline_strings: false,
+        }
+    }
+
+    /// Set the maximum number of buffers kept when not in use (defaults to 16).
+    ///
+    /// Buffers returned to a full pool are dropped.
+    pub fn max_pooled(&mut self, max_pooled: usize) -> &mut Self {
+        self.max_pooled = max_pooled;
+        self
+    }
+
+    /// Set 'compact inline notation' on or off. See [`YamlEmitter::compact`].
+    pub fn compact(&mut self, compact: bool) -> &mut Self {
+        self.compact = compact;
+        self
+    }
+
+    /// Render strings containing multiple lines in literal style. See
+    /// [`YamlEmitter::multiline_strings`].
+    pub fn multiline_strings(&mut self, multiline_strings: bool) -> &mut Self {
+        self.multiline_strings = multiline_strings;
+        self
+    }
+
+    /// Emit `doc` into a buffer of the pool, as [`YamlEmitter::dump`] does.
+    ///
+    /// The buffer goes back to the pool when the returned [`PooledBuffer`] is dropped.
+    ///
+    /// # Errors
+    /// Returns `EmitError` when emitting fails. The buffer goes back to the pool.
+    pub fn dump(&self, doc: &Yaml) -> Result<PooledBuffer<'_>, EmitError> {
+        let mut buffer = PooledBuffer {
+            pool: self,
+            buffer: self.take_buffer(),
+        };
+        let mut emitter = YamlEmitter::new(&mut buffer.buffer);
+        emitter.compact(self.compact);
+        emitter.multiline_strings(self.multiline_strings);
+        emitter.dump(doc)?;
+        Ok(buffer)
+    }
+
+    /// Return the number of buffers not in use.
+    #[must_use]
+    pub fn pooled(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Take a buffer from the pool, or a new one if the pool is empty.
+    fn take_buffer(&self) -> String {
+        self.lock().pop().unwrap_or_default()
+    }
+
+    /// Return `buffer` to the pool, if it is not full.
+    fn return_buffer(&self, mut buffer: String) {
+        let mut buffers = self.lock();
+        if buffers.len() < self.max_pooled {
+            buffer.clear();
+            buffers.push(buffer);
+        }
+    }
+
+    /// Lock the buffers. Since they are cleared before use, a panic while the lock was held does
+    /// not leave them in an invalid state.
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<String>> {
+        self.buffers.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for EmitterPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A document emitted by an [`EmitterPool`], whose buffer goes back to the pool when dropped.
+#[derive(Debug)]
+pub struct PooledBuffer<'a> {
+    /// The pool the buffer comes from.
+    pool: &'a EmitterPool,
+    /// The emitted document.
+    buffer: String,
+}
+
+impl PooledBuffer<'_> {
+    /// Return the emitted document.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.buffer
+    }
+
+    /// Take the emitted document out of the pool.
+    #[must_use]
+    pub fn into_string(mut self) -> String {
+        std::mem::take(&mut self.buffer)
+    }
+}
+
+impl Deref for PooledBuffer<'_> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.buffer
+    }
+}
+
+impl std::fmt::Display for PooledBuffer<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.buffer)
+    }
+}
+
+impl Drop for PooledBuffer<'_> {
+    fn drop(&mut self) {
+        let buffer = std::mem::take(&mut self.buffer);
+        if buffer.capacity() > 0 {
+            self.pool.return_buffer(buffer);
+        }
+    }
+}
+
+/// A writer duplicating what is written to it into several sinks, in one pass.
+///
+/// Sinks may be [`fmt::Write`] (e.g.: a `String`) or [`io::Write`] (e.g.: a file, a socket or a
+/// checksum calculator). Emitting into a tee lets a document be saved and checksummed without
+/// emitting it twice.
+///
+/// ```
+/// use saphyr::{TeeWriter, Yaml, YamlEmitter};
+///
+/// let doc = Yaml::load_from_str("a: [1, 2]").unwrap().remove(0);
+/// let mut text = String::new();
+/// let mut bytes = Vec::new();
+/// let mut tee = TeeWriter::new();
+/// tee.fmt_sink(&mut text).io_sink(&mut bytes);
+/// YamlEmitter::new(&mut tee).dump(&doc).unwrap();
+/// drop(tee);
+///
+/// assert_eq!(text, "---\na:\n  - 1\n  - 2");
+/// assert_eq!(bytes, text.as_bytes());
+/// ```
+///
+/// Text is written to the sinks in the order they were added. If a sink fails, the text is not
+/// written to the following sinks. Since [`fmt::Error`] carries no information, the error of a
+/// failing [`io::Write`] sink is kept and can be retrieved with [`Self::take_error`].
+///
+/// The tee also implements [`io::Write`], e.g. to be the sink of a [`StreamWriter`]. Bytes
+/// written to it must then be valid UTF-8 for [`fmt::Write`] sinks to accept them, though a
+/// character may be split between writes: its first bytes are held back from [`fmt::Write`] sinks
+/// until the rest of it is written. Invalid UTF-8 is rejected before any sink is written to.
+///
+/// [`io::Write::write`] never reports a partial write: either all sinks accept the bytes, or it
+/// returns the error of the first failing sink. The sinks before that one have then accepted the
+/// bytes already, and writing them again duplicates them into those sinks.
+#[derive(Default)]
+pub struct TeeWriter<'a> {
+    /// The sinks into which we duplicate what is written.
+    sinks: Vec<TeeSink<'a>>,
+    /// The error of the last [`io::Write`] sink that failed while writing as a [`fmt::Write`].
+    error: Option<io::Error>,
+    /// The first bytes of a character split between writes as an [`io::Write`], held back from
+    /// [`fmt::Write`] sinks until the rest of it is written.
+    pending: Vec<u8>,
+}
+
+/// A sink of a [`TeeWriter`].
+enum TeeSink<'a> {
+    /// A sink accepting text.
+    Fmt(&'a mut dyn fmt::Write),
+    /// A sink accepting bytes.
+    Io(&'a mut dyn io::Write),
+}
+
+impl<'a> TeeWriter<'a> {
+    /// Create a tee without sinks, which discards what is written to it.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a sink accepting text.
+    pub fn fmt_sink(&mut self, sink: &'a mut dyn fmt::Write) -> &mut Self {
+        self.sinks.push(TeeSink::Fmt(sink));
+        self
+    }
+
+    /// Add a sink accepting bytes.
+    pub fn io_sink(&mut self, sink: &'a mut dyn io::Write) -> &mut Self {
+        self.sinks.push(TeeSink::Io(sink));
+        self
+    }
+
+    /// Return the number of sinks.
+    #[must_use]
+    pub fn sinks(&self) -> usize {
+        self.sinks.len()
+    }
+
+    /// Take the error of the last [`io::Write`] sink which failed while the tee was written to as
+    /// a [`fmt::Write`], if any.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+}
+
+impl fmt::Write for TeeWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for sink in &mut self.sinks {
+            match sink {
+                TeeSink::Fmt(sink) => sink.write_str(s)?,
+                TeeSink::Io(sink) => sink.write_all(s.as_bytes()).map_err(|e| {
+                    self.error = Some(e);
+                    fmt::Error
+                })?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl io::Write for TeeWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let bytes = if self.pending.is_empty() {
+            Cow::Borrowed(buf)
+        } else {
+            Cow::Owned([&self.pending, buf].concat())
+        };
+        let text = match std::str::from_utf8(&bytes) {
+            Ok(text) => text,
+            // The last character is incomplete: hold its bytes back.
+            Err(e) if e.error_len().is_none() => {
+                std::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default()
+            }
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+        for sink in &mut self.sinks {
+            match sink {
+                TeeSink::Fmt(sink) => sink
+                    .write_str(text)
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?,
+                TeeSink::Io(sink) => sink.write_all(buf)?,
+            }
+        }
+        self.pending = bytes[text.len()..].to_vec();
+        Ok(buf.len())
+    }
+
+    /// Flush the [`io::Write`] sinks.
+    fn flush(&mut self) -> io::Result<()> {
+        for sink in &mut self.sinks {
+            if let TeeSink::Io(sink) = sink {
+                sink.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -2,12 +2,12 @@
 
 #![allow(clippy::module_name_repetitions)]
 
-use std::{convert::TryFrom, ops::Index, ops::IndexMut};
+use std::{convert::TryFrom, ops::Index, ops::IndexMut, str::FromStr};
 
 use hashlink::LinkedHashMap;
 use saphyr_parser::{Parser, ScanError};
 
-use crate::{loader::parse_f64, YamlLoader};
+use crate::{loader::parse_real, AnchorTable, ErrorCode, LoadError, Tag, Utf8Policy, YamlLoader};
 
 /// A YAML node is stored as this `Yaml` enumeration, which provides an easy way to
 /// access your YAML document.
@@ -16,7 +16,7 @@ use crate::{loader::parse_f64, YamlLoader};
 ///
 /// ```
 /// use saphyr::Yaml;
-/// let foo = Yaml::from_str("-123"); // convert the string to the appropriate YAML type
+/// let foo = Yaml::value_from_str("-123"); // convert the string to the appropriate YAML type
 /// assert_eq!(foo.as_i64().unwrap(), -123);
 ///
 /// // iterate over an Array
@@ -25,6 +25,22 @@ use crate::{loader::parse_f64, YamlLoader};
 ///     assert!(v.as_i64().is_some());
 /// }
 /// ```
+///
+/// Integers are stored as `i64`. Rather than casting the result of [`Self::as_i64`] to a
+/// narrower type, which silently truncates values out of its range, use the accessors checking
+/// the range of the type (e.g.: [`Self::as_u16_checked`] for a port, [`Self::as_u8_checked`] for
+/// a percentage), whose errors tell the value and the expected type:
+///
+/// ```
+/// use saphyr::Yaml;
+///
+/// let doc = &Yaml::load_from_str("{port: 80, percent: 300}").unwrap()[0];
+/// assert_eq!(doc["port"].as_u16_checked(), Ok(80));
+/// assert_eq!(
+///     doc["percent"].as_u8_checked().unwrap_err().to_string(),
+///     "COERCE003: `300` is out of range for a u8"
+/// );
+/// ```
 #[derive(Clone, PartialEq, PartialOrd, Debug, Eq, Ord, Hash)]
 pub enum Yaml {
     /// Float types are stored as String and parsed on demand.
@@ -40,8 +56,15 @@ pub enum Yaml {
     Array(Array),
     /// YAML hash, can be accessed as a `LinkedHashMap`.
     ///
-    /// Insertion order will match the order of insertion into the map.
+    /// Entries are kept in order. See [`Hash`] for what this order is.
     Hash(Hash),
+    /// A YAML node with an explicit tag the loader recognizes.
+    ///
+    /// The loader only produces this variant for the YAML 1.1 collection tags: `!!set` wraps a
+    /// [`Yaml::Hash`] whose values are all [`Yaml::Null`], `!!omap` and `!!pairs` wrap a
+    /// [`Yaml::Array`] of single-entry [`Yaml::Hash`]es. See [`Yaml::as_set`] and
+    /// [`Yaml::as_pairs`].
+    Tagged(Tag, Box<Yaml>),
     /// Alias, not fully supported yet.
     Alias(usize),
     /// YAML null, e.g. `null` or `~`.
@@ -55,7 +78,161 @@ pub enum Yaml {
 /// The type contained in the `Yaml::Array` variant. This corresponds to YAML sequences.
 pub type Array = Vec<Yaml>;
 /// The type contained in the `Yaml::Hash` variant. This corresponds to YAML mappings.
-pub type Hash = LinkedHashMap<Yaml, Yaml>;
+///
+/// This is a [`LinkedHashMap`] hashing its keys with [`HashBuilder`], which it dereferences to.
+///
+/// Iterating over a mapping yields its entries in a stable order, which this crate guarantees:
+///
+///   * Loading a document keeps the order its keys are written in, and emitting a mapping
+///     writes its entries in order. A document loaded and emitted again keeps the order of its
+///     keys.
+///   * `insert` adds a new key after the others, and moves an existing key after the others.
+///     `replace` and `get_mut` change the value of an existing key where it is.
+///   * `remove` keeps the order of the other entries.
+///
+/// Use [`Yaml::reorder_key`] to move a key to another position deliberately.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Hash(LinkedHashMap<Yaml, Yaml, HashBuilder>);
+
+impl Hash {
+    /// Create an empty mapping.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty mapping with room for at least `capacity` entries.
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self(LinkedHashMap::with_capacity_and_hasher(
+            capacity,
+            HashBuilder::default(),
+        ))
+    }
+
+    /// Return the [`LinkedHashMap`] holding the entries of the mapping.
+    #[must_use]
+    pub fn into_inner(self) -> LinkedHashMap<Yaml, Yaml, HashBuilder> {
+        self.0
+    }
+}
+
+impl std::ops::Deref for Hash {
+    type Target = LinkedHashMap<Yaml, Yaml, HashBuilder>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for Hash {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl From<LinkedHashMap<Yaml, Yaml, HashBuilder>> for Hash {
+    fn from(map: LinkedHashMap<Yaml, Yaml, HashBuilder>) -> Self {
+        Self(map)
+    }
+}
+
+impl FromIterator<(Yaml, Yaml)> for Hash {
+    fn from_iter<I: IntoIterator<Item = (Yaml, Yaml)>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl Extend<(Yaml, Yaml)> for Hash {
+    fn extend<I: IntoIterator<Item = (Yaml, Yaml)>>(&mut self, iter: I) {
+        self.0.extend(iter);
+    }
+}
+
+impl IntoIterator for Hash {
+    type Item = (Yaml, Yaml);
+    type IntoIter = hashlink::linked_hash_map::IntoIter<Yaml, Yaml>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Hash {
+    type Item = (&'a Yaml, &'a Yaml);
+    type IntoIter = hashlink::linked_hash_map::Iter<'a, Yaml, Yaml>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Hash {
+    type Item = (&'a Yaml, &'a mut Yaml);
+    type IntoIter = hashlink::linked_hash_map::IterMut<'a, Yaml, Yaml>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter_mut()
+    }
+}
+
+/// The hasher used by the mappings ([`Hash`] and [`AnnotatedHash`]) of loaded documents.
+///
+/// By default, this is the standard library's [`RandomState`], using `SipHash` 1-3 with keys
+/// randomly chosen for each map. The keys of a document cannot be crafted so that they collide,
+/// which would make loading it quadratic in the number of keys. Services loading untrusted YAML
+/// are hence not vulnerable to hash flooding.
+///
+/// With the `fast-hash` feature, this is [`hashbrown`]'s default hasher instead. It is faster,
+/// but not seeded from a source of randomness and should only be used with trusted input. The
+/// type is the same either way, so enabling the feature does not break code naming it.
+///
+/// [`AnnotatedHash`]: crate::AnnotatedHash
+/// [`RandomState`]: std::collections::hash_map::RandomState
+/// [`hashbrown`]: https://docs.rs/hashbrown
+#[derive(Clone, Debug, Default)]
+pub struct HashBuilder(HashBuilderImpl);
+
+/// The hasher [`HashBuilder`] wraps, as per the `fast-hash` feature.
+#[cfg(not(feature = "fast-hash"))]
+type HashBuilderImpl = std::collections::hash_map::RandomState;
+/// The hasher [`HashBuilder`] wraps, as per the `fast-hash` feature.
+#[cfg(feature = "fast-hash")]
+type HashBuilderImpl = hashbrown::hash_map::DefaultHashBuilder;
+
+impl std::hash::BuildHasher for HashBuilder {
+    type Hasher = KeyHasher;
+
+    fn build_hasher(&self) -> KeyHasher {
+        KeyHasher(self.0.build_hasher())
+    }
+}
+
+/// The hasher [`HashBuilder`] builds for each key.
+#[derive(Clone, Debug)]
+pub struct KeyHasher(<HashBuilderImpl as std::hash::BuildHasher>::Hasher);
+
+impl std::hash::Hasher for KeyHasher {
+    fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.write(bytes);
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.0.write_u8(i);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.0.write_u64(i);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.0.write_usize(i);
+    }
+}
 
 impl Yaml {
     /// Load the given string as an array of YAML documents.
@@ -90,6 +267,44 @@ impl Yaml {
         Self::load_from_iter(source.chars())
     }
 
+    /// Load the given bytes, encoded in UTF-8, as an array of YAML documents.
+    ///
+    /// Files found in the wild sometimes contain a few bytes which are not valid UTF-8 (e.g.: a
+    /// comment saved in Latin-1). `policy` tells how to decode them. Other encodings are not
+    /// detected; see [`YamlDecoder`] for those.
+    ///
+    /// ```
+    /// use saphyr::{LoadError, Utf8Policy, Yaml};
+    ///
+    /// let bytes = b"# R\xe9sum\xe9\nname: Jos\xe9";
+    /// let docs = Yaml::load_from_bytes(bytes, Utf8Policy::Latin1Fallback).unwrap();
+    /// assert_eq!(docs[0]["name"].as_str(), Some("Jos\u{E9}"));
+    ///
+    /// let error = Yaml::load_from_bytes(bytes, Utf8Policy::Strict).unwrap_err();
+    /// assert!(matches!(error, LoadError::InvalidUtf8 { offset: 3, .. }));
+    /// ```
+    ///
+    /// A leading UTF-8 byte order mark is skipped.
+    ///
+    /// [`YamlDecoder`]: crate::YamlDecoder
+    ///
+    /// # Errors
+    /// Returns `LoadError` when decoding as per `policy` or loading fails.
+    pub fn load_from_bytes(source: &[u8], policy: Utf8Policy) -> Result<Vec<Self>, LoadError> {
+        let bom = b"\xef\xbb\xbf";
+        let Some(source) = source.strip_prefix(bom) else {
+            return YamlLoader::default().load_from_str(&policy.decode(source)?);
+        };
+        let source = policy.decode(source).map_err(|error| match error {
+            LoadError::InvalidUtf8 { offset, marker } => LoadError::InvalidUtf8 {
+                offset: offset + bom.len(),
+                marker,
+            },
+            error => error,
+        })?;
+        YamlLoader::default().load_from_str(&source)
+    }
+
     /// Load the contents of the given iterator as an array of YAML documents.
     ///
     /// See [`Self::load_from_str`] for details.
@@ -115,6 +330,39 @@ impl Yaml {
         Ok(loader.into_documents())
     }
 
+    /// Load the given string as an array of YAML documents, along with the anchors they define.
+    ///
+    /// This is similar to [`Self::load_from_str`], but also returns, for each document, an
+    /// [`AnchorTable`] which allows retrieving anchored nodes by name. This is useful to address
+    /// reusable fragments of a document:
+    ///
+    /// ```
+    /// use saphyr::Yaml;
+    ///
+    /// let docs = Yaml::load_from_str_with_anchors("
+    /// templates:
+    ///   - &build { image: rust, script: cargo build }
+    /// jobs:
+    ///   - *build
+    /// ").unwrap();
+    /// let (doc, anchors) = &docs[0];
+    /// let build = anchors.resolve("build").unwrap();
+    /// assert_eq!(build["image"].as_str(), Some("rust"));
+    /// assert_eq!(&doc["jobs"][0], build);
+    /// ```
+    ///
+    /// Anchors are looked up in the table rather than through the document: a `Yaml` is a plain
+    /// value, which has nowhere to keep the names of its anchors, and aliases are replaced with a
+    /// copy of the node they refer to.
+    ///
+    /// # Errors
+    /// Returns `LoadError` when loading fails.
+    pub fn load_from_str_with_anchors(
+        source: &str,
+    ) -> Result<Vec<(Self, AnchorTable<Self>)>, LoadError> {
+        YamlLoader::default().load_from_str_with_anchors(source)
+    }
+
     define_as!(as_bool, bool, Boolean);
     define_as!(as_i64, i64, Integer);
 
@@ -140,6 +388,7 @@ impl Yaml {
     define_is!(is_null, Self::Null);
     define_is!(is_real, Self::Real(_));
     define_is!(is_string, Self::String(_));
+    define_is!(is_tagged, Self::Tagged(..));
 
     /// Return the `f64` value contained in this YAML node.
     ///
@@ -148,7 +397,7 @@ impl Yaml {
     #[must_use]
     pub fn as_f64(&self) -> Option<f64> {
         if let Yaml::Real(ref v) = self {
-            parse_f64(v)
+            parse_real(v)
         } else {
             None
         }
@@ -163,6 +412,471 @@ impl Yaml {
         self.as_f64()
     }
 
+    /// Create a `!!set` node containing the given items.
+    ///
+    /// ```
+    /// use saphyr::{Yaml, YamlEmitter};
+    ///
+    /// let set = Yaml::new_set([Yaml::String("a".into()), Yaml::String("b".into())]);
+    /// let mut output = String::new();
+    /// YamlEmitter::new(&mut output).dump(&set).unwrap();
+    /// assert_eq!(output, "---\n!!set\n? a\n? b");
+    /// ```
+    #[must_use]
+    pub fn new_set<I: IntoIterator<Item = Yaml>>(items: I) -> Self {
+        let hash = items.into_iter().map(|item| (item, Yaml::Null)).collect();
+        Yaml::Tagged(Tag::core("set"), Box::new(Yaml::Hash(hash)))
+    }
+
+    /// Create a `!!omap` node containing the given key-value pairs, in order.
+    ///
+    /// Keys of an ordered map must be unique. This is not checked here, but the loader rejects
+    /// documents with duplicate keys in an `!!omap`.
+    #[must_use]
+    pub fn new_omap<I: IntoIterator<Item = (Yaml, Yaml)>>(pairs: I) -> Self {
+        Yaml::Tagged(Tag::core("omap"), Box::new(Self::pair_list(pairs)))
+    }
+
+    /// Create a `!!pairs` node containing the given key-value pairs, in order.
+    ///
+    /// Unlike with [`Yaml::new_omap`], keys may appear more than once.
+    #[must_use]
+    pub fn new_pairs<I: IntoIterator<Item = (Yaml, Yaml)>>(pairs: I) -> Self {
+        Yaml::Tagged(Tag::core("pairs"), Box::new(Self::pair_list(pairs)))
+    }
+
+    /// Build the sequence of single-entry mappings representing `!!omap` and `!!pairs`.
+    fn pair_list<I: IntoIterator<Item = (Yaml, Yaml)>>(pairs: I) -> Self {
+        Yaml::Array(
+            pairs
+                .into_iter()
+                .map(|(key, value)| {
+                    let mut hash = Hash::default();
+                    hash.insert(key, value);
+                    Yaml::Hash(hash)
+                })
+                .collect(),
+        )
+    }
+
+    /// Return the tag and the inner node if `self` is a [`Yaml::Tagged`].
+    #[must_use]
+    pub fn as_tagged(&self) -> Option<(&Tag, &Yaml)> {
+        match self {
+            Yaml::Tagged(tag, node) => Some((tag, node)),
+            _ => None,
+        }
+    }
+
+    /// Return the node without its tags, if any.
+    #[must_use]
+    pub fn untagged(&self) -> &Yaml {
+        match self {
+            Yaml::Tagged(_, node) => node.untagged(),
+            node => node,
+        }
+    }
+
+    /// Return a copy of `self` reduced to its data: without tags, at any depth.
+    ///
+    /// This gives a minimal document to compare with others, or to give to tools which reject
+    /// tags. Comments and anchors are already dropped when loading, which inlines the node of
+    /// each alias: emitting the returned node writes a document with neither comments, anchors,
+    /// aliases nor tags. The few [`Yaml::Alias`] nodes left (e.g.: built by hand), whose node is
+    /// unknown, become [`Yaml::BadValue`]s, as unknown aliases are when loading.
+    ///
+    /// Keys which become equal once their tags are dropped (e.g.: `!a 1` and `1`) are merged,
+    /// with the value of the last one.
+    ///
+    /// ```
+    /// use saphyr::{Yaml, YamlEmitter};
+    ///
+    /// let doc = Yaml::load_from_str("\
+    /// base: &base !config {retries: 3}
+    /// job: *base
+    /// tags: !!set {a, b}
+    /// ").unwrap().remove(0);
+    /// let normalized = doc.normalized();
+    /// assert!(normalized["tags"].as_tagged().is_none());
+    ///
+    /// let mut output = String::new();
+    /// YamlEmitter::new(&mut output).dump(&normalized).unwrap();
+    /// assert_eq!(output, "---\nbase:\n  retries: 3\njob:\n  retries: 3\ntags:\n  a: ~\n  b: ~");
+    /// ```
+    #[must_use]
+    pub fn normalized(&self) -> Yaml {
+        match self.untagged() {
+            Yaml::Array(items) => Yaml::Array(items.iter().map(Yaml::normalized).collect()),
+            Yaml::Hash(hash) => Yaml::Hash(
+                hash.iter()
+                    .map(|(key, value)| (key.normalized(), value.normalized()))
+                    .collect(),
+            ),
+            Yaml::Alias(_) => Yaml::BadValue,
+            node => node.clone(),
+        }
+    }
+
+    /// Return the items of a `!!set` node.
+    ///
+    /// The items are the keys of the returned [`Hash`]. All its values are [`Yaml::Null`].
+    ///
+    /// ```
+    /// use saphyr::Yaml;
+    ///
+    /// let docs = Yaml::load_from_str("!!set { a, b }").unwrap();
+    /// let set = docs[0].as_set().unwrap();
+    /// assert!(set.contains_key(&Yaml::String("a".into())));
+    /// assert_eq!(set.len(), 2);
+    /// ```
+    #[must_use]
+    pub fn as_set(&self) -> Option<&Hash> {
+        match self {
+            Yaml::Tagged(tag, node) if tag.is_core("set") => node.as_hash(),
+            _ => None,
+        }
+    }
+
+    /// Return the key-value pairs of an `!!omap` or `!!pairs` node, in order.
+    ///
+    /// ```
+    /// use saphyr::Yaml;
+    ///
+    /// let docs = Yaml::load_from_str("!!pairs [ a: 1, b: 2, a: 3 ]").unwrap();
+    /// let pairs = docs[0].as_pairs().unwrap();
+    /// assert_eq!(pairs.len(), 3);
+    /// assert_eq!(pairs[2].0.as_str(), Some("a"));
+    /// assert_eq!(pairs[2].1.as_i64(), Some(3));
+    /// ```
+    #[must_use]
+    pub fn as_pairs(&self) -> Option<Vec<(&Yaml, &Yaml)>> {
+        match self {
+            Yaml::Tagged(tag, node) if tag.is_core("omap") || tag.is_core("pairs") => node
+                .as_vec()?
+                .iter()
+                .map(|item| item.as_hash()?.iter().next())
+                .collect(),
+            _ => None,
+        }
+    }
+
+    /// Return the node at `path`, or why it could not be reached.
+    ///
+    /// Unlike indexing, which yields [`Yaml::BadValue`] if anything goes wrong, this reports the
+    /// segment of `path` at which the lookup failed, so that users can be told what is wrong with
+    /// their documents. Each segment of `path` is a key of a mapping, or the index of an item of a
+    /// sequence. Segments written as integers also match integer keys of mappings.
+    ///
+    /// ```
+    /// use saphyr::Yaml;
+    ///
+    /// let docs = Yaml::load_from_str("a: {b: [1, 2]}").unwrap();
+    /// assert_eq!(docs[0].lookup(&["a", "b", "1"]).unwrap().as_i64(), Some(2));
+    /// let error = docs[0].lookup(&["a", "b", "c"]).unwrap_err();
+    /// assert_eq!(error.to_string(), "LOOKUP003: expected mapping at `a.b`, found sequence");
+    /// ```
+    ///
+    /// # Errors
+    /// Returns a [`LookupError`] if a segment of `path` is missing from the node it applies to,
+    /// or if that node is not a collection allowing it.
+    pub fn lookup(&self, path: &[&str]) -> Result<&Yaml, LookupError> {
+        let mut node = self;
+        for (depth, segment) in path.iter().enumerate() {
+            let error = |kind| LookupError {
+                path: path[..depth].iter().map(ToString::to_string).collect(),
+                segment: (*segment).to_string(),
+                kind,
+            };
+            let index = segment.parse::<usize>().ok();
+            node = match (node.untagged(), index) {
+                (Yaml::Hash(hash), _) => hash
+                    .get(&Yaml::String((*segment).to_string()))
+                    .or_else(|| hash.get(&Yaml::Integer(segment.parse().ok()?)))
+                    .ok_or_else(|| error(LookupErrorKind::MissingKey))?,
+                (Yaml::Array(items), Some(index)) => items
+                    .get(index)
+                    .ok_or_else(|| error(LookupErrorKind::IndexOutOfRange(items.len())))?,
+                (found, index) => {
+                    return Err(error(LookupErrorKind::TypeMismatch {
+                        expected: if index.is_some() {
+                            "sequence"
+                        } else {
+                            "mapping"
+                        },
+                        found: found.type_name(),
+                    }))
+                }
+            };
+        }
+        Ok(node)
+    }
+
+    /// Return the node at `path` within `self`, to mutate it. See [`Self::lookup`].
+    pub(crate) fn lookup_mut(&mut self, path: &[&str]) -> Result<&mut Yaml, LookupError> {
+        let mut node = self;
+        for (depth, segment) in path.iter().enumerate() {
+            node = child_mut(node, segment).map_err(|kind| LookupError {
+                path: path[..depth].iter().map(ToString::to_string).collect(),
+                segment: (*segment).to_string(),
+                kind,
+            })?;
+        }
+        Ok(node)
+    }
+
+    /// Return the paths of all nodes within `self`, with the nodes, depth first and in order.
+    ///
+    /// Paths are normalized as in `a.b[0].c`: keys of mappings are joined with `.`, and indices of
+    /// sequences are written in brackets. Keys containing whitespace, `.`, `[`, `]` or `"`, or
+    /// which are empty, are written quoted in brackets (e.g.: `a["b.c"]`), and keys which are not
+    /// scalars are written `[?]`. Scalar keys which are not strings are written as their text.
+    /// `self` itself, whose path would be empty, is not returned.
+    ///
+    /// ```
+    /// use saphyr::Yaml;
+    ///
+    /// let docs = Yaml::load_from_str("a: {b: [{c: 1}]}\n\"d.e\": true").unwrap();
+    /// let paths: Vec<_> = docs[0]
+    ///     .paths()
+    ///     .map(|entry| format!("{} {}", entry.path, entry.kind))
+    ///     .collect();
+    /// assert_eq!(paths, [
+    ///     "a mapping",
+    ///     "a.b sequence",
+    ///     "a.b[0] mapping",
+    ///     "a.b[0].c integer",
+    ///     "[\"d.e\"] boolean",
+    /// ]);
+    /// ```
+    #[must_use]
+    pub fn paths(&self) -> YamlPaths<'_> {
+        let mut paths = YamlPaths { stack: Vec::new() };
+        paths.push_children("", self);
+        paths
+    }
+
+    /// Move the key at `path` to position `index` among the keys of its mapping, shifting the
+    /// keys between its former and new positions.
+    ///
+    /// The last segment of `path` is the key, and the others lead to its mapping, as with
+    /// [`Yaml::lookup`]. The value of the key moves along with it.
+    ///
+    /// ```
+    /// use saphyr::Yaml;
+    ///
+    /// let mut doc = Yaml::load_from_str("db: {user: admin, host: localhost, port: 5432}")
+    ///     .unwrap()
+    ///     .remove(0);
+    /// doc.reorder_key(&["db", "host"], 0).unwrap();
+    /// let keys: Vec<_> = doc["db"].as_hash().unwrap().keys().filter_map(Yaml::as_str).collect();
+    /// assert_eq!(keys, ["host", "user", "port"]);
+    /// ```
+    ///
+    /// # Errors
+    /// Returns a [`LookupError`] if `path` is empty or cannot be looked up, if its last segment is
+    /// not a key of a mapping, or if `index` is not less than the number of keys of the mapping.
+    /// `self` is left unchanged then.
+    pub fn reorder_key(&mut self, path: &[&str], index: usize) -> Result<(), LookupError> {
+        let Some((last, parents)) = path.split_last() else {
+            return Err(LookupError {
+                path: vec![],
+                segment: String::new(),
+                kind: LookupErrorKind::MissingSegment,
+            });
+        };
+        let hash = match untagged_mut(self.lookup_mut(parents)?) {
+            Yaml::Hash(hash) => hash,
+            found => {
+                return Err(LookupError {
+                    path: parents.iter().map(ToString::to_string).collect(),
+                    segment: (*last).to_string(),
+                    kind: LookupErrorKind::TypeMismatch {
+                        expected: "mapping",
+                        found: found.type_name(),
+                    },
+                })
+            }
+        };
+        let key = Yaml::String((*last).to_owned());
+        let position = hash.keys().position(|other| *other == key).or_else(|| {
+            let key = Yaml::Integer(last.parse().ok()?);
+            hash.keys().position(|other| *other == key)
+        });
+        let Some(position) = position else {
+            return Err(LookupError {
+                path: parents.iter().map(ToString::to_string).collect(),
+                segment: (*last).to_string(),
+                kind: LookupErrorKind::MissingKey,
+            });
+        };
+        if index >= hash.len() {
+            return Err(LookupError {
+                path: parents.iter().map(ToString::to_string).collect(),
+                segment: index.to_string(),
+                kind: LookupErrorKind::IndexOutOfRange(hash.len()),
+            });
+        }
+        let mut entries: Vec<(Yaml, Yaml)> = std::mem::take(hash).into_iter().collect();
+        let entry = entries.remove(position);
+        entries.insert(index, entry);
+        hash.extend(entries);
+        Ok(())
+    }
+
+    /// Append `item` to the sequence `self`.
+    ///
+    /// ```
+    /// use saphyr::Yaml;
+    ///
+    /// let mut doc = Yaml::load_from_str("[3, 1]").unwrap().remove(0);
+    /// doc.push(Yaml::Integer(2)).unwrap();
+    /// doc.sort_by(|a, b| a.as_i64().cmp(&b.as_i64())).unwrap();
+    /// assert_eq!(doc, Yaml::load_from_str("[1, 2, 3]").unwrap()[0]);
+    /// assert!(Yaml::Null.push(Yaml::Integer(1)).is_err());
+    /// ```
+    ///
+    /// # Errors
+    /// Returns a [`TypeError`] if `self` is not a sequence.
+    pub fn push(&mut self, item: Yaml) -> Result<(), TypeError> {
+        self.sequence_mut()?.push(item);
+        Ok(())
+    }
+
+    /// Insert `item` at position `index` in the sequence `self`, shifting the items after it.
+    ///
+    /// # Errors
+    /// Returns a [`TypeError`] if `self` is not a sequence.
+    ///
+    /// # Panics
+    /// This function panics if `index` is greater than the length of the sequence.
+    pub fn insert(&mut self, index: usize, item: Yaml) -> Result<(), TypeError> {
+        self.sequence_mut()?.insert(index, item);
+        Ok(())
+    }
+
+    /// Remove and return the item at position `index` in the sequence `self`, shifting the items
+    /// after it.
+    ///
+    /// # Errors
+    /// Returns a [`TypeError`] if `self` is not a sequence.
+    ///
+    /// # Panics
+    /// This function panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> Result<Yaml, TypeError> {
+        Ok(self.sequence_mut()?.remove(index))
+    }
+
+    /// Keep only the items of the sequence `self` for which `f` returns `true`.
+    ///
+    /// # Errors
+    /// Returns a [`TypeError`] if `self` is not a sequence.
+    pub fn retain<F: FnMut(&Yaml) -> bool>(&mut self, f: F) -> Result<(), TypeError> {
+        self.sequence_mut()?.retain(f);
+        Ok(())
+    }
+
+    /// Sort the items of the sequence `self` with the comparator `compare`. The sort is stable.
+    ///
+    /// # Errors
+    /// Returns a [`TypeError`] if `self` is not a sequence.
+    pub fn sort_by<F: FnMut(&Yaml, &Yaml) -> std::cmp::Ordering>(
+        &mut self,
+        compare: F,
+    ) -> Result<(), TypeError> {
+        self.sequence_mut()?.sort_by(compare);
+        Ok(())
+    }
+
+    /// Return the items of `self` if it is a sequence, looking through tags.
+    fn sequence_mut(&mut self) -> Result<&mut Array, TypeError> {
+        match self {
+            Yaml::Array(items) => Ok(items),
+            Yaml::Tagged(_, node) => node.sequence_mut(),
+            node => Err(TypeError {
+                expected: "sequence",
+                found: node.type_name(),
+            }),
+        }
+    }
+
+    /// Return the name of the type of the node, as used in error messages (e.g.: `mapping`).
+    ///
+    /// Tagged nodes have the type of the node they wrap.
+    #[must_use]
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Yaml::Real(_) => "real",
+            Yaml::Integer(_) => "integer",
+            Yaml::String(_) => "string",
+            Yaml::Boolean(_) => "boolean",
+            Yaml::Array(_) => "sequence",
+            Yaml::Hash(_) => "mapping",
+            Yaml::Tagged(_, node) => node.type_name(),
+            Yaml::Alias(_) => "alias",
+            Yaml::Null => "null",
+            Yaml::BadValue => "bad value",
+        }
+    }
+
+    /// Return the value for the string key `key` if `self` is a mapping, ignoring case.
+    ///
+    /// A key matching exactly is preferred. Otherwise, the first key (in insertion order) equal to
+    /// `key` once both are lowercased is used. The mapping keeps its keys as they are written.
+    ///
+    /// ```
+    /// use saphyr::Yaml;
+    ///
+    /// let docs = Yaml::load_from_str("Path: C:\\Windows\npath: /usr").unwrap();
+    /// assert_eq!(docs[0].get_ci("PATH").and_then(Yaml::as_str), Some("C:\\Windows"));
+    /// assert_eq!(docs[0].get_ci("path").and_then(Yaml::as_str), Some("/usr"));
+    /// assert!(docs[0].get_ci("home").is_none());
+    /// ```
+    #[must_use]
+    pub fn get_ci(&self, key: &str) -> Option<&Yaml> {
+        let hash = self.untagged().as_hash()?;
+        hash.get(&Yaml::String(key.to_owned())).or_else(|| {
+            hash.iter()
+                .find(|(k, _)| k.as_str().is_some_and(|k| eq_ignore_case(k, key)))
+                .map(|(_, v)| v)
+        })
+    }
+
+    /// Move the node out of `self`, leaving [`Yaml::Null`] in its place.
+    ///
+    /// This allows transforming trees without cloning their nodes. Unlike
+    /// [`LoadableYamlNode::take`], which leaves a [`Yaml::BadValue`], the node is left valid. This
+    /// method takes precedence over the latter on a `Yaml`, which must then be called as
+    /// `LoadableYamlNode::take(&mut node)`.
+    ///
+    /// ```
+    /// use saphyr::Yaml;
+    ///
+    /// let mut doc = Yaml::load_from_str("a: [1, 2]").unwrap().remove(0);
+    /// let a = doc["a"].take();
+    /// assert_eq!(a, Yaml::load_from_str("[1, 2]").unwrap()[0]);
+    /// assert!(doc["a"].is_null());
+    /// ```
+    ///
+    /// [`LoadableYamlNode::take`]: crate::LoadableYamlNode::take
+    #[must_use]
+    pub fn take(&mut self) -> Yaml {
+        self.replace(Yaml::Null)
+    }
+
+    /// Replace the node in `self` with `node`, returning the previous one.
+    ///
+    /// ```
+    /// use saphyr::Yaml;
+    ///
+    /// let mut doc = Yaml::load_from_str("a: 1").unwrap().remove(0);
+    /// assert_eq!(doc["a"].replace(Yaml::Integer(2)), Yaml::Integer(1));
+    /// assert_eq!(doc["a"], Yaml::Integer(2));
+    /// ```
+    #[must_use = "if the previous node is not needed, assign the new one instead"]
+    pub fn replace(&mut self, node: Yaml) -> Yaml {
+        std::mem::replace(self, node)
+    }
+
     /// If a value is null or otherwise bad (see variants), consume it and
     /// replace it with a given value `other`. Otherwise, return self unchanged.
     ///
@@ -192,27 +906,27 @@ impl Yaml {
     }
 }
 
-#[allow(clippy::should_implement_trait)]
 impl Yaml {
     /// Convert a string to a [`Yaml`] node.
     ///
-    /// [`Yaml`] does not implement [`std::str::FromStr`] since conversion may not fail. This
-    /// function falls back to [`Yaml::String`] if nothing else matches.
+    /// Unlike [`str::parse`], which loads a YAML document (see the [`FromStr`] implementation),
+    /// this only resolves the type of a plain scalar, and may not fail. This function falls back to
+    /// [`Yaml::String`] if nothing else matches.
     ///
     /// # Examples
     /// ```
     /// # use saphyr::Yaml;
-    /// assert!(matches!(Yaml::from_str("42"), Yaml::Integer(42)));
-    /// assert!(matches!(Yaml::from_str("0x2A"), Yaml::Integer(42)));
-    /// assert!(matches!(Yaml::from_str("0o52"), Yaml::Integer(42)));
-    /// assert!(matches!(Yaml::from_str("~"), Yaml::Null));
-    /// assert!(matches!(Yaml::from_str("null"), Yaml::Null));
-    /// assert!(matches!(Yaml::from_str("true"), Yaml::Boolean(true)));
-    /// assert!(matches!(Yaml::from_str("3.14"), Yaml::Real(_)));
-    /// assert!(matches!(Yaml::from_str("foo"), Yaml::String(_)));
+    /// assert!(matches!(Yaml::value_from_str("42"), Yaml::Integer(42)));
+    /// assert!(matches!(Yaml::value_from_str("0x2A"), Yaml::Integer(42)));
+    /// assert!(matches!(Yaml::value_from_str("0o52"), Yaml::Integer(42)));
+    /// assert!(matches!(Yaml::value_from_str("~"), Yaml::Null));
+    /// assert!(matches!(Yaml::value_from_str("null"), Yaml::Null));
+    /// assert!(matches!(Yaml::value_from_str("true"), Yaml::Boolean(true)));
+    /// assert!(matches!(Yaml::value_from_str("3.14"), Yaml::Real(_)));
+    /// assert!(matches!(Yaml::value_from_str("foo"), Yaml::String(_)));
     /// ```
     #[must_use]
-    pub fn from_str(v: &str) -> Yaml {
+    pub fn value_from_str(v: &str) -> Yaml {
         if let Some(number) = v.strip_prefix("0x") {
             if let Ok(i) = i64::from_str_radix(number, 16) {
                 return Yaml::Integer(i);
@@ -233,7 +947,7 @@ impl Yaml {
             _ => {
                 if let Ok(integer) = v.parse::<i64>() {
                     Yaml::Integer(integer)
-                } else if parse_f64(v).is_some() {
+                } else if parse_real(v).is_some() {
                     Yaml::Real(v.to_owned())
                 } else {
                     Yaml::String(v.to_owned())
@@ -241,6 +955,210 @@ impl Yaml {
             }
         }
     }
+
+    /// Build a sequence of mappings from tabular data, one mapping per row.
+    ///
+    /// Each mapping associates each header with the cell of the row in the same column. Cells are
+    /// converted with [`Yaml::value_from_str`], except for empty cells (and cells missing at the
+    /// end of short rows), which become [`Yaml::Null`]. Cells past the last header are ignored.
+    ///
+    /// ```
+    /// use saphyr::Yaml;
+    ///
+    /// let rows = [["alice", "30"], ["bob", ""]];
+    /// let yaml = Yaml::from_rows(&["name", "age"], rows);
+    /// assert_eq!(yaml[0]["name"].as_str(), Some("alice"));
+    /// assert_eq!(yaml[0]["age"].as_i64(), Some(30));
+    /// assert!(yaml[1]["age"].is_null());
+    /// ```
+    pub fn from_rows<H, R, C>(headers: &[H], rows: R) -> Yaml
+    where
+        H: AsRef<str>,
+        R: IntoIterator,
+        R::Item: IntoIterator<Item = C>,
+        C: AsRef<str>,
+    {
+        Yaml::Array(
+            rows.into_iter()
+                .map(|row| {
+                    let mut cells = row.into_iter();
+                    let mut hash = Hash::default();
+                    for header in headers {
+                        let cell = match cells.next() {
+                            Some(cell) if !cell.as_ref().is_empty() => {
+                                Yaml::value_from_str(cell.as_ref())
+                            }
+                            _ => Yaml::Null,
+                        };
+                        hash.insert(Yaml::String(header.as_ref().to_owned()), cell);
+                    }
+                    Yaml::Hash(hash)
+                })
+                .collect(),
+        )
+    }
+
+    /// Convert a sequence of mappings into tabular data, as headers and rows of cells.
+    ///
+    /// This is the inverse of [`Yaml::from_rows`]. Headers are the keys of the mappings, in the
+    /// order in which they first appear. Cells are the text of the values, or empty if the value is
+    /// null or missing from the mapping of the row. Strings looking like other scalars (e.g.: `"1"`)
+    /// are written like them, so their type is not preserved.
+    ///
+    /// Return `None` if `self` is not a sequence of mappings whose keys are strings and whose values
+    /// are scalars.
+    ///
+    /// ```
+    /// use saphyr::Yaml;
+    ///
+    /// let docs = Yaml::load_from_str("[{name: alice, age: 30}, {name: bob, admin: true}]").unwrap();
+    /// let (headers, rows) = docs[0].to_rows().unwrap();
+    /// assert_eq!(headers, ["name", "age", "admin"]);
+    /// assert_eq!(rows, [["alice", "30", ""], ["bob", "", "true"]]);
+    /// ```
+    #[must_use]
+    pub fn to_rows(&self) -> Option<(Vec<String>, Vec<Vec<String>>)> {
+        let items = self.untagged().as_vec()?;
+        let mut headers = Vec::<String>::new();
+        for item in items {
+            for key in item.untagged().as_hash()?.keys() {
+                let key = key.untagged().as_str()?;
+                if !headers.iter().any(|header| header == key) {
+                    headers.push(key.to_owned());
+                }
+            }
+        }
+        let rows = items
+            .iter()
+            .map(|item| {
+                headers
+                    .iter()
+                    .map(|header| match item[header.as_str()].untagged() {
+                        Yaml::String(v) | Yaml::Real(v) => Some(v.clone()),
+                        Yaml::Integer(v) => Some(v.to_string()),
+                        Yaml::Boolean(v) => Some(v.to_string()),
+                        Yaml::Null | Yaml::BadValue => Some(String::new()),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .collect::<Option<_>>()?;
+        Some((headers, rows))
+    }
+}
+
+/// The reason a [`Yaml::lookup`], or a getter of a [`ConfigStack`], failed.
+///
+/// [`ConfigStack`]: crate::ConfigStack
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LookupError {
+    /// The segments of the path leading to the node at which the lookup failed.
+    pub path: Vec<String>,
+    /// The segment which could not be looked up in that node. Empty if the node was found, but is
+    /// not of the type a getter returns.
+    pub segment: String,
+    /// Why the segment could not be looked up.
+    pub kind: LookupErrorKind,
+}
+
+/// The kinds of [`LookupError`]s.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LookupErrorKind {
+    /// The node is a mapping without the segment as a key.
+    MissingKey,
+    /// The node is a sequence with fewer items than the segment requires. This holds its length.
+    IndexOutOfRange(usize),
+    /// The node is not of the type the segment, or the getter, requires.
+    TypeMismatch {
+        /// The type the segment requires (`mapping` or `sequence`), or the getter returns.
+        expected: &'static str,
+        /// The type of the node. See [`Yaml::type_name`].
+        found: &'static str,
+    },
+    /// The path has no segment, where the operation requires one (e.g.: the key of
+    /// [`Yaml::reorder_key`]).
+    MissingSegment,
+}
+
+impl LookupError {
+    /// Return the stable code of the error, which [`Display`] prefixes the message with.
+    ///
+    /// [`Display`]: std::fmt::Display
+    #[must_use]
+    pub fn code(&self) -> ErrorCode {
+        match self.kind {
+            LookupErrorKind::MissingKey => ErrorCode::new("LOOKUP001"),
+            LookupErrorKind::IndexOutOfRange(_) => ErrorCode::new("LOOKUP002"),
+            LookupErrorKind::TypeMismatch { .. } => ErrorCode::new("LOOKUP003"),
+            LookupErrorKind::MissingSegment => ErrorCode::new("LOOKUP004"),
+        }
+    }
+}
+
+impl std::error::Error for LookupError {}
+
+impl std::fmt::Display for LookupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.code())?;
+        let at = if self.path.is_empty() {
+            "at the root".to_string()
+        } else {
+            format!("at `{}`", self.path.join("."))
+        };
+        match self.kind {
+            LookupErrorKind::MissingKey => write!(f, "missing key `{}` {at}", self.segment),
+            LookupErrorKind::IndexOutOfRange(len) => write!(
+                f,
+                "index {} is out of range {at}, which has {len} items",
+                self.segment
+            ),
+            LookupErrorKind::TypeMismatch { expected, found } => {
+                write!(f, "expected {expected} {at}, found {found}")
+            }
+            LookupErrorKind::MissingSegment => write!(f, "missing a segment {at}"),
+        }
+    }
+}
+
+/// An operation required a node of another type (e.g.: [`Yaml::push`] on a mapping).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TypeError {
+    /// The type the operation requires. See [`Yaml::type_name`].
+    pub expected: &'static str,
+    /// The type of the node. See [`Yaml::type_name`].
+    pub found: &'static str,
+}
+
+impl TypeError {
+    /// Return the stable code of the error, which [`Display`] prefixes the message with.
+    ///
+    /// [`Display`]: std::fmt::Display
+    #[must_use]
+    #[allow(clippy::unused_self)]
+    pub fn code(&self) -> ErrorCode {
+        ErrorCode::new("TYPE001")
+    }
+}
+
+impl std::error::Error for TypeError {}
+
+impl std::fmt::Display for TypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: expected {}, found {}",
+            self.code(),
+            self.expected,
+            self.found
+        )
+    }
+}
+
+/// Return whether `a` and `b` are equal once lowercased.
+fn eq_ignore_case(a: &str, b: &str) -> bool {
+    a.chars()
+        .flat_map(char::to_lowercase)
+        .eq(b.chars().flat_map(char::to_lowercase))
 }
 
 static BAD_VALUE: Yaml = Yaml::BadValue;
@@ -249,7 +1167,7 @@ impl<'a> Index<&'a str> for Yaml {
 
     fn index(&self, idx: &'a str) -> &Yaml {
         let key = Yaml::String(idx.to_owned());
-        match self.as_hash() {
+        match self.untagged().as_hash() {
             Some(h) => h.get(&key).unwrap_or(&BAD_VALUE),
             None => &BAD_VALUE,
         }
@@ -264,6 +1182,9 @@ impl<'a> IndexMut<&'a str> for Yaml {
     ///
     /// This function also panics if `self` is not a [`Yaml::Hash`].
     fn index_mut(&mut self, idx: &'a str) -> &mut Yaml {
+        if let Yaml::Tagged(_, node) = self {
+            return node.index_mut(idx);
+        }
         let key = Yaml::String(idx.to_owned());
         match self.as_mut_hash() {
             Some(h) => h.get_mut(&key).unwrap(),
@@ -276,11 +1197,15 @@ impl Index<usize> for Yaml {
     type Output = Yaml;
 
     fn index(&self, idx: usize) -> &Yaml {
-        if let Some(v) = self.as_vec() {
+        let node = self.untagged();
+        if let Some(v) = node.as_vec() {
             v.get(idx).unwrap_or(&BAD_VALUE)
-        } else if let Some(v) = self.as_hash() {
-            let key = Yaml::Integer(i64::try_from(idx).unwrap());
-            v.get(&key).unwrap_or(&BAD_VALUE)
+        } else if let Some(v) = node.as_hash() {
+            // Indices past `i64::MAX` cannot be keys.
+            i64::try_from(idx)
+                .ok()
+                .and_then(|key| v.get(&Yaml::Integer(key)))
+                .unwrap_or(&BAD_VALUE)
         } else {
             &BAD_VALUE
         }
@@ -300,15 +1225,71 @@ impl IndexMut<usize> for Yaml {
     fn index_mut(&mut self, idx: usize) -> &mut Yaml {
         match self {
             Yaml::Array(sequence) => sequence.index_mut(idx),
-            Yaml::Hash(mapping) => {
-                let key = Yaml::Integer(i64::try_from(idx).unwrap());
-                mapping.get_mut(&key).unwrap()
-            }
+            // Indices past `i64::MAX` cannot be keys.
+            Yaml::Hash(mapping) => i64::try_from(idx)
+                .ok()
+                .and_then(|key| mapping.get_mut(&Yaml::Integer(key)))
+                .unwrap_or_else(|| panic!("{idx}: key not found in the mapping")),
+            Yaml::Tagged(_, node) => node.index_mut(idx),
             _ => panic!("Attempting to index but `self` is not a sequence nor a mapping"),
         }
     }
 }
 
+/// Load a string holding a single YAML document.
+///
+/// ```
+/// use saphyr::{LoadError, Yaml};
+///
+/// let doc: Yaml = "{ name: saphyr, version: 0.8 }".parse().unwrap();
+/// assert_eq!(doc["name"].as_str(), Some("saphyr"));
+/// assert_eq!("".parse::<Yaml>().unwrap(), Yaml::Null);
+/// assert!(matches!("a\n---\nb".parse::<Yaml>(), Err(LoadError::TooManyDocuments(_))));
+/// ```
+///
+/// An empty stream loads as [`Yaml::Null`]. A stream with several documents makes loading fail
+/// with [`LoadError::TooManyDocuments`]; use [`Yaml::load_from_str`] to load those.
+impl FromStr for Yaml {
+    type Err = LoadError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut loader = YamlLoader::default();
+        loader.max_documents(1);
+        Ok(loader.load_from_str(source)?.pop().unwrap_or(Yaml::Null))
+    }
+}
+
+/// Collect nodes into a [`Yaml::Array`].
+///
+/// ```
+/// use saphyr::Yaml;
+///
+/// let doc: Yaml = (1..=3).map(Yaml::Integer).collect();
+/// assert_eq!(doc, Yaml::Array(vec![Yaml::Integer(1), Yaml::Integer(2), Yaml::Integer(3)]));
+/// ```
+impl FromIterator<Yaml> for Yaml {
+    fn from_iter<I: IntoIterator<Item = Yaml>>(iter: I) -> Self {
+        Yaml::Array(iter.into_iter().collect())
+    }
+}
+
+/// Collect key-value pairs into a [`Yaml::Hash`], keeping the last value of duplicate keys.
+///
+/// ```
+/// use saphyr::Yaml;
+///
+/// let doc: Yaml = [("a", 1), ("b", 2)]
+///     .into_iter()
+///     .map(|(key, value)| (Yaml::String(key.to_owned()), Yaml::Integer(value)))
+///     .collect();
+/// assert_eq!(doc["b"].as_i64(), Some(2));
+/// ```
+impl FromIterator<(Yaml, Yaml)> for Yaml {
+    fn from_iter<I: IntoIterator<Item = (Yaml, Yaml)>>(iter: I) -> Self {
+        Yaml::Hash(iter.into_iter().collect())
+    }
+}
+
 impl IntoIterator for Yaml {
     type Item = Yaml;
     type IntoIter = YamlIter;
@@ -320,6 +1301,130 @@ impl IntoIterator for Yaml {
     }
 }
 
+/// A node within a [`Yaml`] document, along with its path. See [`Yaml::paths`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct YamlPath<'a> {
+    /// The normalized path of the node from the root of the document (e.g.: `a.b[0].c`).
+    pub path: String,
+    /// The kind of the node. See [`Yaml::type_name`].
+    pub kind: &'static str,
+    /// The node.
+    pub node: &'a Yaml,
+}
+
+/// An iterator over the nodes within a [`Yaml`] document, with their paths. See
+/// [`Yaml::paths`].
+pub struct YamlPaths<'a> {
+    /// The nodes yet to be returned, the next one last.
+    stack: Vec<(String, &'a Yaml)>,
+}
+
+impl<'a> YamlPaths<'a> {
+    /// Queue the items or entries of `node`, whose path is `path`.
+    fn push_children(&mut self, path: &str, node: &'a Yaml) {
+        let start = self.stack.len();
+        match node.untagged() {
+            Yaml::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    self.stack.push((format!("{path}[{index}]"), item));
+                }
+            }
+            Yaml::Hash(hash) => {
+                for (key, value) in hash {
+                    let text = key_text(key);
+                    self.stack.push((key_path(path, text.as_deref()), value));
+                }
+            }
+            _ => {}
+        }
+        self.stack[start..].reverse();
+    }
+}
+
+impl<'a> Iterator for YamlPaths<'a> {
+    type Item = YamlPath<'a>;
+
+    fn next(&mut self) -> Option<YamlPath<'a>> {
+        let (path, node) = self.stack.pop()?;
+        self.push_children(&path, node);
+        Some(YamlPath {
+            path,
+            kind: node.type_name(),
+            node,
+        })
+    }
+}
+
+/// Return the child of `node` at `segment`, as [`Yaml::lookup`] finds it, or the kind of error it
+/// reports.
+pub(crate) fn child_mut<'a>(
+    node: &'a mut Yaml,
+    segment: &str,
+) -> Result<&'a mut Yaml, LookupErrorKind> {
+    let index = segment.parse::<usize>().ok();
+    match (untagged_mut(node), index) {
+        (Yaml::Hash(hash), _) => {
+            let key = Yaml::String(segment.to_owned());
+            let key = if hash.contains_key(&key) {
+                key
+            } else {
+                Yaml::Integer(segment.parse().map_err(|_| LookupErrorKind::MissingKey)?)
+            };
+            hash.get_mut(&key).ok_or(LookupErrorKind::MissingKey)
+        }
+        (Yaml::Array(items), Some(index)) => {
+            let len = items.len();
+            items
+                .get_mut(index)
+                .ok_or(LookupErrorKind::IndexOutOfRange(len))
+        }
+        (found, index) => Err(LookupErrorKind::TypeMismatch {
+            expected: if index.is_some() {
+                "sequence"
+            } else {
+                "mapping"
+            },
+            found: found.type_name(),
+        }),
+    }
+}
+
+/// Return `node` without its tags, if any.
+pub(crate) fn untagged_mut(node: &mut Yaml) -> &mut Yaml {
+    match node {
+        Yaml::Tagged(_, node) => untagged_mut(node),
+        node => node,
+    }
+}
+
+/// Return the text of `key` in paths, if it is a scalar. See [`Yaml::paths`].
+pub(crate) fn key_text(key: &Yaml) -> Option<String> {
+    match key.untagged() {
+        Yaml::String(text) | Yaml::Real(text) => Some(text.clone()),
+        Yaml::Integer(i) => Some(i.to_string()),
+        Yaml::Boolean(b) => Some(b.to_string()),
+        Yaml::Null => Some("~".to_owned()),
+        _ => None,
+    }
+}
+
+/// Return the path of the value of the key written `text` in the mapping at `path`, as
+/// [`Yaml::paths`] normalizes it. `text` is `None` for keys which are not scalars.
+pub(crate) fn key_path(path: &str, text: Option<&str>) -> String {
+    let Some(text) = text else {
+        return format!("{path}[?]");
+    };
+    let plain = !text.is_empty()
+        && !text
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '.' | '[' | ']' | '"'));
+    match (plain, path.is_empty()) {
+        (true, true) => text.to_owned(),
+        (true, false) => format!("{path}.{text}"),
+        (false, _) => format!("{path}[{text:?}]"),
+    }
+}
+
 /// An iterator over a [`Yaml`] node.
 pub struct YamlIter {
     yaml: std::vec::IntoIter<Yaml>,
@@ -1,7 +1,7 @@
 #![allow(clippy::bool_assert_comparison)]
 #![allow(clippy::float_cmp)]
 
-use saphyr::{Yaml, YamlEmitter};
+use saphyr::{LoadError, RealKeyPolicy, ValueKeyPolicy, Yaml, YamlEmitter, YamlLoader};
 
 #[test]
 fn test_api() {
@@ -71,6 +71,29 @@ a2: *DEFAULT
     assert_eq!(doc["a2"]["b1"].as_i64().unwrap(), 4);
 }
 
+#[test]
+fn test_resolve_anchor() {
+    let s = "
+a1: &DEFAULT
+    b1: 4
+a2: &DEFAULT 5
+---
+a3: &OTHER [1, 2]
+";
+    let out = Yaml::load_from_str_with_anchors(s).unwrap();
+    assert_eq!(out.len(), 2);
+    // Redefining an anchor overrides the previous definition.
+    assert_eq!(out[0].1.len(), 1);
+    assert_eq!(out[0].1.resolve("DEFAULT"), Some(&Yaml::Integer(5)));
+    // Anchors are scoped to the document they are defined in.
+    assert!(out[1].1.resolve("DEFAULT").is_none());
+    assert_eq!(out[1].1.resolve("OTHER"), Some(&out[1].0["a3"]));
+
+    // Errors are reported as `YamlLoader::load_from_str` reports them.
+    let error = Yaml::load_from_str_with_anchors("a: &x [1\nb: *x").unwrap_err();
+    assert!(matches!(error, LoadError::Scan(_)));
+}
+
 #[test]
 fn test_bad_anchor() {
     let s = "
@@ -233,3 +256,2976 @@ fn test_integer_key() {
     let first = out.into_iter().next().unwrap();
     assert_eq!(first[0]["important"].as_bool().unwrap(), true);
 }
+
+#[test]
+fn test_collection_tags() {
+    let s = "
+set: !!set
+  ? a
+  ? b
+omap: !!omap
+  - a: 1
+  - b: 2
+pairs: !!pairs [a: 1, a: 2]
+bad_set: !!set { a: 1 }
+bad_omap: !!omap [a: 1, a: 2]
+local: !set { a: 1 }
+";
+    let doc = &Yaml::load_from_str(s).unwrap()[0];
+
+    let set = doc["set"].as_set().unwrap();
+    assert_eq!(set.len(), 2);
+    assert!(set.contains_key(&Yaml::String("b".into())));
+    assert!(doc["set"]["a"].is_null());
+
+    let omap = doc["omap"].as_pairs().unwrap();
+    assert_eq!(omap[1].0.as_str(), Some("b"));
+    assert_eq!(omap[1].1.as_i64(), Some(2));
+    assert_eq!(doc["pairs"].as_pairs().unwrap().len(), 2);
+
+    assert!(doc["bad_set"].is_badvalue());
+    assert!(doc["bad_omap"].is_badvalue());
+    assert_eq!(doc["local"]["a"].as_i64(), Some(1));
+    assert!(!doc["local"].is_tagged());
+}
+
+#[test]
+fn test_real_key_policy() {
+    let s = "{1.0: a, 1.00: b, .nan: c, .NAN: d, 2: e}";
+    let load = |policy| {
+        let mut loader = YamlLoader::<Yaml>::default();
+        loader.real_key_policy(policy);
+        loader.load_from_str(s).map(|docs| docs[0].clone())
+    };
+
+    let doc = load(RealKeyPolicy::Verbatim).unwrap();
+    assert_eq!(doc.as_hash().unwrap().len(), 5);
+
+    let doc = load(RealKeyPolicy::Normalize).unwrap();
+    let hash = doc.as_hash().unwrap();
+    assert_eq!(hash.len(), 3);
+    assert_eq!(hash[&Yaml::Real("1.0".into())].as_str(), Some("b"));
+    assert_eq!(hash[&Yaml::Real(".nan".into())].as_str(), Some("d"));
+
+    let doc = load(RealKeyPolicy::Stringify).unwrap();
+    assert_eq!(doc["1.00"].as_str(), Some("b"));
+
+    match load(RealKeyPolicy::RejectNan) {
+        Err(LoadError::RealKey(marker)) => assert_eq!(marker.col(), 18),
+        other => panic!("unexpected result: {other:?}"),
+    }
+    assert!(matches!(
+        load(RealKeyPolicy::Reject),
+        Err(LoadError::RealKey(_))
+    ));
+    // Values are not affected.
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.real_key_policy(RealKeyPolicy::Reject);
+    assert!(loader.load_from_str("a: 1.5").is_ok());
+}
+
+#[test]
+fn test_empty_documents() {
+    let s = "---\n--- ~\n---\n# comment\n--- null\n---";
+    let mut loader = YamlLoader::<Yaml>::default();
+    let docs = loader.load_from_str(s).unwrap();
+    assert_eq!(docs, vec![Yaml::Null; 5]);
+    let empty = loader
+        .documents()
+        .iter()
+        .map(|doc| doc.empty)
+        .collect::<Vec<_>>();
+    assert_eq!(empty, [true, false, true, false, true]);
+    assert_eq!(loader.documents()[1].start.line(), 2);
+
+    assert!(loader.load_from_str("").unwrap().is_empty());
+    assert!(loader.documents().is_empty());
+    loader.load_from_str("'~'").unwrap();
+    assert!(!loader.documents()[0].empty);
+}
+
+#[cfg(not(feature = "fast-hash"))]
+#[test]
+fn test_hash_uses_siphash() {
+    use std::{
+        fmt::Write,
+        hash::{BuildHasher, Hash, Hasher},
+    };
+
+    // Each mapping hashes keys with its own random keys.
+    let key_hash = |hash: &saphyr::Hash| {
+        let mut hasher = hash.hasher().build_hasher();
+        Yaml::String("key".into()).hash(&mut hasher);
+        hasher.finish()
+    };
+    assert_ne!(
+        key_hash(&saphyr::Hash::new()),
+        key_hash(&saphyr::Hash::new())
+    );
+
+    let mut s = String::new();
+    for i in 0..1000 {
+        writeln!(s, "key{i}: {i}").unwrap();
+    }
+    let doc = &Yaml::load_from_str(&s).unwrap()[0];
+    assert_eq!(doc.as_hash().unwrap().len(), 1000);
+    assert_eq!(doc["key999"].as_i64(), Some(999));
+}
+
+#[test]
+fn test_marker_offset() {
+    use saphyr::{LoadWarning, MarkedYaml, Marker};
+
+    let mut loader = YamlLoader::<MarkedYaml>::default();
+    loader.marker_offset(Marker::new(100, 10, 4));
+    loader.tab_indentation(2);
+    let docs = loader.load_from_str("a: 1\nb:\n\tc: 2").unwrap();
+    let a = &docs[0].data["a"];
+    assert_eq!(
+        (a.marker.index(), a.marker.line(), a.marker.col()),
+        (103, 10, 7)
+    );
+    let c = &docs[0].data["b"].data["c"];
+    assert_eq!((c.marker.line(), c.marker.col()), (12, 5));
+    assert_eq!(
+        loader.warnings(),
+        [LoadWarning::TabIndentation(Marker::new(108, 12, 0))]
+    );
+
+    let error = loader.load_from_str("a: 1\nb: [").unwrap_err();
+    assert_eq!(error.marker().unwrap().line(), 12);
+}
+
+#[test]
+fn test_opaque_block_scalars() {
+    let s = "
+script: |
+    for i in 1 2; do
+      echo $i
+
+    done
+
+
+# Not part of the script.
+template: >
+  folded
+  text
+nested:
+  - body: |2
+       indented
+      less
+    other: |
+      x
+";
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader
+        .opaque_block_scalar(&["script"])
+        .opaque_block_scalar(&["nested", "0", "body"])
+        .opaque_block_scalar(&["missing"]);
+    let doc = &loader.load_from_str(s).unwrap()[0];
+    assert_eq!(
+        doc["script"].as_str(),
+        Some("    for i in 1 2; do\n      echo $i\n\n    done\n")
+    );
+    assert_eq!(doc["template"].as_str(), Some("folded text\n"));
+    assert_eq!(
+        doc["nested"][0]["body"].as_str(),
+        Some("       indented\n      less\n")
+    );
+    assert_eq!(doc["nested"][0]["other"].as_str(), Some("x\n"));
+
+    // Scalars without contents do not take the lines following them.
+    let doc = &loader
+        .load_from_str("script: |\nother: x\nmore: y\n")
+        .unwrap()[0];
+    assert_eq!(doc["script"].as_str(), Some(""));
+    assert_eq!(doc["other"].as_str(), Some("x"));
+    assert_eq!(doc["more"].as_str(), Some("y"));
+    let doc = &loader.load_from_str("script: |+\n\nother: x\n").unwrap()[0];
+    assert_eq!(doc["script"].as_str(), Some(""));
+    assert_eq!(doc["other"].as_str(), Some("x"));
+
+    // Plain scalars at an opaque path are loaded as usual.
+    assert_eq!(
+        loader.load_from_str("script: 1").unwrap()[0]["script"].as_i64(),
+        Some(1)
+    );
+}
+
+#[test]
+fn test_get_ci() {
+    let doc =
+        &Yaml::load_from_str("SystemRoot: C:\\Windows\nÉtat: ok\nlist: [a]\nmap: !!set { a }")
+            .unwrap()[0];
+    assert_eq!(doc.get_ci("systemroot"), Some(&doc["SystemRoot"]));
+    assert_eq!(doc.get_ci("SYSTEMROOT"), Some(&doc["SystemRoot"]));
+    assert_eq!(doc.get_ci("état").and_then(Yaml::as_str), Some("ok"));
+    assert!(doc.get_ci("LIST").unwrap().is_array());
+    assert!(doc.get_ci("missing").is_none());
+    assert!(doc["list"].get_ci("a").is_none());
+    assert!(doc["map"].get_ci("A").unwrap().is_null());
+}
+
+#[test]
+fn test_error_codes() {
+    use saphyr::ErrorCode;
+
+    let code = |s: &str| ErrorCode::of_scan_error(&Yaml::load_from_str(s).unwrap_err());
+    assert_eq!(code("a: b: c").as_str(), "SCAN023");
+    assert_eq!(code("@a").as_str(), "SCAN034");
+    assert_eq!(code("*a").as_str(), "SCAN051");
+    assert_eq!(code("a: !x!y b").as_str(), "SCAN033");
+
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.real_key_policy(RealKeyPolicy::Reject);
+    let error = loader.load_from_str("1.5: a").unwrap_err();
+    assert_eq!(error.code().as_str(), "LOAD003");
+    assert_eq!(
+        error.to_string(),
+        "LOAD003: real number used as a mapping key at line 1 column 1"
+    );
+}
+
+#[test]
+fn test_lookup() {
+    use saphyr::{LookupError, LookupErrorKind};
+
+    let doc = &Yaml::load_from_str("a: {b: [x, {1: one}]}\nc: !!set {d}").unwrap()[0];
+    assert_eq!(doc.lookup(&[]).unwrap(), doc);
+    assert_eq!(
+        doc.lookup(&["a", "b", "1", "1"]).unwrap().as_str(),
+        Some("one")
+    );
+    assert!(doc.lookup(&["c", "d"]).unwrap().is_null());
+
+    let error = doc.lookup(&["a", "b", "2"]).unwrap_err();
+    assert_eq!(
+        error,
+        LookupError {
+            path: vec!["a".into(), "b".into()],
+            segment: "2".into(),
+            kind: LookupErrorKind::IndexOutOfRange(2),
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "LOOKUP002: index 2 is out of range at `a.b`, which has 2 items"
+    );
+    assert_eq!(
+        doc.lookup(&["e"]).unwrap_err().to_string(),
+        "LOOKUP001: missing key `e` at the root"
+    );
+    assert_eq!(
+        doc.lookup(&["a", "b", "0", "0"]).unwrap_err().to_string(),
+        "LOOKUP003: expected sequence at `a.b.0`, found string"
+    );
+}
+
+#[test]
+fn test_rows() {
+    let headers = vec!["id".to_string(), "name".to_string(), "score".to_string()];
+    let rows = vec![vec!["1", "alice", "9.5", "ignored"], vec!["2", "bob"]];
+    let yaml = Yaml::from_rows(&headers, rows);
+    assert_eq!(
+        yaml,
+        Yaml::load_from_str("[{id: 1, name: alice, score: 9.5}, {id: 2, name: bob, score: ~}]")
+            .unwrap()[0]
+    );
+
+    let (back_headers, back_rows) = yaml.to_rows().unwrap();
+    assert_eq!(back_headers, headers);
+    assert_eq!(back_rows, [["1", "alice", "9.5"], ["2", "bob", ""]]);
+
+    assert!(Yaml::load_from_str("[{a: [1]}]").unwrap()[0]
+        .to_rows()
+        .is_none());
+    assert!(Yaml::load_from_str("[1]").unwrap()[0].to_rows().is_none());
+    assert_eq!(Yaml::Array(vec![]).to_rows(), Some((vec![], vec![])));
+}
+
+#[test]
+fn test_sequence_helpers() {
+    let mut doc = Yaml::load_from_str("[b, d]").unwrap().remove(0);
+    doc.insert(0, Yaml::String("c".into())).unwrap();
+    doc.push(Yaml::String("a".into())).unwrap();
+    doc.sort_by(|a, b| a.as_str().cmp(&b.as_str())).unwrap();
+    assert_eq!(doc, Yaml::load_from_str("[a, b, c, d]").unwrap()[0]);
+    assert_eq!(doc.remove(1).unwrap().as_str(), Some("b"));
+    doc.retain(|item| item.as_str() != Some("c")).unwrap();
+    assert_eq!(doc, Yaml::load_from_str("[a, d]").unwrap()[0]);
+
+    // Tags are looked through.
+    let mut omap = Yaml::new_omap([]);
+    omap.push(Yaml::new_omap([])).unwrap();
+    assert_eq!(omap.untagged().as_vec().unwrap().len(), 1);
+
+    let mut hash = Yaml::load_from_str("{a: 1}").unwrap().remove(0);
+    let error = hash.push(Yaml::Null).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "TYPE001: expected sequence, found mapping"
+    );
+}
+
+#[test]
+fn test_take_replace() {
+    let mut doc = Yaml::load_from_str("a: {b: [x, y]}\nc: 1")
+        .unwrap()
+        .remove(0);
+    // Move a subtree elsewhere without cloning it.
+    let b = doc["a"]["b"].take();
+    let previous = doc["c"].replace(b);
+    assert_eq!(previous, Yaml::Integer(1));
+    assert_eq!(doc, Yaml::load_from_str("a: {b: ~}\nc: [x, y]").unwrap()[0]);
+}
+
+#[cfg(feature = "rc")]
+#[test]
+fn test_shared_yaml() {
+    use saphyr::SharedYaml;
+
+    let s = "
+a: [1, 2.5, !!str 3]
+b: {c: true, d: ~}
+";
+    let docs = SharedYaml::load_from_str(s).unwrap();
+    let doc = &docs[0];
+    assert_eq!(doc["a"][0].as_i64(), Some(1));
+    assert_eq!(doc["a"][1].as_f64(), Some(2.5));
+    assert_eq!(doc["a"][2].as_str(), Some("3"));
+    assert_eq!(doc["b"]["c"].as_bool(), Some(true));
+    assert!(doc["b"]["d"].is_null());
+    assert!(doc["z"].is_badvalue());
+
+    let mut copy = doc.clone();
+    assert!(copy["a"].shares_contents_with(&doc["a"]));
+    copy.as_mut_hash()
+        .unwrap()
+        .insert(SharedYaml::String("e".into()), SharedYaml::Integer(4));
+    assert!(copy["a"].shares_contents_with(&doc["a"]));
+    assert!(doc["e"].is_badvalue());
+    assert_eq!(copy["e"].as_i64(), Some(4));
+
+    let yaml = Yaml::from(doc);
+    assert_eq!(yaml, Yaml::load_from_str(s).unwrap().remove(0));
+    assert_eq!(SharedYaml::from(yaml), *doc);
+}
+
+#[cfg(feature = "rc")]
+#[test]
+fn test_string_pool() {
+    use saphyr::{SharedYaml, StringPool};
+
+    let s = "
+- {name: a, labels: [x, y], !!str 1: x}
+- {name: b, labels: [y, 1.5], 1.5: x}
+";
+    let mut docs = SharedYaml::load_from_str(s).unwrap();
+    let original = docs.clone();
+    let mut pool = StringPool::new();
+    assert!(pool.is_empty());
+    pool.deduplicate(&mut docs[0]);
+    assert_eq!(docs, original);
+    // The copy-on-write leaves the clones untouched.
+    assert!(!docs[0][0]["labels"].shares_contents_with(&original[0][0]["labels"]));
+
+    let doc = &docs[0];
+    assert!(doc[0]["labels"][0].shares_contents_with(&doc[0]["1"]));
+    assert!(doc[0]["labels"][1].shares_contents_with(&doc[1]["labels"][0]));
+    let keys: Vec<_> = doc[0].as_hash().unwrap().keys().collect();
+    let other_keys: Vec<_> = doc[1].as_hash().unwrap().keys().collect();
+    assert!(keys[0].shares_contents_with(other_keys[0]));
+    assert!(other_keys[2].shares_contents_with(&doc[1]["labels"][1]));
+    // name, a, labels, x, y, 1, b, 1.5
+    assert_eq!(pool.len(), 8);
+}
+
+#[test]
+fn test_duplicate_anchor_policy() {
+    use saphyr::{DuplicateAnchorPolicy, LoadWarning};
+
+    let s = "
+a: &x 1
+b: [&y 2, &x 3]
+c: *x
+---
+d: &x 4
+";
+    let mut loader = YamlLoader::<Yaml>::default();
+    let docs = loader.load_from_str(s).unwrap();
+    assert_eq!(docs[0]["c"].as_i64(), Some(3));
+    assert!(loader.warnings().is_empty());
+
+    loader.duplicate_anchor_policy(DuplicateAnchorPolicy::Warn);
+    loader.load_from_str(s).unwrap();
+    // Anchors are scoped to their document.
+    match loader.warnings() {
+        [LoadWarning::DuplicateAnchor {
+            name,
+            first,
+            second,
+        }] => {
+            assert_eq!(name, "x");
+            assert_eq!((first.line(), first.col()), (2, 3));
+            assert_eq!((second.line(), second.col()), (3, 10));
+        }
+        warnings => panic!("unexpected warnings: {warnings:?}"),
+    }
+
+    loader.duplicate_anchor_policy(DuplicateAnchorPolicy::Reject);
+    let error = loader.load_from_str(s).unwrap_err();
+    assert!(matches!(&error, LoadError::DuplicateAnchor { name, .. } if name == "x"));
+    assert_eq!(error.marker().map(|m| (m.line(), m.col())), Some((3, 10)));
+    assert_eq!(
+        error.to_string(),
+        "LOAD006: anchor `x` defined at line 2 column 4 is redefined at line 3 column 11"
+    );
+}
+
+#[test]
+fn test_unused_anchors() {
+    use saphyr::LoadWarning;
+
+    let s = "
+base: &base {a: 1}
+tmp: &tmp 2
+job: *base
+---
+other: &base 3
+";
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.load_from_str(s).unwrap();
+    assert!(loader.warnings().is_empty());
+
+    loader.warn_unused_anchors(true);
+    loader.load_from_str(s).unwrap();
+    let unused = loader
+        .warnings()
+        .iter()
+        .map(|warning| match warning {
+            LoadWarning::UnusedAnchor { name, marker } => (name.as_str(), marker.line()),
+            warning => panic!("unexpected warning: {warning:?}"),
+        })
+        .collect::<Vec<_>>();
+    // Anchors are scoped to their document.
+    assert_eq!(unused, [("tmp", 3), ("base", 6)]);
+    assert_eq!(
+        loader.warnings()[0].to_string(),
+        "anchor `tmp` defined at line 3 column 6 is never used"
+    );
+
+    // When redefined, the anchor an alias refers to is used.
+    loader.load_from_str("- &x 1\n- &x 2\n- *x").unwrap();
+    assert!(matches!(
+        loader.warnings(),
+        [LoadWarning::UnusedAnchor { marker, .. }] if marker.line() == 1
+    ));
+}
+
+#[test]
+fn test_core_schema() {
+    use saphyr::Schema;
+
+    let real = |v: &str| Yaml::Real(v.to_owned());
+    let string = |v: &str| Yaml::String(v.to_owned());
+    // Examples from the YAML 1.2.2 specification (10.3.2), along with edge cases.
+    let cases = [
+        ("null", Yaml::Null),
+        ("Null", Yaml::Null),
+        ("NULL", Yaml::Null),
+        ("~", Yaml::Null),
+        ("nULL", string("nULL")),
+        ("true", Yaml::Boolean(true)),
+        ("True", Yaml::Boolean(true)),
+        ("TRUE", Yaml::Boolean(true)),
+        ("FALSE", Yaml::Boolean(false)),
+        ("tRUE", string("tRUE")),
+        ("yes", string("yes")),
+        ("0", Yaml::Integer(0)),
+        ("-19", Yaml::Integer(-19)),
+        ("+12", Yaml::Integer(12)),
+        ("0o14", Yaml::Integer(12)),
+        ("0o19", string("0o19")),
+        ("0x1A", Yaml::Integer(26)),
+        ("0x-1", string("0x-1")),
+        ("+0x1", string("+0x1")),
+        ("1_000", string("1_000")),
+        ("99999999999999999999", real("99999999999999999999")),
+        ("1.", real("1.")),
+        ("0.", real("0.")),
+        ("-0.0", real("-0.0")),
+        (".5", real(".5")),
+        ("+12e03", real("+12e03")),
+        ("-2E+05", real("-2E+05")),
+        ("1e", string("1e")),
+        (".", string(".")),
+        ("e5", string("e5")),
+        (".inf", real(".inf")),
+        ("+.inf", real("+.inf")),
+        ("-.Inf", real("-.Inf")),
+        ("inf", string("inf")),
+        ("infinity", string("infinity")),
+        (".NaN", real(".nan")),
+        ("NaN", string("NaN")),
+        ("-.nan", string("-.nan")),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(Schema::Core.resolve_plain(input), expected, "{input}");
+    }
+
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.schema(Schema::Core);
+    let docs = loader
+        .load_from_str(
+            "
+- a:
+- 'NULL'
+- !!bool True
+- !!bool yes
+- !!int +7
+- !!float 3
+- !!str 3
+- +12e03
+- .NaN
+",
+        )
+        .unwrap();
+    let doc = docs[0].as_vec().unwrap();
+    assert!(doc[0]["a"].is_null());
+    assert_eq!(doc[1], string("NULL"));
+    assert_eq!(doc[2], Yaml::Boolean(true));
+    assert!(doc[3].is_badvalue());
+    assert_eq!(doc[4], Yaml::Integer(7));
+    assert_eq!(doc[5], real("3"));
+    assert_eq!(doc[6], string("3"));
+    assert_eq!(doc[7].as_f64(), Some(12000.0));
+    assert!(doc[8].as_f64().unwrap().is_nan());
+}
+
+#[test]
+fn test_max_alias_depth() {
+    let s = "
+a: &a [x, y]
+b: &b {k: [*a, *a]}
+c: &c [*b, *b, *a]
+d: [[[[*c]]]]
+";
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.max_alias_depth(3);
+    let docs = loader.load_from_str(s).unwrap();
+    assert_eq!(docs[0]["d"][0][0][0][0][0]["k"][1][1].as_str(), Some("y"));
+
+    // Nesting collections does not count towards the limit.
+    loader.max_alias_depth(1);
+    assert!(loader.load_from_str("a: &a [[[[x]]]]\nb: *a").is_ok());
+
+    loader.max_alias_depth(2);
+    let error = loader.load_from_str(s).unwrap_err();
+    assert!(
+        matches!(error, LoadError::AliasTooDeep(marker) if marker.line() == 5),
+        "{error:?}"
+    );
+    assert_eq!(
+        error.to_string(),
+        "LOAD007: alias at line 5 column 8 nests aliases too deeply"
+    );
+}
+
+#[test]
+fn test_trailing_commas() {
+    use saphyr::LoadWarning;
+
+    let s = "
+a: [1, 2,]
+b: {c: [x, y], d: 3,
+}
+e: [{f: g,},]
+";
+    let mut loader = YamlLoader::<Yaml>::default();
+    let docs = loader.load_from_str(s).unwrap();
+    assert_eq!(docs[0]["a"].as_vec().unwrap().len(), 2);
+    assert_eq!(docs[0]["b"].as_hash().unwrap().len(), 2);
+    assert_eq!(docs[0]["e"][0]["f"].as_str(), Some("g"));
+    assert!(loader.warnings().is_empty());
+
+    loader.warn_trailing_commas(true);
+    assert_eq!(loader.load_from_str(s).unwrap(), docs);
+    let commas = loader
+        .warnings()
+        .iter()
+        .map(|warning| match warning {
+            LoadWarning::TrailingComma(marker) => (marker.line(), marker.col()),
+            warning => panic!("unexpected warning: {warning:?}"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(commas, [(2, 8), (3, 19), (5, 9), (5, 11)]);
+    assert_eq!(
+        loader.warnings()[0].to_string(),
+        "trailing comma in flow collection at line 2 column 9"
+    );
+
+    // Empty entries are still rejected.
+    assert!(loader.load_from_str("[a,,]").is_err());
+}
+
+#[cfg(feature = "nfc")]
+#[test]
+fn test_normalize_keys() {
+    // `é` precomposed, then decomposed.
+    let s = "
+caf\u{e9}: 1
+cafe\u{301}: 2
+name: cafe\u{301}
+";
+    let mut loader = YamlLoader::<Yaml>::default();
+    let docs = loader.load_from_str(s).unwrap();
+    assert_eq!(docs[0].as_hash().unwrap().len(), 3);
+
+    loader.normalize_keys(true);
+    let docs = loader.load_from_str(s).unwrap();
+    assert_eq!(docs[0].as_hash().unwrap().len(), 2);
+    assert_eq!(docs[0]["caf\u{e9}"].as_i64(), Some(2));
+    // Values are kept as they are.
+    assert_eq!(docs[0]["name"].as_str(), Some("cafe\u{301}"));
+}
+
+#[test]
+fn test_extractor() {
+    use saphyr::{ExtractError, Extractor, FieldType, FieldValue, Schema};
+
+    let mut extractor = Extractor::new();
+    extractor
+        .field("host", &["server", "host"], FieldType::String)
+        .field("port", &["server", "port"], FieldType::U16)
+        .field("ratio", &["limits", "1", "ratio"], FieldType::F64)
+        .field("retries", &["retries"], FieldType::I64)
+        .field("flag", &["flags", "1"], FieldType::Bool);
+    let s = "
+? [server]
+: {host: wrong}
+server:
+  host: &h example.com
+  aliased: *h
+  port: 8080
+limits: [{ratio: 0}, {ratio: 0.5}]
+other: {server: {host: nested}}
+flags: {1: true}
+mirror: *h
+---
+server: {port: 1}
+";
+    let records = extractor.extract_str(s).unwrap();
+    assert_eq!(records.len(), 2);
+    let record = &records[0];
+    assert_eq!(record.len(), 4);
+    assert_eq!(record.get("host").unwrap().as_str(), Some("example.com"));
+    assert_eq!(record.get("port"), Some(&FieldValue::U64(8080)));
+    assert_eq!(record.get("ratio").unwrap().as_f64(), Some(0.5));
+    assert_eq!(record.get("flag").unwrap().as_bool(), Some(true));
+    assert!(record.get("retries").is_none());
+    assert_eq!(records[1].get("port").unwrap().as_u16(), Some(1));
+    assert!(records[1].get("host").is_none());
+
+    // Aliases to scalars are followed.
+    let mut extractor = Extractor::new();
+    extractor.field("mirror", &["mirror"], FieldType::String);
+    let records = extractor.extract_str(s).unwrap();
+    assert_eq!(
+        records[0].get("mirror").unwrap().as_str(),
+        Some("example.com")
+    );
+
+    // Values must be of the type of their field.
+    let mut extractor = Extractor::new();
+    extractor.field("port", &["server", "port"], FieldType::U16);
+    for (source, line) in [
+        ("server:\n  port: 65536", 2),
+        ("server:\n  port: '80'", 2),
+        ("server:\n  port: [80]", 2),
+    ] {
+        let error = extractor.extract_str(source).unwrap_err();
+        assert!(
+            matches!(&error, ExtractError::InvalidValue { field, marker, .. }
+                if field == "port" && marker.line() == line),
+            "{error:?}"
+        );
+    }
+    assert_eq!(
+        extractor
+            .extract_str("server: {port: -1}")
+            .unwrap_err()
+            .to_string(),
+        "EXTRACT001: field `port` at line 1 column 16 is not of type U16"
+    );
+    assert!(matches!(
+        extractor.extract_str("server: {port: 1"),
+        Err(ExtractError::Scan(_))
+    ));
+
+    let mut extractor = Extractor::new();
+    extractor
+        .field("debug", &["debug"], FieldType::Bool)
+        .schema(Schema::Core);
+    let records = extractor.extract_str("debug: True").unwrap();
+    assert_eq!(records[0].get("debug").unwrap().as_bool(), Some(true));
+}
+
+#[test]
+fn test_key_charset() {
+    use saphyr::KeyCharset;
+
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.key_charset(KeyCharset::identifier());
+    let docs = loader
+        .load_from_str("api.v2:\n  max-conns: 3\n  LOG_LEVEL: 'caf\u{e9} au lait'\n  404: [x]")
+        .unwrap_or_else(|e| panic!("{e}"));
+    // Values are not checked.
+    assert_eq!(
+        docs[0]["api.v2"]["LOG_LEVEL"].as_str(),
+        Some("caf\u{e9} au lait")
+    );
+
+    for (source, key, line) in [
+        ("a:\n  b c: 1", "b c", 2),
+        ("a:\n  'b\t': 1", "b\t", 2),
+        ("caf\u{e9}: 1", "caf\u{e9}", 1),
+        ("'': 1", "", 1),
+        ("[x, {$ref: y}]", "$ref", 1),
+    ] {
+        match loader.load_from_str(source) {
+            Err(LoadError::InvalidKey(k, marker)) => {
+                assert_eq!((k.as_str(), marker.line()), (key, line), "{source:?}");
+            }
+            result => panic!("unexpected result for {source:?}: {result:?}"),
+        }
+    }
+    assert_eq!(
+        loader.load_from_str("a b: 1").unwrap_err().to_string(),
+        "LOAD008: key \"a b\" at line 1 column 1 contains characters that are not allowed"
+    );
+
+    loader.key_charset(KeyCharset::identifier().with("$"));
+    assert!(loader.load_from_str("$ref: y").is_ok());
+    assert!(!KeyCharset::new("ab c").accepts("a c"));
+}
+
+#[test]
+fn test_source_map() {
+    use saphyr::{LoadWarning, MarkedYaml, Marker, SourceMap};
+
+    // Rendered from:
+    // ```
+    // # header
+    // name: {{ .name }}
+    // items:
+    // {{ include "items" }}
+    // ```
+    // where the `items` template, at line 10 of the same file, is indented by 2.
+    let rendered = "name: web\nitems:\n- a\n- [b,]\n";
+    let mut map = SourceMap::new();
+    map.add(Marker::new(0, 1, 0), Marker::new(9, 2, 0))
+        .add(Marker::new(6, 1, 6), Marker::new(15, 2, 6))
+        .add(Marker::new(9, 1, 9), Marker::new(26, 2, 17))
+        .add(Marker::new(17, 3, 0), Marker::new(150, 10, 2));
+    assert_eq!(map.len(), 4);
+
+    let mut loader = YamlLoader::<MarkedYaml>::default();
+    loader.source_map(map.clone()).warn_trailing_commas(true);
+    let docs = loader.load_from_str(rendered).unwrap();
+    let position = |marker: &Marker| (marker.index(), marker.line(), marker.col());
+    assert_eq!(position(&docs[0].data["name"].marker), (15, 2, 6));
+    // Block sequences start at their first item.
+    assert_eq!(position(&docs[0].data["items"].marker), (152, 10, 4));
+    let items = docs[0].data["items"].data.as_vec().unwrap();
+    assert_eq!(position(&items[0].marker), (152, 10, 4));
+    assert_eq!(position(&items[1].marker), (156, 11, 2));
+    assert!(matches!(
+        loader.warnings(),
+        [LoadWarning::TrailingComma(marker)] if position(marker) == (158, 11, 4)
+    ));
+
+    // Markers before the first span are kept.
+    assert_eq!(
+        position(&SourceMap::new().translate(Marker::new(3, 1, 3))),
+        (3, 1, 3)
+    );
+    // Spans starting at the same index replace each other.
+    map.add(Marker::new(17, 3, 0), Marker::new(0, 1, 0));
+    assert_eq!(map.len(), 4);
+}
+
+#[test]
+fn test_coercions() {
+    use saphyr::CoerceErrorKind;
+    use std::time::Duration;
+
+    let s = "
+port: 8080
+big: 70000
+negative: -1
+timeout: 1h 30m
+retry: 1d2h
+huge: 99999999999999999999999s
+cache: 512 KiB
+disk: 20000000TiB
+debug: On
+name: web
+list: [1]
+";
+    let doc = &Yaml::load_from_str(s).unwrap()[0];
+    assert_eq!(doc["port"].as_u16_checked(), Ok(8080));
+    assert_eq!(doc["big"].as_u32_checked(), Ok(70000));
+    assert_eq!(doc["negative"].as_i32_checked(), Ok(-1));
+
+    let error = doc["big"].as_u16_checked().unwrap_err();
+    assert_eq!(error.kind, CoerceErrorKind::OutOfRange);
+    assert_eq!(
+        error.to_string(),
+        "COERCE003: `70000` is out of range for a u16"
+    );
+    let error = doc["negative"].as_u64_checked().unwrap_err();
+    assert_eq!(error.kind, CoerceErrorKind::OutOfRange);
+    let error = doc["name"].as_u8_checked().unwrap_err();
+    assert_eq!(error.to_string(), "COERCE002: `web` is not a u8");
+
+    // Quantities may not be separated.
+    assert_eq!(
+        doc["timeout"].as_duration().unwrap_err().kind,
+        CoerceErrorKind::Invalid
+    );
+    assert_eq!(
+        doc["retry"].as_duration(),
+        Ok(Duration::from_secs(26 * 3600))
+    );
+    let error = doc["huge"].as_duration().unwrap_err();
+    assert_eq!(error.kind, CoerceErrorKind::OutOfRange);
+    assert_eq!(error.text.as_deref(), Some("99999999999999999999999s"));
+    assert_eq!(
+        doc["negative"].as_duration().unwrap_err().kind,
+        CoerceErrorKind::OutOfRange
+    );
+    // Quoted integers are accepted as plain ones, and negative ones are out of range too.
+    let quoted = &Yaml::load_from_str("{ ttl: '60', page: \"4096\", below: '-5s', minus: '-1' }")
+        .unwrap()[0];
+    assert_eq!(quoted["ttl"].as_duration(), Ok(Duration::from_secs(60)));
+    assert_eq!(quoted["page"].as_bytesize(), Ok(4096));
+    assert_eq!(
+        quoted["below"].as_duration().unwrap_err().kind,
+        CoerceErrorKind::OutOfRange
+    );
+    assert_eq!(
+        quoted["minus"].as_bytesize().unwrap_err().kind,
+        CoerceErrorKind::OutOfRange
+    );
+    assert_eq!(
+        doc["negative"].as_bytesize().unwrap_err().kind,
+        CoerceErrorKind::OutOfRange
+    );
+    // A unit may only be left out of a bare integer.
+    assert_eq!(
+        Yaml::String("1m30".into()).as_duration().unwrap_err().kind,
+        CoerceErrorKind::Invalid
+    );
+
+    assert_eq!(doc["cache"].as_bytesize(), Ok(512 * 1024));
+    assert_eq!(
+        doc["disk"].as_bytesize().unwrap_err().kind,
+        CoerceErrorKind::OutOfRange
+    );
+    assert_eq!(
+        doc["name"].as_bytesize().unwrap_err().to_string(),
+        "COERCE002: `web` is not a size in bytes"
+    );
+
+    assert_eq!(doc["debug"].as_bool_relaxed(), Ok(true));
+    assert_eq!(
+        doc["port"].as_bool_relaxed().unwrap_err().kind,
+        CoerceErrorKind::Invalid
+    );
+
+    let error = doc["list"].as_duration().unwrap_err();
+    assert_eq!(error.kind, CoerceErrorKind::NotAScalar("sequence"));
+    assert_eq!(error.text, None);
+    assert_eq!(
+        error.to_string(),
+        "COERCE001: expected a duration, found sequence"
+    );
+    assert_eq!(
+        doc["missing"].as_u16_checked().unwrap_err().kind,
+        CoerceErrorKind::NotAScalar("bad value")
+    );
+}
+
+#[test]
+fn test_manifests() {
+    use saphyr::{group_manifests, join_manifests, split_manifests};
+
+    let s = "%YAML 1.2
+---
+# Source: chart/templates/a.yaml
+kind: ConfigMap
+apiVersion: v1
+metadata:
+  name: a
+...
+%YAML 1.2
+--- # Source: chart/templates/b.yaml
+kind: Secret
+apiVersion: v1
+metadata: {name: b, namespace: ns}
+---
+---
+kind: ConfigMap
+apiVersion: v1
+data:
+  script: |
+    echo ---
+# trailing comment
+";
+    let manifests = split_manifests(s).unwrap();
+    assert_eq!(manifests.len(), 4);
+    assert!(manifests[0].text().starts_with("%YAML 1.2\n---\n"));
+    assert!(manifests[0].text().ends_with("...\n"));
+    assert_eq!(manifests[1].name(), Some("b"));
+    assert_eq!(manifests[1].namespace(), Some("ns"));
+    assert_eq!(manifests[2].text(), "---\n");
+    assert!(manifests[2].doc().is_null());
+    assert_eq!(
+        manifests[3].doc()["data"]["script"].as_str(),
+        Some("echo ---\n")
+    );
+    assert!(manifests[3].text().ends_with("# trailing comment\n"));
+    assert_eq!(join_manifests(&manifests), s);
+
+    let groups = group_manifests(&manifests);
+    let kinds: Vec<_> = groups.iter().map(|group| group.kind).collect();
+    assert_eq!(kinds, [Some("ConfigMap"), Some("Secret"), None]);
+    assert_eq!(groups[0].manifests.len(), 2);
+
+    // Documents without a separator get one when they are not first anymore.
+    let manifests = split_manifests("a: 1\n---\nb: 2").unwrap();
+    assert_eq!(
+        join_manifests(manifests.iter().rev()),
+        "---\nb: 2\n---\na: 1\n"
+    );
+    assert!(split_manifests("").unwrap().is_empty());
+    // Without documents, there is no manifest to keep comments in.
+    assert!(split_manifests("# only comment\n").unwrap().is_empty());
+}
+
+#[test]
+fn test_emit_manifests() {
+    use saphyr::{emit_manifests, split_manifests};
+
+    let s = "%YAML 1.2
+---
+# Source: a.yaml
+kind: ConfigMap
+data: {a: 1}
+...
+%YAML 1.2
+---
+kind: Secret   # untouched
+---
+kind: ConfigMap
+";
+    let mut manifests = split_manifests(s).unwrap();
+    assert_eq!(emit_manifests(&manifests).unwrap(), s);
+
+    // Mutable borrows mark manifests as modified, even without changes.
+    let _ = manifests[2].doc_mut();
+    assert!(manifests[2].is_modified());
+    manifests[0].doc_mut()["data"]["a"] = Yaml::Integer(2);
+    let emitted = emit_manifests(&manifests).unwrap();
+    assert_eq!(
+        emitted,
+        "%YAML 1.2
+---
+kind: ConfigMap
+data:
+  a: 2
+...
+%YAML 1.2
+---
+kind: Secret   # untouched
+---
+kind: ConfigMap
+"
+    );
+    assert_eq!(Yaml::load_from_str(&emitted).unwrap().len(), 3);
+
+    // The first document only gets a separator if it had one.
+    let mut manifests = split_manifests("# comment\na: 1").unwrap();
+    manifests[0].set_doc(Yaml::Integer(1));
+    assert_eq!(emit_manifests(&manifests).unwrap(), "# comment\n1\n");
+}
+
+#[test]
+fn test_opaque_placeholders() {
+    use saphyr::MarkedYaml;
+
+    let s = "{{ .Values.key }}: {{ include \"a\" . }}
+labels: {app: {{ .Release.Name }}, tier: \"{{ .Values.tier }}\"}
+script: |
+  echo {{ .Values.message }}
+";
+    let mut loader = YamlLoader::<MarkedYaml>::default();
+    loader.opaque_placeholders("{{", "}}");
+    let docs = loader.load_from_str(s).unwrap();
+    let doc = &docs[0].data;
+    assert_eq!(
+        doc["{{ .Values.key }}"].data.as_str(),
+        Some("{{ include \"a\" . }}")
+    );
+    assert_eq!(
+        doc["labels"].data["app"].data.as_str(),
+        Some("{{ .Release.Name }}")
+    );
+    assert_eq!(
+        doc["labels"].data["tier"].data.as_str(),
+        Some("{{ .Values.tier }}")
+    );
+    assert_eq!(
+        doc["script"].data.as_str(),
+        Some("echo {{ .Values.message }}\n")
+    );
+    // Markers are not affected by placeholders.
+    let tier = &doc["labels"].data["tier"];
+    assert_eq!((tier.marker.line(), tier.marker.col()), (2, 41));
+
+    // Placeholders must be closed on their line.
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.opaque_placeholders("{{", "}}");
+    assert!(loader.load_from_str("next: {{ unterminated\n").is_err());
+
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.opaque_placeholders("<%=", "%>");
+    let docs = loader.load_from_str("port: <%= port %>").unwrap();
+    assert_eq!(docs[0]["port"].as_str(), Some("<%= port %>"));
+
+    // Private use characters, which placeholders are masked with, are kept as they are.
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.opaque_placeholders("{{", "}}");
+    let docs = loader
+        .load_from_str("a: \u{E000}\u{F0000} {{ x }}\nb: \"{{ \u{F0001} }}\"")
+        .unwrap();
+    assert_eq!(docs[0]["a"].as_str(), Some("\u{E000}\u{F0000} {{ x }}"));
+    assert_eq!(docs[0]["b"].as_str(), Some("{{ \u{F0001} }}"));
+
+    // Escape sequences standing for them are not mistaken for placeholders.
+    let docs = loader
+        .load_from_str("a: {{ x }}\nb: \"\\U000F0009\"\nc: \"\\U000F0000\"")
+        .unwrap();
+    assert_eq!(docs[0]["a"].as_str(), Some("{{ x }}"));
+    assert_eq!(docs[0]["b"].as_str(), Some("\u{F0009}"));
+    assert_eq!(docs[0]["c"].as_str(), Some("\u{F0000}"));
+}
+
+#[test]
+fn test_detailed_events() {
+    use saphyr::{BlockScalarHeader, Chomping, DetailedEvents, Event, ScalarDetails, TScalarStyle};
+
+    let s = "- é: >+ # keep
+    folded
+
+
+- list: [\"a\\\"b\", 'c''d\n    e']
+  empty: |1-
+--- |
+ root
+";
+    let details: Vec<_> = DetailedEvents::new(s)
+        .map(Result::unwrap)
+        .filter(|(event, ..)| matches!(event, Event::Scalar(..)))
+        .map(|(.., details)| details.unwrap())
+        .collect();
+    let block = |style, chomping, indent| ScalarDetails::Block {
+        style,
+        header: BlockScalarHeader { chomping, indent },
+    };
+    assert_eq!(
+        details,
+        [
+            ScalarDetails::Plain,
+            block(TScalarStyle::Folded, Chomping::Keep, None),
+            ScalarDetails::Plain,
+            ScalarDetails::Quoted {
+                style: TScalarStyle::DoubleQuoted,
+                raw: "a\\\"b".into()
+            },
+            ScalarDetails::Quoted {
+                style: TScalarStyle::SingleQuoted,
+                raw: "c''d\n    e".into()
+            },
+            ScalarDetails::Plain,
+            block(TScalarStyle::Literal, Chomping::Strip, Some(1)),
+            block(TScalarStyle::Literal, Chomping::Clip, None),
+        ]
+    );
+
+    // Single quotes do not end double-quoted scalars.
+    let details: Vec<_> = DetailedEvents::new("a: \"it's\"")
+        .filter_map(|result| result.unwrap().2)
+        .collect();
+    assert_eq!(
+        details[1],
+        ScalarDetails::Quoted {
+            style: TScalarStyle::DoubleQuoted,
+            raw: "it's".into()
+        }
+    );
+}
+
+#[test]
+fn test_reject_trailing_content() {
+    let mut loader = YamlLoader::<Yaml>::default();
+    // Bare documents after `...` are valid YAML by default.
+    assert_eq!(loader.load_from_str("a: 1\n...\n- b\n").unwrap().len(), 2);
+
+    loader.reject_trailing_content(true);
+    let ok = "a: 1\n...\n\n# comment\n%YAML 1.2\n---\nb\n...\n";
+    assert_eq!(loader.load_from_str(ok).unwrap().len(), 2);
+    let error = loader.load_from_str("a: 1\n...\n  - b\n").unwrap_err();
+    assert!(
+        matches!(error, LoadError::TrailingContent(marker) if marker.line() == 3 && marker.col() == 2)
+    );
+    assert_eq!(
+        error.to_string(),
+        "LOAD009: contents at line 3 column 3 follow the end of a document without a `---` separator"
+    );
+}
+
+#[test]
+fn test_load_files() {
+    use saphyr::{load_files, LoadOptions, Schema};
+
+    let dir = std::env::temp_dir().join(format!("saphyr-test-load-files-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let paths: Vec<_> = (0..8)
+        .map(|i| {
+            let path = dir.join(format!("{i}.yaml"));
+            std::fs::write(&path, format!("index: {i}\nflag: True\n")).unwrap();
+            path
+        })
+        .collect();
+
+    let mut options = LoadOptions::new();
+    options.threads(4).configure(|loader| {
+        loader.schema(Schema::Core);
+    });
+    let results = load_files(&paths, &options);
+    for (i, (path, result)) in results.iter().enumerate() {
+        assert_eq!(path, &paths[i]);
+        let doc = &result.as_ref().unwrap()[0];
+        assert_eq!(doc["index"].as_i64(), Some(i as i64));
+        assert_eq!(doc["flag"].as_bool(), Some(true));
+    }
+
+    // Each file takes 19 bytes: only 3 fit in the budget.
+    options.threads(1).max_total_bytes(60);
+    let results = load_files(&paths, &options);
+    assert!(results[..3].iter().all(|(_, result)| result.is_ok()));
+    assert!(results[3..]
+        .iter()
+        .all(|(_, result)| matches!(result, Err(LoadError::BudgetExceeded(60)))));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "rc")]
+#[test]
+fn test_load_shared_files() {
+    use saphyr::{load_shared_files, LoadOptions, StringPool};
+
+    let dir = std::env::temp_dir().join(format!("saphyr-test-shared-files-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let paths: Vec<_> = (0..8)
+        .map(|i| {
+            let path = dir.join(format!("{i}.yaml"));
+            std::fs::write(&path, format!("index: {i}\nkind: service\n")).unwrap();
+            path
+        })
+        .collect();
+    std::fs::write(&paths[7], "kind: [").unwrap();
+
+    let mut pool = StringPool::new();
+    let mut options = LoadOptions::new();
+    options.threads(4);
+    let results = load_shared_files(&paths, &options, &mut pool);
+    assert!(matches!(results[7].1, Err(LoadError::Scan(_))));
+    let docs: Vec<_> = results[..7]
+        .iter()
+        .map(|(_, result)| &result.as_ref().unwrap()[0])
+        .collect();
+    for (i, doc) in docs.iter().enumerate() {
+        assert_eq!(doc["index"].as_i64(), Some(i as i64));
+    }
+    assert!(docs[0]["kind"].shares_contents_with(&docs[6]["kind"]));
+    let (first, _) = docs[0].as_hash().unwrap().front().unwrap();
+    let (last, _) = docs[6].as_hash().unwrap().front().unwrap();
+    assert!(first.shares_contents_with(last));
+    // `index`, `kind` and `service`.
+    assert_eq!(pool.len(), 3);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "cache")]
+#[test]
+fn test_document_cache() {
+    use saphyr::{decode_documents, encode_documents, DocumentCache, MarkedYaml};
+
+    let s = "
+a: [1, -2, 2.5, !!str 3, ~, true]
+b: &x {c: 'd', e: *x}
+---
+- !custom { f: g }
+";
+    let docs = Yaml::load_from_str(s).unwrap();
+    let encoded = encode_documents(&docs);
+    assert_eq!(decode_documents::<Yaml>(&encoded).unwrap(), docs);
+    // Data of the wrong node type or truncated is rejected.
+    assert!(decode_documents::<MarkedYaml>(&encoded).is_err());
+    assert_eq!(
+        decode_documents::<Yaml>(&encoded[..encoded.len() - 1])
+            .unwrap_err()
+            .code()
+            .as_str(),
+        "CACHE001"
+    );
+
+    let dir = std::env::temp_dir().join(format!("saphyr-cache-test-{}", std::process::id()));
+    let cache = DocumentCache::new(&dir);
+    let marked = cache.load_str::<MarkedYaml>(s).unwrap();
+    assert_eq!(cache.load_str::<MarkedYaml>(s).unwrap(), marked);
+    assert_eq!(marked[1].data[0].marker.line(), 5);
+
+    // Entries are only used for their source: a corrupted entry is loaded again.
+    std::fs::write(cache.entry_path::<Yaml>(s), b"corrupted").unwrap();
+    assert_eq!(cache.load_str::<Yaml>(s).unwrap(), docs);
+    assert!(cache.load_str::<Yaml>("a: [").is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_explain_resolution() {
+    use saphyr::{ResolutionRule, Schema};
+
+    let s = "[NULL, True, +12, .inf, 'true', !!int x, !custom 1, yes]";
+    let rules = |schema: Schema| -> Vec<_> {
+        schema
+            .explain_str(s)
+            .unwrap()
+            .into_iter()
+            .map(|resolution| resolution.rule)
+            .collect()
+    };
+    use ResolutionRule::{Bool, Fallback, Float, Int, Null, Style, Tag};
+    assert_eq!(
+        rules(Schema::Legacy),
+        [Fallback, Fallback, Int, Float, Style, Tag, Tag, Fallback]
+    );
+    assert_eq!(
+        rules(Schema::Core),
+        [Null, Bool, Int, Float, Style, Tag, Tag, Fallback]
+    );
+
+    let resolutions = Schema::Core.explain_str(s).unwrap();
+    assert_eq!(resolutions[5].value, Yaml::BadValue);
+    assert_eq!(
+        resolutions[4].to_string(),
+        "`true` at line 1 column 25 is a string via its single-quoted style (only plain scalars \
+         are resolved)"
+    );
+    assert_eq!(
+        resolutions[5].to_string(),
+        "`x` at line 1 column 39 is a bad value via its explicit `!!int` tag"
+    );
+    assert!(Schema::Core.explain_str("[").is_err());
+}
+
+#[test]
+fn test_max_documents() {
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.max_documents(2);
+    assert_eq!(loader.load_from_str("a\n---\nb").unwrap().len(), 2);
+    // An empty stream has no documents.
+    assert!(loader.load_from_str("").unwrap().is_empty());
+
+    let error = loader.load_from_str("a\n---\nb\n--- c\n---\n").unwrap_err();
+    assert!(matches!(error, LoadError::TooManyDocuments(marker) if marker.line() == 4));
+    assert_eq!(error.code().as_str(), "LOAD011");
+    assert_eq!(
+        error.to_string(),
+        "LOAD011: document at line 4 column 1 exceeds the maximum number of documents"
+    );
+}
+
+#[test]
+fn test_load_first_document() {
+    let mut loader = YamlLoader::<Yaml>::default();
+    let mut rest = "a: 1\n... # end\n---\n[b]\n...\nnot: [yaml\n";
+    let (doc, next) = loader.load_first_document(rest).unwrap().unwrap();
+    assert_eq!(doc["a"].as_i64(), Some(1));
+    assert_eq!(next, "---\n[b]\n...\nnot: [yaml\n");
+    rest = next;
+    let (doc, next) = loader.load_first_document(rest).unwrap().unwrap();
+    assert_eq!(doc[0].as_str(), Some("b"));
+    assert_eq!(next, "not: [yaml\n");
+
+    // Offsets are in bytes, past multi-byte characters.
+    let (doc, next) = loader
+        .load_first_document("é: ü\n...\nrest")
+        .unwrap()
+        .unwrap();
+    assert_eq!(doc["é"].as_str(), Some("ü"));
+    assert_eq!(next, "rest");
+    let (_, next) = loader.load_first_document("a\n...").unwrap().unwrap();
+    assert_eq!(next, "");
+
+    let error = loader.load_first_document("a: [\n").unwrap_err();
+    assert_eq!(error.marker().unwrap().line(), 2);
+    assert!(loader.load_first_document("").unwrap().is_none());
+}
+
+#[test]
+fn test_from_str_and_collect() {
+    let doc: Yaml = "a: [1, 2]\nb: ~".parse().unwrap();
+    assert_eq!(doc["a"][1].as_i64(), Some(2));
+    assert!(doc["b"].is_null());
+    assert_eq!("# only a comment".parse::<Yaml>().unwrap(), Yaml::Null);
+    assert!(matches!("a: [".parse::<Yaml>(), Err(LoadError::Scan(_))));
+    assert!(matches!(
+        "---\na\n---\nb".parse::<Yaml>(),
+        Err(LoadError::TooManyDocuments(marker)) if marker.line() == 3
+    ));
+    // `Yaml::value_from_str` only resolves plain scalars.
+    assert_eq!(
+        Yaml::value_from_str("a: ["),
+        Yaml::String("a: [".to_owned())
+    );
+
+    let array: Yaml = doc["a"].as_vec().unwrap().iter().cloned().collect();
+    assert_eq!(array, doc["a"]);
+    let hash: Yaml = doc.as_hash().unwrap().clone().into_iter().rev().collect();
+    assert_eq!(
+        hash.as_hash().unwrap().keys().next().unwrap().as_str(),
+        Some("b")
+    );
+    assert_eq!(hash["a"], doc["a"]);
+}
+
+#[test]
+fn test_index_past_integer_keys() {
+    let doc = &Yaml::load_from_str("{0: zero, -1: minus one}").unwrap()[0];
+    assert_eq!(doc[0].as_str(), Some("zero"));
+    assert!(doc[usize::MAX].is_badvalue());
+    assert_eq!(saphyr::FORBID_UNSAFE, cfg!(feature = "forbid-unsafe"));
+}
+
+#[test]
+fn test_folding_regions() {
+    use saphyr::{folding_regions, FoldingKind};
+
+    let source = "\
+jobs:
+  - name: build
+    script: |
+      cargo build
+
+      cargo test
+
+    # Trailing comments are not folded.
+
+  - { name: lint,
+      script: cargo clippy }
+---
+[a,
+ b]
+";
+    let regions: Vec<_> = folding_regions(source)
+        .map(|region| {
+            let (start, end) = (region.start, region.end);
+            (
+                region.kind,
+                (start.line(), start.col()),
+                (end.line(), end.col()),
+            )
+        })
+        .collect();
+    assert_eq!(
+        regions,
+        [
+            (FoldingKind::BlockScalar, (3, 4), (6, 16)),
+            (FoldingKind::Mapping, (2, 4), (6, 16)),
+            (FoldingKind::Mapping, (10, 4), (11, 28)),
+            (FoldingKind::Sequence, (1, 0), (11, 28)),
+            (FoldingKind::Mapping, (1, 0), (11, 28)),
+            (FoldingKind::Sequence, (13, 0), (14, 3)),
+        ]
+    );
+
+    // Regions completed before an error are still returned.
+    let mut regions = folding_regions("a:\n  b: 1\n  c: 2\nd: [\n  'e\n");
+    let region = regions.next().unwrap();
+    assert_eq!((region.start.line(), region.end.line()), (1, 3));
+    assert!(regions.next().is_none());
+    assert!(regions.error().is_some());
+}
+
+#[test]
+fn test_parse_for_completion() {
+    use saphyr::{parse_for_completion, CompletionKind};
+
+    // The cursor is at `|`.
+    let complete = |source: &str| {
+        let cursor = source.find('|').unwrap();
+        let source = source.replace('|', "");
+        parse_for_completion(&source, cursor).map(|completion| {
+            (
+                completion.kind,
+                completion.path.join("."),
+                completion.prefix,
+            )
+        })
+    };
+    let key = |path: &str, prefix: &str| Some((CompletionKind::Key, path.into(), prefix.into()));
+    let value =
+        |path: &str, prefix: &str| Some((CompletionKind::Value, path.into(), prefix.into()));
+
+    assert_eq!(complete("a:\n  b: 1\n  c|"), key("a", "c"));
+    assert_eq!(complete("a:\n  b: 1\nc|: 2"), key("", "c"));
+    assert_eq!(complete("a:\n  b: 1\n  c: |"), value("a.c", ""));
+    assert_eq!(complete("a:\n  b: no|"), value("a.b", "no"));
+    assert_eq!(complete("a:\n  |"), value("a", ""));
+    assert_eq!(complete("a:\n|"), key("", ""));
+    assert_eq!(complete("? ex|"), key("", "ex"));
+
+    // Sequences, with and without indentation.
+    assert_eq!(complete("items:\n- a\n- |"), value("items.1", ""));
+    assert_eq!(complete("items:\n- a\n|"), key("", ""));
+    assert_eq!(
+        complete("items:\n  - name: a\n    ag|"),
+        key("items.0", "ag")
+    );
+    assert_eq!(complete("items:\n  - name: a\n  - |"), value("items.1", ""));
+    assert_eq!(complete("- - |"), value("0.0", ""));
+
+    // Flow collections, and nodes not closed yet.
+    assert_eq!(complete("a: {b: 1, |}"), key("a", ""));
+    assert_eq!(complete("a: [1, 'tw|"), value("a.1", "'tw"));
+    assert_eq!(complete("a: \"x: y|"), value("a", "\"x: y"));
+    assert_eq!(complete("a: [1, 2]|"), None);
+
+    // Comments and documents.
+    assert_eq!(complete("a: 1 # comm|"), None);
+    assert_eq!(complete("# comm|"), None);
+    assert_eq!(complete("a: 1\n---\nb|"), value("", "b"));
+    assert_eq!(complete("a: 1\n---\nb: 2\nc|"), key("", "c"));
+    assert_eq!(complete("|"), value("", ""));
+
+    // The cursor is moved back to a `char` boundary.
+    let completion = parse_for_completion("é: ü", 5).unwrap();
+    assert_eq!(
+        (completion.prefix.as_str(), completion.start.col()),
+        ("", 3)
+    );
+}
+
+#[test]
+fn test_value_key_policy() {
+    let s = "timeout: { =: 30, unit: s }\nlegacy: &l [{ =: a }, { '=': b }]\nnested: { =: { =: 1 } }\nref: *l";
+    let load = |policy| {
+        let mut loader = YamlLoader::<Yaml>::default();
+        loader.value_key_policy(policy);
+        loader.load_from_str(s).map(|docs| docs[0].clone())
+    };
+
+    let doc = load(ValueKeyPolicy::Verbatim).unwrap();
+    assert_eq!(doc["timeout"]["="].as_i64(), Some(30));
+    assert_eq!(doc["timeout"]["unit"].as_str(), Some("s"));
+
+    let doc = load(ValueKeyPolicy::Collapse).unwrap();
+    assert_eq!(doc["timeout"].as_i64(), Some(30));
+    assert_eq!(doc["legacy"][0].as_str(), Some("a"));
+    // Quoted keys are strings.
+    assert_eq!(doc["legacy"][1]["="].as_str(), Some("b"));
+    assert_eq!(doc["nested"].as_i64(), Some(1));
+    assert_eq!(doc["ref"], doc["legacy"]);
+
+    match load(ValueKeyPolicy::Reject) {
+        Err(error @ LoadError::ValueKey(_)) => {
+            assert_eq!(error.code().as_str(), "LOAD013");
+            assert_eq!(error.marker().map(|marker| marker.col()), Some(11));
+        }
+        other => panic!("unexpected result: {other:?}"),
+    }
+    // Values are not affected.
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.value_key_policy(ValueKeyPolicy::Reject);
+    assert_eq!(
+        loader.load_from_str("a: =").unwrap()[0]["a"].as_str(),
+        Some("=")
+    );
+}
+
+#[test]
+fn test_reject_adjacent_values() {
+    let load = |s| {
+        let mut loader = YamlLoader::<Yaml>::default();
+        loader.reject_adjacent_values(true);
+        loader.load_from_str(s).map(|docs| docs[0].clone())
+    };
+    for s in [
+        "{\"a\": b, 'c': [d], [e]: f}",
+        "[\"a\": b]",
+        "{\"a\":, \"b\":}",
+        "a:b",
+        "\"a\": {\"b\" : c}",
+    ] {
+        assert!(load(s).is_ok(), "{s:?}");
+    }
+    for (s, col) in [
+        ("{\"a\":b}", 5),
+        ("[a, \"b\":c]", 8),
+        ("{a: {[b]:[c]}}", 9),
+        ("{'a':&x b, c: *x}", 5),
+        ("{\"a\":!!str 1}", 5),
+    ] {
+        match load(s) {
+            Err(LoadError::AdjacentValue(marker)) => assert_eq!(marker.col(), col, "{s:?}"),
+            other => panic!("unexpected result for {s:?}: {other:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_load_from_bytes() {
+    use saphyr::Utf8Policy;
+
+    let bytes = b"\xef\xbb\xbfa: \xe2\x82\xac\nb: caf\xe9\nc: \xf0\x9f";
+    let doc = &Yaml::load_from_bytes(bytes, Utf8Policy::Lossy).unwrap()[0];
+    assert_eq!(doc["a"].as_str(), Some("\u{20AC}"));
+    assert_eq!(doc["b"].as_str(), Some("caf\u{FFFD}"));
+    // An incomplete sequence at the end is a single invalid sequence.
+    assert_eq!(doc["c"].as_str(), Some("\u{FFFD}"));
+
+    let doc = &Yaml::load_from_bytes(bytes, Utf8Policy::Latin1Fallback).unwrap()[0];
+    assert_eq!(doc["b"].as_str(), Some("caf\u{E9}"));
+    assert_eq!(doc["c"].as_str(), Some("\u{F0}\u{9F}"));
+
+    match Yaml::load_from_bytes(bytes, Utf8Policy::Strict) {
+        Err(error @ LoadError::InvalidUtf8 { offset, marker }) => {
+            assert_eq!(offset, 16);
+            assert_eq!((marker.line(), marker.col()), (2, 6));
+            assert!(error
+                .to_string()
+                .starts_with("LOAD015: invalid UTF-8 at byte 16"));
+        }
+        other => panic!("unexpected result: {other:?}"),
+    }
+    assert_eq!(
+        Yaml::load_from_bytes(b"a: 1", Utf8Policy::Strict).unwrap(),
+        Yaml::load_from_str("a: 1").unwrap()
+    );
+}
+
+#[test]
+fn test_report_error_documents() {
+    let load = |s| {
+        let mut loader = YamlLoader::<Yaml>::default();
+        loader
+            .report_error_documents(true)
+            .real_key_policy(RealKeyPolicy::Reject)
+            .reject_trailing_content(true);
+        loader.load_from_str(s).unwrap_err()
+    };
+    let document = |error: &LoadError| match error {
+        LoadError::InDocument { index, start, .. } => (*index, start.line()),
+        other => panic!("unexpected error: {other:?}"),
+    };
+
+    let error = load("a: 1\n---\nb: 2\n---\n1.5: c\n");
+    assert_eq!(document(&error), (2, 4));
+    assert!(
+        matches!(&error, LoadError::InDocument { error, .. } if matches!(**error, LoadError::RealKey(_)))
+    );
+    assert_eq!(error.code().as_str(), "LOAD003");
+    assert_eq!(error.marker().map(|marker| marker.line()), Some(5));
+    assert_eq!(
+        error.to_string(),
+        "LOAD003: in document 3 starting at line 4: real number used as a mapping key at line 5 column 1"
+    );
+    // The scanner fails on the first token of a document, before the document starts.
+    assert_eq!(document(&load("a: 1\n---\n]\n")), (1, 2));
+    assert_eq!(document(&load("]")), (0, 1));
+    // Trailing contents are found once the stream is loaded.
+    assert_eq!(document(&load("a: 1\n...\nb: 2\n---\nc: 3\n")), (1, 3));
+
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.real_key_policy(RealKeyPolicy::Reject);
+    assert!(matches!(
+        loader.load_from_str("a: 1\n---\n1.5: b"),
+        Err(LoadError::RealKey(_))
+    ));
+}
+
+#[test]
+fn test_resolve_block_scalar() {
+    use saphyr::resolve_block_scalar;
+
+    let body = " a\n b\n\n  c\n d\n\n";
+    assert_eq!(resolve_block_scalar(">", body).unwrap(), "a b\n\n c\nd\n");
+    assert_eq!(
+        resolve_block_scalar(">+", body).unwrap(),
+        "a b\n\n c\nd\n\n"
+    );
+    assert_eq!(
+        resolve_block_scalar("|- # comment", body).unwrap(),
+        "a\nb\n\n c\nd"
+    );
+    assert_eq!(
+        resolve_block_scalar("|", "  a\n# comment\n").unwrap(),
+        "a\n"
+    );
+    // The contents are those the loader gives.
+    for (header, body) in [(">-", body), ("|", ""), ("|+", "\n\n"), ("|2", "   a\n  b")] {
+        let doc = &Yaml::load_from_str(&format!("_: {header}\n{body}")).unwrap()[0];
+        assert_eq!(
+            doc["_"].as_str(),
+            resolve_block_scalar(header, body).ok().as_deref(),
+            "{header:?} {body:?}"
+        );
+    }
+
+    for header in ["", "a", "|0", "|++", "|\n"] {
+        assert!(resolve_block_scalar(header, " a").is_err(), "{header:?}");
+    }
+    let error = resolve_block_scalar("|", "  a\nb: c\n").unwrap_err();
+    assert_eq!((error.marker().line(), error.marker().col()), (3, 0));
+    let error = resolve_block_scalar("|2", " a\n").unwrap_err();
+    assert_eq!(error.marker().line(), 2);
+}
+
+#[test]
+fn test_duplicate_key_policy() {
+    use saphyr::{DuplicateKeyPolicy, LoadWarning};
+
+    let source = "a: 1\nb:\n  c: 2\n  \"c\": 3\n? [x]\n: 4\n? [x]\n: 5\na: { a: 6 }";
+    let mut loader = YamlLoader::<Yaml>::default();
+    let docs = loader.load_from_str(source).unwrap();
+    assert!(loader.warnings().is_empty());
+    assert_eq!(docs[0]["a"]["a"].as_i64(), Some(6));
+
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.duplicate_key_policy(DuplicateKeyPolicy::Warn);
+    let docs = loader.load_from_str(source).unwrap();
+    assert_eq!(docs[0]["b"]["c"].as_i64(), Some(3));
+    let positions: Vec<_> = loader
+        .warnings()
+        .iter()
+        .map(|warning| match warning {
+            LoadWarning::DuplicateKey { key, first, second } => (
+                key.as_str(),
+                (first.line(), first.col()),
+                (second.line(), second.col()),
+            ),
+            _ => panic!("unexpected warning {warning:?}"),
+        })
+        .collect();
+    assert_eq!(
+        positions,
+        [
+            ("c", (3, 2), (4, 2)),
+            ("?", (5, 2), (7, 2)),
+            ("a", (1, 0), (9, 0)),
+        ]
+    );
+
+    // Keys are compared once resolved, and only within the same mapping.
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.duplicate_key_policy(DuplicateKeyPolicy::Reject);
+    assert!(loader
+        .load_from_str("a: { a: 1 }\nb: [{ a: 2 }, { a: 3 }]")
+        .is_ok());
+    let error = loader.load_from_str("- 0x10: a\n  16: b").unwrap_err();
+    assert!(matches!(
+        &error,
+        LoadError::DuplicateKey { key, first, second }
+            if key == "16" && first.line() == 1 && second.line() == 2
+    ));
+    assert_eq!(
+        error.to_string(),
+        "LOAD016: key \"16\" at line 1 column 3 is repeated at line 2 column 3"
+    );
+}
+
+#[test]
+fn test_paths() {
+    let doc = &Yaml::load_from_str(
+        "a:\n  - !!map { b: 1 }\n  - []\n'': x\n\"x y\": {1: a, 1.5: b, null: c, [0]: d}",
+    )
+    .unwrap()[0];
+    let paths: Vec<_> = doc.paths().map(|entry| (entry.path, entry.kind)).collect();
+    assert_eq!(
+        paths,
+        [
+            ("a".to_owned(), "sequence"),
+            ("a[0]".to_owned(), "mapping"),
+            ("a[0].b".to_owned(), "integer"),
+            ("a[1]".to_owned(), "sequence"),
+            ("[\"\"]".to_owned(), "string"),
+            ("[\"x y\"]".to_owned(), "mapping"),
+            ("[\"x y\"].1".to_owned(), "string"),
+            ("[\"x y\"][\"1.5\"]".to_owned(), "string"),
+            ("[\"x y\"].~".to_owned(), "string"),
+            ("[\"x y\"][?]".to_owned(), "string"),
+        ]
+    );
+    let entry = doc.paths().nth(2).unwrap();
+    assert_eq!(entry.node, &doc["a"][0]["b"]);
+    assert_eq!(Yaml::Integer(1).paths().count(), 0);
+}
+
+#[test]
+fn test_key_allowlist() {
+    use saphyr::{KeyAllowlist, MarkedYaml};
+
+    let docs = MarkedYaml::load_from_str(
+        "a:\n  b: 1\n  c: [{d: 2, e: 3}]\n\"x.y\": !!map {z: 4}\n? [k]\n: 5\nf: {g: {h: 6}}",
+    )
+    .unwrap();
+    let unknown = |patterns: &[&str]| {
+        let mut allowlist = KeyAllowlist::new();
+        for pattern in patterns {
+            allowlist.allow(pattern);
+        }
+        allowlist
+            .unknown_keys(&docs[0])
+            .into_iter()
+            .map(|key| (key.path, key.marker.line(), key.marker.col()))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        unknown(&[]),
+        [
+            ("a".to_owned(), 1, 0),
+            ("[\"x.y\"]".to_owned(), 4, 0),
+            ("[?]".to_owned(), 5, 2),
+            ("f".to_owned(), 7, 0)
+        ]
+    );
+    assert!(unknown(&["**"]).is_empty());
+    assert_eq!(
+        unknown(&["a.b", "a.c[0].d", "[\"x.y\"].*", "[?]", "f.**.h"]),
+        [("a.c[0].e".to_owned(), 3, 13)]
+    );
+    assert_eq!(
+        unknown(&["a.*", "a.c[*].*", "x.y.z", "f.g"]),
+        [
+            ("[\"x.y\"]".to_owned(), 4, 0),
+            ("[?]".to_owned(), 5, 2),
+            ("f.g.h".to_owned(), 7, 8)
+        ]
+    );
+    // Keys under sequences require an index in the pattern.
+    assert_eq!(
+        unknown(&["a.c.d", "a.b", "[\"x.y\"].z", "*"]),
+        [
+            ("a.c[0].d".to_owned(), 3, 7),
+            ("a.c[0].e".to_owned(), 3, 13),
+            ("f.g".to_owned(), 7, 4)
+        ]
+    );
+}
+
+#[test]
+fn test_merge_from() {
+    use saphyr::{MarkedYaml, Provenance};
+
+    let layers = [
+        ("base.yaml", "a: {b: 1, c: [1, 2]}\nd: {e: 2}\nf: 3"),
+        ("env.yaml", "a: {c: [3]}\nd: 4\ng: {}"),
+        ("local.yaml", "d: {h: 5}\nf: 6"),
+    ];
+    let expected = Yaml::load_from_str("a: {b: 1, c: [3]}\nd: {h: 5}\nf: 6\ng: {}").unwrap();
+
+    let mut merged = Yaml::Null;
+    for (_, text) in layers {
+        merged.merge_from(Yaml::load_from_str(text).unwrap().remove(0));
+    }
+    assert_eq!(merged, expected[0]);
+
+    let mut merged = Yaml::Null;
+    let mut provenance = Provenance::new();
+    for (source, text) in layers {
+        let doc = MarkedYaml::load_from_str(text).unwrap().remove(0);
+        provenance.merge(&mut merged, &doc, source);
+    }
+    assert_eq!(merged, expected[0]);
+    let origins: Vec<_> = provenance
+        .iter()
+        .map(|(path, origin)| {
+            (
+                path,
+                origin.source.as_str(),
+                origin.marker.line(),
+                origin.marker.col(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        origins,
+        [
+            ("a.b", "base.yaml", 1, 7),
+            ("a.c[0]", "env.yaml", 1, 8),
+            ("d.h", "local.yaml", 1, 7),
+            ("f", "local.yaml", 2, 3),
+            ("g", "env.yaml", 3, 3),
+        ]
+    );
+
+    // Merging a scalar document replaces everything.
+    provenance.merge(
+        &mut merged,
+        &MarkedYaml::load_from_str("x").unwrap()[0],
+        "x",
+    );
+    assert_eq!(merged.as_str(), Some("x"));
+    assert_eq!(provenance.len(), 1);
+    assert_eq!(provenance.get("").unwrap().source, "x");
+}
+
+#[test]
+fn test_config_stack() {
+    use saphyr::{ConfigStack, LookupErrorKind, MergeStrategy};
+
+    let mut config = ConfigStack::new();
+    config
+        .add_str("a", "x: {y: [1], z: 1.5}\nlist: [1]\nflag: yes")
+        .unwrap()
+        .strategy(MergeStrategy::AppendSequences)
+        .add_str("b", "list: [2]\nx: {y: [2]}\n---\nlist: [3]")
+        .unwrap();
+    assert_eq!(
+        config.get("list"),
+        Yaml::load_from_str("[1, 2, 3]").unwrap().first()
+    );
+    assert_eq!(config.origin("list[2]").unwrap().source, "b");
+    assert_eq!(config.get_i64("x.y[1]"), Ok(2));
+    assert_eq!(config.get_f64("x.z"), Ok(1.5));
+    assert_eq!(config.get_f64("x.y[0]"), Ok(1.0));
+    assert!(config.add_str("c", "[").is_err());
+
+    config
+        .strategy(MergeStrategy::Shallow)
+        .add_str("c", "x: {w: true}")
+        .unwrap();
+    assert!(config.get("x.y").is_none());
+    assert_eq!(config.get_bool("x.w"), Ok(true));
+    assert_eq!(config.origin("x.w").unwrap().source, "c");
+    assert!(config.origin("x.z").is_none());
+
+    let error = config.get_bool("flag").unwrap_err();
+    assert_eq!(
+        error.kind,
+        LookupErrorKind::TypeMismatch {
+            expected: "boolean",
+            found: "string"
+        }
+    );
+    assert_eq!(error.path, ["flag"]);
+    assert_eq!(config.origin("flag").unwrap().marker.line(), 3);
+    let error = config.get_str("x.v").unwrap_err();
+    assert_eq!(error.kind, LookupErrorKind::MissingKey);
+    assert_eq!(error.to_string(), "LOOKUP001: missing key `v` at `x`");
+    let error = config.get_i64("x.w[0]").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "LOOKUP003: expected sequence at `x.w`, found boolean"
+    );
+    assert!(config.add_file("/nonexistent/config.yaml").is_err());
+}
+
+#[test]
+fn test_fingerprinted() {
+    use saphyr::Fingerprinted;
+
+    let doc = Yaml::load_from_str("a: [1, 2]\nb: {c: true}")
+        .unwrap()
+        .remove(0);
+    let desired = Fingerprinted::new(doc.clone());
+    let mut actual = Fingerprinted::from(doc.clone());
+    assert_eq!(actual.fingerprint(), desired.fingerprint());
+    assert_eq!(actual, desired);
+
+    actual.get_mut()["b"]["c"] = Yaml::Boolean(false);
+    assert_ne!(actual, desired);
+    assert_eq!(desired.get(), &doc);
+
+    let copy = actual.clone();
+    actual.get_mut()["b"]["c"] = Yaml::Boolean(true);
+    assert_eq!(actual, desired);
+    assert_ne!(copy, actual);
+    assert_eq!(copy.get()["b"]["c"], Yaml::Boolean(false));
+
+    assert_eq!(actual.into_inner(), doc);
+}
+
+#[test]
+fn test_fingerprinted_subtrees() {
+    use saphyr::{Fingerprinted, LookupErrorKind};
+
+    let doc = Yaml::load_from_str("a: [1, {b: 2}]\n3: !!set {c}\nd: x")
+        .unwrap()
+        .remove(0);
+    let desired = Fingerprinted::new(doc.clone());
+    let mut actual = desired.clone();
+    assert_eq!(
+        actual.fingerprint_at(&["a", "1"]),
+        desired.fingerprint_at(&["a", "1"])
+    );
+
+    *actual.get_mut_at(&["a", "1", "b"]).unwrap() = Yaml::Integer(4);
+    assert_ne!(actual, desired);
+    assert_ne!(
+        actual.fingerprint_at(&["a", "1"]),
+        desired.fingerprint_at(&["a", "1"])
+    );
+    assert_eq!(
+        actual.fingerprint_at(&["a", "0"]),
+        desired.fingerprint_at(&["a", "0"])
+    );
+    assert_eq!(
+        actual.fingerprint_at(&["3"]),
+        desired.fingerprint_at(&["3"])
+    );
+    assert_eq!(desired.get(), &doc);
+
+    *actual.get_mut_at(&["a", "1", "b"]).unwrap() = Yaml::Integer(2);
+    assert_eq!(actual.fingerprint(), desired.fingerprint());
+    assert_eq!(actual, desired);
+    assert_eq!(
+        Fingerprinted::new(doc.clone()).fingerprint_at(&["3", "c"]),
+        actual.fingerprint_at(&["3", "c"])
+    );
+
+    let error = actual.get_mut_at(&["a", "2"]).unwrap_err();
+    assert_eq!(error.kind, LookupErrorKind::IndexOutOfRange(2));
+    assert!(actual.fingerprint_at(&["d", "e"]).is_err());
+}
+
+#[test]
+fn test_bool_lexemes() {
+    use saphyr::{BoolLexemes, Schema, YamlEmitter, YamlLoader};
+
+    let source = "\
+a: &flag yes
+b: [True, OFF, *flag, y]
+? [on]
+: No
+c: !!bool On
+---
+- off
+";
+    assert_eq!(
+        Schema::Yaml11Booleans.resolve_plain("Yes"),
+        Yaml::Boolean(true)
+    );
+    assert_eq!(
+        Schema::Yaml11Booleans.resolve_plain("n"),
+        Yaml::String("n".into())
+    );
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.schema(Schema::Yaml11Booleans);
+    let docs = loader.load_from_str(source).unwrap();
+    let lexemes = BoolLexemes::scan_str(source, Schema::Yaml11Booleans).unwrap();
+    assert_eq!(lexemes.len(), 2);
+    assert_eq!(lexemes[0].len(), 6);
+    assert_eq!(lexemes[0].get("b[2]"), Some("yes"));
+    assert_eq!(lexemes[0].get("[?]"), Some("No"));
+    assert_eq!(lexemes[1].get("[0]"), Some("off"));
+
+    let mut doc = docs[0].clone();
+    doc["b"][1] = Yaml::Boolean(true);
+    let mut output = String::new();
+    let mut emitter = YamlEmitter::new(&mut output);
+    emitter.bool_lexemes(&lexemes[0]);
+    emitter.dump(&doc).unwrap();
+    assert_eq!(
+        output,
+        "---\na: yes\nb:\n  - True\n  - true\n  - yes\n  - y\n? - true\n: No\nc: On"
+    );
+
+    let mut lexemes = BoolLexemes::new();
+    assert!(lexemes.is_empty());
+    assert_eq!(lexemes.insert("", "ON"), None);
+    assert_eq!(lexemes.iter().collect::<Vec<_>>(), [("", "ON")]);
+    let mut output = String::new();
+    let mut emitter = YamlEmitter::new(&mut output);
+    emitter.bool_lexemes(&lexemes);
+    emitter.dump(&Yaml::Boolean(true)).unwrap();
+    assert_eq!(output, "---\nON");
+}
+
+#[test]
+fn test_marker_ext() {
+    use saphyr::{MarkedYaml, Marker, MarkerExt};
+    use std::cmp::Ordering;
+
+    let text = "a: é\nbb: [1]\n";
+    let docs = MarkedYaml::load_from_str(text).unwrap();
+    let one = &docs[0].data["bb"].data[0];
+    assert_eq!(one.marker.to_byte_offset(text), text.find('1').unwrap());
+    assert_eq!(
+        Marker::from_byte_offset(text, text.find('1').unwrap()),
+        one.marker
+    );
+    // Offsets within a `char` move back to its start.
+    let e = Marker::from_byte_offset(text, 4);
+    assert_eq!((e.index(), e.line(), e.col()), (3, 1, 3));
+    let end = Marker::from_byte_offset(text, 100);
+    assert_eq!((end.index(), end.line(), end.col()), (13, 3, 0));
+    assert_eq!(end.to_byte_offset(text), text.len());
+    assert_eq!(e.cmp_position(&one.marker), Ordering::Less);
+    assert_eq!(end.cmp_position(&end), Ordering::Equal);
+}
+
+#[test]
+fn test_report_error_collections() {
+    use saphyr::{LoadError, YamlLoader};
+    use std::error::Error;
+
+    let source = "key: [1]\n---\n- a\n- [b, @]\n";
+    let mut loader = YamlLoader::<Yaml>::default();
+    assert!(matches!(
+        loader.load_from_str(source),
+        Err(LoadError::Scan(_))
+    ));
+
+    loader.report_error_collections(true);
+    let error = loader.load_from_str(source).unwrap_err();
+    let LoadError::InCollection { kind, start, .. } = &error else {
+        panic!("{error:?}");
+    };
+    // The scanner fails before the parser starts the flow sequence, so the block sequence
+    // containing it is reported.
+    assert_eq!((*kind, start.line(), start.col()), ("sequence", 3, 0));
+    assert_eq!(error.code().as_str(), "SCAN034");
+    assert_eq!(error.marker().unwrap().col(), 6);
+    assert_eq!(
+        error.to_string(),
+        "SCAN034: in the sequence starting at line 3 column 1: unexpected character: `@' \
+         at byte 23 line 4 column 7"
+    );
+    let source_error = error.source().unwrap().downcast_ref::<LoadError>().unwrap();
+    assert!(matches!(source_error, LoadError::Scan(_)));
+
+    loader.report_error_documents(true);
+    let error = loader.load_from_str(source).unwrap_err();
+    let LoadError::InDocument {
+        index: 1, error, ..
+    } = error
+    else {
+        panic!("{error:?}");
+    };
+    assert!(matches!(*error, LoadError::InCollection { .. }));
+    // Errors outside of collections are not wrapped.
+    assert!(matches!(
+        loader.load_from_str("'a"),
+        Err(LoadError::InDocument { error, .. }) if matches!(*error, LoadError::Scan(_))
+    ));
+}
+
+#[test]
+fn test_parse_embedded() {
+    use saphyr::{EmbeddedError, LoadError, LookupErrorKind, MarkedYaml};
+
+    let source = "\
+kind: ConfigMap
+data:
+  config.yaml: |
+    name: web
+
+    ports: [80, 443]
+  inline: 'a: [1, 2]'
+  folded: >
+    [1,
+    2]
+  broken: |
+    a: [1
+  count: 3
+";
+    let doc = &Yaml::load_from_str(source).unwrap()[0];
+    let embedded = doc.parse_embedded(&["data", "config.yaml"]).unwrap();
+    assert_eq!(embedded[0]["ports"][1].as_i64(), Some(443));
+    assert!(matches!(
+        doc.parse_embedded(&["data", "count"]),
+        Err(EmbeddedError::NotAString(error)) if error.found == "integer"
+    ));
+    assert!(matches!(
+        doc.parse_embedded(&["data", "missing"]),
+        Err(EmbeddedError::Lookup(error)) if error.kind == LookupErrorKind::MissingKey
+    ));
+
+    let doc = &MarkedYaml::load_from_str(source).unwrap()[0];
+    let position = |node: &MarkedYaml| (node.marker.line(), node.marker.col());
+    let embedded = doc
+        .parse_embedded(&["data", "config.yaml"], source)
+        .unwrap();
+    assert_eq!(position(&embedded[0].data["name"]), (4, 10));
+    let ports = &embedded[0].data["ports"];
+    assert_eq!(position(&ports.data[1]), (6, 16));
+    assert_eq!(ports.data[1].marker.index(), source.find("443").unwrap());
+    let embedded = doc.parse_embedded(&["data", "inline"], source).unwrap();
+    assert_eq!(position(&embedded[0].data["a"].data[1]), (7, 18));
+    // Folded text is not written as it is loaded: markers are relative to the string.
+    let embedded = doc.parse_embedded(&["data", "folded"], source).unwrap();
+    assert_eq!(position(&embedded[0].data[1]), (1, 4));
+
+    let error = doc.parse_embedded(&["data", "broken"], source).unwrap_err();
+    let EmbeddedError::Load(LoadError::Scan(error)) = &error else {
+        panic!("{error:?}");
+    };
+    assert_eq!(error.marker().line(), 13);
+}
+
+#[test]
+fn test_real_round_trip() {
+    use saphyr::{parse_real, MarkedYaml, YamlEmitter};
+
+    let values = [
+        0.1,
+        1.0 / 3.0,
+        -0.0,
+        5e-324,
+        f64::MIN_POSITIVE,
+        f64::MAX,
+        -1e300,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+    ];
+    for value in values {
+        let doc = Yaml::Array(vec![Yaml::Real(format!("{value:?}"))]);
+        let mut output = String::new();
+        YamlEmitter::new(&mut output).dump(&doc).unwrap();
+        let loaded = &Yaml::load_from_str(&output).unwrap()[0][0];
+        let parsed = loaded.as_f64().unwrap();
+        assert_eq!(parsed.to_bits(), value.to_bits(), "{output}");
+        let marked = &MarkedYaml::load_from_str(&output).unwrap()[0].data[0];
+        assert_eq!(
+            marked.data.as_f64().map(f64::to_bits),
+            Some(value.to_bits())
+        );
+    }
+    assert!(Yaml::load_from_str("NaN").unwrap()[0]
+        .as_f64()
+        .unwrap()
+        .is_nan());
+
+    for (text, real) in [
+        ("1e3", Some(1000.0)),
+        ("+1.5", Some(1.5)),
+        (".5", Some(0.5)),
+        ("-.Inf", Some(f64::NEG_INFINITY)),
+        ("infinity", Some(f64::INFINITY)),
+        ("1_000.5", None),
+        ("190:20:30.15", None),
+        ("0x1p3", None),
+        ("", None),
+    ] {
+        assert_eq!(parse_real(text), real, "{text}");
+        // Plain scalars are loaded as reals by the same definition.
+        let loaded = Yaml::load_from_str(&format!("[{text}]")).unwrap().remove(0);
+        assert_eq!(loaded[0].as_f64(), real, "{text}");
+    }
+}
+
+#[test]
+fn test_to_json_compatible() {
+    use saphyr::{JsonLoss, JsonLossKind, JsonLossPolicy, Tag};
+
+    let mut doc = Yaml::load_from_str(
+        "\
+name: web
+ratios: [1e3, .5, .nan]
+1: one
+'1': uno
+true: yes
+? [a, b]
+: dropped
+missing: ~
+",
+    )
+    .unwrap()
+    .remove(0);
+    let Yaml::Hash(hash) = &mut doc else {
+        panic!("{doc:?}");
+    };
+    let tag = Tag {
+        handle: "!".into(),
+        suffix: "custom".into(),
+    };
+    hash.insert(
+        Yaml::String("tagged".into()),
+        Yaml::Tagged(tag, Box::new(Yaml::Integer(3))),
+    );
+    hash.insert(Yaml::String("alias".into()), Yaml::Alias(0));
+
+    let projection = doc.to_json_compatible(JsonLossPolicy::Report).unwrap();
+    let mut expected = Yaml::load_from_str(
+        "\
+name: web
+ratios: [1000.0, 0.5, .nan]
+'1': uno
+'true': yes
+missing: ~
+tagged: 3
+alias: ~
+",
+    )
+    .unwrap()
+    .remove(0);
+    // `.nan` is kept as the string of its text.
+    expected["ratios"][2] = Yaml::String(".nan".into());
+    assert_eq!(projection.value, expected);
+
+    let losses: Vec<_> = projection
+        .losses
+        .iter()
+        .map(|loss| (loss.path.as_str(), loss.code().as_str()))
+        .collect();
+    assert_eq!(
+        losses,
+        [
+            ("ratios[2]", "JSON004"),
+            ("1", "JSON001"),
+            ("1", "JSON003"),
+            ("true", "JSON001"),
+            ("[?]", "JSON002"),
+            ("tagged", "JSON005"),
+            ("alias", "JSON006"),
+        ]
+    );
+    assert_eq!(
+        projection.losses[0].to_string(),
+        "JSON004: real which is not a JSON number turned into a string at `ratios[2]`"
+    );
+
+    assert_eq!(
+        doc.to_json_compatible(JsonLossPolicy::Reject),
+        Err(JsonLoss {
+            path: "ratios[2]".into(),
+            kind: JsonLossKind::StringifiedReal,
+        })
+    );
+    let clean = Yaml::load_from_str("a: [1, 2.5, x]").unwrap().remove(0);
+    let projection = clean.to_json_compatible(JsonLossPolicy::Reject).unwrap();
+    assert_eq!(projection.value, clean);
+    assert!(projection.losses.is_empty());
+}
+
+#[test]
+fn test_anchor_graph() {
+    use saphyr::AnchorGraph;
+
+    let source = "\
+base: &base
+  retries: 3
+defaults: &defaults
+  policy: *base
+  name: &name web
+jobs:
+  - *defaults
+  - *defaults
+*name : named
+---
+again: &base [1]
+copy: *base
+";
+    let graph = AnchorGraph::scan_str(source).unwrap();
+    let anchors: Vec<_> = graph
+        .anchors
+        .iter()
+        .map(|anchor| {
+            (
+                anchor.name.as_str(),
+                anchor.document,
+                anchor.path.as_deref(),
+                anchor.within,
+                anchor.marker.line(),
+                anchor.marker.col(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        anchors,
+        [
+            ("base", 0, Some("base"), None, 1, 6),
+            ("defaults", 0, Some("defaults"), None, 3, 10),
+            ("name", 0, Some("defaults.name"), Some(1), 5, 8),
+            ("base", 1, Some("again"), None, 11, 7),
+        ]
+    );
+    let aliases: Vec<_> = graph
+        .aliases
+        .iter()
+        .map(|alias| {
+            (
+                alias.anchor,
+                alias.document,
+                alias.path.as_deref(),
+                alias.within,
+                alias.marker.line(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        aliases,
+        [
+            (0, 0, Some("defaults.policy"), Some(1), 4),
+            (1, 0, Some("jobs[0]"), None, 7),
+            (1, 0, Some("jobs[1]"), None, 8),
+            (2, 0, None, None, 9),
+            (3, 1, Some("copy"), None, 12),
+        ]
+    );
+    assert_eq!(graph.aliases_of(1).count(), 2);
+    assert_eq!(
+        graph.to_dot(),
+        "\
+digraph anchors {
+  anchor0 [shape=box, label=\"&base\\nbase\"];
+  anchor1 [shape=box, label=\"&defaults\\ndefaults\"];
+  anchor2 [shape=box, label=\"&name\\ndefaults.name\"];
+  anchor3 [shape=box, label=\"&base\\nagain\"];
+  document0 [shape=plaintext, label=\"document 0\"];
+  document1 [shape=plaintext, label=\"document 1\"];
+  anchor1 -> anchor0 [label=\"defaults.policy\"];
+  document0 -> anchor1 [label=\"jobs[0]\"];
+  document0 -> anchor1 [label=\"jobs[1]\"];
+  document0 -> anchor2 [label=\"?\"];
+  document1 -> anchor3 [label=\"copy\"];
+}
+"
+    );
+}
+
+#[test]
+fn test_normalized() {
+    use saphyr::Tag;
+
+    let tag = |suffix: &str| Tag {
+        handle: "!".into(),
+        suffix: suffix.into(),
+    };
+    let source = "\
+defaults: &defaults
+  retries: 3
+jobs:
+  - *defaults
+  - !!omap [a: 1, b: 2]
+";
+    let mut doc = Yaml::load_from_str(source).unwrap().remove(0);
+    let Yaml::Hash(hash) = &mut doc else {
+        panic!("{doc:?}");
+    };
+    hash.insert(
+        Yaml::Tagged(tag("key"), Box::new(Yaml::String("tagged".into()))),
+        Yaml::Tagged(
+            tag("outer"),
+            Box::new(Yaml::Tagged(
+                tag("inner"),
+                Box::new(Yaml::Array(vec![Yaml::Tagged(
+                    tag("item"),
+                    Box::new(Yaml::Integer(1)),
+                )])),
+            )),
+        ),
+    );
+    hash.insert(Yaml::String("alias".into()), Yaml::Alias(1));
+
+    let expected = Yaml::load_from_str(
+        "\
+defaults: {retries: 3}
+jobs:
+  - {retries: 3}
+  - [{a: 1}, {b: 2}]
+tagged: [1]
+alias: ~
+",
+    )
+    .unwrap()
+    .remove(0);
+    let mut normalized = doc.normalized();
+    assert_eq!(normalized["alias"], Yaml::BadValue);
+    normalized["alias"] = Yaml::Null;
+    assert_eq!(normalized, expected);
+    assert_eq!(normalized.normalized(), normalized);
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_event_trace() {
+    use saphyr::{EventTrace, YamlLoader};
+
+    let s = "
+a: &x [1, 'two\\', \"x\\ty\", !!str 3]
+b: *x
+c: !custom |
+  a
+---
+a: two
+";
+    let load = |trace: &EventTrace| {
+        let mut loader = YamlLoader::<Yaml>::default();
+        trace.replay(&mut loader);
+        loader.into_documents()
+    };
+    let trace = EventTrace::record_str(s).unwrap();
+    assert_eq!(load(&trace), Yaml::load_from_str(s).unwrap());
+    let text = trace.to_string();
+    assert!(text.contains("+SEQ &1\n"));
+    assert!(text.contains("=VAL 'two\\\\\n"));
+    assert!(text.contains("=VAL \"x\\ty\n"));
+    assert!(text.contains("=VAL <tag:yaml.org,2002: str> :3\n"));
+    assert!(text.contains("=ALI *1\n"));
+    assert!(text.contains("=VAL <! custom> |a\\n\n"));
+    assert_eq!(text.parse::<EventTrace>().unwrap(), trace);
+
+    let redacted = load(&trace.redacted());
+    assert_eq!(
+        redacted,
+        Yaml::load_from_str(
+            "
+s1: &x [1, s2, s3, s4]
+s5: *x
+s6: s7
+---
+s1: s8
+"
+        )
+        .unwrap()
+    );
+
+    let error = "+STR\n+DOC\n=VAL ?x\n".parse::<EventTrace>().unwrap_err();
+    assert_eq!(error.line, 3);
+    assert_eq!(error.to_string(), "TRACE001: invalid event at line 3");
+    assert!(EventTrace::record_str("a: [").is_err());
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn test_token_trace() {
+    use saphyr::{TScalarStyle, TokenTrace, TokenType};
+
+    let s = "%YAML 1.2\n%TAG !e! tag:example.com,2000:\n---\n- !e!x &a 'key'\n- *a\n- |\n  x\ty\n";
+    let trace = TokenTrace::record_str(s).unwrap();
+    assert!(trace.tokens().contains(&TokenType::Anchor("a".to_owned())));
+    let text = trace.to_string();
+    assert!(text.starts_with("STREAM-START\nVERSION-DIRECTIVE 1.2\n"));
+    assert!(text.contains("TAG-DIRECTIVE !e! tag:example.com,2000:\n"));
+    assert!(text.contains("TAG !e! x\nANCHOR a\nSCALAR 'key\n"));
+    assert!(text.contains("SCALAR |x\\ty\\n\n"));
+    assert_eq!(text.parse::<TokenTrace>().unwrap(), trace);
+
+    let redacted = trace.redacted();
+    assert!(redacted.tokens().contains(&TokenType::Scalar(
+        TScalarStyle::SingleQuoted,
+        "s1".to_owned()
+    )));
+    assert!(redacted
+        .tokens()
+        .contains(&TokenType::Alias("a1".to_owned())));
+
+    let error = "STREAM-START\nKEY x\n".parse::<TokenTrace>().unwrap_err();
+    assert_eq!(error.line, 2);
+    assert!(TokenTrace::record_str("a: 'b").is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_traces_deserialize_from_text() {
+    use saphyr::{EventTrace, TokenTrace};
+    use serde::{de::value::StringDeserializer, Deserialize};
+
+    let deserialize =
+        |text: &str| StringDeserializer::<serde::de::value::Error>::new(text.to_owned());
+    let events = EventTrace::record_str("a: [1]").unwrap();
+    let tokens = TokenTrace::record_str("a: [1]").unwrap();
+    assert_eq!(
+        EventTrace::deserialize(deserialize(&events.to_string())).unwrap(),
+        events
+    );
+    assert_eq!(
+        TokenTrace::deserialize(deserialize(&tokens.to_string())).unwrap(),
+        tokens
+    );
+    let error = EventTrace::deserialize(deserialize("+STR\n?")).unwrap_err();
+    assert_eq!(error.to_string(), "TRACE001: invalid event at line 2");
+}
+
+#[test]
+fn test_warn_mixed_indentation() {
+    use saphyr::{LoadWarning, YamlLoader};
+
+    let s = "
+a:
+    b:
+      c: 1
+    d:
+    - x
+    - y: 1
+      z: |
+          text
+            more
+    -
+        q: [
+          1, 2]
+    e: 'quoted
+      on two lines'
+---
+f:
+  g:
+      h: 1
+";
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.warn_mixed_indentation(true);
+    loader.load_from_str(s).unwrap();
+    let mixed: Vec<_> = loader
+        .warnings()
+        .iter()
+        .map(|warning| match warning {
+            LoadWarning::MixedIndentation {
+                width,
+                found,
+                marker,
+            } => (*width, *found, marker.line(), marker.col()),
+            warning => panic!("unexpected warning: {warning}"),
+        })
+        .collect();
+    assert_eq!(mixed, [(4, 2, 4, 6), (2, 4, 19, 6)]);
+    assert_eq!(
+        loader.warnings()[0].to_string(),
+        "line 4 indented by 2 spaces, while its document is indented by 4"
+    );
+
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.load_from_str(s).unwrap();
+    assert!(loader.warnings().is_empty());
+}
+
+#[test]
+fn test_key_order() {
+    use saphyr::LookupErrorKind;
+
+    let keys = |node: &Yaml| -> Vec<String> {
+        node.as_hash()
+            .unwrap()
+            .keys()
+            .map(|key| match key {
+                Yaml::Integer(i) => i.to_string(),
+                key => key.as_str().unwrap().to_owned(),
+            })
+            .collect()
+    };
+    let s = "zeta: 1\nalpha: 2\n3: three\nmid: {b: 1, a: 2}\nomega: 4";
+    let mut doc = Yaml::load_from_str(s).unwrap().remove(0);
+    assert_eq!(keys(&doc), ["zeta", "alpha", "3", "mid", "omega"]);
+
+    let mut output = String::new();
+    YamlEmitter::new(&mut output).dump(&doc).unwrap();
+    let reloaded = Yaml::load_from_str(&output).unwrap().remove(0);
+    assert_eq!(keys(&reloaded), keys(&doc));
+
+    let hash = doc.as_mut_hash().unwrap();
+    hash.replace(Yaml::String("zeta".into()), Yaml::Integer(10));
+    hash.remove(&Yaml::String("alpha".into()));
+    hash.insert(Yaml::String("new".into()), Yaml::Null);
+    assert_eq!(keys(&doc), ["zeta", "3", "mid", "omega", "new"]);
+
+    doc.reorder_key(&["new"], 0).unwrap();
+    doc.reorder_key(&["3"], 4).unwrap();
+    doc.reorder_key(&["mid", "a"], 0).unwrap();
+    assert_eq!(keys(&doc), ["new", "zeta", "mid", "omega", "3"]);
+    assert_eq!(keys(&doc["mid"]), ["a", "b"]);
+    assert_eq!(doc["zeta"].as_i64(), Some(10));
+    assert_eq!(doc[3].as_str(), Some("three"));
+
+    let error = doc.reorder_key(&["missing"], 0).unwrap_err();
+    assert_eq!(error.kind, LookupErrorKind::MissingKey);
+    let error = doc.reorder_key(&["mid", "a"], 2).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "LOOKUP002: index 2 is out of range at `mid`, which has 2 items"
+    );
+    let error = doc.reorder_key(&[], 0).unwrap_err();
+    assert_eq!(error.kind, LookupErrorKind::MissingSegment);
+    assert_eq!(keys(&doc), ["new", "zeta", "mid", "omega", "3"]);
+    assert_eq!(keys(&doc["mid"]), ["a", "b"]);
+    let mut seq = Yaml::load_from_str("[1, 2]").unwrap().remove(0);
+    let error = seq.reorder_key(&["0"], 1).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "LOOKUP003: expected mapping at the root, found sequence"
+    );
+}
+
+#[test]
+fn test_coerce_path() {
+    use saphyr::{PathCoercion, Schema};
+
+    let s = "
+labels: {on: yes, count: 010, nested: {deep: true}}
+matrix:
+  - [1, true]
+  - [2, 'x', !!int 3]
+flags: {a: yes, b: true}
+top: 1
+";
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader
+        .coerce_path(&["labels", "*"], PathCoercion::String)
+        .coerce_path(&["matrix", "*", "1"], PathCoercion::String)
+        .coerce_path(&["matrix", "1", "*"], PathCoercion::Schema(Schema::Core))
+        .coerce_path(
+            &["flags", "*"],
+            PathCoercion::Schema(Schema::Yaml11Booleans),
+        );
+    let doc = loader.load_from_str(s).unwrap().remove(0);
+    assert_eq!(doc["labels"]["on"].as_str(), Some("yes"));
+    assert_eq!(doc["labels"]["count"].as_str(), Some("010"));
+    // Only scalars at the path itself are coerced.
+    assert_eq!(doc["labels"]["nested"]["deep"].as_bool(), Some(true));
+    assert_eq!(doc["matrix"][0][0].as_i64(), Some(1));
+    assert_eq!(doc["matrix"][0][1].as_str(), Some("true"));
+    assert_eq!(doc["matrix"][1][1].as_str(), Some("x"));
+    assert_eq!(doc["matrix"][1][2].as_i64(), Some(3));
+    assert_eq!(doc["flags"]["a"].as_bool(), Some(true));
+    assert_eq!(doc["top"].as_i64(), Some(1));
+    // Keys are loaded as usual.
+    assert_eq!(doc["labels"].as_hash().unwrap().len(), 3);
+    assert!(doc["labels"]
+        .as_hash()
+        .unwrap()
+        .contains_key(&Yaml::String("on".into())));
+}
+
+#[test]
+fn test_strings_schema() {
+    use saphyr::Schema;
+
+    let s = "
+- [NO, yes, true, 010, 0x1F, 1.10, .inf, 1e3, -0]
+- [~, null, NULL, '', ]
+- ['true', !!bool true, !!int 010, !!float 1.5, !!int foo, !custom 3]
+- {42: 1.0}
+";
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.schema(Schema::Strings);
+    let doc = loader.load_from_str(s).unwrap().remove(0);
+    let plain: Vec<_> = doc[0].as_vec().unwrap().iter().map(Yaml::as_str).collect();
+    assert_eq!(
+        plain,
+        ["NO", "yes", "true", "010", "0x1F", "1.10", ".inf", "1e3", "-0"].map(Some)
+    );
+    assert!(doc[1][0].is_null() && doc[1][1].is_null() && doc[1][2].is_null());
+    assert_eq!(doc[1][3].as_str(), Some(""));
+    assert_eq!(doc[2][0].as_str(), Some("true"));
+    assert_eq!(doc[2][1].as_bool(), Some(true));
+    assert_eq!(doc[2][2].as_i64(), Some(10));
+    assert_eq!(doc[2][3].as_f64(), Some(1.5));
+    assert!(doc[2][4].is_badvalue());
+    assert_eq!(doc[2][5].as_str(), Some("3"));
+    assert_eq!(doc[3]["42"].as_str(), Some("1.0"));
+    assert_eq!(Schema::Strings.to_string(), "strings");
+}
+
+#[test]
+fn test_run_regression_corpus() {
+    use saphyr::run_regression_corpus;
+
+    let dir = std::env::temp_dir().join(format!("saphyr-test-corpus-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("nested")).unwrap();
+    std::fs::write(dir.join("anchors"), "a: &x [1, {b: *x}]\n---\n- *x").unwrap();
+    std::fs::write(dir.join("nested").join("skipped"), "a: 1").unwrap();
+    std::fs::write(dir.join("binary"), b"a: \xff\xfe\n- [").unwrap();
+    std::fs::write(
+        dir.join("strings"),
+        "- \"0x1F\"\n- 'yes'\n- \"a\\n b\"\n- !!set {a, b}",
+    )
+    .unwrap();
+    std::fs::write(dir.join("empty"), "").unwrap();
+    let report = run_regression_corpus(&dir).unwrap();
+    assert!(report.is_success(), "{report}");
+    assert_eq!(report.files, 4);
+    assert_eq!(report.to_string(), "0 of 4 files failed");
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(run_regression_corpus(&dir).is_err());
+}
+
+#[test]
+fn test_validate_syntax() {
+    use saphyr::validate_syntax;
+
+    assert_eq!(validate_syntax(""), Ok(()));
+    assert_eq!(
+        validate_syntax("a: &x 1\nb: *x\n---\n- [1, {c: d}]\n"),
+        Ok(())
+    );
+
+    // Errors are reported relative to the whole input, once per broken document.
+    let s = "ok: 1\n---\né: [1,\n  2\n---\n---\nfine: yes\n---\n  bad: 'x\n---\nlast: ]\n";
+    let errors = validate_syntax(s).unwrap_err();
+    let positions: Vec<_> = errors
+        .iter()
+        .map(|error| (error.marker().line(), error.marker().col()))
+        .collect();
+    assert_eq!(positions.len(), 3);
+    assert_eq!(positions[0].0, 5);
+    assert_eq!(positions[1].0, 9);
+    assert_eq!(positions[2], (11, 6));
+    let index = errors[2].marker().index();
+    assert_eq!(s.chars().nth(index), Some(']'));
+    assert_eq!(
+        Yaml::load_from_str(s).unwrap_err().marker(),
+        errors[0].marker()
+    );
+}
+
+#[test]
+fn test_lex_line() {
+    use saphyr::{lex_line, LineState, LineToken, LineTokenKind};
+
+    fn lex_all(source: &str) -> Vec<(Vec<LineToken>, LineState)> {
+        let mut state = LineState::new();
+        source
+            .lines()
+            .map(|line| {
+                let (tokens, next) = lex_line(line, state);
+                let start = state;
+                state = next;
+                (tokens, start)
+            })
+            .collect()
+    }
+    let text = |line: &str, token: &LineToken| -> String {
+        line.chars()
+            .skip(token.start)
+            .take(token.end - token.start)
+            .collect()
+    };
+
+    let source = "\
+%YAML 1.2
+--- # start
+base: &base {a: 1, 'b': [x, y]}
+list:
+  - *base
+  - !!str 3
+  - -1
+script: |2-
+    echo ok
+
+  exit 0
+msg: \"multi
+  line\"
+end: ~ # done
+...";
+    let lexed = lex_all(source);
+    let lines: Vec<&str> = source.lines().collect();
+    let tokens = |i: usize| -> Vec<(LineTokenKind, String)> {
+        lexed[i]
+            .0
+            .iter()
+            .map(|token| (token.kind, text(lines[i], token)))
+            .collect()
+    };
+    use LineTokenKind::{
+        Alias, Anchor, BlockScalar, Comment, Directive, DocumentMarker, Indicator, Key, Null,
+        Number, QuotedString, Tag,
+    };
+    let owned = |tokens: &[(LineTokenKind, &str)]| -> Vec<(LineTokenKind, String)> {
+        tokens
+            .iter()
+            .map(|(kind, text)| (*kind, (*text).to_owned()))
+            .collect()
+    };
+    assert_eq!(tokens(0), owned(&[(Directive, "%YAML 1.2")]));
+    assert_eq!(
+        tokens(1),
+        owned(&[(DocumentMarker, "---"), (Comment, "# start")])
+    );
+    assert_eq!(
+        tokens(2),
+        owned(&[
+            (Key, "base"),
+            (Indicator, ":"),
+            (Anchor, "&base"),
+            (Indicator, "{"),
+            (Key, "a"),
+            (Indicator, ":"),
+            (Number, "1"),
+            (Indicator, ","),
+            (Key, "'b'"),
+            (Indicator, ":"),
+            (Indicator, "["),
+            (LineTokenKind::String, "x"),
+            (Indicator, ","),
+            (LineTokenKind::String, "y"),
+            (Indicator, "]"),
+            (Indicator, "}"),
+        ])
+    );
+    assert_eq!(tokens(4), owned(&[(Indicator, "-"), (Alias, "*base")]));
+    assert_eq!(
+        tokens(5),
+        owned(&[(Indicator, "-"), (Tag, "!!str"), (Number, "3")])
+    );
+    assert_eq!(tokens(6), owned(&[(Indicator, "-"), (Number, "-1")]));
+    assert_eq!(
+        tokens(7),
+        owned(&[(Key, "script"), (Indicator, ":"), (Indicator, "|2-")])
+    );
+    // The indentation of the contents is explicit, so the line is indented within them.
+    assert_eq!(tokens(8), owned(&[(BlockScalar, "  echo ok")]));
+    assert_eq!(tokens(9), owned(&[]));
+    assert_eq!(tokens(10), owned(&[(BlockScalar, "exit 0")]));
+    assert_eq!(
+        tokens(11),
+        owned(&[(Key, "msg"), (Indicator, ":"), (QuotedString, "\"multi")])
+    );
+    assert!(lexed[12].1.in_scalar());
+    assert_eq!(tokens(12), owned(&[(QuotedString, "line\"")]));
+    assert_eq!(
+        tokens(13),
+        owned(&[
+            (Key, "end"),
+            (Indicator, ":"),
+            (Null, "~"),
+            (Comment, "# done")
+        ])
+    );
+    assert_eq!(tokens(14), owned(&[(DocumentMarker, "...")]));
+    assert!(!lexed[13].1.in_scalar());
+    assert_eq!(lexed[3].1.flow_depth(), 0);
+
+    // Re-lexing an edited line only changes the following lines until states match again.
+    let edited = source.replace("base: &base {a: 1,", "base: &base {a: 1, [");
+    let relexed = lex_all(&edited);
+    assert_eq!(relexed[3].1.flow_depth(), 1);
+    assert_eq!(relexed[2].1, lexed[2].1);
+    let edited = source.replace("  - -1", "  - 'open");
+    let relexed = lex_all(&edited);
+    assert!(relexed[7].1.in_scalar());
+    // Document markers end unterminated scalars.
+    assert_eq!(relexed[14].0, lexed[14].0);
+
+    // The contents of block scalars are indented relative to their key, not to their line.
+    let source = "- key: |2\n      x\n  other: y\n- &a !t k: >\n     z\n   w: 1\n";
+    let lexed = lex_all(source);
+    let lines: Vec<&str> = source.lines().collect();
+    let tokens = |i: usize| -> Vec<(LineTokenKind, String)> {
+        lexed[i]
+            .0
+            .iter()
+            .map(|token| (token.kind, text(lines[i], token)))
+            .collect()
+    };
+    assert_eq!(tokens(1), owned(&[(BlockScalar, "  x")]));
+    assert_eq!(
+        tokens(2),
+        owned(&[
+            (Key, "other"),
+            (Indicator, ":"),
+            (LineTokenKind::String, "y")
+        ])
+    );
+    assert_eq!(tokens(4), owned(&[(BlockScalar, "z")]));
+    assert_eq!(
+        tokens(5),
+        owned(&[(Key, "w"), (Indicator, ":"), (Number, "1")])
+    );
+}
+
+#[test]
+fn test_document_marker_in_scalar() {
+    use saphyr::{LoadError, LoadWarning, MarkedYaml, MarkerInScalarPolicy};
+
+    let load = |policy, source| {
+        let mut loader = YamlLoader::<Yaml>::default();
+        loader.marker_in_scalar_policy(policy);
+        let result = loader.load_from_str(source);
+        (result, loader.warnings().to_vec())
+    };
+    let plain = "a: b\n  c\n---\nd: e";
+    let single = "a: b # c\n---\nd: e";
+    let quoted = "- 'a\n  b\n\n---\n- \"c\\\n...\n";
+
+    // Plain scalars end at the marker; quoted ones are a scan error.
+    let (docs, warnings) = load(MarkerInScalarPolicy::Standard, plain);
+    assert_eq!(docs.unwrap()[0]["a"].as_str(), Some("b c"));
+    assert!(warnings.is_empty());
+    let (result, _) = load(MarkerInScalarPolicy::Standard, quoted);
+    assert!(matches!(result, Err(LoadError::Scan(_))));
+
+    let (docs, warnings) = load(MarkerInScalarPolicy::Terminate, plain);
+    assert_eq!(docs.unwrap()[1]["d"].as_str(), Some("e"));
+    assert!(matches!(
+        warnings[..],
+        [LoadWarning::ScalarEndedByMarker { scalar, marker }]
+            if (scalar.line(), scalar.col(), marker.line(), marker.index()) == (1, 3, 3, 9)
+    ));
+    let (docs, warnings) = load(MarkerInScalarPolicy::Terminate, quoted);
+    let docs = docs.unwrap();
+    assert_eq!(docs.len(), 2);
+    assert_eq!(docs[0][0].as_str(), Some("a b"));
+    // The trailing `\` escapes a space instead of the closing quote.
+    assert_eq!(docs[1][0].as_str(), Some("c "));
+    let ended: Vec<_> = warnings
+        .iter()
+        .map(|warning| match warning {
+            LoadWarning::ScalarEndedByMarker { scalar, marker } => {
+                (scalar.line(), marker.line(), marker.index())
+            }
+            warning => panic!("unexpected warning: {warning}"),
+        })
+        .collect();
+    // Positions point into the input, without the added quotes.
+    assert_eq!(ended, [(1, 4, 10), (5, 6, 20)]);
+    let mut loader = YamlLoader::<MarkedYaml>::default();
+    loader.marker_in_scalar_policy(MarkerInScalarPolicy::Terminate);
+    let docs = loader.load_from_str(&format!("{quoted}--- x\n")).unwrap();
+    let marker = &docs[2].marker;
+    assert_eq!((marker.index(), marker.line(), marker.col()), (28, 7, 4));
+    assert_eq!(
+        warnings[0].to_string(),
+        "document marker at line 4 within the scalar starting at line 1 column 3, which ends the scalar"
+    );
+
+    let (result, _) = load(MarkerInScalarPolicy::Reject, plain);
+    let error = result.unwrap_err();
+    assert!(matches!(
+        error,
+        LoadError::MarkerInScalar { scalar, marker } if scalar.line() == 1 && marker.line() == 3
+    ));
+    assert_eq!(
+        error.to_string(),
+        "LOAD017: document marker at line 3 within the scalar starting at line 1 column 4"
+    );
+    let (result, _) = load(MarkerInScalarPolicy::Reject, quoted);
+    assert!(matches!(
+        result,
+        Err(LoadError::MarkerInScalar { scalar, marker }) if scalar.line() == 1 && marker.line() == 4
+    ));
+
+    // A marker after a scalar on a single line is the usual end of a document.
+    for policy in [
+        MarkerInScalarPolicy::Terminate,
+        MarkerInScalarPolicy::Reject,
+    ] {
+        let (docs, warnings) = load(policy, single);
+        assert_eq!(docs.unwrap()[0]["a"].as_str(), Some("b"));
+        assert!(warnings.is_empty());
+    }
+}
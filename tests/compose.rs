@@ -0,0 +1,120 @@
+use saphyr::{compose_str, ComposeError, ComposedData, ComposedDocument, TScalarStyle, Tag, Yaml};
+
+#[test]
+fn test_construct_matches_load() {
+    let s = "
+a: &a [1, 2.5, true, ~, '3']
+b: *a
+c: !!set { x, y }
+d: !!str 42
+? [k]
+: &v { n: *a }
+e: *v
+---
+---
+scalar
+";
+    let composed = compose_str(s).unwrap();
+    let constructed = composed
+        .iter()
+        .map(ComposedDocument::construct)
+        .collect::<Vec<_>>();
+    assert_eq!(constructed, Yaml::load_from_str(s).unwrap());
+}
+
+#[test]
+fn test_intercept_before_construction() {
+    let mut docs = compose_str("port: !env PORT\nhost: localhost").unwrap();
+    let doc = &mut docs[0];
+
+    // Replace nodes tagged `!env` before they are constructed.
+    for node in doc.nodes_mut() {
+        if node.tag == Some(Tag::new("!", "env")) {
+            node.tag = None;
+            node.data = ComposedData::Scalar {
+                value: "8080".into(),
+                style: TScalarStyle::Plain,
+            };
+        }
+    }
+    let yaml = doc.construct();
+    assert_eq!(yaml["port"].as_i64(), Some(8080));
+    assert_eq!(yaml["host"].as_str(), Some("localhost"));
+}
+
+#[test]
+fn test_compose_errors() {
+    assert!(matches!(compose_str("a: [b"), Err(ComposeError::Scan(_))));
+}
+
+#[test]
+fn test_node_view_shares_aliases() {
+    let mut s = String::from("l0: &l0 [x, 1, 2.5, true, ~]\n");
+    for i in 1..20 {
+        s.push_str(&format!("l{i}: &l{i} [*l{0}, *l{0}]\n", i - 1));
+    }
+    let docs = compose_str(&s).unwrap();
+    let doc = &docs[0];
+    // 2^19 copies of `l0` are reachable, but it is stored once.
+    assert_eq!(doc.nodes().len(), 1 + 20 + 1 + 5 + 19);
+
+    let mut node = doc.view().get("l19").unwrap();
+    while node.get("missing").is_none() && node.at(1).is_some() && node.len() == Some(2) {
+        node = node.at(1).unwrap();
+    }
+    assert_eq!(node.id(), doc.view().get("l0").unwrap().id());
+    assert_eq!(node.at(0).unwrap().as_str(), Some("x"));
+    assert_eq!(node.at(1).unwrap().as_i64(), Some(1));
+    assert_eq!(node.at(2).unwrap().as_f64(), Some(2.5));
+    assert_eq!(node.at(3).unwrap().as_bool(), Some(true));
+    assert!(node.at(4).unwrap().is_null());
+    assert_eq!(node.items().unwrap().count(), 5);
+
+    let yaml = doc.view().get("l1").unwrap().to_yaml();
+    assert_eq!(
+        yaml,
+        Yaml::load_from_str("[[x, 1, 2.5, true, ~], [x, 1, 2.5, true, ~]]").unwrap()[0]
+    );
+}
+
+#[test]
+fn test_node_view_duplicate_keys() {
+    let docs = compose_str("a: 1\nb: 2\na: 3").unwrap();
+    let view = docs[0].view();
+    assert_eq!(view.get("a").unwrap().as_i64(), Some(3));
+    assert_eq!(view.entries().unwrap().count(), 3);
+    assert!(view.get("a").unwrap().get("a").is_none());
+}
+
+#[test]
+fn test_node_map() {
+    use saphyr::NodeMap;
+
+    let docs = compose_str("a: &a [1, 2]\nb: *a\nc: {d: 3}").unwrap();
+    let doc = &docs[0];
+
+    // Annotate each node with its depth.
+    let mut depths = NodeMap::new(doc);
+    let mut stack = vec![(doc.view(), 0)];
+    while let Some((view, depth)) = stack.pop() {
+        depths.insert(view.id(), depth);
+        if let Some(items) = view.items() {
+            stack.extend(items.map(|item| (item, depth + 1)));
+        }
+        if let Some(entries) = view.entries() {
+            stack.extend(entries.flat_map(|(k, v)| [(k, depth + 1), (v, depth + 1)]));
+        }
+    }
+    assert_eq!(depths.len(), doc.nodes().len());
+    assert!(doc.ids().all(|id| depths.contains(id)));
+    let d = doc.view().get("c").unwrap().get("d").unwrap();
+    assert_eq!(depths.get(d.id()), Some(&2));
+
+    assert_eq!(depths.insert(d.id(), 5), Some(2));
+    *depths.get_mut(d.id()).unwrap() += 1;
+    assert_eq!(depths.remove(d.id()), Some(6));
+    assert_eq!(depths.remove(d.id()), None);
+    assert_eq!(depths.len(), doc.nodes().len() - 1);
+    assert_eq!(depths.iter().map(|(_, depth)| depth).max(), Some(&2));
+    assert!(NodeMap::<()>::new(doc).is_empty());
+}
@@ -1,4 +1,4 @@
-use saphyr::{Yaml, YamlEmitter};
+use saphyr::{EmitProfile, Yaml, YamlEmitter};
 
 #[allow(clippy::similar_names)]
 #[test]
@@ -292,3 +292,790 @@ a:
 
     assert_eq!(s, writer);
 }
+
+#[test]
+fn test_emit_collection_tags() {
+    let s = "---
+root: !!set
+  ? a
+  ? [b, c]
+list:
+  - !!omap
+    - a: 1
+    - b: 2
+  - !!pairs []
+? !!set
+  ? k
+: v";
+    let docs = Yaml::load_from_str(s).unwrap();
+    let mut writer = String::new();
+    YamlEmitter::new(&mut writer).dump(&docs[0]).unwrap();
+    assert_eq!(
+        writer,
+        "---
+root: !!set
+  ? a
+  ? - b
+    - c
+list:
+  - !!omap
+    - a: 1
+    - b: 2
+  - !!pairs []
+? !!set
+  ? k
+: v"
+    );
+    assert_eq!(Yaml::load_from_str(&writer).unwrap(), docs);
+}
+
+#[test]
+fn test_emit_unrepresentable_node() {
+    use saphyr::EmitError;
+
+    let mut doc = Yaml::load_from_str("a:\n  - x\n  - b: y\n")
+        .unwrap()
+        .remove(0);
+    doc["a"][1]["b"] = Yaml::Alias(1);
+    let mut output = String::new();
+    let error = YamlEmitter::new(&mut output).dump(&doc).unwrap_err();
+    assert!(matches!(&error, EmitError::Unrepresentable { path, .. } if path == &["a", "1", "b"]));
+    assert_eq!(error.code().as_str(), "EMIT002");
+    assert_eq!(
+        error.to_string(),
+        "EMIT002: cannot emit the node at `a.1.b`: aliases cannot be emitted without the node \
+         they refer to"
+    );
+
+    let mut output = String::new();
+    let error = YamlEmitter::new(&mut output)
+        .dump(&Yaml::Alias(1))
+        .unwrap_err();
+    assert!(error.to_string().contains("cannot emit the root node"));
+}
+
+#[test]
+fn test_dump_scalar() {
+    use saphyr::{Event, Parser, Scalar, TScalarStyle};
+
+    let s = "
+- plain text
+- 'single ''quoted'''
+- \"double\\tquoted\"
+- !!int 42
+- |
+  literal
+  text
+- >-
+  folded
+  text
+- true
+";
+    let parser = Parser::new_from_str(s);
+    let scalars: Vec<_> = parser
+        .map(Result::unwrap)
+        .filter_map(|(event, marker)| Scalar::from_event(event, marker).ok())
+        .map(|(scalar, _)| scalar)
+        .collect();
+    assert_eq!(scalars.len(), 7);
+    assert_eq!(scalars[1].marker.line(), 3);
+
+    // Each scalar loads back with its value, and in its style if it was written in it.
+    for scalar in &scalars {
+        let mut output = String::new();
+        YamlEmitter::new(&mut output).dump_scalar(scalar).unwrap();
+        let parser = Parser::new_from_str(&output);
+        let reparsed = parser
+            .map(Result::unwrap)
+            .find_map(|(event, marker)| Scalar::from_event(event, marker).ok())
+            .unwrap()
+            .0;
+        assert_eq!(reparsed.value, scalar.value, "{output}");
+        assert_eq!(reparsed.tag, scalar.tag, "{output}");
+        if scalar.style != TScalarStyle::Folded {
+            assert_eq!(reparsed.style, scalar.style, "{output}");
+        }
+    }
+    assert_eq!(scalars[3].clone().into_yaml(), Yaml::Integer(42));
+    assert_eq!(scalars[6].clone().into_yaml(), Yaml::Boolean(true));
+
+    // Scalars are written in double quotes when their style cannot hold them.
+    let mut scalar = Scalar::new(" leading space");
+    assert_eq!(scalar.style, TScalarStyle::DoubleQuoted);
+    scalar.style = TScalarStyle::Plain;
+    let mut output = String::new();
+    YamlEmitter::new(&mut output).dump_scalar(&scalar).unwrap();
+    assert_eq!(output, "---\n\" leading space\"");
+
+    let docs = saphyr::compose_str("!x a").unwrap();
+    let scalar = docs[0].node(docs[0].root()).scalar().unwrap();
+    assert_eq!(scalar.tag, Some(saphyr::Tag::new("!", "x")));
+    assert!(matches!(scalar.into_event(1), Event::Scalar(value, _, 1, Some(_)) if value == "a"));
+}
+
+#[test]
+fn test_emit_quote_style() {
+    use saphyr::QuoteStyle;
+
+    let s = r#"
+plain: text
+"true": 'yes'
+empty: ""
+quote: "it's"
+control: "bell\x01"
+multiline: "a\nb"
+number: 42
+"#;
+    let docs = Yaml::load_from_str(s).unwrap();
+    let emit = |quote_style, quote_all_strings| {
+        let mut output = String::new();
+        let mut emitter = YamlEmitter::new(&mut output);
+        emitter.quote_style(quote_style);
+        emitter.quote_all_strings(quote_all_strings);
+        assert_eq!(emitter.get_quote_style(), quote_style);
+        assert_eq!(emitter.is_quote_all_strings(), quote_all_strings);
+        emitter.dump(&docs[0]).unwrap();
+        output
+    };
+
+    assert_eq!(
+        emit(QuoteStyle::Single, false),
+        r#"---
+plain: text
+'true': 'yes'
+empty: ''
+quote: 'it''s'
+control: "bell\u0001"
+multiline: "a\nb"
+number: 42"#
+    );
+    assert_eq!(
+        emit(QuoteStyle::Double, true),
+        r#"---
+"plain": "text"
+"true": "yes"
+"empty": ""
+"quote": "it's"
+"control": "bell\u0001"
+"multiline": "a\nb"
+"number": 42"#
+    );
+    for quote_style in [QuoteStyle::Double, QuoteStyle::Single] {
+        for quote_all_strings in [false, true] {
+            let output = emit(quote_style, quote_all_strings);
+            assert_eq!(Yaml::load_from_str(&output).unwrap(), docs, "{output}");
+        }
+    }
+}
+
+#[test]
+fn test_emit_quotes_indicators_and_special_values() {
+    let strings = [
+        "- a", "? a", ": a", "#a", "&a", "*a", "!a", "|a", ">a", "%a", "@a", "`a", "a: b", "a #b",
+        "0o17", "+.inf", "-.INF", ".NaN", "0x1F", "1e5",
+    ];
+    let doc: Yaml = strings
+        .iter()
+        .map(|s| (Yaml::String((*s).to_owned()), Yaml::String((*s).to_owned())))
+        .collect();
+    let mut output = String::new();
+    YamlEmitter::new(&mut output).dump(&doc).unwrap();
+    for (line, s) in output.lines().skip(1).zip(strings) {
+        assert_eq!(line, format!("\"{s}\": \"{s}\""));
+    }
+    assert_eq!(Yaml::load_from_str(&output).unwrap()[0], doc);
+}
+
+#[test]
+fn test_emit_yaml_directive() {
+    use saphyr::EmitError;
+
+    let emit = |version, doc: &Yaml| {
+        let mut output = String::new();
+        let mut emitter = YamlEmitter::new(&mut output);
+        emitter.yaml_directive(version);
+        assert_eq!(emitter.get_yaml_directive(), version);
+        emitter.dump(doc).map(|()| output)
+    };
+    let doc = Yaml::load_from_str("[y, N, 0b1, '0777', 1_000, '190:20', 1.5, '1.5', plain]")
+        .unwrap()
+        .remove(0);
+    assert_eq!(
+        emit(Some((1, 1)), &doc).unwrap(),
+        "%YAML 1.1\n---\n- \"y\"\n- \"N\"\n- \"0b1\"\n- \"0777\"\n- \"1_000\"\n- \"190:20\"\n\
+         - 1.5\n- \"1.5\"\n- plain"
+    );
+    assert_eq!(
+        emit(Some((1, 2)), &doc).unwrap(),
+        "%YAML 1.2\n---\n- y\n- N\n- 0b1\n- \"0777\"\n- 1_000\n- \"190:20\"\n- 1.5\n- \"1.5\"\n\
+         - plain"
+    );
+
+    let reals = Yaml::Array(vec![
+        Yaml::Real("1e3".into()),
+        Yaml::Real("1_000.5".into()),
+        Yaml::Real(".inf".into()),
+    ]);
+    assert!(emit(None, &reals).is_ok());
+    let error = emit(Some((1, 1)), &reals).unwrap_err();
+    assert!(matches!(&error, EmitError::Unrepresentable { path, .. } if path == &["0"]));
+    let error = emit(Some((1, 2)), &reals).unwrap_err();
+    assert!(matches!(&error, EmitError::Unrepresentable { path, .. } if path == &["1"]));
+
+    let error = emit(Some((2, 0)), &doc).unwrap_err();
+    assert_eq!(error, EmitError::UnsupportedVersion { major: 2, minor: 0 });
+    assert_eq!(
+        error.to_string(),
+        "EMIT004: cannot emit YAML 2.0, only YAML 1.1 and 1.2 are supported"
+    );
+
+    let mut output = String::new();
+    let mut emitter = YamlEmitter::new(&mut output);
+    emitter.yaml_directive(Some((1, 2)));
+    emitter.dump_empty().unwrap();
+    assert_eq!(output, "%YAML 1.2\n---");
+
+    // Documents following another one are ended with `...`, so that directives are not read as
+    // part of its contents.
+    let docs = Yaml::load_from_str("a\n--- [b]\n--- {c: d}").unwrap();
+    let mut output = String::new();
+    let mut emitter = YamlEmitter::new(&mut output);
+    emitter.yaml_directive(Some((1, 2)));
+    for doc in &docs {
+        emitter.dump(doc).unwrap();
+    }
+    emitter.dump_empty().unwrap();
+    assert_eq!(
+        output,
+        "%YAML 1.2\n---\na\n...\n%YAML 1.2\n---\n- b\n...\n%YAML 1.2\n---\nc: d\n...\n%YAML 1.2\n---"
+    );
+    let reloaded = Yaml::load_from_str(&output).unwrap();
+    assert_eq!(reloaded[..3], docs[..]);
+    assert!(reloaded[3].is_null());
+}
+
+#[test]
+fn test_emit_auto_style() {
+    use saphyr::AutoStyle;
+
+    let emit = |auto_style: AutoStyle, doc: &Yaml| {
+        let mut output = String::new();
+        let mut emitter = YamlEmitter::new(&mut output);
+        emitter.auto_style(Some(auto_style));
+        emitter.dump(doc).map(|()| output)
+    };
+    let source = "\
+name: web
+ports: [80, 443]
+labels: {app: web, 'tier': 'front end', url: 'http://x'}
+matrix: [[1, 2], [3]]
+notes: [\"a\\nb\"]
+empty: []
+long: [a, b, c, d, e, f, g, h, i]
+";
+    let doc = Yaml::load_from_str(source).unwrap().remove(0);
+    let output = emit(AutoStyle::default(), &doc).unwrap();
+    assert_eq!(
+        output,
+        "---
+name: web
+ports: [80, 443]
+labels: {app: web, tier: front end, url: \"http://x\"}
+matrix:
+  - [1, 2]
+  - [3]
+notes:
+  - \"a\\nb\"
+empty: []
+long:
+  - a
+  - b
+  - c
+  - d
+  - e
+  - f
+  - g
+  - h
+  - i"
+    );
+    assert_eq!(Yaml::load_from_str(&output).unwrap()[0], doc);
+
+    let nested = AutoStyle {
+        max_depth: 1,
+        max_width: 20,
+        ..AutoStyle::default()
+    };
+    let output = emit(nested, &doc).unwrap();
+    assert!(output.contains("\nmatrix: [[1, 2], [3]]\n"));
+    // The labels are too wide.
+    assert!(output.contains("\nlabels:\n  app: web\n"));
+    assert_eq!(Yaml::load_from_str(&output).unwrap()[0], doc);
+
+    // Flow collections are checked as block ones.
+    let doc = Yaml::load_from_str("a: [1, 1e3]").unwrap().remove(0);
+    let mut output = String::new();
+    let mut emitter = YamlEmitter::new(&mut output);
+    emitter.auto_style(Some(AutoStyle::default()));
+    emitter.yaml_directive(Some((1, 1)));
+    let error = emitter.dump(&doc).unwrap_err();
+    assert!(
+        matches!(&error, saphyr::EmitError::Unrepresentable { path, .. } if path == &["a", "1"])
+    );
+}
+
+#[test]
+fn test_emit_comments() {
+    use saphyr::{CommentPlacement, Comments};
+
+    let source = "\
+# Services.
+services: # All of them.
+  - name: web # The front.
+    # Ports.
+    ports: [80, 443]
+    motd: |
+      # Not a comment.
+      Hello
+    # After the block scalar.
+    url: 'http://x # not a comment'
+    tag: a#b
+
+  # Removed:
+  # - name: db
+
+  - worker
+# The end.
+---
+- 1 # One.
+";
+    let comments = Comments::scan_str(source).unwrap();
+    assert_eq!(comments.len(), 2);
+    let placements: Vec<_> = comments[0]
+        .iter()
+        .map(|comment| {
+            (
+                comment.text.as_str(),
+                comment.path.as_deref(),
+                comment.placement,
+                comment.marker.line(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        placements,
+        [
+            (" Services.", Some("services"), CommentPlacement::Leading, 1),
+            (
+                " All of them.",
+                Some("services"),
+                CommentPlacement::Trailing,
+                2
+            ),
+            (
+                " The front.",
+                Some("services[0].name"),
+                CommentPlacement::Trailing,
+                3
+            ),
+            (
+                " Ports.",
+                Some("services[0].ports"),
+                CommentPlacement::Leading,
+                4
+            ),
+            (
+                " After the block scalar.",
+                Some("services[0].url"),
+                CommentPlacement::Leading,
+                9
+            ),
+            (
+                " Removed:",
+                Some("services[1]"),
+                CommentPlacement::Standalone,
+                13
+            ),
+            (
+                " - name: db",
+                Some("services[1]"),
+                CommentPlacement::Standalone,
+                14
+            ),
+            (" The end.", None, CommentPlacement::Standalone, 17),
+        ]
+    );
+    assert_eq!(comments[1].of(Some("[0]")).count(), 1);
+
+    let docs = Yaml::load_from_str(source).unwrap();
+    let mut output = String::new();
+    let mut emitter = YamlEmitter::new(&mut output);
+    emitter.comments(&comments[0]);
+    emitter.dump(&docs[0]).unwrap();
+    assert_eq!(
+        output,
+        "\
+---
+# Services.
+services: # All of them.
+  - name: web # The front.
+    # Ports.
+    ports:
+      - 80
+      - 443
+    motd: \"# Not a comment.\\nHello\\n\"
+    # After the block scalar.
+    url: \"http://x # not a comment\"
+    tag: \"a#b\"
+  # Removed:
+  # - name: db
+
+  - worker
+# The end."
+    );
+    assert_eq!(Yaml::load_from_str(&output).unwrap()[0], docs[0]);
+    // Comments are attached to the same nodes once written back.
+    let rescanned = Comments::scan_str(&output).unwrap().remove(0);
+    let paths = |comments: &Comments| -> Vec<_> {
+        comments
+            .iter()
+            .map(|comment| (comment.path.clone(), comment.placement))
+            .collect()
+    };
+    assert_eq!(paths(&rescanned), paths(&comments[0]));
+
+    let mut output = String::new();
+    let mut emitter = YamlEmitter::new(&mut output);
+    emitter.comments(&comments[1]);
+    emitter.dump(&docs[1]).unwrap();
+    assert_eq!(output, "---\n- 1 # One.");
+}
+
+#[test]
+fn test_emit_quote_non_string_keys() {
+    use saphyr::AutoStyle;
+
+    let s = "
+8080: http
+1.5: {~: a, false: b}
+!!str 12: c
+[1, 2]: d
+'9': e
+";
+    let doc = &Yaml::load_from_str(s).unwrap()[0];
+    let mut output = String::new();
+    let mut emitter = YamlEmitter::new(&mut output);
+    emitter.quote_non_string_keys(true);
+    assert!(emitter.is_quote_non_string_keys());
+    emitter.dump(doc).unwrap();
+    assert_eq!(
+        output,
+        r#"---
+"8080": http
+"1.5":
+  "null": a
+  "false": b
+"12": c
+? - 1
+  - 2
+: d
+"9": e"#
+    );
+    let loaded = &Yaml::load_from_str(&output).unwrap()[0];
+    assert_eq!(loaded["8080"].as_str(), Some("http"));
+    assert_eq!(loaded["1.5"]["null"].as_str(), Some("a"));
+
+    // Keys of collections in flow style are quoted too.
+    let mut output = String::new();
+    let mut emitter = YamlEmitter::new(&mut output);
+    emitter.quote_non_string_keys(true);
+    emitter.auto_style(Some(AutoStyle::default()));
+    emitter.dump(doc).unwrap();
+    assert!(output.contains("\"1.5\": {\"null\": a, \"false\": b}"));
+}
+
+#[test]
+fn test_emit_with_overrides() {
+    use saphyr::{EmitError, LookupErrorKind};
+
+    let doc = &Yaml::load_from_str("a: !!omap [{b: 1}]\nc: [x, y]\n8: z").unwrap()[0];
+    let dump = |overrides: &[(&[&str], Yaml)]| {
+        let mut output = String::new();
+        YamlEmitter::new(&mut output)
+            .dump_with_overrides(doc, overrides)
+            .map(|()| output)
+    };
+    assert_eq!(
+        dump(&[
+            (&["c", "1"], Yaml::String("w".into())),
+            (&["8"], Yaml::Null),
+            (&["e"], Yaml::Array(vec![])),
+            (&["e"], Yaml::Integer(2)),
+        ])
+        .unwrap(),
+        "---\na: !!omap\n  - b: 1\nc:\n  - x\n  - w\n8: ~\ne: 2"
+    );
+    assert_eq!(dump(&[(&[], Yaml::Integer(1))]).unwrap(), "---\n1");
+    assert_eq!(doc["c"][1].as_str(), Some("y"));
+
+    let error = dump(&[(&["c", "2"], Yaml::Null)]).unwrap_err();
+    let EmitError::InvalidOverride(lookup) = &error else {
+        panic!("unexpected error: {error}");
+    };
+    assert_eq!(lookup.kind, LookupErrorKind::IndexOutOfRange(2));
+    assert_eq!(error.code().as_str(), "EMIT005");
+    assert!(dump(&[(&["x", "y"], Yaml::Null)]).is_err());
+}
+
+#[test]
+fn test_emit_tag_directives() {
+    use saphyr::{Event, Parser, Tag};
+
+    let tagged = |handle: &str, suffix: &str| {
+        let node = Yaml::Array(vec![Yaml::Integer(1)]);
+        Yaml::Tagged(Tag::new(handle, suffix), Box::new(node))
+    };
+    let doc = Yaml::Array(vec![
+        tagged("tag:example.com,2024:", "a"),
+        tagged("http://example.org/types/", "b"),
+        tagged("http://example.org/types/", "c,d"),
+        tagged("http://example.org/types/", "e"),
+        tagged("tag:example.com,2024:", "f"),
+        tagged("http://example.org/types/", "g"),
+        tagged("tag:yaml.org,2002:", "h"),
+        tagged("tag:yaml.org,2002:", "i"),
+    ]);
+    let mut output = String::new();
+    let mut emitter = YamlEmitter::new(&mut output);
+    emitter.tag_directives(true);
+    assert!(emitter.is_tag_directives());
+    emitter.dump(&doc).unwrap();
+    emitter.dump_empty().unwrap();
+    assert_eq!(
+        output,
+        "\
+%TAG !example! http://example.org/types/
+---
+- !<tag:example.com,2024:a>
+  - 1
+- !example!b
+  - 1
+- !<http://example.org/types/c,d>
+  - 1
+- !example!e
+  - 1
+- !<tag:example.com,2024:f>
+  - 1
+- !example!g
+  - 1
+- !!h
+  - 1
+- !!i
+  - 1
+---"
+    );
+    // The loader drops tags it does not know, but the parser reads them back.
+    let output = output.strip_suffix("---").unwrap();
+    let tags: Vec<String> = Parser::new_from_str(output)
+        .filter_map(|event| match event.unwrap().0 {
+            Event::SequenceStart(_, Some(tag)) => Some(tag.handle + &tag.suffix),
+            _ => None,
+        })
+        .collect();
+    let expected: Vec<String> = doc
+        .as_vec()
+        .unwrap()
+        .iter()
+        .map(|item| {
+            let tag = item.as_tagged().unwrap().0;
+            format!("{}{}", tag.handle, tag.suffix)
+        })
+        .collect();
+    assert_eq!(tags, expected);
+
+    // Documents with `%TAG` directives load back when written one after another.
+    let docs = [
+        Yaml::Array(vec![tagged("urn:a:", "p"), tagged("urn:a:", "q")]),
+        tagged("tag:example.com,2024:", "x"),
+        Yaml::Array(vec![tagged("urn:b:", "y"), tagged("urn:b:", "z")]),
+    ];
+    let mut output = String::new();
+    let mut emitter = YamlEmitter::new(&mut output);
+    emitter.tag_directives(true);
+    for doc in &docs {
+        emitter.dump(doc).unwrap();
+    }
+    assert_eq!(
+        output,
+        "\
+%TAG !urn! urn:a:
+---
+- !urn!p
+  - 1
+- !urn!q
+  - 1
+---
+!<tag:example.com,2024:x>
+- 1
+...
+%TAG !urn! urn:b:
+---
+- !urn!y
+  - 1
+- !urn!z
+  - 1"
+    );
+    let tags: Vec<String> = Parser::new_from_str(&output)
+        .filter_map(|event| match event.unwrap().0 {
+            Event::SequenceStart(_, Some(tag)) => Some(tag.handle + &tag.suffix),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        tags,
+        [
+            "urn:a:p",
+            "urn:a:q",
+            "tag:example.com,2024:x",
+            "urn:b:y",
+            "urn:b:z"
+        ]
+    );
+    assert_eq!(Yaml::load_from_str(&output).unwrap().len(), docs.len());
+
+    // A prefix of a single tag is not shortened.
+    let mut output = String::new();
+    let mut emitter = YamlEmitter::new(&mut output);
+    emitter.tag_directives(true);
+    emitter.dump(&tagged("urn:x:", "y")).unwrap();
+    assert_eq!(output, "---\n!<urn:x:y>\n- 1");
+}
+
+#[test]
+fn test_write_compact() {
+    use saphyr::{write_compact, EmitError, Hash};
+
+    let s = r#"
+labels: {app.kubernetes.io/name: web, tier: "1", on: "yes", empty: "", nan: 'nan', x: 'inf', y: inf}
+list: [a b, "c,d", "line\nbreak", -1, 2.5, true, ~, [], {}]
+set: !!set {a, b}
+"#;
+    let doc = &Yaml::load_from_str(s).unwrap()[0];
+    let mut output = String::new();
+    write_compact(doc, &mut output).unwrap();
+    assert_eq!(
+        output,
+        r#"{labels: {app.kubernetes.io/name: web, tier: "1", "on": "yes", empty: "", nan: "nan", x: "inf", y: inf}, list: ["a b", "c,d", "line\nbreak", -1, 2.5, true, ~, [], {}], set: !!set {a: ~, b: ~}}"#
+    );
+    assert_eq!(Yaml::load_from_str(&output).unwrap()[0], *doc);
+
+    let mut key = Hash::default();
+    key.insert(Yaml::Array(vec![Yaml::Integer(1)]), Yaml::Integer(2));
+    let mut output = String::new();
+    write_compact(&Yaml::Hash(key.clone()), &mut output).unwrap();
+    assert_eq!(output, "{[1]: 2}");
+    assert_eq!(Yaml::load_from_str(&output).unwrap()[0], Yaml::Hash(key));
+
+    let error = write_compact(&Yaml::Array(vec![Yaml::Alias(0)]), &mut String::new());
+    assert!(matches!(error, Err(EmitError::Unrepresentable { path, .. }) if path == ["0"]));
+}
+
+#[test]
+fn test_emit_profiles() {
+    use saphyr::{BoolLexemes, EmitProfile, NullStyle, QuoteStyle, Schema, YamlLoader};
+
+    let s = "
+metadata:
+  labels: {app: web}
+data:
+  8080: 'yes'
+  script: \"set -e\\nmake\\n\"
+spec:
+  containers:
+    - name: web
+      args: [--port, 80]
+      env: [{name: PROXY, value: ~}]
+  enabled: yes
+";
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.schema(Schema::Yaml11Booleans);
+    let doc = loader.load_from_str(s).unwrap().remove(0);
+    let lexemes = BoolLexemes::scan_str(s, Schema::Yaml11Booleans)
+        .unwrap()
+        .remove(0);
+    let emit = |profile| {
+        let mut output = String::new();
+        let mut emitter = YamlEmitter::new(&mut output);
+        emitter.bool_lexemes(&lexemes);
+        emitter.profile(profile);
+        emitter.dump(&doc).unwrap();
+        output
+    };
+
+    let kubernetes = emit(EmitProfile::Kubernetes);
+    assert_eq!(
+        kubernetes,
+        r#"---
+metadata:
+  labels:
+    app: web
+data:
+  "8080": "yes"
+  script: |
+    set -e
+    make
+spec:
+  containers:
+  - name: web
+    args:
+    - "--port"
+    - 80
+    env:
+    - name: PROXY
+      value: null
+  enabled: true"#
+    );
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.schema(Schema::Yaml11Booleans);
+    let reloaded = loader.load_from_str(&kubernetes).unwrap().remove(0);
+    assert_eq!(reloaded["data"]["8080"].as_str(), Some("yes"));
+    assert_eq!(reloaded["spec"], doc["spec"]);
+
+    let ansible = emit(EmitProfile::Ansible);
+    assert!(ansible.contains("  8080: \"yes\"\n"));
+    assert!(ansible.contains("  containers:\n    - name: web\n      args:\n        - \"--port\""));
+    let github = emit(EmitProfile::GitHubActions);
+    assert!(github.contains("  8080: 'yes'\n"));
+    assert!(github.contains("      - '--port'\n"));
+
+    // Options set after the profile override it.
+    let mut output = String::new();
+    let mut emitter = YamlEmitter::new(&mut output);
+    emitter.profile(EmitProfile::Kubernetes);
+    emitter.null_style(NullStyle::Tilde);
+    assert_eq!(emitter.get_quote_style(), QuoteStyle::Double);
+    assert!(!emitter.is_indent_sequences());
+    emitter.dump(&doc["spec"]["containers"][0]["env"]).unwrap();
+    assert_eq!(output, "---\n- name: PROXY\n  value: ~");
+}
+
+#[test]
+fn test_emit_multiline_strings_round_trip() {
+    let doc: Yaml = [
+        ("\n*x@", "a\nb"),
+        ("k", "\n\""),
+        ("l", "a\nb\n"),
+        ("m", "a\nb\n\n"),
+    ]
+    .into_iter()
+    .map(|(k, v)| (Yaml::String(k.to_owned()), Yaml::String(v.to_owned())))
+    .collect();
+    let mut output = String::new();
+    let mut emitter = YamlEmitter::new(&mut output);
+    emitter.profile(EmitProfile::GitHubActions);
+    emitter.dump(&doc).unwrap();
+    assert_eq!(
+        output,
+        "---\n\"\\n*x@\": |-\n  a\n  b\nk: \"\\n\\\"\"\nl: |\n  a\n  b\nm: \"a\\nb\\n\\n\""
+    );
+    assert_eq!(Yaml::load_from_str(&output).unwrap()[0], doc);
+}
@@ -0,0 +1,151 @@
+use saphyr::{Formatter, TextEdit, Yaml};
+
+fn reindented(source: &str, width: usize) -> String {
+    let mut formatter = Formatter::new();
+    formatter.indent(width);
+    let output = formatter.format(source).unwrap();
+    assert_eq!(
+        Yaml::load_from_str(&output).unwrap(),
+        Yaml::load_from_str(source).unwrap()
+    );
+    output
+}
+
+#[test]
+fn test_format_nothing_by_default() {
+    let source = "a:    1   \nb:\n      - x\n";
+    assert_eq!(Formatter::new().format(source).unwrap(), source);
+    assert!(Formatter::new().edits(source).unwrap().is_empty());
+}
+
+#[test]
+fn test_format_indent() {
+    let source = "\
+root:
+    - name: a   # first
+      tags:
+          - x
+    -   name: b
+        deps:
+        - c
+    - - nested
+      - list
+other: {a: 1}
+";
+    assert_eq!(
+        reindented(source, 2),
+        "\
+root:
+  - name: a   # first
+    tags:
+      - x
+  -   name: b
+      deps:
+      - c
+  - - nested
+    - list
+other: {a: 1}
+"
+    );
+    assert_eq!(reindented(&reindented(source, 2), 4), reindented(source, 4));
+}
+
+#[test]
+fn test_format_indent_keeps_scalars_and_flow() {
+    let source = "\
+a:
+      text: >
+          folded
+            more indented
+      plain: first
+        second
+      quoted: \"first
+          second\"
+      flow: [1,
+             2, {k: v,
+                 l: w}]
+      ? complex
+      : value
+";
+    assert_eq!(
+        reindented(source, 2),
+        "\
+a:
+  text: >
+      folded
+        more indented
+  plain: first
+    second
+  quoted: \"first
+      second\"
+  flow: [1,
+         2, {k: v,
+             l: w}]
+  ? complex
+  : value
+"
+    );
+}
+
+#[test]
+fn test_format_indent_comments() {
+    let source = "\
+a:
+    # About b.
+    b: 1
+        # Unaligned.
+# Top-level.
+c:
+    d: 2
+    # After d.
+";
+    assert_eq!(
+        reindented(source, 2),
+        "\
+a:
+  # About b.
+  b: 1
+        # Unaligned.
+# Top-level.
+c:
+  d: 2
+  # After d.
+"
+    );
+}
+
+#[test]
+fn test_format_strip_trailing_whitespace() {
+    let source = "a: 1  \r\nb: |\n  kept  \n  \nc:\t\n    d: 2 \n";
+    let mut formatter = Formatter::new();
+    formatter.strip_trailing_whitespace(true);
+    assert_eq!(
+        formatter.format(source).unwrap(),
+        "a: 1\r\nb: |\n  kept  \n  \nc:\n    d: 2\n"
+    );
+    formatter.indent(2);
+    assert_eq!(
+        formatter.format(source).unwrap(),
+        "a: 1\r\nb: |\n  kept  \n  \nc:\n  d: 2\n"
+    );
+    assert_eq!(
+        formatter.edits("a:\n    b: 1 \n").unwrap(),
+        [
+            TextEdit {
+                range: 3..7,
+                replacement: "  ".into()
+            },
+            TextEdit {
+                range: 11..12,
+                replacement: String::new()
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_format_invalid() {
+    let mut formatter = Formatter::new();
+    formatter.indent(2);
+    assert!(formatter.format("a:\n    b: \"c").is_err());
+}
@@ -0,0 +1,167 @@
+use saphyr::{
+    CancellableInput, CancellationToken, LineLimitedInput, LoadError, LoadWarning, Marker,
+    PreprocessedInput, Progress, ProgressInput, Yaml, YamlLoader,
+};
+use saphyr_parser::Parser;
+
+#[test]
+fn test_progress_markers() {
+    let source = "a: 1\nb: é\n";
+    let mut reports: Vec<Progress> = vec![];
+    let input = ProgressInput::new(source.chars(), 5, |progress| reports.push(*progress));
+    Yaml::load_from_iter(input).unwrap();
+
+    assert_eq!(reports.len(), 3);
+    assert_eq!(reports[0].bytes_read, 5);
+    assert_eq!(reports[0].marker.line(), 2);
+    assert_eq!(reports[0].marker.col(), 0);
+    assert_eq!(reports[0].fraction(), None);
+    // `é` takes 2 bytes but a single column.
+    assert_eq!(reports[1].bytes_read, 10);
+    assert_eq!(reports[1].marker.index(), 9);
+    assert_eq!(reports[2].bytes_read, source.len());
+    assert_eq!(reports[2].marker.line(), 3);
+}
+
+#[test]
+fn test_cancel_while_reading() {
+    let token = CancellationToken::new();
+    let source = "- item\n".repeat(1000);
+    let mut read = 0;
+    let input = CancellableInput::new(
+        ProgressInput::new_from_str(&source, 100, |progress| {
+            read = progress.bytes_read;
+            if progress.bytes_read >= 1000 {
+                token.cancel();
+            }
+        }),
+        token.clone(),
+    );
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.cancellation(token.clone());
+    let result = loader.load_from_parser(&mut Parser::new(input));
+    assert!(matches!(result, Err(LoadError::Cancelled)));
+    assert!(read < 1100);
+}
+
+#[test]
+fn test_cancel_between_documents() {
+    // Stopping at the end of a document does not yield a parse error.
+    let token = CancellationToken::new();
+    let mut chars = 0;
+    let input = CancellableInput::new(
+        "a\n---\nb\n".chars().inspect(|_| {
+            chars += 1;
+            if chars == 2 {
+                token.cancel();
+            }
+        }),
+        token.clone(),
+    );
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.cancellation(token.clone());
+    let result = loader.load_from_parser(&mut Parser::new(input));
+    assert!(matches!(result, Err(LoadError::Cancelled)));
+}
+
+#[test]
+fn test_preprocess_text() {
+    let source = "a:\t1 \t\n\tb: 'x\ty'  \n  \n";
+    let expanded = PreprocessedInput::new(source.chars())
+        .expand_tabs(4)
+        .collect::<String>();
+    assert_eq!(expanded, "a:  1   \n    b: 'x   y'  \n  \n");
+
+    let stripped = PreprocessedInput::new(source.chars())
+        .strip_trailing_whitespace(true)
+        .collect::<String>();
+    assert_eq!(stripped, "a:\t1\n\tb: 'x\ty'\n\n");
+
+    assert_eq!(
+        PreprocessedInput::new(source.chars()).collect::<String>(),
+        source
+    );
+}
+
+#[test]
+fn test_preprocess_original_marker() {
+    let source = "a: 1  \t\nb:\t[x,\t\ty]\n";
+    let mut input = PreprocessedInput::new(source.chars())
+        .expand_tabs(8)
+        .strip_trailing_whitespace(true);
+    let output = (&mut input).collect::<String>();
+    assert_eq!(output, "a: 1\nb:      [x,             y]\n");
+
+    // `y` is at index 29, line 2, column 24 in the output.
+    let original = input.original_marker(&Marker::new(29, 2, 24));
+    assert_eq!(original, Marker::new(16, 2, 8));
+    // Any position within the expansion of a tab maps to the tab.
+    let original = input.original_marker(&Marker::new(10, 2, 5));
+    assert_eq!(original, Marker::new(10, 2, 2));
+}
+
+#[test]
+fn test_tab_indentation() {
+    let source = "a:\n\tb:\n\t\t- c\td\n\te: f\ng: |\n  \tliteral\n";
+    assert!(Yaml::load_from_str(source).is_err());
+
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.tab_indentation(2);
+    let docs = loader.load_from_str(source).unwrap();
+    // Tabs after the indentation are kept.
+    assert_eq!(docs[0]["a"]["b"][0].as_str(), Some("c\td"));
+    assert_eq!(docs[0]["a"]["e"].as_str(), Some("f"));
+    // Including within block scalars, past the indentation of their contents.
+    assert_eq!(docs[0]["g"].as_str(), Some("\tliteral\n"));
+
+    let lines = loader
+        .warnings()
+        .iter()
+        .filter_map(|warning| match warning {
+            LoadWarning::TabIndentation(marker) => Some((marker.line(), marker.col())),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(lines, [(2, 0), (3, 0), (4, 0)]);
+
+    // Tabs indenting the contents of block scalars are expanded, up to their indentation.
+    let docs = loader
+        .load_from_str("a:\n\tb: |\n\t\tfoo\n\t\t\tbar\n")
+        .unwrap();
+    assert_eq!(docs[0]["a"]["b"].as_str(), Some("foo\n\tbar\n"));
+
+    // Warnings are cleared on each load.
+    loader.load_from_str("a: b").unwrap();
+    assert!(loader.warnings().is_empty());
+}
+
+#[test]
+fn test_absurdly_long_lines() {
+    let long = "a".repeat(1_000_000);
+    let source = format!("key: [{long}, {long}]\n");
+    let docs = Yaml::load_from_str(&source).unwrap();
+    assert_eq!(docs[0]["key"][1].as_str(), Some(long.as_str()));
+
+    let mut loader = YamlLoader::<Yaml>::default();
+    loader.max_line_length(1000);
+    let error = loader.load_from_str(&source).unwrap_err();
+    assert!(
+        matches!(&error, LoadError::LineTooLong(marker) if marker.index() == 1000 && marker.col() == 1000)
+    );
+    assert_eq!(
+        error.to_string(),
+        "LOAD005: line 1 is longer than 1000 characters"
+    );
+}
+
+#[test]
+fn test_line_limit_counts_each_line() {
+    // Line breaks reset the count, and `\r\n` counts as a single line break.
+    let mut input = LineLimitedInput::new("1234\r\n1234\n12345".chars(), 4);
+    assert_eq!(input.by_ref().count(), 15);
+    assert_eq!(input.exceeded(), Some(Marker::new(15, 3, 4)));
+
+    let mut input = LineLimitedInput::new("1234\n1234\n".chars(), 4);
+    assert_eq!(input.by_ref().count(), 10);
+    assert_eq!(input.exceeded(), None);
+}
@@ -0,0 +1,89 @@
+use saphyr::{apply_patch, compute_patch, Yaml};
+
+fn patched(original: &str, edit: impl FnOnce(&mut Vec<Yaml>)) -> String {
+    let mut docs = Yaml::load_from_str(original).unwrap();
+    edit(&mut docs);
+    let edits = compute_patch(original, &docs).unwrap();
+    let output = apply_patch(original, &edits);
+    assert_eq!(Yaml::load_from_str(&output).unwrap(), docs);
+    output
+}
+
+#[test]
+fn test_patch_no_change() {
+    let original = "a: 1 # comment\nb: [x, y]\n";
+    let docs = Yaml::load_from_str(original).unwrap();
+    assert!(compute_patch(original, &docs).unwrap().is_empty());
+}
+
+#[test]
+fn test_patch_keeps_comments_and_quotes() {
+    let original = "\
+# Deployment
+name: 'web' # the service name
+replicas: 2
+labels: [a, \"b\", c]
+---
+other: document
+";
+    let output = patched(original, |docs| {
+        docs[0]["name"] = Yaml::String("it's api".into());
+        docs[0]["labels"][1] = Yaml::String("b\tc".into());
+        docs[1]["other"] = Yaml::Boolean(true);
+    });
+    assert_eq!(
+        output,
+        "\
+# Deployment
+name: 'it''s api' # the service name
+replicas: 2
+labels: [a, \"b\\tc\", c]
+---
+other: true
+"
+    );
+}
+
+#[test]
+fn test_patch_plain_to_string_needing_quotes() {
+    let output = patched("key: value # trailing\n", |docs| {
+        docs[0]["key"] = Yaml::String("true".into());
+    });
+    assert_eq!(output, "key: \"true\" # trailing\n");
+}
+
+#[test]
+fn test_patch_structural_change_reemits() {
+    let original = "a: 1 # comment\n";
+    let output = patched(original, |docs| {
+        docs[0]
+            .as_mut_hash()
+            .unwrap()
+            .insert(Yaml::String("b".into()), Yaml::Integer(2));
+    });
+    assert_eq!(output, "---\na: 1\nb: 2\n");
+}
+
+#[test]
+fn test_patch_aliased_scalar_reemits() {
+    let original = "a: &x foo # comment\nb: *x\n";
+    let output = patched(original, |docs| {
+        docs[0]["a"] = Yaml::String("bar".into());
+    });
+    assert!(!output.contains("comment"));
+
+    // Anchors no alias refers to do not prevent replacing the scalar.
+    let output = patched("a: &x foo # comment\nb: x\n", |docs| {
+        docs[0]["a"] = Yaml::String("bar".into());
+    });
+    assert_eq!(output, "a: &x bar # comment\nb: x\n");
+}
+
+#[test]
+fn test_patch_block_scalar_reemits() {
+    let original = "a: |\n  text\nb: 1\n";
+    let output = patched(original, |docs| {
+        docs[0]["a"] = Yaml::String("other".into());
+    });
+    assert_eq!(output, "---\na: other\nb: 1\n");
+}
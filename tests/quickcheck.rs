@@ -3,9 +3,113 @@ extern crate quickcheck;
 
 use quickcheck::TestResult;
 
-use saphyr::{Yaml, YamlEmitter};
+use saphyr::{
+    compose_str, folding_regions, parse_for_completion, DetailedEvents, EmitProfile, Event,
+    KeyCharset, MarkedEventReceiver, MarkedYaml, Marker, TScalarStyle, Yaml, YamlEmitter,
+    YamlLoader,
+};
+
+/// Characters significant to YAML, from which inputs are built so that they get past the first
+/// few characters more often than random strings do.
+const YAML_CHARS: &[char] = &[
+    'a',
+    'b',
+    '0',
+    ':',
+    '-',
+    ' ',
+    '\n',
+    '\t',
+    '[',
+    ']',
+    '{',
+    '}',
+    ',',
+    '?',
+    '!',
+    '&',
+    '*',
+    '#',
+    '|',
+    '>',
+    '\'',
+    '"',
+    '%',
+    '@',
+    '\\',
+    '.',
+    '~',
+    'é',
+    '\u{E000}',
+    '\u{F0001}',
+];
+
+/// Fragments from which strings of emitted trees are built, including the indicators, special
+/// values and line breaks the emitter must quote.
+const STRING_FRAGMENTS: &[&str] = &[
+    "a", "b", "0", "1", "e", "_", "+", "-", ".", "0o", "0x", "inf", "nan", "true", "null", "~",
+    "=", "<<", ": ", " #", "- ", "? ", ":", "#", "-", "?", "[", "]", "{", "}", ",", "!", "&", "*",
+    "|", ">", "'", "\"", "%", "@", "`", "\\", " ", "\n", "\r", "\t", "é", "\u{7f}", "\u{85}",
+    "\u{a0}", "\u{2028}", "\u{feff}",
+];
+
+/// Build a node of at most `depth` nested collections from `bytes`.
+fn arbitrary_node(bytes: &mut impl Iterator<Item = u8>, depth: usize) -> Yaml {
+    let Some(code) = bytes.next() else {
+        return Yaml::Null;
+    };
+    let len = usize::from(bytes.next().unwrap_or_default() % 4);
+    match code % 8 {
+        0 if depth > 0 => Yaml::Array((0..len).map(|_| arbitrary_node(bytes, depth - 1)).collect()),
+        1 if depth > 0 => Yaml::Hash(
+            (0..len)
+                .map(|_| {
+                    (
+                        arbitrary_node(bytes, depth - 1),
+                        arbitrary_node(bytes, depth - 1),
+                    )
+                })
+                .collect(),
+        ),
+        2 => Yaml::Integer(i64::from(bytes.next().unwrap_or_default() as i8) * (1 << 40)),
+        3 => Yaml::Real(["1.5", "-0.25", "1e10", ".inf", "-.inf", ".nan"][len].into()),
+        4 => Yaml::Boolean(len % 2 == 0),
+        5 => Yaml::Null,
+        _ => Yaml::String(
+            bytes
+                .take(len)
+                .map(|byte| STRING_FRAGMENTS[usize::from(byte) % STRING_FRAGMENTS.len()])
+                .collect(),
+        ),
+    }
+}
 
 quickcheck! {
+    fn test_emit_load_identity(bytes: Vec<u8>, options: u8) -> TestResult {
+        let input = arbitrary_node(&mut bytes.into_iter(), 4);
+        let mut out_str = String::new();
+        let mut emitter = YamlEmitter::new(&mut out_str);
+        match options % 5 {
+            0 => {}
+            1 => emitter.multiline_strings(true),
+            2 => emitter.profile(EmitProfile::GitHubActions),
+            3 => {
+                emitter.profile(EmitProfile::Kubernetes);
+                // Quoted keys would load as strings.
+                emitter.quote_non_string_keys(false);
+            }
+            _ => emitter.profile(EmitProfile::Ansible),
+        }
+        if let Err(error) = emitter.dump(&input) {
+            return TestResult::error(error.to_string());
+        }
+        match Yaml::load_from_str(&out_str) {
+            Ok(output) if output.len() == 1 && input == output[0] => TestResult::passed(),
+            Ok(output) => TestResult::error(format!("{input:?} emitted as {out_str:?} loaded as {output:?}")),
+            Err(err) => TestResult::error(format!("{input:?} emitted as {out_str:?}: {err}")),
+        }
+    }
+
     fn test_check_weird_keys(xs: Vec<String>) -> TestResult {
         let mut out_str = String::new();
         let input = Yaml::Array(xs.into_iter().map(Yaml::String).collect());
@@ -18,4 +122,96 @@ quickcheck! {
             Err(err) => TestResult::error(err.to_string()),
         }
     }
+
+    fn test_load_never_panics(bytes: Vec<u8>) -> bool {
+        let source: String = bytes
+            .iter()
+            .map(|&byte| YAML_CHARS[usize::from(byte) % YAML_CHARS.len()])
+            .collect();
+        if let Ok(docs) = Yaml::load_from_str(&source) {
+            for doc in &docs {
+                YamlEmitter::new(&mut String::new()).dump(doc).unwrap();
+            }
+        }
+        let _ = MarkedYaml::load_from_str(&source);
+        let _ = compose_str(&source);
+        DetailedEvents::new(&source).count();
+        true
+    }
+
+    fn test_load_with_options_never_panics(bytes: Vec<u8>, options: u8) -> bool {
+        let source: String = bytes
+            .iter()
+            .map(|&byte| YAML_CHARS[usize::from(byte) % YAML_CHARS.len()])
+            .collect();
+        let mut loader = YamlLoader::<Yaml>::default();
+        if options & 1 != 0 {
+            loader.opaque_placeholders("{", "}");
+        }
+        if options & 2 != 0 {
+            loader.tab_indentation(usize::from(options >> 6) + 1);
+        }
+        if options & 4 != 0 {
+            loader.max_line_length(usize::from(options >> 4));
+        }
+        if options & 8 != 0 {
+            loader.opaque_block_scalar(&["a"]).opaque_block_scalar(&["0", "b"]);
+        }
+        if options & 16 != 0 {
+            loader.key_charset(KeyCharset::new("ab0-_."));
+        }
+        if let Ok(docs) = loader.load_from_str(&source) {
+            for doc in &docs {
+                YamlEmitter::new(&mut String::new()).dump(doc).unwrap();
+            }
+        }
+        true
+    }
+
+    fn test_parse_for_completion_never_panics(bytes: Vec<u8>) -> bool {
+        let source: String = bytes
+            .iter()
+            .map(|&byte| YAML_CHARS[usize::from(byte) % YAML_CHARS.len()])
+            .collect();
+        for cursor in 0..=source.len() {
+            let _ = parse_for_completion(&source, cursor);
+        }
+        true
+    }
+
+    fn test_folding_regions_within_source(bytes: Vec<u8>) -> bool {
+        let source: String = bytes
+            .iter()
+            .map(|&byte| YAML_CHARS[usize::from(byte) % YAML_CHARS.len()])
+            .collect();
+        let len = source.chars().count();
+        folding_regions(&source).all(|region| {
+            region.start.index() < region.end.index()
+                && region.start.line() < region.end.line()
+                && region.end.index() <= len
+        })
+    }
+
+    fn test_load_from_events_never_panics(codes: Vec<u8>) -> bool {
+        let mut loader = YamlLoader::<Yaml>::default();
+        let _ = loader.load_from_events(|loader| {
+            for code in codes {
+                // Anchor IDs 1 and 2 make aliases resolve every now and then.
+                let anchor_id = usize::from(code / 16 % 3);
+                let event = match code % 9 {
+                    0 => Event::DocumentStart,
+                    1 => Event::DocumentEnd,
+                    2 => Event::SequenceStart(anchor_id, None),
+                    3 => Event::SequenceEnd,
+                    4 => Event::MappingStart(anchor_id, None),
+                    5 => Event::MappingEnd,
+                    6 => Event::Scalar("a".into(), TScalarStyle::Plain, anchor_id, None),
+                    7 => Event::Alias(anchor_id),
+                    _ => Event::StreamEnd,
+                };
+                loader.on_event(event, Marker::default());
+            }
+        });
+        true
+    }
 }
@@ -0,0 +1,58 @@
+use saphyr::{ErrorSnippet, Marker, Yaml};
+
+#[test]
+fn test_snippet_short_line() {
+    let source = "a: 1\nb: c: d\ne: f";
+    let error = Yaml::load_from_str(source).unwrap_err();
+    let snippet = ErrorSnippet::new(source, error.marker());
+    assert!(!snippet.is_truncated());
+    assert_eq!(snippet.line(), "b: c: d");
+    assert_eq!(snippet.to_string(), "b: c: d\n    ^");
+}
+
+#[test]
+fn test_snippet_truncated() {
+    let source = format!("key: {}: {}", "a".repeat(500), "b".repeat(500));
+    let error = Yaml::load_from_str(&source).unwrap_err();
+    let snippet = ErrorSnippet::new(&source, error.marker()).max_width(20);
+
+    assert!(snippet.is_truncated());
+    assert_eq!(snippet.line(), source);
+    let rendered = snippet.to_string();
+    let (excerpt, caret) = rendered.split_once('\n').unwrap();
+    assert_eq!(excerpt.chars().count(), 26);
+    assert!(excerpt.starts_with("...") && excerpt.ends_with("..."));
+    let caret_col = caret.len() - 1;
+    assert_eq!(&excerpt[caret_col..=caret_col], ":");
+}
+
+#[test]
+fn test_snippet_multibyte() {
+    let source = "é: ü\nfoo";
+    let snippet = ErrorSnippet::new(source, &Marker::new(3, 1, 3));
+    assert_eq!(snippet.line(), "é: ü");
+    assert_eq!(snippet.col(), 3);
+    assert_eq!(snippet.to_string(), "é: ü\n   ^");
+}
+
+#[test]
+fn test_snippet_indentation_help() {
+    let source = "a:\n  b:\n    c: 1\n   d: 2\n";
+    let error = Yaml::load_from_str(source).unwrap_err();
+    let snippet = ErrorSnippet::new(source, error.marker());
+    assert_eq!(
+        snippet.to_string(),
+        "   d: 2\n    ^\nhelp: did you mean to indent line 4 by 4 spaces instead of 3?"
+    );
+
+    // Block scalars and items of sequences may be indented further.
+    let source = "s: |\n  x\n   y\nl:\n- a: 1\n  b: 2\n z: 1\n";
+    let error = Yaml::load_from_str(source).unwrap_err();
+    let help = *ErrorSnippet::new(source, error.marker()).help().unwrap();
+    assert_eq!((help.line, help.found, help.suggested), (7, 1, 2));
+
+    // Errors unrelated to indentation get no help.
+    let source = "a:\n  b: [1\nc: 2\n";
+    let error = Yaml::load_from_str(source).unwrap_err();
+    assert!(ErrorSnippet::new(source, error.marker()).help().is_none());
+}
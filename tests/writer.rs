@@ -0,0 +1,246 @@
+use std::io;
+
+use saphyr::{EmitError, SplitWriter, StreamWriter, TeeWriter, Yaml, YamlEmitter, YamlLoader};
+
+/// A sink recording how many times it was flushed.
+#[derive(Default)]
+struct FlushCounter {
+    data: Vec<u8>,
+    flushes: usize,
+}
+
+impl io::Write for FlushCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.data.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_stream_writer_appends_documents() {
+    let mut writer = StreamWriter::new(FlushCounter::default());
+    writer.flush_each_document(true);
+    let docs = Yaml::load_from_str("a: [1, 2]\n---\n- b\n---\nc").unwrap();
+    for doc in &docs {
+        writer.write_document(doc).unwrap();
+    }
+    assert_eq!(writer.documents_written(), 3);
+    assert_eq!(writer.get_ref().flushes, 3);
+
+    let output = String::from_utf8(writer.into_inner().data).unwrap();
+    assert_eq!(output, "---\na:\n  - 1\n  - 2\n---\n- b\n---\nc\n");
+    assert_eq!(Yaml::load_from_str(&output).unwrap(), docs);
+}
+
+#[test]
+fn test_write_empty_document() {
+    let mut writer = StreamWriter::new(Vec::new());
+    writer.write_empty_document().unwrap();
+    writer.write_document(&Yaml::Null).unwrap();
+    writer.write_empty_document().unwrap();
+    let output = String::from_utf8(writer.into_inner()).unwrap();
+    assert_eq!(output, "---\n---\n~\n---\n");
+
+    let mut loader = YamlLoader::<Yaml>::default();
+    assert_eq!(loader.load_from_str(&output).unwrap().len(), 3);
+    let empty = loader
+        .documents()
+        .iter()
+        .map(|doc| doc.empty)
+        .collect::<Vec<_>>();
+    assert_eq!(empty, [true, false, true]);
+}
+
+#[test]
+fn test_stream_writer_lazy_mapping() {
+    let doc = Yaml::load_from_str(
+        "
+name: report
+rows:
+  - {id: 1, tags: [a, b]}
+  - {id: 2, tags: []}
+? [complex, key]
+: |
+  multi
+  line
+empty: {}
+",
+    )
+    .unwrap()
+    .remove(0);
+
+    let mut expected = StreamWriter::new(Vec::new());
+    expected.explicit_end(true).multiline_strings(true);
+    expected.write_document(&doc).unwrap();
+
+    let mut writer = StreamWriter::new(Vec::new());
+    writer.explicit_end(true).multiline_strings(true);
+    writer
+        .write_mapping(|sink| {
+            for (key, value) in doc.as_hash().unwrap() {
+                sink.entry(key.clone(), value.clone())?;
+            }
+            assert_eq!(sink.entries_written(), 4);
+            Ok::<_, io::Error>(())
+        })
+        .unwrap();
+    writer.write_mapping(|_| Ok::<_, io::Error>(())).unwrap();
+    assert_eq!(writer.documents_written(), 2);
+
+    let output = String::from_utf8(writer.into_inner()).unwrap();
+    let expected = String::from_utf8(expected.into_inner()).unwrap();
+    assert_eq!(output, format!("{expected}---\n{{}}\n...\n"));
+    let docs = YamlLoader::<Yaml>::default()
+        .load_from_str(&output)
+        .unwrap();
+    assert_eq!(docs[0], doc);
+    assert!(docs[1].as_hash().unwrap().is_empty());
+
+    // Errors of the closure are returned as they are.
+    let mut writer = StreamWriter::new(Vec::new());
+    let result = writer.write_mapping(|sink| {
+        sink.entry(Yaml::Integer(1), Yaml::Null)?;
+        Err(io::Error::new(io::ErrorKind::Other, "query failed"))
+    });
+    assert_eq!(result.unwrap_err().to_string(), "query failed");
+    assert_eq!(writer.documents_written(), 0);
+}
+
+#[test]
+fn test_emitter_pool_reuses_buffers() {
+    use saphyr::EmitterPool;
+
+    let pool = EmitterPool::new();
+    let big = Yaml::Array((0..1000).map(Yaml::Integer).collect());
+    let emitted = pool.dump(&big).unwrap();
+    let len = emitted.len();
+    assert_eq!(Yaml::load_from_str(&emitted).unwrap()[0], big);
+    assert_eq!(pool.pooled(), 0);
+    drop(emitted);
+    assert_eq!(pool.pooled(), 1);
+
+    // The buffer is cleared, but keeps its capacity.
+    let small = pool.dump(&Yaml::Boolean(true)).unwrap();
+    assert_eq!(small.as_str(), "---\ntrue");
+    assert_eq!(pool.pooled(), 0);
+    assert!(small.into_string().capacity() >= len);
+    assert_eq!(pool.pooled(), 0);
+
+    // Buffers are returned even if emitting fails.
+    assert!(pool.dump(&Yaml::Alias(1)).is_err());
+    assert_eq!(pool.pooled(), 1);
+
+    let mut pool = EmitterPool::new();
+    pool.max_pooled(1).multiline_strings(true);
+    let a = pool.dump(&Yaml::String("a\nb".into())).unwrap();
+    let b = pool.dump(&Yaml::Integer(1)).unwrap();
+    assert!(a.to_string().starts_with("---\n|"));
+    drop(a);
+    drop(b);
+    assert_eq!(pool.pooled(), 1);
+
+    // The pool can be shared between threads.
+    fn assert_sync<T: Send + Sync>() {}
+    assert_sync::<EmitterPool>();
+}
+
+/// A sink failing on any write.
+struct FailingSink;
+
+impl io::Write for FailingSink {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_tee_writer() {
+    let doc = Yaml::load_from_str("a: [1, 2]\nb: c").unwrap().remove(0);
+
+    let mut text = String::new();
+    let mut flushes = FlushCounter::default();
+    let mut tee = TeeWriter::new();
+    tee.fmt_sink(&mut text).io_sink(&mut flushes);
+    let mut writer = StreamWriter::new(tee);
+    writer.flush_each_document(true);
+    writer.write_document(&doc).unwrap();
+    assert_eq!(writer.into_inner().sinks(), 2);
+    assert_eq!(text, "---\na:\n  - 1\n  - 2\nb: c\n");
+    assert_eq!(flushes.data, text.as_bytes());
+    assert_eq!(flushes.flushes, 1);
+
+    // The error of the failing sink is kept, and later sinks are not written to.
+    let mut text = String::new();
+    let mut failing = FailingSink;
+    let mut tee = TeeWriter::new();
+    tee.io_sink(&mut failing).fmt_sink(&mut text);
+    assert!(YamlEmitter::new(&mut tee).dump(&doc).is_err());
+    assert_eq!(tee.take_error().unwrap().kind(), io::ErrorKind::BrokenPipe);
+    assert!(tee.take_error().is_none());
+    drop(tee);
+    assert!(text.is_empty());
+}
+
+#[test]
+fn test_tee_writer_split_characters() {
+    use std::io::Write;
+
+    let mut text = String::new();
+    let mut bytes = Vec::new();
+    let mut tee = TeeWriter::new();
+    tee.fmt_sink(&mut text).io_sink(&mut bytes);
+    let source = "a: é€\n".as_bytes();
+    // Write one byte at a time, splitting each multi-byte character.
+    for byte in source {
+        assert_eq!(tee.write(std::slice::from_ref(byte)).unwrap(), 1);
+    }
+    // Invalid UTF-8 is rejected before any sink is written to.
+    let error = tee.write(b"\xff").unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    drop(tee);
+    assert_eq!(text, "a: é€\n");
+    assert_eq!(bytes, source);
+}
+
+#[test]
+fn test_split_writer() {
+    let docs = Yaml::load_from_str("[a, b]\n---\nc: d\n---\n~\n---\nlonger document").unwrap();
+    let mut writer = SplitWriter::new(24);
+    writer.compact(false);
+    let mut chunks = Vec::new();
+    for doc in &docs {
+        chunks.extend(writer.write_document(doc).unwrap());
+    }
+    assert_eq!(writer.chunk_len(), "---\nlonger document\n".len());
+
+    let too_large = Yaml::String("x".repeat(24));
+    assert_eq!(
+        writer.write_document(&too_large),
+        Err(EmitError::TooLarge { size: 29, max: 24 })
+    );
+    chunks.extend(writer.finish());
+    assert_eq!(
+        chunks,
+        [
+            "---\n- a\n- b\n---\nc: d\n",
+            "---\n~\n",
+            "---\nlonger document\n"
+        ]
+    );
+
+    // Each chunk is a stream of the documents it holds.
+    let reloaded: Vec<_> = chunks
+        .iter()
+        .flat_map(|chunk| Yaml::load_from_str(chunk).unwrap())
+        .collect();
+    assert_eq!(reloaded, docs);
+    assert!(SplitWriter::new(10).finish().is_none());
+}